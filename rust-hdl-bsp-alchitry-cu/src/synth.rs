@@ -56,13 +56,15 @@ fn save_stdout(output: Output, dir: &PathBuf, basename: &str) -> Result<(), std:
 pub fn generate_bitstream<U: Block>(mut uut: U, prefix: &str) {
     uut.connect_all();
     check_all(&uut).unwrap(); // TODO - Change from panic to return an error
-    let verilog_text = generate_verilog(&uut);
+    let (verilog_text, memory_files) =
+        generate_verilog_with_memory_files(&uut, VerilogTarget::ICE40);
     let pcf_text = generate_pcf(&uut);
     let dir = PathBuf::from_str(prefix).unwrap();
     let _ = remove_dir_all(&dir);
     let _ = create_dir_all(&dir);
     let mut v_file = File::create(dir.join("top.v")).unwrap();
     write!(v_file, "{}", verilog_text).unwrap();
+    write_memory_files(&dir, &memory_files).unwrap();
     let pcf_filename = "top.pcf".to_string();
     let mut pcf_file = File::create(dir.join(pcf_filename)).unwrap();
     write!(pcf_file, "{}", pcf_text).unwrap();
@@ -2,6 +2,7 @@ use super::mcb_if::MCBInterface1GDDR2;
 use super::mig::MemoryInterfaceGenerator;
 use crate::xem6010::mig::MIGInstruction;
 use rust_hdl::prelude::*;
+use rust_hdl_ok_core::core::mig_sim::MIGSimConfig;
 
 #[derive(LogicState, Debug, Copy, Clone, PartialEq)]
 pub enum DDRFIFOState {
@@ -61,6 +62,16 @@ pub struct DDRFIFO {
     mig_clock: Signal<Local, Clock>,
 }
 
+impl DDRFIFO {
+    // Set up the behavioral model of the MIG used when simulating the FIFO
+    pub fn with_mig_sim_config(config: MIGSimConfig) -> Self {
+        Self {
+            mig: MemoryInterfaceGenerator::with_sim_config(config),
+            ..Default::default()
+        }
+    }
+}
+
 impl Logic for DDRFIFO {
     #[hdl_gen]
     fn update(&mut self) {
@@ -200,3 +211,60 @@ fn test_ddr_fifo_gen() {
     let ddr = DDRFIFO::default();
     let _vlog = generate_verilog_unchecked(&ddr);
 }
+
+#[test]
+fn test_ddr_fifo_sim() {
+    let mut uut = DDRFIFO::with_mig_sim_config(MIGSimConfig {
+        calibration_cycles: 50,
+        read_latency: 10,
+        stall_probability: 0.2,
+        seed: 1,
+    });
+    uut.raw_sys_clock.connect();
+    uut.mcb.link_connect_dest();
+    uut.data_in.connect();
+    uut.write.connect();
+    uut.read.connect();
+    uut.write_clock.connect();
+    uut.read_clock.connect();
+    uut.reset.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<DDRFIFO>| {
+        x.raw_sys_clock.next = !x.raw_sys_clock.val()
+    });
+    sim.add_clock(4, |x: &mut Box<DDRFIFO>| {
+        x.write_clock.next = !x.write_clock.val()
+    });
+    sim.add_clock(6, |x: &mut Box<DDRFIFO>| {
+        x.read_clock.next = !x.read_clock.val()
+    });
+    let data = (0..1024).map(|_| rand::random::<u32>()).collect::<Vec<_>>();
+    let data_in = data.clone();
+    sim.add_testbench(move |mut sim: Sim<DDRFIFO>| {
+        let mut x = sim.init()?;
+        x.reset.next = true;
+        wait_clock_cycles!(sim, write_clock, x, 4);
+        x.reset.next = false;
+        for val in &data_in {
+            x = sim.watch(|x| !x.full.val(), x)?;
+            x.data_in.next = (*val as u64).into();
+            x.write.next = true;
+            wait_clock_cycle!(sim, write_clock, x);
+            x.write.next = false;
+        }
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<DDRFIFO>| {
+        let mut x = sim.init()?;
+        for val in &data {
+            x = sim.watch(|x| !x.empty.val(), x)?;
+            sim_assert_eq!(sim, x.data_out.val(), *val as u64, x);
+            x.read.next = true;
+            wait_clock_cycle!(sim, read_clock, x);
+            x.read.next = false;
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 200_000).unwrap();
+}
@@ -1,5 +1,6 @@
 use super::mcb_if::MCBInterface1GDDR2;
 use rust_hdl::prelude::*;
+use rust_hdl_ok_core::core::mig_sim::{mig_masked_write, MIGSimConfig, MIGStalls};
 use std::collections::BTreeMap;
 
 #[derive(LogicState, Copy, Clone, Debug, PartialEq)]
@@ -62,12 +63,16 @@ enum State {
     Init,
     Calibrating,
     Idle,
+    ReadLatency,
     Reading,
     Writing,
     Error,
     Refresh,
 }
 
+// In simulation, the MIG is replaced by a behavioral model of the user ports,
+// backed by a sparse memory.  The calibration time, read latency and random
+// stalls of the model are set with a [MIGSimConfig].
 #[derive(LogicBlock)]
 pub struct MemoryInterfaceGenerator {
     // Raw clock from the system - cannot be intercepted
@@ -95,6 +100,9 @@ pub struct MemoryInterfaceGenerator {
     state: DFF<State>,
     calib: DFF<bool>,
     _dram: BTreeMap<Bits<32>, Bits<32>>,
+    _config: MIGSimConfig,
+    _stalls: MIGStalls,
+    _stall: bool,
     cmd: Signal<Local, MIGCommand>,
     wr_reset: AutoReset,
     rd_reset: AutoReset,
@@ -140,6 +148,9 @@ impl Default for MemoryInterfaceGenerator {
             state: Default::default(),
             calib: Default::default(),
             _dram: Default::default(),
+            _config: Default::default(),
+            _stalls: Default::default(),
+            _stall: false,
             cmd: Default::default(),
             wr_reset: Default::default(),
             rd_reset: Default::default(),
@@ -148,8 +159,34 @@ impl Default for MemoryInterfaceGenerator {
     }
 }
 
+impl MemoryInterfaceGenerator {
+    pub fn with_sim_config(config: MIGSimConfig) -> Self {
+        Self {
+            _stalls: MIGStalls::new(&config),
+            _config: config,
+            ..Default::default()
+        }
+    }
+}
+
 impl Logic for MemoryInterfaceGenerator {
     fn update(&mut self) {
+        if self.raw_sys_clk.pos_edge() {
+            // Store the word written in the cycle that just ended
+            if self.write_fifo.read.val() {
+                let write = self.write_fifo.data_out.val();
+                let old = *self
+                    ._dram
+                    .get(&self.address.q.val())
+                    .unwrap_or(&Default::default());
+                self._dram.insert(
+                    self.address.q.val(),
+                    mig_masked_write(old, write.data, write.mask),
+                );
+            }
+            // Decide once per clock if the model stalls this cycle
+            self._stall = self._stalls.stall();
+        }
         // Connect the hardware side of the fifos to the raw clock
         self.cmd_fifo.read_clock.next = self.raw_sys_clk.val();
         self.write_fifo.read_clock.next = self.raw_sys_clk.val();
@@ -193,10 +230,18 @@ impl Logic for MemoryInterfaceGenerator {
         self.cmd_fifo.read.next = false;
         self.write_fifo.read.next = false;
         self.read_fifo.write.next = false;
+        let stall = self._stall
+            && matches!(
+                self.state.q.val(),
+                State::Idle | State::Reading | State::Writing
+            );
+        if stall {
+            self.timer.d.next = self.timer.q.val();
+        }
         match self.state.q.val() {
             State::Init => {
                 self.state.d.next = State::Calibrating;
-                self.timer.d.next = 100.into();
+                self.timer.d.next = (self._config.calibration_cycles.min(0xFFFF) as u64).into();
             }
             State::Calibrating => {
                 if self.timer.q.val() == 0 {
@@ -204,6 +249,7 @@ impl Logic for MemoryInterfaceGenerator {
                     self.state.d.next = State::Idle;
                 }
             }
+            _ if stall => {}
             State::Idle => {
                 if !self.cmd_fifo.empty.val() {
                     // Byte address lower 2 bits must be zero
@@ -219,11 +265,10 @@ impl Logic for MemoryInterfaceGenerator {
                                 self.cmd_fifo.read.next = true;
                             }
                             MIGInstruction::Read | MIGInstruction::ReadPrecharge => {
-                                self.timer.d.next = bit_cast::<16, 6>(self.cmd.val().burst_len) + 1;
-                                self.address.d.next =
-                                    bit_cast::<32, 30>(self.cmd.val().byte_address) >> 2;
-                                self.state.d.next = State::Reading;
-                                self.cmd_fifo.read.next = true;
+                                // The command stays in the FIFO until the latency has passed
+                                self.timer.d.next =
+                                    (self._config.read_latency.min(0xFFFF) as u64).into();
+                                self.state.d.next = State::ReadLatency;
                             }
                             MIGInstruction::Refresh => {
                                 self.state.d.next = State::Refresh;
@@ -232,6 +277,14 @@ impl Logic for MemoryInterfaceGenerator {
                     }
                 }
             }
+            State::ReadLatency => {
+                if self.timer.q.val() == 0 {
+                    self.timer.d.next = bit_cast::<16, 6>(self.cmd.val().burst_len) + 1;
+                    self.address.d.next = bit_cast::<32, 30>(self.cmd.val().byte_address) >> 2;
+                    self.state.d.next = State::Reading;
+                    self.cmd_fifo.read.next = true;
+                }
+            }
             State::Reading => {
                 if self.timer.q.val().any() {
                     self.read_fifo.data_in.next = *self
@@ -246,8 +299,6 @@ impl Logic for MemoryInterfaceGenerator {
             }
             State::Writing => {
                 if self.timer.q.val().any() {
-                    self._dram
-                        .insert(self.address.q.val(), self.write_fifo.data_out.val().data);
                     self.write_fifo.read.next = true;
                     self.address.d.next = self.address.q.val() + 1;
                 } else {
@@ -260,6 +311,10 @@ impl Logic for MemoryInterfaceGenerator {
             }
             State::Error => {}
         }
+        if self.reset.val() {
+            self.state.d.next = State::Init;
+            self.calib.d.next = false;
+        }
     }
     fn connect(&mut self) {
         self.calib_done.connect();
@@ -308,6 +363,11 @@ impl Logic for MemoryInterfaceGenerator {
         self.rd_reset.clock.connect();
         self.cmd_reset.clock.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_dram", &mut self._dram);
+        probe.visit_state("_stalls", &mut self._stalls);
+        probe.visit_state("_stall", &mut self._stall);
+    }
     fn hdl(&self) -> Verilog {
         Verilog::Wrapper(Wrapper {
             code: r##"
@@ -463,3 +523,99 @@ fn test_mig_gen() {
     let mig = MemoryInterfaceGenerator::default();
     let _vlog = generate_verilog_unchecked(&mig);
 }
+
+#[test]
+fn test_mig_checkpoint_includes_stalls() {
+    let config = MIGSimConfig {
+        stall_probability: 0.3,
+        seed: 3,
+        ..Default::default()
+    };
+    let mut uut = MemoryInterfaceGenerator::with_sim_config(config.clone());
+    for _ in 0..50 {
+        uut._stalls.stall();
+    }
+    let checkpoint = Checkpoint::capture(&mut uut, 0);
+    let expected = (0..50).map(|_| uut._stalls.stall()).collect::<Vec<_>>();
+    let mut copy = MemoryInterfaceGenerator::with_sim_config(config);
+    checkpoint.restore(&mut copy).unwrap();
+    let actual = (0..50).map(|_| copy._stalls.stall()).collect::<Vec<_>>();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_mig_sim_model() {
+    let mut uut = MemoryInterfaceGenerator::with_sim_config(MIGSimConfig {
+        calibration_cycles: 20,
+        read_latency: 6,
+        stall_probability: 0.3,
+        seed: 3,
+    });
+    uut.raw_sys_clk.connect();
+    uut.p0_cmd.cmd.connect();
+    uut.p0_cmd.enable.connect();
+    uut.p0_cmd.clock.connect();
+    uut.p0_wr.clock.connect();
+    uut.p0_wr.enable.connect();
+    uut.p0_wr.data.connect();
+    uut.p0_rd.enable.connect();
+    uut.p0_rd.clock.connect();
+    uut.reset.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(4, |x: &mut Box<MemoryInterfaceGenerator>| {
+        x.raw_sys_clk.next = !x.raw_sys_clk.val()
+    });
+    sim.add_clock(5, |x: &mut Box<MemoryInterfaceGenerator>| {
+        x.p0_cmd.clock.next = !x.p0_cmd.clock.val();
+        x.p0_wr.clock.next = !x.p0_wr.clock.val();
+        x.p0_rd.clock.next = !x.p0_rd.clock.val();
+    });
+    sim.add_testbench(move |mut sim: Sim<MemoryInterfaceGenerator>| {
+        let mut x = sim.init()?;
+        x.reset.next = true;
+        x = sim.wait(20, x)?;
+        x.reset.next = false;
+        x = sim.watch(|x| x.calib_done.val(), x)?;
+        wait_clock_true!(sim, p0_cmd.clock, x);
+        // The third word only writes its upper two bytes
+        let writes = [
+            (0xdead_beef_u32, 0),
+            (0xcafe_babe, 0),
+            (0x1234_5678, 0b0011),
+            (0x9abc_def0, 0),
+        ];
+        for (data, mask) in &writes {
+            x.p0_wr.data.next.data = (*data as u64).into();
+            x.p0_wr.data.next.mask = (*mask as u64).into();
+            x.p0_wr.enable.next = true;
+            wait_clock_cycle!(sim, p0_wr.clock, x);
+        }
+        x.p0_wr.enable.next = false;
+        x.p0_cmd.cmd.next.byte_address = 0x40.into();
+        x.p0_cmd.cmd.next.burst_len = 3.into();
+        x.p0_cmd.cmd.next.instruction = MIGInstruction::Write;
+        x.p0_cmd.enable.next = true;
+        wait_clock_cycle!(sim, p0_cmd.clock, x);
+        x.p0_cmd.enable.next = false;
+        x = sim.watch(|x| x.p0_cmd.empty.val() & x.p0_wr.empty.val(), x)?;
+        wait_clock_cycle!(sim, p0_cmd.clock, x);
+        x.p0_cmd.cmd.next.instruction = MIGInstruction::Read;
+        x.p0_cmd.enable.next = true;
+        wait_clock_cycle!(sim, p0_cmd.clock, x);
+        x.p0_cmd.enable.next = false;
+        for expected in [0xdead_beef_u32, 0xcafe_babe, 0x1234_0000, 0x9abc_def0] {
+            x = sim.watch(|x| !x.p0_rd.empty.val(), x)?;
+            sim_assert_eq!(sim, x.p0_rd.data.val(), expected as u64, x);
+            x.p0_rd.enable.next = true;
+            wait_clock_cycle!(sim, p0_rd.clock, x);
+            x.p0_rd.enable.next = false;
+        }
+        x = sim.wait(100, x)?;
+        sim_assert!(sim, x.p0_rd.empty.val(), x);
+        sim_assert!(sim, !x.p0_rd.error.val(), x);
+        sim_assert!(sim, !x.p0_wr.error.val(), x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000).unwrap();
+}
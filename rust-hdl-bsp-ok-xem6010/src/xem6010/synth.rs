@@ -46,7 +46,9 @@ impl Default for ISEOptions {
 
 pub fn generate_bitstream_xem_6010<U: Block>(mut uut: U, prefix: &str, options: ISEOptions) {
     uut.connect_all();
-    let verilog_text = filter_blackbox_directives(&generate_verilog(&uut));
+    let (verilog_text, memory_files) =
+        generate_verilog_with_memory_files(&uut, VerilogTarget::Spartan6);
+    let verilog_text = filter_blackbox_directives(&verilog_text);
     let ucf_text = rust_hdl::fpga::toolchains::ise::generate_ucf(&uut)
         + ";
 CONFIG VCCAUX = \"3.3\"; // Required for Spartan-6
@@ -71,6 +73,7 @@ CONFIG VCCAUX = \"3.3\"; // Required for Spartan-6
     }
     let mut v_file = File::create(dir.clone().join("top.v")).unwrap();
     write!(v_file, "{}", verilog_text).unwrap();
+    write_memory_files(&dir, &memory_files).unwrap();
     let mut ucf_file = File::create(dir.clone().join("top.ucf")).unwrap();
     write!(ucf_file, "{}", ucf_text).unwrap();
     for asset in &assets {
@@ -1,6 +1,7 @@
 use super::mcb_if::MCBInterface4GDDR3;
 use super::mig7::MemoryInterfaceGenerator7Series;
 use rust_hdl::prelude::*;
+use rust_hdl_ok_core::core::mig_sim::MIGSimConfig;
 
 #[derive(LogicState, Debug, Copy, Clone, PartialEq)]
 pub enum DDR7FIFOState {
@@ -47,6 +48,16 @@ pub struct DDR7FIFO<const N: usize> {
     mig_clock: Signal<Local, Clock>,
 }
 
+impl<const N: usize> DDR7FIFO<N> {
+    // Set up the behavioral model of the MIG used when simulating the FIFO
+    pub fn with_mig_sim_config(config: MIGSimConfig) -> Self {
+        Self {
+            mig: MemoryInterfaceGenerator7Series::with_sim_config(config),
+            ..Default::default()
+        }
+    }
+}
+
 impl<const N: usize> Logic for DDR7FIFO<N> {
     #[hdl_gen]
     fn update(&mut self) {
@@ -155,3 +166,61 @@ fn test_ddr7_fifo_gen() {
     ddr.connect_all();
     yosys_validate("ddr7", &generate_verilog(&ddr)).unwrap();
 }
+
+#[test]
+fn test_ddr7_fifo_sim() {
+    let mut uut = DDR7FIFO::<32>::with_mig_sim_config(MIGSimConfig {
+        calibration_cycles: 50,
+        read_latency: 10,
+        stall_probability: 0.2,
+        seed: 1,
+    });
+    uut.sys_clock_p.connect();
+    uut.sys_clock_n.connect();
+    uut.mcb.link_connect_dest();
+    uut.data_in.connect();
+    uut.write.connect();
+    uut.read.connect();
+    uut.write_clock.connect();
+    uut.read_clock.connect();
+    uut.reset.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<DDR7FIFO<32>>| {
+        x.sys_clock_p.next = !x.sys_clock_p.val()
+    });
+    sim.add_clock(4, |x: &mut Box<DDR7FIFO<32>>| {
+        x.write_clock.next = !x.write_clock.val()
+    });
+    sim.add_clock(6, |x: &mut Box<DDR7FIFO<32>>| {
+        x.read_clock.next = !x.read_clock.val()
+    });
+    let data = (0..1024).map(|_| rand::random::<u32>()).collect::<Vec<_>>();
+    let data_in = data.clone();
+    sim.add_testbench(move |mut sim: Sim<DDR7FIFO<32>>| {
+        let mut x = sim.init()?;
+        x.reset.next = true;
+        wait_clock_cycles!(sim, write_clock, x, 4);
+        x.reset.next = false;
+        for val in &data_in {
+            x = sim.watch(|x| !x.full.val(), x)?;
+            x.data_in.next = (*val as u64).into();
+            x.write.next = true;
+            wait_clock_cycle!(sim, write_clock, x);
+            x.write.next = false;
+        }
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<DDR7FIFO<32>>| {
+        let mut x = sim.init()?;
+        for val in &data {
+            x = sim.watch(|x| !x.empty.val(), x)?;
+            sim_assert_eq!(sim, x.data_out.val(), *val as u64, x);
+            x.read.next = true;
+            wait_clock_cycle!(sim, read_clock, x);
+            x.read.next = false;
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 1_000_000).unwrap();
+}
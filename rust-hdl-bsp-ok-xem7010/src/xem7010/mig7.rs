@@ -1,6 +1,11 @@
 use super::mcb_if::MCBInterface4GDDR3;
 use rust_hdl::prelude::*;
+use rust_hdl_ok_core::core::mig_sim::{mig_masked_write, MIGSimConfig, MIGStalls};
+use std::collections::{BTreeMap, VecDeque};
 
+// In simulation, the MIG is replaced by a behavioral model of the application
+// interface (see `MIG7Model` below).  Use [MemoryInterfaceGenerator7Series::with_sim_config]
+// to set the calibration time, read latency and random stalls of the model.
 #[derive(LogicBlock, Default)]
 pub struct MemoryInterfaceGenerator7Series {
     // Raw clock from the system - differential and raw
@@ -25,10 +30,74 @@ pub struct MemoryInterfaceGenerator7Series {
     pub reset: Signal<In, Bit>,
     pub clock: Signal<Out, Clock>,
     pub reset_out: Signal<Out, Bit>,
+    _sim: MIG7Model,
+}
+
+impl MemoryInterfaceGenerator7Series {
+    pub fn with_sim_config(config: MIGSimConfig) -> Self {
+        Self {
+            _sim: MIG7Model::new(config),
+            ..Default::default()
+        }
+    }
 }
 
 impl Logic for MemoryInterfaceGenerator7Series {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        let sim = &mut self._sim;
+        if self.raw_pos_clock.pos_edge() {
+            if self.enable.val() && self.ready.val() {
+                sim.commands
+                    .push_back((self.command.val(), self.address.val()));
+            }
+            if self.write_enable.val() && self.write_fifo_not_full.val() {
+                sim.write_data
+                    .push_back((self.write_data_in.val(), self.write_data_mask.val()));
+            }
+            sim.cycle += 1;
+            sim.stall = sim.stalls.stall();
+            match sim.state {
+                MIG7SimState::Reset => {
+                    sim.timer = sim.config.calibration_cycles;
+                    sim.state = MIG7SimState::Calibrating;
+                }
+                MIG7SimState::Calibrating => {
+                    if sim.timer == 0 {
+                        sim.state = MIG7SimState::Idle;
+                    } else {
+                        sim.timer -= 1;
+                    }
+                }
+                MIG7SimState::Idle => {
+                    if !sim.stall {
+                        sim.run_command();
+                    }
+                }
+            }
+            sim.read_out = None;
+            if matches!(sim.reads.front(), Some((due, _)) if *due <= sim.cycle) {
+                sim.read_out = sim.reads.pop_front().map(|(_, data)| data);
+            }
+            if self.reset.val() {
+                *sim = MIG7Model {
+                    dram: std::mem::take(&mut sim.dram),
+                    ..MIG7Model::new(sim.config.clone())
+                };
+            }
+        }
+        if self.raw_pos_clock.neg_edge() {
+            sim.publish();
+        }
+        let outputs = &sim.outputs;
+        self.clock.next = self.raw_pos_clock.val();
+        self.reset_out.next = outputs.in_reset;
+        self.calib_done.next = outputs.calibrated;
+        self.ready.next = outputs.ready;
+        self.write_fifo_not_full.next = outputs.write_ready;
+        self.read_data_valid.next = outputs.read_out.is_some();
+        self.read_data_end.next = outputs.read_out.is_some();
+        self.read_data_out.next = outputs.read_out.unwrap_or_default();
+    }
     fn connect(&mut self) {
         self.read_data_out.connect();
         self.read_data_end.connect();
@@ -41,6 +110,10 @@ impl Logic for MemoryInterfaceGenerator7Series {
         self.mcb.link_connect_source();
         self.mcb.link_connect_dest();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+        probe.visit_state("_stalls", &mut self._sim.stalls);
+    }
     fn hdl(&self) -> Verilog {
         Verilog::Blackbox(BlackBox {
             code: r##"
@@ -191,6 +264,118 @@ fn test_mig7_gen() {
     let _vlog = generate_verilog_unchecked(&mig);
 }
 
+#[test]
+fn test_mig7_checkpoint_includes_stalls() {
+    let config = MIGSimConfig {
+        stall_probability: 0.3,
+        seed: 7,
+        ..Default::default()
+    };
+    let mut uut = MemoryInterfaceGenerator7Series::with_sim_config(config.clone());
+    for _ in 0..50 {
+        uut._sim.stalls.stall();
+    }
+    let checkpoint = Checkpoint::capture(&mut uut, 0);
+    let expected = (0..50).map(|_| uut._sim.stalls.stall()).collect::<Vec<_>>();
+    let mut copy = MemoryInterfaceGenerator7Series::with_sim_config(config);
+    checkpoint.restore(&mut copy).unwrap();
+    let actual = (0..50)
+        .map(|_| copy._sim.stalls.stall())
+        .collect::<Vec<_>>();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_mig7_sim_model() {
+    let mut uut = MemoryInterfaceGenerator7Series::with_sim_config(MIGSimConfig {
+        calibration_cycles: 20,
+        read_latency: 5,
+        stall_probability: 0.3,
+        seed: 7,
+    });
+    uut.raw_pos_clock.connect();
+    uut.raw_neg_clock.connect();
+    uut.address.connect();
+    uut.command.connect();
+    uut.enable.connect();
+    uut.write_data_in.connect();
+    uut.write_data_end.connect();
+    uut.write_data_mask.connect();
+    uut.write_enable.connect();
+    uut.reset.connect();
+    uut.mcb.link_connect_dest();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<MemoryInterfaceGenerator7Series>| {
+        x.raw_pos_clock.next = !x.raw_pos_clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<MemoryInterfaceGenerator7Series>| {
+        let mut x = sim.init()?;
+        x.reset.next = true;
+        wait_clock_cycles!(sim, clock, x, 4);
+        x.reset.next = false;
+        x = sim.watch(|x| x.calib_done.val(), x)?;
+        // Drive the inputs from the falling edge, so the handshakes are stable
+        wait_clock_false!(sim, clock, x);
+        let data = (0..8)
+            .map(|_| Bits::<128>::from(rand::random::<u64>()) << 64 | rand::random::<u64>())
+            .collect::<Vec<_>>();
+        // The write data goes in first, and then the commands.  The last word is
+        // written with the lowest 4 bytes masked off.
+        for (ndx, word) in data.iter().enumerate() {
+            x.write_data_in.next = *word;
+            x.write_data_mask.next = if ndx == 7 { 0xF.into() } else { 0.into() };
+            x.write_enable.next = true;
+            x.write_data_end.next = true;
+            loop {
+                let accepted = x.write_fifo_not_full.val();
+                wait_clock_cycle!(sim, clock, x);
+                if accepted {
+                    break;
+                }
+            }
+        }
+        x.write_enable.next = false;
+        x.write_data_end.next = false;
+        for ndx in 0..8_u64 {
+            x.command.next = 0.into();
+            x.address.next = (ndx * 8).into();
+            x.enable.next = true;
+            loop {
+                let accepted = x.ready.val();
+                wait_clock_cycle!(sim, clock, x);
+                if accepted {
+                    break;
+                }
+            }
+        }
+        for (ndx, word) in data.iter().enumerate() {
+            x.command.next = 1.into();
+            x.address.next = (ndx as u64 * 8).into();
+            x.enable.next = true;
+            loop {
+                let accepted = x.ready.val();
+                wait_clock_cycle!(sim, clock, x);
+                if accepted {
+                    break;
+                }
+            }
+            x.enable.next = false;
+            x = sim.watch(|x| x.read_data_valid.val(), x)?;
+            let expected = if ndx == 7 {
+                *word & !Bits::<128>::from(0xFFFF_FFFF)
+            } else {
+                *word
+            };
+            sim_assert_eq!(sim, x.read_data_out.val(), expected, x);
+            sim_assert!(sim, x.read_data_end.val(), x);
+            wait_clock_false!(sim, clock, x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000).unwrap();
+}
+
 #[derive(LogicState, Copy, Clone, Debug, PartialEq)]
 pub enum MIG7SimState {
     Reset,
@@ -198,67 +383,178 @@ pub enum MIG7SimState {
     Idle,
 }
 
-/*
-// Just used for simulation...
-#[derive(LogicBlock, Default)]
-pub struct MIG7Sim {
-    pub raw_pos_clock: Signal<In, Clock>,
-    pub raw_neg_clock: Signal<In, Clock>,
-    pub mcb: MCBInterface4GDDR3,
-    pub address: Signal<In, Bits<29>>,
-    pub command: Signal<In, Bits<3>>,
-    pub enable: Signal<In, Bit>,
-    pub write_data_in: Signal<In, Bits<128>>,
-    pub write_data_end: Signal<In, Bit>,
-    pub write_data_mask: Signal<In, Bits<16>>,
-    pub write_enable: Signal<In, Bit>,
-    pub read_data_out: Signal<Out, Bits<128>>,
-    pub read_data_end: Signal<Out, Bit>,
-    pub read_data_valid: Signal<Out, Bit>,
-    pub ready: Signal<Out, Bit>,
-    pub write_fifo_not_full: Signal<Out, Bit>,
-    pub calib_done: Signal<Out, Bit>,
-    pub reset: Signal<In, Reset>,
-    pub clock: Signal<Out, Clock>,
-    pub reset_out: Signal<Out, Bit>,
-    state: DFF<MIG7SimState>,
-    data_in_register: DFF<Bits<128>>,
-    data_out_register: DFF<Bits<128>>,
-    calib_flag: DFF<Bit>,
-    _sim: BTreeMap<Bits<29>, Bits<128>>,
+// Depth of the command queue and write data FIFO in the model
+const MIG7_SIM_COMMAND_DEPTH: usize = 4;
+const MIG7_SIM_WRITE_DEPTH: usize = 16;
+
+// The outputs of the model, which change on the falling edge of the clock.  Logic
+// clocked from the `clock` output sits a few delta cycles behind the raw clock, so
+// this keeps it from seeing outputs that belong to the next cycle.
+#[derive(Clone, Debug, Default)]
+struct MIG7Outputs {
+    calibrated: bool,
+    in_reset: bool,
+    ready: bool,
+    write_ready: bool,
+    read_out: Option<Bits<128>>,
 }
 
-impl<D: Synth, const N: usize> Logic for MIG7Sim {
-    fn update(&mut self) {
-        self.clock.next = self.raw_pos_clock.val();
-        self.state.clk.next = self.raw_pos_clock.val();
-        self.data_in_register.clk.next = self.raw_pos_clock.val();
-        self.data_out_register.clk.next = self.raw_pos_clock.val();
-        self.data_in_register.d.next = self.data_in_register.q.val();
-        self.data_out_register.d.next = self.data_out_register.q.val();
-        self.calib_flag.clk.next = self.raw_pos_clock.val();
-        self.calib_flag.d.next = self.calib_flag.q.val();
-        self.state.d.next = self.state.q.val();
-        match self.state.q.val() {
-            MIG7SimState::Reset => {
-                self.calib_flag.d.next = false;
-                if !self.reset.val() {
-                    self.state.d.next = MIG7SimState::Calibrating;
-                }
-            }
-            MIG7SimState::Calibrating => {
-                self.calb_flag.d.next = true;
-                self.state.d.next = MIG7SimState::Idle;
-            }
-            MIG7SimState::Idle => {
-            }
+// Behavioral model of the MIG application interface, used in simulation.  Commands
+// are accepted while `ready` is high and write data while `write_fifo_not_full` is
+// high.  Commands run in order, one per clock: a write waits for its data, and read
+// data comes back `read_latency` clocks after the read runs.
+#[derive(Clone, Debug, Default)]
+struct MIG7Model {
+    config: MIGSimConfig,
+    stalls: MIGStalls,
+    state: MIG7SimState,
+    timer: u32,
+    stall: bool,
+    cycle: u64,
+    commands: VecDeque<(Bits<3>, Bits<29>)>,
+    write_data: VecDeque<(Bits<128>, Bits<16>)>,
+    reads: VecDeque<(u64, Bits<128>)>,
+    read_out: Option<Bits<128>>,
+    outputs: MIG7Outputs,
+    dram: BTreeMap<Bits<29>, Bits<128>>,
+}
+
+impl MIG7Model {
+    fn new(config: MIGSimConfig) -> Self {
+        Self {
+            stalls: MIGStalls::new(&config),
+            config,
+            ..Default::default()
         }
-        if self.reset.val() {
-            self.state.d.next = MIG7SimState::Reset;
+    }
+
+    fn publish(&mut self) {
+        let idle = self.state == MIG7SimState::Idle && !self.stall;
+        self.outputs = MIG7Outputs {
+            calibrated: self.state == MIG7SimState::Idle,
+            in_reset: self.state == MIG7SimState::Reset,
+            ready: idle && self.commands.len() < MIG7_SIM_COMMAND_DEPTH,
+            write_ready: idle && self.write_data.len() < MIG7_SIM_WRITE_DEPTH,
+            read_out: self.read_out,
+        };
+    }
+
+    // Run the next command, if it can run.  The address is in units of 16 bit
+    // words, and each command moves 8 of them.
+    fn run_command(&mut self) {
+        let Some(&(command, address)) = self.commands.front() else {
+            return;
+        };
+        let key = address & !Bits::<29>::from(7);
+        if command == 0 {
+            let Some((data, mask)) = self.write_data.pop_front() else {
+                return;
+            };
+            let old = self.dram.get(&key).copied().unwrap_or_default();
+            self.dram.insert(key, mig_masked_write(old, data, mask));
+        } else if command == 1 {
+            let data = self.dram.get(&key).copied().unwrap_or_default();
+            let due = self.cycle + self.config.read_latency as u64;
+            self.reads.push_back((due, data));
         }
-        self.calib_done.next = self.calib_flag.val();
-        self.ready.next = self.state.q.val() == MIG7SimState::Idle;
+        self.commands.pop_front();
     }
 }
 
- */
+// The model is saved as its scalar state, then each queue as a length followed by
+// its entries, then the outputs and the memory contents.  The configuration is kept
+// as constructed, and the stall generator is checkpointed on its own as `_stalls`.
+impl SimState for MIG7Model {
+    fn save(&self) -> String {
+        let mut writer = StateWriter::default();
+        writer
+            .put(self.state)
+            .put_u64(self.timer as u64)
+            .put(self.stall)
+            .put_u64(self.cycle);
+        writer.put_u64(self.commands.len() as u64);
+        for (command, address) in &self.commands {
+            writer.put(*command).put(*address);
+        }
+        writer.put_u64(self.write_data.len() as u64);
+        for (data, mask) in &self.write_data {
+            writer.put(*data).put(*mask);
+        }
+        writer.put_u64(self.reads.len() as u64);
+        for (due, data) in &self.reads {
+            writer.put_u64(*due).put(*data);
+        }
+        put_option(&mut writer, self.read_out);
+        writer
+            .put(self.outputs.calibrated)
+            .put(self.outputs.in_reset)
+            .put(self.outputs.ready)
+            .put(self.outputs.write_ready);
+        put_option(&mut writer, self.outputs.read_out);
+        writer.put_u64(self.dram.len() as u64);
+        for (address, data) in &self.dram {
+            writer.put(*address).put(*data);
+        }
+        writer.finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let mut read = || -> Option<Self> {
+            let state = reader.get()?;
+            let timer = reader.get_u64()?.try_into().ok()?;
+            let stall = reader.get()?;
+            let cycle = reader.get_u64()?;
+            let commands = (0..reader.get_u64()?)
+                .map(|_| Some((reader.get()?, reader.get()?)))
+                .collect::<Option<_>>()?;
+            let write_data = (0..reader.get_u64()?)
+                .map(|_| Some((reader.get()?, reader.get()?)))
+                .collect::<Option<_>>()?;
+            let reads = (0..reader.get_u64()?)
+                .map(|_| Some((reader.get_u64()?, reader.get()?)))
+                .collect::<Option<_>>()?;
+            let read_out = get_option(&mut reader)?;
+            let outputs = MIG7Outputs {
+                calibrated: reader.get()?,
+                in_reset: reader.get()?,
+                ready: reader.get()?,
+                write_ready: reader.get()?,
+                read_out: get_option(&mut reader)?,
+            };
+            let dram = (0..reader.get_u64()?)
+                .map(|_| Some((reader.get()?, reader.get()?)))
+                .collect::<Option<_>>()?;
+            Some(Self {
+                config: self.config.clone(),
+                stalls: self.stalls.clone(),
+                state,
+                timer,
+                stall,
+                cycle,
+                commands,
+                write_data,
+                reads,
+                read_out,
+                outputs,
+                dram,
+            })
+        };
+        match read() {
+            Some(x) if reader.is_done() => {
+                *self = x;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn put_option(writer: &mut StateWriter, x: Option<Bits<128>>) {
+    writer.put(x.is_some()).put(x.unwrap_or_default());
+}
+
+fn get_option(reader: &mut StateReader) -> Option<Option<Bits<128>>> {
+    let valid: bool = reader.get()?;
+    let x = reader.get()?;
+    Some(valid.then_some(x))
+}
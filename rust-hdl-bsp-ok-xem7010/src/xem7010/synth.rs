@@ -183,7 +183,9 @@ generate_target {{instantiation_template}} [get_files mig7.xci]", mig_path=mig_p
 
 pub fn generate_bitstream_xem_7010<U: Block>(mut uut: U, prefix: &str, options: VivadoOptions) {
     uut.connect_all();
-    let verilog_text = filter_blackbox_directives(&generate_verilog(&uut));
+    let (verilog_text, memory_files) =
+        generate_verilog_with_memory_files(&uut, VerilogTarget::Xilinx7);
+    let verilog_text = filter_blackbox_directives(&verilog_text);
     let xdc_text = rust_hdl::fpga::toolchains::vivado::generate_xdc(&uut);
     let dir = PathBuf::from(prefix);
     let out_file = dir.join("top.out");
@@ -197,8 +199,9 @@ pub fn generate_bitstream_xem_7010<U: Block>(mut uut: U, prefix: &str, options:
     }
     let _ = remove_dir_all(&dir);
     let _ = create_dir_all(&dir);
-    let assets: Vec<String> = options.assets.clone();
+    let mut assets: Vec<String> = options.assets.clone();
     std::fs::write(dir.clone().join("top.v"), verilog_text).unwrap();
+    let memory_files = write_memory_files(&dir, &memory_files).unwrap();
     std::fs::write(dir.clone().join("top.xdc"), xdc_text).unwrap();
     for asset in &assets {
         let src = PathBuf::from(asset);
@@ -206,6 +209,8 @@ pub fn generate_bitstream_xem_7010<U: Block>(mut uut: U, prefix: &str, options:
         println!("Copy from {:?} -> {:?}", asset, dest);
         copy(asset, dest).unwrap();
     }
+    // The memory files are already in place, and just need to be added to the project
+    assets.extend(memory_files);
     let mig = if options.add_mig {
         add_mig_core_xem_7010(prefix, options.clone())
     } else {
@@ -685,6 +685,13 @@ impl VerilogLiteral {
             _ => panic!("Loop index is too large!"),
         }
     }
+    // The value as (unprefixed) hex digits, in two's complement form
+    // for a `bits` wide word.  This is the format used by `$readmemh`.
+    pub fn hex_digits(&self, bits: usize) -> String {
+        let modulus = BigInt::from(1) << bits;
+        let val = ((&self.val % &modulus) + &modulus) % &modulus;
+        format!("{:0width$x}", val, width = bits.div_ceil(4))
+    }
 }
 
 impl From<bool> for VerilogLiteral {
@@ -1285,6 +1285,18 @@ op!(bitor, BitOr, |);
 op!(bitand, BitAnd, &);
 op!(bitxor, BitXor, ^);
 
+// The shift amount for a shift by a [Bits].  Amounts too wide for a [LiteralType]
+// shift out all N bits.
+fn shift_amount<const N: usize, const M: usize>(rhs: Bits<M>) -> LiteralType {
+    if M <= LITERAL_BITS {
+        rhs.to_u64()
+    } else if (rhs >> LITERAL_BITS as LiteralType).any() {
+        N as LiteralType
+    } else {
+        bit_cast::<LITERAL_BITS, M>(rhs).to_u64()
+    }
+}
+
 macro_rules! op_shift {
     ($func: ident, $method: ident, $op: tt) => {
         impl<const M: usize, const N: usize> std::ops::$method<Bits<M>> for Bits<N> {
@@ -1292,7 +1304,7 @@ macro_rules! op_shift {
 
             #[inline(always)]
             fn $func(self, rhs: Bits<M>) -> Self::Output {
-                self $op rhs.to_u64()
+                self $op shift_amount::<N, M>(rhs)
             }
         }
 
@@ -1889,6 +1901,46 @@ mod tests {
         assert_eq!(r, 0);
     }
 
+    #[test]
+    fn test_shift_by_wide_bits() {
+        let y: Bits<128> = 0xDEAD_BEEF.into();
+        let z: Bits<128> = 64.into();
+        assert_eq!((y << z) >> z, y);
+        let big: Bits<128> = Bits::<128>::from(1) << 100;
+        assert_eq!(y << big, 0);
+        assert_eq!(y >> big, 0);
+        // Amounts just past the range of a LiteralType are not truncated
+        let wrap: Bits<128> = Bits::<128>::from(1) << 64;
+        assert_eq!(y << wrap, 0);
+        assert_eq!(y >> (wrap + 1), 0);
+        // A narrow value shifted by a wide one
+        let x: Bits<8> = 0x81.into();
+        assert_eq!(x >> Bits::<128>::from(7), 1);
+        assert_eq!(x << Bits::<128>::from(1), 0x02);
+        assert_eq!(x << wrap, 0);
+        assert_eq!(
+            Bits::<32>::from(0x1234) << Bits::<256>::from(16),
+            0x1234_0000
+        );
+    }
+
+    #[test]
+    fn test_shift_by_at_least_the_width() {
+        let x: Bits<8> = 0xFF.into();
+        for amount in [8, 9, 63, 200] {
+            assert_eq!(x << Bits::<8>::from(amount), 0);
+            assert_eq!(x >> Bits::<8>::from(amount), 0);
+        }
+        let y: Bits<128> = Bits::<128>::from(0xDEAD_BEEF) << 96 | 0xCAFE_BABF;
+        for amount in [128, 129, 255] {
+            assert_eq!(y << Bits::<8>::from(amount), 0);
+            assert_eq!(y >> Bits::<8>::from(amount), 0);
+        }
+        assert_eq!(y >> Bits::<16>::from(1000), 0);
+        assert_eq!(y >> Bits::<128>::from(127), 1);
+        assert_eq!(y << Bits::<128>::from(127), Bits::<128>::from(1) << 127);
+    }
+
     #[test]
     fn test_shl() {
         seq!(N in 1..150 {
@@ -1,5 +1,5 @@
 use crate::logic::Logic;
-use crate::probe::Probe;
+use crate::probe::{Probe, ProbeMut};
 
 /// The [Block] trait is required for all circuitry that
 /// can be simulated by RustHDL.  If you want to be able
@@ -16,6 +16,15 @@ pub trait Block: Logic {
     fn has_changed(&self) -> bool;
     /// The visitor pattern - allows a circuit to be probed by a [Probe] struct.
     fn accept(&self, name: &str, probe: &mut dyn Probe);
+    /// The visitor pattern for visitors that change the circuit (like restoring a checkpoint).
+    /// The default only visits the state returned by [Logic::accept_state], so a hand written
+    /// [Block] with signals must override it for its signals to be saved in a
+    /// [Checkpoint](crate::checkpoint::Checkpoint).  `#[derive(LogicBlock)]` does this for you.
+    fn accept_mut(&mut self, name: &str, probe: &mut dyn ProbeMut) {
+        probe.visit_start_scope(name);
+        self.accept_state(probe);
+        probe.visit_end_scope(name);
+    }
 }
 
 impl<B: Block> Block for Vec<B> {
@@ -46,6 +55,13 @@ impl<B: Block> Block for Vec<B> {
             x.1.accept(&name, probe);
         }
     }
+
+    fn accept_mut(&mut self, name: &str, probe: &mut dyn ProbeMut) {
+        for x in self.iter_mut().enumerate() {
+            let name = format!("{}${}", name, x.0);
+            x.1.accept_mut(&name, probe);
+        }
+    }
 }
 
 impl<B: Block, const P: usize> Block for [B; P] {
@@ -76,4 +92,11 @@ impl<B: Block, const P: usize> Block for [B; P] {
             x.1.accept(&name, probe);
         }
     }
+
+    fn accept_mut(&mut self, name: &str, probe: &mut dyn ProbeMut) {
+        for x in self.iter_mut().enumerate() {
+            let name = format!("{}${}", name, x.0);
+            x.1.accept_mut(&name, probe);
+        }
+    }
 }
@@ -0,0 +1,375 @@
+//! Checkpoints of the state of a circuit in the middle of a simulation.
+//!
+//! Many simulations spend most of their time getting the circuit into an interesting
+//! state (waiting for an SDRAM to boot, for example) before the part that is actually
+//! being tested.  A [Checkpoint] records the complete state of a circuit at some time
+//! in a simulation - the values of all of its signals (which includes the contents of
+//! every `DFF`), any state held outside of signals (like the contents of a `RAM`), and
+//! the simulation time.  It can then be restored into a freshly built copy of the same
+//! circuit, so that many tests can start from that point.
+//!
+//! A checkpoint is made with [Simulation::run_to_checkpoint](crate::simulate::Simulation::run_to_checkpoint)
+//! (or [Checkpoint::capture] from within a testbench), and a simulation is started from
+//! one with [Simulation::start_from](crate::simulate::Simulation::start_from).  The state
+//! is found by walking the circuit with a [ProbeMut], and is matched up by the path of
+//! each item in the circuit, so the circuit it is restored into must have the same
+//! structure as the one it was taken from.
+//!
+//! Checkpoints are held in memory, and are cheap to clone, so that the same checkpoint
+//! can be used by many tests.  They can also be written to a file with [Checkpoint::save],
+//! and read back with [Checkpoint::load], so that tests in other processes can start
+//! from the same state.  Each item of state is saved as text (see [SimState]), and the
+//! values of signals are written with [Synth::to_text], so a signal can only be restored
+//! if its type can be read back with [Synth::from_text] (all of the built in types can).
+//!
+//! Only the state the circuit exposes is saved.  Every signal is, but state held outside
+//! of signals is only saved where the circuit visits it in
+//! [Logic::accept_state](crate::logic::Logic::accept_state) - as the RAM widgets, the
+//! MIG models (their `_dram`) and the Opal Kelly host and endpoint models do.  Any other
+//! state (in a hand written `Logic` that does not visit it, or held by a testbench, like
+//! the transfers queued on a simulated FrontPanel handle) is not in the checkpoint, and
+//! starts fresh when the checkpoint is restored.
+use crate::block::Block;
+use crate::probe::ProbeMut;
+use crate::synth::Synth;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Simulation state that can be saved to, and restored from, a [Checkpoint].  The
+/// state is saved as a single line of text (usually built with a [StateWriter]), so
+/// that a checkpoint can be written to a file.
+pub trait SimState {
+    /// Save a copy of the state
+    fn save(&self) -> String;
+    /// Restore state written by [SimState::save].  Returns `false` if the text
+    /// is not valid state of the right type.
+    fn restore(&mut self, text: &str) -> bool;
+    /// Set the state from text (used by the [Debugger](crate::debugger::Debugger) to
+    /// force values, which it does after every update pass until the value is
+    /// released).  Returns `false` if the state cannot be set this way.
+    fn force(&mut self, _text: &str) -> bool {
+        false
+    }
+}
+
+/// Builds the text of saved state from a sequence of values.  Values are written
+/// with [Synth::to_text], and separated by spaces.
+#[derive(Default)]
+pub struct StateWriter {
+    items: Vec<String>,
+}
+
+impl StateWriter {
+    pub fn put<T: Synth>(&mut self, x: T) -> &mut Self {
+        self.items.push(x.to_text());
+        self
+    }
+    pub fn put_u64(&mut self, x: u64) -> &mut Self {
+        self.items.push(x.to_string());
+        self
+    }
+    /// Write the name of a type, so that restoring into a different type fails
+    pub fn put_type<T: Synth>(&mut self) -> &mut Self {
+        self.items.push(T::descriptor().name.replace(' ', ""));
+        self
+    }
+    pub fn finish(&self) -> String {
+        self.items.join(" ")
+    }
+}
+
+/// Reads back the values written by a [StateWriter], in the same order.
+pub struct StateReader<'a> {
+    items: std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            items: text.split_whitespace(),
+        }
+    }
+    pub fn get<T: Synth>(&mut self) -> Option<T> {
+        T::from_text(self.items.next()?)
+    }
+    pub fn get_u64(&mut self) -> Option<u64> {
+        self.items.next()?.parse().ok()
+    }
+    /// Check the name of a type written with [StateWriter::put_type]
+    pub fn get_type<T: Synth>(&mut self) -> Option<()> {
+        (self.items.next()? == T::descriptor().name.replace(' ', "")).then_some(())
+    }
+    /// Returns `true` if all of the values have been read
+    pub fn is_done(&mut self) -> bool {
+        self.items.next().is_none()
+    }
+}
+
+impl<T: Synth> SimState for T {
+    fn save(&self) -> String {
+        StateWriter::default().put_type::<T>().put(*self).finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let value = reader.get_type::<T>().and_then(|_| reader.get::<T>());
+        match value {
+            Some(x) if reader.is_done() => {
+                *self = x;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<K: Synth + Ord, V: Synth> SimState for BTreeMap<K, V> {
+    fn save(&self) -> String {
+        let mut writer = StateWriter::default();
+        writer
+            .put_type::<K>()
+            .put_type::<V>()
+            .put_u64(self.len() as u64);
+        for (key, value) in self {
+            writer.put(*key).put(*value);
+        }
+        writer.finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let mut read = || -> Option<Self> {
+            reader.get_type::<K>()?;
+            reader.get_type::<V>()?;
+            (0..reader.get_u64()?)
+                .map(|_| Some((reader.get::<K>()?, reader.get::<V>()?)))
+                .collect()
+        };
+        match read() {
+            Some(x) if reader.is_done() => {
+                *self = x;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The ways restoring a [Checkpoint] can fail
+#[derive(Clone, Debug, PartialEq)]
+pub enum CheckpointError {
+    /// The circuit has state at this path that is not in the checkpoint
+    Missing(String),
+    /// The state in the checkpoint at this path is of a different type
+    Mismatch(String),
+    /// The checkpoint has state at these paths that are not in the circuit
+    Unused(Vec<String>),
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Missing(path) => write!(f, "{} is not in the checkpoint", path),
+            CheckpointError::Mismatch(path) => {
+                write!(f, "{} has a different type in the checkpoint", path)
+            }
+            CheckpointError::Unused(paths) => {
+                write!(
+                    f,
+                    "The checkpoint has state not in the circuit: {:?}",
+                    paths
+                )
+            }
+        }
+    }
+}
+
+// Tracks the path of the item being visited.  Scopes and namespaces are
+// treated the same, and the name of the top level circuit is not included.
+#[derive(Default)]
+struct PathTracker {
+    path: Vec<String>,
+}
+
+impl PathTracker {
+    fn push(&mut self, name: &str) {
+        self.path.push(name.to_string());
+    }
+    fn pop(&mut self) {
+        self.path.pop();
+    }
+    fn join(&self, name: &str) -> String {
+        self.path
+            .iter()
+            .skip(1)
+            .map(|x| x.as_str())
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+#[derive(Default)]
+struct Saver {
+    path: PathTracker,
+    state: BTreeMap<String, String>,
+}
+
+impl ProbeMut for Saver {
+    fn visit_start_scope(&mut self, name: &str) {
+        self.path.push(name);
+    }
+    fn visit_start_namespace(&mut self, name: &str) {
+        self.path.push(name);
+    }
+    fn visit_state(&mut self, name: &str, state: &mut dyn SimState) {
+        self.state.insert(self.path.join(name), state.save());
+    }
+    fn visit_end_namespace(&mut self, _name: &str) {
+        self.path.pop();
+    }
+    fn visit_end_scope(&mut self, _name: &str) {
+        self.path.pop();
+    }
+}
+
+struct Restorer<'a> {
+    path: PathTracker,
+    state: &'a BTreeMap<String, String>,
+    used: usize,
+    error: Option<CheckpointError>,
+}
+
+impl<'a> ProbeMut for Restorer<'a> {
+    fn visit_start_scope(&mut self, name: &str) {
+        self.path.push(name);
+    }
+    fn visit_start_namespace(&mut self, name: &str) {
+        self.path.push(name);
+    }
+    fn visit_state(&mut self, name: &str, state: &mut dyn SimState) {
+        if self.error.is_some() {
+            return;
+        }
+        let path = self.path.join(name);
+        match self.state.get(&path) {
+            Some(saved) => {
+                if state.restore(saved) {
+                    self.used += 1;
+                } else {
+                    self.error = Some(CheckpointError::Mismatch(path));
+                }
+            }
+            None => self.error = Some(CheckpointError::Missing(path)),
+        }
+    }
+    fn visit_end_namespace(&mut self, _name: &str) {
+        self.path.pop();
+    }
+    fn visit_end_scope(&mut self, _name: &str) {
+        self.path.pop();
+    }
+}
+
+/// The saved state of a circuit at a point in a simulation.
+#[derive(Clone)]
+pub struct Checkpoint {
+    time: u64,
+    state: Arc<BTreeMap<String, String>>,
+}
+
+impl Checkpoint {
+    /// Capture the state of the circuit, which is at the given simulation time.
+    pub fn capture<B: Block + ?Sized>(uut: &mut B, time: u64) -> Self {
+        let mut saver = Saver::default();
+        uut.accept_mut("uut", &mut saver);
+        Self {
+            time,
+            state: Arc::new(saver.state),
+        }
+    }
+    /// The simulation time the checkpoint was taken at
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+    /// The number of items of state in the checkpoint
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+    /// The paths of the items of state in the checkpoint
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.state.keys().map(|x| x.as_str())
+    }
+    /// The checkpoint as text - the time, followed by a line with the path and
+    /// state of each item.  Read it back with [Checkpoint::from_text].
+    pub fn to_text(&self) -> String {
+        let mut text = format!("time {}\n", self.time);
+        for (path, state) in self.state.iter() {
+            text += &format!("{} {}\n", path, state);
+        }
+        text
+    }
+    /// Read a checkpoint written by [Checkpoint::to_text]
+    pub fn from_text(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let time = lines.next()?.strip_prefix("time ")?.parse().ok()?;
+        let state = lines
+            .map(|line| {
+                let (path, state) = line.split_once(' ').unwrap_or((line, ""));
+                (path.to_string(), state.to_string())
+            })
+            .collect();
+        Some(Self {
+            time,
+            state: Arc::new(state),
+        })
+    }
+    /// Write the checkpoint to a file, so that it can be used by other tests
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+    /// Read a checkpoint from a file written by [Checkpoint::save]
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid checkpoint")
+        })
+    }
+    /// Restore the state into a circuit with the same structure as the one
+    /// the checkpoint was taken from.
+    pub fn restore<B: Block + ?Sized>(&self, uut: &mut B) -> Result<(), CheckpointError> {
+        let mut restorer = Restorer {
+            path: Default::default(),
+            state: &self.state,
+            used: 0,
+            error: None,
+        };
+        uut.accept_mut("uut", &mut restorer);
+        if let Some(error) = restorer.error {
+            return Err(error);
+        }
+        if restorer.used != self.state.len() {
+            // Find the paths that were not restored
+            let mut saver = Saver::default();
+            uut.accept_mut("uut", &mut saver);
+            let unused = self
+                .state
+                .keys()
+                .filter(|x| !saver.state.contains_key(*x))
+                .cloned()
+                .collect();
+            return Err(CheckpointError::Unused(unused));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("time", &self.time)
+            .field("paths", &self.state.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
@@ -0,0 +1,550 @@
+//! Coverage collection for simulations.
+//!
+//! When coverage is enabled on a [Simulation](crate::simulate::Simulation), three kinds of
+//! coverage are collected while it runs:
+//!
+//! * Branch coverage - every arm of each `if` and `match` in an `#[hdl_gen(coverage)]`
+//!   function (i.e., each arm of the resulting `VerilogConditional` and `VerilogMatch`) counts
+//!   the number of times it is evaluated.  Branches are collected per module type.  Functions
+//!   marked with a plain `#[hdl_gen]` are not instrumented, and contribute no branches.
+//! * State coverage - every signal carrying a `LogicState` enum records the states it
+//!   visits, and the transitions between them.
+//! * Toggle coverage - every bit of every other signal records if it has risen and fallen.
+//!
+//! State and toggle coverage are sampled after each step of the simulation, and are
+//! collected per module instance.  The resulting [Coverage] can be rendered as a text
+//! report or in LCOV format, and can be merged with the coverage from other tests.
+use crate::atom::{Atom, AtomKind};
+use crate::block::Block;
+use crate::probe::Probe;
+use crate::synth::VCDValue;
+use crate::type_descriptor::TypeKind;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Identifies an `if` or `match` in the source code.  The `block` number distinguishes
+/// the branch points within a single function.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BranchPoint {
+    pub file: String,
+    pub line: u32,
+    pub block: u32,
+}
+
+/// The states visited by a `LogicState` signal, and the transitions between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateCoverage {
+    /// The number of times each state was entered (including states never entered)
+    pub visits: BTreeMap<String, u64>,
+    /// The number of times each (from, to) transition was taken
+    pub transitions: BTreeMap<(String, String), u64>,
+}
+
+/// Records which bits of a signal have risen and fallen.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToggleCoverage {
+    pub rose: Vec<bool>,
+    pub fell: Vec<bool>,
+}
+
+impl ToggleCoverage {
+    /// The number of bits that have both risen and fallen
+    pub fn toggled(&self) -> usize {
+        self.rose
+            .iter()
+            .zip(&self.fell)
+            .filter(|(rose, fell)| **rose && **fell)
+            .count()
+    }
+}
+
+/// The state and toggle coverage of a single module instance.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InstanceCoverage {
+    pub states: BTreeMap<String, StateCoverage>,
+    pub toggles: BTreeMap<String, ToggleCoverage>,
+}
+
+/// The coverage collected by one or more simulations.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    /// Hit counts for each arm of each branch point, keyed by module type
+    pub branches: BTreeMap<String, BTreeMap<BranchPoint, Vec<u64>>>,
+    /// State and toggle coverage, keyed by module instance path
+    pub instances: BTreeMap<String, InstanceCoverage>,
+}
+
+fn percent(hit: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        100.0 * hit as f64 / total as f64
+    }
+}
+
+impl Coverage {
+    /// Combine the coverage from another run (e.g., another test) into this one
+    pub fn merge(&mut self, other: &Coverage) {
+        for (module, points) in &other.branches {
+            let mine = self.branches.entry(module.clone()).or_default();
+            for (point, hits) in points {
+                let counts = mine.entry(point.clone()).or_default();
+                if counts.len() < hits.len() {
+                    counts.resize(hits.len(), 0);
+                }
+                for (count, hit) in counts.iter_mut().zip(hits) {
+                    *count += hit;
+                }
+            }
+        }
+        for (path, instance) in &other.instances {
+            let mine = self.instances.entry(path.clone()).or_default();
+            for (signal, states) in &instance.states {
+                let cover = mine.states.entry(signal.clone()).or_default();
+                for (state, count) in &states.visits {
+                    *cover.visits.entry(state.clone()).or_default() += count;
+                }
+                for (transition, count) in &states.transitions {
+                    *cover.transitions.entry(transition.clone()).or_default() += count;
+                }
+            }
+            for (signal, toggles) in &instance.toggles {
+                let cover = mine.toggles.entry(signal.clone()).or_default();
+                if cover.rose.len() < toggles.rose.len() {
+                    cover.rose.resize(toggles.rose.len(), false);
+                    cover.fell.resize(toggles.fell.len(), false);
+                }
+                for (ndx, (rose, fell)) in toggles.rose.iter().zip(&toggles.fell).enumerate() {
+                    cover.rose[ndx] |= rose;
+                    cover.fell[ndx] |= fell;
+                }
+            }
+        }
+    }
+
+    /// The number of branch arms taken, and the total number of branch arms
+    pub fn branch_summary(&self) -> (usize, usize) {
+        let arms = self.branches.values().flat_map(|x| x.values()).flatten();
+        arms.fold((0, 0), |(hit, total), count| {
+            (hit + (*count > 0) as usize, total + 1)
+        })
+    }
+
+    /// The number of states visited, and the total number of states
+    pub fn state_summary(&self) -> (usize, usize) {
+        let states = self
+            .instances
+            .values()
+            .flat_map(|x| x.states.values())
+            .flat_map(|x| x.visits.values());
+        states.fold((0, 0), |(hit, total), count| {
+            (hit + (*count > 0) as usize, total + 1)
+        })
+    }
+
+    /// The number of bits that toggled (both rose and fell), and the total number of bits
+    pub fn toggle_summary(&self) -> (usize, usize) {
+        let toggles = self.instances.values().flat_map(|x| x.toggles.values());
+        toggles.fold((0, 0), |(hit, total), toggle| {
+            (hit + toggle.toggled(), total + toggle.rose.len())
+        })
+    }
+
+    /// A human readable report, with a summary for each module followed by
+    /// the items that were not covered.
+    pub fn to_text(&self) -> String {
+        let mut ret = String::new();
+        let (hit, total) = self.branch_summary();
+        writeln!(
+            ret,
+            "Branch coverage: {}/{} ({:.1}%)",
+            hit,
+            total,
+            percent(hit, total)
+        )
+        .unwrap();
+        for (module, points) in &self.branches {
+            let hits = points.values().flatten().filter(|x| **x > 0).count();
+            let arms = points.values().flatten().count();
+            writeln!(ret, "  module {}: {}/{}", module, hits, arms).unwrap();
+            for (point, counts) in points {
+                for (arm, count) in counts.iter().enumerate() {
+                    if *count == 0 {
+                        writeln!(
+                            ret,
+                            "    not taken: {}:{} block {} arm {}",
+                            point.file, point.line, point.block, arm
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+        }
+        let (hit, total) = self.state_summary();
+        writeln!(
+            ret,
+            "State coverage: {}/{} ({:.1}%)",
+            hit,
+            total,
+            percent(hit, total)
+        )
+        .unwrap();
+        for (path, instance) in &self.instances {
+            for (signal, states) in &instance.states {
+                let visited = states.visits.values().filter(|x| **x > 0).count();
+                writeln!(
+                    ret,
+                    "  {}.{}: {}/{} states, {} transitions",
+                    path,
+                    signal,
+                    visited,
+                    states.visits.len(),
+                    states.transitions.len()
+                )
+                .unwrap();
+                for (state, count) in &states.visits {
+                    if *count == 0 {
+                        writeln!(ret, "    not visited: {}", state).unwrap();
+                    }
+                }
+                for ((from, to), count) in &states.transitions {
+                    writeln!(ret, "    {} -> {}: {}", from, to, count).unwrap();
+                }
+            }
+        }
+        let (hit, total) = self.toggle_summary();
+        writeln!(
+            ret,
+            "Toggle coverage: {}/{} ({:.1}%)",
+            hit,
+            total,
+            percent(hit, total)
+        )
+        .unwrap();
+        for (path, instance) in &self.instances {
+            let toggled = instance
+                .toggles
+                .values()
+                .map(|x| x.toggled())
+                .sum::<usize>();
+            let bits = instance
+                .toggles
+                .values()
+                .map(|x| x.rose.len())
+                .sum::<usize>();
+            writeln!(ret, "  instance {}: {}/{}", path, toggled, bits).unwrap();
+            for (signal, toggles) in &instance.toggles {
+                if toggles.toggled() != toggles.rose.len() {
+                    writeln!(
+                        ret,
+                        "    not toggled: {} ({}/{})",
+                        signal,
+                        toggles.toggled(),
+                        toggles.rose.len()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        ret
+    }
+
+    /// The coverage in LCOV tracefile format.  Branches are reported as `BRDA` records
+    /// against their source files.  LCOV has no notion of state or toggle coverage, so
+    /// those are reported as `FN`/`FNDA` records against the module instance, with one
+    /// "function" per state, per transition taken and per signal bit.  Tracefiles from
+    /// different tests can be combined with `lcov -a`, or merged beforehand with [Coverage::merge].
+    pub fn to_lcov(&self) -> String {
+        let mut ret = String::new();
+        let mut files: BTreeMap<&str, Vec<(&BranchPoint, &Vec<u64>)>> = BTreeMap::new();
+        for points in self.branches.values() {
+            for (point, counts) in points {
+                files.entry(&point.file).or_default().push((point, counts));
+            }
+        }
+        for (file, points) in files {
+            writeln!(ret, "TN:\nSF:{}", file).unwrap();
+            let mut found = 0;
+            let mut hit = 0;
+            for (point, counts) in points {
+                for (arm, count) in counts.iter().enumerate() {
+                    let taken = if *count == 0 {
+                        "-".to_string()
+                    } else {
+                        count.to_string()
+                    };
+                    writeln!(ret, "BRDA:{},{},{},{}", point.line, point.block, arm, taken).unwrap();
+                    found += 1;
+                    hit += (*count > 0) as usize;
+                }
+            }
+            writeln!(ret, "BRF:{}\nBRH:{}\nend_of_record", found, hit).unwrap();
+        }
+        for (path, instance) in &self.instances {
+            let mut functions = vec![];
+            for (signal, states) in &instance.states {
+                for (state, count) in &states.visits {
+                    functions.push((format!("{}::state::{}", signal, state), *count));
+                }
+                for ((from, to), count) in &states.transitions {
+                    functions.push((format!("{}::transition::{}->{}", signal, from, to), *count));
+                }
+            }
+            for (signal, toggles) in &instance.toggles {
+                for (bit, (rose, fell)) in toggles.rose.iter().zip(&toggles.fell).enumerate() {
+                    functions.push((format!("{}[{}]::rise", signal, bit), *rose as u64));
+                    functions.push((format!("{}[{}]::fall", signal, bit), *fell as u64));
+                }
+            }
+            writeln!(ret, "TN:\nSF:{}", path).unwrap();
+            for (name, _) in &functions {
+                writeln!(ret, "FN:0,{}", name).unwrap();
+            }
+            for (name, count) in &functions {
+                writeln!(ret, "FNDA:{},{}", count, name).unwrap();
+            }
+            let hit = functions.iter().filter(|x| x.1 > 0).count();
+            writeln!(ret, "FNF:{}\nFNH:{}\nend_of_record", functions.len(), hit).unwrap();
+        }
+        ret
+    }
+}
+
+// The number of threads currently recording branches.  This keeps the cost of the
+// instrumentation in `#[hdl_gen]` functions to a single atomic load when coverage
+// is not being collected.
+static RECORDING: AtomicUsize = AtomicUsize::new(0);
+
+// Branch hits recorded by the current thread, keyed by module, and then by
+// (file, line, block).  This is converted into a [Coverage] after each update.
+type BranchTable = HashMap<&'static str, HashMap<(&'static str, u32, u32), Vec<u64>>>;
+
+thread_local! {
+    static BRANCHES: RefCell<Option<BranchTable>> = const { RefCell::new(None) };
+}
+
+static ACCUMULATED: Mutex<Option<Coverage>> = Mutex::new(None);
+
+/// Merge the coverage of a test into a process wide total.  Useful to combine the
+/// coverage of all of the tests in a test binary.
+pub fn accumulate(coverage: &Coverage) {
+    ACCUMULATED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Default::default)
+        .merge(coverage);
+}
+
+/// The process wide total of the coverage passed to [accumulate]
+pub fn accumulated() -> Coverage {
+    ACCUMULATED.lock().unwrap().clone().unwrap_or_default()
+}
+
+fn with_branches(func: impl FnOnce(&mut BranchTable)) {
+    if RECORDING.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    BRANCHES.with(|branches| {
+        if let Some(branches) = branches.borrow_mut().as_mut() {
+            func(branches)
+        }
+    })
+}
+
+#[doc(hidden)]
+// Called on entry to an instrumented `#[hdl_gen]` function, so that branch points that
+// are never reached still show up in the coverage.  Each entry is (file, line, block, arms).
+pub fn declare_branches(module: &'static str, points: &[(&'static str, u32, u32, usize)]) {
+    with_branches(|branches| {
+        branches.entry(module).or_insert_with(|| {
+            points
+                .iter()
+                .map(|(file, line, block, arms)| ((*file, *line, *block), vec![0; *arms]))
+                .collect()
+        });
+    })
+}
+
+#[doc(hidden)]
+// Called by an instrumented `#[hdl_gen]` function when a branch arm is taken.
+pub fn record_branch(module: &'static str, file: &'static str, line: u32, block: u32, arm: usize) {
+    with_branches(|branches| {
+        if let Some(count) = branches
+            .get_mut(module)
+            .and_then(|x| x.get_mut(&(file, line, block)))
+            .and_then(|x| x.get_mut(arm))
+        {
+            *count += 1;
+        }
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Sample {
+    State(String),
+    Bits(Vec<Option<bool>>),
+}
+
+fn flatten_bits(value: &VCDValue, bits: &mut Vec<Option<bool>>) {
+    let as_bit = |x: &vcd::Value| match x {
+        vcd::Value::V0 => Some(false),
+        vcd::Value::V1 => Some(true),
+        _ => None,
+    };
+    match value {
+        VCDValue::Single(x) => bits.push(as_bit(x)),
+        // Vectors are most significant bit first
+        VCDValue::Vector(x) => bits.extend(x.iter().rev().map(as_bit)),
+        VCDValue::String(_) => {}
+        VCDValue::Composite(x) => {
+            for field in x {
+                flatten_bits(field, bits);
+            }
+        }
+    }
+}
+
+/// Collects coverage for a [Simulation](crate::simulate::Simulation).  The simulation
+/// brackets each update of the circuit with [CoverageCollector::begin_update] and
+/// [CoverageCollector::end_update].
+#[derive(Default)]
+pub struct CoverageCollector {
+    coverage: Coverage,
+    last: HashMap<usize, Sample>,
+}
+
+impl CoverageCollector {
+    /// Start recording the branches taken by this thread
+    pub fn begin_update(&mut self) {
+        BRANCHES.with(|branches| {
+            let mut branches = branches.borrow_mut();
+            if branches.is_none() {
+                RECORDING.fetch_add(1, Ordering::Relaxed);
+                *branches = Some(Default::default());
+            }
+        })
+    }
+    /// Stop recording branches, and sample the state and toggle coverage of the circuit
+    pub fn end_update(&mut self, uut: &dyn Block) {
+        let branches = BRANCHES.with(|branches| branches.borrow_mut().take());
+        if let Some(branches) = branches {
+            RECORDING.fetch_sub(1, Ordering::Relaxed);
+            let branches = branches
+                .into_iter()
+                .map(|(module, points)| {
+                    let points = points
+                        .into_iter()
+                        .map(|((file, line, block), counts)| {
+                            let file = file.to_string();
+                            (BranchPoint { file, line, block }, counts)
+                        })
+                        .collect();
+                    (module.to_string(), points)
+                })
+                .collect();
+            self.coverage.merge(&Coverage {
+                branches,
+                instances: Default::default(),
+            });
+        }
+        let mut sampler = CoverageSampler {
+            collector: self,
+            scopes: vec![],
+            namespaces: vec![],
+        };
+        uut.accept("uut", &mut sampler);
+    }
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+}
+
+struct CoverageSampler<'a> {
+    collector: &'a mut CoverageCollector,
+    scopes: Vec<String>,
+    namespaces: Vec<String>,
+}
+
+impl<'a> Probe for CoverageSampler<'a> {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.scopes.push(name.to_string());
+    }
+
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.namespaces.push(name.to_string());
+    }
+
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        if signal.kind() == AtomKind::Constant {
+            return;
+        }
+        let instance = self
+            .collector
+            .coverage
+            .instances
+            .entry(self.scopes.join("."))
+            .or_default();
+        let mut path = self.namespaces.clone();
+        path.push(name.to_string());
+        let path = path.join(".");
+        let sample = if let TypeKind::Enum(states) = signal.descriptor().kind {
+            let state = match signal.vcd() {
+                VCDValue::String(x) => x,
+                _ => return,
+            };
+            let cover = instance
+                .states
+                .entry(path)
+                .or_insert_with(|| StateCoverage {
+                    visits: states
+                        .iter()
+                        .map(|x| (x.rsplit("::").next().unwrap().to_string(), 0))
+                        .collect(),
+                    transitions: Default::default(),
+                });
+            match self.collector.last.get(&signal.id()) {
+                Some(Sample::State(last)) if *last == state => {}
+                last => {
+                    *cover.visits.entry(state.clone()).or_default() += 1;
+                    if let Some(Sample::State(last)) = last {
+                        *cover
+                            .transitions
+                            .entry((last.clone(), state.clone()))
+                            .or_default() += 1;
+                    }
+                }
+            }
+            Sample::State(state)
+        } else {
+            let mut bits = vec![];
+            flatten_bits(&signal.vcd(), &mut bits);
+            let cover = instance.toggles.entry(path).or_default();
+            if cover.rose.len() < bits.len() {
+                cover.rose.resize(bits.len(), false);
+                cover.fell.resize(bits.len(), false);
+            }
+            if let Some(Sample::Bits(last)) = self.collector.last.get(&signal.id()) {
+                for (ndx, (before, after)) in last.iter().zip(&bits).enumerate() {
+                    match (before, after) {
+                        (Some(false), Some(true)) => cover.rose[ndx] = true,
+                        (Some(true), Some(false)) => cover.fell[ndx] = true,
+                        _ => {}
+                    }
+                }
+            }
+            Sample::Bits(bits)
+        };
+        self.collector.last.insert(signal.id(), sample);
+    }
+
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.namespaces.pop();
+    }
+
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.scopes.pop();
+    }
+}
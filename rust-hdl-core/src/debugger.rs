@@ -0,0 +1,617 @@
+//! An interactive debugger for simulations.
+//!
+//! [Simulation::run_debug](crate::simulate::Simulation::run_debug) runs a simulation
+//! under the control of a [Debugger].  The debugger stops the simulation at
+//! breakpoints (when a signal takes on a value, when a condition on the circuit
+//! becomes true, at a time, or when a testbench halts the simulation because an
+//! assertion failed), and then reads commands that inspect and change the circuit:
+//!
+//! ```text
+//! continue (c)               resume the simulation
+//! step (s) [n]               process the next n events (clock edges and testbench steps)
+//! delta (d) [n]              run n update passes of the circuit
+//! cycle <clock> [n]          run until n rising edges of the clock signal
+//! print (p) [path]           print the signals at (or under) a path
+//! force <path> <value>       hold a signal at a value
+//! release <path>             stop holding a forced signal
+//! break <path> <value>       stop when a signal takes on a value
+//! break @<time>              stop at a time
+//! break halt                 stop when a testbench halts the simulation
+//! breakpoints                list the breakpoints
+//! delete <n>                 delete a breakpoint
+//! time                       print the simulation time
+//! quit (q)                   abort the simulation
+//! ```
+//!
+//! Signals are named by their path in the circuit, with the names of the fields
+//! separated by `.` (e.g. `cntrl.state.q`).  Values are given as decimal, `0x` hex
+//! or `0b` binary numbers, Verilog literals (like `8'h2a`), `true` or `false`, or the
+//! name of an enum variant.
+//!
+//! Each event updates the circuit until it settles.  A `delta` step stops after a
+//! single update pass, so the signals can be printed part way through settling.  A
+//! forced signal is set again after every update pass, so it keeps its value even if
+//! the logic drives it, until it is released.
+//!
+//! The commands come from a [DebugConsole].  The [TerminalConsole] reads them from
+//! standard input, and the [ScriptConsole] from a list, so that a test can script a
+//! debugging session and check its [transcript](Debugger::transcript).  When the
+//! console runs out of commands, the simulation runs to completion.
+//!
+//! Setting the `RUST_HDL_DEBUG` environment variable makes [Simulation::run](crate::simulate::Simulation::run)
+//! run under a terminal debugger that stops when a testbench halts, so a failing
+//! test (run with `--nocapture`) drops into the debugger at the point of failure.
+//! Setting it to `start` also stops before the simulation starts, so that
+//! breakpoints can be set.
+use crate::atom::{Atom, AtomKind};
+use crate::block::Block;
+use crate::checkpoint::SimState;
+use crate::named_path::NamedPath;
+use crate::probe::{Probe, ProbeMut};
+use crate::synth::{parse_literal, VCDValue};
+use crate::type_descriptor::TypeKind;
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// The environment variable that turns on the debugger for [Simulation::run](crate::simulate::Simulation::run)
+pub const DEBUG_VARIABLE: &str = "RUST_HDL_DEBUG";
+
+/// The source of commands for a [Debugger], and the place its output goes
+pub trait DebugConsole {
+    /// The next command, or `None` if there are no more
+    fn read_command(&mut self) -> Option<String>;
+    fn print(&mut self, text: &str);
+}
+
+/// A console that reads commands from standard input
+#[derive(Default)]
+pub struct TerminalConsole;
+
+impl DebugConsole for TerminalConsole {
+    fn read_command(&mut self) -> Option<String> {
+        print!("(rust-hdl) ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+    fn print(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// A console that takes its commands from a script, and discards its output
+/// (which is kept in the [transcript](Debugger::transcript) of the debugger).
+pub struct ScriptConsole {
+    commands: VecDeque<String>,
+}
+
+impl ScriptConsole {
+    pub fn new(commands: &[&str]) -> Self {
+        Self {
+            commands: commands.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
+impl DebugConsole for ScriptConsole {
+    fn read_command(&mut self) -> Option<String> {
+        self.commands.pop_front()
+    }
+    fn print(&mut self, _text: &str) {}
+}
+
+/// A condition that stops the simulation
+pub enum Breakpoint<T> {
+    /// Stop at (or just after) the given time
+    Time(u64),
+    /// Stop when the signal at the path takes on the value
+    Value { path: String, value: String },
+    /// Stop when the named condition on the circuit becomes true
+    When {
+        name: String,
+        check: Box<dyn Fn(&T) -> bool>,
+    },
+    /// Stop when a testbench halts the simulation (e.g., with `sim_assert!`)
+    Halt,
+}
+
+impl<T> Breakpoint<T> {
+    fn describe(&self) -> String {
+        match self {
+            Breakpoint::Time(t) => format!("@{}", t),
+            Breakpoint::Value { path, value } => format!("{} == {}", path, value),
+            Breakpoint::When { name, .. } => name.clone(),
+            Breakpoint::Halt => "halt".into(),
+        }
+    }
+}
+
+/// What the simulation should do after the debugger has run
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugAction {
+    Continue,
+    Quit,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    Run,
+    Stop,
+    Step(usize),
+    Delta(usize),
+    Cycle {
+        clock: String,
+        count: usize,
+        last: Option<String>,
+    },
+}
+
+/// Controls a simulation run with [Simulation::run_debug](crate::simulate::Simulation::run_debug)
+pub struct Debugger<T> {
+    console: Box<dyn DebugConsole>,
+    breakpoints: Vec<Breakpoint<T>>,
+    // Whether each breakpoint was true at the last event, so they
+    // only trigger when they become true
+    armed: Vec<bool>,
+    mode: Mode,
+    // The signals held at a value (path and value), until they are released
+    forces: Vec<(String, String)>,
+    detached: bool,
+    quit: bool,
+    transcript: Vec<String>,
+}
+
+/// Settles the circuit after the debugger changes it.  The second argument is called
+/// after each update pass (to hold the forced signals).
+pub type Settle<'a, T> = dyn FnMut(&mut T, &mut dyn FnMut(&mut T)) -> bool + 'a;
+
+// Collects the displayed values of the signals in a circuit, by path
+struct SignalReader {
+    path: NamedPath,
+    prefix: Option<String>,
+    values: Vec<(String, String)>,
+}
+
+impl SignalReader {
+    fn read(uut: &dyn Block, prefix: Option<&str>) -> Vec<(String, String)> {
+        let mut reader = SignalReader {
+            path: Default::default(),
+            prefix: prefix.map(|x| x.to_string()),
+            values: vec![],
+        };
+        uut.accept("uut", &mut reader);
+        reader.values
+    }
+}
+
+fn signal_path(path: &NamedPath, name: &str) -> String {
+    // The top level circuit is not part of the path
+    let path = path.flat(".");
+    match path.split_once('.') {
+        Some((_, rest)) => format!("{}.{}", rest, name),
+        None => name.to_string(),
+    }
+}
+
+fn under_prefix(path: &str, prefix: &str) -> bool {
+    path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with(['.', '$']))
+}
+
+fn display_value(signal: &dyn Atom) -> String {
+    match (signal.descriptor().kind, signal.vcd()) {
+        (TypeKind::Enum(_), VCDValue::String(x)) => x,
+        _ => signal.verilog().to_string(),
+    }
+}
+
+fn values_match(shown: &str, wanted: &str) -> bool {
+    let wanted = wanted.rsplit("::").next().unwrap_or(wanted);
+    shown == wanted
+        || matches!((parse_literal(shown), parse_literal(wanted)), (Some(a), Some(b)) if a == b)
+}
+
+impl Probe for SignalReader {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.path.push(name);
+    }
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.path.push(name);
+    }
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let path = signal_path(&self.path, name);
+        if let Some(prefix) = &self.prefix {
+            if !under_prefix(&path, prefix) {
+                return;
+            }
+        }
+        let value = if signal.kind() == AtomKind::Constant {
+            format!("{} (constant)", display_value(signal))
+        } else {
+            display_value(signal)
+        };
+        self.values.push((path, value));
+    }
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.path.pop();
+    }
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.path.pop();
+    }
+}
+
+// Forces the values of the signals at a list of paths, and counts the
+// signals that were found and the ones that took the value
+struct SignalForcer<'a> {
+    path: NamedPath,
+    forces: &'a [(String, String)],
+    found: usize,
+    forced: usize,
+}
+
+impl<'a> SignalForcer<'a> {
+    fn apply<T: Block>(x: &mut T, forces: &'a [(String, String)]) -> (usize, usize) {
+        let mut forcer = SignalForcer {
+            path: Default::default(),
+            forces,
+            found: 0,
+            forced: 0,
+        };
+        if !forces.is_empty() {
+            x.accept_mut("uut", &mut forcer);
+        }
+        (forcer.found, forcer.forced)
+    }
+}
+
+impl<'a> ProbeMut for SignalForcer<'a> {
+    fn visit_start_scope(&mut self, name: &str) {
+        self.path.push(name);
+    }
+    fn visit_start_namespace(&mut self, name: &str) {
+        self.path.push(name);
+    }
+    fn visit_state(&mut self, name: &str, state: &mut dyn SimState) {
+        let path = signal_path(&self.path, name);
+        for (target, value) in self.forces {
+            if path == *target {
+                self.found += 1;
+                if state.force(value) {
+                    self.forced += 1;
+                }
+            }
+        }
+    }
+    fn visit_end_namespace(&mut self, _name: &str) {
+        self.path.pop();
+    }
+    fn visit_end_scope(&mut self, _name: &str) {
+        self.path.pop();
+    }
+}
+
+const HELP: &str = "\
+continue (c)               resume the simulation
+step (s) [n]               process the next n events
+delta (d) [n]              run n update passes of the circuit
+cycle <clock> [n]          run until n rising edges of the clock signal
+print (p) [path]           print the signals at (or under) a path
+force <path> <value>       hold a signal at a value
+release <path>             stop holding a forced signal
+break <path> <value>       stop when a signal takes on a value
+break @<time>              stop at a time
+break halt                 stop when a testbench halts the simulation
+breakpoints                list the breakpoints
+delete <n>                 delete a breakpoint
+time                       print the simulation time
+quit (q)                   abort the simulation";
+
+impl<T: Block> Debugger<T> {
+    pub fn new<C: DebugConsole + 'static>(console: C) -> Self {
+        Self {
+            console: Box::new(console),
+            breakpoints: vec![],
+            armed: vec![],
+            mode: Mode::Run,
+            forces: vec![],
+            detached: false,
+            quit: false,
+            transcript: vec![],
+        }
+    }
+    /// A debugger driven from the terminal, that stops before the simulation
+    /// starts (so that breakpoints can be set) and when a testbench halts.
+    pub fn terminal() -> Self {
+        Self::new(TerminalConsole).stop_at_start().break_on_halt()
+    }
+    /// The terminal debugger requested by the `RUST_HDL_DEBUG` environment variable, if any
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(DEBUG_VARIABLE).ok()?;
+        let debugger = Self::new(TerminalConsole).break_on_halt();
+        if value == "start" {
+            Some(debugger.stop_at_start())
+        } else {
+            Some(debugger)
+        }
+    }
+    /// A debugger driven by a script of commands
+    pub fn script(commands: &[&str]) -> Self {
+        Self::new(ScriptConsole::new(commands))
+    }
+    /// Stop (and read commands) before the simulation starts
+    pub fn stop_at_start(mut self) -> Self {
+        self.mode = Mode::Stop;
+        self
+    }
+    pub fn break_at(self, time: u64) -> Self {
+        self.with_breakpoint(Breakpoint::Time(time))
+    }
+    pub fn break_on_value(self, path: &str, value: &str) -> Self {
+        self.with_breakpoint(Breakpoint::Value {
+            path: path.into(),
+            value: value.into(),
+        })
+    }
+    pub fn break_when<F: Fn(&T) -> bool + 'static>(self, name: &str, check: F) -> Self {
+        self.with_breakpoint(Breakpoint::When {
+            name: name.into(),
+            check: Box::new(check),
+        })
+    }
+    pub fn break_on_halt(self) -> Self {
+        self.with_breakpoint(Breakpoint::Halt)
+    }
+    fn with_breakpoint(mut self, breakpoint: Breakpoint<T>) -> Self {
+        self.add_breakpoint(breakpoint);
+        self
+    }
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint<T>) {
+        self.breakpoints.push(breakpoint);
+        self.armed.push(false);
+    }
+    /// Everything the debugger has printed
+    pub fn transcript(&self) -> &[String] {
+        &self.transcript
+    }
+    fn print(&mut self, text: String) {
+        self.console.print(&text);
+        self.transcript.push(text);
+    }
+    /// Called by the simulation before it starts
+    pub fn on_start(&mut self, x: &mut T, time: u64, settle: &mut Settle<T>) -> DebugAction {
+        if self.mode == Mode::Stop {
+            self.print(format!("Stopped at time {}: start", time));
+            return self.repl(x, time, settle);
+        }
+        DebugAction::Continue
+    }
+    /// Called by the simulation after each update pass within an event.  This holds
+    /// the forced signals at their values, and stops for a delta step.
+    pub fn on_delta(&mut self, x: &mut T, time: u64) {
+        SignalForcer::apply(x, &self.forces);
+        if self.detached || self.quit {
+            return;
+        }
+        if let Mode::Delta(n) = &mut self.mode {
+            *n -= 1;
+            if *n == 0 {
+                self.print(format!("Stopped at time {}: delta step", time));
+                // The event is still settling, so changes made here are left to it
+                if self.repl(x, time, &mut |_, _| true) == DebugAction::Quit {
+                    self.quit = true;
+                }
+            }
+        }
+    }
+    /// Called by the simulation after each event
+    pub fn on_event(&mut self, x: &mut T, time: u64, settle: &mut Settle<T>) -> DebugAction {
+        if self.quit {
+            return DebugAction::Quit;
+        }
+        if self.detached {
+            return DebugAction::Continue;
+        }
+        let mut reason = None;
+        match &mut self.mode {
+            Mode::Step(n) => {
+                *n -= 1;
+                if *n == 0 {
+                    reason = Some("step".to_string());
+                }
+            }
+            Mode::Cycle { clock, count, last } => {
+                let now = SignalReader::read(x, Some(clock))
+                    .into_iter()
+                    .find(|(path, _)| path == clock)
+                    .map(|(_, value)| value);
+                let rose = matches!((&last, &now), (Some(a), Some(b)) if values_match(a, "0") && values_match(b, "1"));
+                *last = now;
+                if rose {
+                    *count -= 1;
+                    if *count == 0 {
+                        reason = Some(format!("rising edge of {}", clock));
+                    }
+                }
+            }
+            _ => {}
+        }
+        for ndx in 0..self.breakpoints.len() {
+            let active = match &self.breakpoints[ndx] {
+                Breakpoint::Time(t) => time >= *t,
+                Breakpoint::Value { path, value } => SignalReader::read(x, Some(path))
+                    .iter()
+                    .any(|(p, shown)| p == path && values_match(shown, value)),
+                Breakpoint::When { check, .. } => check(x),
+                Breakpoint::Halt => false,
+            };
+            if active && !self.armed[ndx] && reason.is_none() {
+                reason = Some(format!(
+                    "breakpoint {} ({})",
+                    ndx,
+                    self.breakpoints[ndx].describe()
+                ));
+            }
+            self.armed[ndx] = active;
+        }
+        match reason {
+            Some(reason) => {
+                self.print(format!("Stopped at time {}: {}", time, reason));
+                self.repl(x, time, settle)
+            }
+            None => DebugAction::Continue,
+        }
+    }
+    /// Called by the simulation when a testbench halts it
+    pub fn on_halt(&mut self, x: &mut T, time: u64, settle: &mut Settle<T>) -> DebugAction {
+        if self.detached
+            || !self
+                .breakpoints
+                .iter()
+                .any(|x| matches!(x, Breakpoint::Halt))
+        {
+            return DebugAction::Continue;
+        }
+        self.print(format!("Stopped at time {}: simulation halted", time));
+        self.repl(x, time, settle)
+    }
+
+    fn repl(&mut self, x: &mut T, time: u64, settle: &mut Settle<T>) -> DebugAction {
+        loop {
+            let line = match self.console.read_command() {
+                Some(line) => line,
+                None => {
+                    self.detached = true;
+                    return DebugAction::Continue;
+                }
+            };
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let count = |ndx: usize| -> Option<usize> {
+                match words.get(ndx) {
+                    Some(n) => n.parse().ok().filter(|n| *n > 0),
+                    None => Some(1),
+                }
+            };
+            match words.as_slice() {
+                [] => {}
+                ["help"] | ["h"] => self.print(HELP.into()),
+                ["continue"] | ["c"] => {
+                    self.mode = Mode::Run;
+                    return DebugAction::Continue;
+                }
+                ["step" | "s", ..] => match count(1) {
+                    Some(n) => {
+                        self.mode = Mode::Step(n);
+                        return DebugAction::Continue;
+                    }
+                    None => self.print(format!("Bad step count in: {}", line)),
+                },
+                ["delta" | "d", ..] => match count(1) {
+                    Some(n) => {
+                        self.mode = Mode::Delta(n);
+                        return DebugAction::Continue;
+                    }
+                    None => self.print(format!("Bad delta count in: {}", line)),
+                },
+                ["cycle", clock, ..] => match count(2) {
+                    Some(n) => {
+                        let last = SignalReader::read(x, Some(clock))
+                            .into_iter()
+                            .find(|(path, _)| path == clock)
+                            .map(|(_, value)| value);
+                        if last.is_none() {
+                            self.print(format!("No signal {}", clock));
+                            continue;
+                        }
+                        self.mode = Mode::Cycle {
+                            clock: clock.to_string(),
+                            count: n,
+                            last,
+                        };
+                        return DebugAction::Continue;
+                    }
+                    None => self.print(format!("Bad cycle count in: {}", line)),
+                },
+                ["print" | "p"] => self.print_signals(x, None),
+                ["print" | "p", path] => self.print_signals(x, Some(path)),
+                ["force", path, value] => {
+                    let force = [(path.to_string(), value.to_string())];
+                    match SignalForcer::apply(x, &force) {
+                        (0, _) => self.print(format!("No signal {}", path)),
+                        (_, 0) => self.print(format!("Cannot set {} to {}", path, value)),
+                        _ => {
+                            self.forces.retain(|(p, _)| p != path);
+                            self.forces.extend(force);
+                            let forces = &self.forces;
+                            if !settle(x, &mut |x| {
+                                SignalForcer::apply(x, forces);
+                            }) {
+                                self.print("The circuit failed to converge".into());
+                            }
+                            self.print_signals(x, Some(path));
+                        }
+                    }
+                }
+                ["release", path] => {
+                    let before = self.forces.len();
+                    self.forces.retain(|(p, _)| p != path);
+                    if self.forces.len() == before {
+                        self.print(format!("{} is not forced", path));
+                    }
+                }
+                ["break", "halt"] => self.add_breakpoint(Breakpoint::Halt),
+                ["break", time] if time.starts_with('@') => match time[1..].parse() {
+                    Ok(t) => self.add_breakpoint(Breakpoint::Time(t)),
+                    Err(_) => self.print(format!("Bad time in: {}", line)),
+                },
+                ["break", path, value] => {
+                    if SignalReader::read(x, Some(path))
+                        .iter()
+                        .any(|(p, _)| p == path)
+                    {
+                        self.add_breakpoint(Breakpoint::Value {
+                            path: path.to_string(),
+                            value: value.to_string(),
+                        })
+                    } else {
+                        self.print(format!("No signal {}", path));
+                    }
+                }
+                ["breakpoints"] => {
+                    let list = self
+                        .breakpoints
+                        .iter()
+                        .enumerate()
+                        .map(|(ndx, x)| format!("{}: {}", ndx, x.describe()))
+                        .collect::<Vec<_>>();
+                    self.print(list.join("\n"));
+                }
+                ["delete", n] => match n.parse::<usize>() {
+                    Ok(n) if n < self.breakpoints.len() => {
+                        self.breakpoints.remove(n);
+                        self.armed.remove(n);
+                    }
+                    _ => self.print(format!("No breakpoint {}", n)),
+                },
+                ["time"] => self.print(format!("{}", time)),
+                ["quit"] | ["q"] => return DebugAction::Quit,
+                _ => self.print(format!("Unknown command: {} (try help)", line)),
+            }
+        }
+    }
+
+    fn print_signals(&mut self, x: &T, path: Option<&str>) {
+        let values = SignalReader::read(x, path);
+        if values.is_empty() {
+            self.print(format!("No signal {}", path.unwrap_or_default()));
+            return;
+        }
+        let text = values
+            .iter()
+            .map(|(path, value)| format!("{} = {}", path, value))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.print(text);
+    }
+}
@@ -0,0 +1,259 @@
+//! Details of why a simulation failed.
+//!
+//! When a testbench halts a simulation (usually because a `sim_assert!` or
+//! `sim_assert_eq!` failed), the [SimError::SimHalted](crate::simulate::SimError::SimHalted)
+//! error carries a [SimFailure] that records where in the testbench it happened, the
+//! simulation time, the text of the assertion, and (for `sim_assert_eq!`) the expected
+//! and actual values.  A testbench that panics fails with [SimError::SimPanic](crate::simulate::SimError::SimPanic),
+//! which carries the panic message.
+//!
+//! To see what the circuit was doing just before a failure, a simulation can keep a
+//! rolling record of the last stretch of time for a few signals with
+//! [Simulation::trace_tail](crate::simulate::Simulation::trace_tail).  The record is
+//! attached to the failure as a [TraceTail], which holds a small VCD of just those
+//! signals, and can be drawn as a text waveform with `rust_hdl::docs::vcd2svg::trace_tail_to_txt`.
+//!
+//! ```rust
+//! # use rust_hdl_core::prelude::*;
+//! #[derive(LogicBlock, Default)]
+//! struct Foo {
+//!    pub clock: Signal<In, Clock>,
+//!    pub count: Signal<In, Bits<8>>,
+//! }
+//!
+//! impl Logic for Foo {
+//!   #[hdl_gen]
+//!   fn update(&mut self) {
+//!   }
+//! }
+//!
+//! let mut sim = simple_sim!(Foo, clock, 100_000_000, ep, {
+//!     let mut x = ep.init()?;
+//!     x.count.next = 4.into();
+//!     wait_clock_cycle!(ep, clock, x);
+//!     sim_assert_eq!(ep, x.count.val(), 5, x);
+//!     ep.done(x)
+//! });
+//! sim.trace_tail(&["uut.clock", "uut.count"], 50_000);
+//! let mut uut = Foo::default();
+//! uut.count.connect();
+//! match sim.run(Box::new(uut), 1_000_000) {
+//!     Err(SimError::SimHalted(failure)) => {
+//!         assert_eq!(failure.message, "x.count.val() == 5");
+//!         assert_eq!(failure.expected.as_deref(), Some("5"));
+//!         assert!(failure.trace.is_some());
+//!     }
+//!     _ => panic!("Expected the assertion to fail"),
+//! }
+//! ```
+use crate::atom::Atom;
+use crate::block::Block;
+use crate::probe::Probe;
+use crate::synth::VCDValue;
+use crate::type_descriptor::TypeDescriptor;
+use crate::vcd_probe::write_vcd_trace;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+
+/// Why (and where) a testbench halted a simulation
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct SimFailure {
+    /// The simulation time of the failure
+    pub time: u64,
+    /// Where in the testbench the failure happened (as `file:line:column`)
+    pub location: String,
+    /// What failed (for an assertion, the asserted expression)
+    pub message: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    /// The last stretch of the signals selected with
+    /// [Simulation::trace_tail](crate::simulate::Simulation::trace_tail)
+    pub trace: Option<TraceTail>,
+}
+
+impl SimFailure {
+    /// A failure with a message, located at the caller
+    #[track_caller]
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            location: std::panic::Location::caller().to_string(),
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+    /// Record the expected and actual values
+    pub fn with_values<S: Into<String>, R: Into<String>>(mut self, expected: S, actual: R) -> Self {
+        self.expected = Some(expected.into());
+        self.actual = Some(actual.into());
+        self
+    }
+}
+
+impl std::fmt::Display for SimFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at time {} ({})",
+            self.message, self.time, self.location
+        )?;
+        if let (Some(expected), Some(actual)) = (&self.expected, &self.actual) {
+            write!(f, ": expected {}, got {}", expected, actual)?;
+        }
+        Ok(())
+    }
+}
+
+/// A recording of a few signals over the stretch of time just before a failure,
+/// stored as a VCD.  The signals are named as in a full VCD of the simulation
+/// (e.g., `uut.cntrl.state.q`).
+#[derive(Clone, PartialEq)]
+pub struct TraceTail {
+    pub signals: Vec<String>,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub vcd: Vec<u8>,
+}
+
+impl std::fmt::Debug for TraceTail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceTail")
+            .field("signals", &self.signals)
+            .field("start_time", &self.start_time)
+            .field("end_time", &self.end_time)
+            .field("vcd_bytes", &self.vcd.len())
+            .finish()
+    }
+}
+
+// Extract the message from the payload of a panic
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".into()
+    }
+}
+
+struct TracedSignal {
+    path: String,
+    descriptor: TypeDescriptor,
+    // The changes to the signal, oldest first
+    changes: VecDeque<(u64, VCDValue)>,
+}
+
+/// Keeps the recent history of a few signals for a [TraceTail]
+pub(crate) struct TailRecorder {
+    window: u64,
+    selected: Vec<String>,
+    signals: Vec<TracedSignal>,
+    by_id: HashMap<usize, usize>,
+    searched: bool,
+}
+
+// Finds the selected signals in the circuit
+struct SignalFinder<'a> {
+    path: Vec<String>,
+    recorder: &'a mut TailRecorder,
+}
+
+impl<'a> Probe for SignalFinder<'a> {
+    fn visit_start_scope(&mut self, name: &str, _node: &dyn Block) {
+        self.path.push(name.to_string());
+    }
+    fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
+        self.path.push(name.to_string());
+    }
+    fn visit_atom(&mut self, name: &str, signal: &dyn Atom) {
+        let path = format!("{}.{}", self.path.join("."), name);
+        if self.recorder.selected.contains(&path) {
+            self.recorder
+                .by_id
+                .insert(signal.id(), self.recorder.signals.len());
+            self.recorder.signals.push(TracedSignal {
+                path,
+                descriptor: signal.descriptor(),
+                changes: Default::default(),
+            });
+        }
+    }
+    fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {
+        self.path.pop();
+    }
+    fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {
+        self.path.pop();
+    }
+}
+
+// Records the values of the selected signals that changed
+struct SignalSampler<'a> {
+    time: u64,
+    recorder: &'a mut TailRecorder,
+}
+
+impl<'a> Probe for SignalSampler<'a> {
+    fn visit_atom(&mut self, _name: &str, signal: &dyn Atom) {
+        if let Some(ndx) = self.recorder.by_id.get(&signal.id()) {
+            let value = signal.vcd();
+            let traced = &mut self.recorder.signals[*ndx];
+            if traced.changes.back().map(|x| &x.1) != Some(&value) {
+                traced.changes.push_back((self.time, value));
+            }
+            // Keep one change from before the window, so the value at the start is known
+            let start = self.time.saturating_sub(self.recorder.window);
+            while traced.changes.len() > 1 && traced.changes[1].0 <= start {
+                traced.changes.pop_front();
+            }
+        }
+    }
+}
+
+impl TailRecorder {
+    pub(crate) fn new(signals: &[&str], window: u64) -> Self {
+        Self {
+            window,
+            selected: signals.iter().map(|x| x.to_string()).collect(),
+            signals: vec![],
+            by_id: Default::default(),
+            searched: false,
+        }
+    }
+    /// Forget the history (and the signals found), for a run on a new circuit
+    pub(crate) fn clear(&mut self) {
+        self.signals.clear();
+        self.by_id.clear();
+        self.searched = false;
+    }
+    /// Sample the circuit at the given time
+    pub(crate) fn sample(&mut self, uut: &dyn Block, time: u64) {
+        if !self.searched {
+            self.searched = true;
+            let mut finder = SignalFinder {
+                path: vec![],
+                recorder: self,
+            };
+            uut.accept("uut", &mut finder);
+        }
+        let mut sampler = SignalSampler {
+            time,
+            recorder: self,
+        };
+        uut.accept("uut", &mut sampler);
+    }
+    /// The trace of the window that ends at the given time
+    pub(crate) fn tail(&self, time: u64) -> TraceTail {
+        let start_time = time.saturating_sub(self.window);
+        let signals = self
+            .signals
+            .iter()
+            .map(|x| (x.path.as_str(), &x.descriptor, &x.changes))
+            .collect::<Vec<_>>();
+        TraceTail {
+            signals: self.signals.iter().map(|x| x.path.clone()).collect(),
+            start_time,
+            end_time: time,
+            vcd: write_vcd_trace(&signals, start_time),
+        }
+    }
+}
@@ -11,22 +11,32 @@ pub mod check_error;
 pub mod check_logic_loops;
 pub mod check_timing;
 pub mod check_write_inputs;
+pub mod checkpoint;
 pub mod clock;
 pub mod code_writer;
 pub mod constant;
 pub mod constraint;
+pub mod coverage;
+pub mod debugger;
 pub mod direction;
+pub mod failure;
 pub mod logic;
+pub mod mem_file;
 pub mod module_defines;
 pub mod named_path;
 pub mod path_tools;
 pub mod prelude;
 pub mod probe;
+pub mod property;
+pub mod reset;
+pub mod runner;
+pub mod scoreboard;
 #[doc(hidden)]
 pub mod short_bit_vec;
 pub mod signal;
 pub mod signed;
 pub mod simulate;
+pub mod stimulus;
 pub mod synth;
 pub mod timing;
 pub mod top_wrap;
@@ -1,4 +1,6 @@
 use crate::ast::{Verilog, VerilogLink};
+use crate::mem_file::MemoryInit;
+use crate::probe::ProbeMut;
 use crate::timing::TimingInfo;
 
 pub trait Logic {
@@ -10,6 +12,13 @@ pub trait Logic {
     fn timing(&self) -> Vec<TimingInfo> {
         vec![]
     }
+    /// The initial contents of any memory arrays declared in the Verilog from [Logic::hdl]
+    fn memory_init(&self) -> Vec<MemoryInit> {
+        vec![]
+    }
+    /// Visit any simulation state that is not held in signals (like the contents
+    /// of a RAM), so that it can be saved in a [Checkpoint](crate::checkpoint::Checkpoint).
+    fn accept_state(&mut self, _probe: &mut dyn ProbeMut) {}
 }
 
 pub fn logic_connect_fn<L: Logic>(x: &mut L) {
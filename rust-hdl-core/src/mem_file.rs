@@ -0,0 +1,167 @@
+//! Support for initializing memories from `.mem` files.
+//!
+//! Rather than inlining the contents of a large memory into the generated
+//! Verilog, the memory widgets describe their contents with a [MemoryInit]
+//! (returned from [Logic::memory_init](crate::logic::Logic::memory_init)).
+//! [generate_verilog_with_memory_files](crate::module_defines::generate_verilog_with_memory_files)
+//! loads each one from a `.mem` file with `$readmemh`, and returns the
+//! files needed by the design, which must be written into the directory in
+//! which the synthesis tools run (i.e., next to `top.v`) with
+//! [write_memory_files].  The file names are derived from the contents, so
+//! that identical memories share a single file.  Plain
+//! [generate_verilog](crate::module_defines::generate_verilog) inlines the
+//! contents instead, so that its output is self-contained.
+use crate::bits::Bits;
+use crate::synth::Synth;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The `.mem` files loaded by a design, keyed by file name
+pub type MemoryFiles = BTreeMap<String, String>;
+
+/// Render the contents of a memory in the format read by `$readmemh`.
+/// Each word is written on its own line, and an `@address` line is
+/// inserted wherever the addresses are not contiguous.
+pub fn memory_file_text<D: Synth, const N: usize>(values: &BTreeMap<Bits<N>, D>) -> String {
+    memory_file_text_with_width(values, D::BITS)
+}
+
+/// Render the contents of a memory as for [memory_file_text], keeping
+/// only the low `width` bits of each word.
+pub fn memory_file_text_with_width<D: Synth, const N: usize>(
+    values: &BTreeMap<Bits<N>, D>,
+    width: usize,
+) -> String {
+    let mut text = String::new();
+    let mut next_address = None;
+    for (address, value) in values {
+        let address: usize = address.index();
+        if next_address != Some(address) {
+            text += &format!("@{:x}\n", address);
+        }
+        text += &value.verilog().hex_digits(width);
+        text += "\n";
+        next_address = Some(address + 1);
+    }
+    text
+}
+
+fn fnv1a_64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ (*byte as u64)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The initial contents of a memory array in the Verilog for a module
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryInit {
+    /// The name of the memory array
+    pub array: String,
+    /// The width of each word
+    pub width: usize,
+    /// The contents, in the format read by `$readmemh`
+    pub text: String,
+}
+
+impl MemoryInit {
+    pub fn new<D: Synth, const N: usize>(array: &str, values: &BTreeMap<Bits<N>, D>) -> Self {
+        Self::with_width(array, values, D::BITS)
+    }
+
+    /// The contents of a memory array holding the low `width` bits of `values`
+    pub fn with_width<D: Synth, const N: usize>(
+        array: &str,
+        values: &BTreeMap<Bits<N>, D>,
+        width: usize,
+    ) -> Self {
+        Self {
+            array: array.into(),
+            width,
+            text: memory_file_text_with_width(values, width),
+        }
+    }
+
+    /// The name of the `.mem` file holding the contents.  The name is a
+    /// 64 bit FNV-1a hash of the contents, so it does not change between
+    /// runs (or versions of Rust).
+    pub fn file_name(&self) -> String {
+        format!("rust_hdl_{:016x}.mem", fnv1a_64(self.text.as_bytes()))
+    }
+
+    /// An `initial` block that loads the array from its `.mem` file
+    pub fn readmemh(&self) -> String {
+        format!(
+            "initial $readmemh(\"{}\", {});\n",
+            self.file_name(),
+            self.array
+        )
+    }
+
+    /// An `initial` block that assigns each word of the array
+    pub fn inline(&self) -> String {
+        let mut code = "initial begin\n".to_string();
+        let mut address = 0;
+        for line in self.text.lines() {
+            if let Some(next) = line.strip_prefix('@') {
+                address = usize::from_str_radix(next, 16).unwrap();
+            } else {
+                code += &format!(
+                    "    {}[{}] = {}'h{};\n",
+                    self.array, address, self.width, line
+                );
+                address += 1;
+            }
+        }
+        code + "end\n"
+    }
+}
+
+/// Write the memory files of a design into `dir`, and return their names.
+/// Call this before running the synthesis (or simulation) tools in `dir`.
+pub fn write_memory_files(dir: &Path, files: &MemoryFiles) -> std::io::Result<Vec<String>> {
+    for (name, text) in files {
+        std::fs::write(dir.join(name), text)?;
+    }
+    Ok(files.keys().cloned().collect())
+}
+
+#[test]
+fn test_memory_file_text() {
+    let mut values: BTreeMap<Bits<8>, Bits<12>> = BTreeMap::new();
+    values.insert(0.into(), 0x123.into());
+    values.insert(1.into(), 0xABC.into());
+    values.insert(8.into(), 0x00F.into());
+    assert_eq!(memory_file_text(&values), "@0\n123\nabc\n@8\n00f\n");
+    assert_eq!(memory_file_text_with_width(&values, 4), "@0\n3\nc\n@8\nf\n");
+}
+
+#[test]
+fn test_memory_init_code() {
+    let mut values: BTreeMap<Bits<4>, Bits<8>> = BTreeMap::new();
+    values.insert(3.into(), 0x5A.into());
+    values.insert(4.into(), 0x01.into());
+    let init = MemoryInit::new("mem", &values);
+    assert_eq!(init, MemoryInit::new("mem", &values));
+    assert_eq!(
+        init.readmemh(),
+        format!("initial $readmemh(\"{}\", mem);\n", init.file_name())
+    );
+    assert_eq!(
+        init.inline(),
+        "initial begin\n    mem[3] = 8'h5a;\n    mem[4] = 8'h01;\nend\n"
+    );
+}
+
+#[test]
+fn test_memory_file_names_are_stable() {
+    // The FNV-1a test vectors
+    assert_eq!(fnv1a_64(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    let mut values: BTreeMap<Bits<4>, Bits<8>> = BTreeMap::new();
+    values.insert(0.into(), 0x5A.into());
+    let init = MemoryInit::new("mem", &values);
+    assert_eq!(
+        init.file_name(),
+        format!("rust_hdl_{:016x}.mem", fnv1a_64(b"@0\n5a\n"))
+    );
+}
@@ -4,6 +4,7 @@ use crate::atom::{is_atom_signed, Atom, AtomKind};
 use crate::block::Block;
 use crate::check_error::check_all;
 use crate::code_writer::CodeWriter;
+use crate::mem_file::{MemoryFiles, MemoryInit};
 use crate::named_path::NamedPath;
 use crate::probe::Probe;
 use crate::type_descriptor::{TypeDescriptor, TypeKind};
@@ -23,6 +24,7 @@ struct ModuleDetails {
     enums: Vec<EnumDefinition>,
     code: Verilog,
     links: Vec<VerilogLink>,
+    memory_init: Vec<MemoryInit>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -84,6 +86,7 @@ pub struct ModuleDefines {
     path: NamedPath,
     namespace: NamedPath,
     details: BTreeMap<String, ModuleDetails>,
+    use_memory_files: bool,
 }
 
 impl ModuleDefines {
@@ -132,6 +135,10 @@ impl ModuleDefines {
         };
         entry.code = code;
     }
+    fn add_memory_init(&mut self, module: &str, memory_init: Vec<MemoryInit>) {
+        let entry = self.details.entry(module.into()).or_default();
+        entry.memory_init = memory_init;
+    }
 }
 
 impl Probe for ModuleDefines {
@@ -141,6 +148,7 @@ impl Probe for ModuleDefines {
         self.namespace.reset();
         self.add_submodule(&top_level, name, &self.path.to_string());
         self.add_code(&self.path.to_string(), node.hdl());
+        self.add_memory_init(&self.path.to_string(), node.memory_init());
     }
 
     fn visit_start_namespace(&mut self, name: &str, _node: &dyn Block) {
@@ -428,6 +436,13 @@ impl ModuleDefines {
             Verilog::Blackbox(_) => {}
             Verilog::Empty => {}
         }
+        for init in &module_details.memory_init {
+            if self.use_memory_files {
+                io.add(init.readmemh());
+            } else {
+                io.add(init.inline());
+            }
+        }
         for x in &module_details.links {
             let equiv = get_link_equivalence(x);
             if !self.signal_name_is_module_argument(module_details, &equiv.0)
@@ -488,6 +503,15 @@ impl ModuleDefines {
         });
         io.to_string()
     }
+
+    /// The `.mem` files loaded by the modules (when generating with memory files)
+    pub fn memory_files(&self) -> MemoryFiles {
+        self.details
+            .values()
+            .flat_map(|x| x.memory_init.iter())
+            .map(|x| (x.file_name(), x.text.clone()))
+            .collect()
+    }
 }
 
 pub fn generate_verilog<U: Block>(uut: &U) -> String {
@@ -502,3 +526,63 @@ pub fn generate_verilog_unchecked<U: Block>(uut: &U) -> String {
     uut.accept("top", &mut defines);
     defines.defines()
 }
+
+/// The FPGA family for which Verilog is generated.  Widgets that have
+/// both a portable description and a vendor primitive (such as the DDR
+/// registers) emit both, selected with `` `ifdef `` on the macro
+/// returned by [VerilogTarget::define].  Generic Verilog leaves the macro
+/// undefined, and so uses the portable description.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum VerilogTarget {
+    #[default]
+    Generic,
+    ECP5,
+    ICE40,
+    Spartan6,
+    Xilinx7,
+}
+
+impl VerilogTarget {
+    pub fn define(&self) -> Option<&'static str> {
+        match self {
+            VerilogTarget::Generic => None,
+            VerilogTarget::ECP5 => Some("RUST_HDL_TARGET_ECP5"),
+            VerilogTarget::ICE40 => Some("RUST_HDL_TARGET_ICE40"),
+            VerilogTarget::Spartan6 => Some("RUST_HDL_TARGET_SPARTAN6"),
+            VerilogTarget::Xilinx7 => Some("RUST_HDL_TARGET_XILINX7"),
+        }
+    }
+}
+
+fn add_target_define(verilog: String, target: VerilogTarget) -> String {
+    match target.define() {
+        Some(define) => format!("`define {}\n{}", define, verilog),
+        None => verilog,
+    }
+}
+
+/// Generate the Verilog for `uut`, with vendor specific primitives
+/// selected for `target`.
+pub fn generate_verilog_for_target<U: Block>(uut: &U, target: VerilogTarget) -> String {
+    add_target_define(generate_verilog(uut), target)
+}
+
+/// Generate the Verilog for `uut` (as for [generate_verilog_for_target]), with
+/// the contents of its memories loaded from `.mem` files rather than inlined.
+/// Returns the Verilog, and the memory files to write alongside it with
+/// [write_memory_files](crate::mem_file::write_memory_files).
+pub fn generate_verilog_with_memory_files<U: Block>(
+    uut: &U,
+    target: VerilogTarget,
+) -> (String, MemoryFiles) {
+    let mut defines = ModuleDefines {
+        use_memory_files: true,
+        ..Default::default()
+    };
+    check_all(uut).unwrap(); // TODO - make this not panic...
+    uut.accept("top", &mut defines);
+    (
+        add_target_define(defines.defines(), target),
+        defines.memory_files(),
+    )
+}
@@ -15,6 +15,7 @@ pub use crate::block::Block;
 pub use crate::check_connected::check_connected;
 pub use crate::check_error::check_all;
 pub use crate::check_timing::check_timing;
+pub use crate::checkpoint::{Checkpoint, CheckpointError, SimState, StateReader, StateWriter};
 pub use crate::clock;
 pub use crate::clock::freq_hz_to_period_femto;
 pub use crate::clock::Clock;
@@ -22,16 +23,32 @@ pub use crate::clock::NANOS_PER_FEMTO;
 pub use crate::constant::Constant;
 pub use crate::constraint::Timing::*;
 pub use crate::constraint::*;
+pub use crate::coverage;
+pub use crate::coverage::Coverage;
+pub use crate::debugger::{
+    Breakpoint, DebugAction, DebugConsole, Debugger, ScriptConsole, TerminalConsole,
+};
 pub use crate::direction::{Direction, In, InOut, Local, Out};
+pub use crate::failure::{SimFailure, TraceTail};
 pub use crate::logic;
 pub use crate::logic::Logic;
 pub use crate::logic::LogicJoin;
 pub use crate::logic::LogicLink;
+pub use crate::mem_file::{
+    memory_file_text, memory_file_text_with_width, write_memory_files, MemoryFiles, MemoryInit,
+};
 pub use crate::module_defines::ModuleDefines;
-pub use crate::module_defines::{generate_verilog, generate_verilog_unchecked};
+pub use crate::module_defines::{
+    generate_verilog, generate_verilog_for_target, generate_verilog_unchecked,
+    generate_verilog_with_memory_files, VerilogTarget,
+};
 pub use crate::named_path::NamedPath;
 pub use crate::probe;
-pub use crate::probe::Probe;
+pub use crate::probe::{Probe, ProbeMut};
+pub use crate::property::{PropertyFailure, PropertyTest, Shrink};
+pub use crate::reset::Reset;
+pub use crate::runner::{RunSummary, ScenarioResult, SimRunner};
+pub use crate::scoreboard::{PassThrough, ReferenceModel, Scoreboard};
 pub use crate::signal::Signal;
 pub use crate::signed::ToSignedBits;
 pub use crate::signed::{
@@ -39,12 +56,16 @@ pub use crate::signed::{
 };
 pub use crate::sim_assert;
 pub use crate::sim_assert_eq;
+pub use crate::sim_scoreboard;
 pub use crate::simple_sim;
 pub use crate::simulate::sim_time;
 pub use crate::simulate::simulate;
+pub use crate::simulate::simulation_time;
 pub use crate::simulate::SIMULATION_TIME_ONE_SECOND;
 pub use crate::simulate::{Sim, SimError, Simulation};
+pub use crate::stimulus::{RandomSynth, SimRng, Stimulus};
 pub use crate::synth;
+pub use crate::synth::split_composite;
 pub use crate::synth::Synth;
 pub use crate::synth::VCDValue;
 pub use crate::target_path;
@@ -1,5 +1,6 @@
 use crate::atom::Atom;
 use crate::block::Block;
+use crate::checkpoint::SimState;
 
 pub trait Probe {
     fn visit_start_scope(&mut self, _name: &str, _node: &dyn Block) {}
@@ -8,3 +9,15 @@ pub trait Probe {
     fn visit_end_namespace(&mut self, _name: &str, _node: &dyn Block) {}
     fn visit_end_scope(&mut self, _name: &str, _node: &dyn Block) {}
 }
+
+/// A visitor that can change a circuit - used to save and restore the state
+/// of a simulation (see [Checkpoint](crate::checkpoint::Checkpoint)).  Every
+/// [Signal](crate::signal::Signal) is visited as a piece of [SimState], as is
+/// any state a circuit holds outside of its signals (see [Logic::accept_state](crate::logic::Logic::accept_state)).
+pub trait ProbeMut {
+    fn visit_start_scope(&mut self, _name: &str) {}
+    fn visit_start_namespace(&mut self, _name: &str) {}
+    fn visit_state(&mut self, _name: &str, _state: &mut dyn SimState) {}
+    fn visit_end_namespace(&mut self, _name: &str) {}
+    fn visit_end_scope(&mut self, _name: &str) {}
+}
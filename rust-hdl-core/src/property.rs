@@ -0,0 +1,266 @@
+//! Property based testing of circuits.
+//!
+//! A [PropertyTest] runs a circuit against many randomly generated sequences of
+//! input transactions.  For each case, a fresh circuit is built, and the testbenches
+//! that apply the sequence (and check the results, usually against a model, with
+//! `sim_assert!` or `sim_scoreboard!`) are added to a [Simulation] that has been
+//! [reset](Simulation::reset).  If any case fails, the failing sequence is shrunk
+//! to a minimal one that still fails in the same way (by removing transactions, and
+//! by simplifying the ones that remain), and that minimal run is written to a VCD file.
+//! A shorter sequence only counts as failing if it produces the same kind of
+//! [SimError] as the original, and for an assertion, the same assertion - so that a
+//! candidate that merely leaves a testbench waiting forever (and reaches the maximum
+//! time) is not reported in place of the real failure.
+use crate::block::Block;
+use crate::simulate::{SimError, Simulation};
+use crate::stimulus::SimRng;
+use std::cell::Cell;
+use std::fmt::Debug;
+
+/// Types that can be simplified when shrinking a failing sequence.  The candidates
+/// are tried in order, so the simplest should come first.
+pub trait Shrink: Sized {
+    fn shrink(&self) -> Vec<Self>;
+}
+
+impl<const N: usize> Shrink for crate::bits::Bits<N> {
+    fn shrink(&self) -> Vec<Self> {
+        if !self.any() {
+            return vec![];
+        }
+        let mut ret = vec![Self::default()];
+        let half = *self >> 1;
+        if half.any() {
+            ret.push(half);
+        }
+        ret
+    }
+}
+
+impl Shrink for bool {
+    fn shrink(&self) -> Vec<Self> {
+        if *self {
+            vec![false]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// A failing case, after shrinking.
+#[derive(Clone, Debug)]
+pub struct PropertyFailure<I> {
+    /// The seed of the property test - set `RUST_HDL_SEED` to this to rerun it
+    pub seed: u64,
+    /// The index of the first case that failed
+    pub case: usize,
+    /// The length of the failing sequence before shrinking
+    pub original_len: usize,
+    /// The minimal failing sequence
+    pub minimal: Vec<I>,
+    /// The way the minimal sequence fails
+    pub error: SimError,
+}
+
+impl<I: Debug> std::fmt::Display for PropertyFailure<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Property failed on case {} with {} (rerun with {}=0x{:x}).  Shrunk from {} to {} transactions: {:?}",
+            self.case,
+            self.error,
+            crate::stimulus::SEED_VARIABLE,
+            self.seed,
+            self.original_len,
+            self.minimal.len(),
+            self.minimal
+        )
+    }
+}
+
+type ShrinkFn<I> = Box<dyn Fn(&I) -> Vec<I>>;
+
+/// Runs a property over randomly generated sequences of transactions of type `I`.
+pub struct PropertyTest<I> {
+    rng: SimRng,
+    cases: usize,
+    max_len: usize,
+    max_time: u64,
+    max_shrink_runs: usize,
+    vcd: Option<String>,
+    shrink: Option<ShrinkFn<I>>,
+}
+
+impl<I: Clone + Debug> PropertyTest<I> {
+    /// A property test seeded with [SimRng::from_env], running 64 cases of up to
+    /// 32 transactions each.
+    pub fn new() -> Self {
+        Self {
+            rng: SimRng::from_env(),
+            cases: 64,
+            max_len: 32,
+            max_time: 1_000_000,
+            max_shrink_runs: 1000,
+            vcd: None,
+            shrink: None,
+        }
+    }
+    pub fn seed(mut self, rng: SimRng) -> Self {
+        self.rng = rng;
+        self
+    }
+    pub fn cases(mut self, cases: usize) -> Self {
+        self.cases = cases;
+        self
+    }
+    /// The maximum number of transactions in a case
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+    /// The maximum simulation time of a case
+    pub fn max_time(mut self, max_time: u64) -> Self {
+        self.max_time = max_time;
+        self
+    }
+    /// A limit on the number of simulations used to shrink a failure
+    pub fn max_shrink_runs(mut self, runs: usize) -> Self {
+        self.max_shrink_runs = runs;
+        self
+    }
+    /// Write the minimal failing run to the given VCD file
+    pub fn vcd_file(mut self, path: &str) -> Self {
+        self.vcd = Some(path.to_string());
+        self
+    }
+    /// Simplify the individual transactions of a failing sequence (e.g., with [Shrink])
+    pub fn shrink_with<F: Fn(&I) -> Vec<I> + 'static>(mut self, shrink: F) -> Self {
+        self.shrink = Some(Box::new(shrink));
+        self
+    }
+
+    /// Run the property.  `generate` makes a single random transaction, `build` makes
+    /// a fresh (unconnected) circuit, and `setup` adds the clocks and testbenches that
+    /// apply a sequence of transactions to the simulation.  A case passes if the
+    /// simulation completes without error.
+    pub fn check<T, G, B, S>(
+        mut self,
+        mut generate: G,
+        build: B,
+        setup: S,
+    ) -> Result<(), PropertyFailure<I>>
+    where
+        T: Block + Send + 'static,
+        G: FnMut(&mut SimRng) -> I,
+        B: Fn() -> T,
+        S: Fn(&mut Simulation<T>, &[I]),
+    {
+        let seed = self.rng.seed();
+        let max_time = self.max_time;
+        let mut sim = Simulation::new();
+        for case in 0..self.cases {
+            let len = self.rng.range(1, self.max_len.max(1) as u64) as usize;
+            let sequence = (0..len)
+                .map(|_| generate(&mut self.rng))
+                .collect::<Vec<_>>();
+            let run = |sim: &mut Simulation<T>, sequence: &[I]| {
+                sim.reset();
+                setup(sim, sequence);
+                sim.run(Box::new(build()), max_time)
+            };
+            if let Err(error) = run(&mut sim, &sequence) {
+                let (minimal, error) = self.shrink_failure(&mut sim, &run, sequence, error);
+                if let Some(vcd) = &self.vcd {
+                    sim.reset();
+                    setup(&mut sim, &minimal);
+                    let _ = sim.run_to_file(Box::new(build()), max_time, vcd);
+                }
+                return Err(PropertyFailure {
+                    seed,
+                    case,
+                    original_len: len,
+                    minimal,
+                    error,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn shrink_failure<T, R>(
+        &self,
+        sim: &mut Simulation<T>,
+        run: &R,
+        mut sequence: Vec<I>,
+        mut error: SimError,
+    ) -> (Vec<I>, SimError)
+    where
+        R: Fn(&mut Simulation<T>, &[I]) -> Result<(), SimError>,
+    {
+        let runs = Cell::new(0);
+        let fails = |sim: &mut Simulation<T>, candidate: &[I], error: &mut SimError| {
+            runs.set(runs.get() + 1);
+            match run(sim, candidate) {
+                Err(e) if same_failure(error, &e) => {
+                    *error = e;
+                    true
+                }
+                _ => false,
+            }
+        };
+        let mut progress = true;
+        while progress && runs.get() < self.max_shrink_runs {
+            progress = false;
+            // Remove chunks of transactions, from half the sequence down to single ones
+            let mut chunk = sequence.len() / 2;
+            while chunk >= 1 && runs.get() < self.max_shrink_runs {
+                let mut start = 0;
+                while start + chunk <= sequence.len() && runs.get() < self.max_shrink_runs {
+                    let mut candidate = sequence[..start].to_vec();
+                    candidate.extend_from_slice(&sequence[start + chunk..]);
+                    if !candidate.is_empty() && fails(sim, &candidate, &mut error) {
+                        sequence = candidate;
+                        progress = true;
+                    } else {
+                        start += chunk;
+                    }
+                }
+                chunk /= 2;
+            }
+            // Simplify the transactions that remain
+            if let Some(shrink) = &self.shrink {
+                for ndx in 0..sequence.len() {
+                    for simpler in shrink(&sequence[ndx]) {
+                        if runs.get() >= self.max_shrink_runs {
+                            break;
+                        }
+                        let mut candidate = sequence.clone();
+                        candidate[ndx] = simpler;
+                        if fails(sim, &candidate, &mut error) {
+                            sequence = candidate;
+                            progress = true;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        (sequence, error)
+    }
+}
+
+// Whether a candidate fails in the same way as the original sequence
+fn same_failure(original: &SimError, candidate: &SimError) -> bool {
+    match (original, candidate) {
+        (SimError::SimHalted(original), SimError::SimHalted(candidate)) => {
+            original.location == candidate.location
+        }
+        _ => std::mem::discriminant(original) == std::mem::discriminant(candidate),
+    }
+}
+
+impl<I: Clone + Debug> Default for PropertyTest<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
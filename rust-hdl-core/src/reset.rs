@@ -0,0 +1,27 @@
+use std::fmt::Debug;
+
+/// A Reset signal in RustHDL is, like a [Clock](crate::clock::Clock), a transparent wrapper
+/// around a boolean valued signal.  Giving resets their own type makes the reset tree of
+/// a design explicit, so that resets are only connected to the dedicated reset ports of
+/// synchronous logic (like `ResetDFF`), and show up as resets in traces and checks.
+/// Whether the reset is active high or active low is up to the logic that receives it.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Reset {
+    /// The reset signal itself.  Available using this field of the struct.
+    pub rst: bool,
+}
+
+impl std::ops::Not for Reset {
+    type Output = Reset;
+
+    fn not(self) -> Self::Output {
+        Reset { rst: !self.rst }
+    }
+}
+
+impl From<bool> for Reset {
+    fn from(x: bool) -> Reset {
+        Reset { rst: x }
+    }
+}
@@ -0,0 +1,253 @@
+//! Run many independent simulations of the same circuit in parallel.
+//!
+//! A single [Simulation] runs on one core at a time - the testbenches take turns
+//! with the circuit.  When a circuit has many independent tests (scenarios), the
+//! [SimRunner] runs them on separate threads instead.  Each scenario gets its own
+//! freshly built circuit and its own [Simulation] (typically made with `simple_sim!`),
+//! so that nothing is shared between them.  The results are collected into a
+//! [RunSummary], which records whether each scenario passed, how long it took,
+//! and where its VCD file (if any) was written.
+//!
+//! ```rust
+//! # use rust_hdl_core::prelude::*;
+//! #[derive(LogicBlock, Default)]
+//! struct Foo {
+//!    pub clock: Signal<In, Clock>,
+//! }
+//!
+//! impl Logic for Foo {
+//!   #[hdl_gen]
+//!   fn update(&mut self) {
+//!   }
+//! }
+//!
+//! let summary = SimRunner::new(|| {
+//!         let mut uut = Foo::default();
+//!         uut.clock.connect();
+//!         uut
+//!     })
+//!     .scenario("ten_cycles", 1_000_000, || {
+//!         simple_sim!(Foo, clock, 100_000_000, ep, {
+//!             let mut x = ep.init()?;
+//!             wait_clock_cycles!(ep, clock, x, 10);
+//!             ep.done(x)
+//!         })
+//!     })
+//!     .run();
+//! summary.assert_passed();
+//! ```
+use crate::block::Block;
+use crate::checkpoint::Checkpoint;
+use crate::failure::panic_message;
+use crate::simulate::{SimError, Simulation};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+type BuildFn<T> = Box<dyn Fn() -> T + Send + Sync>;
+type ScenarioFn<T> = Box<dyn Fn() -> Simulation<T> + Send + Sync>;
+
+struct Scenario<T> {
+    name: String,
+    max_time: u64,
+    setup: ScenarioFn<T>,
+}
+
+/// The outcome of a single scenario
+#[derive(Clone, Debug)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub result: Result<(), SimError>,
+    /// The simulation time the scenario ended at
+    pub sim_time: u64,
+    /// The wall clock time the scenario took to run
+    pub elapsed: Duration,
+    /// The VCD file written for the scenario, if any
+    pub vcd: Option<String>,
+}
+
+impl ScenarioResult {
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// The outcome of all of the scenarios, in the order they were added to the runner.
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+    pub results: Vec<ScenarioResult>,
+    pub threads: usize,
+    pub elapsed: Duration,
+}
+
+impl RunSummary {
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|x| x.passed())
+    }
+    pub fn passed(&self) -> impl Iterator<Item = &ScenarioResult> {
+        self.results.iter().filter(|x| x.passed())
+    }
+    pub fn failed(&self) -> impl Iterator<Item = &ScenarioResult> {
+        self.results.iter().filter(|x| !x.passed())
+    }
+    /// The result of the named scenario
+    pub fn get(&self, name: &str) -> Option<&ScenarioResult> {
+        self.results.iter().find(|x| x.name == name)
+    }
+    /// Panic with the summary if any of the scenarios failed
+    pub fn assert_passed(&self) {
+        if !self.all_passed() {
+            panic!("{}", self);
+        }
+    }
+}
+
+impl std::fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Ran {} scenarios on {} threads in {:.3}s: {} passed, {} failed",
+            self.results.len(),
+            self.threads,
+            self.elapsed.as_secs_f64(),
+            self.passed().count(),
+            self.failed().count()
+        )?;
+        for result in &self.results {
+            write!(
+                f,
+                "  {} {} (sim time {}, {:.3}s)",
+                if result.passed() { "PASS" } else { "FAIL" },
+                result.name,
+                result.sim_time,
+                result.elapsed.as_secs_f64()
+            )?;
+            if let Err(err) = &result.result {
+                write!(f, " {:?}", err)?;
+            }
+            if let Some(vcd) = &result.vcd {
+                write!(f, " -> {}", vcd)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs independent scenarios against copies of one circuit, in parallel.
+pub struct SimRunner<T> {
+    build: BuildFn<T>,
+    scenarios: Vec<Scenario<T>>,
+    threads: usize,
+    vcd_dir: Option<String>,
+    start: Option<Checkpoint>,
+}
+
+impl<T: Block + Send + 'static> SimRunner<T> {
+    /// A runner for circuits made by `build`, which is called once per scenario.  The
+    /// circuit does not need to be connected (with `connect_all`) - the simulation does that.
+    pub fn new<B: Fn() -> T + Send + Sync + 'static>(build: B) -> Self {
+        Self {
+            build: Box::new(build),
+            scenarios: vec![],
+            threads: std::thread::available_parallelism()
+                .map(|x| x.get())
+                .unwrap_or(1),
+            vcd_dir: None,
+            start: None,
+        }
+    }
+    /// Add a scenario.  `setup` makes the simulation (with its clocks and testbenches)
+    /// for the scenario, which is run for at most `max_time`.
+    pub fn scenario<S: Fn() -> Simulation<T> + Send + Sync + 'static>(
+        mut self,
+        name: &str,
+        max_time: u64,
+        setup: S,
+    ) -> Self {
+        self.scenarios.push(Scenario {
+            name: name.to_string(),
+            max_time,
+            setup: Box::new(setup),
+        });
+        self
+    }
+    /// The number of scenarios to run at once (the default is the number of cores)
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+    /// Write a VCD file for each scenario, named after the scenario, into this directory
+    pub fn vcd_dir(mut self, path: &str) -> Self {
+        self.vcd_dir = Some(path.to_string());
+        self
+    }
+    /// Start every scenario from a [Checkpoint] (see [Simulation::start_from])
+    pub fn start_from(mut self, checkpoint: &Checkpoint) -> Self {
+        self.start = Some(checkpoint.clone());
+        self
+    }
+
+    fn run_scenario(&self, scenario: &Scenario<T>) -> ScenarioResult {
+        let vcd = self
+            .vcd_dir
+            .as_ref()
+            .map(|dir| format!("{}/{}.vcd", dir, scenario.name));
+        let started = Instant::now();
+        // A panic in the setup (or the circuit) fails only this scenario
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut sim = (scenario.setup)();
+            if let Some(checkpoint) = &self.start {
+                sim.start_from(checkpoint);
+            }
+            let uut = Box::new((self.build)());
+            let result = match &vcd {
+                Some(vcd) => sim.run_to_file(uut, scenario.max_time, vcd),
+                None => sim.run(uut, scenario.max_time),
+            };
+            (result, sim.time())
+        }));
+        let (result, sim_time) =
+            outcome.unwrap_or_else(|e| (Err(SimError::SimPanic(panic_message(e.as_ref()))), 0));
+        ScenarioResult {
+            name: scenario.name.clone(),
+            result,
+            sim_time,
+            elapsed: started.elapsed(),
+            vcd,
+        }
+    }
+
+    /// Run all of the scenarios, and summarize the results.
+    pub fn run(self) -> RunSummary {
+        if let Some(dir) = &self.vcd_dir {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let started = Instant::now();
+        let threads = self.threads.min(self.scenarios.len()).max(1);
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; self.scenarios.len()]);
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| loop {
+                    let ndx = next.fetch_add(1, Ordering::SeqCst);
+                    if ndx >= self.scenarios.len() {
+                        break;
+                    }
+                    let result = self.run_scenario(&self.scenarios[ndx]);
+                    results.lock().unwrap()[ndx] = Some(result);
+                });
+            }
+        });
+        RunSummary {
+            results: results
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|x| x.unwrap())
+                .collect(),
+            threads,
+            elapsed: started.elapsed(),
+        }
+    }
+}
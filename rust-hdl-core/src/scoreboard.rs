@@ -0,0 +1,177 @@
+//! A scoreboard that checks the output of a circuit against a reference model.
+//!
+//! The transactions sent to the circuit are also given to the [Scoreboard], which
+//! passes them through a [ReferenceModel] to get the outputs it expects.  The
+//! transactions seen on the output of the circuit are then compared, in order,
+//! against the expected ones.  Every mismatch is reported along with the seed
+//! of the random stimulus, so the failing run can be repeated.
+//!
+//! The scoreboard is a cheap handle to shared state, so that clones of it can be
+//! moved into the testbenches that drive the inputs and monitor the outputs.
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+/// A model of the expected behavior of a circuit at the transaction level.  Each
+/// input transaction produces zero or more output transactions.
+pub trait ReferenceModel: Send + 'static {
+    type Input;
+    type Output: PartialEq + Debug + Send;
+    fn apply(&mut self, input: Self::Input) -> Vec<Self::Output>;
+}
+
+/// The trivial reference model, for circuits (like FIFOs) that should pass their
+/// input through unchanged.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PassThrough<T>(std::marker::PhantomData<T>);
+
+impl<T: PartialEq + Debug + Send + 'static> ReferenceModel for PassThrough<T> {
+    type Input = T;
+    type Output = T;
+    fn apply(&mut self, input: T) -> Vec<T> {
+        vec![input]
+    }
+}
+
+struct ScoreboardState<M: ReferenceModel> {
+    model: M,
+    expected: VecDeque<M::Output>,
+    matched: usize,
+    errors: Vec<String>,
+}
+
+/// Compares the output of a circuit against a [ReferenceModel].
+pub struct Scoreboard<M: ReferenceModel> {
+    name: String,
+    seed: u64,
+    state: Arc<Mutex<ScoreboardState<M>>>,
+}
+
+impl<M: ReferenceModel> Clone for Scoreboard<M> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            seed: self.seed,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<M: ReferenceModel> Scoreboard<M> {
+    /// A scoreboard for the named check.  The `seed` is the seed of the
+    /// stimulus (e.g., [SimRng::seed](crate::stimulus::SimRng::seed)), and is reported on failure.
+    pub fn new(name: &str, seed: u64, model: M) -> Self {
+        Self {
+            name: name.to_string(),
+            seed,
+            state: Arc::new(Mutex::new(ScoreboardState {
+                model,
+                expected: Default::default(),
+                matched: 0,
+                errors: vec![],
+            })),
+        }
+    }
+    /// Record a transaction sent to the circuit
+    pub fn input(&self, input: M::Input) {
+        let mut state = self.state.lock().unwrap();
+        let outputs = state.model.apply(input);
+        state.expected.extend(outputs);
+    }
+    /// Check a transaction seen on the output of the circuit against the next
+    /// expected one.  On a mismatch, the returned error describes the failure.
+    pub fn output(&self, output: M::Output) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let error = match state.expected.pop_front() {
+            Some(expected) if expected == output => {
+                state.matched += 1;
+                return Ok(());
+            }
+            Some(expected) => format!(
+                "transaction {}: expected {:?}, got {:?}",
+                state.matched + state.errors.len(),
+                expected,
+                output
+            ),
+            None => format!("unexpected output {:?}", output),
+        };
+        let error = self.message(&error);
+        state.errors.push(error.clone());
+        Err(error)
+    }
+    /// The number of output transactions that matched the model
+    pub fn matched(&self) -> usize {
+        self.state.lock().unwrap().matched
+    }
+    /// The number of expected transactions not yet seen on the output
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().expected.len()
+    }
+    /// Call at the end of the test.  Fails if there were any mismatches, or if
+    /// some expected transactions never appeared on the output.
+    pub fn finish(&self) -> Result<usize, String> {
+        let state = self.state.lock().unwrap();
+        if let Some(error) = state.errors.first() {
+            return Err(error.clone());
+        }
+        if !state.expected.is_empty() {
+            return Err(self.message(&format!(
+                "{} expected transactions were not seen (first {:?})",
+                state.expected.len(),
+                state.expected.front().unwrap()
+            )));
+        }
+        Ok(state.matched)
+    }
+    fn message(&self, error: &str) -> String {
+        format!(
+            "Scoreboard {} failed: {} (rerun with {}=0x{:x})",
+            self.name,
+            error,
+            crate::stimulus::SEED_VARIABLE,
+            self.seed
+        )
+    }
+}
+
+/// Check a monitored transaction against a [Scoreboard] from within a testbench.
+/// On a mismatch, the failure (and the seed) is printed and the simulation halts.
+#[macro_export]
+macro_rules! sim_scoreboard {
+    ($sim: ident, $scoreboard: expr, $output: expr, $circuit: ident) => {
+        if let Err(err) = $scoreboard.output($output) {
+            return $sim.halt_with($circuit, $crate::failure::SimFailure::new(err));
+        }
+    };
+}
+
+#[test]
+fn test_scoreboard_reports_seed() {
+    struct Doubler;
+    impl ReferenceModel for Doubler {
+        type Input = u32;
+        type Output = u32;
+        fn apply(&mut self, input: u32) -> Vec<u32> {
+            vec![input * 2]
+        }
+    }
+    let board = Scoreboard::new("doubler", 0xBEEF, Doubler);
+    let monitor = board.clone();
+    board.input(1);
+    board.input(2);
+    board.input(3);
+    assert!(monitor.output(2).is_ok());
+    assert_eq!(monitor.pending(), 2);
+    let err = monitor.output(5).unwrap_err();
+    assert!(err.contains("expected 4, got 5"));
+    assert!(err.contains("RUST_HDL_SEED=0xbeef"));
+    assert!(board.finish().is_err());
+    let board = Scoreboard::new("pass", 1, PassThrough::<u8>::default());
+    board.input(7);
+    assert!(board
+        .finish()
+        .unwrap_err()
+        .contains("1 expected transactions"));
+    board.output(7).unwrap();
+    assert_eq!(board.finish(), Ok(1));
+}
@@ -5,11 +5,13 @@ use crate::ast::{VerilogLink, VerilogLinkDetails, VerilogLiteral};
 use crate::atom::{Atom, AtomKind};
 use crate::bits::Bit;
 use crate::block::Block;
+use crate::checkpoint::{SimState, StateReader, StateWriter};
 use crate::clock::Clock;
 use crate::constraint::{Constraint, PinConstraint, SignalType};
 use crate::direction::{Direction, In, InOut, Local, Out};
 use crate::logic::{Logic, LogicJoin, LogicLink};
-use crate::probe::Probe;
+use crate::probe::{Probe, ProbeMut};
+use crate::reset::Reset;
 use crate::synth::{Synth, VCDValue};
 use crate::type_descriptor::TypeDescriptor;
 
@@ -47,6 +49,17 @@ impl<T: Synth> Signal<In, T> {
     }
 }
 
+impl Signal<In, Reset> {
+    #[inline(always)]
+    pub fn pos_edge(&self) -> bool {
+        self.changed && self.val.rst && !self.prev.rst
+    }
+    #[inline(always)]
+    pub fn neg_edge(&self) -> bool {
+        self.changed && !self.val.rst && self.prev.rst
+    }
+}
+
 impl<T: Synth> Signal<Out, T> {
     pub fn join(&mut self, other: &mut Signal<In, T>) {
         other.next = self.val();
@@ -235,6 +248,75 @@ impl<D: Direction, T: Synth> Block for Signal<D, T> {
     fn accept(&self, name: &str, probe: &mut dyn Probe) {
         probe.visit_atom(name, self);
     }
+
+    fn accept_mut(&mut self, name: &str, probe: &mut dyn ProbeMut) {
+        probe.visit_state(name, self);
+    }
+}
+
+// The part of a signal that changes during a simulation is saved as its type,
+// its next, current and previous values, and its flags.
+impl<D: Direction, T: Synth> SimState for Signal<D, T> {
+    fn save(&self) -> String {
+        StateWriter::default()
+            .put_type::<T>()
+            .put(self.next)
+            .put(self.val)
+            .put(self.prev)
+            .put(self.changed)
+            .put(self.tristate_is_output)
+            .put(self.signal_is_undriven)
+            .finish()
+    }
+
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let mut read = || -> Option<(T, T, T, bool, bool, bool)> {
+            reader.get_type::<T>()?;
+            Some((
+                reader.get()?,
+                reader.get()?,
+                reader.get()?,
+                reader.get()?,
+                reader.get()?,
+                reader.get()?,
+            ))
+        };
+        match read() {
+            Some(x) if reader.is_done() => {
+                self.next = x.0;
+                self.val = x.1;
+                self.prev = x.2;
+                self.changed = x.3;
+                self.tristate_is_output = x.4;
+                self.signal_is_undriven = x.5;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // The debugger forces a signal after every update pass.  If the last pass moved
+    // the signal off of the forced value, that change is undone, so that a forced
+    // signal settles even when logic drives it.
+    fn force(&mut self, text: &str) -> bool {
+        match T::from_text(text) {
+            Some(x) => {
+                if self.val != x {
+                    if self.changed && self.prev == x {
+                        self.changed = false;
+                    } else {
+                        self.prev = self.val;
+                        self.changed = true;
+                    }
+                    self.val = x;
+                }
+                self.next = x;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl Signal<In, Clock> {
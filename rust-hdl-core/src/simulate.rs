@@ -3,7 +3,12 @@ use crossbeam::channel::{RecvError, SendError};
 
 use crate::block::Block;
 use crate::check_error::{check_all, CheckError};
+use crate::checkpoint::{Checkpoint, CheckpointError};
+use crate::coverage::{Coverage, CoverageCollector};
+use crate::debugger::{DebugAction, Debugger};
+use crate::failure::{panic_message, SimFailure, TailRecorder};
 use crate::vcd_probe::{write_vcd_change, write_vcd_dump, write_vcd_header};
+use std::cell::Cell;
 use std::io::Write;
 use std::thread::JoinHandle;
 
@@ -26,6 +31,38 @@ pub fn simulate<B: Block>(uut: &mut B, max_iters: usize) -> bool {
     false
 }
 
+// Update the circuit (and run the custom logic) until it stabilizes.  The hook is
+// called after each update pass (the debugger uses it to hold forced signals, and
+// to stop between passes).
+fn converge<T: Block>(
+    custom_logic: &[CustomLogicFn<T>],
+    x: &mut T,
+    after_pass: &mut dyn FnMut(&mut T),
+) -> bool {
+    for _ in 0..100 {
+        for l in custom_logic {
+            l(x);
+        }
+        x.update_all();
+        after_pass(x);
+        if !x.has_changed() {
+            return true;
+        }
+    }
+    false
+}
+
+thread_local! {
+    static UPDATE_TIME: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The time of the simulation that is updating the circuit on this thread.  Models
+/// that record what happens in a simulation (like the violations seen by an SDRAM
+/// model) can call this from their `update` to timestamp their records.
+pub fn simulation_time() -> u64 {
+    UPDATE_TIME.with(|t| t.get())
+}
+
 #[derive(Clone, Debug, PartialEq)]
 /// The error type returned by a simulation
 pub enum SimError {
@@ -33,14 +70,32 @@ pub enum SimError {
     SimTerminated,
     /// The simulation reached the maximum allowed time for the simulation
     MaxTimeReached,
-    /// The simulation halted - usually this means an assertion failed
-    SimHalted,
+    /// The simulation halted - usually this means an assertion failed.  The [SimFailure]
+    /// records why.
+    SimHalted(Box<SimFailure>),
     /// The circuit failed to converge.  This means the logic has some issue (like an oscillation).
     FailedToConverge,
     /// Something went wrong with the circuit check (either a missing connection or other issue, like a latching write).
     Check(CheckError),
     /// The simulation panicked.  This usually means `.unwrap` was called on a result in the testbench.
-    SimPanic,
+    /// Holds the panic message.
+    SimPanic(String),
+    /// The checkpoint the simulation was started from does not match the circuit.
+    Checkpoint(CheckpointError),
+}
+
+impl std::fmt::Display for SimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimError::SimTerminated => write!(f, "The simulation terminated"),
+            SimError::MaxTimeReached => write!(f, "The simulation reached its maximum time"),
+            SimError::SimHalted(failure) => write!(f, "The simulation halted: {}", failure),
+            SimError::FailedToConverge => write!(f, "The circuit failed to converge"),
+            SimError::Check(err) => write!(f, "The circuit failed its checks: {:?}", err),
+            SimError::SimPanic(msg) => write!(f, "The simulation panicked: {}", msg),
+            SimError::Checkpoint(err) => write!(f, "The checkpoint does not match: {}", err),
+        }
+    }
 }
 
 impl From<CheckError> for SimError {
@@ -49,6 +104,12 @@ impl From<CheckError> for SimError {
     }
 }
 
+impl From<CheckpointError> for SimError {
+    fn from(x: CheckpointError) -> Self {
+        SimError::Checkpoint(x)
+    }
+}
+
 impl From<RecvError> for SimError {
     fn from(_x: RecvError) -> Self {
         SimError::SimTerminated
@@ -69,7 +130,7 @@ enum TriggerType<T> {
     Time(u64),
     Function(Box<dyn Fn(&T) -> bool + Send>),
     Clock(u64),
-    Halt,
+    Halt(Box<SimFailure>),
 }
 
 struct Message<T> {
@@ -79,7 +140,7 @@ struct Message<T> {
 
 enum MessageOrPanic<T> {
     Message(Message<T>),
-    Panic,
+    Panic(String),
 }
 
 struct Worker<T> {
@@ -102,13 +163,16 @@ pub struct Simulation<T> {
     time: u64,
     testbenches: Vec<JoinHandle<Result<()>>>,
     custom_logic: Vec<CustomLogicFn<T>>,
+    coverage: Option<CoverageCollector>,
+    start: Option<Checkpoint>,
+    tail: Option<TailRecorder>,
 }
 
 /// The `Sim` struct is used to communicate with a simulation.  Every testbench
 /// will be provided with a copy of this struct, and will use it to communicate
 /// with the core simulation.
 pub struct Sim<T> {
-    time: u64,
+    time: Cell<u64>,
     to_sim: Sender<MessageOrPanic<T>>,
     from_sim: Receiver<Message<T>>,
 }
@@ -137,6 +201,9 @@ impl<T: Send + 'static + Block> Simulation<T> {
             time: 0,
             testbenches: vec![],
             custom_logic: vec![],
+            coverage: None,
+            start: None,
+            tail: None,
         }
     }
     /// Add a clock function to the simulation
@@ -244,9 +311,10 @@ impl<T: Send + 'static + Block> Simulation<T> {
             let result = std::panic::catch_unwind(|| testbench(ep));
             match result {
                 Ok(x) => x,
-                Err(_e) => {
-                    ep_panic.send(MessageOrPanic::Panic).unwrap();
-                    Err(SimError::SimPanic)
+                Err(e) => {
+                    let msg = panic_message(e.as_ref());
+                    ep_panic.send(MessageOrPanic::Panic(msg.clone())).unwrap();
+                    Err(SimError::SimPanic(msg))
                 }
             }
         }));
@@ -257,6 +325,85 @@ impl<T: Send + 'static + Block> Simulation<T> {
     {
         self.custom_logic.push(Box::new(logic));
     }
+    /// Collect branch, state and toggle coverage while the simulation runs.  See
+    /// the [coverage](crate::coverage) module for details.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Default::default);
+    }
+    /// Keep a record of the given signals (named as in a VCD of the simulation, like
+    /// `uut.cntrl.state.q`) over the last `window` of simulation time (e.g., 20 clock
+    /// periods).  If a testbench halts the simulation, the record is attached to the
+    /// [SimFailure] as a [TraceTail](crate::failure::TraceTail).
+    pub fn trace_tail(&mut self, signals: &[&str], window: u64) {
+        self.tail = Some(TailRecorder::new(signals, window));
+    }
+    /// The coverage collected so far, if it was enabled with [Simulation::enable_coverage]
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref().map(|x| x.coverage())
+    }
+    /// Start the next run of the simulation from a [Checkpoint], instead of from
+    /// the initial state of the circuit.  The state in the checkpoint is restored
+    /// into the circuit given to the run (which must have the same structure as the
+    /// one the checkpoint was taken from), and the simulation time starts at the
+    /// time of the checkpoint.  The `max_time` of the run is still measured from
+    /// zero.  The clocks and testbenches of the run are started fresh at that time,
+    /// and [Sim::time] will return it after [Sim::init].
+    pub fn start_from(&mut self, checkpoint: &Checkpoint) {
+        self.start = Some(checkpoint.clone());
+    }
+    /// Run the simulation up to the given time, and then stop it and return a
+    /// [Checkpoint] of the state of the circuit, after everything that happened
+    /// before that time.  Unlike [Simulation::run], the simulation does not stop
+    /// early if only the clocks are left running, so a simulation with just the
+    /// clocks (and, say, a testbench that holds a reset) can be used to get a
+    /// circuit through a long initialization.  The clocks and testbenches are
+    /// discarded, so [Simulation::reset] must be called before the simulation
+    /// is used again.
+    ///
+    /// State that is not held in signals is only in the checkpoint if the circuit
+    /// visits it with [Logic::accept_state](crate::logic::Logic::accept_state).  See
+    /// the [checkpoint](crate::checkpoint) module for what is (and is not) saved.
+    pub fn run_to_checkpoint(&mut self, mut x: Box<T>, time: u64) -> Result<Checkpoint> {
+        self.begin_run(x.as_mut())?;
+        // First initialize the workers.
+        for id in 0..self.workers.len() {
+            x = self.dispatch(id, x)?;
+        }
+        let mut halted = None;
+        loop {
+            let next = self.scan_workers(&x);
+            if next.halted {
+                halted = Some(self.halt_error(next.idx));
+                break;
+            }
+            if next.time == !0 || next.time >= time {
+                break;
+            }
+            self.time = next.time;
+            x = self.dispatch(next.idx, x)?;
+        }
+        self.terminate();
+        if let Some(err) = halted {
+            return Err(err);
+        }
+        Ok(Checkpoint::capture(x.as_mut(), time))
+    }
+    fn begin_run(&mut self, x: &mut T) -> Result<()> {
+        x.connect_all();
+        check_all(x)?;
+        if let Some(tail) = &mut self.tail {
+            tail.clear();
+        }
+        if let Some(checkpoint) = self.start.take() {
+            checkpoint.restore(x)?;
+            self.time = checkpoint.time();
+        }
+        Ok(())
+    }
+    /// The current simulation time (after a run, the time it ended at)
+    pub fn time(&self) -> u64 {
+        self.time
+    }
     pub fn endpoint(&mut self) -> Sim<T> {
         let (send_to_worker, recv_from_sim_to_worker) = bounded(0);
         let id = self.workers.len();
@@ -269,10 +416,18 @@ impl<T: Send + 'static + Block> Simulation<T> {
         Sim {
             to_sim: self.channel_to_sim.clone(),
             from_sim: recv_from_sim_to_worker,
-            time: 0,
+            time: Cell::new(0),
         }
     }
     fn dispatch(&mut self, idx: usize, x: Box<T>) -> Result<Box<T>> {
+        self.dispatch_with(idx, x, &mut |_| {})
+    }
+    fn dispatch_with(
+        &mut self,
+        idx: usize,
+        x: Box<T>,
+        after_pass: &mut dyn FnMut(&mut T),
+    ) -> Result<Box<T>> {
         let worker = &mut self.workers[idx];
         worker.channel_to_worker.send(Message {
             kind: TriggerType::Time(self.time),
@@ -281,22 +436,22 @@ impl<T: Send + 'static + Block> Simulation<T> {
         let x = self.recv.recv()?;
         let mut x = match x {
             MessageOrPanic::Message(x) => x,
-            MessageOrPanic::Panic => {
-                return Err(SimError::SimPanic);
+            MessageOrPanic::Panic(msg) => {
+                return Err(SimError::SimPanic(msg));
             }
         };
         worker.kind = x.kind;
         // Update the circuit
-        let mut converged = false;
-        for _ in 0..100 {
-            for l in &self.custom_logic {
-                l(&mut x.circuit);
-            }
-            x.circuit.update_all();
-            if !x.circuit.has_changed() {
-                converged = true;
-                break;
-            }
+        UPDATE_TIME.with(|t| t.set(self.time));
+        if let Some(coverage) = &mut self.coverage {
+            coverage.begin_update();
+        }
+        let converged = converge(&self.custom_logic, x.circuit.as_mut(), after_pass);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.end_update(x.circuit.as_ref());
+        }
+        if let Some(tail) = &mut self.tail {
+            tail.sample(x.circuit.as_ref(), self.time);
         }
         if !converged {
             Err(SimError::FailedToConverge)
@@ -304,17 +459,28 @@ impl<T: Send + 'static + Block> Simulation<T> {
             Ok(x.circuit)
         }
     }
+    // The error for a testbench that halted the simulation, with the trace tail (if any)
+    fn halt_error(&self, idx: usize) -> SimError {
+        let mut failure = match &self.workers[idx].kind {
+            TriggerType::Halt(failure) => failure.clone(),
+            _ => Box::new(SimFailure::new("halted")),
+        };
+        if let Some(tail) = &self.tail {
+            failure.trace = Some(tail.tail(self.time));
+        }
+        SimError::SimHalted(failure)
+    }
     fn scan_workers(&self, x: &T) -> NextTime {
         let mut min_time = !0_u64;
         let mut min_idx = 0;
         let mut only_clock_waiters = true;
         for worker in self.workers.iter() {
             match &worker.kind {
-                TriggerType::Halt => {
+                TriggerType::Halt(_) => {
                     return NextTime {
                         halted: true,
                         time: !0,
-                        idx: !0,
+                        idx: worker.id,
                         clocks_only: false,
                     }
                 }
@@ -349,25 +515,49 @@ impl<T: Send + 'static + Block> Simulation<T> {
             halted: false,
         }
     }
+    /// Prepare the simulation to be run again on a new circuit.  The clocks and
+    /// testbenches (which are consumed by a run) are discarded, and the time is
+    /// set back to zero, so that new ones can be added.  Custom logic and the
+    /// collected coverage are kept.  This is much cheaper than setting up a new
+    /// simulation for each of many short runs (e.g., in a property test).
+    pub fn reset(&mut self) {
+        // Dropping the workers and the channel from the testbenches unblocks any
+        // that were left waiting by a failed run
+        self.workers.clear();
+        let (send, recv) = bounded(0);
+        self.recv = recv;
+        self.channel_to_sim = send;
+        for handle in std::mem::take(&mut self.testbenches) {
+            let _ = handle.join();
+        }
+        self.time = 0;
+    }
     fn terminate(&mut self) {
         self.workers.clear();
         for handle in std::mem::take(&mut self.testbenches) {
             let _ = handle.join().unwrap();
         }
     }
+    /// Run the simulation for at most `max_time`.  If the `RUST_HDL_DEBUG` environment
+    /// variable is set, the simulation is run under a terminal [Debugger] instead
+    /// (see [Debugger::from_env]).
     pub fn run(&mut self, mut x: Box<T>, max_time: u64) -> Result<()> {
-        x.as_mut().connect_all();
-        check_all(x.as_mut())?;
+        if let Some(mut debugger) = Debugger::from_env() {
+            return self.run_debug(x, max_time, &mut debugger);
+        }
+        self.begin_run(x.as_mut())?;
         // First initialize the workers.
         for id in 0..self.workers.len() {
             x = self.dispatch(id, x)?;
         }
         // Next run until we have no one else waiting
-        let mut halted = false;
+        let mut halted = None;
         while self.time < max_time {
             let next = self.scan_workers(&x);
             if next.time == !0 || next.clocks_only || next.halted {
-                halted = next.halted;
+                if next.halted {
+                    halted = Some(self.halt_error(next.idx));
+                }
                 break;
             }
             self.time = next.time;
@@ -377,8 +567,59 @@ impl<T: Send + 'static + Block> Simulation<T> {
         if self.time >= max_time {
             return Err(SimError::MaxTimeReached);
         }
-        if halted {
-            return Err(SimError::SimHalted);
+        if let Some(err) = halted {
+            return Err(err);
+        }
+        Ok(())
+    }
+    /// Run the simulation for at most `max_time` under the control of a [Debugger],
+    /// which is called after each event (a clock edge or a testbench step) and after
+    /// each update pass within an event, and can stop the simulation to inspect and
+    /// change the circuit.  If the debugger quits, the
+    /// run fails with [SimError::SimTerminated].
+    pub fn run_debug(
+        &mut self,
+        mut x: Box<T>,
+        max_time: u64,
+        debugger: &mut Debugger<T>,
+    ) -> Result<()> {
+        self.begin_run(x.as_mut())?;
+        // First initialize the workers.
+        for id in 0..self.workers.len() {
+            x = self.dispatch(id, x)?;
+        }
+        let mut action = debugger.on_start(x.as_mut(), self.time, &mut |x, forces| {
+            converge(&self.custom_logic, x, forces)
+        });
+        let mut halted = None;
+        while self.time < max_time && action == DebugAction::Continue {
+            let next = self.scan_workers(x.as_ref());
+            if next.halted {
+                halted = Some(self.halt_error(next.idx));
+                action = debugger.on_halt(x.as_mut(), self.time, &mut |x, forces| {
+                    converge(&self.custom_logic, x, forces)
+                });
+                break;
+            }
+            if next.time == !0 || next.clocks_only {
+                break;
+            }
+            self.time = next.time;
+            let time = self.time;
+            x = self.dispatch_with(next.idx, x, &mut |x| debugger.on_delta(x, time))?;
+            action = debugger.on_event(x.as_mut(), self.time, &mut |x, forces| {
+                converge(&self.custom_logic, x, forces)
+            });
+        }
+        self.terminate();
+        if action == DebugAction::Quit {
+            return Err(SimError::SimTerminated);
+        }
+        if self.time >= max_time {
+            return Err(SimError::MaxTimeReached);
+        }
+        if let Some(err) = halted {
+            return Err(err);
         }
         Ok(())
     }
@@ -389,20 +630,21 @@ impl<T: Send + 'static + Block> Simulation<T> {
         result
     }
     pub fn run_traced<W: Write>(&mut self, mut x: Box<T>, max_time: u64, trace: W) -> Result<()> {
-        x.as_mut().connect_all();
-        check_all(x.as_mut())?;
+        self.begin_run(x.as_mut())?;
         let mut vcd = write_vcd_header(trace, x.as_ref());
         // First initialize the workers.
         for id in 0..self.workers.len() {
             x = self.dispatch(id, x)?;
         }
         vcd = write_vcd_dump(vcd, x.as_ref());
-        let mut halted = false;
+        let mut halted = None;
         // Next run until we have no one else waiting
         while self.time < max_time {
             let next = self.scan_workers(x.as_ref());
             if next.time == !0 || next.clocks_only || next.halted {
-                halted = next.halted;
+                if next.halted {
+                    halted = Some(self.halt_error(next.idx));
+                }
                 break;
             }
             self.time = next.time;
@@ -414,8 +656,8 @@ impl<T: Send + 'static + Block> Simulation<T> {
         if self.time >= max_time {
             return Err(SimError::MaxTimeReached);
         }
-        if halted {
-            return Err(SimError::SimHalted);
+        if let Some(err) = halted {
+            return Err(err);
         }
         Ok(())
     }
@@ -431,7 +673,12 @@ pub mod sim_time {
 
 impl<T> Sim<T> {
     pub fn init(&self) -> Result<Box<T>> {
-        Ok(self.from_sim.recv()?.circuit)
+        // The simulation may be starting from a checkpoint
+        let t = self.from_sim.recv()?;
+        if let TriggerType::Time(t0) = t.kind {
+            self.time.set(t0);
+        }
+        Ok(t.circuit)
     }
     pub fn watch<S>(&mut self, check: S, x: Box<T>) -> Result<Box<T>>
     where
@@ -443,29 +690,29 @@ impl<T> Sim<T> {
         }))?;
         let t = self.from_sim.recv()?;
         if let TriggerType::Time(t0) = t.kind {
-            self.time = t0;
+            self.time.set(t0);
         }
         Ok(t.circuit)
     }
     pub fn clock(&mut self, delta: u64, x: Box<T>) -> Result<Box<T>> {
         self.to_sim.send(MessageOrPanic::Message(Message {
-            kind: TriggerType::Clock(delta + self.time),
+            kind: TriggerType::Clock(delta + self.time.get()),
             circuit: x,
         }))?;
         let t = self.from_sim.recv()?;
         if let TriggerType::Time(t0) = t.kind {
-            self.time = t0;
+            self.time.set(t0);
         }
         Ok(t.circuit)
     }
     pub fn wait(&mut self, delta: u64, x: Box<T>) -> Result<Box<T>> {
         self.to_sim.send(MessageOrPanic::Message(Message {
-            kind: TriggerType::Time(delta + self.time),
+            kind: TriggerType::Time(delta + self.time.get()),
             circuit: x,
         }))?;
         let t = self.from_sim.recv()?;
         if let TriggerType::Time(t0) = t.kind {
-            self.time = t0;
+            self.time.set(t0);
         }
         Ok(t.circuit)
     }
@@ -476,15 +723,24 @@ impl<T> Sim<T> {
         }))?;
         Ok(())
     }
+    /// Halt the simulation (i.e., fail it)
+    #[track_caller]
     pub fn halt(&self, x: Box<T>) -> Result<()> {
+        self.halt_with(x, SimFailure::new("halted by the testbench"))
+    }
+    /// Halt the simulation, with details of why (used by `sim_assert!`)
+    pub fn halt_with(&self, x: Box<T>, mut failure: SimFailure) -> Result<()> {
+        failure.time = self.time();
+        println!("HALT {}", failure);
+        let failure = Box::new(failure);
         self.to_sim.send(MessageOrPanic::Message(Message {
-            kind: TriggerType::Halt,
+            kind: TriggerType::Halt(failure.clone()),
             circuit: x,
         }))?;
-        Err(SimError::SimHalted)
+        Err(SimError::SimHalted(failure))
     }
     pub fn time(&self) -> u64 {
-        self.time
+        self.time.get()
     }
 }
 
@@ -533,8 +789,10 @@ macro_rules! wait_clock_cycles {
 macro_rules! sim_assert {
     ($sim: ident, $test: expr, $circuit: ident) => {
         if !($test) {
-            println!("HALT {}", stringify!($test));
-            return $sim.halt($circuit);
+            return $sim.halt_with(
+                $circuit,
+                $crate::failure::SimFailure::new(stringify!($test)),
+            );
         }
     };
 }
@@ -543,14 +801,13 @@ macro_rules! sim_assert {
 macro_rules! sim_assert_eq {
     ($sim: ident, $lhs: expr, $rhs: expr, $circuit: ident) => {
         if !($lhs == $rhs) {
-            println!(
-                "HALT {} != {},  {:?} != {:?}",
+            let failure = $crate::failure::SimFailure::new(format!(
+                "{} == {}",
                 stringify!($lhs),
-                stringify!($rhs),
-                $lhs,
-                $rhs
-            );
-            return $sim.halt($circuit);
+                stringify!($rhs)
+            ))
+            .with_values(format!("{:?}", $rhs), format!("{:?}", $lhs));
+            return $sim.halt_with($circuit, failure);
         }
     };
 }
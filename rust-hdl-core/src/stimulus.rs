@@ -0,0 +1,150 @@
+//! Seeded, reproducible random stimulus for testbenches.
+//!
+//! A [SimRng] is a random number generator with a known seed.  The seed is taken
+//! from the `RUST_HDL_SEED` environment variable if it is set (so that a failing
+//! run can be repeated exactly), and is otherwise chosen at random.  Values of
+//! [Synth] types are drawn from a [Stimulus], which describes the distribution
+//! of the values (uniform, a range, a set of values, or a weighted mix of these).
+use crate::bits::{Bit, Bits, LiteralType, ToBits};
+use crate::synth::Synth;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The environment variable used to set the seed of [SimRng::from_env]
+pub const SEED_VARIABLE: &str = "RUST_HDL_SEED";
+
+/// A seeded random number generator for testbenches.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl SimRng {
+    /// A generator with the given seed.  The same seed always produces the same values.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+    /// A generator seeded from the `RUST_HDL_SEED` environment variable (in decimal,
+    /// or hex with a leading `0x`), or with a random seed if it is not set.
+    pub fn from_env() -> Self {
+        let seed = std::env::var(SEED_VARIABLE)
+            .ok()
+            .and_then(|x| match x.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                None => x.parse().ok(),
+            })
+            .unwrap_or_else(|| rand::thread_rng().gen());
+        Self::new(seed)
+    }
+    /// The seed of this generator - report this when a test fails
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// A uniformly distributed value
+    pub fn random<T: RandomSynth>(&mut self) -> T {
+        T::random(self)
+    }
+    /// A value drawn from the given distribution
+    pub fn sample<T: RandomSynth>(&mut self, stimulus: &Stimulus<T>) -> T {
+        match stimulus {
+            Stimulus::Uniform => T::random(self),
+            Stimulus::Range(low, high) => T::in_range(self, *low, *high),
+            Stimulus::Values(values) => self.choose(values),
+            Stimulus::Weighted(choices) => {
+                let total = choices.iter().map(|x| x.0 as u64).sum::<u64>();
+                assert_ne!(total, 0, "Weighted stimulus needs a non-zero weight");
+                let mut pick = self.rng.gen_range(0..total);
+                for (weight, choice) in choices {
+                    if pick < *weight as u64 {
+                        return self.sample(choice);
+                    }
+                    pick -= *weight as u64;
+                }
+                unreachable!()
+            }
+        }
+    }
+    /// A sequence of `count` values drawn from the given distribution
+    pub fn samples<T: RandomSynth>(&mut self, stimulus: &Stimulus<T>, count: usize) -> Vec<T> {
+        (0..count).map(|_| self.sample(stimulus)).collect()
+    }
+    /// Returns true with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.rng.gen_bool(probability)
+    }
+    /// A value in the (inclusive) range `low..=high`
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        self.rng.gen_range(low..=high)
+    }
+    /// One of the given values, picked uniformly
+    pub fn choose<T: Clone>(&mut self, values: &[T]) -> T {
+        assert!(!values.is_empty());
+        values[self.rng.gen_range(0..values.len())].clone()
+    }
+}
+
+/// The distribution of values for a [Synth] type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stimulus<T> {
+    /// Every value is equally likely
+    Uniform,
+    /// Values in the inclusive range (low, high)
+    Range(LiteralType, LiteralType),
+    /// One of a set of values.  This is the way to randomize `LogicState` enums.
+    Values(Vec<T>),
+    /// A mix of distributions, each picked with probability proportional to its weight
+    Weighted(Vec<(u32, Stimulus<T>)>),
+}
+
+/// [Synth] types that can be randomized.  Enums can be randomized with [Stimulus::Values].
+pub trait RandomSynth: Synth {
+    /// A uniformly distributed value
+    fn random(rng: &mut SimRng) -> Self;
+    /// A value in the inclusive range `low..=high`
+    fn in_range(rng: &mut SimRng, low: LiteralType, high: LiteralType) -> Self;
+}
+
+impl<const N: usize> RandomSynth for Bits<N> {
+    fn random(rng: &mut SimRng) -> Self {
+        if N <= 64 {
+            let value: u64 = rng.rng.gen();
+            let mask = if N == 64 { !0 } else { (1_u64 << N) - 1 };
+            (value & mask).to_bits()
+        } else {
+            (0..N).fold(Bits::default(), |acc, ndx| {
+                acc.replace_bit(ndx, rng.rng.gen())
+            })
+        }
+    }
+    fn in_range(rng: &mut SimRng, low: LiteralType, high: LiteralType) -> Self {
+        rng.range(low, high).to_bits()
+    }
+}
+
+impl RandomSynth for Bit {
+    fn random(rng: &mut SimRng) -> Self {
+        rng.rng.gen()
+    }
+    fn in_range(rng: &mut SimRng, low: LiteralType, high: LiteralType) -> Self {
+        rng.range(low.min(1), high.min(1)) != 0
+    }
+}
+
+#[test]
+fn test_stimulus_is_reproducible() {
+    let stimulus = Stimulus::<Bits<12>>::Weighted(vec![
+        (3, Stimulus::Range(0, 15)),
+        (1, Stimulus::Values(vec![0xFFF.into()])),
+    ]);
+    let values = SimRng::new(0x1234).samples(&stimulus, 200);
+    assert_eq!(values, SimRng::new(0x1234).samples(&stimulus, 200));
+    assert!(values.iter().all(|x| *x < 16 || *x == 0xFFF));
+    let big = values.iter().filter(|x| **x == 0xFFF).count();
+    assert!(big > 20 && big < 80);
+    let wide: Bits<100> = SimRng::new(7).random();
+    assert_eq!(wide, SimRng::new(7).random::<Bits<100>>());
+}
@@ -3,8 +3,10 @@ use std::fmt::Debug;
 use crate::ast::VerilogLiteral;
 use crate::bits::{Bit, Bits};
 use crate::clock::Clock;
+use crate::reset::Reset;
 use crate::signed::Signed;
 use crate::type_descriptor::{TypeDescriptor, TypeKind};
+use num_bigint::{BigInt, BigUint, Sign};
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum VCDValue {
@@ -32,6 +34,81 @@ pub trait Synth: Default + Copy + PartialEq + Debug {
     fn bits(self) -> usize {
         Self::BITS
     }
+    /// Parse a value from text (as typed into the simulation debugger).  Returns
+    /// `None` if the text is not a valid value for the type.
+    fn from_text(_text: &str) -> Option<Self> {
+        None
+    }
+    /// The value as text, in a form that [Synth::from_text] reads back (used to
+    /// save the value in a [Checkpoint](crate::checkpoint::Checkpoint)).  The text
+    /// has no whitespace in it.
+    fn to_text(self) -> String {
+        match self.vcd() {
+            VCDValue::String(name) => name,
+            _ => self.verilog().to_string(),
+        }
+    }
+}
+
+/// Split the text of a composite value, written as `{a,b,c}` (which is how the
+/// values of a `LogicStruct` are written by [Synth::to_text]), into its fields.
+pub fn split_composite(text: &str) -> Option<Vec<&str>> {
+    let inner = text.trim().strip_prefix('{')?.strip_suffix('}')?;
+    if inner.trim().is_empty() {
+        return Some(vec![]);
+    }
+    let mut fields = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (ndx, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                fields.push(inner[start..ndx].trim());
+                start = ndx + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(inner[start..].trim());
+    Some(fields)
+}
+
+/// Parse an unsigned literal, written as a decimal, `0x` hex or `0b` binary number,
+/// a Verilog literal (like `8'h2a`), or `true` or `false`.  Underscores are ignored.
+pub fn parse_literal(text: &str) -> Option<BigUint> {
+    let text = text.trim().replace('_', "");
+    match text.as_str() {
+        "true" => return Some(1_u32.into()),
+        "false" => return Some(0_u32.into()),
+        _ => {}
+    }
+    let (radix, digits) = if let Some((_width, rest)) = text.split_once('\'') {
+        match rest.split_at(rest.len().min(1)) {
+            ("h" | "H", digits) => (16, digits),
+            ("b" | "B", digits) => (2, digits),
+            ("d" | "D", digits) => (10, digits),
+            ("o" | "O", digits) => (8, digits),
+            _ => return None,
+        }
+    } else if let Some(digits) = text.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = text.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, text.as_str())
+    };
+    BigUint::parse_bytes(digits.as_bytes(), radix)
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    let value = parse_literal(text)?;
+    if value.bits() <= 1 {
+        Some(value.bits() == 1)
+    } else {
+        None
+    }
 }
 
 impl<const N: usize> Synth for Bits<N> {
@@ -51,6 +128,15 @@ impl<const N: usize> Synth for Bits<N> {
     fn verilog(self) -> VerilogLiteral {
         self.into()
     }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let value = parse_literal(text)?;
+        if value.bits() <= N as u64 {
+            Some(value.into())
+        } else {
+            None
+        }
+    }
 }
 
 impl Synth for Bit {
@@ -74,6 +160,10 @@ impl Synth for Bit {
     fn verilog(self) -> VerilogLiteral {
         self.into()
     }
+
+    fn from_text(text: &str) -> Option<Self> {
+        parse_bool(text)
+    }
 }
 
 impl Synth for Clock {
@@ -93,6 +183,33 @@ impl Synth for Clock {
     fn verilog(self) -> VerilogLiteral {
         self.clk.into()
     }
+
+    fn from_text(text: &str) -> Option<Self> {
+        parse_bool(text).map(|clk| Clock { clk })
+    }
+}
+
+impl Synth for Reset {
+    const BITS: usize = 1;
+
+    fn descriptor() -> TypeDescriptor {
+        TypeDescriptor {
+            name: "reset".to_string(),
+            kind: TypeKind::Bits(1),
+        }
+    }
+
+    fn vcd(self) -> VCDValue {
+        self.rst.into()
+    }
+
+    fn verilog(self) -> VerilogLiteral {
+        self.rst.into()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        parse_bool(text).map(|rst| Reset { rst })
+    }
 }
 
 impl<const N: usize> Synth for Signed<N> {
@@ -109,4 +226,21 @@ impl<const N: usize> Synth for Signed<N> {
     fn verilog(self) -> VerilogLiteral {
         self.inner().into()
     }
+    fn from_text(text: &str) -> Option<Self> {
+        let (negative, magnitude) = match text.trim().strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let magnitude = parse_literal(magnitude)?;
+        // The range is -2^(N-1) to 2^(N-1)-1
+        let limit = BigUint::from(1_u32) << (N - 1);
+        if magnitude > limit || (magnitude == limit && !negative) {
+            return None;
+        }
+        let sign = if negative { Sign::Minus } else { Sign::Plus };
+        Some(BigInt::from_biguint(sign, magnitude).into())
+    }
+    fn to_text(self) -> String {
+        self.bigint().to_string()
+    }
 }
@@ -1,7 +1,11 @@
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct TimingInfo {
     pub name: String,
     pub clock: String,
     pub inputs: Vec<String>,
     pub outputs: Vec<String>,
+    /// The asynchronous reset of the register, if it has one.  Like the
+    /// clock, an asynchronous reset decouples the outputs from the inputs.
+    /// (A synchronous reset is just another input.)
+    pub reset: Option<String>,
 }
@@ -1,4 +1,10 @@
-use crate::{ast::Verilog, block::Block, logic::Logic, probe::Probe, timing::TimingInfo};
+use crate::{
+    ast::Verilog,
+    block::Block,
+    logic::Logic,
+    probe::{Probe, ProbeMut},
+    timing::TimingInfo,
+};
 
 pub struct TopWrap<U: Block> {
     pub uut: U,
@@ -31,4 +37,9 @@ impl<U: Block> Block for TopWrap<U> {
         self.uut.accept("uut", probe);
         probe.visit_end_scope(name, self);
     }
+    fn accept_mut(&mut self, name: &str, probe: &mut dyn ProbeMut) {
+        probe.visit_start_scope(name);
+        self.uut.accept_mut("uut", probe);
+        probe.visit_end_scope(name);
+    }
 }
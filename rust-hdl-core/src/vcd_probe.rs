@@ -4,7 +4,7 @@ use crate::probe::Probe;
 use crate::synth::VCDValue;
 use crate::type_descriptor::TypeDescriptor;
 use crate::type_descriptor::TypeKind;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::io::Write;
 
 #[derive(Clone, Debug)]
@@ -193,3 +193,61 @@ pub fn write_vcd_dump<W: Write>(vcd: VCDProbe<W>, uut: &dyn Block) -> VCDProbe<W
     visitor.0.vcd.end().unwrap();
     visitor.0
 }
+
+/// A signal to write with [write_vcd_trace]: its path, its type, and its changes
+pub type VCDTrace<'a> = (&'a str, &'a TypeDescriptor, &'a VecDeque<(u64, VCDValue)>);
+
+/// Write a VCD of a few signals, given their paths (like `uut.cntrl.state.q`), their
+/// types, and their changes (in time order).  Changes before the start time are
+/// written at the start time.  Used for the [TraceTail](crate::failure::TraceTail)
+/// of a failed simulation.
+pub fn write_vcd_trace(
+    signals: &[VCDTrace],
+    start_time: u64,
+) -> Vec<u8> {
+    let mut buffer = vec![];
+    let mut vcd = vcd::Writer::new(&mut buffer);
+    vcd.timescale(1, vcd::TimescaleUnit::PS).unwrap();
+    // Declare the signals in order of their paths, so that each scope is only declared once
+    let mut order = (0..signals.len()).collect::<Vec<_>>();
+    order.sort_by_key(|x| signals[*x].0);
+    let mut scopes: Vec<&str> = vec![];
+    let mut codes = vec![None; signals.len()];
+    for ndx in order {
+        let mut path = signals[ndx].0.split('.').collect::<Vec<_>>();
+        let name = path.pop().unwrap();
+        let common = scopes.iter().zip(&path).take_while(|(a, b)| a == b).count();
+        while scopes.len() > common {
+            vcd.upscope().unwrap();
+            scopes.pop();
+        }
+        for scope in &path[common..] {
+            vcd.add_module(scope).unwrap();
+            scopes.push(scope);
+        }
+        codes[ndx] = Some(register_signal(name, signals[ndx].1, &mut vcd));
+    }
+    for _ in scopes {
+        vcd.upscope().unwrap();
+    }
+    vcd.enddefinitions().unwrap();
+    let mut events: BTreeMap<u64, Vec<(usize, &VCDValue)>> = BTreeMap::new();
+    for (ndx, (_, _, changes)) in signals.iter().enumerate() {
+        for (time, value) in changes.iter() {
+            events
+                .entry((*time).max(start_time))
+                .or_default()
+                .push((ndx, value));
+        }
+    }
+    let mut val_map = HashMap::default();
+    for (time, changes) in events {
+        vcd.timestamp(time).unwrap();
+        for (ndx, value) in changes {
+            if let Some(idc) = &codes[ndx] {
+                do_vcd_change(&mut val_map, &mut vcd, idc, value, false);
+            }
+        }
+    }
+    buffer
+}
@@ -12,6 +12,7 @@ pub enum SynthError {
     IOError(std::io::Error),
     WireHasNoDriver(Vec<String>),
     MissingModule(Vec<String>),
+    BlockRAMNotInferred(String),
 }
 
 impl From<std::io::Error> for SynthError {
@@ -20,7 +21,14 @@ impl From<std::io::Error> for SynthError {
     }
 }
 
-pub fn yosys_validate(prefix: &str, translation: &str) -> Result<(), SynthError> {
+// Writes the Verilog into a fresh directory named
+// `prefix`, and runs the yosys `script` on it.  Returns the stdout and
+// stderr of yosys, which are also saved in `yosys.stdout`.
+fn run_yosys(
+    prefix: &str,
+    translation: &str,
+    script: &str,
+) -> Result<(String, String), SynthError> {
     let dir = temp_dir().as_path().join(prefix);
     let _ = remove_dir_all(&dir);
     let _ = create_dir_all(&dir);
@@ -29,19 +37,22 @@ pub fn yosys_validate(prefix: &str, translation: &str) -> Result<(), SynthError>
     let output = Command::new("yosys")
         .current_dir(dir.clone())
         .arg(format!(
-            "-p read -vlog95 top.v; hierarchy -check -top top; proc",
+            "-p read -vlog95 top.v; hierarchy -check -top top; {}",
+            script
         ))
-        .output()
-        .unwrap();
+        .output()?;
     let stdout = String::from_utf8(output.stdout).unwrap();
     let stderr = String::from_utf8(output.stderr).unwrap();
-    {
-        let mut debug = File::create(dir.join("yosys.stdout"))?;
-        write!(debug, "{}", stdout).unwrap();
-        write!(debug, "{}", stderr).unwrap();
-        let mut dump = File::create(dir.join("yosys.v"))?;
-        write!(dump, "{}", translation).unwrap();
-    }
+    let mut debug = File::create(dir.join("yosys.stdout"))?;
+    write!(debug, "{}", stdout).unwrap();
+    write!(debug, "{}", stderr).unwrap();
+    let mut dump = File::create(dir.join("yosys.v"))?;
+    write!(dump, "{}", translation).unwrap();
+    Ok((stdout, stderr))
+}
+
+pub fn yosys_validate(prefix: &str, translation: &str) -> Result<(), SynthError> {
+    let (stdout, stderr) = run_yosys(prefix, translation, "proc")?;
     fn capture(stdout: &str, reg_exp: &str) -> Vec<String> {
         let regex = regex::Regex::new(reg_exp).unwrap();
         let mut signal_name = vec![];
@@ -87,3 +98,80 @@ pub fn yosys_validate(prefix: &str, translation: &str) -> Result<(), SynthError>
     }
     Ok(())
 }
+
+// The FPGA families for which we can check that memories map onto
+// the dedicated block RAM primitives (rather than LUTs or flops).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlockRAMFamily {
+    ICE40,
+    ECP5,
+    Xilinx7,
+}
+
+impl BlockRAMFamily {
+    fn synth_command(&self) -> &'static str {
+        match self {
+            BlockRAMFamily::ICE40 => "synth_ice40 -top top",
+            BlockRAMFamily::ECP5 => "synth_ecp5 -top top",
+            BlockRAMFamily::Xilinx7 => "synth_xilinx -family xc7 -top top",
+        }
+    }
+    fn primitives(&self) -> &'static [&'static str] {
+        match self {
+            BlockRAMFamily::ICE40 => &["SB_RAM40_4K"],
+            BlockRAMFamily::ECP5 => &["DP16KD", "PDPW16KD"],
+            BlockRAMFamily::Xilinx7 => &["RAMB18E1", "RAMB36E1"],
+        }
+    }
+}
+
+// Synthesizes the design for the given family, and counts the
+// number of block RAM primitives in the `stat` report.
+pub fn yosys_block_ram_count(
+    prefix: &str,
+    translation: &str,
+    family: BlockRAMFamily,
+) -> Result<usize, SynthError> {
+    let (stdout, stderr) = run_yosys(
+        prefix,
+        translation,
+        &format!("{}; stat", family.synth_command()),
+    )?;
+    if !stdout.contains("End of script.") {
+        return Err(SynthError::SynthesisFailed { stdout, stderr });
+    }
+    // Only look at the last report, which covers the flattened design.
+    // Depending on the version of yosys, the cell counts are listed
+    // as either "<cell> <count>" or "<count> <cell>".
+    let report = stdout.rsplit("Printing statistics.").next().unwrap_or("");
+    let name_count = regex::Regex::new(r"^\s+(\S+)\s+(\d+)\s*$").unwrap();
+    let count_name = regex::Regex::new(r"^\s+(\d+)\s+(\S+)\s*$").unwrap();
+    Ok(report
+        .lines()
+        .filter_map(|line| {
+            if let Some(x) = name_count.captures(line) {
+                Some((x[1].to_string(), x[2].to_string()))
+            } else {
+                count_name
+                    .captures(line)
+                    .map(|x| (x[2].to_string(), x[1].to_string()))
+            }
+        })
+        .filter(|(name, _)| family.primitives().contains(&name.as_str()))
+        .map(|(_, count)| count.parse::<usize>().unwrap_or(0))
+        .sum())
+}
+
+pub fn yosys_check_block_ram(
+    prefix: &str,
+    translation: &str,
+    family: BlockRAMFamily,
+) -> Result<(), SynthError> {
+    if yosys_block_ram_count(prefix, translation, family)? == 0 {
+        return Err(SynthError::BlockRAMNotInferred(format!(
+            "No block RAM was inferred for {:?}",
+            family
+        )));
+    }
+    Ok(())
+}
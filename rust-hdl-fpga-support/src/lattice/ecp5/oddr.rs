@@ -26,6 +26,9 @@ impl Logic for OutputDDR {
     fn connect(&mut self) {
         self.q.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_capture", &mut self._capture);
+    }
     fn hdl(&self) -> Verilog {
         Verilog::Wrapper(Wrapper {
             code: r##"
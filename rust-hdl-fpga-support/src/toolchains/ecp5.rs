@@ -82,6 +82,9 @@ impl Probe for LPFGenerator {
     }
 }
 
+/// Generate the constraints for `uut`.  The Verilog to go with them should be
+/// generated with `generate_verilog_for_target(uut, VerilogTarget::ECP5)`, so that
+/// widgets with an ECP5 primitive (like the DDR registers) use it.
 pub fn generate_lpf<U: Block>(uut: &U) -> String {
     let mut lpf = LPFGenerator::default();
     uut.accept("top", &mut lpf);
@@ -163,6 +163,18 @@ impl<const A: usize> Logic for BaseController<A> {
     }
 }
 
+// The host side of the `BaseController` protocol.  Implement this for
+// whatever carries the command stream to the controller (a FrontPanel
+// pipe, a UDP socket, a simulation...), and host side libraries can then
+// talk to the ports on the SoC bus through it.
+pub trait ControllerLink {
+    type Error;
+    // Write the data words to the port at the given address (opcode 03)
+    fn write(&mut self, address: u8, data: &[u16]) -> Result<(), Self::Error>;
+    // Read `len` words from the port at the given address (opcode 02)
+    fn read(&mut self, address: u8, len: usize) -> Result<Vec<u16>, Self::Error>;
+}
+
 #[test]
 fn test_base_controller_is_synthesizable() {
     let mut uut = BaseController::<4>::default();
@@ -0,0 +1,304 @@
+use crate::bridge::Bridge;
+use crate::bus::{SoCBusResponder, SoCPortController};
+use crate::miso_port::MISOPort;
+use crate::mosi_port::MOSIPort;
+use crate::HLSNamedPorts;
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+// The address of an I2C target
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum I2CTargetAddress {
+    SevenBit(u8),
+    TenBit(u16),
+}
+
+// Set in the status port when the I2C master has written a register
+pub const I2C_TARGET_WRITTEN: u16 = 0x8000;
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum State {
+    Idle,
+    AddressLow,
+    GetPointer,
+    GetData,
+    ReadFetch,
+    ReadLoad,
+    ReadByte,
+    ReadAck,
+}
+
+// An I2C target that holds a file of `2^A` byte wide registers, which are
+// also available on the SoC bus.  The I2C master writes a register pointer
+// after the target address, followed by any number of register values.  To
+// read the registers back, the master starts a read (typically after a
+// repeated start) which returns registers starting at the pointer.  The
+// pointer moves on to the next register after each byte.  With a 10 bit
+// address, the target responds to a read only if it was addressed with a
+// write (ahead of a repeated start), as required by the I2C specification.
+//
+// On the SoC bus, the `address` port sets the register index, and the
+// `write_data` and `read_data` ports write and read registers (in the low
+// byte of each word) starting at that index, moving on to the next register
+// after each word.  The `status` port holds the index of the last register
+// written by the I2C master, with `I2C_TARGET_WRITTEN` set if there has been
+// a write since the last time the status was read.
+#[derive(LogicBlock)]
+pub struct HLSI2CTarget<const A: usize> {
+    pub i2c: I2CBusDriver,
+    pub upstream: SoCBusResponder<16, 8>,
+    bridge: Bridge<16, 8, 4>,
+    address: MOSIPort<16>,
+    write_data: MOSIPort<16>,
+    read_data: MISOPort<16>,
+    status: MISOPort<16>,
+    phy: I2CTarget,
+    i2c_ram: RAM<Bits<8>, A>,
+    bus_ram: RAM<Bits<8>, A>,
+    state: DFF<State>,
+    active: DFF<Bit>,
+    matched: DFF<Bit>,
+    pointer: DFF<Bits<A>>,
+    outgoing: DFF<Bits<8>>,
+    bus_pointer: DFF<Bits<A>>,
+    bus_data: DFF<Bits<8>>,
+    bus_pending: DFF<Bit>,
+    bus_valid: DFF<Bit>,
+    written: DFF<Bit>,
+    last_written: DFF<Bits<A>>,
+    i2c_write: Signal<Local, Bit>,
+    clock: Signal<Local, Clock>,
+    ten_bit: Constant<Bit>,
+    address_high: Constant<Bits<7>>,
+    address_low: Constant<Bits<8>>,
+}
+
+impl<const A: usize> HLSI2CTarget<A> {
+    pub fn new(address: I2CTargetAddress) -> Self {
+        assert!(A <= 8);
+        // The first address byte is either the 7 bit address, or 11110
+        // followed by the top two bits of the 10 bit address
+        let (ten_bit, address_high, address_low) = match address {
+            I2CTargetAddress::SevenBit(address) => {
+                assert_eq!(address & 0x80, 0, "I2C addresses must be 7 bits");
+                (false, address, 0)
+            }
+            I2CTargetAddress::TenBit(address) => {
+                assert!(address < 0x400, "I2C 10 bit addresses must be 10 bits");
+                (true, 0x78 | (address >> 8) as u8, address as u8)
+            }
+        };
+        Self {
+            i2c: Default::default(),
+            upstream: Default::default(),
+            bridge: Bridge::new(["address", "write_data", "read_data", "status"]),
+            address: Default::default(),
+            write_data: Default::default(),
+            read_data: Default::default(),
+            status: Default::default(),
+            phy: Default::default(),
+            i2c_ram: Default::default(),
+            bus_ram: Default::default(),
+            state: Default::default(),
+            active: Default::default(),
+            matched: Default::default(),
+            pointer: Default::default(),
+            outgoing: Default::default(),
+            bus_pointer: Default::default(),
+            bus_data: Default::default(),
+            bus_pending: Default::default(),
+            bus_valid: Default::default(),
+            written: Default::default(),
+            last_written: Default::default(),
+            i2c_write: Default::default(),
+            clock: Default::default(),
+            ten_bit: Constant::new(ten_bit),
+            address_high: Constant::new(address_high.to_bits()),
+            address_low: Constant::new(address_low.to_bits()),
+        }
+    }
+}
+
+impl<const A: usize> HLSNamedPorts for HLSI2CTarget<A> {
+    fn ports(&self) -> Vec<String> {
+        self.bridge.ports()
+    }
+}
+
+impl<const A: usize> Logic for HLSI2CTarget<A> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SoCBusResponder::<16, 8>::link(&mut self.upstream, &mut self.bridge.upstream);
+        I2CBusDriver::link(&mut self.i2c, &mut self.phy.i2c);
+        self.clock.next = self.upstream.clock.val();
+        self.phy.clock.next = self.clock.val();
+        self.i2c_ram.read_clock.next = self.clock.val();
+        self.i2c_ram.write_clock.next = self.clock.val();
+        self.bus_ram.read_clock.next = self.clock.val();
+        self.bus_ram.write_clock.next = self.clock.val();
+        dff_setup!(
+            self,
+            clock,
+            state,
+            active,
+            matched,
+            pointer,
+            outgoing,
+            bus_pointer,
+            bus_data,
+            bus_pending,
+            bus_valid,
+            written,
+            last_written
+        );
+        SoCPortController::<16>::join(&mut self.bridge.nodes[0], &mut self.address.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[1], &mut self.write_data.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[2], &mut self.read_data.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[3], &mut self.status.bus);
+        // Set default values
+        self.phy.active.next = self.active.q.val();
+        self.phy.to_bus.next = 0.into();
+        self.phy.write_enable.next = false;
+        self.i2c_write.next = false;
+        self.i2c_ram.read_address.next = self.pointer.q.val();
+        self.bus_ram.read_address.next = self.bus_pointer.q.val();
+        match self.state.q.val() {
+            State::Idle => {
+                if self.phy.bus_write.val() {
+                    self.active.d.next = false;
+                    // Check if the address matches
+                    if self.phy.from_bus.val().get_bits::<7>(1) == self.address_high.val() {
+                        if !self.phy.from_bus.val().get_bit(0) {
+                            self.active.d.next = true;
+                            if self.ten_bit.val() {
+                                self.state.d.next = State::AddressLow;
+                            } else {
+                                self.state.d.next = State::GetPointer;
+                            }
+                        } else if !self.ten_bit.val() | self.matched.q.val() {
+                            self.active.d.next = true;
+                            self.state.d.next = State::ReadFetch;
+                        }
+                    }
+                }
+            }
+            State::AddressLow => {
+                if self.phy.bus_write.val() {
+                    if self.phy.from_bus.val() == self.address_low.val() {
+                        self.matched.d.next = true;
+                        self.state.d.next = State::GetPointer;
+                    } else {
+                        self.active.d.next = false;
+                        self.matched.d.next = false;
+                        self.state.d.next = State::Idle;
+                    }
+                }
+            }
+            State::GetPointer => {
+                if self.phy.bus_write.val() {
+                    self.pointer.d.next = self.phy.from_bus.val().get_bits::<A>(0);
+                    self.state.d.next = State::GetData;
+                }
+            }
+            State::GetData => {
+                if self.phy.bus_write.val() {
+                    self.i2c_write.next = true;
+                    self.pointer.d.next = self.pointer.q.val() + 1;
+                }
+            }
+            State::ReadFetch => {
+                self.state.d.next = State::ReadLoad;
+            }
+            State::ReadLoad => {
+                self.outgoing.d.next = self.i2c_ram.read_data.val();
+                self.state.d.next = State::ReadByte;
+            }
+            State::ReadByte => {
+                if self.phy.write_ok.val() {
+                    self.phy.to_bus.next = self.outgoing.q.val();
+                    self.phy.write_enable.next = true;
+                    self.state.d.next = State::ReadAck;
+                }
+            }
+            State::ReadAck => {
+                if self.phy.ack.val() {
+                    self.pointer.d.next = self.pointer.q.val() + 1;
+                    self.state.d.next = State::ReadFetch;
+                }
+                if self.phy.nack.val() {
+                    self.state.d.next = State::Idle;
+                }
+            }
+            _ => {
+                self.state.d.next = State::Idle;
+            }
+        }
+        if self.phy.stop.val() {
+            self.state.d.next = State::Idle;
+            self.active.d.next = false;
+            // A repeated start (rather than a stop) keeps a 10 bit address selected
+            if self.i2c.sda.line_state.val() {
+                self.matched.d.next = false;
+            }
+        }
+        // Both copies of the registers are written together.  Writes from
+        // the I2C master take priority over writes from the SoC bus.
+        self.bus_valid.d.next = true;
+        self.i2c_ram.write_address.next = self.bus_pointer.q.val();
+        self.i2c_ram.write_data.next = self.bus_data.q.val();
+        self.i2c_ram.write_enable.next = false;
+        if self.i2c_write.val() {
+            self.i2c_ram.write_address.next = self.pointer.q.val();
+            self.i2c_ram.write_data.next = self.phy.from_bus.val();
+            self.i2c_ram.write_enable.next = true;
+            self.last_written.d.next = self.pointer.q.val();
+            self.bus_valid.d.next = false;
+        } else if self.bus_pending.q.val() {
+            self.i2c_ram.write_enable.next = true;
+            self.bus_pending.d.next = false;
+            self.bus_pointer.d.next = self.bus_pointer.q.val() + 1;
+            self.bus_valid.d.next = false;
+        }
+        self.bus_ram.write_address.next = self.i2c_ram.write_address.val();
+        self.bus_ram.write_data.next = self.i2c_ram.write_data.val();
+        self.bus_ram.write_enable.next = self.i2c_ram.write_enable.val();
+        // The SoC bus side
+        self.address.ready.next = true;
+        if self.address.strobe_out.val() {
+            self.bus_pointer.d.next = self.address.port_out.val().get_bits::<A>(0);
+            self.bus_valid.d.next = false;
+        }
+        self.write_data.ready.next = !self.bus_pending.q.val() & !self.write_data.strobe_out.val();
+        if self.write_data.strobe_out.val() {
+            self.bus_data.d.next = self.write_data.port_out.val().get_bits::<8>(0);
+            self.bus_pending.d.next = true;
+        }
+        self.read_data.port_in.next = bit_cast::<16, 8>(self.bus_ram.read_data.val());
+        self.read_data.ready_in.next = self.bus_valid.q.val() & !self.bus_pending.q.val();
+        if self.read_data.strobe_out.val() {
+            self.bus_pointer.d.next = self.bus_pointer.q.val() + 1;
+            self.bus_valid.d.next = false;
+        }
+        self.status.port_in.next = bit_cast::<16, A>(self.last_written.q.val());
+        if self.written.q.val() {
+            self.status.port_in.next =
+                bits::<16>(0x8000) | bit_cast::<16, A>(self.last_written.q.val());
+        }
+        self.status.ready_in.next = true;
+        if self.status.strobe_out.val() {
+            self.written.d.next = false;
+        }
+        if self.i2c_write.val() {
+            self.written.d.next = true;
+        }
+    }
+}
+
+#[test]
+fn test_hls_i2c_target_is_synthesizable() {
+    let mut uut = HLSI2CTarget::<4>::new(I2CTargetAddress::TenBit(0x2A5));
+    uut.upstream.link_connect_dest();
+    uut.i2c.link_connect_dest();
+    uut.connect_all();
+    yosys_validate("hls_i2c_target", &generate_verilog(&uut)).unwrap();
+}
@@ -2,11 +2,15 @@ pub mod bidi;
 pub mod bridge;
 pub mod bus;
 pub mod controller;
+pub mod crc_stream;
 pub mod cross_fifo;
 pub mod expander;
 pub mod fifo;
 pub mod fifo_linker;
 pub mod host;
+pub mod i2c_target;
+pub mod logic_analyzer;
+pub mod logic_analyzer_host;
 pub mod miso_fifo_port;
 pub mod miso_port;
 pub mod miso_wide_port;
@@ -22,7 +26,11 @@ pub mod sdram_controller_tester;
 pub mod sdram_fifo;
 pub mod sim;
 pub mod spi;
+pub mod spi_flash;
+pub mod spi_flash_host;
 pub mod test_helpers;
+pub mod transactor;
+pub mod udp;
 
 pub trait HLSNamedPorts {
     fn ports(&self) -> Vec<String>;
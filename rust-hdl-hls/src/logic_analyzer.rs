@@ -0,0 +1,244 @@
+use crate::bridge::Bridge;
+use crate::bus::{SoCBusResponder, SoCPortController};
+use crate::miso_port::MISOPort;
+use crate::mosi_port::MOSIPort;
+use crate::mosi_wide_port::MOSIWidePort;
+use crate::HLSNamedPorts;
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+#[derive(Debug, Copy, Clone, LogicState, PartialEq)]
+enum LogicAnalyzerState {
+    Idle,
+    Armed,
+    Capturing,
+    Done,
+}
+
+// Bits of the status port
+pub const LOGIC_ANALYZER_ARMED: u16 = 1;
+pub const LOGIC_ANALYZER_TRIGGERED: u16 = 2;
+pub const LOGIC_ANALYZER_DONE: u16 = 4;
+
+// The names of the ports of the logic analyzer, in bus address order
+pub const LOGIC_ANALYZER_PORTS: [&str; 7] = [
+    "trigger_value",
+    "trigger_mask",
+    "trigger_edge",
+    "pre_trigger",
+    "control",
+    "status",
+    "data",
+];
+
+// An embedded logic analyzer.  The `probes` are sampled on every clock of
+// the bus, and stored in a circular buffer of 2^N samples.  Once armed
+// (by writing to the `control` port), the analyzer waits for the trigger
+// condition.  A sample matches the trigger when the bits selected by
+// `trigger_mask` equal those of `trigger_value`, and each bit selected by
+// `trigger_edge` has just changed to the value given in `trigger_value`
+// (i.e., a 1 selects a rising edge, and a 0 a falling edge).  With
+// both masks zero, the analyzer triggers immediately.  The capture holds
+// `pre_trigger` samples from before the trigger (at most 2^N-1, larger
+// values are limited to that), the trigger sample and the samples after
+// it, which are then read back through the `data` port, oldest sample
+// first and least significant word first.  The `status` port reports the
+// progress of the capture.
+//
+// The probes are packed into the `probes` signal in the order they are
+// given to `new`, starting with the least significant bit.  The names
+// are used by the `LogicAnalyzerHost` to label the signals in the VCD.
+#[derive(LogicBlock)]
+pub struct LogicAnalyzer<const W: usize, const N: usize> {
+    pub upstream: SoCBusResponder<16, 8>,
+    pub probes: Signal<In, Bits<W>>,
+    local_bridge: Bridge<16, 8, 7>,
+    trigger_value: MOSIWidePort<W, 16>,
+    trigger_mask: MOSIWidePort<W, 16>,
+    trigger_edge: MOSIWidePort<W, 16>,
+    pre_trigger: MOSIPort<16>,
+    control: MOSIPort<16>,
+    status: MISOPort<16>,
+    data: MISOPort<16>,
+    buffer: RAM<Bits<W>, N>,
+    state: DFF<LogicAnalyzerState>,
+    previous: DFF<Bits<W>>,
+    write_address: DFF<Bits<N>>,
+    read_address: DFF<Bits<N>>,
+    fill_count: DFF<Bits<16>>,
+    post_count: DFF<Bits<16>>,
+    word_offset: DFF<Bits<16>>,
+    read_valid: DFF<Bit>,
+    trigger: Signal<Local, Bit>,
+    pre_samples: Signal<Local, Bits<16>>,
+    depth: Constant<Bits<16>>,
+    max_pre_trigger: Constant<Bits<16>>,
+    last_word: Constant<Bits<16>>,
+    clock: Signal<Local, Clock>,
+    _probe_names: Vec<(String, usize)>,
+}
+
+impl<const W: usize, const N: usize> LogicAnalyzer<W, N> {
+    pub fn new(probes: &[(&str, usize)]) -> Self {
+        assert_eq!(W % 16, 0);
+        assert!(W > 16);
+        assert!(N < 16);
+        assert!(probes.iter().map(|x| x.1).sum::<usize>() <= W);
+        Self {
+            upstream: Default::default(),
+            probes: Default::default(),
+            local_bridge: Bridge::new(LOGIC_ANALYZER_PORTS),
+            trigger_value: Default::default(),
+            trigger_mask: Default::default(),
+            trigger_edge: Default::default(),
+            pre_trigger: Default::default(),
+            control: Default::default(),
+            status: Default::default(),
+            data: Default::default(),
+            buffer: Default::default(),
+            state: Default::default(),
+            previous: Default::default(),
+            write_address: Default::default(),
+            read_address: Default::default(),
+            fill_count: Default::default(),
+            post_count: Default::default(),
+            word_offset: Default::default(),
+            read_valid: Default::default(),
+            trigger: Default::default(),
+            pre_samples: Default::default(),
+            depth: Constant::new((1_usize << N).to_bits()),
+            max_pre_trigger: Constant::new(((1_usize << N) - 1).to_bits()),
+            last_word: Constant::new((W - 16).to_bits()),
+            clock: Default::default(),
+            _probe_names: probes.iter().map(|x| (x.0.to_string(), x.1)).collect(),
+        }
+    }
+    // The names and widths of the probes
+    pub fn probe_names(&self) -> &[(String, usize)] {
+        &self._probe_names
+    }
+}
+
+impl<const W: usize, const N: usize> HLSNamedPorts for LogicAnalyzer<W, N> {
+    fn ports(&self) -> Vec<String> {
+        self.local_bridge.ports()
+    }
+}
+
+impl<const W: usize, const N: usize> Logic for LogicAnalyzer<W, N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SoCBusResponder::<16, 8>::link(&mut self.upstream, &mut self.local_bridge.upstream);
+        self.clock.next = self.upstream.clock.val();
+        dff_setup!(
+            self,
+            clock,
+            state,
+            previous,
+            write_address,
+            read_address,
+            fill_count,
+            post_count,
+            word_offset,
+            read_valid
+        );
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[0], &mut self.trigger_value.bus);
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[1], &mut self.trigger_mask.bus);
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[2], &mut self.trigger_edge.bus);
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[3], &mut self.pre_trigger.bus);
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[4], &mut self.control.bus);
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[5], &mut self.status.bus);
+        SoCPortController::<16>::join(&mut self.local_bridge.nodes[6], &mut self.data.bus);
+        self.pre_trigger.ready.next = true;
+        self.control.ready.next = true;
+        self.status.ready_in.next = true;
+        self.status.port_in.next = 0.into();
+        // The buffer is written and read in the bus clock domain
+        self.buffer.write_clock.next = self.clock.val();
+        self.buffer.read_clock.next = self.clock.val();
+        self.buffer.write_data.next = self.probes.val();
+        self.buffer.write_address.next = self.write_address.q.val();
+        self.buffer.write_enable.next = false;
+        self.buffer.read_address.next = self.read_address.q.val();
+        self.previous.d.next = self.probes.val();
+        // Evaluate the trigger condition
+        self.trigger.next = !((self.probes.val() ^ self.trigger_value.port_out.val())
+            & (self.trigger_mask.port_out.val() | self.trigger_edge.port_out.val()))
+        .any()
+            & (((self.probes.val() ^ self.previous.q.val()) & self.trigger_edge.port_out.val())
+                == self.trigger_edge.port_out.val());
+        // Keep at least the trigger sample in the capture
+        self.pre_samples.next = self.pre_trigger.port_out.val();
+        if self.pre_samples.val() > self.max_pre_trigger.val() {
+            self.pre_samples.next = self.max_pre_trigger.val();
+        }
+        // The read data is valid one clock after the read address changes
+        self.read_valid.d.next = true;
+        self.data.ready_in.next = false;
+        self.data.port_in.next = self
+            .buffer
+            .read_data
+            .val()
+            .get_bits::<16>(self.word_offset.q.val().index());
+        match self.state.q.val() {
+            LogicAnalyzerState::Idle => {}
+            LogicAnalyzerState::Armed => {
+                // LOGIC_ANALYZER_ARMED
+                self.status.port_in.next = 1.into();
+                self.buffer.write_enable.next = true;
+                self.write_address.d.next = self.write_address.q.val() + 1;
+                if self.fill_count.q.val() < self.pre_samples.val() {
+                    self.fill_count.d.next = self.fill_count.q.val() + 1;
+                } else if self.trigger.val() {
+                    self.post_count.d.next = self.depth.val() - self.pre_samples.val() - 1;
+                    self.state.d.next = LogicAnalyzerState::Capturing;
+                }
+            }
+            LogicAnalyzerState::Capturing => {
+                // LOGIC_ANALYZER_TRIGGERED
+                self.status.port_in.next = 2.into();
+                if self.post_count.q.val().any() {
+                    self.buffer.write_enable.next = true;
+                    self.write_address.d.next = self.write_address.q.val() + 1;
+                    self.post_count.d.next = self.post_count.q.val() - 1;
+                } else {
+                    // The oldest sample is the next one that would be overwritten
+                    self.read_address.d.next = self.write_address.q.val();
+                    self.read_valid.d.next = false;
+                    self.word_offset.d.next = 0.into();
+                    self.state.d.next = LogicAnalyzerState::Done;
+                }
+            }
+            LogicAnalyzerState::Done => {
+                // LOGIC_ANALYZER_DONE
+                self.status.port_in.next = 4.into();
+                self.data.ready_in.next = self.read_valid.q.val();
+                if self.data.strobe_out.val() {
+                    if self.word_offset.q.val() == self.last_word.val() {
+                        self.word_offset.d.next = 0.into();
+                        self.read_address.d.next = self.read_address.q.val() + 1;
+                        self.read_valid.d.next = false;
+                    } else {
+                        self.word_offset.d.next = self.word_offset.q.val() + 16;
+                    }
+                }
+            }
+            _ => {
+                self.state.d.next = LogicAnalyzerState::Idle;
+            }
+        }
+        // A write to the control port (re)arms the analyzer
+        if self.control.strobe_out.val() {
+            self.fill_count.d.next = 0.into();
+            self.state.d.next = LogicAnalyzerState::Armed;
+        }
+    }
+}
+
+#[test]
+fn test_logic_analyzer_synthesizes() {
+    let mut uut = LogicAnalyzer::<32, 8>::new(&[("count", 8), ("strobe", 1)]);
+    uut.probes.connect();
+    uut.connect_all();
+    yosys_validate("logic_analyzer", &generate_verilog(&uut)).unwrap();
+}
@@ -0,0 +1,247 @@
+use crate::controller::ControllerLink;
+use crate::logic_analyzer::{LogicAnalyzer, LOGIC_ANALYZER_DONE, LOGIC_ANALYZER_PORTS};
+use std::io::Write;
+
+// The largest number of words requested from the controller in a single
+// read command.
+const READ_CHUNK: usize = 1024;
+
+fn probe_location(probes: &[(String, usize)], name: &str) -> (usize, usize) {
+    let mut offset = 0;
+    for (probe, width) in probes {
+        if probe == name {
+            return (offset, *width);
+        }
+        offset += width;
+    }
+    panic!("No probe named {} in the logic analyzer", name)
+}
+
+fn probe_mask(width: usize) -> u128 {
+    if width >= 128 {
+        u128::MAX
+    } else {
+        (1_u128 << width) - 1
+    }
+}
+
+// A trigger condition for a `LogicAnalyzer`, built up from conditions on
+// the named probes.  Conditions on different probes must all hold for
+// the analyzer to trigger.
+#[derive(Clone, Debug)]
+pub struct LogicAnalyzerTrigger {
+    pub value: u128,
+    pub mask: u128,
+    pub edge: u128,
+    pub pre_trigger: usize,
+    probes: Vec<(String, usize)>,
+}
+
+impl LogicAnalyzerTrigger {
+    // Trigger when the probe has the given value
+    pub fn equals(mut self, name: &str, value: u128) -> Self {
+        let (offset, width) = probe_location(&self.probes, name);
+        let mask = probe_mask(width) << offset;
+        self.value = (self.value & !mask) | ((value << offset) & mask);
+        self.mask |= mask;
+        self
+    }
+    // Trigger when the (single bit) probe goes from low to high
+    pub fn rising(self, name: &str) -> Self {
+        self.edge_to(name, true)
+    }
+    // Trigger when the (single bit) probe goes from high to low
+    pub fn falling(self, name: &str) -> Self {
+        self.edge_to(name, false)
+    }
+    // The number of samples to keep from before the trigger
+    pub fn pre_trigger(mut self, samples: usize) -> Self {
+        self.pre_trigger = samples;
+        self
+    }
+    fn edge_to(mut self, name: &str, level: bool) -> Self {
+        let (offset, width) = probe_location(&self.probes, name);
+        assert_eq!(width, 1, "Edge triggers require a single bit probe");
+        let bit = 1_u128 << offset;
+        self.value = if level {
+            self.value | bit
+        } else {
+            self.value & !bit
+        };
+        self.edge |= bit;
+        self
+    }
+}
+
+// The samples captured by a `LogicAnalyzer`, oldest first.
+#[derive(Clone, Debug)]
+pub struct LogicAnalyzerCapture {
+    pub samples: Vec<u128>,
+    pub trigger_index: usize,
+    probes: Vec<(String, usize)>,
+}
+
+impl LogicAnalyzerCapture {
+    // The value of the named probe in the given sample
+    pub fn probe(&self, sample: usize, name: &str) -> u128 {
+        let (offset, width) = probe_location(&self.probes, name);
+        (self.samples[sample] >> offset) & probe_mask(width)
+    }
+    // Write the capture as a VCD file, with one sample every `sample_period_ps`
+    // picoseconds.  Each probe appears under its own name, along with a
+    // `trigger` signal that marks the trigger sample.
+    pub fn write_vcd<W: Write>(&self, w: W, sample_period_ps: u64) -> std::io::Result<()> {
+        let mut vcd = vcd::Writer::new(w);
+        vcd.timescale(1, vcd::TimescaleUnit::PS)?;
+        vcd.add_module("logic_analyzer")?;
+        let trigger = vcd.add_wire(1, "trigger")?;
+        let mut ids = vec![];
+        for (name, width) in &self.probes {
+            ids.push(vcd.add_wire(*width as u32, name)?);
+        }
+        vcd.upscope()?;
+        vcd.enddefinitions()?;
+        let mut last: Option<u128> = None;
+        for (ndx, sample) in self.samples.iter().enumerate() {
+            vcd.timestamp(ndx as u64 * sample_period_ps)?;
+            if ndx == 0 || ndx == self.trigger_index || ndx == self.trigger_index + 1 {
+                vcd.change_scalar(trigger, ndx == self.trigger_index)?;
+            }
+            let mut offset = 0;
+            for ((_, width), id) in self.probes.iter().zip(&ids) {
+                let mask = probe_mask(*width);
+                let value = (sample >> offset) & mask;
+                if last.map(|x| (x >> offset) & mask) != Some(value) {
+                    if *width == 1 {
+                        vcd.change_scalar(*id, value != 0)?;
+                    } else {
+                        let bits = (0..*width)
+                            .rev()
+                            .map(|bit| ((value >> bit) & 1 != 0).into())
+                            .collect::<Vec<vcd::Value>>();
+                        vcd.change_vector(*id, &bits)?;
+                    }
+                }
+                offset += width;
+            }
+            last = Some(*sample);
+        }
+        vcd.timestamp(self.samples.len() as u64 * sample_period_ps)?;
+        Ok(())
+    }
+}
+
+// The host side driver for a `LogicAnalyzer`.  It configures and arms
+// the analyzer, and reads back the capture, through any `ControllerLink`.
+#[derive(Clone, Debug)]
+pub struct LogicAnalyzerHost {
+    base_address: u8,
+    width: usize,
+    depth: usize,
+    probes: Vec<(String, usize)>,
+}
+
+impl LogicAnalyzerHost {
+    // `base_address` is the bus address of the first port of the analyzer
+    pub fn new<const W: usize, const N: usize>(
+        analyzer: &LogicAnalyzer<W, N>,
+        base_address: u8,
+    ) -> Self {
+        assert!(W <= 128);
+        Self {
+            base_address,
+            width: W,
+            depth: 1 << N,
+            probes: analyzer.probe_names().to_vec(),
+        }
+    }
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    // An empty trigger condition, which triggers immediately
+    pub fn trigger(&self) -> LogicAnalyzerTrigger {
+        LogicAnalyzerTrigger {
+            value: 0,
+            mask: 0,
+            edge: 0,
+            pre_trigger: 0,
+            probes: self.probes.clone(),
+        }
+    }
+    fn address(&self, port: &str) -> u8 {
+        let ndx = LOGIC_ANALYZER_PORTS
+            .iter()
+            .position(|x| *x == port)
+            .unwrap();
+        self.base_address + ndx as u8
+    }
+    // The wide ports are written most significant word first
+    fn wide_words(&self, value: u128) -> Vec<u16> {
+        (0..self.width / 16)
+            .rev()
+            .map(|ndx| (value >> (ndx * 16)) as u16)
+            .collect()
+    }
+    // Load the trigger condition, and arm the analyzer
+    pub fn arm<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        trigger: &LogicAnalyzerTrigger,
+    ) -> Result<(), L::Error> {
+        assert!(trigger.pre_trigger < self.depth);
+        link.write(
+            self.address("trigger_value"),
+            &self.wide_words(trigger.value),
+        )?;
+        link.write(self.address("trigger_mask"), &self.wide_words(trigger.mask))?;
+        link.write(self.address("trigger_edge"), &self.wide_words(trigger.edge))?;
+        link.write(self.address("pre_trigger"), &[trigger.pre_trigger as u16])?;
+        link.write(self.address("control"), &[1])
+    }
+    pub fn status<L: ControllerLink>(&self, link: &mut L) -> Result<u16, L::Error> {
+        Ok(link.read(self.address("status"), 1)?[0])
+    }
+    // Poll the status of the analyzer (up to `polls` times) until the
+    // capture is complete.  Returns false if it did not complete.
+    pub fn wait_for_capture<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        polls: usize,
+    ) -> Result<bool, L::Error> {
+        for _ in 0..polls {
+            if self.status(link)? & LOGIC_ANALYZER_DONE != 0 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+    // Read back a completed capture.  The buffer can only be read once
+    // per capture.
+    pub fn read_capture<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        trigger: &LogicAnalyzerTrigger,
+    ) -> Result<LogicAnalyzerCapture, L::Error> {
+        let words_per_sample = self.width / 16;
+        let mut words = vec![];
+        let total = self.depth * words_per_sample;
+        while words.len() < total {
+            let count = READ_CHUNK.min(total - words.len());
+            words.extend(link.read(self.address("data"), count)?);
+        }
+        let samples = words
+            .chunks(words_per_sample)
+            .map(|sample| {
+                sample
+                    .iter()
+                    .rev()
+                    .fold(0_u128, |acc, word| (acc << 16) | (*word as u128))
+            })
+            .collect();
+        Ok(LogicAnalyzerCapture {
+            samples,
+            trigger_index: trigger.pre_trigger,
+            probes: self.probes.clone(),
+        })
+    }
+}
@@ -6,7 +6,8 @@ pub use crate::bus::{
 };
 pub use crate::bus_address_strobe;
 pub use crate::bus_write_strobe;
-pub use crate::controller::BaseController;
+pub use crate::controller::{BaseController, ControllerLink};
+pub use crate::crc_stream::CRCStream;
 pub use crate::cross_fifo::{CrossNarrow, CrossWiden};
 pub use crate::expander::Expander;
 pub use crate::fifo::{AsyncFIFO, SyncFIFO};
@@ -24,6 +25,11 @@ pub use crate::hls_host_ping;
 pub use crate::hls_host_put_word;
 pub use crate::hls_host_write;
 pub use crate::host::Host;
+pub use crate::i2c_target::{HLSI2CTarget, I2CTargetAddress, I2C_TARGET_WRITTEN};
+pub use crate::logic_analyzer::LogicAnalyzer;
+pub use crate::logic_analyzer_host::{
+    LogicAnalyzerCapture, LogicAnalyzerHost, LogicAnalyzerTrigger,
+};
 pub use crate::miso_fifo_port::MISOFIFOPort;
 pub use crate::miso_port::MISOPort;
 pub use crate::miso_wide_port::MISOWidePort;
@@ -39,5 +45,9 @@ pub use crate::sdram_fifo::SDRAMFIFO;
 pub use crate::spi::HLSSPIMaster;
 pub use crate::spi::HLSSPIMasterDynamicMode;
 pub use crate::spi::{HLSSPIMuxMasters, HLSSPIMuxSlaves};
+pub use crate::spi_flash::HLSSPIFlash;
+pub use crate::spi_flash_host::{SPIFlashError, SPIFlashHost};
 pub use crate::test_helpers::*;
+pub use crate::transactor::{FIFOReadDriver, FIFOWriteDriver, SoCBusDriver};
+pub use crate::udp::{UDPConfig, UDPHost, UDPStack};
 pub use crate::HLSNamedPorts;
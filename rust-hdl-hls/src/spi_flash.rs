@@ -0,0 +1,279 @@
+use crate::bridge::Bridge;
+use crate::bus::{SoCBusResponder, SoCPortController};
+use crate::miso_port::MISOPort;
+use crate::mosi_port::MOSIPort;
+use crate::mosi_wide_port::MOSIWidePort;
+use crate::HLSNamedPorts;
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+// Commands written to the control port
+pub const SPI_FLASH_READ_ID: u16 = 1;
+pub const SPI_FLASH_READ_STATUS: u16 = 2;
+pub const SPI_FLASH_SECTOR_ERASE: u16 = 3;
+pub const SPI_FLASH_BEGIN_PROGRAM: u16 = 4;
+pub const SPI_FLASH_END_PROGRAM: u16 = 5;
+pub const SPI_FLASH_BEGIN_READ: u16 = 6;
+pub const SPI_FLASH_END_READ: u16 = 7;
+
+// Set in the status port while a command is still in progress
+pub const SPI_FLASH_BUSY: u16 = 0x8000;
+
+// The names of the ports of the flash controller, in bus address order
+pub const SPI_FLASH_PORTS: [&str; 6] = [
+    "address",
+    "control",
+    "program_data",
+    "read_data",
+    "jedec_id",
+    "status",
+];
+
+// A SPI NOR flash on the SoC bus, using a `SPIFlashController`.  The
+// `address` (2 words, most significant first) is latched when a command
+// is written to the `control` port.  Each word written to `program_data`
+// programs two bytes (most significant byte first) of the page program
+// started with `SPI_FLASH_BEGIN_PROGRAM`.  After `SPI_FLASH_BEGIN_READ`,
+// the flash is streamed out through the `read_data` port, two bytes per
+// word, with the next word fetched as soon as the last one has been read.
+// Any other command ends the read.  The `status` port holds the
+// last status register read from the flash, with `SPI_FLASH_BUSY` set
+// while a command (including the erase or program cycle that follows
+// it) is still in progress.  The `jedec_id` port returns the ID loaded
+// by the `SPI_FLASH_READ_ID` command, as two words (most significant
+// first).  A flash built with `with_quad_read` reads with the fast read
+// quad output command (see `SPIFlashController::with_quad_read`).
+#[derive(LogicBlock)]
+pub struct HLSSPIFlash {
+    pub spi: QSPIWiresMaster,
+    pub upstream: SoCBusResponder<16, 8>,
+    bridge: Bridge<16, 8, 6>,
+    address: MOSIWidePort<32, 16>,
+    control: MOSIPort<16>,
+    program_data: MOSIPort<16>,
+    read_data: MISOPort<16>,
+    jedec_id: MISOPort<16>,
+    status: MISOPort<16>,
+    core: SPIFlashController,
+    cmd: DFF<Bits<16>>,
+    cmd_address: DFF<Bits<24>>,
+    cmd_pending: DFF<Bit>,
+    write_word: DFF<Bits<16>>,
+    hi_pending: DFF<Bit>,
+    lo_pending: DFF<Bit>,
+    reading: DFF<Bit>,
+    fetch_address: DFF<Bits<24>>,
+    read_word: DFF<Bits<16>>,
+    hi_valid: DFF<Bit>,
+    word_valid: DFF<Bit>,
+    id_low: DFF<Bit>,
+    pending: Signal<Local, Bit>,
+    clock: Signal<Local, Clock>,
+}
+
+impl HLSSPIFlash {
+    pub fn new(config: SPIConfig) -> Self {
+        Self::build(SPIFlashController::new(config))
+    }
+    pub fn with_quad_read(config: SPIConfig) -> Self {
+        Self::build(SPIFlashController::with_quad_read(config))
+    }
+    fn build(core: SPIFlashController) -> Self {
+        Self {
+            spi: Default::default(),
+            upstream: Default::default(),
+            bridge: Bridge::new(SPI_FLASH_PORTS),
+            address: Default::default(),
+            control: Default::default(),
+            program_data: Default::default(),
+            read_data: Default::default(),
+            jedec_id: Default::default(),
+            status: Default::default(),
+            core,
+            cmd: Default::default(),
+            cmd_address: Default::default(),
+            cmd_pending: Default::default(),
+            write_word: Default::default(),
+            hi_pending: Default::default(),
+            lo_pending: Default::default(),
+            reading: Default::default(),
+            fetch_address: Default::default(),
+            read_word: Default::default(),
+            hi_valid: Default::default(),
+            word_valid: Default::default(),
+            id_low: Default::default(),
+            pending: Default::default(),
+            clock: Default::default(),
+        }
+    }
+}
+
+impl HLSNamedPorts for HLSSPIFlash {
+    fn ports(&self) -> Vec<String> {
+        self.bridge.ports()
+    }
+}
+
+impl Logic for HLSSPIFlash {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SoCBusResponder::<16, 8>::link(&mut self.upstream, &mut self.bridge.upstream);
+        self.clock.next = self.upstream.clock.val();
+        self.core.clock.next = self.clock.val();
+        dff_setup!(
+            self,
+            clock,
+            cmd,
+            cmd_address,
+            cmd_pending,
+            write_word,
+            hi_pending,
+            lo_pending,
+            reading,
+            fetch_address,
+            read_word,
+            hi_valid,
+            word_valid,
+            id_low
+        );
+        SoCPortController::<16>::join(&mut self.bridge.nodes[0], &mut self.address.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[1], &mut self.control.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[2], &mut self.program_data.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[3], &mut self.read_data.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[4], &mut self.jedec_id.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[5], &mut self.status.bus);
+        QSPIWiresMaster::link(&mut self.spi, &mut self.core.wires);
+        // Commands and program data are issued in the order they are written,
+        // so hold off the bus until the last one has been taken by the core
+        self.pending.next = self.cmd_pending.q.val()
+            | self.hi_pending.q.val()
+            | self.lo_pending.q.val()
+            | self.control.strobe_out.val()
+            | self.program_data.strobe_out.val();
+        self.control.ready.next = !self.pending.val();
+        self.program_data.ready.next = !self.pending.val();
+        self.status.ready_in.next = true;
+        self.status.port_in.next = bit_cast::<16, 8>(self.core.status.val());
+        if self.pending.val() | self.core.busy.val() {
+            self.status.port_in.next =
+                bits::<16>(0x8000) | bit_cast::<16, 8>(self.core.status.val());
+        }
+        // The ID is read as two words, most significant first
+        self.jedec_id.ready_in.next = true;
+        self.jedec_id.port_in.next = bit_cast::<16, 8>(self.core.jedec_id.val().get_bits::<8>(16));
+        if self.id_low.q.val() {
+            self.jedec_id.port_in.next = self.core.jedec_id.val().get_bits::<16>(0);
+        }
+        if self.jedec_id.strobe_out.val() {
+            self.id_low.d.next = !self.id_low.q.val();
+        }
+        self.read_data.port_in.next = self.read_word.q.val();
+        self.read_data.ready_in.next = self.word_valid.q.val();
+        if self.read_data.strobe_out.val() {
+            self.word_valid.d.next = false;
+        }
+        // Latch the incoming commands and program data
+        if self.control.strobe_out.val() {
+            self.cmd.d.next = self.control.port_out.val();
+            self.cmd_address.d.next = self.address.port_out.val().get_bits::<24>(0);
+            self.cmd_pending.d.next = true;
+        }
+        if self.program_data.strobe_out.val() {
+            self.write_word.d.next = self.program_data.port_out.val();
+            self.hi_pending.d.next = true;
+            self.lo_pending.d.next = true;
+        }
+        // Feed the core
+        self.core.run.next = false;
+        self.core.cmd.next = SPIFlashCmd::Noop;
+        self.core.address.next = self.cmd_address.q.val();
+        self.core.write_data_in.next = self.write_word.q.val().get_bits::<8>(0);
+        if !self.core.busy.val() {
+            if self.hi_pending.q.val() {
+                self.core.cmd.next = SPIFlashCmd::Write;
+                self.core.write_data_in.next = self.write_word.q.val().get_bits::<8>(8);
+                self.core.run.next = true;
+                self.hi_pending.d.next = false;
+            } else if self.lo_pending.q.val() {
+                self.core.cmd.next = SPIFlashCmd::Write;
+                self.core.run.next = true;
+                self.lo_pending.d.next = false;
+            } else if self.cmd_pending.q.val() {
+                self.cmd_pending.d.next = false;
+                self.reading.d.next = false;
+                if self.cmd.q.val() == 1 {
+                    // SPI_FLASH_READ_ID
+                    self.core.cmd.next = SPIFlashCmd::ReadId;
+                    self.core.run.next = true;
+                    self.id_low.d.next = false;
+                }
+                if self.cmd.q.val() == 2 {
+                    // SPI_FLASH_READ_STATUS
+                    self.core.cmd.next = SPIFlashCmd::ReadStatus;
+                    self.core.run.next = true;
+                }
+                if self.cmd.q.val() == 3 {
+                    // SPI_FLASH_SECTOR_ERASE
+                    self.core.cmd.next = SPIFlashCmd::SectorErase;
+                    self.core.run.next = true;
+                }
+                if self.cmd.q.val() == 4 {
+                    // SPI_FLASH_BEGIN_PROGRAM
+                    self.core.cmd.next = SPIFlashCmd::BeginProgram;
+                    self.core.run.next = true;
+                }
+                if self.cmd.q.val() == 5 {
+                    // SPI_FLASH_END_PROGRAM
+                    self.core.cmd.next = SPIFlashCmd::EndProgram;
+                    self.core.run.next = true;
+                }
+                if self.cmd.q.val() == 6 {
+                    // SPI_FLASH_BEGIN_READ
+                    self.reading.d.next = true;
+                    self.fetch_address.d.next = self.cmd_address.q.val();
+                    self.hi_valid.d.next = false;
+                    self.word_valid.d.next = false;
+                }
+                if self.cmd.q.val() == 7 {
+                    // SPI_FLASH_END_READ
+                    self.core.cmd.next = SPIFlashCmd::EndRead;
+                    self.core.run.next = true;
+                }
+            } else if self.reading.q.val() & !self.word_valid.q.val() {
+                self.core.cmd.next = SPIFlashCmd::Fetch;
+                self.core.address.next = self.fetch_address.q.val();
+                self.core.run.next = true;
+            }
+        }
+        // Collect the fetched bytes into words
+        if self.core.read_valid.val() {
+            self.fetch_address.d.next = self.fetch_address.q.val() + 1;
+            if self.hi_valid.q.val() {
+                self.read_word.d.next =
+                    self.read_word.q.val() | bit_cast::<16, 8>(self.core.read_data_out.val());
+                self.hi_valid.d.next = false;
+                self.word_valid.d.next = true;
+            } else {
+                self.read_word.d.next = bit_cast::<16, 8>(self.core.read_data_out.val()) << 8;
+                self.hi_valid.d.next = true;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_hls_spi_flash_is_synthesizable() {
+    let spi_config = SPIConfig {
+        clock_speed: 48_000_000,
+        cs_off: true,
+        mosi_off: true,
+        speed_hz: 1_000_000,
+        cpha: true,
+        cpol: true,
+    };
+    let mut uut = HLSSPIFlash::new(spi_config);
+    uut.upstream.link_connect_dest();
+    uut.spi.link_connect_dest();
+    uut.connect_all();
+    yosys_validate("hls_spi_flash", &generate_verilog(&uut)).unwrap();
+}
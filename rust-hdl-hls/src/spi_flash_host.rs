@@ -0,0 +1,201 @@
+use crate::controller::ControllerLink;
+use crate::spi_flash::{
+    SPI_FLASH_BEGIN_PROGRAM, SPI_FLASH_BEGIN_READ, SPI_FLASH_BUSY, SPI_FLASH_END_PROGRAM,
+    SPI_FLASH_END_READ, SPI_FLASH_PORTS, SPI_FLASH_READ_ID, SPI_FLASH_READ_STATUS,
+    SPI_FLASH_SECTOR_ERASE,
+};
+use std::path::Path;
+
+// The largest number of words requested from the controller in a single
+// read command.
+const READ_CHUNK: usize = 1024;
+
+pub const SPI_FLASH_PAGE_SIZE: usize = 256;
+pub const SPI_FLASH_SECTOR_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum SPIFlashError<E> {
+    // The link to the controller failed
+    Link(E),
+    // The image file could not be read
+    Io(std::io::Error),
+    // The flash stayed busy for longer than the allowed number of polls
+    Timeout,
+    // The flash did not read back what was written, starting at this address
+    Verify { address: usize },
+}
+
+// The host side driver for a `HLSSPIFlash`, through any `ControllerLink`.
+// Besides the individual flash commands, it can write a complete image
+// (e.g., a bitstream `.bin` file) to the flash: the sectors that hold the
+// image are erased, the image is programmed a page at a time, and then
+// read back to verify it.
+#[derive(Clone, Debug)]
+pub struct SPIFlashHost {
+    base_address: u8,
+    max_polls: usize,
+}
+
+impl SPIFlashHost {
+    // `base_address` is the bus address of the first port of the flash
+    pub fn new(base_address: u8) -> Self {
+        Self {
+            base_address,
+            max_polls: 1_000_000,
+        }
+    }
+    // The number of times to poll the status before giving up on a command
+    pub fn max_polls(mut self, polls: usize) -> Self {
+        self.max_polls = polls;
+        self
+    }
+    fn address(&self, port: &str) -> u8 {
+        let ndx = SPI_FLASH_PORTS.iter().position(|x| *x == port).unwrap();
+        self.base_address + ndx as u8
+    }
+    fn command<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        cmd: u16,
+        address: usize,
+    ) -> Result<(), SPIFlashError<L::Error>> {
+        link.write(
+            self.address("address"),
+            &[(address >> 16) as u16, address as u16],
+        )
+        .map_err(SPIFlashError::Link)?;
+        link.write(self.address("control"), &[cmd])
+            .map_err(SPIFlashError::Link)
+    }
+    // Poll the status port until the last command has finished, and
+    // return the last status register read from the flash.
+    pub fn wait_ready<L: ControllerLink>(
+        &self,
+        link: &mut L,
+    ) -> Result<u8, SPIFlashError<L::Error>> {
+        for _ in 0..self.max_polls {
+            let status = link
+                .read(self.address("status"), 1)
+                .map_err(SPIFlashError::Link)?[0];
+            if status & SPI_FLASH_BUSY == 0 {
+                return Ok(status as u8);
+            }
+        }
+        Err(SPIFlashError::Timeout)
+    }
+    // The JEDEC ID of the flash (manufacturer, memory type and capacity)
+    pub fn jedec_id<L: ControllerLink>(
+        &self,
+        link: &mut L,
+    ) -> Result<u32, SPIFlashError<L::Error>> {
+        self.command(link, SPI_FLASH_READ_ID, 0)?;
+        self.wait_ready(link)?;
+        let id = link
+            .read(self.address("jedec_id"), 2)
+            .map_err(SPIFlashError::Link)?;
+        Ok(((id[0] as u32) << 16) | (id[1] as u32))
+    }
+    // The status register of the flash
+    pub fn status<L: ControllerLink>(&self, link: &mut L) -> Result<u8, SPIFlashError<L::Error>> {
+        self.command(link, SPI_FLASH_READ_STATUS, 0)?;
+        self.wait_ready(link)
+    }
+    // Erase the sector that holds `address`
+    pub fn erase_sector<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        address: usize,
+    ) -> Result<(), SPIFlashError<L::Error>> {
+        self.command(link, SPI_FLASH_SECTOR_ERASE, address)?;
+        self.wait_ready(link)?;
+        Ok(())
+    }
+    // Program `data` starting at `address`.  The data must fit in the page
+    // that holds `address`.  An odd length is padded with an erased (0xFF)
+    // byte, which leaves the flash unchanged.
+    pub fn program_page<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        address: usize,
+        data: &[u8],
+    ) -> Result<(), SPIFlashError<L::Error>> {
+        assert!(address % SPI_FLASH_PAGE_SIZE + data.len() <= SPI_FLASH_PAGE_SIZE);
+        let words = data
+            .chunks(2)
+            .map(|x| ((x[0] as u16) << 8) | (*x.get(1).unwrap_or(&0xFF) as u16))
+            .collect::<Vec<_>>();
+        self.command(link, SPI_FLASH_BEGIN_PROGRAM, address)?;
+        link.write(self.address("program_data"), &words)
+            .map_err(SPIFlashError::Link)?;
+        self.command(link, SPI_FLASH_END_PROGRAM, address)?;
+        self.wait_ready(link)?;
+        Ok(())
+    }
+    // Read `len` bytes starting at `address`
+    pub fn read<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        address: usize,
+        len: usize,
+    ) -> Result<Vec<u8>, SPIFlashError<L::Error>> {
+        self.command(link, SPI_FLASH_BEGIN_READ, address)?;
+        let total = len.div_ceil(2);
+        let mut words = vec![];
+        while words.len() < total {
+            let count = READ_CHUNK.min(total - words.len());
+            words.extend(
+                link.read(self.address("read_data"), count)
+                    .map_err(SPIFlashError::Link)?,
+            );
+        }
+        self.command(link, SPI_FLASH_END_READ, 0)?;
+        let mut bytes = words
+            .iter()
+            .flat_map(|x| [(x >> 8) as u8, *x as u8])
+            .collect::<Vec<_>>();
+        bytes.truncate(len);
+        Ok(bytes)
+    }
+    // Erase, program and verify `image` starting at `address`.  Everything
+    // else in the sectors that hold the image is erased.
+    pub fn write_image<L: ControllerLink>(
+        &self,
+        link: &mut L,
+        address: usize,
+        image: &[u8],
+    ) -> Result<(), SPIFlashError<L::Error>> {
+        let end = address + image.len();
+        let first_sector = address / SPI_FLASH_SECTOR_SIZE;
+        let last_sector = end.div_ceil(SPI_FLASH_SECTOR_SIZE);
+        for sector in first_sector..last_sector {
+            self.erase_sector(link, sector * SPI_FLASH_SECTOR_SIZE)?;
+        }
+        let mut page_start = address;
+        while page_start < end {
+            let page_end = ((page_start / SPI_FLASH_PAGE_SIZE + 1) * SPI_FLASH_PAGE_SIZE).min(end);
+            self.program_page(
+                link,
+                page_start,
+                &image[page_start - address..page_end - address],
+            )?;
+            page_start = page_end;
+        }
+        let readback = self.read(link, address, image.len())?;
+        match readback.iter().zip(image).position(|(x, y)| x != y) {
+            Some(offset) => Err(SPIFlashError::Verify {
+                address: address + offset,
+            }),
+            None => Ok(()),
+        }
+    }
+    // Write a binary file (such as a bitstream) to the flash at `address`
+    pub fn write_bin<L: ControllerLink, P: AsRef<Path>>(
+        &self,
+        link: &mut L,
+        address: usize,
+        path: P,
+    ) -> Result<(), SPIFlashError<L::Error>> {
+        let image = std::fs::read(path).map_err(SPIFlashError::Io)?;
+        self.write_image(link, address, &image)
+    }
+}
@@ -0,0 +1,226 @@
+// Transaction level drivers for testbenches.  These replace
+// the `hls_fifo_write_lazy!` style macros with structs that can carry
+// state (like a seeded random generator for inserting idle cycles), and
+// that deal in whole transactions (a word written to a FIFO, a read from
+// the SoC bus) that can be checked with a `Scoreboard`.
+//
+// Each one is built from a function that selects the interface from the
+// circuit, and a function that reads the clock the interface runs on.
+// They are used from within a testbench, starting on the rising edge
+// of the clock, e.g.:
+//
+//    let mut writer = FIFOWriteDriver::new(|x: &mut Test| &mut x.input, |x| x.clock.val());
+//    x = writer.write(&mut sim, x, 0x42.into())?;
+use crate::bus::{FIFOReadController, FIFOWriteController, SoCBusController};
+use rust_hdl_core::prelude::*;
+use rust_hdl_core::stimulus::SimRng;
+
+pub type ClockFn<T> = fn(&T) -> Clock;
+
+// Wait for the next rising edge of the clock
+fn next_rising_edge<T: Send + 'static>(
+    sim: &mut Sim<T>,
+    x: Box<T>,
+    clock: ClockFn<T>,
+) -> Result<Box<T>, SimError> {
+    let x = sim.watch(move |x| !clock(x).clk, x)?;
+    sim.watch(move |x| clock(x).clk, x)
+}
+
+// Random idle cycles inserted between transactions
+#[derive(Clone, Debug)]
+struct Idler {
+    rng: SimRng,
+    probability: f64,
+    max_cycles: u64,
+}
+
+impl Idler {
+    fn new(rng: SimRng, probability: f64, max_cycles: u64) -> Self {
+        assert!(max_cycles >= 1, "An idle period must be at least one cycle");
+        Self {
+            rng,
+            probability,
+            max_cycles,
+        }
+    }
+    fn idle<T: Send + 'static>(
+        idler: &mut Option<Idler>,
+        sim: &mut Sim<T>,
+        mut x: Box<T>,
+        clock: ClockFn<T>,
+    ) -> Result<Box<T>, SimError> {
+        if let Some(idler) = idler {
+            if idler.rng.chance(idler.probability) {
+                for _ in 0..idler.rng.range(1, idler.max_cycles) {
+                    x = next_rising_edge(sim, x, clock)?;
+                }
+            }
+        }
+        Ok(x)
+    }
+}
+
+// Writes transactions into a FIFO through its write interface
+pub struct FIFOWriteDriver<T, D: Synth> {
+    fifo: fn(&mut T) -> &mut FIFOWriteController<D>,
+    clock: ClockFn<T>,
+    idler: Option<Idler>,
+}
+
+impl<T: Send + 'static, D: Synth> FIFOWriteDriver<T, D> {
+    pub fn new(fifo: fn(&mut T) -> &mut FIFOWriteController<D>, clock: ClockFn<T>) -> Self {
+        Self {
+            fifo,
+            clock,
+            idler: None,
+        }
+    }
+    // After each write, idle for 1 to `max_cycles` clocks with the given probability
+    pub fn with_idle(mut self, rng: SimRng, probability: f64, max_cycles: u64) -> Self {
+        self.idler = Some(Idler::new(rng, probability, max_cycles));
+        self
+    }
+    // Write a single value, waiting for the FIFO to have space for it
+    pub fn write(&mut self, sim: &mut Sim<T>, mut x: Box<T>, data: D) -> Result<Box<T>, SimError> {
+        while (self.fifo)(&mut x).full.val() {
+            x = next_rising_edge(sim, x, self.clock)?;
+        }
+        let fifo = (self.fifo)(&mut x);
+        fifo.data.next = data;
+        fifo.write.next = true;
+        x = next_rising_edge(sim, x, self.clock)?;
+        (self.fifo)(&mut x).write.next = false;
+        Idler::idle(&mut self.idler, sim, x, self.clock)
+    }
+    pub fn write_all<I: IntoIterator<Item = D>>(
+        &mut self,
+        sim: &mut Sim<T>,
+        mut x: Box<T>,
+        data: I,
+    ) -> Result<Box<T>, SimError> {
+        for item in data {
+            x = self.write(sim, x, item)?;
+        }
+        Ok(x)
+    }
+}
+
+// Reads transactions out of a FIFO by driving its read interface
+pub struct FIFOReadDriver<T, D: Synth> {
+    fifo: fn(&mut T) -> &mut FIFOReadController<D>,
+    clock: ClockFn<T>,
+    idler: Option<Idler>,
+}
+
+impl<T: Send + 'static, D: Synth> FIFOReadDriver<T, D> {
+    pub fn new(fifo: fn(&mut T) -> &mut FIFOReadController<D>, clock: ClockFn<T>) -> Self {
+        Self {
+            fifo,
+            clock,
+            idler: None,
+        }
+    }
+    // After each read, idle for 1 to `max_cycles` clocks with the given probability
+    pub fn with_idle(mut self, rng: SimRng, probability: f64, max_cycles: u64) -> Self {
+        self.idler = Some(Idler::new(rng, probability, max_cycles));
+        self
+    }
+    // Read a single value, waiting for the FIFO to have one
+    pub fn read(&mut self, sim: &mut Sim<T>, mut x: Box<T>) -> Result<(Box<T>, D), SimError> {
+        while (self.fifo)(&mut x).empty.val() {
+            x = next_rising_edge(sim, x, self.clock)?;
+        }
+        let fifo = (self.fifo)(&mut x);
+        let data = fifo.data.val();
+        fifo.read.next = true;
+        x = next_rising_edge(sim, x, self.clock)?;
+        (self.fifo)(&mut x).read.next = false;
+        Ok((Idler::idle(&mut self.idler, sim, x, self.clock)?, data))
+    }
+    pub fn read_n(
+        &mut self,
+        sim: &mut Sim<T>,
+        mut x: Box<T>,
+        count: usize,
+    ) -> Result<(Box<T>, Vec<D>), SimError> {
+        let mut ret = vec![];
+        for _ in 0..count {
+            let (y, data) = self.read(sim, x)?;
+            x = y;
+            ret.push(data);
+        }
+        Ok((x, ret))
+    }
+}
+
+// Drives read and write transactions on an SoC bus.  The clock of the bus
+// is driven by the testbench, and is given by `clock`.
+pub struct SoCBusDriver<T, const D: usize, const A: usize> {
+    bus: fn(&mut T) -> &mut SoCBusController<D, A>,
+    clock: ClockFn<T>,
+}
+
+impl<T: Send + 'static, const D: usize, const A: usize> SoCBusDriver<T, D, A> {
+    pub fn new(bus: fn(&mut T) -> &mut SoCBusController<D, A>, clock: ClockFn<T>) -> Self {
+        Self { bus, clock }
+    }
+    fn select(
+        &self,
+        sim: &mut Sim<T>,
+        mut x: Box<T>,
+        address: Bits<A>,
+    ) -> Result<Box<T>, SimError> {
+        let bus = (self.bus)(&mut x);
+        bus.address.next = address;
+        bus.address_strobe.next = true;
+        x = next_rising_edge(sim, x, self.clock)?;
+        (self.bus)(&mut x).address_strobe.next = false;
+        Ok(x)
+    }
+    fn wait_ready(&self, sim: &mut Sim<T>, mut x: Box<T>) -> Result<Box<T>, SimError> {
+        while !(self.bus)(&mut x).ready.val() {
+            x = next_rising_edge(sim, x, self.clock)?;
+        }
+        Ok(x)
+    }
+    // Write the words to the port at the given address
+    pub fn write(
+        &self,
+        sim: &mut Sim<T>,
+        mut x: Box<T>,
+        address: Bits<A>,
+        data: &[Bits<D>],
+    ) -> Result<Box<T>, SimError> {
+        x = self.select(sim, x, address)?;
+        for word in data {
+            x = self.wait_ready(sim, x)?;
+            let bus = (self.bus)(&mut x);
+            bus.from_controller.next = *word;
+            bus.strobe.next = true;
+            x = next_rising_edge(sim, x, self.clock)?;
+            (self.bus)(&mut x).strobe.next = false;
+        }
+        Ok(x)
+    }
+    // Read `count` words from the port at the given address
+    pub fn read(
+        &self,
+        sim: &mut Sim<T>,
+        mut x: Box<T>,
+        address: Bits<A>,
+        count: usize,
+    ) -> Result<(Box<T>, Vec<Bits<D>>), SimError> {
+        x = self.select(sim, x, address)?;
+        let mut ret = vec![];
+        for _ in 0..count {
+            x = self.wait_ready(sim, x)?;
+            let bus = (self.bus)(&mut x);
+            ret.push(bus.to_controller.val());
+            bus.strobe.next = true;
+            x = next_rising_edge(sim, x, self.clock)?;
+            (self.bus)(&mut x).strobe.next = false;
+        }
+        Ok((x, ret))
+    }
+}
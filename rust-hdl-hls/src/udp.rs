@@ -0,0 +1,501 @@
+use crate::bus::{FIFOReadController, FIFOWriteController, SoCBusController};
+use crate::controller::BaseController;
+use crate::cross_fifo::{CrossNarrow, CrossWiden};
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+// The network identity of a UDP endpoint.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UDPConfig {
+    pub mac: [u8; 6],
+    pub ip: [u8; 4],
+    pub port: u16,
+}
+
+// All of the headers we deal with (Ethernet + IPv4 + UDP, and
+// Ethernet + ARP) happen to be 42 bytes long.
+const HEADER_BYTES: usize = 42;
+// The largest UDP payload that fits in a standard Ethernet frame
+const MAX_PAYLOAD: usize = 1472;
+
+fn header_bits(bytes: &[u8]) -> Bits<336> {
+    assert_eq!(bytes.len(), HEADER_BYTES);
+    bytes.iter().fold(Bits::<336>::default(), |acc, byte| {
+        (acc << 8) | bit_cast::<336, 8>(byte.to_bits())
+    })
+}
+
+fn mac_bits(mac: &[u8; 6]) -> Bits<48> {
+    mac.iter()
+        .fold(0_u64, |acc, byte| (acc << 8) | (*byte as u64))
+        .to_bits()
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum UDPRxState {
+    Listen,
+    Receive,
+    Copy,
+    Skip,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum UDPTxState {
+    Idle,
+    Collect,
+    Load,
+    Header,
+    Payload,
+}
+
+// A minimal ARP/IPv4/UDP stack that sits on top of an Ethernet MAC.
+// It answers ARP requests for its IP address, and accepts UDP datagrams
+// sent to its port.  The payload of each good datagram is delivered on
+// `data_out` (frames are stored and only forwarded once the FCS checks out).
+// Bytes written into the `data_in` FIFO are sent back in UDP datagrams
+// to whichever host sent us the last datagram.  Payload is accumulated
+// until the FIFO stays empty for a while, or a full frame is collected.
+// IP options, fragments, and UDP checksums are not supported (the UDP
+// checksum on outgoing datagrams is left as zero, which IPv4 allows).
+#[derive(LogicBlock)]
+pub struct UDPStack {
+    pub clock: Signal<In, Clock>,
+    pub rx: EthernetRxSink,
+    pub tx: EthernetTxSource,
+    pub data_out: FIFOWriteController<Bits<8>>,
+    pub data_in: FIFOReadController<Bits<8>>,
+    my_mac: Constant<Bits<48>>,
+    my_ip: Constant<Bits<32>>,
+    my_port: Constant<Bits<16>>,
+    broadcast: Constant<Bits<48>>,
+    udp_template: Constant<Bits<336>>,
+    arp_template: Constant<Bits<336>>,
+    ip_sum: Constant<Bits<32>>,
+    max_payload: Constant<Bits<11>>,
+    // Receive side
+    rx_state: DFF<UDPRxState>,
+    rx_header: DFF<Bits<336>>,
+    rx_count: DFF<Bits<11>>,
+    rx_len: DFF<Bits<11>>,
+    rx_addr: DFF<Bits<11>>,
+    rx_fetched: DFF<Bit>,
+    in_frame: DFF<Bit>,
+    rx_ram: RAM<Bits<8>, 11>,
+    mac_ok: Signal<Local, Bit>,
+    is_udp: Signal<Local, Bit>,
+    is_arp: Signal<Local, Bit>,
+    udp_len: Signal<Local, Bits<16>>,
+    payload_len: Signal<Local, Bits<16>>,
+    // Peer addresses
+    peer_valid: DFF<Bit>,
+    peer_mac: DFF<Bits<48>>,
+    peer_ip: DFF<Bits<32>>,
+    peer_port: DFF<Bits<16>>,
+    arp_pending: DFF<Bit>,
+    arp_mac: DFF<Bits<48>>,
+    arp_ip: DFF<Bits<32>>,
+    // Transmit side
+    tx_state: DFF<UDPTxState>,
+    tx_header: DFF<Bits<336>>,
+    tx_count: DFF<Bits<6>>,
+    tx_is_arp: DFF<Bit>,
+    tx_len: DFF<Bits<11>>,
+    tx_addr: DFF<Bits<11>>,
+    tx_idle: DFF<Bits<8>>,
+    tx_ram: RAM<Bits<8>, 11>,
+    ip_len: Signal<Local, Bits<16>>,
+    ip_checksum: Signal<Local, Bits<32>>,
+}
+
+impl UDPStack {
+    pub fn new(config: UDPConfig) -> Self {
+        let ip = config.ip;
+        let port = config.port.to_be_bytes();
+        let mut udp = [0_u8; HEADER_BYTES];
+        udp[6..12].copy_from_slice(&config.mac);
+        udp[12..24].copy_from_slice(&[0x08, 0x00, 0x45, 0x00, 0, 0, 0, 0, 0x40, 0x00, 0x40, 0x11]);
+        udp[26..30].copy_from_slice(&ip);
+        udp[34..36].copy_from_slice(&port);
+        let mut arp = [0_u8; HEADER_BYTES];
+        arp[6..12].copy_from_slice(&config.mac);
+        arp[12..22].copy_from_slice(&[0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x02]);
+        arp[22..28].copy_from_slice(&config.mac);
+        arp[28..32].copy_from_slice(&ip);
+        // The part of the IP header checksum that does not depend on the
+        // length or destination of the datagram
+        let ip_sum = 0x4500_u64
+            + 0x4000
+            + 0x4011
+            + u16::from_be_bytes([ip[0], ip[1]]) as u64
+            + u16::from_be_bytes([ip[2], ip[3]]) as u64;
+        Self {
+            clock: Default::default(),
+            rx: Default::default(),
+            tx: Default::default(),
+            data_out: Default::default(),
+            data_in: Default::default(),
+            my_mac: Constant::new(mac_bits(&config.mac)),
+            my_ip: Constant::new(u32::from_be_bytes(ip).to_bits()),
+            my_port: Constant::new(config.port.to_bits()),
+            broadcast: Constant::new(mac_bits(&[0xFF; 6])),
+            udp_template: Constant::new(header_bits(&udp)),
+            arp_template: Constant::new(header_bits(&arp)),
+            ip_sum: Constant::new(ip_sum.to_bits()),
+            max_payload: Constant::new(MAX_PAYLOAD.to_bits()),
+            rx_state: Default::default(),
+            rx_header: Default::default(),
+            rx_count: Default::default(),
+            rx_len: Default::default(),
+            rx_addr: Default::default(),
+            rx_fetched: Default::default(),
+            in_frame: Default::default(),
+            rx_ram: Default::default(),
+            mac_ok: Default::default(),
+            is_udp: Default::default(),
+            is_arp: Default::default(),
+            udp_len: Default::default(),
+            payload_len: Default::default(),
+            peer_valid: Default::default(),
+            peer_mac: Default::default(),
+            peer_ip: Default::default(),
+            peer_port: Default::default(),
+            arp_pending: Default::default(),
+            arp_mac: Default::default(),
+            arp_ip: Default::default(),
+            tx_state: Default::default(),
+            tx_header: Default::default(),
+            tx_count: Default::default(),
+            tx_is_arp: Default::default(),
+            tx_len: Default::default(),
+            tx_addr: Default::default(),
+            tx_idle: Default::default(),
+            tx_ram: Default::default(),
+            ip_len: Default::default(),
+            ip_checksum: Default::default(),
+        }
+    }
+}
+
+impl Logic for UDPStack {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(
+            self,
+            clock,
+            rx_state,
+            rx_header,
+            rx_count,
+            rx_len,
+            rx_addr,
+            rx_fetched,
+            in_frame,
+            peer_valid,
+            peer_mac,
+            peer_ip,
+            peer_port,
+            arp_pending,
+            arp_mac,
+            arp_ip,
+            tx_state,
+            tx_header,
+            tx_count,
+            tx_is_arp,
+            tx_len,
+            tx_addr,
+            tx_idle
+        );
+        self.rx_ram.read_clock.next = self.clock.val();
+        self.rx_ram.write_clock.next = self.clock.val();
+        self.tx_ram.read_clock.next = self.clock.val();
+        self.tx_ram.write_clock.next = self.clock.val();
+        // Decode the header of the frame.  Byte n of the frame
+        // lands in bits 8*(41-n)+7..8*(41-n) of the header register.
+        self.mac_ok.next = (self.rx_header.q.val().get_bits::<48>(288) == self.my_mac.val())
+            | (self.rx_header.q.val().get_bits::<48>(288) == self.broadcast.val());
+        self.udp_len.next = self.rx_header.q.val().get_bits::<16>(16);
+        self.payload_len.next = self.udp_len.val() - 8;
+        self.is_udp.next = self.mac_ok.val()
+            & (self.rx_header.q.val().get_bits::<16>(224) == 0x0800)
+            & (self.rx_header.q.val().get_bits::<8>(216) == 0x45)
+            & (self.rx_header.q.val().get_bits::<8>(144) == 17)
+            & ((self.rx_header.q.val().get_bits::<16>(160) & 0x3FFF) == 0)
+            & (self.rx_header.q.val().get_bits::<32>(64) == self.my_ip.val())
+            & (self.rx_header.q.val().get_bits::<16>(32) == self.my_port.val())
+            & (self.udp_len.val() > 8)
+            & (self.payload_len.val() <= bit_cast::<16, 11>(self.max_payload.val()))
+            & (bit_cast::<16, 11>(self.rx_count.q.val()) >= self.udp_len.val() + 34);
+        self.is_arp.next = self.mac_ok.val()
+            & (self.rx_header.q.val().get_bits::<16>(224) == 0x0806)
+            & (self.rx_header.q.val().get_bits::<16>(160) == 1)
+            & (self.rx_header.q.val().get_bits::<32>(0) == self.my_ip.val());
+        // Receive side
+        if self.rx.strobe.val() & self.rx.start.val() {
+            self.in_frame.d.next = true;
+        }
+        if self.rx.end.val() {
+            self.in_frame.d.next = false;
+        }
+        self.rx_ram.write_address.next = self.rx_count.q.val() - 42;
+        self.rx_ram.write_data.next = self.rx.data.val();
+        self.rx_ram.write_enable.next = false;
+        self.rx_ram.read_address.next = self.rx_addr.q.val();
+        self.data_out.data.next = self.rx_ram.read_data.val();
+        self.data_out.write.next = false;
+        match self.rx_state.q.val() {
+            UDPRxState::Listen => {
+                if self.rx.strobe.val() & self.rx.start.val() {
+                    self.rx_header.d.next =
+                        (self.rx_header.q.val() << 8) | bit_cast::<336, 8>(self.rx.data.val());
+                    self.rx_count.d.next = 1.into();
+                    self.rx_state.d.next = UDPRxState::Receive;
+                }
+            }
+            UDPRxState::Receive => {
+                if self.rx.strobe.val() {
+                    if self.rx_count.q.val() < 42 {
+                        self.rx_header.d.next =
+                            (self.rx_header.q.val() << 8) | bit_cast::<336, 8>(self.rx.data.val());
+                    } else if bit_cast::<16, 11>(self.rx_count.q.val() - 42)
+                        < self.payload_len.val()
+                    {
+                        self.rx_ram.write_enable.next = true;
+                    }
+                    if !self.rx_count.q.val().all() {
+                        self.rx_count.d.next = self.rx_count.q.val() + 1;
+                    }
+                }
+                if self.rx.end.val() {
+                    self.rx_state.d.next = UDPRxState::Listen;
+                    if self.rx.ok.val() & self.is_udp.val() {
+                        self.peer_valid.d.next = true;
+                        self.peer_mac.d.next = self.rx_header.q.val().get_bits::<48>(240);
+                        self.peer_ip.d.next = self.rx_header.q.val().get_bits::<32>(96);
+                        self.peer_port.d.next = self.rx_header.q.val().get_bits::<16>(48);
+                        self.rx_len.d.next = self.payload_len.val().get_bits::<11>(0);
+                        self.rx_addr.d.next = 0.into();
+                        self.rx_fetched.d.next = false;
+                        self.rx_state.d.next = UDPRxState::Copy;
+                    }
+                    if self.rx.ok.val() & self.is_arp.val() {
+                        self.arp_pending.d.next = true;
+                        self.arp_mac.d.next = self.rx_header.q.val().get_bits::<48>(112);
+                        self.arp_ip.d.next = self.rx_header.q.val().get_bits::<32>(80);
+                    }
+                }
+            }
+            UDPRxState::Copy => {
+                // The RAM has a one cycle read latency, so each byte takes
+                // (at least) two clocks to move into the FIFO.  Frames that
+                // arrive during the copy are dropped.
+                if !self.rx_fetched.q.val() {
+                    self.rx_fetched.d.next = true;
+                } else if !self.data_out.full.val() {
+                    self.data_out.write.next = true;
+                    self.rx_fetched.d.next = false;
+                    self.rx_addr.d.next = self.rx_addr.q.val() + 1;
+                    if self.rx_addr.q.val() + 1 == self.rx_len.q.val() {
+                        self.rx_state.d.next = UDPRxState::Skip;
+                    }
+                }
+            }
+            UDPRxState::Skip => {
+                if !self.in_frame.q.val() {
+                    self.rx_state.d.next = UDPRxState::Listen;
+                }
+            }
+            _ => {
+                self.rx_state.d.next = UDPRxState::Listen;
+            }
+        }
+        // Transmit side
+        self.ip_len.next = bit_cast::<16, 11>(self.tx_addr.q.val()) + 28;
+        self.ip_checksum.next = self.ip_sum.val()
+            + bit_cast::<32, 16>(self.ip_len.val())
+            + bit_cast::<32, 16>(self.peer_ip.q.val().get_bits::<16>(16))
+            + bit_cast::<32, 16>(self.peer_ip.q.val().get_bits::<16>(0));
+        self.ip_checksum.next = bit_cast::<32, 16>(self.ip_checksum.val().get_bits::<16>(0))
+            + bit_cast::<32, 16>(self.ip_checksum.val().get_bits::<16>(16));
+        self.ip_checksum.next = bit_cast::<32, 16>(self.ip_checksum.val().get_bits::<16>(0))
+            + bit_cast::<32, 16>(self.ip_checksum.val().get_bits::<16>(16));
+        self.ip_checksum.next = !self.ip_checksum.val();
+        self.tx.data.next = self.tx_header.q.val().get_bits::<8>(328);
+        self.tx.start.next = false;
+        self.tx.last.next = false;
+        self.data_in.read.next = false;
+        self.tx_ram.write_address.next = self.tx_addr.q.val();
+        self.tx_ram.write_data.next = self.data_in.data.val();
+        self.tx_ram.write_enable.next = false;
+        self.tx_ram.read_address.next = self.tx_addr.q.val();
+        match self.tx_state.q.val() {
+            UDPTxState::Idle => {
+                self.tx_count.d.next = 0.into();
+                if self.arp_pending.q.val() {
+                    self.tx_header.d.next = self.arp_template.val()
+                        | (bit_cast::<336, 48>(self.arp_mac.q.val()) << 288)
+                        | (bit_cast::<336, 48>(self.arp_mac.q.val()) << 32)
+                        | bit_cast::<336, 32>(self.arp_ip.q.val());
+                    self.tx_is_arp.d.next = true;
+                    self.arp_pending.d.next = false;
+                    self.tx_state.d.next = UDPTxState::Header;
+                } else if self.peer_valid.q.val() & !self.data_in.empty.val() {
+                    self.tx_addr.d.next = 0.into();
+                    self.tx_idle.d.next = 0.into();
+                    self.tx_state.d.next = UDPTxState::Collect;
+                }
+            }
+            UDPTxState::Collect => {
+                if !self.data_in.empty.val() {
+                    self.data_in.read.next = true;
+                    self.tx_ram.write_enable.next = true;
+                    self.tx_addr.d.next = self.tx_addr.q.val() + 1;
+                    self.tx_idle.d.next = 0.into();
+                    if self.tx_addr.q.val() + 1 == self.max_payload.val() {
+                        self.tx_state.d.next = UDPTxState::Load;
+                    }
+                } else {
+                    self.tx_idle.d.next = self.tx_idle.q.val() + 1;
+                    if self.tx_idle.q.val().all() {
+                        self.tx_state.d.next = UDPTxState::Load;
+                    }
+                }
+            }
+            UDPTxState::Load => {
+                self.tx_header.d.next = self.udp_template.val()
+                    | (bit_cast::<336, 48>(self.peer_mac.q.val()) << 288)
+                    | (bit_cast::<336, 16>(self.ip_len.val()) << 192)
+                    | (bit_cast::<336, 16>(self.ip_checksum.val().get_bits::<16>(0)) << 128)
+                    | (bit_cast::<336, 32>(self.peer_ip.q.val()) << 64)
+                    | (bit_cast::<336, 16>(self.peer_port.q.val()) << 32)
+                    | (bit_cast::<336, 16>(self.ip_len.val() - 20) << 16);
+                self.tx_len.d.next = self.tx_addr.q.val();
+                self.tx_addr.d.next = 0.into();
+                self.tx_is_arp.d.next = false;
+                self.tx_state.d.next = UDPTxState::Header;
+            }
+            UDPTxState::Header => {
+                self.tx.start.next = true;
+                self.tx.last.next = self.tx_is_arp.q.val() & (self.tx_count.q.val() == 41);
+                if self.tx.advance.val() {
+                    self.tx_header.d.next = self.tx_header.q.val() << 8;
+                    self.tx_count.d.next = self.tx_count.q.val() + 1;
+                    if self.tx_count.q.val() == 41 {
+                        if self.tx_is_arp.q.val() {
+                            self.tx_state.d.next = UDPTxState::Idle;
+                        } else {
+                            self.tx_state.d.next = UDPTxState::Payload;
+                        }
+                    }
+                }
+            }
+            UDPTxState::Payload => {
+                // Look ahead in the RAM, so that the next byte is ready
+                // on the clock after the MAC consumes this one
+                self.tx.start.next = true;
+                self.tx.data.next = self.tx_ram.read_data.val();
+                self.tx.last.next = self.tx_addr.q.val() + 1 == self.tx_len.q.val();
+                if self.tx.advance.val() {
+                    self.tx_addr.d.next = self.tx_addr.q.val() + 1;
+                    self.tx_ram.read_address.next = self.tx_addr.q.val() + 1;
+                    if self.tx_addr.q.val() + 1 == self.tx_len.q.val() {
+                        self.tx_state.d.next = UDPTxState::Idle;
+                    }
+                }
+            }
+            _ => {
+                self.tx_state.d.next = UDPTxState::Idle;
+            }
+        }
+    }
+}
+
+// Connects a BaseController to the network.  Commands arrive as UDP
+// datagrams on the configured port, and responses are sent back to the
+// sender.  The MAC and stack run off the RMII reference clock, and
+// the controller runs in the `sys_clock` domain.
+#[derive(LogicBlock)]
+pub struct UDPHost<const A: usize> {
+    pub rmii: RMIIWiresMAC,
+    pub bus: SoCBusController<16, A>,
+    pub sys_clock: Signal<In, Clock>,
+    pub eth_clock: Signal<In, Clock>,
+    mac: RMIIEthernetMAC,
+    stack: UDPStack,
+    bus_to_controller: CrossWiden<8, 4, 5, 16, 3, 4>,
+    controller_to_bus: CrossNarrow<16, 3, 4, 8, 4, 5>,
+    controller: BaseController<A>,
+}
+
+impl<const A: usize> UDPHost<A> {
+    pub fn new(config: UDPConfig, speed: EthernetSpeed, order: WordOrder) -> Self {
+        Self {
+            rmii: Default::default(),
+            bus: Default::default(),
+            sys_clock: Default::default(),
+            eth_clock: Default::default(),
+            mac: RMIIEthernetMAC::new(speed),
+            stack: UDPStack::new(config),
+            bus_to_controller: CrossWiden::new(order),
+            controller_to_bus: CrossNarrow::new(order),
+            controller: Default::default(),
+        }
+    }
+}
+
+impl<const A: usize> Logic for UDPHost<A> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, eth_clock, mac, stack);
+        RMIIWiresMAC::link(&mut self.rmii, &mut self.mac.rmii);
+        EthernetRxSource::join(&mut self.mac.rx, &mut self.stack.rx);
+        EthernetTxSource::join(&mut self.stack.tx, &mut self.mac.tx);
+        FIFOWriteController::<Bits<8>>::join(
+            &mut self.stack.data_out,
+            &mut self.bus_to_controller.narrow_bus,
+        );
+        self.bus_to_controller.narrow_clock.next = self.eth_clock.val();
+        self.bus_to_controller.wide_clock.next = self.sys_clock.val();
+        FIFOReadController::<Bits<8>>::join(
+            &mut self.stack.data_in,
+            &mut self.controller_to_bus.narrow_bus,
+        );
+        self.controller_to_bus.narrow_clock.next = self.eth_clock.val();
+        self.controller_to_bus.wide_clock.next = self.sys_clock.val();
+        FIFOReadController::<Bits<16>>::join(
+            &mut self.controller.from_cpu,
+            &mut self.bus_to_controller.wide_bus,
+        );
+        FIFOWriteController::<Bits<16>>::join(
+            &mut self.controller.to_cpu,
+            &mut self.controller_to_bus.wide_bus,
+        );
+        clock!(self, sys_clock, controller);
+        SoCBusController::<16, A>::link(&mut self.bus, &mut self.controller.bus);
+    }
+}
+
+#[cfg(test)]
+fn test_config() -> UDPConfig {
+    UDPConfig {
+        mac: [0x02, 0x00, 0x00, 0x12, 0x34, 0x56],
+        ip: [192, 168, 1, 10],
+        port: 5000,
+    }
+}
+
+#[test]
+fn test_udp_stack_synthesizes() {
+    let mut uut = UDPStack::new(test_config());
+    uut.connect_all();
+    yosys_validate("udp_stack", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_udp_host_synthesizes() {
+    let mut uut = UDPHost::<8>::new(
+        test_config(),
+        EthernetSpeed::Mbps100,
+        WordOrder::MostSignificantFirst,
+    );
+    uut.connect_all();
+    yosys_validate("udp_host", &generate_verilog(&uut)).unwrap();
+}
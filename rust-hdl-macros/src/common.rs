@@ -131,23 +131,35 @@ pub fn fixup_ident(x: String) -> String {
     y
 }
 
-// The dff_setup macro uses clock, dfflist arguments
+// The dff_setup macro uses clock, dfflist arguments, with an
+// optional `reset = <reset>` argument after the clock
 #[derive(Debug)]
 pub struct DFFSetupArgs {
     pub me: Expr,
     pub clock: Expr,
+    pub reset: Option<Expr>,
     pub dffs: Vec<Expr>,
 }
 
 impl Parse for DFFSetupArgs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let clock: Expr;
+        let mut reset = None;
         let mut dffs = Vec::new();
 
         let me: Expr = input.parse()?;
         input.parse::<Token![,]>()?;
         clock = input.parse()?;
         input.parse::<Token![,]>()?;
+        if input.peek(syn::Ident) && input.peek2(Token![=]) {
+            let keyword: syn::Ident = input.parse()?;
+            if keyword != "reset" {
+                return Err(syn::Error::new(keyword.span(), "Expected reset = <reset>"));
+            }
+            input.parse::<Token![=]>()?;
+            reset = Some(input.parse()?);
+            input.parse::<Token![,]>()?;
+        }
         while !input.is_empty() {
             let dff_name: Expr = input.parse()?;
             dffs.push(dff_name);
@@ -155,6 +167,11 @@ impl Parse for DFFSetupArgs {
                 input.parse::<Token![,]>()?;
             }
         }
-        Ok(DFFSetupArgs { me, clock, dffs })
+        Ok(DFFSetupArgs {
+            me,
+            clock,
+            reset,
+            dffs,
+        })
     }
 }
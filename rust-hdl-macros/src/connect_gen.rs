@@ -141,12 +141,23 @@ fn connect_macro(m: &syn::ExprMacro) -> Result<TS> {
             let args: DFFSetupArgs = m.mac.parse_body()?;
             let me = &args.me;
             let dff = &args.dffs;
-            Ok(quote! {
-                #(
-                    logic::logic_connect_fn(&mut #me.#dff.clock);
-                    logic::logic_connect_fn(&mut #me.#dff.d);
-                )*
-            })
+            if args.reset.is_some() {
+                Ok(quote! {
+                    #(
+                        logic::logic_connect_fn(&mut #me.#dff.clock);
+                        logic::logic_connect_fn(&mut #me.#dff.reset);
+                        logic::logic_connect_fn(&mut #me.#dff.enable);
+                        logic::logic_connect_fn(&mut #me.#dff.d);
+                    )*
+                })
+            } else {
+                Ok(quote! {
+                    #(
+                        logic::logic_connect_fn(&mut #me.#dff.clock);
+                        logic::logic_connect_fn(&mut #me.#dff.d);
+                    )*
+                })
+            }
         } else if macro_name == "clock" {
             let args: DFFSetupArgs = m.mac.parse_body()?;
             let me = &args.me;
@@ -0,0 +1,116 @@
+use crate::common::TS;
+use proc_macro2::Span;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{parse_quote, Expr, Stmt};
+
+// Branch coverage is only added to functions marked `#[hdl_gen(coverage)]`, so that
+// designs that do not ask for it compile exactly as they would without it.
+pub(crate) fn coverage_requested(attr: proc_macro::TokenStream) -> syn::Result<bool> {
+    if attr.is_empty() {
+        return Ok(false);
+    }
+    let arg: syn::Ident = syn::parse(attr)?;
+    if arg == "coverage" {
+        Ok(true)
+    } else {
+        Err(syn::Error::new(
+            arg.span(),
+            "expected `coverage` as the only argument to `hdl_gen`",
+        ))
+    }
+}
+
+// Adds branch coverage instrumentation to an HDL function.  Each arm of every
+// `if` and `match` records a hit when it is taken, and the branch points are
+// declared on entry to the function, so that the ones never reached are still
+// counted.  An `if` without an `else` gets an (otherwise empty) `else` arm.
+// The recording is a no-op unless a simulation is collecting coverage.
+pub(crate) fn coverage_gen(item: &syn::ItemFn) -> TS {
+    let mut item = item.clone();
+    let mut instrument = BranchInstrument::default();
+    instrument.visit_block_mut(&mut item.block);
+    if instrument.points.is_empty() {
+        return quote!(#item);
+    }
+    let points = instrument
+        .points
+        .iter()
+        .map(|(span, block, arms)| quote_spanned!(*span=> (file!(), line!(), #block, #arms)));
+    let declare: Stmt = parse_quote! {
+        coverage::declare_branches(::std::any::type_name::<Self>(), &[#(#points),*]);
+    };
+    item.block.stmts.insert(0, declare);
+    quote!(#item)
+}
+
+#[derive(Default)]
+struct BranchInstrument {
+    points: Vec<(Span, u32, usize)>,
+}
+
+impl BranchInstrument {
+    fn branch_point(&mut self, span: Span, arms: usize) -> u32 {
+        let block = self.points.len() as u32;
+        self.points.push((span, block, arms));
+        block
+    }
+}
+
+fn record_branch(span: Span, block: u32, arm: usize) -> Stmt {
+    syn::parse2(quote_spanned! {span=>
+        coverage::record_branch(::std::any::type_name::<Self>(), file!(), line!(), #block, #arm);
+    })
+    .unwrap()
+}
+
+impl VisitMut for BranchInstrument {
+    fn visit_expr_if_mut(&mut self, node: &mut syn::ExprIf) {
+        syn::visit_mut::visit_expr_if_mut(self, node);
+        let span = node.if_token.span();
+        let block = self.branch_point(span, 2);
+        node.then_branch
+            .stmts
+            .insert(0, record_branch(span, block, 0));
+        let record_else = record_branch(span, block, 1);
+        match &mut node.else_branch {
+            Some((_, else_branch)) => match else_branch.as_mut() {
+                Expr::Block(x) => x.block.stmts.insert(0, record_else),
+                // An `else if` chain becomes `else { record; if ... }`
+                chained => {
+                    let inner = chained.clone();
+                    *chained = parse_quote!({
+                        #record_else
+                        #inner
+                    })
+                }
+            },
+            None => {
+                node.else_branch = Some((
+                    Default::default(),
+                    Box::new(parse_quote!({
+                        #record_else
+                    })),
+                ))
+            }
+        }
+    }
+
+    fn visit_expr_match_mut(&mut self, node: &mut syn::ExprMatch) {
+        syn::visit_mut::visit_expr_match_mut(self, node);
+        let span = node.match_token.span();
+        let block = self.branch_point(span, node.arms.len());
+        for (ndx, arm) in node.arms.iter_mut().enumerate() {
+            let record = record_branch(span, block, ndx);
+            let body = arm.body.clone();
+            *arm.body = parse_quote!({
+                #record
+                #body
+            });
+            if arm.comma.is_none() {
+                arm.comma = Some(Default::default());
+            }
+        }
+    }
+}
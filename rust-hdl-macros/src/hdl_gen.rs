@@ -630,10 +630,31 @@ fn hdl_macro(x: &syn::ExprMacro) -> Result<TS> {
                 .iter()
                 .map(|x| common::fixup_ident(quote!(#x.q).to_string()))
                 .collect::<Vec<_>>();
+            // With a reset, each register also gets the reset, and is enabled
+            let (dffs_rst, dffs_en) = match &args.reset {
+                Some(_) => (
+                    args.dffs
+                        .iter()
+                        .map(|x| common::fixup_ident(quote!(#x.reset.next).to_string()))
+                        .collect::<Vec<_>>(),
+                    args.dffs
+                        .iter()
+                        .map(|x| common::fixup_ident(quote!(#x.enable.next).to_string()))
+                        .collect::<Vec<_>>(),
+                ),
+                None => (vec![], vec![]),
+            };
+            let rst = args
+                .reset
+                .as_ref()
+                .map(|x| common::fixup_ident(quote!(#x).to_string()))
+                .unwrap_or_default();
             Ok(quote!(
                 {
                     let mut ret = vec![];
                     #(ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#dffs_clk.to_string()), ast::VerilogExpression::Signal(#clk.to_string()))));*;
+                    #(ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#dffs_rst.to_string()), ast::VerilogExpression::Signal(#rst.to_string()))));*;
+                    #(ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#dffs_en.to_string()), ast::VerilogExpression::Literal(true.into()))));*;
                     #(ret.push(ast::VerilogStatement::Assignment(ast::VerilogExpression::Signal(#dffs_d.to_string()), ast::VerilogExpression::Signal(#dffs_q.to_string()))));*;
                     ast::VerilogStatement::Macro(ret)
                 }
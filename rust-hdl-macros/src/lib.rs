@@ -1,5 +1,6 @@
 mod common;
 mod connect_gen;
+mod coverage_gen;
 mod hdl_gen;
 mod logic_block;
 mod logic_interface;
@@ -11,6 +12,7 @@ use syn::DeriveInput;
 
 use crate::common::TS;
 use crate::connect_gen::connect_gen;
+use crate::coverage_gen::{coverage_gen, coverage_requested};
 use crate::hdl_gen::hdl_gen_process;
 use crate::logic_block::get_impl_for_logic_block;
 use crate::logic_interface::get_impl_for_logic_interface;
@@ -60,9 +62,14 @@ pub fn logic_struct(input: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_attribute]
-pub fn hdl_gen(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn hdl_gen(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let coverage = match coverage_requested(attr) {
+        Err(e) => return e.to_compile_error().into(),
+        Ok(t) => t,
+    };
     let orig = TS::from(item.clone());
     let parse = parse_macro_input!(item as syn::ItemFn);
+    let orig = if coverage { coverage_gen(&parse) } else { orig };
     let connects = match connect_gen(&parse) {
         Err(e) => return e.to_compile_error().into(),
         Ok(t) => t,
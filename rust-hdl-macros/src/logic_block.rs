@@ -9,7 +9,8 @@ pub(crate) fn get_impl_for_logic_block(input: &syn::DeriveInput) -> Result<TS> {
     let update_all = common::get_update_all(fields.clone())?;
     let has_changed = common::get_has_changed(fields.clone())?;
     let connect_all = common::get_connect_all(fields.clone())?;
-    let accept = get_accept(fields)?;
+    let accept = get_accept(fields.clone())?;
+    let accept_mut = get_accept_mut(fields)?;
     let name = &input.ident;
     let (impl_generics, ty_generics, _where_clause) = &input.generics.split_for_impl();
     Ok(quote! {
@@ -18,6 +19,7 @@ pub(crate) fn get_impl_for_logic_block(input: &syn::DeriveInput) -> Result<TS> {
             #update_all
             #has_changed
             #accept
+            #accept_mut
         }
     })
 }
@@ -32,3 +34,15 @@ fn get_accept(fields: Vec<TS>) -> Result<TS> {
         }
     })
 }
+
+fn get_accept_mut(fields: Vec<TS>) -> Result<TS> {
+    let fields_as_strings = fields.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    Ok(quote! {
+        fn accept_mut(&mut self, name: &str, probe: &mut dyn probe::ProbeMut) {
+            probe.visit_start_scope(name);
+            #(self.#fields.accept_mut(#fields_as_strings, probe);)*
+            logic::Logic::accept_state(self, probe);
+            probe.visit_end_scope(name);
+        }
+    })
+}
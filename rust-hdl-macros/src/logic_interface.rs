@@ -16,6 +16,7 @@ pub(crate) fn get_impl_for_logic_interface(input: &syn::DeriveInput) -> Result<T
     let join_connect = get_join_connect(fields.clone())?;
     let join_hdl = get_join_hdl(fields.clone(), field_types)?;
     let accept = get_accept(fields.clone())?;
+    let accept_mut = get_accept_mut(fields.clone())?;
     let nvps = get_nvps_from_attributes(input)?;
     let (impl_generics, ty_generics, _where_clause) = &input.generics.split_for_impl();
     let name = &input.ident;
@@ -40,6 +41,7 @@ pub(crate) fn get_impl_for_logic_interface(input: &syn::DeriveInput) -> Result<T
             #update_all
             #has_changed
             #accept
+            #accept_mut
         }
 
         impl #impl_generics logic::LogicLink for #name #ty_generics {
@@ -121,6 +123,17 @@ fn get_accept(fields: Vec<TS>) -> Result<TS> {
     })
 }
 
+fn get_accept_mut(fields: Vec<TS>) -> Result<TS> {
+    let fields_as_strings = fields.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    Ok(quote! {
+        fn accept_mut(&mut self, name: &str, probe: &mut dyn probe::ProbeMut) {
+            probe.visit_start_namespace(name);
+            #(self.#fields.accept_mut(#fields_as_strings, probe);)*
+            probe.visit_end_namespace(name);
+        }
+    })
+}
+
 fn get_nvps_from_attributes(input: &syn::DeriveInput) -> Result<HashMap<String, String>> {
     let mut ret = HashMap::new();
     for attr in &input.attrs {
@@ -68,6 +68,12 @@ pub fn get_logic_state_impls(input: &syn::DeriveInput) -> Result<TS> {
                     #(#name::#variants => #discriminants.into(),)*
                 }
             }
+            fn from_text(text: &str) -> Option<Self> {
+                match text.trim().rsplit("::").next().unwrap_or_default() {
+                    #(#variants_only_as_strings => Some(#name::#variants),)*
+                    _ => None,
+                }
+            }
         }
 
         impl Into<Bits<{#name::BITS}>> for #name {
@@ -73,6 +73,23 @@ pub(crate) fn get_impl_for_logic_struct(input: &syn::DeriveInput) -> Result<TS>
                 let t: Bits<{Self::BITS}> = self.into();
                 t.into()
             }
+
+            fn to_text(self) -> String {
+                let fields: Vec<String> = vec![#(self.#fields.to_text(),)*];
+                format!("{{{}}}", fields.join(","))
+            }
+
+            fn from_text(text: &str) -> Option<Self> {
+                let parts = split_composite(text)?;
+                let mut parts = parts.into_iter();
+                let ret = Self {
+                    #(#fields: <#field_types>::from_text(parts.next()?)?,)*
+                };
+                if parts.next().is_some() {
+                    return None;
+                }
+                Some(ret)
+            }
         }
     })
 }
@@ -0,0 +1,127 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_hdl_core::prelude::*;
+
+/// Settings for the behavioral models of the Xilinx Memory Interface Generator (MIG)
+/// cores used on the Opal Kelly boards.  The models stand in for the MIG blackbox
+/// when a design is simulated, and keep the memory contents in a sparse map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MIGSimConfig {
+    /// Number of clock cycles after reset before calibration completes
+    pub calibration_cycles: u32,
+    /// Number of clock cycles between accepting a read command and returning data
+    pub read_latency: u32,
+    /// Probability (0.0 to 1.0) that the model stalls on any given clock cycle
+    pub stall_probability: f64,
+    /// Seed for the random stalls, so that a simulation can be repeated
+    pub seed: u64,
+}
+
+impl Default for MIGSimConfig {
+    fn default() -> Self {
+        Self {
+            calibration_cycles: 100,
+            read_latency: 4,
+            stall_probability: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Source of the random stalls in a MIG model.  Call [MIGStalls::stall] once per clock.
+/// The generator is checkpointed as its seed and the number of values drawn from it,
+/// so a restored model stalls on the same clocks as the one that was saved.
+#[derive(Clone, Debug)]
+pub struct MIGStalls {
+    rng: StdRng,
+    seed: u64,
+    draws: u64,
+    probability: f64,
+}
+
+impl MIGStalls {
+    pub fn new(config: &MIGSimConfig) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            seed: config.seed,
+            draws: 0,
+            probability: config.stall_probability,
+        }
+    }
+
+    pub fn stall(&mut self) -> bool {
+        if self.probability <= 0.0 {
+            return false;
+        }
+        self.draws += 1;
+        self.rng.gen::<f64>() < self.probability
+    }
+}
+
+// The stall probability is part of the configuration, and is kept as constructed.
+impl SimState for MIGStalls {
+    fn save(&self) -> String {
+        StateWriter::default()
+            .put_u64(self.seed)
+            .put_u64(self.draws)
+            .finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let (Some(seed), Some(draws)) = (reader.get_u64(), reader.get_u64()) else {
+            return false;
+        };
+        if !reader.is_done() {
+            return false;
+        }
+        self.rng = StdRng::seed_from_u64(seed);
+        for _ in 0..draws {
+            self.rng.gen::<f64>();
+        }
+        self.seed = seed;
+        self.draws = draws;
+        true
+    }
+}
+
+impl Default for MIGStalls {
+    fn default() -> Self {
+        Self::new(&MIGSimConfig::default())
+    }
+}
+
+/// Merge write data into a memory word one byte at a time.  As with the MIG, a set bit
+/// in the mask means the corresponding byte is left unchanged.
+pub fn mig_masked_write<const N: usize, const M: usize>(
+    old: Bits<N>,
+    data: Bits<N>,
+    mask: Bits<M>,
+) -> Bits<N> {
+    let mut word = old;
+    for byte in 0..M {
+        if !mask.get_bit(byte) {
+            word.set_bits::<8>(byte * 8, data.get_bits::<8>(byte * 8));
+        }
+    }
+    word
+}
+
+#[test]
+fn test_mig_stalls_resume_after_restore() {
+    let config = MIGSimConfig {
+        stall_probability: 0.3,
+        seed: 42,
+        ..Default::default()
+    };
+    let mut stalls = MIGStalls::new(&config);
+    for _ in 0..100 {
+        stalls.stall();
+    }
+    let saved = stalls.save();
+    let expected = (0..100).map(|_| stalls.stall()).collect::<Vec<_>>();
+    let mut restored = MIGStalls::new(&config);
+    assert!(restored.restore(&saved));
+    let actual = (0..100).map(|_| restored.stall()).collect::<Vec<_>>();
+    assert_eq!(actual, expected);
+    assert!(!restored.restore("42"));
+}
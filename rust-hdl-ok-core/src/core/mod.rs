@@ -5,11 +5,14 @@ use ok_host::OpalKellyHost;
 
 pub mod bsp;
 pub mod clock;
+pub mod mig_sim;
+pub mod ok_device;
 pub mod ok_download;
 pub mod ok_hi;
 pub mod ok_hls_bridge;
 pub mod ok_host;
 pub mod ok_pipe;
+pub mod ok_sim;
 pub mod ok_trigger;
 pub mod ok_wire;
 pub mod prelude;
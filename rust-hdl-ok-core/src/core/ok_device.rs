@@ -0,0 +1,340 @@
+use std::io;
+
+use rust_hdl_ok_frontpanel_sys::{
+    ok_ErrorCode_ok_DataAlignmentError, ok_ErrorCode_ok_InvalidBlockSize,
+    ok_ErrorCode_ok_InvalidEndpoint, ok_ErrorCode_ok_InvalidParameter, OkError, OkHandle,
+};
+
+// Each endpoint type owns a fixed range of the FrontPanel address space.  The
+// address is checked when the constant is built, so an address in the wrong
+// range (like `WireInAddr::new::<0x20>()`) is a compile error.
+macro_rules! ok_endpoint {
+    ($name: ident, $lo: expr, $hi: expr, $kind: expr) => {
+        #[doc = concat!("Address of a ", $kind, " endpoint, in the range ", stringify!($lo), "-", stringify!($hi))]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub struct $name(u8);
+
+        impl $name {
+            pub const fn new<const A: u8>() -> Self {
+                const { assert!(matches!(A, $lo..=$hi), concat!("Address out of range for a ", $kind)) };
+                Self(A)
+            }
+
+            pub const fn addr(&self) -> u8 {
+                self.0
+            }
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = OkError;
+
+            fn try_from(addr: u8) -> Result<Self, OkError> {
+                if ($lo..=$hi).contains(&addr) {
+                    Ok(Self(addr))
+                } else {
+                    Err(OkError {
+                        code: ok_ErrorCode_ok_InvalidEndpoint,
+                    })
+                }
+            }
+        }
+    };
+}
+
+ok_endpoint!(WireInAddr, 0x00, 0x1F, "wire in");
+ok_endpoint!(WireOutAddr, 0x20, 0x3F, "wire out");
+ok_endpoint!(TriggerInAddr, 0x40, 0x5F, "trigger in");
+ok_endpoint!(TriggerOutAddr, 0x60, 0x7F, "trigger out");
+ok_endpoint!(PipeInAddr, 0x80, 0x9F, "pipe in");
+ok_endpoint!(PipeOutAddr, 0xA0, 0xBF, "pipe out");
+
+/// The host side of the FrontPanel API.  Every call returns a [Result], and endpoints
+/// are typed by their address range.  Host code written against this trait runs on
+/// real hardware through an [OkDevice], or against a simulation through a
+/// [SimOkHandle](super::ok_sim::SimOkHandle).
+pub trait FrontPanel {
+    fn set_wire_in(&self, ep: WireInAddr, val: u16) -> Result<(), OkError>;
+    fn update_wire_ins(&self) -> Result<(), OkError>;
+    fn update_wire_outs(&self) -> Result<(), OkError>;
+    fn get_wire_out(&self, ep: WireOutAddr) -> Result<u16, OkError>;
+    fn activate_trigger_in(&self, ep: TriggerInAddr, bit: u8) -> Result<(), OkError>;
+    fn update_trigger_outs(&self) -> Result<(), OkError>;
+    fn is_triggered(&self, ep: TriggerOutAddr, mask: u16) -> Result<bool, OkError>;
+    fn write_to_pipe_in(&self, ep: PipeInAddr, data: &[u8]) -> Result<(), OkError>;
+    fn write_to_block_pipe_in(
+        &self,
+        ep: PipeInAddr,
+        blocksize: usize,
+        data: &[u8],
+    ) -> Result<(), OkError>;
+    fn read_from_pipe_out(&self, ep: PipeOutAddr, data: &mut [u8]) -> Result<(), OkError>;
+    fn read_from_block_pipe_out(
+        &self,
+        ep: PipeOutAddr,
+        blocksize: usize,
+        data: &mut [u8],
+    ) -> Result<(), OkError>;
+}
+
+fn invalid_parameter() -> OkError {
+    OkError {
+        code: ok_ErrorCode_ok_InvalidParameter,
+    }
+}
+
+/// An open FrontPanel device.  The device is closed when this is dropped.
+pub struct OkDevice {
+    hnd: OkHandle,
+}
+
+impl OkDevice {
+    /// Open the device with the given serial number (an empty string opens the first
+    /// device found).
+    pub fn open(serial: &str) -> Result<Self, OkError> {
+        let hnd = OkHandle::new();
+        hnd.open_with_serial(serial)?;
+        Ok(Self { hnd })
+    }
+
+    pub fn configure(&self, firmware: &str) -> Result<(), OkError> {
+        self.hnd.configure_fpga(firmware)
+    }
+
+    pub fn reset(&self) -> Result<(), OkError> {
+        self.hnd.reset_fpga()
+    }
+
+    pub fn serial_number(&self) -> String {
+        self.hnd.get_serial_number()
+    }
+
+    pub fn device_id(&self) -> String {
+        self.hnd.get_device_id()
+    }
+
+    /// The underlying handle, for calls not covered by [FrontPanel]
+    pub fn handle(&self) -> &OkHandle {
+        &self.hnd
+    }
+}
+
+impl FrontPanel for OkDevice {
+    fn set_wire_in(&self, ep: WireInAddr, val: u16) -> Result<(), OkError> {
+        self.hnd.try_set_wire_in(ep.addr() as i32, val)
+    }
+
+    fn update_wire_ins(&self) -> Result<(), OkError> {
+        self.hnd.try_update_wire_ins()
+    }
+
+    fn update_wire_outs(&self) -> Result<(), OkError> {
+        self.hnd.try_update_wire_outs()
+    }
+
+    fn get_wire_out(&self, ep: WireOutAddr) -> Result<u16, OkError> {
+        Ok(self.hnd.get_wire_out(ep.addr() as i32))
+    }
+
+    fn activate_trigger_in(&self, ep: TriggerInAddr, bit: u8) -> Result<(), OkError> {
+        if bit >= 16 {
+            return Err(invalid_parameter());
+        }
+        self.hnd.activate_trigger_in(ep.addr() as i32, bit as i32)
+    }
+
+    fn update_trigger_outs(&self) -> Result<(), OkError> {
+        self.hnd.try_update_trigger_outs()
+    }
+
+    fn is_triggered(&self, ep: TriggerOutAddr, mask: u16) -> Result<bool, OkError> {
+        Ok(self.hnd.is_triggered(ep.addr() as i32, mask as i32))
+    }
+
+    fn write_to_pipe_in(&self, ep: PipeInAddr, data: &[u8]) -> Result<(), OkError> {
+        self.hnd.write_to_pipe_in(ep.addr() as i32, data)
+    }
+
+    fn write_to_block_pipe_in(
+        &self,
+        ep: PipeInAddr,
+        blocksize: usize,
+        data: &[u8],
+    ) -> Result<(), OkError> {
+        let blocksize = i32::try_from(blocksize).map_err(|_| invalid_parameter())?;
+        self.hnd
+            .write_to_block_pipe_in(ep.addr() as i32, blocksize, data)
+    }
+
+    fn read_from_pipe_out(&self, ep: PipeOutAddr, data: &mut [u8]) -> Result<(), OkError> {
+        self.hnd.read_from_pipe_out(ep.addr() as i32, data)
+    }
+
+    fn read_from_block_pipe_out(
+        &self,
+        ep: PipeOutAddr,
+        blocksize: usize,
+        data: &mut [u8],
+    ) -> Result<(), OkError> {
+        let blocksize = i32::try_from(blocksize).map_err(|_| invalid_parameter())?;
+        self.hnd
+            .read_from_block_pipe_out(ep.addr() as i32, blocksize, data)
+    }
+}
+
+// Transfers are made in whole units of `transfer_size` bytes.  For a block throttled
+// pipe, this must be a multiple of the block size.
+fn check_transfer(block_size: Option<usize>, transfer_size: usize) -> Result<(), OkError> {
+    if transfer_size == 0 || !transfer_size.is_multiple_of(2) {
+        return Err(OkError {
+            code: ok_ErrorCode_ok_DataAlignmentError,
+        });
+    }
+    match block_size {
+        Some(block) if block == 0 || !transfer_size.is_multiple_of(block) => Err(OkError {
+            code: ok_ErrorCode_ok_InvalidBlockSize,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// A buffered writer to a pipe in endpoint.  Bytes are collected until `transfer_size`
+/// of them are available, and then sent in one transfer.  [io::Write::flush] sends
+/// whatever is left, which must be a whole number of 16 bit words (and blocks, for a
+/// block throttled pipe).  The writer flushes when dropped, ignoring errors.
+pub struct PipeWriter<'a, D: FrontPanel> {
+    dev: &'a D,
+    ep: PipeInAddr,
+    block_size: Option<usize>,
+    transfer_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<'a, D: FrontPanel> PipeWriter<'a, D> {
+    pub fn new(dev: &'a D, ep: PipeInAddr, transfer_size: usize) -> Result<Self, OkError> {
+        check_transfer(None, transfer_size)?;
+        Ok(Self {
+            dev,
+            ep,
+            block_size: None,
+            transfer_size,
+            buffer: Vec::with_capacity(transfer_size),
+        })
+    }
+
+    pub fn block_throttled(
+        dev: &'a D,
+        ep: PipeInAddr,
+        block_size: usize,
+        transfer_size: usize,
+    ) -> Result<Self, OkError> {
+        check_transfer(Some(block_size), transfer_size)?;
+        Ok(Self {
+            dev,
+            ep,
+            block_size: Some(block_size),
+            transfer_size,
+            buffer: Vec::with_capacity(transfer_size),
+        })
+    }
+
+    fn send(&mut self) -> Result<(), OkError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        match self.block_size {
+            Some(block) => self
+                .dev
+                .write_to_block_pipe_in(self.ep, block, &self.buffer)?,
+            None => self.dev.write_to_pipe_in(self.ep, &self.buffer)?,
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<D: FrontPanel> io::Write for PipeWriter<'_, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(self.transfer_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+        if self.buffer.len() == self.transfer_size {
+            self.send()?;
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(self.send()?)
+    }
+}
+
+impl<D: FrontPanel> Drop for PipeWriter<'_, D> {
+    fn drop(&mut self) {
+        let _ = self.send();
+    }
+}
+
+/// A buffered reader from a pipe out endpoint.  Data is fetched from the device
+/// `transfer_size` bytes at a time, so the device must be able to supply that many
+/// bytes on each transfer.
+pub struct PipeReader<'a, D: FrontPanel> {
+    dev: &'a D,
+    ep: PipeOutAddr,
+    block_size: Option<usize>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<'a, D: FrontPanel> PipeReader<'a, D> {
+    pub fn new(dev: &'a D, ep: PipeOutAddr, transfer_size: usize) -> Result<Self, OkError> {
+        check_transfer(None, transfer_size)?;
+        Ok(Self {
+            dev,
+            ep,
+            block_size: None,
+            buffer: vec![0; transfer_size],
+            pos: transfer_size,
+        })
+    }
+
+    pub fn block_throttled(
+        dev: &'a D,
+        ep: PipeOutAddr,
+        block_size: usize,
+        transfer_size: usize,
+    ) -> Result<Self, OkError> {
+        check_transfer(Some(block_size), transfer_size)?;
+        Ok(Self {
+            dev,
+            ep,
+            block_size: Some(block_size),
+            buffer: vec![0; transfer_size],
+            pos: transfer_size,
+        })
+    }
+
+    fn fill(&mut self) -> Result<(), OkError> {
+        match self.block_size {
+            Some(block) => self
+                .dev
+                .read_from_block_pipe_out(self.ep, block, &mut self.buffer)?,
+            None => self.dev.read_from_pipe_out(self.ep, &mut self.buffer)?,
+        }
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<D: FrontPanel> io::Read for PipeReader<'_, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.pos == self.buffer.len() {
+            self.fill()?;
+        }
+        let count = buf.len().min(self.buffer.len() - self.pos);
+        buf[..count].copy_from_slice(&self.buffer[self.pos..self.pos + count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
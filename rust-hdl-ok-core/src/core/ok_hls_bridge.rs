@@ -341,3 +341,21 @@ pub fn drain_stream(
     }
     Ok(make_u16_buffer(&ret))
 }
+
+// A `ControllerLink` that talks to the `BaseController` behind an
+// `OpalKellyHLSBridge`, so that host side libraries (such as the
+// `LogicAnalyzerHost`) can be used with the XEM boards.
+pub struct OkControllerLink<'a> {
+    pub hnd: &'a OkHandle,
+    pub config: OKHLSBridgeAddressConfig,
+}
+
+impl<'a> ControllerLink for OkControllerLink<'a> {
+    type Error = OkError;
+    fn write(&mut self, address: u8, data: &[u16]) -> Result<(), OkError> {
+        write_data_to_address(self.hnd, &self.config, address, data)
+    }
+    fn read(&mut self, address: u8, len: usize) -> Result<Vec<u16>, OkError> {
+        read_data_from_address(self.hnd, &self.config, address, len)
+    }
+}
@@ -1,4 +1,5 @@
 use super::ok_hi::OpalKellyHostInterface;
+use super::ok_sim::OkHostSim;
 use rust_hdl_core::prelude::*;
 
 #[derive(Clone, Debug, LogicBlock)]
@@ -7,10 +8,16 @@ pub struct OpalKellyHost {
     pub ok1: Signal<Out, Bits<31>>,
     pub ok2: Signal<In, Bits<17>>,
     pub ti_clk: Signal<Out, Clock>,
+    pub(crate) _sim: OkHostSim,
 }
 
 impl Logic for OpalKellyHost {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        // In simulation, the host runs the bus cycles queued by a SimOkHandle
+        let clk = self.hi.sig_in.val().get_bit(0);
+        self.ti_clk.next = clk.into();
+        self.ok1.next = self._sim.tick(clk, self.ok2.val());
+    }
     fn connect(&mut self) {
         self.ok1.connect();
         self.ok2.connect();
@@ -21,6 +28,9 @@ impl Logic for OpalKellyHost {
         self.hi.sig_mux.connect();
         self.ti_clk.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         Verilog::Blackbox(BlackBox {
             code: r#"
@@ -72,6 +82,7 @@ impl OpalKellyHost {
             ok1: Signal::default(),
             ok2: Signal::default(),
             ti_clk: Signal::default(),
+            _sim: Default::default(),
         }
     }
     pub fn xem_7010() -> Self {
@@ -81,6 +92,7 @@ impl OpalKellyHost {
             ok1: Signal::default(),
             ok2: Signal::default(),
             ti_clk: Signal::default(),
+            _sim: Default::default(),
         }
     }
 }
@@ -1,3 +1,4 @@
+use super::ok_sim::*;
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
@@ -10,6 +11,7 @@ pub struct BTPipeIn {
     pub dataout: Signal<Out, Bits<16>>,
     pub ready: Signal<In, bool>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl BTPipeIn {
@@ -23,18 +25,37 @@ impl BTPipeIn {
             dataout: Default::default(),
             ready: Default::default(),
             _n: n,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for BTPipeIn {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        let cycle = OkBusCycle::decode(self.ok1.val());
+        self.write.next = cycle.is(OK_CMD_PIPE_IN, self._n);
+        self.blockstrobe.next = cycle.is(OK_CMD_BLOCK_STROBE, self._n);
+        if self.write.next {
+            self.dataout.next = (cycle.data as u64).into();
+        }
+        if let Some(cycle) = self._sim.rising_edge(self.ok1.val()) {
+            self._sim.response = if cycle.is(OK_CMD_BLOCK_READY, self._n) && self.ready.val() {
+                OK2_READY
+            } else {
+                0
+            };
+        }
+        self.ok2.next = self._sim.response.into();
+    }
     fn connect(&mut self) {
         self.ok2.connect();
         self.write.connect();
         self.blockstrobe.connect();
         self.dataout.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("BTPipeIn_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
@@ -88,6 +109,7 @@ pub struct PipeIn {
     pub write: Signal<Out, bool>,
     pub dataout: Signal<Out, Bits<16>>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl PipeIn {
@@ -99,17 +121,27 @@ impl PipeIn {
             write: Default::default(),
             dataout: Default::default(),
             _n: n,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for PipeIn {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        let cycle = OkBusCycle::decode(self.ok1.val());
+        self.write.next = cycle.is(OK_CMD_PIPE_IN, self._n);
+        if self.write.next {
+            self.dataout.next = (cycle.data as u64).into();
+        }
+    }
     fn connect(&mut self) {
         self.ok2.connect();
         self.write.connect();
         self.dataout.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("PipeIn_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
@@ -156,6 +188,7 @@ pub struct PipeOut {
     pub read: Signal<Out, Bit>,
     pub datain: Signal<In, Bits<16>>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl PipeOut {
@@ -167,16 +200,32 @@ impl PipeOut {
             read: Default::default(),
             datain: Default::default(),
             _n: n,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for PipeOut {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        // The data for a read is taken on the cycle after read is asserted
+        let cycle = OkBusCycle::decode(self.ok1.val());
+        self.read.next = cycle.is(OK_CMD_PIPE_OUT, self._n);
+        if let Some(cycle) = self._sim.rising_edge(self.ok1.val()) {
+            self._sim.reading = cycle.is(OK_CMD_PIPE_OUT, self._n);
+        }
+        self.ok2.next = if self._sim.reading {
+            self.datain.val().to_u64().into()
+        } else {
+            0.into()
+        };
+    }
     fn connect(&mut self) {
         self.ok2.connect();
         self.read.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("PipeOut_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
@@ -226,6 +275,7 @@ pub struct BTPipeOut {
     pub datain: Signal<In, Bits<16>>,
     pub ready: Signal<In, Bit>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl BTPipeOut {
@@ -239,17 +289,39 @@ impl BTPipeOut {
             datain: Default::default(),
             ready: Default::default(),
             _n: n,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for BTPipeOut {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        // The data for a read is taken on the cycle after read is asserted
+        let cycle = OkBusCycle::decode(self.ok1.val());
+        self.read.next = cycle.is(OK_CMD_PIPE_OUT, self._n);
+        self.blockstrobe.next = cycle.is(OK_CMD_BLOCK_STROBE, self._n);
+        if let Some(cycle) = self._sim.rising_edge(self.ok1.val()) {
+            self._sim.reading = cycle.is(OK_CMD_PIPE_OUT, self._n);
+            self._sim.response = if cycle.is(OK_CMD_BLOCK_READY, self._n) && self.ready.val() {
+                OK2_READY
+            } else {
+                0
+            };
+        }
+        self.ok2.next = if self._sim.reading {
+            self.datain.val().to_u64().into()
+        } else {
+            self._sim.response.into()
+        };
+    }
     fn connect(&mut self) {
         self.ok2.connect();
         self.read.connect();
         self.blockstrobe.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("BTPipeOut_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
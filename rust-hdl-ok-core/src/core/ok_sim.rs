@@ -0,0 +1,637 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use rust_hdl_core::prelude::*;
+use rust_hdl_ok_frontpanel_sys::{
+    ok_ErrorCode_ok_DataAlignmentError, ok_ErrorCode_ok_Failed, ok_ErrorCode_ok_InvalidBlockSize,
+    ok_ErrorCode_ok_InvalidEndpoint, ok_ErrorCode_ok_InvalidParameter, OkError,
+};
+
+use super::ok_device::*;
+use super::ok_host::OpalKellyHost;
+
+/// Half the period of the 48 MHz host interface clock, in picoseconds.  A simulation
+/// clocks the [OpalKellyHost] by toggling bit 0 of `hi.sig_in` at this interval.
+pub const OK_HOST_HALF_PERIOD: u64 = 10_417;
+
+// The FrontPanel bus protocol is proprietary, so the simulation models use their own
+// encoding of the ok1 and ok2 busses.  Each period of ti_clk carries one bus cycle:
+//    ok1[15:0]  - data
+//    ok1[23:16] - endpoint address
+//    ok1[27:24] - command
+//    ok1[30]    - ti_clk (so that endpoints without a clock input can see it)
+// The host puts a new cycle on ok1 at the falling edge of ti_clk, so the endpoint outputs
+// that the firmware sees (write, read, blockstrobe, dataout) are stable at the rising
+// edge.  An endpoint answers a read command on ok2 during the following cycle (ok2[15:0]
+// is the data, and ok2[16] is the ready flag of a block throttled pipe), and the host
+// samples the answer at the next rising edge.  Idle endpoints drive ok2 to zero, since
+// the ok2 busses of the endpoints are OR-ed together.
+pub(crate) const OK_CMD_IDLE: u8 = 0;
+pub(crate) const OK_CMD_WIRE_IN: u8 = 1;
+pub(crate) const OK_CMD_WIRE_OUT: u8 = 2;
+pub(crate) const OK_CMD_TRIGGER_IN: u8 = 3;
+pub(crate) const OK_CMD_TRIGGER_OUT: u8 = 4;
+pub(crate) const OK_CMD_PIPE_IN: u8 = 5;
+pub(crate) const OK_CMD_PIPE_OUT: u8 = 6;
+pub(crate) const OK_CMD_BLOCK_STROBE: u8 = 7;
+pub(crate) const OK_CMD_BLOCK_READY: u8 = 8;
+
+pub(crate) const OK2_READY: u64 = 1 << 16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct OkBusCycle {
+    pub(crate) cmd: u8,
+    pub(crate) addr: u8,
+    pub(crate) data: u16,
+}
+
+impl OkBusCycle {
+    pub(crate) fn new(cmd: u8, addr: u8, data: u16) -> Self {
+        Self { cmd, addr, data }
+    }
+    pub(crate) fn decode(ok1: Bits<31>) -> Self {
+        let x = ok1.to_u64();
+        Self {
+            cmd: ((x >> 24) & 0xF) as u8,
+            addr: ((x >> 16) & 0xFF) as u8,
+            data: (x & 0xFFFF) as u16,
+        }
+    }
+    pub(crate) fn encode(&self, clk: bool) -> Bits<31> {
+        ((self.data as u64)
+            | ((self.addr as u64) << 16)
+            | ((self.cmd as u64) << 24)
+            | ((clk as u64) << 30))
+            .into()
+    }
+    pub(crate) fn is(&self, cmd: u8, addr: u8) -> bool {
+        self.cmd == cmd && self.addr == addr
+    }
+    fn is_read(&self) -> bool {
+        matches!(
+            self.cmd,
+            OK_CMD_WIRE_OUT | OK_CMD_TRIGGER_OUT | OK_CMD_PIPE_OUT | OK_CMD_BLOCK_READY
+        )
+    }
+    fn save(&self, writer: &mut StateWriter) {
+        writer
+            .put_u64(self.cmd as u64)
+            .put_u64(self.addr as u64)
+            .put_u64(self.data as u64);
+    }
+    fn restore(reader: &mut StateReader) -> Option<Self> {
+        Some(Self {
+            cmd: reader.get_u64()?.try_into().ok()?,
+            addr: reader.get_u64()?.try_into().ok()?,
+            data: reader.get_u64()?.try_into().ok()?,
+        })
+    }
+}
+
+// The simulation state of an endpoint
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OkEndpointSim {
+    clk: bool,
+    // Trigger bits that have not been passed on yet
+    pub(crate) pending: u16,
+    // The answer to a read in the previous cycle (zero if there was none)
+    pub(crate) response: u64,
+    // Set if a pipe read happened in the previous cycle
+    pub(crate) reading: bool,
+}
+
+impl OkEndpointSim {
+    // Returns the bus cycle if ok1 shows a rising edge of ti_clk
+    pub(crate) fn rising_edge(&mut self, ok1: Bits<31>) -> Option<OkBusCycle> {
+        let clk = ok1.get_bit(30);
+        let edge = clk && !self.clk;
+        self.clk = clk;
+        edge.then(|| OkBusCycle::decode(ok1))
+    }
+}
+
+impl SimState for OkEndpointSim {
+    fn save(&self) -> String {
+        StateWriter::default()
+            .put(self.clk)
+            .put_u64(self.pending as u64)
+            .put_u64(self.response)
+            .put(self.reading)
+            .finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let mut read = || -> Option<Self> {
+            Some(Self {
+                clk: reader.get()?,
+                pending: reader.get_u64()?.try_into().ok()?,
+                response: reader.get_u64()?,
+                reading: reader.get()?,
+            })
+        };
+        match read() {
+            Some(x) if reader.is_done() => {
+                *self = x;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum OkHostOp {
+    Cycle(OkBusCycle),
+    // Ask a block throttled pipe if it is ready until it is
+    WaitReady(u8),
+}
+
+// The simulation state of the host - the bus cycles it has been asked to run
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OkHostSim {
+    clk: bool,
+    queue: VecDeque<OkHostOp>,
+    current: OkBusCycle,
+    // The read on the bus in the previous cycle, whose answer is on ok2
+    awaiting: Option<OkBusCycle>,
+    ready: bool,
+    responses: Vec<u16>,
+}
+
+impl OkHostSim {
+    // Advance the host given its clock and the ok2 bus, and return the value of ok1
+    pub(crate) fn tick(&mut self, clk: bool, ok2: Bits<17>) -> Bits<31> {
+        if clk && !self.clk {
+            if let Some(cycle) = self.awaiting.take() {
+                if cycle.cmd == OK_CMD_BLOCK_READY {
+                    self.ready = ok2.to_u64() & OK2_READY != 0;
+                } else {
+                    self.responses.push((ok2.to_u64() & 0xFFFF) as u16);
+                }
+            }
+            if self.current.is_read() {
+                self.awaiting = Some(self.current);
+            }
+        }
+        if !clk && self.clk {
+            self.current = self.next_cycle();
+        }
+        self.clk = clk;
+        self.current.encode(clk)
+    }
+    fn next_cycle(&mut self) -> OkBusCycle {
+        while let Some(op) = self.queue.front() {
+            match *op {
+                OkHostOp::Cycle(cycle) => {
+                    self.queue.pop_front();
+                    return cycle;
+                }
+                OkHostOp::WaitReady(addr) => {
+                    if self.ready {
+                        self.ready = false;
+                        self.queue.pop_front();
+                    } else if self.awaiting.is_some() {
+                        // Wait for the answer to the last question
+                        return OkBusCycle::default();
+                    } else {
+                        return OkBusCycle::new(OK_CMD_BLOCK_READY, addr, 0);
+                    }
+                }
+            }
+        }
+        OkBusCycle::default()
+    }
+    pub(crate) fn queue(&mut self, ops: Vec<OkHostOp>) {
+        self.queue.extend(ops);
+    }
+    pub(crate) fn is_idle(&self) -> bool {
+        self.queue.is_empty() && self.awaiting.is_none() && self.current.cmd == OK_CMD_IDLE
+    }
+    pub(crate) fn take_responses(&mut self) -> Vec<u16> {
+        std::mem::take(&mut self.responses)
+    }
+}
+
+// The host is saved as its flags, the queued operations (a tag of 0 for a
+// bus cycle, and 1 for a wait on a pipe), the current and awaited bus cycles,
+// and the responses that have not been collected.
+impl SimState for OkHostSim {
+    fn save(&self) -> String {
+        let mut writer = StateWriter::default();
+        writer.put(self.clk).put(self.ready);
+        writer.put_u64(self.queue.len() as u64);
+        for op in &self.queue {
+            match op {
+                OkHostOp::Cycle(cycle) => {
+                    writer.put_u64(0);
+                    cycle.save(&mut writer);
+                }
+                OkHostOp::WaitReady(addr) => {
+                    writer.put_u64(1).put_u64(*addr as u64);
+                }
+            }
+        }
+        self.current.save(&mut writer);
+        writer.put(self.awaiting.is_some());
+        if let Some(cycle) = &self.awaiting {
+            cycle.save(&mut writer);
+        }
+        writer.put_u64(self.responses.len() as u64);
+        for response in &self.responses {
+            writer.put_u64(*response as u64);
+        }
+        writer.finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let mut read = || -> Option<Self> {
+            let clk = reader.get()?;
+            let ready = reader.get()?;
+            let queue = (0..reader.get_u64()?)
+                .map(|_| match reader.get_u64()? {
+                    0 => Some(OkHostOp::Cycle(OkBusCycle::restore(&mut reader)?)),
+                    1 => Some(OkHostOp::WaitReady(reader.get_u64()?.try_into().ok()?)),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?;
+            let current = OkBusCycle::restore(&mut reader)?;
+            let awaiting = if reader.get::<bool>()? {
+                Some(OkBusCycle::restore(&mut reader)?)
+            } else {
+                None
+            };
+            let responses = (0..reader.get_u64()?)
+                .map(|_| reader.get_u64()?.try_into().ok())
+                .collect::<Option<_>>()?;
+            Some(Self {
+                clk,
+                queue,
+                current,
+                awaiting,
+                ready,
+                responses,
+            })
+        };
+        match read() {
+            Some(x) if reader.is_done() => {
+                *self = x;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+struct SimOkState<T> {
+    sim: Sim<T>,
+    circuit: Option<Box<T>>,
+    error: Option<SimError>,
+    wire_ins: [u16; 32],
+    wire_outs: [u16; 32],
+    trigger_outs: [u16; 32],
+}
+
+/// A simulated FrontPanel device, with the same methods as [OkHandle](rust_hdl_ok_frontpanel_sys::OkHandle).
+/// It lives in a testbench, and drives the [OpalKellyHost] of the circuit (found with
+/// the `host` function) so that host code can be tested against the firmware without a
+/// board.  Each call runs the simulation until the host has finished the transfer.  The
+/// host clock must be toggled by another testbench, e.g.,
+/// ```ignore
+/// sim.add_clock(OK_HOST_HALF_PERIOD, |x: &mut Box<MyDesign>| {
+///     x.hi.sig_in.next = x.hi.sig_in.val() ^ 1
+/// });
+/// sim.add_testbench(move |sim: Sim<MyDesign>| {
+///     let x = sim.init()?;
+///     let hnd = SimOkHandle::new(sim, x, |x| &mut x.ok_host);
+///     hnd.set_wire_in(0x00, 0x45);
+///     hnd.update_wire_ins();
+///     hnd.done()
+/// });
+/// ```
+/// If the simulation fails during a call, the calls that return a [Result] fail, and
+/// [SimOkHandle::done] returns the simulation error.
+pub struct SimOkHandle<T, F: Fn(&mut T) -> &mut OpalKellyHost> {
+    host: F,
+    state: RefCell<SimOkState<T>>,
+}
+
+fn ok_error(code: i32) -> OkError {
+    OkError { code }
+}
+
+fn check_endpoint(addr: i32, base: i32) -> Result<u8, OkError> {
+    if addr >= base && addr < base + 0x20 {
+        Ok(addr as u8)
+    } else {
+        Err(ok_error(ok_ErrorCode_ok_InvalidEndpoint))
+    }
+}
+
+fn check_block_size(blocksize: i32, len: usize) -> Result<usize, OkError> {
+    if blocksize <= 0 || blocksize % 2 != 0 || !len.is_multiple_of(blocksize as usize) {
+        Err(ok_error(ok_ErrorCode_ok_InvalidBlockSize))
+    } else {
+        Ok(blocksize as usize)
+    }
+}
+
+fn check_alignment(len: usize) -> Result<(), OkError> {
+    if !len.is_multiple_of(2) {
+        Err(ok_error(ok_ErrorCode_ok_DataAlignmentError))
+    } else {
+        Ok(())
+    }
+}
+
+fn pipe_writes(addr: u8, data: &[u8]) -> impl Iterator<Item = OkHostOp> + '_ {
+    data.chunks(2).map(move |x| {
+        OkHostOp::Cycle(OkBusCycle::new(
+            OK_CMD_PIPE_IN,
+            addr,
+            u16::from_le_bytes([x[0], x[1]]),
+        ))
+    })
+}
+
+fn pipe_reads(addr: u8, len: usize) -> impl Iterator<Item = OkHostOp> {
+    (0..len / 2).map(move |_| OkHostOp::Cycle(OkBusCycle::new(OK_CMD_PIPE_OUT, addr, 0)))
+}
+
+// Copy the responses to the reads of a bank of endpoints into its snapshot
+fn copy_endpoints(values: &[u16], snapshot: &mut [u16; 32]) -> Result<(), OkError> {
+    if values.len() != snapshot.len() {
+        return Err(ok_error(ok_ErrorCode_ok_Failed));
+    }
+    snapshot.copy_from_slice(values);
+    Ok(())
+}
+
+fn copy_words(words: &[u16], data: &mut [u8]) {
+    for (word, bytes) in words.iter().zip(data.chunks_mut(2)) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+impl<T, F: Fn(&mut T) -> &mut OpalKellyHost> SimOkHandle<T, F> {
+    pub fn new(sim: Sim<T>, x: Box<T>, host: F) -> Self {
+        Self {
+            host,
+            state: RefCell::new(SimOkState {
+                sim,
+                circuit: Some(x),
+                error: None,
+                wire_ins: [0; 32],
+                wire_outs: [0; 32],
+                trigger_outs: [0; 32],
+            }),
+        }
+    }
+
+    // Run bus cycles on the host until they are done, and return the words read
+    fn transact(&self, ops: Vec<OkHostOp>) -> Result<Vec<u16>, OkError> {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        let mut x = state
+            .circuit
+            .take()
+            .ok_or_else(|| ok_error(ok_ErrorCode_ok_Failed))?;
+        (self.host)(x.as_mut())._sim.queue(ops);
+        loop {
+            x = match state.sim.wait(OK_HOST_HALF_PERIOD, x) {
+                Ok(x) => x,
+                Err(err) => {
+                    state.error = Some(err);
+                    return Err(ok_error(ok_ErrorCode_ok_Failed));
+                }
+            };
+            let host = &mut (self.host)(x.as_mut())._sim;
+            if host.is_idle() {
+                let responses = host.take_responses();
+                state.circuit = Some(x);
+                return Ok(responses);
+            }
+        }
+    }
+
+    /// Let the simulation run for the given time (in picoseconds)
+    pub fn wait(&self, delta: u64) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        if let Some(x) = state.circuit.take() {
+            match state.sim.wait(delta, x) {
+                Ok(x) => state.circuit = Some(x),
+                Err(err) => state.error = Some(err),
+            }
+        }
+    }
+
+    /// Finish with the device, and pass the circuit back to the simulation.  Returns
+    /// the error if the simulation failed during a call.
+    pub fn done(self) -> rust_hdl_core::simulate::Result<()> {
+        let state = self.state.into_inner();
+        match (state.error, state.circuit) {
+            (Some(err), _) => Err(err),
+            (None, Some(x)) => state.sim.done(x),
+            (None, None) => Err(SimError::SimTerminated),
+        }
+    }
+
+    pub fn set_wire_in(&self, addr: i32, val: u16) {
+        let _ = self.try_set_wire_in(addr, val);
+    }
+
+    pub fn try_set_wire_in(&self, addr: i32, val: u16) -> Result<(), OkError> {
+        let addr = check_endpoint(addr, 0x00)?;
+        self.state.borrow_mut().wire_ins[addr as usize] = val;
+        Ok(())
+    }
+
+    pub fn update_wire_ins(&self) {
+        let _ = self.try_update_wire_ins();
+    }
+
+    pub fn try_update_wire_ins(&self) -> Result<(), OkError> {
+        let wire_ins = self.state.borrow().wire_ins;
+        let ops = wire_ins
+            .iter()
+            .enumerate()
+            .map(|(addr, val)| OkHostOp::Cycle(OkBusCycle::new(OK_CMD_WIRE_IN, addr as u8, *val)))
+            .collect();
+        self.transact(ops)?;
+        Ok(())
+    }
+
+    pub fn get_wire_in(&self, addr: i32) -> Result<u16, OkError> {
+        let addr = check_endpoint(addr, 0x00)?;
+        Ok(self.state.borrow().wire_ins[addr as usize])
+    }
+
+    pub fn update_wire_outs(&self) {
+        let _ = self.try_update_wire_outs();
+    }
+
+    pub fn try_update_wire_outs(&self) -> Result<(), OkError> {
+        let ops = (0x20..0x40)
+            .map(|addr| OkHostOp::Cycle(OkBusCycle::new(OK_CMD_WIRE_OUT, addr, 0)))
+            .collect();
+        let values = self.transact(ops)?;
+        copy_endpoints(&values, &mut self.state.borrow_mut().wire_outs)
+    }
+
+    pub fn get_wire_out(&self, addr: i32) -> u16 {
+        check_endpoint(addr, 0x20)
+            .map(|addr| self.state.borrow().wire_outs[addr as usize - 0x20])
+            .unwrap_or_default()
+    }
+
+    pub fn activate_trigger_in(&self, addr: i32, bit: i32) -> Result<(), OkError> {
+        let addr = check_endpoint(addr, 0x40)?;
+        if !(0..16).contains(&bit) {
+            return Err(ok_error(ok_ErrorCode_ok_InvalidParameter));
+        }
+        self.transact(vec![OkHostOp::Cycle(OkBusCycle::new(
+            OK_CMD_TRIGGER_IN,
+            addr,
+            1 << bit,
+        ))])?;
+        Ok(())
+    }
+
+    pub fn update_trigger_outs(&self) {
+        let _ = self.try_update_trigger_outs();
+    }
+
+    pub fn try_update_trigger_outs(&self) -> Result<(), OkError> {
+        let ops = (0x60..0x80)
+            .map(|addr| OkHostOp::Cycle(OkBusCycle::new(OK_CMD_TRIGGER_OUT, addr, 0)))
+            .collect();
+        let values = self.transact(ops)?;
+        copy_endpoints(&values, &mut self.state.borrow_mut().trigger_outs)
+    }
+
+    pub fn is_triggered(&self, addr: i32, mask: i32) -> bool {
+        check_endpoint(addr, 0x60)
+            .map(|addr| self.state.borrow().trigger_outs[addr as usize - 0x60] as i32 & mask != 0)
+            .unwrap_or_default()
+    }
+
+    pub fn write_to_pipe_in(&self, addr: i32, data: &[u8]) -> Result<(), OkError> {
+        let addr = check_endpoint(addr, 0x80)?;
+        check_alignment(data.len())?;
+        self.transact(pipe_writes(addr, data).collect())?;
+        Ok(())
+    }
+
+    pub fn write_to_block_pipe_in(
+        &self,
+        addr: i32,
+        blocksize: i32,
+        data: &[u8],
+    ) -> Result<(), OkError> {
+        let addr = check_endpoint(addr, 0x80)?;
+        let blocksize = check_block_size(blocksize, data.len())?;
+        let mut ops = vec![];
+        for block in data.chunks(blocksize) {
+            ops.push(OkHostOp::WaitReady(addr));
+            ops.push(OkHostOp::Cycle(OkBusCycle::new(
+                OK_CMD_BLOCK_STROBE,
+                addr,
+                0,
+            )));
+            ops.extend(pipe_writes(addr, block));
+        }
+        self.transact(ops)?;
+        Ok(())
+    }
+
+    pub fn read_from_pipe_out(&self, addr: i32, data: &mut [u8]) -> Result<(), OkError> {
+        let addr = check_endpoint(addr, 0xA0)?;
+        check_alignment(data.len())?;
+        let words = self.transact(pipe_reads(addr, data.len()).collect())?;
+        copy_words(&words, data);
+        Ok(())
+    }
+
+    pub fn read_from_block_pipe_out(
+        &self,
+        addr: i32,
+        blocksize: i32,
+        data: &mut [u8],
+    ) -> Result<(), OkError> {
+        let addr = check_endpoint(addr, 0xA0)?;
+        let blocksize = check_block_size(blocksize, data.len())?;
+        let mut ops = vec![];
+        for _ in 0..data.len() / blocksize {
+            ops.push(OkHostOp::WaitReady(addr));
+            ops.push(OkHostOp::Cycle(OkBusCycle::new(
+                OK_CMD_BLOCK_STROBE,
+                addr,
+                0,
+            )));
+            ops.extend(pipe_reads(addr, blocksize));
+        }
+        let words = self.transact(ops)?;
+        copy_words(&words, data);
+        Ok(())
+    }
+}
+
+impl<T, F: Fn(&mut T) -> &mut OpalKellyHost> FrontPanel for SimOkHandle<T, F> {
+    fn set_wire_in(&self, ep: WireInAddr, val: u16) -> Result<(), OkError> {
+        self.try_set_wire_in(ep.addr() as i32, val)
+    }
+
+    fn update_wire_ins(&self) -> Result<(), OkError> {
+        self.try_update_wire_ins()
+    }
+
+    fn update_wire_outs(&self) -> Result<(), OkError> {
+        self.try_update_wire_outs()
+    }
+
+    fn get_wire_out(&self, ep: WireOutAddr) -> Result<u16, OkError> {
+        Ok(self.get_wire_out(ep.addr() as i32))
+    }
+
+    fn activate_trigger_in(&self, ep: TriggerInAddr, bit: u8) -> Result<(), OkError> {
+        self.activate_trigger_in(ep.addr() as i32, bit as i32)
+    }
+
+    fn update_trigger_outs(&self) -> Result<(), OkError> {
+        self.try_update_trigger_outs()
+    }
+
+    fn is_triggered(&self, ep: TriggerOutAddr, mask: u16) -> Result<bool, OkError> {
+        Ok(self.is_triggered(ep.addr() as i32, mask as i32))
+    }
+
+    fn write_to_pipe_in(&self, ep: PipeInAddr, data: &[u8]) -> Result<(), OkError> {
+        self.write_to_pipe_in(ep.addr() as i32, data)
+    }
+
+    fn write_to_block_pipe_in(
+        &self,
+        ep: PipeInAddr,
+        blocksize: usize,
+        data: &[u8],
+    ) -> Result<(), OkError> {
+        let blocksize =
+            i32::try_from(blocksize).map_err(|_| ok_error(ok_ErrorCode_ok_InvalidParameter))?;
+        self.write_to_block_pipe_in(ep.addr() as i32, blocksize, data)
+    }
+
+    fn read_from_pipe_out(&self, ep: PipeOutAddr, data: &mut [u8]) -> Result<(), OkError> {
+        self.read_from_pipe_out(ep.addr() as i32, data)
+    }
+
+    fn read_from_block_pipe_out(
+        &self,
+        ep: PipeOutAddr,
+        blocksize: usize,
+        data: &mut [u8],
+    ) -> Result<(), OkError> {
+        let blocksize =
+            i32::try_from(blocksize).map_err(|_| ok_error(ok_ErrorCode_ok_InvalidParameter))?;
+        self.read_from_block_pipe_out(ep.addr() as i32, blocksize, data)
+    }
+}
@@ -1,3 +1,4 @@
+use super::ok_sim::*;
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
@@ -8,6 +9,7 @@ pub struct TriggerOut {
     pub clk: Signal<In, Clock>,
     pub trigger: Signal<In, Bits<16>>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl TriggerOut {
@@ -19,15 +21,32 @@ impl TriggerOut {
             clk: Default::default(),
             trigger: Default::default(),
             _n: n,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for TriggerOut {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        // Hold on to the triggers until the host asks for them
+        if self.clk.pos_edge() {
+            self._sim.pending |= self.trigger.val().to_u64() as u16;
+        }
+        if let Some(cycle) = self._sim.rising_edge(self.ok1.val()) {
+            self._sim.response = if cycle.is(OK_CMD_TRIGGER_OUT, self._n) {
+                std::mem::take(&mut self._sim.pending) as u64
+            } else {
+                0
+            };
+        }
+        self.ok2.next = self._sim.response.into();
+    }
     fn connect(&mut self) {
         self.ok2.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("TriggerOut_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
@@ -81,6 +100,7 @@ pub struct TriggerIn {
     pub clk: Signal<In, Clock>,
     pub trigger: Signal<Out, Bits<16>>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl TriggerIn {
@@ -91,15 +111,29 @@ impl TriggerIn {
             clk: Default::default(),
             trigger: Default::default(),
             _n: n,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for TriggerIn {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        // The triggers are passed on for one cycle of clk
+        if let Some(cycle) = self._sim.rising_edge(self.ok1.val()) {
+            if cycle.is(OK_CMD_TRIGGER_IN, self._n) {
+                self._sim.pending |= cycle.data;
+            }
+        }
+        if self.clk.pos_edge() {
+            self.trigger.next = (std::mem::take(&mut self._sim.pending) as u64).into();
+        }
+    }
     fn connect(&mut self) {
         self.trigger.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("TriggerIn_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
@@ -1,3 +1,4 @@
+use super::ok_sim::*;
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
@@ -7,6 +8,7 @@ pub struct WireOut {
     pub ok2: Signal<Out, Bits<17>>,
     pub datain: Signal<In, Bits<16>>,
     _n: u8,
+    _sim: OkEndpointSim,
 }
 
 impl WireOut {
@@ -18,15 +20,28 @@ impl WireOut {
             ok2: Default::default(),
             datain: Default::default(),
             _n: port,
+            _sim: Default::default(),
         }
     }
 }
 
 impl Logic for WireOut {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        if let Some(cycle) = self._sim.rising_edge(self.ok1.val()) {
+            self._sim.response = if cycle.is(OK_CMD_WIRE_OUT, self._n) {
+                self.datain.val().to_u64()
+            } else {
+                0
+            };
+        }
+        self.ok2.next = self._sim.response.into();
+    }
     fn connect(&mut self) {
         self.ok2.connect();
     }
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+    }
     fn hdl(&self) -> Verilog {
         let name = format!("WireOut_{:x}", self._n);
         Verilog::Blackbox(BlackBox {
@@ -89,7 +104,12 @@ impl WireIn {
 }
 
 impl Logic for WireIn {
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        let cycle = OkBusCycle::decode(self.ok1.val());
+        if cycle.is(OK_CMD_WIRE_IN, self._n) {
+            self.dataout.next = (cycle.data as u64).into();
+        }
+    }
     fn connect(&mut self) {
         self.dataout.connect();
     }
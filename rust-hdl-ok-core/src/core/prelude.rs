@@ -1,8 +1,11 @@
 pub use super::bsp::*;
+pub use super::mig_sim::*;
+pub use super::ok_device::*;
 pub use super::ok_download::*;
 pub use super::ok_hi::*;
 pub use super::ok_host::*;
 pub use super::ok_pipe::*;
+pub use super::ok_sim::*;
 pub use super::ok_trigger::*;
 pub use super::ok_wire::*;
 pub use super::spi::*;
@@ -0,0 +1,351 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_ok_core::core::prelude::*;
+use rust_hdl_ok_core::frontpanel::{
+    make_u16_buffer, ok_ErrorCode_ok_InvalidBlockSize, ok_ErrorCode_ok_InvalidParameter,
+};
+use rust_hdl_widgets::prelude::*;
+
+use std::num::Wrapping;
+
+fn connect_hi(hi: &mut OpalKellyHostInterface) {
+    hi.sig_in.connect();
+    hi.sig_inout.connect();
+    hi.sig_out.connect();
+    hi.sig_aa.connect();
+}
+
+#[derive(LogicBlock)]
+pub struct OpalKellyWireSimTest {
+    pub hi: OpalKellyHostInterface,
+    pub ok_host: OpalKellyHost,
+    pub wire_0: WireIn,
+    pub wire_1: WireIn,
+    pub o_wire: WireOut,
+    pub o_wire_1: WireOut,
+    pub trig: TriggerIn,
+    pub o_trig: TriggerOut,
+    pub trig_counter: DFF<Bits<16>>,
+}
+
+impl Default for OpalKellyWireSimTest {
+    fn default() -> Self {
+        Self {
+            hi: OpalKellyHostInterface::xem_6010(),
+            ok_host: OpalKellyHost::xem_6010(),
+            wire_0: WireIn::new(0),
+            wire_1: WireIn::new(1),
+            o_wire: WireOut::new(0x20),
+            o_wire_1: WireOut::new(0x21),
+            trig: TriggerIn::new(0x40),
+            o_trig: TriggerOut::new(0x60),
+            trig_counter: Default::default(),
+        }
+    }
+}
+
+impl Logic for OpalKellyWireSimTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        OpalKellyHostInterface::link(&mut self.hi, &mut self.ok_host.hi);
+        self.o_wire.datain.next = self.wire_0.dataout.val() ^ self.wire_1.dataout.val();
+        self.trig_counter.d.next = self.trig_counter.q.val() + self.trig.trigger.val();
+        if self.trig_counter.q.val() == 0x0A {
+            self.o_trig.trigger.next = 0x01.into();
+        } else {
+            self.o_trig.trigger.next = 0x00.into();
+        }
+        self.o_wire_1.datain.next = self.trig_counter.q.val();
+        // Fan out clock
+        self.trig_counter.clock.next = self.ok_host.ti_clk.val();
+        self.trig.clk.next = self.ok_host.ti_clk.val();
+        self.o_trig.clk.next = self.ok_host.ti_clk.val();
+        // Fan out OK1
+        self.wire_0.ok1.next = self.ok_host.ok1.val();
+        self.wire_1.ok1.next = self.ok_host.ok1.val();
+        self.o_wire.ok1.next = self.ok_host.ok1.val();
+        self.o_wire_1.ok1.next = self.ok_host.ok1.val();
+        self.trig.ok1.next = self.ok_host.ok1.val();
+        self.o_trig.ok1.next = self.ok_host.ok1.val();
+        // Wire or in OK2
+        self.ok_host.ok2.next =
+            self.o_wire.ok2.val() | self.o_wire_1.ok2.val() | self.o_trig.ok2.val();
+    }
+}
+
+#[test]
+fn test_opalkelly_wire_sim() {
+    let mut uut = OpalKellyWireSimTest::default();
+    connect_hi(&mut uut.hi);
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(OK_HOST_HALF_PERIOD, |x: &mut Box<OpalKellyWireSimTest>| {
+        x.hi.sig_in.next = x.hi.sig_in.val() ^ 1
+    });
+    sim.add_testbench(move |sim: Sim<OpalKellyWireSimTest>| {
+        let x = sim.init()?;
+        let hnd = SimOkHandle::new(sim, x, |x| &mut x.ok_host);
+        for i in 0..12 {
+            let w1 = if i % 2 == 0 { 0xFF } else { 0x00 };
+            hnd.set_wire_in(0x01, w1);
+            hnd.set_wire_in(0x00, 0x42 + i);
+            hnd.activate_trigger_in(0x40, 0).unwrap();
+            hnd.update_wire_ins();
+            hnd.update_wire_outs();
+            assert_eq!(hnd.get_wire_out(0x20), (0x42 + i) ^ w1);
+            assert_eq!(hnd.get_wire_out(0x21), i + 1);
+            hnd.update_trigger_outs();
+            // The trigger out fires while the counter is at 10, which spans two updates
+            assert_eq!(hnd.is_triggered(0x60, 0xFFFF), i == 9 || i == 10);
+        }
+        assert_eq!(hnd.get_wire_in(0x01).unwrap(), 0x00);
+        assert!(hnd.get_wire_in(0x20).is_err());
+        assert!(hnd.activate_trigger_in(0x20, 0).is_err());
+        assert_eq!(
+            hnd.activate_trigger_in(0x40, 16).unwrap_err().code,
+            ok_ErrorCode_ok_InvalidParameter
+        );
+        hnd.done()
+    });
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+}
+
+#[derive(LogicBlock)]
+pub struct OpalKellyPipeSimTest {
+    pub hi: OpalKellyHostInterface,
+    pub ok_host: OpalKellyHost,
+    pub ram: RAM<Bits<16>, 8>,
+    pub i_pipe: PipeIn,
+    pub o_pipe: PipeOut,
+    pub read_address: DFF<Bits<8>>,
+    pub write_address: DFF<Bits<8>>,
+}
+
+impl Default for OpalKellyPipeSimTest {
+    fn default() -> Self {
+        Self {
+            hi: OpalKellyHostInterface::xem_6010(),
+            ok_host: OpalKellyHost::xem_6010(),
+            ram: RAM::new(Default::default()),
+            i_pipe: PipeIn::new(0x80),
+            o_pipe: PipeOut::new(0xA0),
+            read_address: Default::default(),
+            write_address: Default::default(),
+        }
+    }
+}
+
+impl Logic for OpalKellyPipeSimTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        OpalKellyHostInterface::link(&mut self.hi, &mut self.ok_host.hi);
+        // Clock connections
+        self.read_address.clock.next = self.ok_host.ti_clk.val();
+        self.write_address.clock.next = self.ok_host.ti_clk.val();
+        self.ram.read_clock.next = self.ok_host.ti_clk.val();
+        self.ram.write_clock.next = self.ok_host.ti_clk.val();
+        // Bus connections
+        self.i_pipe.ok1.next = self.ok_host.ok1.val();
+        self.o_pipe.ok1.next = self.ok_host.ok1.val();
+        self.ok_host.ok2.next = self.i_pipe.ok2.val() | self.o_pipe.ok2.val();
+        // Data connections
+        self.ram.read_address.next = self.read_address.q.val();
+        self.ram.write_address.next = self.write_address.q.val();
+        self.o_pipe.datain.next = self.ram.read_data.val();
+        self.ram.write_data.next = self.i_pipe.dataout.val();
+        self.ram.write_enable.next = self.i_pipe.write.val();
+        // Advance the address counters
+        self.write_address.d.next = self.write_address.q.val() + self.i_pipe.write.val();
+        self.read_address.d.next = self.read_address.q.val() + self.o_pipe.read.val();
+    }
+}
+
+#[test]
+fn test_opalkelly_pipe_sim() {
+    let mut uut = OpalKellyPipeSimTest::default();
+    connect_hi(&mut uut.hi);
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(OK_HOST_HALF_PERIOD, |x: &mut Box<OpalKellyPipeSimTest>| {
+        x.hi.sig_in.next = x.hi.sig_in.val() ^ 1
+    });
+    sim.add_testbench(move |sim: Sim<OpalKellyPipeSimTest>| {
+        let x = sim.init()?;
+        let hnd = SimOkHandle::new(sim, x, |x| &mut x.ok_host);
+        let data = (0..512).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        hnd.write_to_pipe_in(0x80, &data).unwrap();
+        // The RAM holds 256 words, so the address wraps around after each read
+        let mut out = vec![0_u8; 512];
+        hnd.read_from_pipe_out(0xA0, &mut out).unwrap();
+        assert_eq!(data, out);
+        let mut out2 = vec![0_u8; 512];
+        hnd.read_from_pipe_out(0xA0, &mut out2).unwrap();
+        assert_eq!(data, out2);
+        assert!(hnd.write_to_pipe_in(0x80, &data[0..3]).is_err());
+        assert!(hnd.read_from_pipe_out(0x80, &mut out).is_err());
+        hnd.done()
+    });
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+}
+
+const RAM_IN: PipeInAddr = PipeInAddr::new::<0x80>();
+const RAM_OUT: PipeOutAddr = PipeOutAddr::new::<0xA0>();
+
+// Host code written against the FrontPanel trait, so it can run on hardware or in simulation
+fn ram_round_trip<D: FrontPanel>(dev: &D, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::{Read, Write};
+    let mut writer = PipeWriter::new(dev, RAM_IN, 128)?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    let mut reader = PipeReader::new(dev, RAM_OUT, 64)?;
+    let mut out = vec![0_u8; data.len()];
+    reader.read_exact(&mut out)?;
+    Ok(out)
+}
+
+#[test]
+fn test_opalkelly_pipe_io_sim() {
+    assert!(WireInAddr::try_from(0x20).is_err());
+    assert_eq!(PipeOutAddr::try_from(0xBF).unwrap().addr(), 0xBF);
+    let mut uut = OpalKellyPipeSimTest::default();
+    connect_hi(&mut uut.hi);
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(OK_HOST_HALF_PERIOD, |x: &mut Box<OpalKellyPipeSimTest>| {
+        x.hi.sig_in.next = x.hi.sig_in.val() ^ 1
+    });
+    sim.add_testbench(move |sim: Sim<OpalKellyPipeSimTest>| {
+        let x = sim.init()?;
+        let hnd = SimOkHandle::new(sim, x, |x| &mut x.ok_host);
+        let data = (0..512).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        assert_eq!(ram_round_trip(&hnd, &data).unwrap(), data);
+        // A partial word cannot be flushed to the pipe
+        assert!(ram_round_trip(&hnd, &data[0..5]).is_err());
+        assert!(PipeWriter::new(&hnd, RAM_IN, 3).is_err());
+        assert!(PipeReader::block_throttled(&hnd, RAM_OUT, 48, 64).is_err());
+        hnd.done()
+    });
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+}
+
+#[derive(LogicBlock)]
+pub struct OpalKellyBTPipeSimTest {
+    pub hi: OpalKellyHostInterface,
+    pub ok_host: OpalKellyHost,
+    pub i_pipe: BTPipeIn,
+    pub o_pipe: BTPipeOut,
+    pub blocks: WireOut,
+    pub sum: WireOut,
+    pub block_count: DFF<Bits<16>>,
+    pub accum: DFF<Bits<16>>,
+    pub in_busy: DFF<Bits<8>>,
+    pub out_busy: DFF<Bits<8>>,
+    pub delay_read: DFF<Bit>,
+    pub counter: DFF<Bits<16>>,
+}
+
+impl Default for OpalKellyBTPipeSimTest {
+    fn default() -> Self {
+        Self {
+            hi: OpalKellyHostInterface::xem_6010(),
+            ok_host: OpalKellyHost::xem_6010(),
+            i_pipe: BTPipeIn::new(0x80),
+            o_pipe: BTPipeOut::new(0xA0),
+            blocks: WireOut::new(0x20),
+            sum: WireOut::new(0x21),
+            block_count: Default::default(),
+            accum: Default::default(),
+            in_busy: Default::default(),
+            out_busy: Default::default(),
+            delay_read: Default::default(),
+            counter: Default::default(),
+        }
+    }
+}
+
+impl Logic for OpalKellyBTPipeSimTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        OpalKellyHostInterface::link(&mut self.hi, &mut self.ok_host.hi);
+        // Clock connections
+        self.block_count.clock.next = self.ok_host.ti_clk.val();
+        self.accum.clock.next = self.ok_host.ti_clk.val();
+        self.in_busy.clock.next = self.ok_host.ti_clk.val();
+        self.out_busy.clock.next = self.ok_host.ti_clk.val();
+        self.delay_read.clock.next = self.ok_host.ti_clk.val();
+        self.counter.clock.next = self.ok_host.ti_clk.val();
+        // Latch by default
+        self.block_count.d.next = self.block_count.q.val();
+        self.accum.d.next = self.accum.q.val();
+        self.in_busy.d.next = self.in_busy.q.val();
+        self.out_busy.d.next = self.out_busy.q.val();
+        // Bus connections
+        self.i_pipe.ok1.next = self.ok_host.ok1.val();
+        self.o_pipe.ok1.next = self.ok_host.ok1.val();
+        self.blocks.ok1.next = self.ok_host.ok1.val();
+        self.sum.ok1.next = self.ok_host.ok1.val();
+        self.ok_host.ok2.next = self.i_pipe.ok2.val()
+            | self.o_pipe.ok2.val()
+            | self.blocks.ok2.val()
+            | self.sum.ok2.val();
+        // The input pipe is busy for a while after each block starts
+        self.i_pipe.ready.next = self.in_busy.q.val() == 0;
+        if self.i_pipe.blockstrobe.val() {
+            self.in_busy.d.next = 200.into();
+            self.block_count.d.next = self.block_count.q.val() + 1;
+        } else if self.in_busy.q.val() != 0 {
+            self.in_busy.d.next = self.in_busy.q.val() - 1;
+        }
+        if self.i_pipe.write.val() {
+            self.accum.d.next = self.accum.q.val() + self.i_pipe.dataout.val();
+        }
+        self.blocks.datain.next = self.block_count.q.val();
+        self.sum.datain.next = self.accum.q.val();
+        // The output pipe counts the words read, and is also busy after each block starts
+        self.o_pipe.ready.next = self.out_busy.q.val() == 0;
+        if self.o_pipe.blockstrobe.val() {
+            self.out_busy.d.next = 200.into();
+        } else if self.out_busy.q.val() != 0 {
+            self.out_busy.d.next = self.out_busy.q.val() - 1;
+        }
+        self.delay_read.d.next = self.o_pipe.read.val();
+        self.counter.d.next = self.counter.q.val() + self.delay_read.q.val();
+        self.o_pipe.datain.next = self.counter.q.val();
+    }
+}
+
+#[test]
+fn test_opalkelly_btpipe_sim() {
+    let mut uut = OpalKellyBTPipeSimTest::default();
+    connect_hi(&mut uut.hi);
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(
+        OK_HOST_HALF_PERIOD,
+        |x: &mut Box<OpalKellyBTPipeSimTest>| x.hi.sig_in.next = x.hi.sig_in.val() ^ 1,
+    );
+    sim.add_testbench(move |sim: Sim<OpalKellyBTPipeSimTest>| {
+        let x = sim.init()?;
+        let hnd = SimOkHandle::new(sim, x, |x| &mut x.ok_host);
+        let data = (0..1024).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        hnd.write_to_block_pipe_in(0x80, 256, &data).unwrap();
+        hnd.update_wire_outs();
+        let sum = make_u16_buffer(&data)
+            .iter()
+            .fold(Wrapping(0_u16), |a, x| a + Wrapping(*x));
+        assert_eq!(hnd.get_wire_out(0x20), 4);
+        assert_eq!(hnd.get_wire_out(0x21), sum.0);
+        let mut out = vec![0_u8; 1024];
+        hnd.read_from_block_pipe_out(0xA0, 128, &mut out).unwrap();
+        for (ndx, val) in make_u16_buffer(&out).iter().enumerate() {
+            assert_eq!(*val, ndx as u16);
+        }
+        assert_eq!(
+            hnd.write_to_block_pipe_in(0x80, 100, &data)
+                .unwrap_err()
+                .code,
+            ok_ErrorCode_ok_InvalidBlockSize
+        );
+        hnd.done()
+    });
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+}
@@ -27,6 +27,20 @@ impl OkError {
     }
 }
 
+impl std::fmt::Display for OkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FrontPanel error code {}", self.code)
+    }
+}
+
+impl std::error::Error for OkError {}
+
+impl From<OkError> for std::io::Error {
+    fn from(err: OkError) -> Self {
+        std::io::Error::other(err)
+    }
+}
+
 impl Drop for OkHandle {
     fn drop(&mut self) {
         self.close();
@@ -133,6 +147,17 @@ impl OkHandle {
         unsafe { okFrontPanel_UpdateWireIns(self.hnd) };
     }
 
+    // The try_ versions of the update functions return any error from the device
+    pub fn try_set_wire_in(&self, addr: i32, val: u16) -> Result<(), OkError> {
+        OkError::make_result(unsafe {
+            okFrontPanel_SetWireInValue(self.hnd, addr, val as u64, 0xFFFF)
+        })
+    }
+
+    pub fn try_update_wire_ins(&self) -> Result<(), OkError> {
+        OkError::make_result(unsafe { okFrontPanel_UpdateWireIns(self.hnd) })
+    }
+
     pub fn get_wire_in(&self, addr: i32) -> Result<u16, OkError> {
         let mut val: u32 = 0;
         let ecode = unsafe { okFrontPanel_GetWireInValue(self.hnd, addr, &mut val) };
@@ -147,6 +172,10 @@ impl OkHandle {
         unsafe { okFrontPanel_UpdateWireOuts(self.hnd) };
     }
 
+    pub fn try_update_wire_outs(&self) -> Result<(), OkError> {
+        OkError::make_result(unsafe { okFrontPanel_UpdateWireOuts(self.hnd) })
+    }
+
     pub fn get_wire_out(&self, addr: i32) -> u16 {
         let val = unsafe { okFrontPanel_GetWireOutValue(self.hnd, addr) } as u16;
         val
@@ -169,6 +198,10 @@ impl OkHandle {
         }
     }
 
+    pub fn try_update_trigger_outs(&self) -> Result<(), OkError> {
+        OkError::make_result(unsafe { okFrontPanel_UpdateTriggerOuts(self.hnd) })
+    }
+
     pub fn is_triggered(&self, addr: i32, mask: i32) -> bool {
         let b = unsafe { okFrontPanel_IsTriggered(self.hnd, addr, mask as _) };
         if b == 0 {
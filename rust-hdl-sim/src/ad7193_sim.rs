@@ -1,40 +1,19 @@
+use crate::register_map::spi::{SPICommandFormat, SPIRegisterMap};
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 use std::time::Duration;
 
-#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
-enum AD7193State {
-    Init,
-    Ready,
-    GettingCmd,
-    ReadCmd,
-    WaitSlaveIdle,
-    WriteCmd,
-    DoWrite,
-    SingleConversion,
-    SingleConversionCommit,
-}
-
 #[derive(LogicBlock)]
 pub struct AD7193Simulator {
     // Slave SPI bus
     pub wires: SPIWiresSlave,
     pub clock: Signal<In, Clock>,
-    // ROM that stores register widths
-    reg_width_rom: ROM<Bits<5>, 3>,
-    // RAM that stores register contents
-    reg_ram: RAM<Bits<24>, 3>,
+    // The register map and SPI interface
+    regs: SPIRegisterMap<3, 24>,
     // Used to time a single conversion
     oneshot: Shot<32>,
-    // Separate bits out of a SPI message
-    cmd: Signal<Local, Bits<8>>,
-    reg_index: Signal<Local, Bits<3>>,
-    rw_flag: Signal<Local, Bit>,
-    // The spi slave device
-    spi_slave: SPISlave<64>,
-    // FSM state
-    state: DFF<AD7193State>,
-    reg_write_index: DFF<Bits<3>>,
+    // Set while a single conversion is running
+    converting: DFF<Bit>,
     // Rolling counter to emulate conversions
     conversion_counter: DFF<Bits<24>>,
 }
@@ -77,23 +56,38 @@ impl AD7193Config {
 pub const AD7193_REG_WIDTHS: [u32; 8] = [8, 24, 24, 24, 8, 8, 24, 24];
 const AD7193_REG_INITS: [u64; 8] = [0x40, 0x80060, 0x117, 0x0, 0xa2, 0x0, 0x800000, 0x5544d0];
 
+// Commands are <0> <r/w> <reg_address> <cont> <0> <0>, and the register
+// contents are followed by the status register on a read.
+const AD7193_COMMAND_FORMAT: SPICommandFormat = SPICommandFormat {
+    command_bits: 8,
+    address_offset: 3,
+    write_bit: 6,
+    write_level: false,
+    device_commands: None,
+    zero_is_device_command: false,
+    read_turnaround_bits: 0,
+    status_bits: 8,
+    status_value: 0xBA,
+    write_echo: false,
+    auto_increment: false,
+    reset_on_ones: true,
+    framed: false,
+    idle_value: 0xFFFF_FFFF,
+};
+
 impl AD7193Simulator {
     pub fn new(config: AD7193Config) -> Self {
-        assert!(config.spi.clock_speed > 10 * config.spi.speed_hz);
-        let reg_width_rom = AD7193_REG_WIDTHS.iter().map(|x| x.to_bits()).into();
-        let reg_ram = AD7193_REG_INITS.iter().map(|x| x.to_bits()).into();
         Self {
             wires: Default::default(),
             clock: Default::default(),
-            reg_width_rom,
-            reg_ram,
+            regs: SPIRegisterMap::new(
+                config.spi,
+                AD7193_COMMAND_FORMAT,
+                &AD7193_REG_WIDTHS.map(|x| x as usize),
+                &AD7193_REG_INITS,
+            ),
             oneshot: Shot::new(config.spi.clock_speed, config.sample_time),
-            cmd: Default::default(),
-            reg_index: Default::default(),
-            rw_flag: Default::default(),
-            spi_slave: SPISlave::new(config.spi),
-            state: Default::default(),
-            reg_write_index: Default::default(),
+            converting: Default::default(),
             conversion_counter: Default::default(),
         }
     }
@@ -103,107 +97,34 @@ impl Logic for AD7193Simulator {
     #[hdl_gen]
     fn update(&mut self) {
         // Connect the spi bus
-        SPIWiresSlave::link(&mut self.wires, &mut self.spi_slave.wires);
+        SPIWiresSlave::link(&mut self.wires, &mut self.regs.wires);
         // Clock internal components
-        self.reg_ram.read_clock.next = self.clock.val();
-        self.reg_ram.write_clock.next = self.clock.val();
-        clock!(self, clock, oneshot, spi_slave);
-        dff_setup!(self, clock, state, reg_write_index, conversion_counter);
+        clock!(self, clock, regs, oneshot);
+        dff_setup!(self, clock, converting, conversion_counter);
         // Set default values
-        self.spi_slave.start_send.next = false;
-        self.cmd.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
-        self.reg_index.next = self.cmd.val().get_bits::<3>(3);
-        self.rw_flag.next = self.cmd.val().get_bit(6);
-        self.reg_width_rom.address.next = self.reg_index.val();
-        self.reg_ram.read_address.next = self.reg_index.val();
-        self.reg_ram.write_address.next = self.reg_index.val();
-        self.spi_slave.continued_transaction.next = false;
-        self.spi_slave.bits.next = 0.into();
-        self.spi_slave.data_outbound.next = 0.into();
-        self.reg_ram.write_enable.next = false;
-        self.reg_ram.write_data.next = 0.into();
-        self.spi_slave.disabled.next = false;
+        self.regs.update_address.next = 3.into();
+        self.regs.update_data.next = self.conversion_counter.q.val();
+        self.regs.update_enable.next = false;
+        self.regs.peek_address.next = 0.into();
+        self.regs.peek_enable.next = false;
+        self.regs.reply.next = 0.into();
+        self.regs.reply_bits.next = 0.into();
+        // The interface is shut off while a conversion is running
+        self.regs.hold.next = self.converting.q.val();
+        self.regs.disabled.next = self.converting.q.val();
         self.oneshot.trigger.next = false;
-        match self.state.q.val() {
-            AD7193State::Init => {
-                if self.spi_slave.transfer_done.val() {
-                    self.state.d.next = AD7193State::Ready;
-                }
-            }
-            AD7193State::Ready => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.data_outbound.next = 0xFF.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = AD7193State::GettingCmd;
-            }
-            AD7193State::GettingCmd => {
-                if self.spi_slave.transfer_done.val() {
-                    if self.rw_flag.val() {
-                        self.state.d.next = AD7193State::ReadCmd;
-                    } else {
-                        self.reg_write_index.d.next = self.reg_index.val();
-                        self.state.d.next = AD7193State::WriteCmd;
-                    }
-                }
-            }
-            AD7193State::ReadCmd => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = bit_cast::<16, 5>(self.reg_width_rom.data.val()) + 8;
-                self.spi_slave.data_outbound.next =
-                    (bit_cast::<64, 24>(self.reg_ram.read_data.val()) << 8)
-                        | Bits::<64>::from(0xBA);
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = AD7193State::WaitSlaveIdle;
-            }
-            AD7193State::WriteCmd => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = bit_cast::<16, 5>(self.reg_width_rom.data.val());
-                self.spi_slave.data_outbound.next = 0xFFFF_FFFF_u64.to_bits();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = AD7193State::DoWrite;
-            }
-            AD7193State::DoWrite => {
-                if self.spi_slave.transfer_done.val() {
-                    self.reg_ram.write_data.next =
-                        bit_cast::<24, 64>(self.spi_slave.data_inbound.val());
-                    self.reg_ram.write_enable.next = true;
-                    self.reg_ram.write_address.next = self.reg_write_index.q.val();
-                    self.state.d.next = AD7193State::WaitSlaveIdle;
-                    if (self.reg_write_index.q.val() == 1)
-                        & self.spi_slave.data_inbound.val().get_bit(21)
-                    {
-                        self.state.d.next = AD7193State::SingleConversion;
-                        self.oneshot.trigger.next = true;
-                    }
-                }
-            }
-            AD7193State::WaitSlaveIdle => {
-                if !self.spi_slave.busy.val() {
-                    self.state.d.next = AD7193State::Ready;
-                }
-            }
-            AD7193State::SingleConversion => {
-                self.spi_slave.disabled.next = true;
-                if self.oneshot.fired.val() {
-                    self.state.d.next = AD7193State::SingleConversionCommit;
-                }
-            }
-            AD7193State::SingleConversionCommit => {
-                self.reg_ram.write_address.next = 3.into();
-                self.reg_ram.write_data.next = self.conversion_counter.q.val();
-                self.reg_ram.write_enable.next = true;
-                self.conversion_counter.d.next = self.conversion_counter.q.val() + 0x100;
-                self.spi_slave.data_outbound.next = 0.into();
-                self.state.d.next = AD7193State::Ready;
-            }
-            _ => {
-                self.state.d.next = AD7193State::Init;
-            }
+        // Setting the single conversion mode in the mode register starts a conversion
+        if self.regs.write_strobe.val()
+            & (self.regs.write_address.val() == 1)
+            & self.regs.write_data.val().get_bit(21)
+        {
+            self.converting.d.next = true;
+            self.oneshot.trigger.next = true;
         }
-        if self.spi_slave.transfer_done.val() & self.spi_slave.data_inbound.val().all() {
-            println!("Reset encountered");
-            self.state.d.next = AD7193State::Ready;
+        if self.converting.q.val() & self.oneshot.fired.val() {
+            self.regs.update_enable.next = true;
+            self.conversion_counter.d.next = self.conversion_counter.q.val() + 0x100;
+            self.converting.d.next = false;
         }
     }
 }
@@ -1,40 +1,13 @@
+use crate::register_map::spi::{SPICommandFormat, SPIRegisterMap};
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
-#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
-enum State {
-    Init,
-    Ready,
-    GettingCmd,
-    WaitSlaveIdle,
-    DoWrite,
-    DoRead,
-    DoNoop,
-    DoCommand,
-    ReadBack,
-    DoConvert,
-}
-
 #[derive(LogicBlock)]
 pub struct ADS8688Simulator {
     pub wires: SPIWiresSlave,
     pub clock: Signal<In, Clock>,
-    // RAM that stores the register contents (these are Program Registers)
-    reg_ram: RAM<Bits<8>, 6>,
-    // Used to split bits out of the SPI message
-    // Organized as: <cmd_flag> <reg_address> <r/w>
-    reg_address: Signal<Local, Bits<6>>,
-    rw_flag: Signal<Local, Bit>,
-    cmd_flag: Signal<Local, Bit>,
-    noop_flag: Signal<Local, Bit>,
-    // The SPI slave device
-    spi_slave: SPISlave<64>,
-    // FSM state
-    state: DFF<State>,
-    // FLOP to hold the register address
-    reg_address_flop: DFF<Bits<6>>,
-    // FLOP to hold the last written value
-    readback_flop: DFF<Bits<8>>,
+    // The Program Registers and SPI interface
+    regs: SPIRegisterMap<6, 8>,
     // Rolling counter to emulate conversions
     conversion_counter: DFF<Bits<12>>,
     // Command register
@@ -66,22 +39,37 @@ fn test_ram_init_vec_is_correct() {
     assert_eq!(vec[0x3A], 0xFF);
 }
 
+// Commands are organized as: <cmd_flag> <reg_address> <r/w>.  Commands
+// with the flag set (and the all zero NO_OP) are followed by a 16 bit
+// conversion result.  Register reads have a byte of turnaround, and
+// register writes are echoed back.
+const ADS8688_COMMAND_FORMAT: SPICommandFormat = SPICommandFormat {
+    command_bits: 8,
+    address_offset: 1,
+    write_bit: 0,
+    write_level: true,
+    device_commands: Some((0x80, 0x80)),
+    zero_is_device_command: true,
+    read_turnaround_bits: 8,
+    status_bits: 0,
+    status_value: 0,
+    write_echo: true,
+    auto_increment: false,
+    reset_on_ones: false,
+    framed: false,
+    idle_value: 0,
+};
+
 impl ADS8688Simulator {
     pub fn new(config: SPIConfig) -> Self {
-        assert!(config.clock_speed > 10 * config.speed_hz);
-        let reg_ram = ram_init_vec().iter().map(|x| x.to_bits()).into();
+        let inits = ram_init_vec()
+            .into_iter()
+            .map(|x| x as u64)
+            .collect::<Vec<_>>();
         Self {
             wires: Default::default(),
             clock: Default::default(),
-            reg_ram,
-            reg_address: Default::default(),
-            rw_flag: Default::default(),
-            cmd_flag: Default::default(),
-            noop_flag: Default::default(),
-            spi_slave: SPISlave::new(config),
-            state: Default::default(),
-            reg_address_flop: Default::default(),
-            readback_flop: Default::default(),
+            regs: SPIRegisterMap::new(config, ADS8688_COMMAND_FORMAT, &[8; 64], &inits),
             conversion_counter: Default::default(),
             command_register: Default::default(),
             output_register: Default::default(),
@@ -93,133 +81,37 @@ impl Logic for ADS8688Simulator {
     #[hdl_gen]
     fn update(&mut self) {
         // Connect the spi bus
-        SPIWiresSlave::link(&mut self.wires, &mut self.spi_slave.wires);
+        SPIWiresSlave::link(&mut self.wires, &mut self.regs.wires);
         // Clock internal components
-        self.reg_ram.read_clock.next = self.clock.val();
-        self.reg_ram.write_clock.next = self.clock.val();
-        clock!(self, clock, spi_slave);
+        clock!(self, clock, regs);
         dff_setup!(
             self,
             clock,
-            state,
-            readback_flop,
-            reg_address_flop,
             conversion_counter,
             output_register,
             command_register
         );
-        // Set default values, and unpack the command word
-        self.spi_slave.start_send.next = false;
-        self.cmd_flag.next = self.spi_slave.data_inbound.val().get_bit(7);
-        self.reg_address.next = self.spi_slave.data_inbound.val().get_bits::<6>(1);
-        self.rw_flag.next = self.spi_slave.data_inbound.val().get_bit(0);
-        self.noop_flag.next = !self.spi_slave.data_inbound.val().get_bits::<8>(0).any();
-        self.reg_ram.read_address.next = self.reg_address_flop.q.val();
-        self.reg_ram.write_address.next = self.reg_address_flop.q.val();
-        self.spi_slave.continued_transaction.next = false;
-        self.spi_slave.bits.next = 0.into();
-        self.spi_slave.data_outbound.next = 0.into();
-        self.reg_ram.write_enable.next = false;
-        self.reg_ram.write_data.next = 0.into();
-        self.spi_slave.disabled.next = false;
-        match self.state.q.val() {
-            State::Init => {
-                if !self.spi_slave.busy.val() {
-                    self.state.d.next = State::Ready;
-                }
-            }
-            State::Ready => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.data_outbound.next = 0x00.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = State::GettingCmd;
-            }
-            State::GettingCmd => {
-                self.reg_address_flop.d.next = self.reg_address.val();
-                if self.spi_slave.transfer_done.val() {
-                    self.state.d.next = State::WaitSlaveIdle;
-                    if self.noop_flag.val() {
-                        self.state.d.next = State::DoNoop;
-                    } else {
-                        if self.cmd_flag.val() {
-                            self.state.d.next = State::DoCommand;
-                        } else {
-                            if self.rw_flag.val() {
-                                self.spi_slave.continued_transaction.next = true;
-                                self.spi_slave.bits.next = 8.into();
-                                self.spi_slave.start_send.next = true;
-                                self.state.d.next = State::DoWrite;
-                            } else {
-                                self.spi_slave.continued_transaction.next = true;
-                                self.spi_slave.bits.next = 8.into();
-                                self.spi_slave.start_send.next = true;
-                                self.state.d.next = State::DoRead;
-                            }
-                        }
-                    }
-                }
-            }
-            State::WaitSlaveIdle => {
-                if !self.spi_slave.busy.val() {
-                    self.state.d.next = State::Ready;
-                }
-            }
-            State::DoRead => {
-                if self.spi_slave.transfer_done.val() {
-                    self.spi_slave.continued_transaction.next = true;
-                    self.spi_slave.bits.next = 8.into();
-                    self.spi_slave.data_outbound.next =
-                        bit_cast::<64, 8>(self.reg_ram.read_data.val());
-                    self.spi_slave.start_send.next = true;
-                    self.state.d.next = State::WaitSlaveIdle;
-                }
-            }
-            State::DoWrite => {
-                if self.spi_slave.transfer_done.val() {
-                    self.reg_ram.write_data.next =
-                        self.spi_slave.data_inbound.val().get_bits::<8>(0);
-                    self.readback_flop.d.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
-                    self.reg_ram.write_enable.next = true;
-                    self.state.d.next = State::ReadBack;
-                }
-            }
-            State::ReadBack => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.data_outbound.next = bit_cast::<64, 8>(self.readback_flop.q.val());
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = State::WaitSlaveIdle;
-            }
-            State::DoNoop => {
-                self.state.d.next = State::DoConvert;
-                self.output_register.d.next =
-                    bit_cast::<16, 3>(self.command_register.q.val().get_bits::<3>(2)) << 12
-                        | bit_cast::<16, 12>(self.conversion_counter.q.val());
-                self.conversion_counter.d.next = self.conversion_counter.q.val() + 1;
-            }
-            State::DoCommand => {
-                self.command_register.d.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
-                self.reg_ram.write_address.next = 0x3F.into();
-                self.reg_ram.write_data.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
-                self.reg_ram.write_enable.next = true;
-                self.state.d.next = State::DoConvert;
-                self.output_register.d.next =
-                    bit_cast::<16, 3>(self.command_register.q.val().get_bits::<3>(2)) << 12
-                        | bit_cast::<16, 12>(self.conversion_counter.q.val());
-                self.conversion_counter.d.next = self.conversion_counter.q.val() + 1;
-            }
-            State::DoConvert => {
-                self.spi_slave.data_outbound.next =
-                    bit_cast::<64, 16>(self.output_register.q.val());
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 16.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = State::WaitSlaveIdle;
-            }
-            _ => {
-                self.state.d.next = State::Init;
+        // Set default values
+        self.regs.update_address.next = 0x3F.into();
+        self.regs.update_data.next = self.regs.command.val().get_bits::<8>(0);
+        self.regs.update_enable.next = false;
+        self.regs.peek_address.next = 0.into();
+        self.regs.peek_enable.next = false;
+        self.regs.reply.next = bit_cast::<64, 16>(self.output_register.q.val());
+        self.regs.reply_bits.next = 16.into();
+        self.regs.hold.next = false;
+        self.regs.disabled.next = false;
+        // Both commands and the NO_OP return the next conversion
+        if self.regs.command_strobe.val() {
+            // Commands (but not the NO_OP) are stored in the command read back register
+            if self.regs.command.val().any() {
+                self.command_register.d.next = self.regs.command.val().get_bits::<8>(0);
+                self.regs.update_enable.next = true;
             }
+            self.output_register.d.next =
+                bit_cast::<16, 3>(self.command_register.q.val().get_bits::<3>(2)) << 12
+                    | bit_cast::<16, 12>(self.conversion_counter.q.val());
+            self.conversion_counter.d.next = self.conversion_counter.q.val() + 1;
         }
     }
 }
@@ -1,10 +1,11 @@
+use crate::register_map::spi::{SPICommandFormat, SPIRegisterMap};
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
 #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
 enum ADS868XState {
-    Ready,
-    Waiting,
+    Nop,
+    Idle,
     Dispatch,
     ReadWordCmd,
     ReadByteCmd,
@@ -12,23 +13,23 @@ enum ADS868XState {
     WriteMSBCmd,
     WriteLSBCmd,
     WriteDone,
-    Nop,
 }
 
 #[derive(LogicBlock)]
 pub struct ADS868XSimulator {
     pub wires: SPIWiresSlave,
     pub clock: Signal<In, Clock>,
-    // RAM to store register values
-    reg_ram: RAM<Bits<16>, 5>,
-    // SPI slave device
-    spi_slave: SPISlave<32>,
+    // The registers and SPI interface
+    regs: SPIRegisterMap<5, 16>,
     // FSM State
     state: DFF<ADS868XState>,
     // Rolling counter to emulate conversions
     conversion_counter: DFF<Bits<16>>,
     // Inbound register
     inbound: DFF<Bits<32>>,
+    // The reply that goes out with the next frame
+    reply: DFF<Bits<32>>,
+    reply_bits: DFF<Bits<16>>,
     // Local signal to store the command bits
     read_cmd: Signal<Local, Bits<5>>,
     write_cmd: Signal<Local, Bits<7>>,
@@ -37,6 +38,25 @@ pub struct ADS868XSimulator {
     id_parity: Signal<Local, Bit>,
 }
 
+// Every 32 bit frame is a command that is decoded by the device, and the
+// response is sent back in the next frame.
+const ADS868X_COMMAND_FORMAT: SPICommandFormat = SPICommandFormat {
+    command_bits: 32,
+    address_offset: 0,
+    write_bit: 0,
+    write_level: true,
+    device_commands: Some((0, 0)),
+    zero_is_device_command: true,
+    read_turnaround_bits: 0,
+    status_bits: 0,
+    status_value: 0,
+    write_echo: false,
+    auto_increment: false,
+    reset_on_ones: false,
+    framed: true,
+    idle_value: 0,
+};
+
 impl ADS868XSimulator {
     pub fn spi_hw() -> SPIConfig {
         SPIConfig {
@@ -60,15 +80,15 @@ impl ADS868XSimulator {
     }
 
     pub fn new(spi_config: SPIConfig) -> Self {
-        assert!(spi_config.clock_speed > 10 * spi_config.speed_hz);
         Self {
             wires: Default::default(),
             clock: Default::default(),
-            reg_ram: Default::default(),
-            spi_slave: SPISlave::new(spi_config),
+            regs: SPIRegisterMap::new(spi_config, ADS868X_COMMAND_FORMAT, &[16; 32], &[]),
             state: Default::default(),
             conversion_counter: Default::default(),
             inbound: Default::default(),
+            reply: Default::default(),
+            reply_bits: Default::default(),
             read_cmd: Default::default(),
             write_cmd: Default::default(),
             address: Default::default(),
@@ -89,125 +109,108 @@ impl Logic for ADS868XSimulator {
     #[hdl_gen]
     fn update(&mut self) {
         // Connect the spi bus
-        SPIWiresSlave::link(&mut self.wires, &mut self.spi_slave.wires);
+        SPIWiresSlave::link(&mut self.wires, &mut self.regs.wires);
         // Clock internal components
-        self.reg_ram.read_clock.next = self.clock.val();
-        self.reg_ram.write_clock.next = self.clock.val();
-        clock!(self, clock, spi_slave);
-        dff_setup!(self, clock, state, conversion_counter, inbound);
+        clock!(self, clock, regs);
+        dff_setup!(
+            self,
+            clock,
+            state,
+            conversion_counter,
+            inbound,
+            reply,
+            reply_bits
+        );
         // Set default values
-        self.spi_slave.start_send.next = false;
-        self.spi_slave.continued_transaction.next = false;
-        self.spi_slave.bits.next = 0.into();
-        self.spi_slave.data_outbound.next = 0.into();
-        self.reg_ram.write_enable.next = false;
-        self.reg_ram.write_data.next = 0.into();
-        self.spi_slave.disabled.next = false;
         self.read_cmd.next = self.inbound.q.val().get_bits::<5>(27);
         self.write_cmd.next = self.inbound.q.val().get_bits::<7>(25);
         self.address.next = self.inbound.q.val().get_bits::<9>(16);
-        self.reg_ram.write_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
-        self.reg_ram.read_address.next = 0.into();
+        self.regs.update_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
+        self.regs.update_data.next = 0.into();
+        self.regs.update_enable.next = false;
+        self.regs.peek_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
+        self.regs.peek_enable.next = false;
+        self.regs.reply.next = bit_cast::<64, 32>(self.reply.q.val());
+        self.regs.reply_bits.next = self.reply_bits.q.val();
+        // Hold off the next frame until the reply is ready
+        self.regs.hold.next = self.state.q.val() != ADS868XState::Idle;
+        self.regs.disabled.next = false;
         self.data_parity.next = self.conversion_counter.q.val().xor();
-        self.id_parity.next = (self.reg_ram.read_data.val() & 0x0FF).xor();
+        self.id_parity.next = (self.regs.peek_data.val() & 0x0FF).xor();
         match self.state.q.val() {
-            ADS868XState::Ready => {
-                if !self.spi_slave.busy.val() {
-                    self.state.d.next = ADS868XState::Nop;
-                }
-            }
-            ADS868XState::Waiting => {
-                if self.spi_slave.transfer_done.val() {
-                    self.inbound.d.next = self.spi_slave.data_inbound.val();
+            ADS868XState::Idle => {
+                if self.regs.command_strobe.val() {
+                    self.inbound.d.next = self.regs.command.val().get_bits::<32>(0);
                     self.state.d.next = ADS868XState::Dispatch;
                 }
             }
             ADS868XState::Dispatch => {
+                self.regs.peek_enable.next = true;
                 if self.read_cmd.val() == 0b11001 {
                     self.state.d.next = ADS868XState::ReadWordCmd;
-                    self.reg_ram.read_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
                 } else if self.read_cmd.val() == 0b01001 {
                     self.state.d.next = ADS868XState::ReadByteCmd;
-                    self.reg_ram.read_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
                 } else if self.write_cmd.val() == 0b11010_00 {
                     self.state.d.next = ADS868XState::WriteWordCmd;
                 } else if self.write_cmd.val() == 0b11010_01 {
                     self.state.d.next = ADS868XState::WriteMSBCmd;
-                    self.reg_ram.read_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
                 } else if self.write_cmd.val() == 0b11010_10 {
                     self.state.d.next = ADS868XState::WriteLSBCmd;
-                    self.reg_ram.read_address.next = bit_cast::<5, 9>(self.address.val() >> 1);
                 } else {
-                    self.reg_ram.read_address.next = 0x02.into();
+                    self.regs.peek_address.next = 0x02.into();
                     self.state.d.next = ADS868XState::Nop;
                 }
             }
             ADS868XState::ReadWordCmd => {
-                self.spi_slave.data_outbound.next =
-                    bit_cast::<32, 16>(self.reg_ram.read_data.val());
-                self.spi_slave.bits.next = 16.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = ADS868XState::Waiting;
+                self.reply.d.next = bit_cast::<32, 16>(self.regs.peek_data.val());
+                self.reply_bits.d.next = 16.into();
+                self.state.d.next = ADS868XState::Idle;
             }
             ADS868XState::ReadByteCmd => {
                 if self.address.val().get_bit(0) {
-                    self.spi_slave.data_outbound.next =
-                        bit_cast::<32, 16>(self.reg_ram.read_data.val() >> 8);
+                    self.reply.d.next = bit_cast::<32, 16>(self.regs.peek_data.val() >> 8);
                 } else {
-                    self.spi_slave.data_outbound.next =
-                        bit_cast::<32, 16>(self.reg_ram.read_data.val() & 0xFF);
+                    self.reply.d.next = bit_cast::<32, 16>(self.regs.peek_data.val() & 0xFF);
                 }
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = ADS868XState::Waiting;
+                self.reply_bits.d.next = 8.into();
+                self.state.d.next = ADS868XState::Idle;
             }
             ADS868XState::WriteWordCmd => {
-                self.reg_ram.write_data.next = bit_cast::<16, 32>(self.inbound.q.val() & 0xFFFF);
-                self.reg_ram.write_enable.next = true;
+                self.regs.update_data.next = bit_cast::<16, 32>(self.inbound.q.val() & 0xFFFF);
+                self.regs.update_enable.next = true;
                 self.state.d.next = ADS868XState::WriteDone;
             }
             ADS868XState::WriteLSBCmd => {
-                self.reg_ram.write_data.next = bit_cast::<16, 32>(self.inbound.q.val() & 0x00FF)
-                    | (self.reg_ram.read_data.val() & 0xFF00);
-                self.reg_ram.write_enable.next = true;
+                self.regs.update_data.next = bit_cast::<16, 32>(self.inbound.q.val() & 0x00FF)
+                    | (self.regs.peek_data.val() & 0xFF00);
+                self.regs.update_enable.next = true;
                 self.state.d.next = ADS868XState::WriteDone;
             }
             ADS868XState::WriteMSBCmd => {
-                self.reg_ram.write_data.next = bit_cast::<16, 32>(self.inbound.q.val() & 0xFF00)
-                    | (self.reg_ram.read_data.val() & 0x00FF);
-                self.reg_ram.write_enable.next = true;
+                self.regs.update_data.next = bit_cast::<16, 32>(self.inbound.q.val() & 0xFF00)
+                    | (self.regs.peek_data.val() & 0x00FF);
+                self.regs.update_enable.next = true;
                 self.state.d.next = ADS868XState::WriteDone;
             }
             ADS868XState::WriteDone => {
-                self.spi_slave.bits.next = 32.into();
-                self.spi_slave.data_outbound.next = self.inbound.q.val();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = ADS868XState::Waiting;
+                self.reply.d.next = self.inbound.q.val();
+                self.reply_bits.d.next = 32.into();
+                self.state.d.next = ADS868XState::Idle;
             }
             ADS868XState::Nop => {
-                self.spi_slave.bits.next = 32.into();
                 // TODO - make this more accurate based on how
                 // the output register is programmed.
-                /*  self.spi_slave.data_outbound.next =
-                (bit_cast::<32, 16>(self.conversion_counter.q.val()) << 16)
-                    | bit_cast::<32, 16>(self.reg_ram.read_data.val() & 0x0FF) << 12
-                    | bit_cast::<32, 1>(self.data_parity.val().into()) << 11
-                    | bit_cast::<32, 1>((self.data_parity.val() ^ self.id_parity.val()).into())
-                    << 10;
-                    */
-                self.spi_slave.data_outbound.next =
-                    (bit_cast::<32, 16>(self.conversion_counter.q.val()) << 16)
-                        | (bit_cast::<32, 16>(self.reg_ram.read_data.val() & 0x0FF) << 12)
-                        | (bit_cast::<32, 1>(self.data_parity.val().into()) << 8)
-                        | (bit_cast::<32, 1>(
-                            (self.data_parity.val() ^ self.id_parity.val()).into(),
-                        ) << 9);
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = ADS868XState::Waiting;
+                self.reply.d.next = (bit_cast::<32, 16>(self.conversion_counter.q.val()) << 16)
+                    | (bit_cast::<32, 16>(self.regs.peek_data.val() & 0x0FF) << 12)
+                    | (bit_cast::<32, 1>(self.data_parity.val().into()) << 8)
+                    | (bit_cast::<32, 1>((self.data_parity.val() ^ self.id_parity.val()).into())
+                        << 9);
+                self.reply_bits.d.next = 32.into();
+                self.state.d.next = ADS868XState::Idle;
                 self.conversion_counter.d.next = self.conversion_counter.q.val() + 1;
             }
             _ => {
-                self.state.d.next = ADS868XState::Ready;
+                self.state.d.next = ADS868XState::Nop;
             }
         }
     }
@@ -0,0 +1,575 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::{
+    prelude::*,
+    sdram::{cmd::SDRAMCommandDecoder, DDR3Device},
+};
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum DDR3State {
+    Reset,
+    PowerUp,
+    ExitReset,
+    ModeRegisters,
+    Calibrating,
+    Ready,
+    Error,
+}
+
+// A model of a DDR3 device, for testing controllers.  The power up sequence
+// (reset, clock enable, mode registers and ZQ calibration) is checked, as are
+// the command timings (tRCD, tRP, tRAS, tRC, tRRD, tCCD, tRFC, write recovery,
+// write to read and read to precharge) and the refresh interval.  Any
+// violation latches `test_error`.  `test_ready` is asserted once the device
+// has been initialized.
+//
+// Only fixed BL8 bursts, starting on a multiple of 8 columns, are supported.
+// Write data is captured on both edges of the strobe, and read data is sent
+// edge aligned with the clock, `CL` clocks after the read.  The write
+// recovery and turnaround times are checked against the most recent read or
+// write to any bank, which is stricter than the spec.
+#[derive(LogicBlock)]
+pub struct DDR3Simulator<
+    const R: usize, // Number of rows
+    const C: usize, // Number of columns
+    const A: usize, // A = R + C + 2
+    const D: usize, // Bits per word
+> {
+    pub ddr: DDR3Device<D>,
+    pub test_error: Signal<Out, Bit>,
+    pub test_ready: Signal<Out, Bit>,
+    decode: SDRAMCommandDecoder,
+    clock: Signal<Local, Clock>,
+    strobe: Signal<Local, Clock>,
+    cmd: Signal<Local, SDRAMCommand>,
+    state: DFF<DDR3State>,
+    counter: DFF<Bits<32>>,
+    refresh_interval: DFF<Bits<32>>,
+    modes_loaded: DFF<Bits<4>>,
+    cas_latency: DFF<Bits<4>>,
+    cas_write_latency: DFF<Bits<4>>,
+    since_mode: DFF<Bits<16>>,
+    since_activate: DFF<Bits<16>>,
+    since_read: DFF<Bits<16>>,
+    since_write: DFF<Bits<16>>,
+    since_refresh: DFF<Bits<16>>,
+    bank_open: [DFF<Bit>; 8],
+    bank_row: [DFF<Bits<R>>; 8],
+    bank_activated: [DFF<Bits<16>>; 8],
+    bank_precharged: [DFF<Bits<16>>; 8],
+    // Write data path
+    write_start: DelayLine<Bit, 16, 4>,
+    write_base: DelayLine<Bits<A>, 16, 4>,
+    write_active: DFF<Bit>,
+    write_beat: DFF<Bits<2>>,
+    write_address: DFF<Bits<A>>,
+    rise_capture: DFF<Bits<D>>,
+    fall_capture: NegEdgeDFF<Bits<D>>,
+    rise_hold: NegEdgeDFF<Bits<D>>,
+    // Read data path
+    read_start: DelayLine<Bit, 16, 4>,
+    read_base: DelayLine<Bits<A>, 16, 4>,
+    read_active: DFF<Bit>,
+    read_beat: DFF<Bits<2>>,
+    read_address: DFF<Bits<A>>,
+    read_valid: DFF<Bit>,
+    dq_out: DDROutputRegister<D>,
+    // Even and odd columns are stored separately
+    even: RAM<Bits<D>, A>,
+    odd: RAM<Bits<D>, A>,
+    cmd_bank: Signal<Local, Bits<3>>,
+    cmd_base: Signal<Local, Bits<A>>,
+    selected_open: Signal<Local, Bit>,
+    selected_row: Signal<Local, Bits<R>>,
+    selected_activated: Signal<Local, Bits<16>>,
+    selected_precharged: Signal<Local, Bits<16>>,
+    all_closed: Signal<Local, Bit>,
+    all_precharged: Signal<Local, Bit>,
+    refreshing: Signal<Local, Bit>,
+    // Timings (in clocks, less one)
+    t_reset: Constant<Bits<32>>,
+    t_cke: Constant<Bits<32>>,
+    t_xpr: Constant<Bits<32>>,
+    t_zqinit: Constant<Bits<32>>,
+    t_refi_max: Constant<Bits<32>>,
+    t_mrd: Constant<Bits<16>>,
+    t_mod: Constant<Bits<16>>,
+    t_rp: Constant<Bits<16>>,
+    t_rcd: Constant<Bits<16>>,
+    t_ras: Constant<Bits<16>>,
+    t_rc: Constant<Bits<16>>,
+    t_rrd: Constant<Bits<16>>,
+    t_rtp: Constant<Bits<16>>,
+    t_ccd: Constant<Bits<16>>,
+    t_rfc: Constant<Bits<16>>,
+    write_to_precharge: Constant<Bits<16>>,
+    write_to_read: Constant<Bits<16>>,
+    read_to_write: Constant<Bits<16>>,
+    bank_shift: Constant<Bits<A>>,
+    row_shift: Constant<Bits<A>>,
+    col_shift: Constant<Bits<A>>,
+}
+
+impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
+    for DDR3Simulator<R, C, A, D>
+{
+    #[hdl_gen]
+    fn update(&mut self) {
+        // Clock logic
+        self.clock.next = self.ddr.ck.val();
+        self.strobe.next = self.ddr.dqs.val().into();
+        dff_setup!(
+            self,
+            clock,
+            state,
+            counter,
+            refresh_interval,
+            modes_loaded,
+            cas_latency,
+            cas_write_latency,
+            since_mode,
+            since_activate,
+            since_read,
+            since_write,
+            since_refresh,
+            write_active,
+            write_beat,
+            write_address,
+            read_active,
+            read_beat,
+            read_address,
+            read_valid
+        );
+        for i in 0..8 {
+            self.bank_open[i].clock.next = self.clock.val();
+            self.bank_open[i].d.next = self.bank_open[i].q.val();
+            self.bank_row[i].clock.next = self.clock.val();
+            self.bank_row[i].d.next = self.bank_row[i].q.val();
+            self.bank_activated[i].clock.next = self.clock.val();
+            self.bank_activated[i].d.next = self.bank_activated[i].q.val();
+            if self.bank_activated[i].q.val() != 0xFFFF {
+                self.bank_activated[i].d.next = self.bank_activated[i].q.val() + 1;
+            }
+            self.bank_precharged[i].clock.next = self.clock.val();
+            self.bank_precharged[i].d.next = self.bank_precharged[i].q.val();
+            if self.bank_precharged[i].q.val() != 0xFFFF {
+                self.bank_precharged[i].d.next = self.bank_precharged[i].q.val() + 1;
+            }
+        }
+        clock!(
+            self,
+            clock,
+            write_start,
+            write_base,
+            read_start,
+            read_base,
+            dq_out
+        );
+        self.rise_capture.clock.next = self.strobe.val();
+        self.fall_capture.clock.next = self.strobe.val();
+        self.rise_hold.clock.next = self.clock.val();
+        self.even.read_clock.next = self.clock.val();
+        self.even.write_clock.next = self.clock.val();
+        self.odd.read_clock.next = self.clock.val();
+        self.odd.write_clock.next = self.clock.val();
+        // Connect the command decoder to the bus
+        self.decode.we_not.next = self.ddr.we_not.val();
+        self.decode.cas_not.next = self.ddr.cas_not.val();
+        self.decode.ras_not.next = self.ddr.ras_not.val();
+        self.decode.cs_not.next = self.ddr.cs_not.val();
+        self.cmd.next = self.decode.cmd.val();
+        self.test_error.next = self.state.q.val() == DDR3State::Error;
+        self.test_ready.next = self.state.q.val() == DDR3State::Ready;
+        // The time since each kind of command, saturating
+        if self.since_mode.q.val() != 0xFFFF {
+            self.since_mode.d.next = self.since_mode.q.val() + 1;
+        }
+        if self.since_activate.q.val() != 0xFFFF {
+            self.since_activate.d.next = self.since_activate.q.val() + 1;
+        }
+        if self.since_read.q.val() != 0xFFFF {
+            self.since_read.d.next = self.since_read.q.val() + 1;
+        }
+        if self.since_write.q.val() != 0xFFFF {
+            self.since_write.d.next = self.since_write.q.val() + 1;
+        }
+        if self.since_refresh.q.val() != 0xFFFF {
+            self.since_refresh.d.next = self.since_refresh.q.val() + 1;
+        }
+        self.refreshing.next = self.since_refresh.q.val() < self.t_rfc.val();
+        // Look up the addressed bank
+        self.cmd_bank.next = self.ddr.bank.val();
+        self.selected_open.next = false;
+        self.selected_row.next = 0.into();
+        self.selected_activated.next = 0.into();
+        self.selected_precharged.next = 0.into();
+        self.all_closed.next = true;
+        self.all_precharged.next = true;
+        for i in 0..8 {
+            if self.cmd_bank.val().index() == i {
+                self.selected_open.next = self.bank_open[i].q.val();
+                self.selected_row.next = self.bank_row[i].q.val();
+                self.selected_activated.next = self.bank_activated[i].q.val();
+                self.selected_precharged.next = self.bank_precharged[i].q.val();
+            }
+            if self.bank_open[i].q.val() {
+                self.all_closed.next = false;
+            }
+            if self.bank_precharged[i].q.val() < self.t_rp.val() {
+                self.all_precharged.next = false;
+            }
+        }
+        // Row-column multiplexing.  The low 2 bits are the beat in the burst.
+        self.cmd_base.next = (bit_cast::<A, 3>(self.cmd_bank.val()) << self.bank_shift.val())
+            | (bit_cast::<A, R>(self.selected_row.val()) << self.row_shift.val())
+            | (bit_cast::<A, C>(self.ddr.address.val().get_bits::<C>(0)) >> self.col_shift.val());
+        // Write data is captured on both edges of the strobe, and written
+        // to the memory a word pair at a time
+        self.rise_capture.d.next = self.ddr.write_data.val();
+        self.fall_capture.d.next = self.ddr.write_data.val();
+        self.rise_hold.d.next = self.rise_capture.q.val();
+        self.write_start.data_in.next = false;
+        self.write_start.delay.next = self.cas_write_latency.q.val();
+        self.write_base.data_in.next = self.cmd_base.val();
+        self.write_base.delay.next = self.cas_write_latency.q.val();
+        if self.write_active.q.val() {
+            self.write_beat.d.next = self.write_beat.q.val() + 1;
+            if self.write_beat.q.val() == 3 {
+                self.write_active.d.next = false;
+            }
+        }
+        if self.write_start.data_out.val() {
+            self.write_active.d.next = true;
+            self.write_beat.d.next = 0.into();
+            self.write_address.d.next = self.write_base.data_out.val();
+        }
+        self.even.write_enable.next = self.write_active.q.val();
+        self.odd.write_enable.next = self.write_active.q.val();
+        self.even.write_address.next =
+            self.write_address.q.val() | bit_cast::<A, 2>(self.write_beat.q.val());
+        self.odd.write_address.next =
+            self.write_address.q.val() | bit_cast::<A, 2>(self.write_beat.q.val());
+        self.even.write_data.next = self.rise_hold.q.val();
+        self.odd.write_data.next = self.fall_capture.q.val();
+        // Read data is fetched 2 clocks ahead, and sent on both edges of the clock
+        self.read_start.data_in.next = false;
+        self.read_start.delay.next = self.cas_latency.q.val() - 2;
+        self.read_base.data_in.next = self.cmd_base.val();
+        self.read_base.delay.next = self.cas_latency.q.val() - 2;
+        if self.read_active.q.val() {
+            self.read_beat.d.next = self.read_beat.q.val() + 1;
+            if self.read_beat.q.val() == 3 {
+                self.read_active.d.next = false;
+            }
+        }
+        if self.read_start.data_out.val() {
+            self.read_active.d.next = true;
+            self.read_beat.d.next = 0.into();
+            self.read_address.d.next = self.read_base.data_out.val();
+        }
+        self.read_valid.d.next = self.read_active.q.val();
+        self.even.read_address.next =
+            self.read_address.q.val() | bit_cast::<A, 2>(self.read_beat.q.val());
+        self.odd.read_address.next =
+            self.read_address.q.val() | bit_cast::<A, 2>(self.read_beat.q.val());
+        self.dq_out.d_rise.next = 0.into();
+        self.dq_out.d_fall.next = 0.into();
+        if self.read_valid.q.val() {
+            self.dq_out.d_rise.next = self.even.read_data.val();
+            self.dq_out.d_fall.next = self.odd.read_data.val();
+        }
+        self.ddr.read_data.next = self.dq_out.q.val();
+        match self.state.q.val() {
+            DDR3State::Reset => {
+                self.counter.d.next = self.counter.q.val() + 1;
+                if self.ddr.reset_not.val() {
+                    if self.counter.q.val() < self.t_reset.val() {
+                        self.state.d.next = DDR3State::Error;
+                    } else {
+                        self.counter.d.next = 0.into();
+                        self.state.d.next = DDR3State::PowerUp;
+                    }
+                }
+            }
+            DDR3State::PowerUp => {
+                // Commands are ignored while the clock is disabled
+                self.counter.d.next = self.counter.q.val() + 1;
+                if self.ddr.cke.val() {
+                    if self.counter.q.val() < self.t_cke.val() {
+                        self.state.d.next = DDR3State::Error;
+                    } else {
+                        self.counter.d.next = 0.into();
+                        self.state.d.next = DDR3State::ExitReset;
+                    }
+                }
+            }
+            DDR3State::ExitReset => {
+                self.counter.d.next = self.counter.q.val() + 1;
+                match self.cmd.val() {
+                    SDRAMCommand::NOP => {}
+                    SDRAMCommand::LoadModeRegister => {
+                        if self.counter.q.val() < self.t_xpr.val() {
+                            self.state.d.next = DDR3State::Error;
+                        } else {
+                            self.state.d.next = DDR3State::ModeRegisters;
+                        }
+                    }
+                    _ => {
+                        self.state.d.next = DDR3State::Error;
+                    }
+                }
+            }
+            DDR3State::ModeRegisters => match self.cmd.val() {
+                SDRAMCommand::NOP => {}
+                SDRAMCommand::LoadModeRegister => {
+                    if self.since_mode.q.val() < self.t_mrd.val() {
+                        self.state.d.next = DDR3State::Error;
+                    }
+                }
+                SDRAMCommand::BurstTerminate => {
+                    // ZQ calibration (long)
+                    if (self.modes_loaded.q.val() != 0xF)
+                        | (self.since_mode.q.val() < self.t_mod.val())
+                        | !self.ddr.address.val().get_bit(10)
+                    {
+                        self.state.d.next = DDR3State::Error;
+                    } else {
+                        self.counter.d.next = 0.into();
+                        self.state.d.next = DDR3State::Calibrating;
+                    }
+                }
+                _ => {
+                    self.state.d.next = DDR3State::Error;
+                }
+            },
+            DDR3State::Calibrating => {
+                self.counter.d.next = self.counter.q.val() + 1;
+                if self.cmd.val() != SDRAMCommand::NOP {
+                    self.state.d.next = DDR3State::Error;
+                }
+                if self.counter.q.val() == self.t_zqinit.val() {
+                    self.refresh_interval.d.next = 0.into();
+                    self.state.d.next = DDR3State::Ready;
+                }
+            }
+            DDR3State::Ready => {
+                self.refresh_interval.d.next = self.refresh_interval.q.val() + 1;
+                if self.refresh_interval.q.val() > self.t_refi_max.val() {
+                    self.state.d.next = DDR3State::Error;
+                }
+                if self.refreshing.val() & (self.cmd.val() != SDRAMCommand::NOP) {
+                    self.state.d.next = DDR3State::Error;
+                }
+                match self.cmd.val() {
+                    SDRAMCommand::NOP => {}
+                    SDRAMCommand::Active => {
+                        if self.selected_open.val()
+                            | (self.selected_precharged.val() < self.t_rp.val())
+                            | (self.selected_activated.val() < self.t_rc.val())
+                            | (self.since_activate.q.val() < self.t_rrd.val())
+                        {
+                            self.state.d.next = DDR3State::Error;
+                        }
+                        for i in 0..8 {
+                            if self.cmd_bank.val().index() == i {
+                                self.bank_open[i].d.next = true;
+                                self.bank_row[i].d.next = self.ddr.address.val().get_bits::<R>(0);
+                                self.bank_activated[i].d.next = 0.into();
+                            }
+                        }
+                        self.since_activate.d.next = 0.into();
+                    }
+                    SDRAMCommand::Read => {
+                        if !self.selected_open.val()
+                            | (self.selected_activated.val() < self.t_rcd.val())
+                            | (self.ddr.address.val().get_bits::<3>(0) != 0)
+                            | (self.since_read.q.val() < self.t_ccd.val())
+                            | (self.since_write.q.val() < self.write_to_read.val())
+                        {
+                            self.state.d.next = DDR3State::Error;
+                        }
+                        self.read_start.data_in.next = true;
+                        self.since_read.d.next = 0.into();
+                    }
+                    SDRAMCommand::Write => {
+                        if !self.selected_open.val()
+                            | (self.selected_activated.val() < self.t_rcd.val())
+                            | (self.ddr.address.val().get_bits::<3>(0) != 0)
+                            | (self.since_write.q.val() < self.t_ccd.val())
+                            | (self.since_read.q.val() < self.read_to_write.val())
+                        {
+                            self.state.d.next = DDR3State::Error;
+                        }
+                        self.write_start.data_in.next = true;
+                        self.since_write.d.next = 0.into();
+                    }
+                    SDRAMCommand::Precharge => {
+                        for i in 0..8 {
+                            if (self.ddr.address.val().get_bit(10)
+                                | (self.cmd_bank.val().index() == i))
+                                & self.bank_open[i].q.val()
+                            {
+                                if (self.bank_activated[i].q.val() < self.t_ras.val())
+                                    | (self.since_write.q.val() < self.write_to_precharge.val())
+                                    | (self.since_read.q.val() < self.t_rtp.val())
+                                {
+                                    self.state.d.next = DDR3State::Error;
+                                }
+                                self.bank_open[i].d.next = false;
+                                self.bank_precharged[i].d.next = 0.into();
+                            }
+                        }
+                    }
+                    SDRAMCommand::AutoRefresh => {
+                        if !self.all_closed.val() | !self.all_precharged.val() {
+                            self.state.d.next = DDR3State::Error;
+                        }
+                        self.since_refresh.d.next = 0.into();
+                        self.refresh_interval.d.next = 0.into();
+                    }
+                    SDRAMCommand::BurstTerminate => {
+                        // ZQ calibration, with all banks closed
+                        if !self.all_closed.val() {
+                            self.state.d.next = DDR3State::Error;
+                        }
+                    }
+                    _ => {
+                        // Mode register changes after initialization are not supported
+                        self.state.d.next = DDR3State::Error;
+                    }
+                }
+            }
+            DDR3State::Error => {}
+            _ => {
+                self.state.d.next = DDR3State::Reset;
+            }
+        }
+        // Load a mode register.  Only fixed BL8 is supported.
+        if (self.cmd.val() == SDRAMCommand::LoadModeRegister)
+            & ((self.state.q.val() == DDR3State::ExitReset)
+                | (self.state.q.val() == DDR3State::ModeRegisters))
+        {
+            self.since_mode.d.next = 0.into();
+            match self.cmd_bank.val().index() {
+                0 => {
+                    self.modes_loaded.d.next = self.modes_loaded.q.val() | 1;
+                    self.cas_latency.d.next = self.ddr.address.val().get_bits::<4>(4) + 4;
+                    if (self.ddr.address.val().get_bits::<2>(0) != 0)
+                        | self.ddr.address.val().get_bit(2)
+                        | self.ddr.address.val().get_bit(7)
+                    {
+                        self.state.d.next = DDR3State::Error;
+                    }
+                }
+                1 => {
+                    self.modes_loaded.d.next = self.modes_loaded.q.val() | 2;
+                }
+                2 => {
+                    self.modes_loaded.d.next = self.modes_loaded.q.val() | 4;
+                    self.cas_write_latency.d.next = self.ddr.address.val().get_bits::<4>(3) + 5;
+                }
+                3 => {
+                    self.modes_loaded.d.next = self.modes_loaded.q.val() | 8;
+                }
+                _ => {
+                    self.state.d.next = DDR3State::Error;
+                }
+            }
+        }
+        // Pulling reset low restarts the power up sequence
+        if !self.ddr.reset_not.val() & (self.state.q.val() != DDR3State::Reset) {
+            self.counter.d.next = 0.into();
+            self.state.d.next = DDR3State::Reset;
+        }
+    }
+}
+
+fn less_one(x: u32) -> u32 {
+    x.max(1) - 1
+}
+
+impl<const R: usize, const C: usize, const A: usize, const D: usize> DDR3Simulator<R, C, A, D> {
+    pub fn new(timings: DDR3Timings) -> Self {
+        assert_eq!(R + C + 2, A);
+        let gaps = |x: u16| less_one(x as u32).to_bits::<16>();
+        Self {
+            ddr: Default::default(),
+            test_error: Default::default(),
+            test_ready: Default::default(),
+            decode: Default::default(),
+            clock: Default::default(),
+            strobe: Default::default(),
+            cmd: Default::default(),
+            state: Default::default(),
+            counter: Default::default(),
+            refresh_interval: Default::default(),
+            modes_loaded: Default::default(),
+            cas_latency: Default::default(),
+            cas_write_latency: Default::default(),
+            since_mode: Default::default(),
+            since_activate: Default::default(),
+            since_read: Default::default(),
+            since_write: Default::default(),
+            since_refresh: Default::default(),
+            bank_open: Default::default(),
+            bank_row: Default::default(),
+            bank_activated: Default::default(),
+            bank_precharged: Default::default(),
+            write_start: Default::default(),
+            write_base: Default::default(),
+            write_active: Default::default(),
+            write_beat: Default::default(),
+            write_address: Default::default(),
+            rise_capture: Default::default(),
+            fall_capture: Default::default(),
+            rise_hold: Default::default(),
+            read_start: Default::default(),
+            read_base: Default::default(),
+            read_active: Default::default(),
+            read_beat: Default::default(),
+            read_address: Default::default(),
+            read_valid: Default::default(),
+            dq_out: Default::default(),
+            even: Default::default(),
+            odd: Default::default(),
+            cmd_bank: Default::default(),
+            cmd_base: Default::default(),
+            selected_open: Default::default(),
+            selected_row: Default::default(),
+            selected_activated: Default::default(),
+            selected_precharged: Default::default(),
+            all_closed: Default::default(),
+            all_precharged: Default::default(),
+            refreshing: Default::default(),
+            t_reset: Constant::new(less_one(timings.t_reset()).to_bits()),
+            t_cke: Constant::new(less_one(timings.t_cke()).to_bits()),
+            t_xpr: Constant::new(less_one(timings.t_xpr() as u32).to_bits()),
+            t_zqinit: Constant::new(less_one(timings.t_zqinit() as u32).to_bits()),
+            // Up to 8 refreshes can be postponed
+            t_refi_max: Constant::new((9 * timings.t_refi() as u32).to_bits()),
+            t_mrd: Constant::new(gaps(timings.t_mrd())),
+            t_mod: Constant::new(gaps(timings.t_mod())),
+            t_rp: Constant::new(gaps(timings.t_rp())),
+            t_rcd: Constant::new(gaps(timings.t_rcd())),
+            t_ras: Constant::new(gaps(timings.t_ras())),
+            t_rc: Constant::new(gaps(timings.t_rc())),
+            t_rrd: Constant::new(gaps(timings.t_rrd())),
+            t_rtp: Constant::new(gaps(timings.t_rtp())),
+            t_ccd: Constant::new(gaps(timings.t_ccd())),
+            t_rfc: Constant::new(gaps(timings.t_rfc())),
+            write_to_precharge: Constant::new(gaps(timings.write_to_precharge())),
+            write_to_read: Constant::new(gaps(timings.write_to_read())),
+            read_to_write: Constant::new(gaps(timings.read_to_write())),
+            bank_shift: Constant::new({ R + C - 1 }.to_bits()),
+            row_shift: Constant::new({ C - 1 }.to_bits()),
+            col_shift: Constant::new(1_usize.to_bits()),
+        }
+    }
+}
+
+#[test]
+fn test_ddr3_sim_synthesizes() {
+    let mut uut = DDR3Simulator::<4, 5, 11, 16>::new(DDR3Timings::fast_boot_sim(100e6));
+    uut.ddr.link_connect_dest();
+    uut.connect_all();
+    yosys_validate("ddr3_sim", &generate_verilog(&uut)).unwrap();
+}
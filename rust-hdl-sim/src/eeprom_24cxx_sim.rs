@@ -0,0 +1,202 @@
+use crate::register_map::i2c::{I2CRegisterFormat, I2CRegisterMap};
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct EEPROM24CxxConfig {
+    pub address: u8,
+    pub page_bytes: usize,
+    pub write_cycle_time: Duration,
+    pub clock_speed_hz: u64,
+}
+
+impl EEPROM24CxxConfig {
+    // A 24C02 style part (2 Kbit, 8 byte pages)
+    pub fn c02(clock_speed_hz: u64) -> Self {
+        Self {
+            address: 0x50,
+            page_bytes: 8,
+            write_cycle_time: Duration::from_millis(5),
+            clock_speed_hz,
+        }
+    }
+    // A 24C32 style part (32 Kbit, 32 byte pages)
+    pub fn c32(clock_speed_hz: u64) -> Self {
+        Self {
+            address: 0x50,
+            page_bytes: 32,
+            write_cycle_time: Duration::from_millis(5),
+            clock_speed_hz,
+        }
+    }
+}
+
+/// A 24Cxx style I2C EEPROM with `2^A` bytes of storage.  Parts with more
+/// than 256 bytes use a two byte address.  Writes wrap around within a
+/// page, reads run sequentially through the whole array, and the part does
+/// not acknowledge its address during the write cycle that follows a stop
+/// (so the master can use acknowledge polling).
+#[derive(LogicBlock)]
+pub struct EEPROM24CxxSimulator<const A: usize> {
+    pub i2c: I2CBusDriver,
+    pub clock: Signal<In, Clock>,
+    // The memory array and I2C interface
+    mem: I2CRegisterMap<A, 8>,
+    // Times the internal write cycle
+    write_cycle: Shot<32>,
+    // Set when there is data that will be committed at the next stop
+    write_pending: DFF<Bit>,
+}
+
+impl<const A: usize> EEPROM24CxxSimulator<A> {
+    pub fn new(config: EEPROM24CxxConfig) -> Self {
+        assert!(A <= 16);
+        assert!(config.page_bytes.is_power_of_two());
+        let format = I2CRegisterFormat {
+            address: config.address,
+            pointer_bytes: if A > 8 { 2 } else { 1 },
+            auto_increment: true,
+            write_page_bits: Some(config.page_bytes.trailing_zeros() as usize),
+        };
+        // An erased part reads back as all ones
+        let inits = vec![0xFF_u64; 1 << A];
+        Self {
+            i2c: Default::default(),
+            clock: Default::default(),
+            mem: I2CRegisterMap::new(format, &inits),
+            write_cycle: Shot::new(config.clock_speed_hz, config.write_cycle_time),
+            write_pending: Default::default(),
+        }
+    }
+}
+
+impl<const A: usize> Logic for EEPROM24CxxSimulator<A> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        I2CBusDriver::link(&mut self.i2c, &mut self.mem.i2c);
+        clock!(self, clock, mem, write_cycle);
+        dff_setup!(self, clock, write_pending);
+        self.mem.update_address.next = 0.into();
+        self.mem.update_data.next = 0.into();
+        self.mem.update_enable.next = false;
+        self.mem.peek_address.next = 0.into();
+        self.mem.peek_enable.next = false;
+        self.mem.write_protect.next = false;
+        // The part is deaf while the write cycle runs
+        self.mem.hold.next = self.write_cycle.active.val();
+        self.write_cycle.trigger.next = false;
+        if self.mem.write_strobe.val() {
+            self.write_pending.d.next = true;
+        }
+        if self.mem.stop.val() & self.write_pending.q.val() {
+            self.write_cycle.trigger.next = true;
+            self.write_pending.d.next = false;
+        }
+    }
+}
+
+#[test]
+fn test_eeprom_24cxx_synthesizes() {
+    let mut uut: EEPROM24CxxSimulator<8> =
+        EEPROM24CxxSimulator::new(EEPROM24CxxConfig::c02(1_000_000));
+    uut.connect_all();
+    yosys_validate("eeprom_24c02", &generate_verilog(&uut)).unwrap();
+}
+
+#[derive(LogicBlock)]
+struct Test24Cxx {
+    clock: Signal<In, Clock>,
+    controller: rust_hdl_widgets::i2c::i2c_controller::I2CController,
+    eeprom: EEPROM24CxxSimulator<12>,
+    test_bus: I2CTestBus<2>,
+}
+
+impl Logic for Test24Cxx {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, controller, eeprom);
+        I2CBusDriver::join(&mut self.controller.i2c, &mut self.test_bus.endpoints[0]);
+        I2CBusDriver::join(&mut self.eeprom.i2c, &mut self.test_bus.endpoints[1]);
+    }
+}
+
+#[cfg(test)]
+fn mk_test24cxx() -> Test24Cxx {
+    let mut config = EEPROM24CxxConfig::c32(1_000_000);
+    config.write_cycle_time = Duration::from_micros(500);
+    let mut uut = Test24Cxx {
+        clock: Default::default(),
+        controller: rust_hdl_widgets::i2c::i2c_controller::I2CController::new(I2CConfig {
+            delay_time: Duration::from_micros(5),
+            clock_speed_hz: 1_000_000,
+            timeout: Duration::from_millis(25),
+        }),
+        eeprom: EEPROM24CxxSimulator::new(config),
+        test_bus: Default::default(),
+    };
+    uut.clock.connect();
+    uut.controller.cmd.connect();
+    uut.controller.run.connect();
+    uut.controller.write_data_in.connect();
+    uut.controller.address_10.connect();
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_eeprom_24cxx_page_write_and_read() {
+    use rust_hdl_widgets::i2c::i2c_controller::I2CControllerCmd;
+    let uut = mk_test24cxx();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<Test24Cxx>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<Test24Cxx>| {
+        let mut x = sim.init()?;
+        // Write 4 bytes starting 2 bytes before the end of a page, so that
+        // the last 2 wrap around to the start of the page
+        i2c_begin_write!(sim, clock, x, 0x50);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 0x01);
+        i2c_write!(sim, clock, x, 0x3E);
+        for val in [0xA0, 0xA1, 0xA2, 0xA3] {
+            i2c_write!(sim, clock, x, val);
+            sim_assert!(sim, x.controller.ack.val(), x);
+        }
+        i2c_end_transmission!(sim, clock, x);
+        // The part ignores us during the write cycle
+        i2c_begin_write!(sim, clock, x, 0x50);
+        sim_assert!(sim, x.controller.nack.val(), x);
+        i2c_end_transmission!(sim, clock, x);
+        // Poll until the write cycle completes
+        loop {
+            i2c_begin_write!(sim, clock, x, 0x50);
+            if x.controller.ack.val() {
+                break;
+            }
+            i2c_end_transmission!(sim, clock, x);
+        }
+        // Sequential read from the start of the page
+        i2c_write!(sim, clock, x, 0x01);
+        i2c_write!(sim, clock, x, 0x20);
+        i2c_end_transmission!(sim, clock, x);
+        i2c_begin_read!(sim, clock, x, 0x50);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        let mut page = vec![];
+        for _ in 0..31 {
+            page.push(i2c_read!(sim, clock, x).index());
+        }
+        page.push(i2c_read_last!(sim, clock, x).index());
+        i2c_end_transmission!(sim, clock, x);
+        sim_assert_eq!(sim, page[0], 0xA2, x);
+        sim_assert_eq!(sim, page[1], 0xA3, x);
+        for val in &page[2..30] {
+            sim_assert_eq!(sim, *val, 0xFF, x);
+        }
+        sim_assert_eq!(sim, page[30], 0xA0, x);
+        sim_assert_eq!(sim, page[31], 0xA1, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 200_000_000_000).unwrap();
+}
@@ -1,9 +1,16 @@
 pub mod ad7193_sim;
 pub mod ads8688_sim;
 pub mod ads868x_sim;
+pub mod ddr3_sdram;
+pub mod eeprom_24cxx_sim;
 pub mod max31856_sim;
 pub mod muxed_ad7193_sim;
 pub mod muxed_ads868x_sim;
 pub mod muxed_max31856_sim;
+pub mod pcap;
 pub mod prelude;
+pub mod register_map;
+pub mod rmii_phy_sim;
 pub mod sdr_sdram;
+pub mod tmp102_sim;
+pub mod w25q_sim;
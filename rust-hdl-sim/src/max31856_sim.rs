@@ -1,19 +1,8 @@
 use super::ad7193_sim::AD7193Config;
+use crate::register_map::spi::{SPICommandFormat, SPIRegisterMap};
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
-#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
-enum MAX31856State {
-    Start,
-    Ready,
-    GettingCmd,
-    RegFetchRead,
-    ReadCmd,
-    WaitReadComplete,
-    WriteCmd,
-    DoWrite,
-}
-
 #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
 enum DAQState {
     Idle,
@@ -27,24 +16,12 @@ pub struct MAX31856Simulator {
     // Slave SPI bus
     pub wires: SPIWiresSlave,
     pub clock: Signal<In, Clock>,
-    // RAM that stores the memory contents
-    reg_ram: RAM<Bits<8>, 4>,
+    // The register map and SPI interface
+    regs: SPIRegisterMap<4, 8>,
     // Used to handle auto conversions
     auto_conversions_enabled: DFF<Bit>,
     auto_conversion_strobe: Strobe<32>,
     auto_conversion_counter: DFF<Bits<19>>,
-    // Separate bits out of the SPI message
-    cmd: Signal<Local, Bits<8>>,
-    rw_flag: Signal<Local, Bit>,
-    reg_index: Signal<Local, Bits<4>>,
-    // The SPI slave device
-    spi_slave: SPISlave<64>,
-    // FSM state:
-    state: DFF<MAX31856State>,
-    reg_read_index: DFF<Bits<4>>,
-    reg_write_index: DFF<Bits<4>>,
-    // Boot timer
-    boot: DFF<Bits<4>>,
     // DAQ state:
     dstate: DFF<DAQState>,
 }
@@ -53,24 +30,39 @@ const MAX31856_REG_INITS: [u8; 16] = [
     0x00, 0x03, 0xFF, 0x7F, 0xC0, 0x7F, 0xFF, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
 
+// Commands are <w/r> <0> <0> <0> <reg_address>, and the address
+// increments as long as chip select is held.
+const MAX31856_COMMAND_FORMAT: SPICommandFormat = SPICommandFormat {
+    command_bits: 8,
+    address_offset: 0,
+    write_bit: 7,
+    write_level: true,
+    device_commands: None,
+    zero_is_device_command: false,
+    read_turnaround_bits: 0,
+    status_bits: 0,
+    status_value: 0,
+    write_echo: false,
+    auto_increment: true,
+    reset_on_ones: false,
+    framed: false,
+    idle_value: 0xFF,
+};
+
 impl MAX31856Simulator {
     pub fn new(config: SPIConfig) -> Self {
-        let reg_ram = MAX31856_REG_INITS.iter().map(|x| x.to_bits()).into();
         Self {
             wires: Default::default(),
             clock: Default::default(),
-            reg_ram,
+            regs: SPIRegisterMap::new(
+                config,
+                MAX31856_COMMAND_FORMAT,
+                &[8; 16],
+                &MAX31856_REG_INITS.map(|x| x as u64),
+            ),
             auto_conversions_enabled: Default::default(),
             auto_conversion_strobe: Strobe::new(config.clock_speed, 100.0),
             auto_conversion_counter: Default::default(),
-            cmd: Default::default(),
-            rw_flag: Default::default(),
-            spi_slave: SPISlave::new(config),
-            state: Default::default(),
-            reg_read_index: Default::default(),
-            reg_write_index: Default::default(),
-            boot: DFF::default(),
-            reg_index: Default::default(),
             dstate: Default::default(),
         }
     }
@@ -80,110 +72,32 @@ impl Logic for MAX31856Simulator {
     #[hdl_gen]
     fn update(&mut self) {
         // Connect the spi bus
-        SPIWiresSlave::link(&mut self.wires, &mut self.spi_slave.wires);
-        // Clock the internal logic
-        self.reg_ram.write_clock.next = self.clock.val();
-        self.reg_ram.read_clock.next = self.clock.val();
+        SPIWiresSlave::link(&mut self.wires, &mut self.regs.wires);
         // Setup the DFF and internal widgets
         dff_setup!(
             self,
             clock,
             auto_conversions_enabled,
             auto_conversion_counter,
-            state,
-            reg_read_index,
-            reg_write_index,
-            boot,
             dstate
         );
-        clock!(self, clock, auto_conversion_strobe, spi_slave);
+        clock!(self, clock, regs, auto_conversion_strobe);
         // Set default values
-        self.spi_slave.start_send.next = false;
-        self.spi_slave.continued_transaction.next = false;
-        self.spi_slave.bits.next = 0.into();
-        self.spi_slave.data_outbound.next = 0.into();
-        self.reg_ram.write_enable.next = false;
-        self.spi_slave.disabled.next = false;
-        self.cmd.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
-        self.reg_index.next = self.cmd.val().get_bits::<4>(0);
-        self.rw_flag.next = self.cmd.val().get_bit(7);
-        self.reg_ram.read_address.next = self.reg_read_index.q.val();
-        self.reg_ram.write_address.next = self.reg_write_index.q.val();
-        self.reg_ram.write_data.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
+        self.regs.update_address.next = 0.into();
+        self.regs.update_data.next = 0.into();
+        self.regs.update_enable.next = false;
+        self.regs.peek_address.next = 0.into();
+        self.regs.peek_enable.next = false;
+        self.regs.reply.next = 0.into();
+        self.regs.reply_bits.next = 0.into();
+        self.regs.hold.next = false;
+        self.regs.disabled.next = false;
         self.auto_conversion_strobe.enable.next = self.auto_conversions_enabled.q.val();
-        match self.state.q.val() {
-            MAX31856State::Start => {
-                self.boot.d.next = self.boot.q.val() + 1;
-                if self.boot.q.val().all() {
-                    self.state.d.next = MAX31856State::Ready
-                }
-            }
-            MAX31856State::Ready => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.data_outbound.next = 0xFF.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = MAX31856State::GettingCmd;
-            }
-            MAX31856State::GettingCmd => {
-                if self.spi_slave.transfer_done.val() {
-                    if !self.rw_flag.val() {
-                        self.reg_read_index.d.next = self.reg_index.val();
-                        self.state.d.next = MAX31856State::RegFetchRead;
-                    } else {
-                        self.reg_write_index.d.next = self.reg_index.val();
-                        self.state.d.next = MAX31856State::WriteCmd;
-                    }
-                }
-            }
-            MAX31856State::RegFetchRead => {
-                self.state.d.next = MAX31856State::ReadCmd;
-            }
-            MAX31856State::ReadCmd => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.data_outbound.next = bit_cast::<64, 8>(self.reg_ram.read_data.val());
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = MAX31856State::WaitReadComplete;
-            }
-            MAX31856State::WaitReadComplete => {
-                if !self.spi_slave.busy.val() & self.spi_slave.transfer_done.val() {
-                    self.state.d.next = MAX31856State::Ready;
-                }
-                if self.spi_slave.busy.val() & self.spi_slave.transfer_done.val() {
-                    self.reg_read_index.d.next = self.reg_read_index.q.val() + 1;
-                    self.state.d.next = MAX31856State::RegFetchRead;
-                }
-            }
-            MAX31856State::WriteCmd => {
-                self.spi_slave.continued_transaction.next = true;
-                self.spi_slave.bits.next = 8.into();
-                self.spi_slave.data_outbound.next = 0xFF.into();
-                self.spi_slave.start_send.next = true;
-                self.state.d.next = MAX31856State::DoWrite;
-            }
-            MAX31856State::DoWrite => {
-                if !self.spi_slave.busy.val() & self.spi_slave.transfer_done.val() {
-                    if !self.reg_write_index.q.val().any() {
-                        self.auto_conversions_enabled.d.next =
-                            self.spi_slave.data_inbound.val().get_bit(7);
-                    }
-                    self.reg_ram.write_enable.next = true;
-                    self.state.d.next = MAX31856State::Ready;
-                }
-                if self.spi_slave.busy.val() & self.spi_slave.transfer_done.val() {
-                    self.reg_ram.write_enable.next = true;
-                    self.reg_write_index.d.next = self.reg_write_index.q.val() + 1;
-                    self.state.d.next = MAX31856State::WriteCmd;
-                }
-            }
-            _ => {
-                self.state.d.next = MAX31856State::Start;
-            }
+        // The CMODE bit in configuration register 0 turns on auto conversions
+        if self.regs.write_strobe.val() & !self.regs.write_address.val().any() {
+            self.auto_conversions_enabled.d.next = self.regs.write_data.val().get_bit(7);
         }
-        // Warning! There is a contention between writes from the SPI bus and
-        // writes from the DAQ...  A more sophisticated model would segment
-        // the register ram into 2 blocks, and limit SPI writes to the lower block.
+        // Conversion results are written into the linearized temperature registers
         match self.dstate.q.val() {
             DAQState::Idle => {
                 if self.auto_conversion_strobe.strobe.val() {
@@ -192,24 +106,22 @@ impl Logic for MAX31856Simulator {
                 }
             }
             DAQState::Convert => {
-                self.reg_ram.write_address.next = 0x0E.into();
-                self.reg_ram.write_data.next =
+                self.regs.update_address.next = 0x0E.into();
+                self.regs.update_data.next =
                     bit_cast::<8, 3>(self.auto_conversion_counter.q.val().get_bits::<3>(0)) << 5;
-                self.reg_ram.write_enable.next = true;
+                self.regs.update_enable.next = true;
                 self.dstate.d.next = DAQState::Copy0;
             }
             DAQState::Copy0 => {
-                self.reg_ram.write_address.next = 0x0D.into();
-                self.reg_ram.write_data.next =
-                    self.auto_conversion_counter.q.val().get_bits::<8>(3);
-                self.reg_ram.write_enable.next = true;
+                self.regs.update_address.next = 0x0D.into();
+                self.regs.update_data.next = self.auto_conversion_counter.q.val().get_bits::<8>(3);
+                self.regs.update_enable.next = true;
                 self.dstate.d.next = DAQState::Copy1;
             }
             DAQState::Copy1 => {
-                self.reg_ram.write_address.next = 0x0C.into();
-                self.reg_ram.write_data.next =
-                    self.auto_conversion_counter.q.val().get_bits::<8>(11);
-                self.reg_ram.write_enable.next = true;
+                self.regs.update_address.next = 0x0C.into();
+                self.regs.update_data.next = self.auto_conversion_counter.q.val().get_bits::<8>(11);
+                self.regs.update_enable.next = true;
                 self.dstate.d.next = DAQState::Idle;
             }
             _ => {
@@ -0,0 +1,108 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+// Minimal support for the classic libpcap capture format, so that
+// captured network traffic can be replayed into a simulation.  Only
+// Ethernet captures (link type 1) are supported.  Frames in a capture
+// generally do not include the FCS.
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+const PCAP_LINKTYPE_ETHERNET: u32 = 1;
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn parse_pcap(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    if data.len() < 24 {
+        return Err(invalid("pcap file is too short for a header"));
+    }
+    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let little_endian = if magic == PCAP_MAGIC_MICROS || magic == PCAP_MAGIC_NANOS {
+        true
+    } else if magic.swap_bytes() == PCAP_MAGIC_MICROS || magic.swap_bytes() == PCAP_MAGIC_NANOS {
+        false
+    } else {
+        return Err(invalid("not a pcap file (bad magic number)"));
+    };
+    let word = |offset: usize| -> u32 {
+        let bytes = [
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ];
+        if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    };
+    if word(20) & 0xFFFF != PCAP_LINKTYPE_ETHERNET {
+        return Err(invalid("only Ethernet pcap captures are supported"));
+    }
+    let mut frames = vec![];
+    let mut offset = 24;
+    while offset < data.len() {
+        if offset + 16 > data.len() {
+            return Err(invalid("truncated pcap record header"));
+        }
+        let captured = word(offset + 8) as usize;
+        let original = word(offset + 12) as usize;
+        offset += 16;
+        if captured != original {
+            return Err(invalid("pcap record was truncated during capture"));
+        }
+        if offset + captured > data.len() {
+            return Err(invalid("truncated pcap record"));
+        }
+        frames.push(data[offset..offset + captured].to_vec());
+        offset += captured;
+    }
+    Ok(frames)
+}
+
+pub fn read_pcap<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<u8>>> {
+    parse_pcap(&std::fs::read(path)?)
+}
+
+// Writes the frames out as a pcap file (with zero timestamps), which
+// is handy for looking at simulated traffic with Wireshark.
+pub fn write_pcap<P: AsRef<Path>>(path: P, frames: &[Vec<u8>]) -> Result<()> {
+    let mut data = vec![];
+    data.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+    data.extend_from_slice(&2_u16.to_le_bytes());
+    data.extend_from_slice(&4_u16.to_le_bytes());
+    data.extend_from_slice(&0_u32.to_le_bytes());
+    data.extend_from_slice(&0_u32.to_le_bytes());
+    data.extend_from_slice(&65535_u32.to_le_bytes());
+    data.extend_from_slice(&PCAP_LINKTYPE_ETHERNET.to_le_bytes());
+    for frame in frames {
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        data.extend_from_slice(frame);
+    }
+    std::fs::write(path, data)
+}
+
+#[test]
+fn test_pcap_round_trip() {
+    let frames = vec![vec![1, 2, 3, 4], (0..100).collect::<Vec<u8>>(), vec![]];
+    let path = std::env::temp_dir().join("rust_hdl_pcap_round_trip.pcap");
+    write_pcap(&path, &frames).unwrap();
+    assert_eq!(read_pcap(&path).unwrap(), frames);
+}
+
+#[test]
+fn test_pcap_big_endian() {
+    let mut data = vec![0xA1, 0xB2, 0xC3, 0xD4, 0, 2, 0, 4];
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&[0, 0, 0, 1]);
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 2, 0xAB, 0xCD]);
+    assert_eq!(parse_pcap(&data).unwrap(), vec![vec![0xAB, 0xCD]]);
+    data[23] = 105;
+    assert!(parse_pcap(&data).is_err());
+}
@@ -1,7 +1,16 @@
 pub use super::ad7193_sim::*;
 pub use super::ads868x_sim::*;
+pub use super::eeprom_24cxx_sim::{EEPROM24CxxConfig, EEPROM24CxxSimulator};
 pub use super::max31856_sim::*;
 pub use super::max31856_sim::*;
 pub use super::muxed_ad7193_sim::*;
 pub use super::muxed_ads868x_sim::*;
+pub use super::pcap::{parse_pcap, read_pcap, write_pcap};
+pub use super::rmii_phy_sim::RMIIPHYSimulator;
+pub use super::tmp102_sim::{TMP102Config, TMP102Simulator};
+pub use super::w25q_sim::{W25QConfig, W25QSimulator};
+pub use crate::ddr3_sdram::DDR3Simulator;
+pub use crate::register_map::i2c::{I2CRegisterFormat, I2CRegisterMap};
+pub use crate::register_map::spi::{SPICommandFormat, SPIRegisterMap};
 pub use crate::sdr_sdram::chip::SDRAMSimulator;
+pub use crate::sdr_sdram::violation::{SDRAMViolation, SDRAMViolationEvent};
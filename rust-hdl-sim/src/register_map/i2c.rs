@@ -0,0 +1,272 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+/// Describes how a register mapped I2C peripheral is addressed.  The
+/// master writes a register pointer after the target address, and then
+/// either writes register contents, or starts a read (with a repeated
+/// start) to read them back.  Registers are transferred MSB first.
+#[derive(Clone, Copy, Debug)]
+pub struct I2CRegisterFormat {
+    /// The 7 bit target address
+    pub address: u8,
+    /// Number of bytes in the register pointer
+    pub pointer_bytes: usize,
+    /// Move the pointer on to the next register after each register transfer
+    pub auto_increment: bool,
+    /// Writes wrap around within pages of `2^bits` registers (as in an EEPROM page write)
+    pub write_page_bits: Option<usize>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum I2CRegisterMapState {
+    Idle,
+    GetPointer,
+    GetData,
+    ReadFetch,
+    ReadLoad,
+    ReadByte,
+    ReadAck,
+}
+
+/// A register mapped I2C peripheral built on an [I2CTarget].  The map
+/// holds `2^A` registers of `D` bits (a multiple of 8).  Like the
+/// [SPIRegisterMap](crate::register_map::spi::SPIRegisterMap), the device
+/// model sees writes from the master on `write_strobe` (and can veto them
+/// for read only registers with `write_protect`), and can update registers
+/// through the `update` port.  While `hold` is asserted, the target does
+/// not acknowledge its address.
+#[derive(LogicBlock)]
+pub struct I2CRegisterMap<const A: usize, const D: usize> {
+    /// The I2C bus
+    pub i2c: I2CBusDriver,
+    pub clock: Signal<In, Clock>,
+    /// Register writes from the device.  These take priority over writes from the bus.
+    pub update_address: Signal<In, Bits<A>>,
+    pub update_data: Signal<In, Bits<D>>,
+    pub update_enable: Signal<In, Bit>,
+    /// Register reads from the device.  The contents appear on `peek_data` on the next clock.
+    pub peek_address: Signal<In, Bits<A>>,
+    pub peek_enable: Signal<In, Bit>,
+    pub peek_data: Signal<Out, Bits<D>>,
+    /// Strobed when the master writes a register
+    pub write_strobe: Signal<Out, Bit>,
+    pub write_address: Signal<Out, Bits<A>>,
+    pub write_data: Signal<Out, Bits<D>>,
+    /// Raise (in response to `write_address`) to ignore the write
+    pub write_protect: Signal<In, Bit>,
+    /// Strobed when the master reads a register
+    pub read_strobe: Signal<Out, Bit>,
+    pub read_address: Signal<Out, Bits<A>>,
+    /// Strobed when a stop condition is seen on the bus
+    pub stop: Signal<Out, Bit>,
+    /// Raise to ignore the target address (e.g., while the device is busy)
+    pub hold: Signal<In, Bit>,
+    phy: I2CTarget,
+    reg_ram: RAM<Bits<D>, A>,
+    state: DFF<I2CRegisterMapState>,
+    active: DFF<Bit>,
+    pointer: DFF<Bits<A>>,
+    count: DFF<Bits<4>>,
+    accum: DFF<Bits<D>>,
+    outgoing: DFF<Bits<D>>,
+    next_write: Signal<Local, Bits<A>>,
+    word: Signal<Local, Bits<D>>,
+    address: Constant<Bits<7>>,
+    pointer_bytes: Constant<Bits<4>>,
+    reg_bytes: Constant<Bits<4>>,
+    msb_offset: Constant<Bits<8>>,
+    auto_increment: Constant<Bit>,
+    page_mask: Constant<Bits<A>>,
+}
+
+impl<const A: usize, const D: usize> I2CRegisterMap<A, D> {
+    /// Build a register map with the given initial contents
+    pub fn new(format: I2CRegisterFormat, inits: &[u64]) -> Self {
+        assert_eq!(format.address & 0x80, 0, "I2C addresses must be 7 bits");
+        assert!(D.is_multiple_of(8) && D <= 64);
+        assert!(format.pointer_bytes > 0 && format.pointer_bytes <= 2);
+        assert!(inits.len() <= 1 << A);
+        let page_mask = match format.write_page_bits {
+            Some(bits) => (1_u64 << bits.min(A)) - 1,
+            None => (1_u64 << A) - 1,
+        };
+        Self {
+            i2c: Default::default(),
+            clock: Default::default(),
+            update_address: Default::default(),
+            update_data: Default::default(),
+            update_enable: Default::default(),
+            peek_address: Default::default(),
+            peek_enable: Default::default(),
+            peek_data: Default::default(),
+            write_strobe: Default::default(),
+            write_address: Default::default(),
+            write_data: Default::default(),
+            write_protect: Default::default(),
+            read_strobe: Default::default(),
+            read_address: Default::default(),
+            stop: Default::default(),
+            hold: Default::default(),
+            phy: Default::default(),
+            reg_ram: inits.iter().map(|x| x.to_bits()).into(),
+            state: Default::default(),
+            active: Default::default(),
+            pointer: Default::default(),
+            count: Default::default(),
+            accum: Default::default(),
+            outgoing: Default::default(),
+            next_write: Default::default(),
+            word: Default::default(),
+            address: Constant::new(format.address.to_bits()),
+            pointer_bytes: Constant::new(format.pointer_bytes.to_bits()),
+            reg_bytes: Constant::new((D / 8).to_bits()),
+            msb_offset: Constant::new((D - 8).to_bits()),
+            auto_increment: Constant::new(format.auto_increment),
+            page_mask: Constant::new(page_mask.to_bits()),
+        }
+    }
+}
+
+impl<const A: usize, const D: usize> Logic for I2CRegisterMap<A, D> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        I2CBusDriver::link(&mut self.i2c, &mut self.phy.i2c);
+        // Clock internal logic
+        clock!(self, clock, phy);
+        self.reg_ram.read_clock.next = self.clock.val();
+        self.reg_ram.write_clock.next = self.clock.val();
+        dff_setup!(self, clock, state, active, pointer, count, accum, outgoing);
+        // Writes wrap around within a page
+        self.next_write.next = (self.pointer.q.val() & !self.page_mask.val())
+            | ((self.pointer.q.val() + 1) & self.page_mask.val());
+        // Set default values
+        self.phy.active.next = self.active.q.val();
+        self.phy.to_bus.next = 0.into();
+        self.phy.write_enable.next = false;
+        self.reg_ram.read_address.next = self.pointer.q.val();
+        if self.peek_enable.val() {
+            self.reg_ram.read_address.next = self.peek_address.val();
+        }
+        self.peek_data.next = self.reg_ram.read_data.val();
+        self.write_strobe.next = false;
+        self.write_address.next = self.pointer.q.val();
+        self.word.next = (self.accum.q.val() << 8) | bit_cast::<D, 8>(self.phy.from_bus.val());
+        self.write_data.next = self.word.val();
+        self.reg_ram.write_address.next = self.pointer.q.val();
+        self.reg_ram.write_data.next = self.word.val();
+        self.reg_ram.write_enable.next = false;
+        self.read_strobe.next = false;
+        self.read_address.next = self.pointer.q.val();
+        self.stop.next = self.phy.stop.val();
+        match self.state.q.val() {
+            I2CRegisterMapState::Idle => {
+                if self.phy.bus_write.val() {
+                    // Check if the address matches
+                    if (self.phy.from_bus.val().get_bits::<7>(1) == self.address.val())
+                        & !self.hold.val()
+                    {
+                        self.active.d.next = true;
+                        self.count.d.next = 0.into();
+                        if !self.phy.from_bus.val().get_bit(0) {
+                            self.state.d.next = I2CRegisterMapState::GetPointer;
+                        } else {
+                            self.state.d.next = I2CRegisterMapState::ReadFetch;
+                        }
+                    } else {
+                        self.active.d.next = false;
+                    }
+                }
+            }
+            I2CRegisterMapState::GetPointer => {
+                if self.phy.bus_write.val() {
+                    self.pointer.d.next =
+                        (self.pointer.q.val() << 8) | bit_cast::<A, 8>(self.phy.from_bus.val());
+                    self.count.d.next = self.count.q.val() + 1;
+                    if self.count.q.val() + 1 == self.pointer_bytes.val() {
+                        self.count.d.next = 0.into();
+                        self.state.d.next = I2CRegisterMapState::GetData;
+                    }
+                }
+            }
+            I2CRegisterMapState::GetData => {
+                if self.phy.bus_write.val() {
+                    self.accum.d.next = self.word.val();
+                    self.count.d.next = self.count.q.val() + 1;
+                    if self.count.q.val() + 1 == self.reg_bytes.val() {
+                        self.count.d.next = 0.into();
+                        self.write_strobe.next = true;
+                        self.reg_ram.write_enable.next = !self.write_protect.val();
+                        if self.auto_increment.val() {
+                            self.pointer.d.next = self.next_write.val();
+                        }
+                    }
+                }
+            }
+            I2CRegisterMapState::ReadFetch => {
+                self.state.d.next = I2CRegisterMapState::ReadLoad;
+            }
+            I2CRegisterMapState::ReadLoad => {
+                self.read_strobe.next = true;
+                self.outgoing.d.next = self.reg_ram.read_data.val();
+                self.count.d.next = 0.into();
+                self.state.d.next = I2CRegisterMapState::ReadByte;
+            }
+            I2CRegisterMapState::ReadByte => {
+                if self.phy.write_ok.val() {
+                    self.phy.to_bus.next = self
+                        .outgoing
+                        .q
+                        .val()
+                        .get_bits::<8>(self.msb_offset.val().index());
+                    self.phy.write_enable.next = true;
+                    self.outgoing.d.next = self.outgoing.q.val() << 8;
+                    self.count.d.next = self.count.q.val() + 1;
+                    self.state.d.next = I2CRegisterMapState::ReadAck;
+                }
+            }
+            I2CRegisterMapState::ReadAck => {
+                if self.phy.ack.val() {
+                    if self.count.q.val() == self.reg_bytes.val() {
+                        if self.auto_increment.val() {
+                            self.pointer.d.next = self.pointer.q.val() + 1;
+                        }
+                        self.state.d.next = I2CRegisterMapState::ReadFetch;
+                    } else {
+                        self.state.d.next = I2CRegisterMapState::ReadByte;
+                    }
+                }
+                if self.phy.nack.val() {
+                    self.state.d.next = I2CRegisterMapState::Idle;
+                }
+            }
+            _ => {
+                self.state.d.next = I2CRegisterMapState::Idle;
+            }
+        }
+        if self.phy.stop.val() {
+            self.state.d.next = I2CRegisterMapState::Idle;
+            self.active.d.next = false;
+        }
+        // Writes from the device win over writes from the bus
+        if self.update_enable.val() {
+            self.reg_ram.write_address.next = self.update_address.val();
+            self.reg_ram.write_data.next = self.update_data.val();
+            self.reg_ram.write_enable.next = true;
+        }
+    }
+}
+
+#[test]
+fn test_i2c_register_map_synthesizes() {
+    let mut uut: I2CRegisterMap<4, 16> = I2CRegisterMap::new(
+        I2CRegisterFormat {
+            address: 0x48,
+            pointer_bytes: 1,
+            auto_increment: true,
+            write_page_bits: None,
+        },
+        &[],
+    );
+    uut.connect_all();
+    yosys_validate("i2c_register_map", &generate_verilog(&uut)).unwrap();
+}
@@ -0,0 +1,2 @@
+pub mod i2c;
+pub mod spi;
@@ -0,0 +1,417 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+/// Describes how a register mapped SPI peripheral decodes the command word
+/// that starts each transaction, and how the register contents are framed
+/// on the bus.
+#[derive(Clone, Copy, Debug)]
+pub struct SPICommandFormat {
+    /// Number of bits in the command word.  Framed protocols take the
+    /// frame length from the device `reply_bits` instead.
+    pub command_bits: usize,
+    /// Offset of the register address in the command word
+    pub address_offset: usize,
+    /// The bit in the command word that selects a read or a write
+    pub write_bit: usize,
+    /// The value of `write_bit` that indicates a write
+    pub write_level: bool,
+    /// Command words with `(cmd & mask) == value` are handed to the device
+    /// instead of being decoded as register accesses
+    pub device_commands: Option<(u64, u64)>,
+    /// An all zero command word is handed to the device (a NOP on many parts)
+    pub zero_is_device_command: bool,
+    /// Bits clocked between the command word and the register contents on a read
+    pub read_turnaround_bits: usize,
+    /// Number of status bits appended to the register contents on a read
+    pub status_bits: usize,
+    /// The value of the appended status bits
+    pub status_value: u64,
+    /// Send the written value back to the master after a register write
+    pub write_echo: bool,
+    /// Move on to the next register if the master keeps the chip select asserted
+    pub auto_increment: bool,
+    /// The interface ignores the bus until the first transfer, and is reset
+    /// by a transfer of all ones
+    pub reset_on_ones: bool,
+    /// Each command is a complete transaction, and the device reply is
+    /// shifted out during the next one
+    pub framed: bool,
+    /// Shifted out while the master sends commands and write data
+    pub idle_value: u64,
+}
+
+impl Default for SPICommandFormat {
+    fn default() -> Self {
+        Self {
+            command_bits: 8,
+            address_offset: 0,
+            write_bit: 7,
+            write_level: true,
+            device_commands: None,
+            zero_is_device_command: false,
+            read_turnaround_bits: 0,
+            status_bits: 0,
+            status_value: 0,
+            write_echo: false,
+            auto_increment: false,
+            reset_on_ones: false,
+            framed: false,
+            idle_value: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum SPIRegisterMapState {
+    Boot,
+    WaitReset,
+    Ready,
+    GettingCmd,
+    Dispatch,
+    ReadTurnaround,
+    ReadFetch,
+    ReadSend,
+    ReadWait,
+    WriteStart,
+    WriteRecv,
+    WriteEcho,
+    CommandWait,
+    WaitIdle,
+    Held,
+}
+
+/// A register mapped SPI peripheral.  The [SPIRegisterMap] decodes the
+/// command word according to a [SPICommandFormat], and services register
+/// reads and writes out of a RAM of `2^A` registers of up to `D` bits each.
+/// The device model built around it sees the register writes from the
+/// master on `write_strobe`, can update registers (e.g., with conversion
+/// results) through the `update` port, and handles any device commands
+/// that are not register accesses.
+#[derive(LogicBlock)]
+pub struct SPIRegisterMap<const A: usize, const D: usize> {
+    /// The SPI bus
+    pub wires: SPIWiresSlave,
+    pub clock: Signal<In, Clock>,
+    /// Register writes from the device.  These take priority over writes from the bus.
+    pub update_address: Signal<In, Bits<A>>,
+    pub update_data: Signal<In, Bits<D>>,
+    pub update_enable: Signal<In, Bit>,
+    /// Register reads from the device.  The contents appear on `peek_data` on the next clock.
+    pub peek_address: Signal<In, Bits<A>>,
+    pub peek_enable: Signal<In, Bit>,
+    pub peek_data: Signal<Out, Bits<D>>,
+    /// Strobed when the master writes a register
+    pub write_strobe: Signal<Out, Bit>,
+    pub write_address: Signal<Out, Bits<A>>,
+    pub write_data: Signal<Out, Bits<D>>,
+    /// Strobed when the master reads a register
+    pub read_strobe: Signal<Out, Bit>,
+    pub read_address: Signal<Out, Bits<A>>,
+    /// Strobed when the master sends a device command
+    pub command_strobe: Signal<Out, Bit>,
+    pub command: Signal<Out, Bits<64>>,
+    /// The reply to the last device command
+    pub reply: Signal<In, Bits<64>>,
+    pub reply_bits: Signal<In, Bits<16>>,
+    /// Raise to hold off the next transaction (e.g., while the device is busy)
+    pub hold: Signal<In, Bit>,
+    /// Raise to ignore the bus entirely
+    pub disabled: Signal<In, Bit>,
+    spi_slave: SPISlave<64>,
+    reg_ram: RAM<Bits<D>, A>,
+    reg_width_rom: ROM<Bits<8>, A>,
+    state: DFF<SPIRegisterMapState>,
+    cmd: DFF<Bits<64>>,
+    address: DFF<Bits<A>>,
+    // Fields split out of the command word
+    cmd_address: Signal<Local, Bits<A>>,
+    write_flag: Signal<Local, Bit>,
+    device_flag: Signal<Local, Bit>,
+    // The command format
+    command_bits: Constant<Bits<16>>,
+    address_offset: Constant<Bits<8>>,
+    write_bit: Constant<Bits<8>>,
+    write_level: Constant<Bit>,
+    has_device_commands: Constant<Bit>,
+    command_mask: Constant<Bits<64>>,
+    command_value: Constant<Bits<64>>,
+    zero_is_command: Constant<Bit>,
+    turnaround_bits: Constant<Bits<16>>,
+    status_bits: Constant<Bits<16>>,
+    status_value: Constant<Bits<64>>,
+    write_echo: Constant<Bit>,
+    auto_increment: Constant<Bit>,
+    reset_on_ones: Constant<Bit>,
+    framed: Constant<Bit>,
+    idle_value: Constant<Bits<64>>,
+}
+
+impl<const A: usize, const D: usize> SPIRegisterMap<A, D> {
+    /// Build a register map with the given register widths (in bits) and initial contents.
+    pub fn new(spi: SPIConfig, format: SPICommandFormat, widths: &[usize], inits: &[u64]) -> Self {
+        assert!(spi.clock_speed > 10 * spi.speed_hz);
+        assert!(D <= 64);
+        assert_eq!(widths.len(), 1 << A);
+        assert!(inits.len() <= 1 << A);
+        assert!(widths.iter().all(|x| *x <= D));
+        assert!(format.command_bits <= 64);
+        assert!(format.address_offset + A <= format.command_bits);
+        let (command_mask, command_value) = format.device_commands.unwrap_or_default();
+        Self {
+            wires: Default::default(),
+            clock: Default::default(),
+            update_address: Default::default(),
+            update_data: Default::default(),
+            update_enable: Default::default(),
+            peek_address: Default::default(),
+            peek_enable: Default::default(),
+            peek_data: Default::default(),
+            write_strobe: Default::default(),
+            write_address: Default::default(),
+            write_data: Default::default(),
+            read_strobe: Default::default(),
+            read_address: Default::default(),
+            command_strobe: Default::default(),
+            command: Default::default(),
+            reply: Default::default(),
+            reply_bits: Default::default(),
+            hold: Default::default(),
+            disabled: Default::default(),
+            spi_slave: SPISlave::new(spi),
+            reg_ram: inits.iter().map(|x| x.to_bits()).into(),
+            reg_width_rom: widths.iter().map(|x| x.to_bits()).into(),
+            state: Default::default(),
+            cmd: Default::default(),
+            address: Default::default(),
+            cmd_address: Default::default(),
+            write_flag: Default::default(),
+            device_flag: Default::default(),
+            command_bits: Constant::new(format.command_bits.to_bits()),
+            address_offset: Constant::new(format.address_offset.to_bits()),
+            write_bit: Constant::new(format.write_bit.to_bits()),
+            write_level: Constant::new(format.write_level),
+            has_device_commands: Constant::new(format.device_commands.is_some()),
+            command_mask: Constant::new(command_mask.to_bits()),
+            command_value: Constant::new(command_value.to_bits()),
+            zero_is_command: Constant::new(format.zero_is_device_command),
+            turnaround_bits: Constant::new(format.read_turnaround_bits.to_bits()),
+            status_bits: Constant::new(format.status_bits.to_bits()),
+            status_value: Constant::new(format.status_value.to_bits()),
+            write_echo: Constant::new(format.write_echo),
+            auto_increment: Constant::new(format.auto_increment),
+            reset_on_ones: Constant::new(format.reset_on_ones),
+            framed: Constant::new(format.framed),
+            idle_value: Constant::new(format.idle_value.to_bits()),
+        }
+    }
+}
+
+impl<const A: usize, const D: usize> Logic for SPIRegisterMap<A, D> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        // Connect the spi bus
+        SPIWiresSlave::link(&mut self.wires, &mut self.spi_slave.wires);
+        // Clock internal components
+        self.reg_ram.read_clock.next = self.clock.val();
+        self.reg_ram.write_clock.next = self.clock.val();
+        clock!(self, clock, spi_slave);
+        dff_setup!(self, clock, state, cmd, address);
+        // Decode the command word
+        self.cmd_address.next = self
+            .cmd
+            .q
+            .val()
+            .get_bits::<A>(self.address_offset.val().index());
+        self.write_flag.next =
+            self.cmd.q.val().get_bit(self.write_bit.val().index()) == self.write_level.val();
+        self.device_flag.next = (self.has_device_commands.val()
+            & ((self.cmd.q.val() & self.command_mask.val()) == self.command_value.val()))
+            | (self.zero_is_command.val() & !self.cmd.q.val().any());
+        // Set default values
+        self.spi_slave.start_send.next = false;
+        self.spi_slave.continued_transaction.next = !self.framed.val();
+        self.spi_slave.bits.next = 0.into();
+        self.spi_slave.data_outbound.next = self.idle_value.val();
+        self.spi_slave.disabled.next = self.disabled.val();
+        self.reg_width_rom.address.next = self.address.q.val();
+        self.reg_ram.read_address.next = self.address.q.val();
+        if self.peek_enable.val() {
+            self.reg_ram.read_address.next = self.peek_address.val();
+        }
+        self.peek_data.next = self.reg_ram.read_data.val();
+        self.reg_ram.write_address.next = self.address.q.val();
+        self.reg_ram.write_data.next = self.spi_slave.data_inbound.val().get_bits::<D>(0);
+        self.reg_ram.write_enable.next = false;
+        self.write_strobe.next = false;
+        self.write_address.next = self.address.q.val();
+        self.write_data.next = self.spi_slave.data_inbound.val().get_bits::<D>(0);
+        self.read_strobe.next = false;
+        self.read_address.next = self.address.q.val();
+        self.command_strobe.next = false;
+        self.command.next = self.cmd.q.val();
+        match self.state.q.val() {
+            SPIRegisterMapState::Boot => {
+                if !self.spi_slave.busy.val() {
+                    if self.reset_on_ones.val() {
+                        self.state.d.next = SPIRegisterMapState::WaitReset;
+                    } else {
+                        self.state.d.next = SPIRegisterMapState::Ready;
+                    }
+                }
+            }
+            SPIRegisterMapState::WaitReset => {
+                if self.spi_slave.transfer_done.val() {
+                    self.state.d.next = SPIRegisterMapState::Ready;
+                }
+            }
+            SPIRegisterMapState::Ready => {
+                if !self.hold.val() {
+                    if self.framed.val() {
+                        self.spi_slave.bits.next = self.reply_bits.val();
+                        self.spi_slave.data_outbound.next = self.reply.val();
+                    } else {
+                        self.spi_slave.bits.next = self.command_bits.val();
+                    }
+                    self.spi_slave.start_send.next = true;
+                    self.state.d.next = SPIRegisterMapState::GettingCmd;
+                }
+            }
+            SPIRegisterMapState::GettingCmd => {
+                if self.spi_slave.transfer_done.val() {
+                    self.cmd.d.next = self.spi_slave.data_inbound.val();
+                    self.state.d.next = SPIRegisterMapState::Dispatch;
+                }
+            }
+            SPIRegisterMapState::Dispatch => {
+                self.address.d.next = self.cmd_address.val();
+                if self.device_flag.val() {
+                    self.command_strobe.next = true;
+                    self.state.d.next = SPIRegisterMapState::CommandWait;
+                } else if self.write_flag.val() {
+                    self.state.d.next = SPIRegisterMapState::WriteStart;
+                } else if self.turnaround_bits.val().any() {
+                    self.spi_slave.bits.next = self.turnaround_bits.val();
+                    self.spi_slave.start_send.next = true;
+                    self.state.d.next = SPIRegisterMapState::ReadTurnaround;
+                } else {
+                    self.state.d.next = SPIRegisterMapState::ReadFetch;
+                }
+            }
+            SPIRegisterMapState::ReadTurnaround => {
+                if self.spi_slave.transfer_done.val() {
+                    self.state.d.next = SPIRegisterMapState::ReadFetch;
+                }
+            }
+            SPIRegisterMapState::ReadFetch => {
+                self.read_strobe.next = true;
+                self.state.d.next = SPIRegisterMapState::ReadSend;
+            }
+            SPIRegisterMapState::ReadSend => {
+                self.spi_slave.bits.next =
+                    bit_cast::<16, 8>(self.reg_width_rom.data.val()) + self.status_bits.val();
+                self.spi_slave.data_outbound.next =
+                    (bit_cast::<64, D>(self.reg_ram.read_data.val()) << self.status_bits.val())
+                        | self.status_value.val();
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = SPIRegisterMapState::ReadWait;
+            }
+            SPIRegisterMapState::ReadWait => {
+                if self.spi_slave.transfer_done.val()
+                    & self.spi_slave.busy.val()
+                    & self.auto_increment.val()
+                {
+                    self.address.d.next = self.address.q.val() + 1;
+                    self.state.d.next = SPIRegisterMapState::ReadFetch;
+                } else if !self.spi_slave.busy.val() {
+                    self.state.d.next = SPIRegisterMapState::Ready;
+                }
+            }
+            SPIRegisterMapState::WriteStart => {
+                self.spi_slave.bits.next = bit_cast::<16, 8>(self.reg_width_rom.data.val());
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = SPIRegisterMapState::WriteRecv;
+            }
+            SPIRegisterMapState::WriteRecv => {
+                if self.spi_slave.transfer_done.val() {
+                    self.reg_ram.write_enable.next = true;
+                    self.write_strobe.next = true;
+                    if self.write_echo.val() {
+                        self.state.d.next = SPIRegisterMapState::WriteEcho;
+                    } else if self.spi_slave.busy.val() & self.auto_increment.val() {
+                        self.address.d.next = self.address.q.val() + 1;
+                        self.state.d.next = SPIRegisterMapState::WriteStart;
+                    } else {
+                        self.state.d.next = SPIRegisterMapState::WaitIdle;
+                    }
+                }
+            }
+            SPIRegisterMapState::WriteEcho => {
+                self.spi_slave.bits.next = bit_cast::<16, 8>(self.reg_width_rom.data.val());
+                self.spi_slave.data_outbound.next =
+                    bit_cast::<64, D>(self.spi_slave.data_inbound.val().get_bits::<D>(0));
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = SPIRegisterMapState::WaitIdle;
+            }
+            SPIRegisterMapState::CommandWait => {
+                if !self.hold.val() {
+                    if self.framed.val() {
+                        self.state.d.next = SPIRegisterMapState::Ready;
+                    } else if self.reply_bits.val().any() {
+                        self.spi_slave.bits.next = self.reply_bits.val();
+                        self.spi_slave.data_outbound.next = self.reply.val();
+                        self.spi_slave.start_send.next = true;
+                        self.state.d.next = SPIRegisterMapState::WaitIdle;
+                    } else {
+                        self.state.d.next = SPIRegisterMapState::WaitIdle;
+                    }
+                }
+            }
+            SPIRegisterMapState::WaitIdle => {
+                if self.hold.val() {
+                    self.state.d.next = SPIRegisterMapState::Held;
+                } else if !self.spi_slave.busy.val() {
+                    self.state.d.next = SPIRegisterMapState::Ready;
+                }
+            }
+            SPIRegisterMapState::Held => {
+                if !self.hold.val() {
+                    self.state.d.next = SPIRegisterMapState::Ready;
+                }
+            }
+            _ => {
+                self.state.d.next = SPIRegisterMapState::Boot;
+            }
+        }
+        if self.reset_on_ones.val()
+            & self.spi_slave.transfer_done.val()
+            & self.spi_slave.data_inbound.val().all()
+        {
+            self.state.d.next = SPIRegisterMapState::Ready;
+        }
+        // Writes from the device win over writes from the bus
+        if self.update_enable.val() {
+            self.reg_ram.write_address.next = self.update_address.val();
+            self.reg_ram.write_data.next = self.update_data.val();
+            self.reg_ram.write_enable.next = true;
+        }
+    }
+}
+
+#[test]
+fn test_spi_register_map_synthesizes() {
+    let mut uut: SPIRegisterMap<4, 8> = SPIRegisterMap::new(
+        SPIConfig {
+            clock_speed: 1_000_000,
+            cs_off: true,
+            mosi_off: true,
+            speed_hz: 10_000,
+            cpha: true,
+            cpol: true,
+        },
+        SPICommandFormat::default(),
+        &[8; 16],
+        &[],
+    );
+    uut.connect_all();
+    yosys_validate("spi_register_map", &generate_verilog(&uut)).unwrap();
+}
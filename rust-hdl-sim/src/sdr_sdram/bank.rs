@@ -1,3 +1,4 @@
+use crate::sdr_sdram::violation::SDRAMViolation;
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::prelude::*;
 
@@ -9,7 +10,6 @@ pub enum BankState {
     Reading,
     Precharging,
     Writing,
-    Error,
     Autorefreshing,
     WriteRecovery,
 }
@@ -29,6 +29,7 @@ pub struct MemoryBank<const R: usize, const C: usize, const A: usize, const D: u
     pub burst_len: Signal<In, Bits<4>>,
     pub cmd: Signal<In, SDRAMCommand>,
     pub error: Signal<Out, Bit>,
+    pub violation: Signal<Out, SDRAMViolation>,
     pub busy: Signal<Out, Bit>,
     pub write_data: Signal<In, Bits<D>>,
     pub read_data: Signal<Out, Bits<D>>,
@@ -38,6 +39,8 @@ pub struct MemoryBank<const R: usize, const C: usize, const A: usize, const D: u
     read_delay_line: DelayLine<Bit, 7, 3>,
     refresh_counter: DFF<Bits<32>>,
     refresh_active: DFF<Bit>,
+    // Latched on the first violation, while the bank keeps checking
+    failed: DFF<Bit>,
     mem: RAM<Bits<D>, A>,
     write_reg: DFF<Bits<D>>,
     state: DFF<BankState>,
@@ -75,6 +78,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> MemoryBank<
             burst_len: Default::default(),
             cmd: Default::default(),
             error: Default::default(),
+            violation: Default::default(),
             busy: Default::default(),
             write_data: Default::default(),
             read_data: Default::default(),
@@ -92,6 +96,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> MemoryBank<
             delay_counter: Default::default(),
             refresh_counter: Default::default(),
             refresh_active: Default::default(),
+            failed: Default::default(),
             t_activate: Default::default(),
             t_ras: Constant::new(t_ras.to_bits()),
             t_rc: Constant::new(t_rc.to_bits()),
@@ -118,6 +123,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
             clock,
             refresh_counter,
             refresh_active,
+            failed,
             write_reg,
             state,
             auto_precharge,
@@ -129,7 +135,8 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
         );
         clock!(self, clock, delay_line, read_delay_line);
         self.delay_counter.d.next = self.delay_counter.q.val() + 1;
-        self.error.next = false;
+        self.error.next = self.failed.q.val();
+        self.violation.next = SDRAMViolation::None;
         // Model the row-column multiplexing
         self.mem.read_address.next = (bit_cast::<A, R>(self.active_row.q.val())
             << self.row_shift.val())
@@ -160,19 +167,19 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                 if self.select.val() {
                     match self.cmd.val() {
                         SDRAMCommand::Active => {
-                            // Reset the activate timer
                             if self.t_activate.q.val() < self.t_rc.val() {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                self.t_activate.d.next = 0.into();
-                                // Activate the given row.
-                                // Load the row into the row register
-                                self.active_row.d.next = self.address.val().get_bits::<R>(0);
-                                // Reset the delay timer
-                                self.delay_counter.d.next = 0.into();
-                                // Transition to the activating state.
-                                self.state.d.next = BankState::Active;
+                                self.violation.next = SDRAMViolation::TRc;
+                                self.failed.d.next = true;
                             }
+                            // Reset the activate timer
+                            self.t_activate.d.next = 0.into();
+                            // Activate the given row.
+                            // Load the row into the row register
+                            self.active_row.d.next = self.address.val().get_bits::<R>(0);
+                            // Reset the delay timer
+                            self.delay_counter.d.next = 0.into();
+                            // Transition to the activating state.
+                            self.state.d.next = BankState::Active;
                         }
                         SDRAMCommand::NOP => {}
                         SDRAMCommand::Precharge => {} // See ISSI docs.  Precharging an idle bank is a NOP
@@ -180,16 +187,17 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                             if self.refresh_active.q.val()
                                 & (self.refresh_counter.q.val() < self.t_rc.val())
                             {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                self.state.d.next = BankState::Autorefreshing;
-                                self.refresh_active.d.next = true;
-                                self.refresh_counter.d.next = 0.into();
+                                self.violation.next = SDRAMViolation::TRfc;
+                                self.failed.d.next = true;
                             }
+                            self.state.d.next = BankState::Autorefreshing;
+                            self.refresh_active.d.next = true;
+                            self.refresh_counter.d.next = 0.into();
                         } // Handled at the chip level
                         SDRAMCommand::LoadModeRegister => {} // Ignored by banks
                         _ => {
-                            self.state.d.next = BankState::Error;
+                            self.violation.next = SDRAMViolation::IllegalCommand;
+                            self.failed.d.next = true;
                         }
                     }
                 }
@@ -200,39 +208,38 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                         SDRAMCommand::NOP => {}
                         SDRAMCommand::Read => {
                             if self.t_activate.q.val() < self.t_rcd.val() {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                // RCD is met, we want to read
-                                self.active_col.d.next = self.address.val().get_bits::<C>(0);
-                                self.burst_counter.d.next = 0.into();
-                                self.state.d.next = BankState::Reading;
-                                // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
-                                self.auto_precharge.d.next = self.address.val().get_bit(10);
+                                self.violation.next = SDRAMViolation::TRcd;
+                                self.failed.d.next = true;
                             }
+                            self.active_col.d.next = self.address.val().get_bits::<C>(0);
+                            self.burst_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Reading;
+                            // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
+                            self.auto_precharge.d.next = self.address.val().get_bit(10);
                         }
                         SDRAMCommand::Write => {
                             if self.t_activate.q.val() < self.t_rcd.val() {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                // RCD is met, we want to write
-                                self.active_col.d.next = self.address.val().get_bits::<C>(0);
-                                self.burst_counter.d.next = 0.into();
-                                self.state.d.next = BankState::Writing;
-                                // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
-                                self.auto_precharge.d.next = self.address.val().get_bit(10);
+                                self.violation.next = SDRAMViolation::TRcd;
+                                self.failed.d.next = true;
                             }
+                            self.active_col.d.next = self.address.val().get_bits::<C>(0);
+                            self.burst_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Writing;
+                            // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
+                            self.auto_precharge.d.next = self.address.val().get_bit(10);
                         }
                         SDRAMCommand::Precharge => {
                             if self.t_activate.q.val() < self.t_ras.val() {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                // RAS is met, we can close the current row
-                                self.delay_counter.d.next = 0.into();
-                                self.state.d.next = BankState::Precharging;
+                                self.violation.next = SDRAMViolation::TRas;
+                                self.failed.d.next = true;
                             }
+                            // Close the current row
+                            self.delay_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Precharging;
                         }
                         _ => {
-                            self.state.d.next = BankState::Error;
+                            self.violation.next = SDRAMViolation::IllegalCommand;
+                            self.failed.d.next = true;
                         }
                     }
                 }
@@ -265,14 +272,15 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                         }
                         SDRAMCommand::Precharge => {
                             if self.auto_precharge.q.val() {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                self.delay_counter.d.next = 0.into();
-                                self.state.d.next = BankState::Precharging;
+                                self.violation.next = SDRAMViolation::BurstInterrupted;
+                                self.failed.d.next = true;
                             }
+                            self.delay_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Precharging;
                         }
                         _ => {
-                            self.state.d.next = BankState::Error;
+                            self.violation.next = SDRAMViolation::BurstInterrupted;
+                            self.failed.d.next = true;
                         }
                     }
                 }
@@ -285,7 +293,8 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                     match self.cmd.val() {
                         SDRAMCommand::NOP => {}
                         _ => {
-                            self.state.d.next = BankState::Error;
+                            self.violation.next = SDRAMViolation::TRp;
+                            self.failed.d.next = true;
                         }
                     }
                 }
@@ -298,7 +307,8 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                     match self.cmd.val() {
                         SDRAMCommand::NOP => {}
                         _ => {
-                            self.state.d.next = BankState::Error;
+                            self.violation.next = SDRAMViolation::TRfc;
+                            self.failed.d.next = true;
                         }
                     }
                 }
@@ -329,43 +339,45 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                         }
                         SDRAMCommand::Precharge => {
                             if self.auto_precharge.q.val() {
-                                self.state.d.next = BankState::Error;
-                            } else {
-                                self.delay_counter.d.next = 0.into();
-                                self.state.d.next = BankState::Precharging;
+                                self.violation.next = SDRAMViolation::BurstInterrupted;
+                                self.failed.d.next = true;
                             }
+                            self.delay_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Precharging;
                         }
                         _ => {
-                            self.state.d.next = BankState::Error;
+                            self.violation.next = SDRAMViolation::BurstInterrupted;
+                            self.failed.d.next = true;
                         }
                     }
                 }
             }
-            BankState::Error => {
-                self.error.next = true;
-            }
             BankState::WriteRecovery => {
                 if self.delay_counter.q.val() == self.t_wr.val() {
                     self.state.d.next = BankState::Active;
                 }
-                match self.cmd.val() {
-                    SDRAMCommand::NOP => {}
-                    SDRAMCommand::Read => {
-                        self.active_col.d.next = self.address.val().get_bits::<C>(0);
-                        self.burst_counter.d.next = 0.into();
-                        self.state.d.next = BankState::Reading;
-                        // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
-                        self.auto_precharge.d.next = self.address.val().get_bit(10);
-                    }
-                    SDRAMCommand::Write => {
-                        self.active_col.d.next = self.address.val().get_bits::<C>(0);
-                        self.burst_counter.d.next = 0.into();
-                        self.state.d.next = BankState::Writing;
-                        // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
-                        self.auto_precharge.d.next = self.address.val().get_bit(10);
-                    }
-                    _ => {
-                        self.state.d.next = BankState::Error;
+                // Commands to other banks can be sent during write recovery
+                if self.select.val() {
+                    match self.cmd.val() {
+                        SDRAMCommand::NOP => {}
+                        SDRAMCommand::Read => {
+                            self.active_col.d.next = self.address.val().get_bits::<C>(0);
+                            self.burst_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Reading;
+                            // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
+                            self.auto_precharge.d.next = self.address.val().get_bit(10);
+                        }
+                        SDRAMCommand::Write => {
+                            self.active_col.d.next = self.address.val().get_bits::<C>(0);
+                            self.burst_counter.d.next = 0.into();
+                            self.state.d.next = BankState::Writing;
+                            // Capture the auto precharge bit (bit 10) - this is the per the JEDEC spec
+                            self.auto_precharge.d.next = self.address.val().get_bit(10);
+                        }
+                        _ => {
+                            self.violation.next = SDRAMViolation::TWr;
+                            self.failed.d.next = true;
+                        }
                     }
                 }
             }
@@ -373,8 +385,10 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                 self.state.d.next = BankState::Boot;
             }
         }
-        if self.refresh_counter.q.val() >= self.t_refresh_max.val() {
-            self.state.d.next = BankState::Error;
+        // Flag the refresh interval once, when it runs out
+        if self.refresh_counter.q.val() == self.t_refresh_max.val() {
+            self.violation.next = SDRAMViolation::RefreshInterval;
+            self.failed.d.next = true;
         }
     }
 }
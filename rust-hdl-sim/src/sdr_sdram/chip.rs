@@ -1,4 +1,5 @@
 use crate::sdr_sdram::bank::MemoryBank;
+use crate::sdr_sdram::violation::{SDRAMViolation, SDRAMViolationEvent, SDRAMViolationMonitor};
 use rust_hdl_core::prelude::*;
 use rust_hdl_widgets::{
     prelude::*,
@@ -17,6 +18,14 @@ enum MasterState {
 }
 
 // Clock enable, and DQM are ignored.
+//
+// A command that breaks a timing or protocol rule is reported on `violation`
+// (and the bank on `violation_bank`) for the clock in which the command was
+// sent, recorded for the testbench (see [SDRAMSimulator::violations]), and
+// latches `test_error`.  The banks keep checking after a violation (a command
+// sent too early is still carried out, and an illegal one is ignored), so
+// every violation is recorded.  A violation of the boot sequence leaves the
+// chip uninitialized, and it accepts no further commands.
 #[derive(LogicBlock)]
 pub struct SDRAMSimulator<
     const R: usize, // Number of rows
@@ -27,6 +36,9 @@ pub struct SDRAMSimulator<
     pub sdram: SDRAMDevice<D>,
     pub test_error: Signal<Out, Bit>,
     pub test_ready: Signal<Out, Bit>,
+    pub violation: Signal<Out, SDRAMViolation>,
+    pub violation_bank: Signal<Out, Bits<2>>,
+    monitor: SDRAMViolationMonitor,
     decode: SDRAMCommandDecoder,
     clock: Signal<Local, Clock>,
     cmd: Signal<Local, SDRAMCommand>,
@@ -38,6 +50,8 @@ pub struct SDRAMSimulator<
     burst_type: DFF<Bit>,
     burst_len: DFF<Bits<3>>,
     op_mode: DFF<Bits<2>>,
+    since_activate: DFF<Bits<32>>,
+    failed: DFF<Bit>,
     banks: [MemoryBank<R, C, A, D>; 4],
     // Timings
     // Number of clocks to delay for boot initialization
@@ -65,7 +79,9 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
             cas_latency,
             burst_type,
             burst_len,
-            op_mode
+            op_mode,
+            since_activate,
+            failed
         );
         // Connect the command decoder to the bus
         self.decode.we_not.next = self.sdram.we_not.val();
@@ -73,8 +89,11 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
         self.decode.ras_not.next = self.sdram.ras_not.val();
         self.decode.cs_not.next = self.sdram.cs_not.val();
         self.cmd.next = self.decode.cmd.val();
-        self.test_error.next = false;
+        self.test_error.next = self.failed.q.val();
         self.test_ready.next = false;
+        self.violation.next = SDRAMViolation::None;
+        self.violation_bank.next = self.sdram.bank.val();
+        self.since_activate.d.next = self.since_activate.q.val() + 1;
         // Connect up the banks to the I/O buffer
         self.sdram.read_data.next = 0.into();
         for i in 0..4 {
@@ -96,14 +115,24 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                 1 => self.banks[i].burst_len.next = 2.into(),
                 2 => self.banks[i].burst_len.next = 4.into(),
                 3 => self.banks[i].burst_len.next = 8.into(),
-                _ => self.state.d.next = MasterState::Error,
+                _ => {
+                    if self.state.q.val() != MasterState::Error {
+                        self.violation.next = SDRAMViolation::BurstLength;
+                    }
+                    self.state.d.next = MasterState::Error;
+                }
             }
             self.banks[i].cas_delay.next = 2.into();
             match self.cas_latency.q.val().index() {
                 0 => self.banks[i].cas_delay.next = 0.into(),
                 2 => self.banks[i].cas_delay.next = 2.into(),
                 3 => self.banks[i].cas_delay.next = 3.into(),
-                _ => self.state.d.next = MasterState::Error,
+                _ => {
+                    if self.state.q.val() != MasterState::Error {
+                        self.violation.next = SDRAMViolation::CasLatency;
+                    }
+                    self.state.d.next = MasterState::Error;
+                }
             }
             if self.sdram.bank.val().index() == i {
                 self.banks[i].select.next = true;
@@ -139,6 +168,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                     SDRAMCommand::Precharge => {
                         // make sure the ALL bit is set
                         if self.sdram.address.val().get_bit(10) != true {
+                            self.violation.next = SDRAMViolation::Boot;
                             self.state.d.next = MasterState::Error;
                         } else {
                             self.counter.d.next = 0.into();
@@ -146,6 +176,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                         }
                     }
                     _ => {
+                        self.violation.next = SDRAMViolation::Boot;
                         self.state.d.next = MasterState::Error;
                     }
                 }
@@ -156,6 +187,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                     self.state.d.next = MasterState::WaitAutorefresh;
                 }
                 if self.cmd.val() != SDRAMCommand::NOP {
+                    self.violation.next = SDRAMViolation::TRp;
                     self.state.d.next = MasterState::Error;
                 }
             }
@@ -163,6 +195,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                 SDRAMCommand::NOP => {}
                 SDRAMCommand::AutoRefresh => {
                     if self.banks_busy.val() {
+                        self.violation.next = SDRAMViolation::TRfc;
                         self.state.d.next = MasterState::Error;
                     } else {
                         self.auto_refresh_init_counter.d.next =
@@ -171,6 +204,7 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                 }
                 SDRAMCommand::LoadModeRegister => {
                     if self.auto_refresh_init_counter.q.val() < 2 {
+                        self.violation.next = SDRAMViolation::Boot;
                         self.state.d.next = MasterState::Error;
                     } else {
                         self.counter.d.next = 0.into();
@@ -181,11 +215,13 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                         self.op_mode.d.next = self.sdram.address.val().get_bits::<2>(7);
                         self.write_burst_mode.d.next = self.sdram.address.val().get_bit(9);
                         if self.sdram.address.val().get_bits::<2>(10) != 0 {
+                            self.violation.next = SDRAMViolation::ModeRegister;
                             self.state.d.next = MasterState::Error;
                         }
                     }
                 }
                 _ => {
+                    self.violation.next = SDRAMViolation::Boot;
                     self.state.d.next = MasterState::Error;
                 }
             },
@@ -195,34 +231,60 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> Logic
                     self.state.d.next = MasterState::Ready;
                 }
                 if self.cmd.val() != SDRAMCommand::NOP {
+                    self.violation.next = SDRAMViolation::ModeRegister;
                     self.state.d.next = MasterState::Error;
                 }
                 if self.burst_len.q.val() > 3 {
+                    self.violation.next = SDRAMViolation::BurstLength;
                     self.state.d.next = MasterState::Error;
                 }
                 if (self.cas_latency.q.val() > 3) | (self.cas_latency.q.val() == 0) {
+                    self.violation.next = SDRAMViolation::CasLatency;
                     self.state.d.next = MasterState::Error;
                 }
                 if self.op_mode.q.val() != 0 {
+                    self.violation.next = SDRAMViolation::ModeRegister;
                     self.state.d.next = MasterState::Error;
                 }
             }
-            MasterState::Error => {
-                self.test_error.next = true;
-            }
+            MasterState::Error => {}
             MasterState::Ready => {
                 self.test_ready.next = true;
+                if self.cmd.val() == SDRAMCommand::Active {
+                    if self.since_activate.q.val() < self.t_rrd.val() {
+                        self.violation.next = SDRAMViolation::TRrd;
+                    }
+                    self.since_activate.d.next = 0.into();
+                }
             }
             _ => {
                 self.state.d.next = MasterState::Boot;
             }
         }
-        // Any banks that are in error mean the chip is in error.
-        for i in 0..4 {
-            if self.banks[i].error.val() {
-                self.state.d.next = MasterState::Error;
-            }
+        // Report the violations flagged by the banks
+        if self.banks[0].violation.val() != SDRAMViolation::None {
+            self.violation.next = self.banks[0].violation.val();
+            self.violation_bank.next = 0.into();
+        }
+        if self.banks[1].violation.val() != SDRAMViolation::None {
+            self.violation.next = self.banks[1].violation.val();
+            self.violation_bank.next = 1.into();
         }
+        if self.banks[2].violation.val() != SDRAMViolation::None {
+            self.violation.next = self.banks[2].violation.val();
+            self.violation_bank.next = 2.into();
+        }
+        if self.banks[3].violation.val() != SDRAMViolation::None {
+            self.violation.next = self.banks[3].violation.val();
+            self.violation_bank.next = 3.into();
+        }
+        if self.violation.val() != SDRAMViolation::None {
+            self.failed.d.next = true;
+        }
+        self.monitor.clock.next = self.clock.val();
+        self.monitor.violation.next = self.violation.val();
+        self.monitor.bank.next = self.violation_bank.val();
+        self.monitor.command.next = self.cmd.val();
     }
 }
 
@@ -238,6 +300,9 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> SDRAMSimula
             sdram: Default::default(),
             test_error: Default::default(),
             test_ready: Default::default(),
+            violation: Default::default(),
+            violation_bank: Default::default(),
+            monitor: Default::default(),
             state: Default::default(),
             counter: Default::default(),
             auto_refresh_init_counter: Default::default(),
@@ -246,6 +311,8 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> SDRAMSimula
             burst_type: Default::default(),
             burst_len: Default::default(),
             op_mode: Default::default(),
+            since_activate: Default::default(),
+            failed: Default::default(),
             banks: array_init::array_init(|_| MemoryBank::new(timings)),
             boot_delay: Constant::new(boot_delay.to_bits()),
             t_rp: Constant::new(precharge_delay.to_bits()),
@@ -257,6 +324,10 @@ impl<const R: usize, const C: usize, const A: usize, const D: usize> SDRAMSimula
             decode: Default::default(),
         }
     }
+    // The violations seen so far in the simulation, in the order they happened
+    pub fn violations(&self) -> &[SDRAMViolationEvent] {
+        self.monitor.events()
+    }
 }
 
 #[cfg(test)]
@@ -521,8 +592,118 @@ fn test_sdram_init_works() {
         sim_assert!(sim, !x.banks_busy.val(), x);
         sim_assert_eq!(sim, x.state.q.val(), MasterState::Ready, x);
         wait_clock_cycles!(sim, clock, x, 10);
+        sim_assert!(sim, x.violations().is_empty(), x);
         sim.done(x)
     });
     sim.run_to_file(Box::new(uut), 200_000_000, &vcd_path!("sdr_init.vcd"))
         .unwrap()
 }
+
+#[cfg(test)]
+fn boot_sdr_sim(
+    sim: &mut Sim<SDRAMSimulator<5, 5, 10, 16>>,
+    mut x: Box<SDRAMSimulator<5, 5, 10, 16>>,
+) -> rust_hdl_core::simulate::Result<Box<SDRAMSimulator<5, 5, 10, 16>>> {
+    let timings = MemoryTimings::fast_boot_sim(125e6);
+    wait_clock_cycles!(sim, clock, x, 16);
+    sdram_boot!(sim, clock, x, timings);
+    sdram_cmd!(x, SDRAMCommand::LoadModeRegister);
+    // CAS latency of 3, sequential bursts of 8
+    x.sdram.address.next = 0x33.into();
+    wait_clock_cycle!(sim, clock, x);
+    sdram_cmd!(x, SDRAMCommand::NOP);
+    wait_clock_cycles!(sim, clock, x, 5);
+    Ok(x)
+}
+
+// Boot the chip, and run a scenario that should end with the violations
+// the scenario returns.
+#[cfg(test)]
+type SDRViolationScenario = fn(
+    &mut Sim<SDRAMSimulator<5, 5, 10, 16>>,
+    Box<SDRAMSimulator<5, 5, 10, 16>>,
+) -> rust_hdl_core::simulate::Result<(
+    Box<SDRAMSimulator<5, 5, 10, 16>>,
+    Vec<SDRAMViolationEvent>,
+)>;
+
+#[cfg(test)]
+fn run_sdr_violation_test(name: &str, scenario: SDRViolationScenario) {
+    let uut = mk_sdr_sim();
+    let mut sim = Simulation::new();
+    sim.add_clock(4000, |x: &mut Box<SDRAMSimulator<5, 5, 10, 16>>| {
+        x.sdram.clk.next = !x.sdram.clk.val();
+    });
+    sim.add_testbench(move |mut sim: Sim<SDRAMSimulator<5, 5, 10, 16>>| {
+        let x = sim.init()?;
+        let x = boot_sdr_sim(&mut sim, x)?;
+        sim_assert_eq!(sim, x.state.q.val(), MasterState::Ready, x);
+        let (mut x, expected) = scenario(&mut sim, x)?;
+        sdram_cmd!(x, SDRAMCommand::NOP);
+        wait_clock_cycles!(sim, clock, x, 2);
+        sim_assert!(sim, x.test_error.val(), x);
+        sim_assert_eq!(sim, x.violations(), expected, x);
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 200_000_000, &vcd_path!(name))
+        .unwrap()
+}
+
+#[test]
+fn test_sdram_sim_reports_trcd_violation() {
+    run_sdr_violation_test("sdr_trcd_violation.vcd", |sim, mut x| {
+        sdram_activate!(sim, clock, x, 2, 14);
+        // Read from the row without waiting for tRCD
+        sdram_cmd!(x, SDRAMCommand::Read);
+        x.sdram.bank.next = 2.into();
+        x.sdram.address.next = 0.into();
+        wait_clock_cycle!(sim, clock, x);
+        let event = SDRAMViolationEvent {
+            time: sim.time(),
+            bank: 2,
+            command: SDRAMCommand::Read,
+            violation: SDRAMViolation::TRcd,
+        };
+        Ok((x, vec![event]))
+    });
+}
+
+#[test]
+fn test_sdram_sim_reports_trrd_violation() {
+    run_sdr_violation_test("sdr_trrd_violation.vcd", |sim, mut x| {
+        // Activate two banks back to back
+        sdram_activate!(sim, clock, x, 2, 14);
+        sdram_activate!(sim, clock, x, 1, 7);
+        let event = SDRAMViolationEvent {
+            time: sim.time(),
+            bank: 1,
+            command: SDRAMCommand::Active,
+            violation: SDRAMViolation::TRrd,
+        };
+        Ok((x, vec![event]))
+    });
+}
+
+#[test]
+fn test_sdram_sim_keeps_checking_after_a_violation() {
+    run_sdr_violation_test("sdr_repeated_violations.vcd", |sim, mut x| {
+        // Close the row without waiting for tRAS, and then open it again
+        // without waiting for the precharge to finish
+        sdram_activate!(sim, clock, x, 3, 14);
+        sdram_precharge_one!(sim, clock, x, 3);
+        let precharge = SDRAMViolationEvent {
+            time: sim.time(),
+            bank: 3,
+            command: SDRAMCommand::Precharge,
+            violation: SDRAMViolation::TRas,
+        };
+        sdram_activate!(sim, clock, x, 3, 14);
+        let activate = SDRAMViolationEvent {
+            time: sim.time(),
+            bank: 3,
+            command: SDRAMCommand::Active,
+            violation: SDRAMViolation::TRp,
+        };
+        Ok((x, vec![precharge, activate]))
+    });
+}
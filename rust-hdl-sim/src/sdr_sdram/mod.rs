@@ -1,2 +1,3 @@
 pub mod bank;
 pub mod chip;
+pub mod violation;
@@ -0,0 +1,138 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+// The protocol rule (or JEDEC timing parameter) broken by a command sent
+// to the SDRAM simulator.
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+pub enum SDRAMViolation {
+    None,
+    // Wrong command during the initialization sequence
+    Boot,
+    // Load mode register to command delay, or an unsupported mode
+    ModeRegister,
+    CasLatency,
+    BurstLength,
+    // Activate to read or write
+    TRcd,
+    // Precharge to command
+    TRp,
+    // Activate to precharge
+    TRas,
+    // Activate to activate (same bank)
+    TRc,
+    // Activate to activate (different banks)
+    TRrd,
+    // Write recovery
+    TWr,
+    // Auto refresh to command
+    TRfc,
+    // A row went too long without being refreshed
+    RefreshInterval,
+    // A burst was interrupted by a command that cannot interrupt it
+    BurstInterrupted,
+    // A command that is not valid for the state of the bank (like a read
+    // to a bank with no open row)
+    IllegalCommand,
+}
+
+// A violation seen by the simulator.  `time` is the simulation time of the
+// clock edge at which the command was sampled, and `bank` is the bank that
+// flagged the violation (or the bank the command addressed, for violations
+// of the chip as a whole).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SDRAMViolationEvent {
+    pub time: u64,
+    pub bank: usize,
+    pub command: SDRAMCommand,
+    pub violation: SDRAMViolation,
+}
+
+impl std::fmt::Display for SDRAMViolationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} violated by {:?} to bank {} at time {}",
+            self.violation, self.command, self.bank, self.time
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct SDRAMViolationLog(Vec<SDRAMViolationEvent>);
+
+impl SimState for SDRAMViolationLog {
+    fn save(&self) -> String {
+        let mut writer = StateWriter::default();
+        writer.put_u64(self.0.len() as u64);
+        for event in &self.0 {
+            writer
+                .put_u64(event.time)
+                .put_u64(event.bank as u64)
+                .put(event.command)
+                .put(event.violation);
+        }
+        writer.finish()
+    }
+    fn restore(&mut self, text: &str) -> bool {
+        let mut reader = StateReader::new(text);
+        let mut read = || -> Option<Vec<SDRAMViolationEvent>> {
+            (0..reader.get_u64()?)
+                .map(|_| {
+                    Some(SDRAMViolationEvent {
+                        time: reader.get_u64()?,
+                        bank: reader.get_u64()? as usize,
+                        command: reader.get()?,
+                        violation: reader.get()?,
+                    })
+                })
+                .collect()
+        };
+        match read() {
+            Some(events) if reader.is_done() => {
+                self.0 = events;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// Records the violations flagged by the simulator, for the testbench to
+// query.  This only exists in simulation - there is nothing to synthesize.
+#[derive(LogicBlock, Default)]
+pub struct SDRAMViolationMonitor {
+    pub clock: Signal<In, Clock>,
+    pub violation: Signal<In, SDRAMViolation>,
+    pub bank: Signal<In, Bits<2>>,
+    pub command: Signal<In, SDRAMCommand>,
+    _log: SDRAMViolationLog,
+}
+
+impl SDRAMViolationMonitor {
+    pub fn events(&self) -> &[SDRAMViolationEvent] {
+        &self._log.0
+    }
+    pub fn clear(&mut self) {
+        self._log.0.clear()
+    }
+}
+
+impl Logic for SDRAMViolationMonitor {
+    fn update(&mut self) {
+        if self.clock.pos_edge() && self.violation.val() != SDRAMViolation::None {
+            self._log.0.push(SDRAMViolationEvent {
+                time: simulation_time(),
+                bank: self.bank.val().index(),
+                command: self.command.val(),
+                violation: self.violation.val(),
+            });
+        }
+    }
+    fn connect(&mut self) {}
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_log", &mut self._log);
+    }
+    fn hdl(&self) -> Verilog {
+        Verilog::Empty
+    }
+}
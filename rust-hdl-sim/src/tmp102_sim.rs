@@ -0,0 +1,256 @@
+use crate::register_map::i2c::{I2CRegisterFormat, I2CRegisterMap};
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+
+#[derive(Clone, Copy)]
+pub struct TMP102Config {
+    pub address: u8,
+    pub clock_speed_hz: u64,
+    pub conversion_rate_hz: f64,
+}
+
+impl TMP102Config {
+    pub fn hw() -> Self {
+        Self {
+            address: 0x48,
+            clock_speed_hz: 48_000_000,
+            conversion_rate_hz: 4.0,
+        }
+    }
+    pub fn sw() -> Self {
+        Self {
+            address: 0x48,
+            clock_speed_hz: 1_000_000,
+            conversion_rate_hz: 1000.0,
+        }
+    }
+}
+
+// Temperature, Configuration, T_LOW and T_HIGH registers
+const TMP102_REG_INITS: [u64; 4] = [0x0000, 0x60A0, 0x4B00, 0x5000];
+
+/// A TMP102 style I2C temperature sensor.  The `temperature` input is
+/// sampled at the conversion rate into the (read only) temperature
+/// register as a 12 bit two's complement value in units of 1/16 degree C.
+/// The `alert` output (active low) works in comparator mode - it is asserted
+/// when the temperature reaches T_HIGH, and released when it falls below
+/// T_LOW.  Setting the shutdown bit in the configuration register stops
+/// conversions.
+#[derive(LogicBlock)]
+pub struct TMP102Simulator {
+    pub i2c: I2CBusDriver,
+    pub clock: Signal<In, Clock>,
+    pub temperature: Signal<In, Bits<12>>,
+    pub alert: Signal<Out, Bit>,
+    // The registers and I2C interface
+    regs: I2CRegisterMap<2, 16>,
+    conversion_strobe: Strobe<32>,
+    // Shadows of the registers that control the device
+    shutdown: DFF<Bit>,
+    t_low: DFFWithInit<Bits<12>>,
+    t_high: DFFWithInit<Bits<12>>,
+    alert_active: DFF<Bit>,
+    last_temp: DFF<Bits<12>>,
+    // Offset binary versions of the temperatures, so they can be compared
+    cur_temp: Signal<Local, Bits<12>>,
+    low_limit: Signal<Local, Bits<12>>,
+    high_limit: Signal<Local, Bits<12>>,
+}
+
+impl TMP102Simulator {
+    pub fn new(config: TMP102Config) -> Self {
+        let format = I2CRegisterFormat {
+            address: config.address,
+            pointer_bytes: 1,
+            auto_increment: false,
+            write_page_bits: None,
+        };
+        Self {
+            i2c: Default::default(),
+            clock: Default::default(),
+            temperature: Default::default(),
+            alert: Default::default(),
+            regs: I2CRegisterMap::new(format, &TMP102_REG_INITS),
+            conversion_strobe: Strobe::new(config.clock_speed_hz, config.conversion_rate_hz),
+            shutdown: Default::default(),
+            t_low: DFFWithInit::new((TMP102_REG_INITS[2] >> 4).to_bits()),
+            t_high: DFFWithInit::new((TMP102_REG_INITS[3] >> 4).to_bits()),
+            alert_active: Default::default(),
+            last_temp: Default::default(),
+            cur_temp: Default::default(),
+            low_limit: Default::default(),
+            high_limit: Default::default(),
+        }
+    }
+}
+
+impl Logic for TMP102Simulator {
+    #[hdl_gen]
+    fn update(&mut self) {
+        I2CBusDriver::link(&mut self.i2c, &mut self.regs.i2c);
+        clock!(self, clock, regs, conversion_strobe);
+        dff_setup!(
+            self,
+            clock,
+            shutdown,
+            t_low,
+            t_high,
+            alert_active,
+            last_temp
+        );
+        self.conversion_strobe.enable.next = !self.shutdown.q.val();
+        self.regs.update_address.next = 0.into();
+        self.regs.update_data.next = bit_cast::<16, 12>(self.temperature.val()) << 4;
+        self.regs.update_enable.next = false;
+        self.regs.peek_address.next = 0.into();
+        self.regs.peek_enable.next = false;
+        self.regs.hold.next = false;
+        // The temperature register is read only
+        self.regs.write_protect.next = !self.regs.write_address.val().any();
+        if self.regs.write_strobe.val() {
+            if self.regs.write_address.val() == 1 {
+                self.shutdown.d.next = self.regs.write_data.val().get_bit(8);
+            }
+            if self.regs.write_address.val() == 2 {
+                self.t_low.d.next = self.regs.write_data.val().get_bits::<12>(4);
+            }
+            if self.regs.write_address.val() == 3 {
+                self.t_high.d.next = self.regs.write_data.val().get_bits::<12>(4);
+            }
+        }
+        if self.conversion_strobe.strobe.val() {
+            self.regs.update_enable.next = true;
+            self.last_temp.d.next = self.temperature.val();
+        }
+        // Flipping the sign bit maps two's complement onto an unsigned order
+        self.cur_temp.next = self.last_temp.q.val() ^ 0x800;
+        self.low_limit.next = self.t_low.q.val() ^ 0x800;
+        self.high_limit.next = self.t_high.q.val() ^ 0x800;
+        if self.cur_temp.val() >= self.high_limit.val() {
+            self.alert_active.d.next = true;
+        }
+        if self.cur_temp.val() < self.low_limit.val() {
+            self.alert_active.d.next = false;
+        }
+        self.alert.next = !self.alert_active.q.val();
+    }
+}
+
+#[test]
+fn test_tmp102_synthesizes() {
+    let mut uut = TMP102Simulator::new(TMP102Config::hw());
+    uut.connect_all();
+    yosys_validate("tmp102", &generate_verilog(&uut)).unwrap();
+}
+
+#[derive(LogicBlock)]
+struct Test102 {
+    clock: Signal<In, Clock>,
+    controller: rust_hdl_widgets::i2c::i2c_controller::I2CController,
+    sensor: TMP102Simulator,
+    test_bus: I2CTestBus<2>,
+}
+
+impl Logic for Test102 {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, controller, sensor);
+        I2CBusDriver::join(&mut self.controller.i2c, &mut self.test_bus.endpoints[0]);
+        I2CBusDriver::join(&mut self.sensor.i2c, &mut self.test_bus.endpoints[1]);
+    }
+}
+
+#[cfg(test)]
+fn mk_test102() -> Test102 {
+    let mut uut = Test102 {
+        clock: Default::default(),
+        controller: rust_hdl_widgets::i2c::i2c_controller::I2CController::new(I2CConfig {
+            delay_time: std::time::Duration::from_micros(5),
+            clock_speed_hz: 1_000_000,
+            timeout: std::time::Duration::from_millis(25),
+        }),
+        sensor: TMP102Simulator::new(TMP102Config::sw()),
+        test_bus: Default::default(),
+    };
+    uut.clock.connect();
+    uut.controller.cmd.connect();
+    uut.controller.run.connect();
+    uut.controller.write_data_in.connect();
+    uut.controller.address_10.connect();
+    uut.sensor.temperature.connect();
+    uut.connect_all();
+    uut
+}
+
+#[cfg(test)]
+fn reg_read(
+    reg_index: u8,
+    mut x: Box<Test102>,
+    sim: &mut Sim<Test102>,
+) -> Result<(u16, Box<Test102>), SimError> {
+    use rust_hdl_widgets::i2c::i2c_controller::I2CControllerCmd;
+    i2c_begin_write!(sim, clock, x, 0x48);
+    i2c_write!(sim, clock, x, reg_index);
+    i2c_end_transmission!(sim, clock, x);
+    i2c_begin_read!(sim, clock, x, 0x48);
+    let msb = i2c_read!(sim, clock, x).index() as u16;
+    let lsb = i2c_read_last!(sim, clock, x).index() as u16;
+    i2c_end_transmission!(sim, clock, x);
+    Ok(((msb << 8) | lsb, x))
+}
+
+#[cfg(test)]
+fn reg_write(
+    reg_index: u8,
+    val: u16,
+    mut x: Box<Test102>,
+    sim: &mut Sim<Test102>,
+) -> Result<Box<Test102>, SimError> {
+    use rust_hdl_widgets::i2c::i2c_controller::I2CControllerCmd;
+    i2c_begin_write!(sim, clock, x, 0x48);
+    i2c_write!(sim, clock, x, reg_index);
+    i2c_write!(sim, clock, x, val >> 8);
+    i2c_write!(sim, clock, x, val & 0xFF);
+    i2c_end_transmission!(sim, clock, x);
+    Ok(x)
+}
+
+#[test]
+fn test_tmp102_registers_and_alert() {
+    let uut = mk_test102();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<Test102>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<Test102>| {
+        let mut x = sim.init()?;
+        let mut val;
+        // 25 degrees C
+        x.sensor.temperature.next = 0x190.into();
+        wait_clock_cycles!(sim, clock, x, 2000);
+        (val, x) = reg_read(0, x, &mut sim)?;
+        sim_assert_eq!(sim, val, 0x1900, x);
+        (val, x) = reg_read(1, x, &mut sim)?;
+        sim_assert_eq!(sim, val, 0x60A0, x);
+        // The temperature register is read only
+        x = reg_write(0, 0x1234, x, &mut sim)?;
+        (val, x) = reg_read(0, x, &mut sim)?;
+        sim_assert_eq!(sim, val, 0x1900, x);
+        sim_assert!(sim, x.sensor.alert.val(), x);
+        // Drop T_HIGH to 20 degrees and T_LOW to 10 degrees
+        x = reg_write(3, 0x1400, x, &mut sim)?;
+        x = reg_write(2, 0x0A00, x, &mut sim)?;
+        (val, x) = reg_read(3, x, &mut sim)?;
+        sim_assert_eq!(sim, val, 0x1400, x);
+        wait_clock_cycles!(sim, clock, x, 10);
+        sim_assert!(sim, !x.sensor.alert.val(), x);
+        // The alert holds until the temperature falls below T_LOW (-10 degrees C here)
+        x.sensor.temperature.next = 0xF60.into();
+        wait_clock_cycles!(sim, clock, x, 2000);
+        sim_assert!(sim, x.sensor.alert.val(), x);
+        (val, x) = reg_read(0, x, &mut sim)?;
+        sim_assert_eq!(sim, val, 0xF600, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000_000_000).unwrap();
+}
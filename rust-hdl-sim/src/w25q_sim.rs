@@ -0,0 +1,658 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_widgets::prelude::*;
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct W25QConfig {
+    pub spi: SPIConfig,
+    pub page_program_time: Duration,
+    pub erase_time: Duration,
+}
+
+impl W25QConfig {
+    pub fn hw() -> Self {
+        Self {
+            spi: SPIConfig {
+                clock_speed: 48_000_000,
+                cs_off: true,
+                mosi_off: true,
+                speed_hz: 1_000_000,
+                cpha: true,
+                cpol: true,
+            },
+            page_program_time: Duration::from_micros(700),
+            erase_time: Duration::from_millis(45),
+        }
+    }
+    pub fn sw() -> Self {
+        Self {
+            spi: SPIConfig {
+                clock_speed: 1_000_000,
+                cs_off: true,
+                mosi_off: true,
+                speed_hz: 10_000,
+                cpha: true,
+                cpol: true,
+            },
+            page_program_time: Duration::from_millis(5),
+            erase_time: Duration::from_millis(20),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum W25QState {
+    Boot,
+    Ready,
+    GettingCmd,
+    Dispatch,
+    Pause,
+    SendId,
+    StatusSend,
+    StatusWait,
+    AddrStart,
+    GettingAddr,
+    DummyStart,
+    DummyWait,
+    ReadFetch,
+    ReadSend,
+    ReadWait,
+    ProgStart,
+    ProgRecv,
+    WaitIdle,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum W25QOpState {
+    Idle,
+    Programming,
+    Erasing,
+    EraseWait,
+}
+
+/// A W25Q style SPI NOR flash with `2^A` bytes of storage (SPI mode 3).
+/// Supports the JEDEC ID (0x9F), read/write status (0x05), write enable and
+/// disable (0x06/0x04), read (0x03), fast read (0x0B), fast read quad output
+/// (0x6B), page program (0x02), 4K sector erase (0x20), 64K block erase (0xD8)
+/// and chip erase (0xC7/0x60) commands.  The quad output read takes its command
+/// and address on `io0`, and sends the data on all four lanes after 8 dummy
+/// clocks.  Programming can only clear bits, and wraps around within a 256
+/// byte page.  The program and erase operations start when the chip select
+/// is released, and set the BUSY bit in the status register for the duration
+/// of the operation.  While busy, only the status register can be read.
+#[derive(LogicBlock)]
+pub struct W25QSimulator<const A: usize> {
+    // Slave (quad) SPI bus
+    pub wires: QSPIWiresSlave,
+    pub clock: Signal<In, Clock>,
+    // The flash array
+    mem: RAM<Bits<8>, A>,
+    // The SPI slave device
+    spi_slave: QSPISlave<64>,
+    // Used to see if the master has paused or hung up between bytes
+    mclk_sync: BitSynchronizer,
+    msel_sync: BitSynchronizer,
+    // Command FSM
+    state: DFF<W25QState>,
+    resume: DFF<W25QState>,
+    cmd: DFF<Bits<8>>,
+    address: DFF<Bits<A>>,
+    write_enable_latch: DFF<Bit>,
+    programmed: DFF<Bit>,
+    lead_in: DFF<Bit>,
+    // Program/erase FSM
+    op_state: DFF<W25QOpState>,
+    erase_address: DFF<Bits<A>>,
+    erase_mask: DFF<Bits<A>>,
+    program_timer: Shot<32>,
+    erase_timer: Shot<32>,
+    // Requests from the command FSM to the program/erase FSM
+    start_program: Signal<Local, Bit>,
+    start_erase: Signal<Local, Bit>,
+    op_busy: Signal<Local, Bit>,
+    status: Signal<Local, Bits<8>>,
+    next_program: Signal<Local, Bits<A>>,
+    jedec_id: Constant<Bits<64>>,
+    page_mask: Constant<Bits<A>>,
+    sector_mask: Constant<Bits<A>>,
+    block_mask: Constant<Bits<A>>,
+    chip_mask: Constant<Bits<A>>,
+    cpol: Constant<Bit>,
+    cs_off: Constant<Bit>,
+}
+
+impl<const A: usize> W25QSimulator<A> {
+    pub fn new(config: W25QConfig) -> Self {
+        assert!((8..=24).contains(&A));
+        let chip_mask = (1_u64 << A) - 1;
+        Self {
+            wires: Default::default(),
+            clock: Default::default(),
+            // An erased part reads back as all ones
+            mem: std::iter::repeat_n(0xFF_u64.to_bits(), 1 << A).into(),
+            spi_slave: QSPISlave::new(config.spi),
+            mclk_sync: Default::default(),
+            msel_sync: Default::default(),
+            state: Default::default(),
+            resume: Default::default(),
+            cmd: Default::default(),
+            address: Default::default(),
+            write_enable_latch: Default::default(),
+            programmed: Default::default(),
+            lead_in: Default::default(),
+            op_state: Default::default(),
+            erase_address: Default::default(),
+            erase_mask: Default::default(),
+            program_timer: Shot::new(config.spi.clock_speed, config.page_program_time),
+            erase_timer: Shot::new(config.spi.clock_speed, config.erase_time),
+            start_program: Default::default(),
+            start_erase: Default::default(),
+            op_busy: Default::default(),
+            status: Default::default(),
+            next_program: Default::default(),
+            // Winbond manufacturer ID, memory type, and capacity (log2 of the size in bytes)
+            jedec_id: Constant::new((0xEF_40_00 | A as u64).to_bits()),
+            page_mask: Constant::new(0xFF_u64.to_bits()),
+            sector_mask: Constant::new((0xFFF & chip_mask).to_bits()),
+            block_mask: Constant::new((0xFFFF & chip_mask).to_bits()),
+            chip_mask: Constant::new(chip_mask.to_bits()),
+            cpol: Constant::new(config.spi.cpol),
+            cs_off: Constant::new(config.spi.cs_off),
+        }
+    }
+}
+
+impl<const A: usize> Logic for W25QSimulator<A> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        // Connect the spi bus
+        QSPIWiresSlave::link(&mut self.wires, &mut self.spi_slave.wires);
+        // Clock the internal logic
+        self.mem.write_clock.next = self.clock.val();
+        self.mem.read_clock.next = self.clock.val();
+        clock!(
+            self,
+            clock,
+            spi_slave,
+            mclk_sync,
+            msel_sync,
+            program_timer,
+            erase_timer
+        );
+        dff_setup!(
+            self,
+            clock,
+            state,
+            resume,
+            cmd,
+            address,
+            write_enable_latch,
+            programmed,
+            lead_in,
+            op_state,
+            erase_address,
+            erase_mask
+        );
+        self.mclk_sync.sig_in.next = self.wires.mclk.val();
+        self.msel_sync.sig_in.next = self.wires.msel.val();
+        // Set default values
+        self.spi_slave.start_send.next = false;
+        self.spi_slave.continued_transaction.next = true;
+        self.spi_slave.bits.next = 0.into();
+        self.spi_slave.data_outbound.next = 0.into();
+        self.spi_slave.width.next = QSPIWidth::Single;
+        self.spi_slave.transmit.next = false;
+        self.spi_slave.dummy_cycles.next = 0.into();
+        self.mem.read_address.next = self.address.q.val();
+        self.mem.write_address.next = self.address.q.val();
+        // Programming can only clear bits
+        self.mem.write_data.next =
+            self.mem.read_data.val() & self.spi_slave.data_inbound.val().get_bits::<8>(0);
+        self.mem.write_enable.next = false;
+        self.program_timer.trigger.next = false;
+        self.erase_timer.trigger.next = false;
+        self.start_program.next = false;
+        self.start_erase.next = false;
+        self.op_busy.next = self.op_state.q.val() != W25QOpState::Idle;
+        self.status.next = (bit_cast::<8, 1>(self.write_enable_latch.q.val().into()) << 1)
+            | bit_cast::<8, 1>(self.op_busy.val().into());
+        // Page programming wraps around within the page
+        self.next_program.next = (self.address.q.val() & !self.page_mask.val())
+            | ((self.address.q.val() + 1) & self.page_mask.val());
+        match self.state.q.val() {
+            W25QState::Boot => {
+                if !self.spi_slave.busy.val() {
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            W25QState::Ready => {
+                self.spi_slave.bits.next = 8.into();
+                self.spi_slave.start_send.next = true;
+                self.programmed.d.next = false;
+                self.state.d.next = W25QState::GettingCmd;
+            }
+            W25QState::GettingCmd => {
+                if self.spi_slave.transfer_done.val() {
+                    self.cmd.d.next = self.spi_slave.data_inbound.val().get_bits::<8>(0);
+                    self.state.d.next = W25QState::Dispatch;
+                }
+            }
+            W25QState::Dispatch => {
+                self.state.d.next = W25QState::WaitIdle;
+                if self.spi_slave.busy.val() {
+                    // The master is still talking, so this command has more to it
+                    self.state.d.next = W25QState::Pause;
+                    self.resume.d.next = W25QState::WaitIdle;
+                    if self.cmd.q.val() == 0x9F {
+                        self.resume.d.next = W25QState::SendId;
+                    } else if self.cmd.q.val() == 0x05 {
+                        self.resume.d.next = W25QState::StatusSend;
+                    } else if !self.op_busy.val() {
+                        if (self.cmd.q.val() == 0x03)
+                            | (self.cmd.q.val() == 0x0B)
+                            | (self.cmd.q.val() == 0x6B)
+                        {
+                            self.resume.d.next = W25QState::AddrStart;
+                        }
+                        if ((self.cmd.q.val() == 0x02)
+                            | (self.cmd.q.val() == 0x20)
+                            | (self.cmd.q.val() == 0xD8))
+                            & self.write_enable_latch.q.val()
+                        {
+                            self.resume.d.next = W25QState::AddrStart;
+                        }
+                    }
+                } else {
+                    // Single byte commands take effect when the chip select is released
+                    self.state.d.next = W25QState::Ready;
+                    if !self.op_busy.val() {
+                        if self.cmd.q.val() == 0x06 {
+                            self.write_enable_latch.d.next = true;
+                        }
+                        if self.cmd.q.val() == 0x04 {
+                            self.write_enable_latch.d.next = false;
+                        }
+                        if ((self.cmd.q.val() == 0xC7) | (self.cmd.q.val() == 0x60))
+                            & self.write_enable_latch.q.val()
+                        {
+                            self.erase_address.d.next = 0.into();
+                            self.erase_mask.d.next = self.chip_mask.val();
+                            self.start_erase.next = true;
+                        }
+                    }
+                }
+            }
+            W25QState::Pause => {
+                // The master may pause between bytes.  Only arm the slave for the
+                // next byte once the clock starts again, so that releasing the
+                // chip select instead ends the command cleanly.
+                if self.msel_sync.sig_out.val() == self.cs_off.val() {
+                    self.start_program.next =
+                        (self.resume.q.val() == W25QState::ProgStart) & self.programmed.q.val();
+                    self.state.d.next = W25QState::Ready;
+                } else if self.mclk_sync.sig_out.val() != self.cpol.val() {
+                    self.state.d.next = self.resume.q.val();
+                }
+            }
+            W25QState::SendId => {
+                self.spi_slave.bits.next = 24.into();
+                self.spi_slave.data_outbound.next = self.jedec_id.val();
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = W25QState::WaitIdle;
+            }
+            W25QState::StatusSend => {
+                self.spi_slave.bits.next = 8.into();
+                self.spi_slave.data_outbound.next = bit_cast::<64, 8>(self.status.val());
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = W25QState::StatusWait;
+            }
+            W25QState::StatusWait => {
+                // The status register repeats for as long as the master keeps clocking
+                if self.spi_slave.transfer_done.val() & self.spi_slave.busy.val() {
+                    self.resume.d.next = W25QState::StatusSend;
+                    self.state.d.next = W25QState::Pause;
+                } else if !self.spi_slave.busy.val() {
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            W25QState::AddrStart => {
+                self.spi_slave.bits.next = 24.into();
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = W25QState::GettingAddr;
+            }
+            W25QState::GettingAddr => {
+                if self.spi_slave.transfer_done.val() {
+                    self.address.d.next = self.spi_slave.data_inbound.val().get_bits::<A>(0);
+                    if self.spi_slave.busy.val() {
+                        self.state.d.next = W25QState::Pause;
+                        self.resume.d.next = W25QState::WaitIdle;
+                        if self.cmd.q.val() == 0x03 {
+                            self.resume.d.next = W25QState::ReadFetch;
+                        }
+                        if self.cmd.q.val() == 0x0B {
+                            self.resume.d.next = W25QState::DummyStart;
+                        }
+                        if self.cmd.q.val() == 0x6B {
+                            // The dummy clocks are part of the first quad transfer
+                            self.lead_in.d.next = true;
+                            self.resume.d.next = W25QState::ReadFetch;
+                        }
+                        if self.cmd.q.val() == 0x02 {
+                            self.resume.d.next = W25QState::ProgStart;
+                        }
+                    } else {
+                        self.state.d.next = W25QState::Ready;
+                        if self.cmd.q.val() == 0x20 {
+                            self.erase_address.d.next =
+                                self.spi_slave.data_inbound.val().get_bits::<A>(0)
+                                    & !self.sector_mask.val();
+                            self.erase_mask.d.next = self.sector_mask.val();
+                            self.start_erase.next = true;
+                        }
+                        if self.cmd.q.val() == 0xD8 {
+                            self.erase_address.d.next =
+                                self.spi_slave.data_inbound.val().get_bits::<A>(0)
+                                    & !self.block_mask.val();
+                            self.erase_mask.d.next = self.block_mask.val();
+                            self.start_erase.next = true;
+                        }
+                    }
+                } else if !self.spi_slave.busy.val() {
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            W25QState::DummyStart => {
+                self.spi_slave.bits.next = 8.into();
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = W25QState::DummyWait;
+            }
+            W25QState::DummyWait => {
+                if self.spi_slave.transfer_done.val() & self.spi_slave.busy.val() {
+                    self.resume.d.next = W25QState::ReadFetch;
+                    self.state.d.next = W25QState::Pause;
+                } else if !self.spi_slave.busy.val() {
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            W25QState::ReadFetch => {
+                self.state.d.next = W25QState::ReadSend;
+            }
+            W25QState::ReadSend => {
+                self.spi_slave.bits.next = 8.into();
+                self.spi_slave.data_outbound.next = bit_cast::<64, 8>(self.mem.read_data.val());
+                self.spi_slave.start_send.next = true;
+                if self.cmd.q.val() == 0x6B {
+                    self.spi_slave.width.next = QSPIWidth::Quad;
+                    self.spi_slave.transmit.next = true;
+                    if self.lead_in.q.val() {
+                        self.spi_slave.dummy_cycles.next = 8.into();
+                    }
+                }
+                self.lead_in.d.next = false;
+                self.state.d.next = W25QState::ReadWait;
+            }
+            W25QState::ReadWait => {
+                // Reads run on through the whole array
+                if self.spi_slave.transfer_done.val() & self.spi_slave.busy.val() {
+                    self.address.d.next = self.address.q.val() + 1;
+                    self.resume.d.next = W25QState::ReadFetch;
+                    self.state.d.next = W25QState::Pause;
+                } else if !self.spi_slave.busy.val() {
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            W25QState::ProgStart => {
+                self.spi_slave.bits.next = 8.into();
+                self.spi_slave.start_send.next = true;
+                self.state.d.next = W25QState::ProgRecv;
+            }
+            W25QState::ProgRecv => {
+                if self.spi_slave.transfer_done.val() {
+                    self.mem.write_enable.next = true;
+                    self.programmed.d.next = true;
+                    self.address.d.next = self.next_program.val();
+                    if self.spi_slave.busy.val() {
+                        self.resume.d.next = W25QState::ProgStart;
+                        self.state.d.next = W25QState::Pause;
+                    } else {
+                        self.start_program.next = true;
+                        self.state.d.next = W25QState::Ready;
+                    }
+                } else if !self.spi_slave.busy.val() {
+                    self.start_program.next = self.programmed.q.val();
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            W25QState::WaitIdle => {
+                if !self.spi_slave.busy.val() {
+                    self.state.d.next = W25QState::Ready;
+                }
+            }
+            _ => {
+                self.state.d.next = W25QState::Boot;
+            }
+        }
+        match self.op_state.q.val() {
+            W25QOpState::Idle => {
+                if self.start_program.val() {
+                    self.write_enable_latch.d.next = false;
+                    self.program_timer.trigger.next = true;
+                    self.op_state.d.next = W25QOpState::Programming;
+                }
+                if self.start_erase.val() {
+                    self.write_enable_latch.d.next = false;
+                    self.erase_timer.trigger.next = true;
+                    self.op_state.d.next = W25QOpState::Erasing;
+                }
+            }
+            W25QOpState::Programming => {
+                if self.program_timer.fired.val() {
+                    self.op_state.d.next = W25QOpState::Idle;
+                }
+            }
+            W25QOpState::Erasing => {
+                self.mem.write_address.next = self.erase_address.q.val();
+                self.mem.write_data.next = 0xFF.into();
+                self.mem.write_enable.next = true;
+                self.erase_address.d.next = self.erase_address.q.val() + 1;
+                if (self.erase_address.q.val() & self.erase_mask.q.val()) == self.erase_mask.q.val()
+                {
+                    self.op_state.d.next = W25QOpState::EraseWait;
+                }
+            }
+            W25QOpState::EraseWait => {
+                if !self.erase_timer.active.val() {
+                    self.op_state.d.next = W25QOpState::Idle;
+                }
+            }
+            _ => {
+                self.op_state.d.next = W25QOpState::Idle;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_w25q_synthesizes() {
+    let mut uut: W25QSimulator<16> = W25QSimulator::new(W25QConfig::hw());
+    uut.connect_all();
+    yosys_validate("w25q", &generate_verilog(&uut)).unwrap();
+}
+
+#[derive(LogicBlock)]
+struct TestW25Q {
+    clock: Signal<In, Clock>,
+    master: SPIMaster<64>,
+    lanes: QSPISingleLane,
+    uut: W25QSimulator<13>,
+}
+
+impl Logic for TestW25Q {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, master, uut);
+        SPIWiresMaster::join(&mut self.master.wires, &mut self.lanes.spi);
+        QSPIWiresMaster::join(&mut self.lanes.wires, &mut self.uut.wires);
+    }
+}
+
+#[cfg(test)]
+fn mk_test_w25q() -> TestW25Q {
+    let mut uut = TestW25Q {
+        clock: Default::default(),
+        master: SPIMaster::new(W25QConfig::sw().spi),
+        lanes: Default::default(),
+        uut: W25QSimulator::new(W25QConfig::sw()),
+    };
+    uut.clock.connect();
+    uut.master.continued_transaction.connect();
+    uut.master.start_send.connect();
+    uut.master.data_outbound.connect();
+    uut.master.bits_outbound.connect();
+    uut.connect_all();
+    uut
+}
+
+#[cfg(test)]
+fn do_spi_txn(
+    bits: u16,
+    value: u64,
+    continued: bool,
+    mut x: Box<TestW25Q>,
+    sim: &mut Sim<TestW25Q>,
+) -> Result<(u64, Box<TestW25Q>), SimError> {
+    wait_clock_true!(sim, clock, x);
+    wait_clock_cycles!(sim, clock, x, 10);
+    x.master.data_outbound.next = value.to_bits();
+    x.master.bits_outbound.next = bits.to_bits();
+    x.master.continued_transaction.next = continued;
+    x.master.start_send.next = true;
+    wait_clock_cycle!(sim, clock, x);
+    x.master.start_send.next = false;
+    x = sim.watch(|x| x.clock.val().clk && x.master.transfer_done.val(), x)?;
+    let ret = x.master.data_inbound.val().to_u64();
+    wait_clock_true!(sim, clock, x);
+    wait_clock_cycles!(sim, clock, x, 50);
+    Ok((ret, x))
+}
+
+#[cfg(test)]
+fn wait_not_busy(
+    mut x: Box<TestW25Q>,
+    sim: &mut Sim<TestW25Q>,
+) -> Result<(u64, Box<TestW25Q>), SimError> {
+    let mut polls = 0;
+    loop {
+        let status;
+        (status, x) = do_spi_txn(16, 0x05_00, false, x, sim)?;
+        if status & 1 == 0 {
+            return Ok((polls, x));
+        }
+        polls += 1;
+    }
+}
+
+#[test]
+fn test_w25q_id_and_status() {
+    let uut = mk_test_w25q();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<TestW25Q>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<TestW25Q>| {
+        let mut x = sim.init()?;
+        let mut result;
+        wait_clock_cycles!(sim, clock, x, 10);
+        (result, x) = do_spi_txn(32, 0x9F_00_00_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF_FF_FF, 0xEF_40_0D, x);
+        (result, x) = do_spi_txn(16, 0x05_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0, x);
+        (_, x) = do_spi_txn(8, 0x06, false, x, &mut sim)?;
+        // The status register repeats while the master keeps clocking
+        (result, x) = do_spi_txn(32, 0x05_00_00_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF_FF_FF, 0x02_02_02, x);
+        (_, x) = do_spi_txn(8, 0x04, false, x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0x05_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000_000).unwrap();
+}
+
+#[test]
+fn test_w25q_program_read_and_erase() {
+    let uut = mk_test_w25q();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<TestW25Q>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<TestW25Q>| {
+        let mut x = sim.init()?;
+        let mut result;
+        wait_clock_cycles!(sim, clock, x, 10);
+        // Programming without the write enable latch set is ignored
+        (_, x) = do_spi_txn(32, 0x02_00_01_00, true, x, &mut sim)?;
+        (_, x) = do_spi_txn(16, 0x12_34, false, x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0x05_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0, x);
+        // Program 4 bytes starting 2 bytes before the end of a page
+        (_, x) = do_spi_txn(8, 0x06, false, x, &mut sim)?;
+        (_, x) = do_spi_txn(32, 0x02_00_01_FE, true, x, &mut sim)?;
+        (_, x) = do_spi_txn(32, 0x01_02_03_04, false, x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0x05_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0x01, x);
+        (_, x) = wait_not_busy(x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0x05_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0, x);
+        // The last 2 bytes wrap around to the start of the page
+        (_, x) = do_spi_txn(32, 0x03_00_01_FE, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF_FF, 0x01_02, x);
+        (_, x) = do_spi_txn(32, 0x03_00_01_00, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(32, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF_FF_FF_FF, 0x03_04_FF_FF, x);
+        // Fast read has 8 dummy clocks after the address
+        (_, x) = do_spi_txn(40, 0x0B_00_01_00_00, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF_FF, 0x03_04, x);
+        // Programming can only clear bits
+        (_, x) = do_spi_txn(8, 0x06, false, x, &mut sim)?;
+        (_, x) = do_spi_txn(40, 0x02_00_01_00_F0, false, x, &mut sim)?;
+        (_, x) = wait_not_busy(x, &mut sim)?;
+        (_, x) = do_spi_txn(32, 0x03_00_01_00, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(8, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0x00, x);
+        // The master can pause between bytes, or hang up after a pause
+        (_, x) = do_spi_txn(8, 0x06, false, x, &mut sim)?;
+        (_, x) = do_spi_txn(32, 0x02_00_02_00, true, x, &mut sim)?;
+        wait_clock_cycles!(sim, clock, x, 1000);
+        (_, x) = do_spi_txn(8, 0x77, false, x, &mut sim)?;
+        (_, x) = wait_not_busy(x, &mut sim)?;
+        (_, x) = do_spi_txn(32, 0x03_00_02_00, true, x, &mut sim)?;
+        wait_clock_cycles!(sim, clock, x, 1000);
+        (result, x) = do_spi_txn(8, 0, true, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0x77, x);
+        wait_clock_cycles!(sim, clock, x, 1000);
+        (_, x) = do_spi_txn(0, 0, false, x, &mut sim)?;
+        (result, x) = do_spi_txn(16, 0x05_00, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0, x);
+        // Put something in the second sector
+        (_, x) = do_spi_txn(8, 0x06, false, x, &mut sim)?;
+        (_, x) = do_spi_txn(40, 0x02_00_10_00_5A, false, x, &mut sim)?;
+        (_, x) = wait_not_busy(x, &mut sim)?;
+        // Erase the first sector
+        (_, x) = do_spi_txn(8, 0x06, false, x, &mut sim)?;
+        (_, x) = do_spi_txn(32, 0x20_00_01_23, false, x, &mut sim)?;
+        // Reads are ignored while the erase runs
+        (_, x) = do_spi_txn(32, 0x03_00_10_00, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(8, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0x00, x);
+        let polls;
+        (polls, x) = wait_not_busy(x, &mut sim)?;
+        sim_assert!(sim, polls > 0, x);
+        (_, x) = do_spi_txn(32, 0x03_00_01_00, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(32, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF_FF_FF_FF, 0xFF_FF_FF_FF_u64, x);
+        (_, x) = do_spi_txn(32, 0x03_00_10_00, true, x, &mut sim)?;
+        (result, x) = do_spi_txn(8, 0, false, x, &mut sim)?;
+        sim_assert_eq!(sim, result & 0xFF, 0x5A, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000_000).unwrap();
+}
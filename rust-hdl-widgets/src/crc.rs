@@ -0,0 +1,297 @@
+use crate::{dff_setup, dff_with_init::DFFWithInit};
+use rust_hdl_core::prelude::*;
+
+// Describes a CRC in the usual "Rocksoft" parameter model.  The
+// polynomial is given in its normal (MSB first) form, without the
+// leading term.  `check` is the CRC of the ASCII string "123456789",
+// and is used to validate the parameters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CRCSpec {
+    pub width: usize,
+    pub poly: u64,
+    pub init: u64,
+    pub reflect_in: bool,
+    pub reflect_out: bool,
+    pub xor_out: u64,
+    pub check: u64,
+}
+
+pub const CRC8_CCITT: CRCSpec = CRCSpec {
+    width: 8,
+    poly: 0x07,
+    init: 0x00,
+    reflect_in: false,
+    reflect_out: false,
+    xor_out: 0x00,
+    check: 0xF4,
+};
+
+pub const CRC16_MODBUS: CRCSpec = CRCSpec {
+    width: 16,
+    poly: 0x8005,
+    init: 0xFFFF,
+    reflect_in: true,
+    reflect_out: true,
+    xor_out: 0x0000,
+    check: 0x4B37,
+};
+
+pub const CRC16_XMODEM: CRCSpec = CRCSpec {
+    width: 16,
+    poly: 0x1021,
+    init: 0x0000,
+    reflect_in: false,
+    reflect_out: false,
+    xor_out: 0x0000,
+    check: 0x31C3,
+};
+
+pub const CRC32: CRCSpec = CRCSpec {
+    width: 32,
+    poly: 0x04C1_1DB7,
+    init: 0xFFFF_FFFF,
+    reflect_in: true,
+    reflect_out: true,
+    xor_out: 0xFFFF_FFFF,
+    check: 0xCBF4_3926,
+};
+
+pub const CRC32C: CRCSpec = CRCSpec {
+    width: 32,
+    poly: 0x1EDC_6F41,
+    init: 0xFFFF_FFFF,
+    reflect_in: true,
+    reflect_out: true,
+    xor_out: 0xFFFF_FFFF,
+    check: 0xE306_9283,
+};
+
+fn reflect(x: u64, width: usize) -> u64 {
+    (0..width).fold(0, |acc, i| acc | (((x >> i) & 1) << (width - 1 - i)))
+}
+
+impl CRCSpec {
+    fn mask(&self) -> u64 {
+        if self.width == 64 {
+            !0
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+    // The hardware keeps the CRC register in the bit order of the
+    // output, so no reversal is needed at the end.
+    fn register_poly(&self) -> u64 {
+        if self.reflect_out {
+            reflect(self.poly, self.width)
+        } else {
+            self.poly
+        }
+    }
+    fn register_init(&self) -> u64 {
+        if self.reflect_out {
+            reflect(self.init, self.width)
+        } else {
+            self.init
+        }
+    }
+    // Software reference for the CRC of a sequence of `n` bit words.
+    // The bits of each word are consumed LSB first if `reflect_in` is
+    // set, and MSB first otherwise.  This is bit-exact with the `CRC`
+    // widget fed the same words.
+    pub fn compute_words(&self, words: &[u64], n: usize) -> u64 {
+        let mask = self.mask();
+        let poly = self.register_poly();
+        let mut crc = self.register_init();
+        for word in words {
+            for i in 0..n {
+                let bit = if self.reflect_in {
+                    (word >> i) & 1
+                } else {
+                    (word >> (n - 1 - i)) & 1
+                };
+                let feedback = if self.reflect_out {
+                    let fb = crc & 1;
+                    crc >>= 1;
+                    fb
+                } else {
+                    let fb = (crc >> (self.width - 1)) & 1;
+                    crc = (crc << 1) & mask;
+                    fb
+                };
+                if feedback ^ bit != 0 {
+                    crc ^= poly;
+                }
+            }
+        }
+        (crc ^ self.xor_out) & mask
+    }
+    pub fn compute(&self, data: &[u8]) -> u64 {
+        self.compute_words(&data.iter().map(|x| *x as u64).collect::<Vec<_>>(), 8)
+    }
+}
+
+// Computes a CRC over a stream of `N` bit words, one word per `strobe`.
+// `clear` restarts the computation, and `crc` holds the (finalized) CRC
+// of the words seen so far.  The word is consumed LSB first if the spec
+// reflects its input, and MSB first otherwise, so that `N=8` matches
+// the usual byte oriented definition of the CRC.
+#[derive(LogicBlock)]
+pub struct CRC<const W: usize, const N: usize> {
+    pub clock: Signal<In, Clock>,
+    pub clear: Signal<In, Bit>,
+    pub data: Signal<In, Bits<N>>,
+    pub strobe: Signal<In, Bit>,
+    pub crc: Signal<Out, Bits<W>>,
+    state: DFFWithInit<Bits<W>>,
+    poly: Constant<Bits<W>>,
+    init: Constant<Bits<W>>,
+    xor_out: Constant<Bits<W>>,
+    reflect_in: Constant<Bit>,
+    reflect_out: Constant<Bit>,
+    crc_msb: Constant<Bits<W>>,
+    data_msb: Constant<Bits<N>>,
+    update: Signal<Local, Bits<W>>,
+    word: Signal<Local, Bits<N>>,
+    feedback: Signal<Local, Bit>,
+}
+
+impl<const W: usize, const N: usize> CRC<W, N> {
+    pub fn new(spec: CRCSpec) -> Self {
+        assert_eq!(spec.width, W);
+        assert!(W <= 64);
+        Self {
+            clock: Default::default(),
+            clear: Default::default(),
+            data: Default::default(),
+            strobe: Default::default(),
+            crc: Default::default(),
+            state: DFFWithInit::new(spec.register_init().to_bits()),
+            poly: Constant::new(spec.register_poly().to_bits()),
+            init: Constant::new(spec.register_init().to_bits()),
+            xor_out: Constant::new(spec.xor_out.to_bits()),
+            reflect_in: Constant::new(spec.reflect_in),
+            reflect_out: Constant::new(spec.reflect_out),
+            crc_msb: Constant::new(Bits::<W>::default().replace_bit(W - 1, true)),
+            data_msb: Constant::new(Bits::<N>::default().replace_bit(N - 1, true)),
+            update: Default::default(),
+            word: Default::default(),
+            feedback: Default::default(),
+        }
+    }
+}
+
+impl<const W: usize, const N: usize> Logic for CRC<W, N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state);
+        self.update.next = self.state.q.val();
+        self.word.next = self.data.val();
+        for _i in 0..N {
+            if self.reflect_out.val() {
+                self.feedback.next = self.update.val().get_bit(0);
+                self.update.next = self.update.val() >> 1;
+            } else {
+                self.feedback.next = (self.update.val() & self.crc_msb.val()).any();
+                self.update.next = self.update.val() << 1;
+            }
+            if self.reflect_in.val() {
+                self.feedback.next = self.feedback.val() ^ self.word.val().get_bit(0);
+                self.word.next = self.word.val() >> 1;
+            } else {
+                self.feedback.next =
+                    self.feedback.val() ^ (self.word.val() & self.data_msb.val()).any();
+                self.word.next = self.word.val() << 1;
+            }
+            if self.feedback.val() {
+                self.update.next = self.update.val() ^ self.poly.val();
+            }
+        }
+        if self.strobe.val() {
+            self.state.d.next = self.update.val();
+        }
+        if self.clear.val() {
+            self.state.d.next = self.init.val();
+        }
+        self.crc.next = self.state.q.val() ^ self.xor_out.val();
+    }
+}
+
+#[test]
+fn test_crc_presets_check() {
+    for spec in [CRC8_CCITT, CRC16_MODBUS, CRC16_XMODEM, CRC32, CRC32C] {
+        assert_eq!(spec.compute(b"123456789"), spec.check);
+    }
+}
+
+#[test]
+fn test_crc_synthesizes() {
+    let mut uut = CRC::<16, 8>::new(CRC16_MODBUS);
+    uut.connect_all();
+    yosys_validate("crc16", &generate_verilog(&uut)).unwrap();
+}
+
+#[cfg(test)]
+fn crc_sim_test<const W: usize, const N: usize>(spec: CRCSpec, words: Vec<u64>) {
+    let mut uut = CRC::<W, N>::new(spec);
+    uut.connect_all();
+    let expected = spec.compute_words(&words, N);
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<CRC<W, N>>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<CRC<W, N>>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        x.clear.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.clear.next = false;
+        for word in &words {
+            x.data.next = (*word).to_bits();
+            x.strobe.next = true;
+            wait_clock_cycle!(sim, clock, x);
+            x.strobe.next = false;
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim_assert_eq!(sim, x.crc.val(), expected.to_bits::<W>(), x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000).unwrap();
+}
+
+#[test]
+fn test_crc_check_values_in_sim() {
+    let check = b"123456789".iter().map(|x| *x as u64).collect::<Vec<_>>();
+    crc_sim_test::<8, 8>(CRC8_CCITT, check.clone());
+    crc_sim_test::<16, 8>(CRC16_MODBUS, check.clone());
+    crc_sim_test::<16, 8>(CRC16_XMODEM, check.clone());
+    crc_sim_test::<32, 8>(CRC32, check.clone());
+    crc_sim_test::<32, 8>(CRC32C, check);
+}
+
+#[test]
+fn test_crc_multiple_bits_per_clock() {
+    let words = (0..40_u64)
+        .map(|x| (x * 0x1357) & 0xFFFF)
+        .collect::<Vec<_>>();
+    crc_sim_test::<32, 16>(CRC32, words.clone());
+    crc_sim_test::<16, 16>(CRC16_XMODEM, words);
+    let bits = (0..50_u64).map(|x| (x * 5 / 3) & 1).collect::<Vec<_>>();
+    crc_sim_test::<8, 1>(CRC8_CCITT, bits);
+}
+
+#[test]
+fn test_crc_wide_words_match_bytes() {
+    // Two bytes per clock, packed in the order they are consumed
+    let data = (0..64_u32).map(|x| (x * 13 + 1) as u8).collect::<Vec<_>>();
+    let words = data
+        .chunks(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]) as u64)
+        .collect::<Vec<_>>();
+    assert_eq!(CRC32.compute_words(&words, 16), CRC32.compute(&data));
+    let words = data
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], x[1]]) as u64)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        CRC16_XMODEM.compute_words(&words, 16),
+        CRC16_XMODEM.compute(&data)
+    );
+}
@@ -0,0 +1,259 @@
+use rust_hdl_core::prelude::*;
+
+// The vendor primitives are instantiated one bit at a time.  A one bit
+// port is a scalar in the generated Verilog, and cannot be indexed.
+fn lane(width: usize) -> &'static str {
+    if width == 1 {
+        ""
+    } else {
+        "[i]"
+    }
+}
+
+fn per_bit(width: usize, instance: String) -> String {
+    format!(
+        "genvar i;
+generate for (i = 0; i < {width}; i = i + 1) begin : ddr
+   {instance}
+end
+endgenerate",
+        width = width,
+        instance = instance
+    )
+}
+
+// A double data rate output register.  On each rising edge of the clock,
+// `d_rise` and `d_fall` are captured.  `q` presents `d_rise` while the clock
+// is high, and `d_fall` while it is low, so that two words are sent per
+// clock cycle (as with the `SAME_EDGE` mode of the Xilinx `ODDR`).
+//
+// The generated Verilog contains a portable description of the register,
+// and the vendor primitives for it.  When the design is generated with
+// `generate_verilog_for_target`, the `ODDRX1F` (ECP5), `SB_IO` (iCE40),
+// `ODDR2` (Spartan 6) or `ODDR` (7 series) primitives are used instead.
+// These must drive the output pads directly.  The iCE40 `SB_IO` samples
+// `d_fall` on the falling edge of the clock, so it must be held for the
+// whole clock cycle.
+#[derive(Clone, Debug, LogicBlock, Default)]
+pub struct DDROutputRegister<const N: usize> {
+    pub d_rise: Signal<In, Bits<N>>,
+    pub d_fall: Signal<In, Bits<N>>,
+    pub q: Signal<Out, Bits<N>>,
+    pub clock: Signal<In, Clock>,
+    _rise: Bits<N>,
+    _fall: Bits<N>,
+}
+
+impl<const N: usize> Logic for DDROutputRegister<N> {
+    fn update(&mut self) {
+        if self.clock.pos_edge() {
+            self._rise = self.d_rise.val();
+            self._fall = self.d_fall.val();
+        }
+        self.q.next = if self.clock.val().clk {
+            self._rise
+        } else {
+            self._fall
+        };
+    }
+    fn connect(&mut self) {
+        self.q.connect();
+    }
+
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_rise", &mut self._rise);
+        probe.visit_state("_fall", &mut self._fall);
+    }
+    fn hdl(&self) -> Verilog {
+        let i = lane(N);
+        Verilog::Wrapper(Wrapper {
+            code: format!(
+                "
+`ifdef RUST_HDL_TARGET_ECP5
+{ecp5}
+`elsif RUST_HDL_TARGET_ICE40
+{ice40}
+`elsif RUST_HDL_TARGET_SPARTAN6
+{spartan6}
+`elsif RUST_HDL_TARGET_XILINX7
+{xilinx7}
+`else
+reg [{msb}:0] rise_data;
+reg [{msb}:0] fall_data;
+always @(posedge clock) begin
+   rise_data <= d_rise;
+   fall_data <= d_fall;
+end
+assign q = clock ? rise_data : fall_data;
+`endif
+",
+                msb = N - 1,
+                ecp5 = per_bit(
+                    N,
+                    format!(
+                        "ODDRX1F oddr_inst(.SCLK(clock), .RST(1'b0), .D0(d_rise{i}), .D1(d_fall{i}), .Q(q{i}));",
+                        i = i
+                    )
+                ),
+                ice40 = per_bit(
+                    N,
+                    format!(
+                        "SB_IO #(.PIN_TYPE(6'b010000)) oddr_inst(.PACKAGE_PIN(q{i}), .OUTPUT_CLK(clock), .CLOCK_ENABLE(1'b1), .OUTPUT_ENABLE(1'b1), .D_OUT_0(d_rise{i}), .D_OUT_1(d_fall{i}));",
+                        i = i
+                    )
+                ),
+                spartan6 = per_bit(
+                    N,
+                    format!(
+                        "ODDR2 #(.DDR_ALIGNMENT(\"C0\"), .SRTYPE(\"SYNC\")) oddr_inst(.C0(clock), .C1(~clock), .CE(1'b1), .D0(d_rise{i}), .D1(d_fall{i}), .R(1'b0), .S(1'b0), .Q(q{i}));",
+                        i = i
+                    )
+                ),
+                xilinx7 = per_bit(
+                    N,
+                    format!(
+                        "ODDR #(.DDR_CLK_EDGE(\"SAME_EDGE\"), .SRTYPE(\"SYNC\")) oddr_inst(.C(clock), .CE(1'b1), .D1(d_rise{i}), .D2(d_fall{i}), .R(1'b0), .S(1'b0), .Q(q{i}));",
+                        i = i
+                    )
+                ),
+            ),
+            cores: "".into(),
+        })
+    }
+    fn timing(&self) -> Vec<TimingInfo> {
+        vec![TimingInfo {
+            name: "ddr_output_register".into(),
+            clock: "clock".into(),
+            inputs: vec!["d_rise".into(), "d_fall".into()],
+            outputs: vec!["q".into()],
+            ..Default::default()
+        }]
+    }
+}
+
+// A double data rate input register.  `d` is sampled on both edges of
+// the clock.  On each rising edge, `q_rise` and `q_fall` are updated with
+// the values sampled on the previous rising edge, and on the falling edge
+// that followed it (as with the `SAME_EDGE_PIPELINED` mode of the Xilinx
+// `IDDR`).
+//
+// As with the [DDROutputRegister], the `IDDRX1F` (ECP5), `SB_IO` (iCE40),
+// `IDDR2` (Spartan 6) or `IDDR` (7 series) primitives are used when the
+// design is generated for one of those targets.  These must be driven by the input pads
+// directly.  The pipeline latency of the vendor primitives can differ
+// from that of the portable description.
+#[derive(Clone, Debug, LogicBlock, Default)]
+pub struct DDRInputRegister<const N: usize> {
+    pub d: Signal<In, Bits<N>>,
+    pub q_rise: Signal<Out, Bits<N>>,
+    pub q_fall: Signal<Out, Bits<N>>,
+    pub clock: Signal<In, Clock>,
+    _rise: Bits<N>,
+    _fall: Bits<N>,
+}
+
+impl<const N: usize> Logic for DDRInputRegister<N> {
+    fn update(&mut self) {
+        if self.clock.pos_edge() {
+            self.q_rise.next = self._rise;
+            self.q_fall.next = self._fall;
+            self._rise = self.d.val();
+        }
+        if self.clock.neg_edge() {
+            self._fall = self.d.val();
+        }
+    }
+    fn connect(&mut self) {
+        self.q_rise.connect();
+        self.q_fall.connect();
+    }
+
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_rise", &mut self._rise);
+        probe.visit_state("_fall", &mut self._fall);
+    }
+    fn hdl(&self) -> Verilog {
+        let i = lane(N);
+        Verilog::Wrapper(Wrapper {
+            code: format!(
+                "
+`ifdef RUST_HDL_TARGET_ECP5
+{ecp5}
+`elsif RUST_HDL_TARGET_ICE40
+{ice40}
+`elsif RUST_HDL_TARGET_SPARTAN6
+{spartan6}
+`elsif RUST_HDL_TARGET_XILINX7
+{xilinx7}
+`else
+reg [{msb}:0] rise_data;
+reg [{msb}:0] fall_data;
+reg [{msb}:0] rise_out;
+reg [{msb}:0] fall_out;
+always @(posedge clock) begin
+   rise_out <= rise_data;
+   fall_out <= fall_data;
+   rise_data <= d;
+end
+always @(negedge clock) fall_data <= d;
+assign q_rise = rise_out;
+assign q_fall = fall_out;
+`endif
+",
+                msb = N - 1,
+                ecp5 = per_bit(
+                    N,
+                    format!(
+                        "IDDRX1F iddr_inst(.SCLK(clock), .RST(1'b0), .D(d{i}), .Q0(q_rise{i}), .Q1(q_fall{i}));",
+                        i = i
+                    )
+                ),
+                ice40 = per_bit(
+                    N,
+                    format!(
+                        "SB_IO #(.PIN_TYPE(6'b000000)) iddr_inst(.PACKAGE_PIN(d{i}), .INPUT_CLK(clock), .CLOCK_ENABLE(1'b1), .D_IN_0(q_rise{i}), .D_IN_1(q_fall{i}));",
+                        i = i
+                    )
+                ),
+                spartan6 = per_bit(
+                    N,
+                    format!(
+                        "IDDR2 #(.DDR_ALIGNMENT(\"C0\"), .SRTYPE(\"SYNC\")) iddr_inst(.C0(clock), .C1(~clock), .CE(1'b1), .D(d{i}), .R(1'b0), .S(1'b0), .Q0(q_rise{i}), .Q1(q_fall{i}));",
+                        i = i
+                    )
+                ),
+                xilinx7 = per_bit(
+                    N,
+                    format!(
+                        "IDDR #(.DDR_CLK_EDGE(\"SAME_EDGE_PIPELINED\"), .SRTYPE(\"SYNC\")) iddr_inst(.C(clock), .CE(1'b1), .D(d{i}), .R(1'b0), .S(1'b0), .Q1(q_rise{i}), .Q2(q_fall{i}));",
+                        i = i
+                    )
+                ),
+            ),
+            cores: "".into(),
+        })
+    }
+    fn timing(&self) -> Vec<TimingInfo> {
+        vec![TimingInfo {
+            name: "ddr_input_register".into(),
+            clock: "clock".into(),
+            inputs: vec!["d".into()],
+            outputs: vec!["q_rise".into(), "q_fall".into()],
+            ..Default::default()
+        }]
+    }
+}
+
+#[test]
+fn test_ddr_output_register_synthesizes() {
+    let mut uut = DDROutputRegister::<4>::default();
+    uut.connect_all();
+    yosys_validate("ddr_output_register", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_ddr_input_register_synthesizes() {
+    let mut uut = DDRInputRegister::<1>::default();
+    uut.connect_all();
+    yosys_validate("ddr_input_register", &generate_verilog(&uut)).unwrap();
+}
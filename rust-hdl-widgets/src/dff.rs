@@ -46,12 +46,29 @@ end
             clock: "clock".into(),
             inputs: vec!["d".into()],
             outputs: vec!["q".into()],
+            ..Default::default()
         }]
     }
 }
 
+// Connects the clock of each of the registers, and defaults the next
+// state of each register to its current state.  With a `reset = <reset>`
+// argument, the registers (which must be [ResetDFF]s) are also connected
+// to the block level reset, and enabled.  This is how the reset is wired
+// automatically: name the block's reset once (usually its `reset` field,
+// as in `dff_setup!(self, clock, reset = reset, ...)`), and every register
+// listed is reset from it.  The reset is named explicitly because plain
+// [DFF]s have no reset input, and the same macro sets up both.
+//
+// [ResetDFF]: crate::reset_dff::ResetDFF
 #[macro_export]
 macro_rules! dff_setup {
+    ($self: ident, $clock: ident, reset = $reset: ident, $($dff: ident),+) => {
+        $($self.$dff.clock.next = $self.$clock.val());+;
+        $($self.$dff.reset.next = $self.$reset.val());+;
+        $($self.$dff.enable.next = true);+;
+        $($self.$dff.d.next = $self.$dff.q.val());+;
+    };
     ($self: ident, $clock: ident, $($dff: ident),+) => {
         $($self.$dff.clock.next = $self.$clock.val());+;
         $($self.$dff.d.next = $self.$dff.q.val());+;
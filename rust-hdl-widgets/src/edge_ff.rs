@@ -45,6 +45,7 @@ always @(posedge clk) q <= d;",
             clock: "clk".to_string(),
             inputs: vec!["d".into()],
             outputs: vec!["q".into()],
+            ..Default::default()
         }]
     }
 }
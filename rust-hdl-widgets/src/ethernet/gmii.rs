@@ -0,0 +1,115 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use rust_hdl_core::prelude::*;
+
+// The GMII signals as seen from the MAC.  The receive and transmit
+// halves are assumed to share the 125 MHz `clock` of the MAC.
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "GMIIWiresPHY"]
+pub struct GMIIWiresMAC {
+    pub txd: Signal<Out, Bits<8>>,
+    pub tx_en: Signal<Out, Bit>,
+    pub tx_er: Signal<Out, Bit>,
+    pub rxd: Signal<In, Bits<8>>,
+    pub rx_dv: Signal<In, Bit>,
+    pub rx_er: Signal<In, Bit>,
+}
+
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "GMIIWiresMAC"]
+pub struct GMIIWiresPHY {
+    pub txd: Signal<In, Bits<8>>,
+    pub tx_en: Signal<In, Bit>,
+    pub tx_er: Signal<In, Bit>,
+    pub rxd: Signal<Out, Bits<8>>,
+    pub rx_dv: Signal<Out, Bit>,
+    pub rx_er: Signal<Out, Bit>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum GMIIReceiverState {
+    Idle,
+    Data,
+    Discard,
+}
+
+// Strips the preamble and SFD from the GMII receive stream.  Same
+// outputs as the RMII receiver.  A receive error truncates the frame,
+// which will then fail the FCS check in the MAC.
+#[derive(LogicBlock, Default)]
+pub struct GMIIReceiver {
+    pub clock: Signal<In, Clock>,
+    pub rxd: Signal<In, Bits<8>>,
+    pub rx_dv: Signal<In, Bit>,
+    pub rx_er: Signal<In, Bit>,
+    pub data: Signal<Out, Bits<8>>,
+    pub strobe: Signal<Out, Bit>,
+    pub active: Signal<Out, Bit>,
+    state: DFF<GMIIReceiverState>,
+    byte: DFF<Bits<8>>,
+    valid: DFF<Bit>,
+}
+
+impl Logic for GMIIReceiver {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state, byte, valid);
+        self.data.next = self.byte.q.val();
+        self.strobe.next = self.valid.q.val();
+        self.valid.d.next = false;
+        self.active.next = self.state.q.val() == GMIIReceiverState::Data;
+        match self.state.q.val() {
+            GMIIReceiverState::Idle => {
+                if self.rx_dv.val() & (self.rxd.val() == 0xD5) {
+                    self.state.d.next = GMIIReceiverState::Data;
+                }
+            }
+            GMIIReceiverState::Data => {
+                if !self.rx_dv.val() {
+                    self.state.d.next = GMIIReceiverState::Idle;
+                } else if self.rx_er.val() {
+                    self.state.d.next = GMIIReceiverState::Discard;
+                } else {
+                    self.byte.d.next = self.rxd.val();
+                    self.valid.d.next = true;
+                }
+            }
+            GMIIReceiverState::Discard => {
+                if !self.rx_dv.val() {
+                    self.state.d.next = GMIIReceiverState::Idle;
+                }
+            }
+            _ => {
+                self.state.d.next = GMIIReceiverState::Idle;
+            }
+        }
+    }
+}
+
+// Registers bytes out to the PHY.  A byte can be sent every clock,
+// so `strobe` is always asserted.
+#[derive(LogicBlock, Default)]
+pub struct GMIITransmitter {
+    pub clock: Signal<In, Clock>,
+    pub data: Signal<In, Bits<8>>,
+    pub enable: Signal<In, Bit>,
+    pub strobe: Signal<Out, Bit>,
+    pub txd: Signal<Out, Bits<8>>,
+    pub tx_en: Signal<Out, Bit>,
+    pub tx_er: Signal<Out, Bit>,
+    byte: DFF<Bits<8>>,
+    sending: DFF<Bit>,
+}
+
+impl Logic for GMIITransmitter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, byte, sending);
+        self.strobe.next = true;
+        self.byte.d.next = self.data.val();
+        self.sending.d.next = self.enable.val();
+        self.txd.next = self.byte.q.val();
+        self.tx_en.next = self.sending.q.val();
+        self.tx_er.next = false;
+    }
+}
@@ -0,0 +1,194 @@
+use crate::ethernet::gmii::{GMIIReceiver, GMIITransmitter, GMIIWiresMAC};
+use crate::ethernet::mac_rx::EthernetMACReceiver;
+use crate::ethernet::mac_tx::EthernetMACTransmitter;
+use crate::ethernet::rmii::{RMIIReceiver, RMIITransmitter, RMIIWiresMAC};
+use crate::ethernet::{EthernetRxSource, EthernetSpeed, EthernetTxSink};
+use rust_hdl_core::prelude::*;
+
+// A 10/100 MAC with an RMII interface to the PHY.  Everything runs
+// off the 50 MHz RMII reference clock.
+#[derive(LogicBlock)]
+pub struct RMIIEthernetMAC {
+    pub clock: Signal<In, Clock>,
+    pub rmii: RMIIWiresMAC,
+    pub rx: EthernetRxSource,
+    pub tx: EthernetTxSink,
+    phy_rx: RMIIReceiver,
+    phy_tx: RMIITransmitter,
+    mac_rx: EthernetMACReceiver,
+    mac_tx: EthernetMACTransmitter,
+}
+
+impl RMIIEthernetMAC {
+    pub fn new(speed: EthernetSpeed) -> Self {
+        Self {
+            clock: Default::default(),
+            rmii: Default::default(),
+            rx: Default::default(),
+            tx: Default::default(),
+            phy_rx: RMIIReceiver::new(speed),
+            phy_tx: RMIITransmitter::new(speed),
+            mac_rx: Default::default(),
+            mac_tx: Default::default(),
+        }
+    }
+}
+
+impl Logic for RMIIEthernetMAC {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, phy_rx, phy_tx, mac_rx, mac_tx);
+        // Receive path
+        self.phy_rx.rxd.next = self.rmii.rxd.val();
+        self.phy_rx.crs_dv.next = self.rmii.crs_dv.val();
+        self.mac_rx.phy_data.next = self.phy_rx.data.val();
+        self.mac_rx.phy_strobe.next = self.phy_rx.strobe.val();
+        self.mac_rx.phy_active.next = self.phy_rx.active.val();
+        EthernetRxSource::link(&mut self.rx, &mut self.mac_rx.frame);
+        // Transmit path
+        EthernetTxSink::link(&mut self.tx, &mut self.mac_tx.frame);
+        self.phy_tx.data.next = self.mac_tx.phy_data.val();
+        self.phy_tx.enable.next = self.mac_tx.phy_enable.val();
+        self.mac_tx.phy_strobe.next = self.phy_tx.strobe.val();
+        self.rmii.txd.next = self.phy_tx.txd.val();
+        self.rmii.tx_en.next = self.phy_tx.tx_en.val();
+    }
+}
+
+// A gigabit MAC with a GMII interface to the PHY.  Everything runs
+// off the 125 MHz GMII clock.
+#[derive(LogicBlock, Default)]
+pub struct GMIIEthernetMAC {
+    pub clock: Signal<In, Clock>,
+    pub gmii: GMIIWiresMAC,
+    pub rx: EthernetRxSource,
+    pub tx: EthernetTxSink,
+    phy_rx: GMIIReceiver,
+    phy_tx: GMIITransmitter,
+    mac_rx: EthernetMACReceiver,
+    mac_tx: EthernetMACTransmitter,
+}
+
+impl Logic for GMIIEthernetMAC {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, phy_rx, phy_tx, mac_rx, mac_tx);
+        // Receive path
+        self.phy_rx.rxd.next = self.gmii.rxd.val();
+        self.phy_rx.rx_dv.next = self.gmii.rx_dv.val();
+        self.phy_rx.rx_er.next = self.gmii.rx_er.val();
+        self.mac_rx.phy_data.next = self.phy_rx.data.val();
+        self.mac_rx.phy_strobe.next = self.phy_rx.strobe.val();
+        self.mac_rx.phy_active.next = self.phy_rx.active.val();
+        EthernetRxSource::link(&mut self.rx, &mut self.mac_rx.frame);
+        // Transmit path
+        EthernetTxSink::link(&mut self.tx, &mut self.mac_tx.frame);
+        self.phy_tx.data.next = self.mac_tx.phy_data.val();
+        self.phy_tx.enable.next = self.mac_tx.phy_enable.val();
+        self.mac_tx.phy_strobe.next = self.phy_tx.strobe.val();
+        self.gmii.txd.next = self.phy_tx.txd.val();
+        self.gmii.tx_en.next = self.phy_tx.tx_en.val();
+        self.gmii.tx_er.next = self.phy_tx.tx_er.val();
+    }
+}
+
+#[test]
+fn test_rmii_mac_synthesizes() {
+    let mut uut = RMIIEthernetMAC::new(EthernetSpeed::Mbps100);
+    uut.connect_all();
+    yosys_validate("rmii_mac", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_gmii_mac_synthesizes() {
+    let mut uut = GMIIEthernetMAC::default();
+    uut.connect_all();
+    yosys_validate("gmii_mac", &generate_verilog(&uut)).unwrap();
+}
+
+#[cfg(test)]
+#[derive(LogicBlock)]
+struct RMIILoopback {
+    pub clock: Signal<In, Clock>,
+    pub mac: RMIIEthernetMAC,
+}
+
+#[cfg(test)]
+impl Logic for RMIILoopback {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, mac);
+        self.mac.rmii.rxd.next = self.mac.rmii.txd.val();
+        self.mac.rmii.crs_dv.next = self.mac.rmii.tx_en.val();
+    }
+}
+
+#[cfg(test)]
+fn rmii_loopback_test(speed: EthernetSpeed, payload_len: usize) {
+    let mut uut = RMIILoopback {
+        clock: Default::default(),
+        mac: RMIIEthernetMAC::new(speed),
+    };
+    uut.mac.tx.data.connect();
+    uut.mac.tx.start.connect();
+    uut.mac.tx.last.connect();
+    uut.connect_all();
+    let frame = (0..payload_len)
+        .map(|x| (x * 13 + 5) as u8)
+        .collect::<Vec<_>>();
+    let mut expected = frame.clone();
+    expected.resize(60.max(payload_len), 0);
+    let fcs = crate::ethernet::crc32::ethernet_crc32(&expected);
+    expected.extend_from_slice(&fcs.to_le_bytes());
+    let mut sim = Simulation::new();
+    sim.add_clock(10_000, |x: &mut Box<RMIILoopback>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<RMIILoopback>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        x.mac.tx.start.next = true;
+        for (ndx, byte) in frame.iter().enumerate() {
+            x.mac.tx.data.next = (*byte as u64).into();
+            x.mac.tx.last.next = ndx == frame.len() - 1;
+            x = sim.watch(|x| x.mac.tx.advance.val() & x.clock.val().clk, x)?;
+            x.mac.tx.start.next = false;
+            wait_clock_cycle!(sim, clock, x);
+        }
+        x.mac.tx.last.next = false;
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<RMIILoopback>| {
+        let mut x = sim.init()?;
+        let mut received = vec![];
+        loop {
+            wait_clock_true!(sim, clock, x);
+            if x.mac.rx.strobe.val() {
+                received.push(x.mac.rx.data.val().to_u8());
+            }
+            if x.mac.rx.end.val() {
+                sim_assert!(sim, x.mac.rx.ok.val(), x);
+                break;
+            }
+            wait_clock_false!(sim, clock, x);
+        }
+        sim_assert_eq!(sim, received, expected, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 200_000_000).unwrap();
+}
+
+#[test]
+fn test_rmii_mac_loopback_100() {
+    rmii_loopback_test(EthernetSpeed::Mbps100, 100);
+}
+
+#[test]
+fn test_rmii_mac_loopback_pads_short_frames() {
+    rmii_loopback_test(EthernetSpeed::Mbps100, 20);
+}
+
+#[test]
+fn test_rmii_mac_loopback_10() {
+    rmii_loopback_test(EthernetSpeed::Mbps10, 64);
+}
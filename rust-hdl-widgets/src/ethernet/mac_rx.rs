@@ -0,0 +1,59 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::ethernet::crc32::EthernetCRC32;
+use crate::ethernet::EthernetRxSource;
+use rust_hdl_core::prelude::*;
+
+// Receive half of the MAC.  Takes the byte stream from a PHY adapter
+// (e.g., the RMII or GMII receiver), and checks the FCS and minimum
+// length of each frame.  Frames are not buffered - the bytes are passed
+// through as they arrive, and the client must discard a frame if `ok`
+// is not set at the end of it.
+#[derive(LogicBlock, Default)]
+pub struct EthernetMACReceiver {
+    pub clock: Signal<In, Clock>,
+    pub phy_data: Signal<In, Bits<8>>,
+    pub phy_strobe: Signal<In, Bit>,
+    pub phy_active: Signal<In, Bit>,
+    pub frame: EthernetRxSource,
+    crc: EthernetCRC32,
+    was_active: DFF<Bit>,
+    first: DFF<Bit>,
+    count: DFF<Bits<11>>,
+}
+
+impl Logic for EthernetMACReceiver {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, was_active, first, count);
+        clock!(self, clock, crc);
+        self.was_active.d.next = self.phy_active.val();
+        // The CRC is held in its initial state between frames
+        self.crc.data.next = self.phy_data.val();
+        self.crc.strobe.next = self.phy_strobe.val();
+        self.crc.clear.next = !self.phy_active.val();
+        self.frame.data.next = self.phy_data.val();
+        self.frame.strobe.next = self.phy_strobe.val();
+        self.frame.start.next = self.phy_strobe.val() & self.first.q.val();
+        if self.phy_strobe.val() {
+            self.first.d.next = false;
+            if !self.count.q.val().all() {
+                self.count.d.next = self.count.q.val() + 1;
+            }
+        }
+        if !self.phy_active.val() {
+            self.first.d.next = true;
+            self.count.d.next = 0.into();
+        }
+        // Minimum frame size is 64 bytes, including the FCS
+        self.frame.end.next = self.was_active.q.val() & !self.phy_active.val();
+        self.frame.ok.next = self.crc.residue_ok.val() & (self.count.q.val() >= 64);
+    }
+}
+
+#[test]
+fn test_mac_receiver_synthesizes() {
+    let mut uut = EthernetMACReceiver::default();
+    uut.connect_all();
+    yosys_validate("eth_mac_rx", &generate_verilog(&uut)).unwrap();
+}
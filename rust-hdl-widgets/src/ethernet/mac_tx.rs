@@ -0,0 +1,147 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::ethernet::crc32::EthernetCRC32;
+use crate::ethernet::EthernetTxSink;
+use rust_hdl_core::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum EthernetMACTransmitterState {
+    Idle,
+    Preamble,
+    StartFrame,
+    Payload,
+    Pad,
+    CheckSequence,
+    Gap,
+}
+
+// Transmit half of the MAC.  Wraps the frame from the client with the
+// preamble and SFD, pads it to the minimum length, and appends the FCS.
+// A 12 byte inter-frame gap is inserted after each frame.  The state
+// machine advances once per byte time, as indicated by `phy_strobe`
+// from the PHY adapter.
+#[derive(LogicBlock, Default)]
+pub struct EthernetMACTransmitter {
+    pub clock: Signal<In, Clock>,
+    pub frame: EthernetTxSink,
+    pub phy_data: Signal<Out, Bits<8>>,
+    pub phy_enable: Signal<Out, Bit>,
+    pub phy_strobe: Signal<In, Bit>,
+    state: DFF<EthernetMACTransmitterState>,
+    count: DFF<Bits<6>>,
+    crc: EthernetCRC32,
+    fcs: Signal<Local, Bits<32>>,
+}
+
+impl Logic for EthernetMACTransmitter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state, count);
+        clock!(self, clock, crc);
+        self.fcs.next = !self.crc.crc.val();
+        // Latch prevention
+        self.phy_data.next = 0.into();
+        self.phy_enable.next = false;
+        self.frame.advance.next = false;
+        self.crc.data.next = self.frame.data.val();
+        self.crc.strobe.next = false;
+        self.crc.clear.next = false;
+        match self.state.q.val() {
+            EthernetMACTransmitterState::Idle => {
+                self.crc.clear.next = true;
+                if self.frame.start.val() {
+                    self.count.d.next = 0.into();
+                    self.state.d.next = EthernetMACTransmitterState::Preamble;
+                }
+            }
+            EthernetMACTransmitterState::Preamble => {
+                self.phy_data.next = 0x55.into();
+                self.phy_enable.next = true;
+                if self.phy_strobe.val() {
+                    self.count.d.next = self.count.q.val() + 1;
+                    if self.count.q.val() == 6 {
+                        self.state.d.next = EthernetMACTransmitterState::StartFrame;
+                    }
+                }
+            }
+            EthernetMACTransmitterState::StartFrame => {
+                self.phy_data.next = 0xD5.into();
+                self.phy_enable.next = true;
+                if self.phy_strobe.val() {
+                    self.count.d.next = 0.into();
+                    self.state.d.next = EthernetMACTransmitterState::Payload;
+                }
+            }
+            EthernetMACTransmitterState::Payload => {
+                self.phy_data.next = self.frame.data.val();
+                self.phy_enable.next = true;
+                if self.phy_strobe.val() {
+                    self.crc.strobe.next = true;
+                    self.frame.advance.next = true;
+                    // The count saturates - it is only needed to pad short frames
+                    if !self.count.q.val().all() {
+                        self.count.d.next = self.count.q.val() + 1;
+                    }
+                    if self.frame.last.val() {
+                        if self.count.q.val() < 59 {
+                            self.state.d.next = EthernetMACTransmitterState::Pad;
+                        } else {
+                            self.count.d.next = 0.into();
+                            self.state.d.next = EthernetMACTransmitterState::CheckSequence;
+                        }
+                    }
+                }
+            }
+            EthernetMACTransmitterState::Pad => {
+                self.phy_data.next = 0.into();
+                self.phy_enable.next = true;
+                self.crc.data.next = 0.into();
+                if self.phy_strobe.val() {
+                    self.crc.strobe.next = true;
+                    self.count.d.next = self.count.q.val() + 1;
+                    if self.count.q.val() == 59 {
+                        self.count.d.next = 0.into();
+                        self.state.d.next = EthernetMACTransmitterState::CheckSequence;
+                    }
+                }
+            }
+            EthernetMACTransmitterState::CheckSequence => {
+                // The FCS goes out least significant byte first
+                self.phy_enable.next = true;
+                self.phy_data.next = self.fcs.val().get_bits::<8>(0);
+                if self.count.q.val() == 1 {
+                    self.phy_data.next = self.fcs.val().get_bits::<8>(8);
+                } else if self.count.q.val() == 2 {
+                    self.phy_data.next = self.fcs.val().get_bits::<8>(16);
+                } else if self.count.q.val() == 3 {
+                    self.phy_data.next = self.fcs.val().get_bits::<8>(24);
+                }
+                if self.phy_strobe.val() {
+                    self.count.d.next = self.count.q.val() + 1;
+                    if self.count.q.val() == 3 {
+                        self.count.d.next = 0.into();
+                        self.state.d.next = EthernetMACTransmitterState::Gap;
+                    }
+                }
+            }
+            EthernetMACTransmitterState::Gap => {
+                if self.phy_strobe.val() {
+                    self.count.d.next = self.count.q.val() + 1;
+                    if self.count.q.val() == 11 {
+                        self.state.d.next = EthernetMACTransmitterState::Idle;
+                    }
+                }
+            }
+            _ => {
+                self.state.d.next = EthernetMACTransmitterState::Idle;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mac_transmitter_synthesizes() {
+    let mut uut = EthernetMACTransmitter::default();
+    uut.connect_all();
+    yosys_validate("eth_mac_tx", &generate_verilog(&uut)).unwrap();
+}
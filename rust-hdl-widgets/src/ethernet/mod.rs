@@ -0,0 +1,79 @@
+use rust_hdl_core::prelude::*;
+
+pub mod crc32;
+pub mod gmii;
+pub mod mac;
+pub mod mac_rx;
+pub mod mac_tx;
+pub mod rmii;
+
+// The frame level interfaces between a MAC and its client.  Frames
+// are passed a byte at a time, starting with the destination MAC address
+// (i.e., the preamble and SFD are handled by the MAC).
+//
+// On the receive side, every byte after the SFD is presented with
+// a one cycle `strobe` (including the 4 FCS bytes at the end of the frame).
+// `start` is asserted with the first byte of the frame, and `end` is
+// pulsed once the frame is over.  `ok` is valid when `end` is asserted,
+// and indicates that the FCS checked out and the frame was long enough.
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "EthernetRxSink"]
+pub struct EthernetRxSource {
+    pub data: Signal<Out, Bits<8>>,
+    pub strobe: Signal<Out, Bit>,
+    pub start: Signal<Out, Bit>,
+    pub end: Signal<Out, Bit>,
+    pub ok: Signal<Out, Bit>,
+}
+
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "EthernetRxSource"]
+pub struct EthernetRxSink {
+    pub data: Signal<In, Bits<8>>,
+    pub strobe: Signal<In, Bit>,
+    pub start: Signal<In, Bit>,
+    pub end: Signal<In, Bit>,
+    pub ok: Signal<In, Bit>,
+}
+
+// On the transmit side, the client holds `start` high while it has
+// a frame to send, and presents the bytes of the frame on `data`
+// (with `last` marking the final byte).  Each time the MAC consumes
+// a byte, it pulses `advance`, and the client must present the following
+// byte on the next clock.  The MAC takes care of padding short frames
+// and appending the FCS.  Once a frame starts, the client must be
+// able to supply bytes on demand.
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "EthernetTxSink"]
+pub struct EthernetTxSource {
+    pub data: Signal<Out, Bits<8>>,
+    pub start: Signal<Out, Bit>,
+    pub last: Signal<Out, Bit>,
+    pub advance: Signal<In, Bit>,
+}
+
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "EthernetTxSource"]
+pub struct EthernetTxSink {
+    pub data: Signal<In, Bits<8>>,
+    pub start: Signal<In, Bit>,
+    pub last: Signal<In, Bit>,
+    pub advance: Signal<Out, Bit>,
+}
+
+// The line rate of an RMII link.  RMII always runs from a 50 MHz
+// reference clock, so at 10 Mbps, each dibit is held for 10 clocks.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EthernetSpeed {
+    Mbps10,
+    Mbps100,
+}
+
+impl EthernetSpeed {
+    pub(crate) fn clocks_per_dibit(&self) -> u64 {
+        match self {
+            EthernetSpeed::Mbps10 => 10,
+            EthernetSpeed::Mbps100 => 1,
+        }
+    }
+}
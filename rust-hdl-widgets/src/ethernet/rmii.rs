@@ -0,0 +1,184 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::ethernet::EthernetSpeed;
+use rust_hdl_core::prelude::*;
+
+// The RMII signals as seen from the MAC.  All of the signals are
+// synchronous to the 50 MHz reference clock, which is supplied externally.
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "RMIIWiresPHY"]
+pub struct RMIIWiresMAC {
+    pub txd: Signal<Out, Bits<2>>,
+    pub tx_en: Signal<Out, Bit>,
+    pub rxd: Signal<In, Bits<2>>,
+    pub crs_dv: Signal<In, Bit>,
+}
+
+#[derive(Clone, Debug, Default, LogicInterface)]
+#[join = "RMIIWiresMAC"]
+pub struct RMIIWiresPHY {
+    pub txd: Signal<In, Bits<2>>,
+    pub tx_en: Signal<In, Bit>,
+    pub rxd: Signal<Out, Bits<2>>,
+    pub crs_dv: Signal<Out, Bit>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum RMIIReceiverState {
+    Idle,
+    Preamble,
+    Data,
+}
+
+// Collects dibits from the PHY into bytes.  The preamble and SFD are
+// stripped, and each byte of the frame is presented on `data` with a
+// single cycle `strobe`.  `active` is high for the duration of the frame.
+#[derive(LogicBlock)]
+pub struct RMIIReceiver {
+    pub clock: Signal<In, Clock>,
+    pub rxd: Signal<In, Bits<2>>,
+    pub crs_dv: Signal<In, Bit>,
+    pub data: Signal<Out, Bits<8>>,
+    pub strobe: Signal<Out, Bit>,
+    pub active: Signal<Out, Bit>,
+    state: DFF<RMIIReceiverState>,
+    tick: DFF<Bits<4>>,
+    dibit: DFF<Bits<2>>,
+    shift: DFF<Bits<8>>,
+    complete: DFF<Bit>,
+    sample: Signal<Local, Bit>,
+    divider: Constant<Bits<4>>,
+}
+
+impl RMIIReceiver {
+    pub fn new(speed: EthernetSpeed) -> Self {
+        Self {
+            clock: Default::default(),
+            rxd: Default::default(),
+            crs_dv: Default::default(),
+            data: Default::default(),
+            strobe: Default::default(),
+            active: Default::default(),
+            state: Default::default(),
+            tick: Default::default(),
+            dibit: Default::default(),
+            shift: Default::default(),
+            complete: Default::default(),
+            sample: Default::default(),
+            divider: Constant::new((speed.clocks_per_dibit() - 1).into()),
+        }
+    }
+}
+
+impl Logic for RMIIReceiver {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state, tick, dibit, shift, complete);
+        // At 10 Mbps, the PHY holds each dibit for 10 reference clocks.
+        // The tick counter is held in reset while the line is idle, so that
+        // we sample on the first clock of each dibit.
+        self.sample.next = !self.tick.q.val().any();
+        self.tick.d.next = self.tick.q.val() + 1;
+        if self.tick.q.val() == self.divider.val() {
+            self.tick.d.next = 0.into();
+        }
+        self.data.next = self.shift.q.val();
+        self.strobe.next = self.complete.q.val();
+        self.complete.d.next = false;
+        self.active.next = self.state.q.val() == RMIIReceiverState::Data;
+        match self.state.q.val() {
+            RMIIReceiverState::Idle => {
+                if !self.crs_dv.val() {
+                    self.tick.d.next = 0.into();
+                } else if self.sample.val() & (self.rxd.val() == 1) {
+                    self.state.d.next = RMIIReceiverState::Preamble;
+                }
+            }
+            RMIIReceiverState::Preamble => {
+                if self.sample.val() {
+                    if !self.crs_dv.val() {
+                        self.state.d.next = RMIIReceiverState::Idle;
+                    } else if self.rxd.val() == 3 {
+                        self.dibit.d.next = 0.into();
+                        self.state.d.next = RMIIReceiverState::Data;
+                    }
+                }
+            }
+            RMIIReceiverState::Data => {
+                if self.sample.val() {
+                    if !self.crs_dv.val() {
+                        self.state.d.next = RMIIReceiverState::Idle;
+                    } else {
+                        // Dibits arrive least significant first
+                        self.shift.d.next =
+                            (self.shift.q.val() >> 2) | (bit_cast::<8, 2>(self.rxd.val()) << 6);
+                        self.dibit.d.next = self.dibit.q.val() + 1;
+                        // The byte is complete once the 4th dibit is shifted in
+                        self.complete.d.next = self.dibit.q.val().all();
+                    }
+                }
+            }
+            _ => {
+                self.state.d.next = RMIIReceiverState::Idle;
+            }
+        }
+    }
+}
+
+// Serializes bytes into dibits for the PHY.  The transmitter is free
+// running - once per byte time, it pulses `strobe` and latches `data`
+// and `enable` at the end of that cycle.  The latched byte is then sent
+// (if `enable` was set) over the following byte time.
+#[derive(LogicBlock)]
+pub struct RMIITransmitter {
+    pub clock: Signal<In, Clock>,
+    pub data: Signal<In, Bits<8>>,
+    pub enable: Signal<In, Bit>,
+    pub strobe: Signal<Out, Bit>,
+    pub txd: Signal<Out, Bits<2>>,
+    pub tx_en: Signal<Out, Bit>,
+    tick: DFF<Bits<4>>,
+    dibit: DFF<Bits<2>>,
+    shift: DFF<Bits<8>>,
+    sending: DFF<Bit>,
+    divider: Constant<Bits<4>>,
+}
+
+impl RMIITransmitter {
+    pub fn new(speed: EthernetSpeed) -> Self {
+        Self {
+            clock: Default::default(),
+            data: Default::default(),
+            enable: Default::default(),
+            strobe: Default::default(),
+            txd: Default::default(),
+            tx_en: Default::default(),
+            tick: Default::default(),
+            dibit: Default::default(),
+            shift: Default::default(),
+            sending: Default::default(),
+            divider: Constant::new((speed.clocks_per_dibit() - 1).into()),
+        }
+    }
+}
+
+impl Logic for RMIITransmitter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, tick, dibit, shift, sending);
+        self.txd.next = self.shift.q.val().get_bits::<2>(0);
+        self.tx_en.next = self.sending.q.val();
+        self.strobe.next = false;
+        self.tick.d.next = self.tick.q.val() + 1;
+        if self.tick.q.val() == self.divider.val() {
+            self.tick.d.next = 0.into();
+            self.dibit.d.next = self.dibit.q.val() + 1;
+            self.shift.d.next = self.shift.q.val() >> 2;
+            if self.dibit.q.val().all() {
+                self.shift.d.next = self.data.val();
+                self.sending.d.next = self.enable.val();
+                self.strobe.next = true;
+            }
+        }
+    }
+}
@@ -16,6 +16,9 @@ pub enum I2CControllerCmd {
     Read,
     EndTransmission,
     ReadLast,
+    BeginWrite10,
+    BeginRead10,
+    Recover,
 }
 
 #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
@@ -27,8 +30,21 @@ enum State {
     WaitBit,
     Error,
     WaitDriverIdle,
+    ReadRestart,
+    Retry,
 }
 
+// A byte level I2C controller, built on the [I2CDriver].  `BeginWrite` and
+// `BeginRead` address a 7 bit target (in `write_data_in`), and `BeginWrite10`
+// and `BeginRead10` address a 10 bit target (in `address_10`).  Begin
+// commands issued before `EndTransmission` send a repeated start.
+//
+// If arbitration is lost to another master while the target address is
+// being sent, the controller waits for the bus to be free, and then sends
+// the address again.  If it is lost later in the transfer, the controller
+// gives up the bus and pulses `arbitration_lost`, and the transfer must be
+// started over.  After an `error` (e.g., a `timeout`), use `Recover` to free
+// up the bus before starting a new transfer.
 #[derive(LogicBlock)]
 pub struct I2CController {
     pub i2c: I2CBusDriver,
@@ -42,6 +58,9 @@ pub struct I2CController {
     pub read_valid: Signal<Out, Bit>,
     pub ack: Signal<Out, Bit>,
     pub nack: Signal<Out, Bit>,
+    pub address_10: Signal<In, Bits<10>>,
+    pub timeout: Signal<Out, Bit>,
+    pub arbitration_lost: Signal<Out, Bit>,
     driver: I2CDriver,
     counter: DFF<Bits<4>>,
     read_data: DFF<Bits<8>>,
@@ -49,6 +68,12 @@ pub struct I2CController {
     state: DFF<State>,
     started: DFF<Bit>,
     last_read: DFF<Bit>,
+    address_byte: DFF<Bits<8>>,
+    address_low: DFF<Bits<8>>,
+    ten_bit: DFF<Bit>,
+    read_10: DFF<Bit>,
+    stage: DFF<Bits<2>>,
+    ten_bit_high: Signal<Local, Bits<8>>,
 }
 
 impl I2CController {
@@ -65,6 +90,9 @@ impl I2CController {
             read_valid: Default::default(),
             ack: Default::default(),
             nack: Default::default(),
+            address_10: Default::default(),
+            timeout: Default::default(),
+            arbitration_lost: Default::default(),
             driver: I2CDriver::new(config),
             counter: Default::default(),
             read_data: Default::default(),
@@ -72,6 +100,12 @@ impl I2CController {
             state: Default::default(),
             started: Default::default(),
             last_read: Default::default(),
+            address_byte: Default::default(),
+            address_low: Default::default(),
+            ten_bit: Default::default(),
+            read_10: Default::default(),
+            stage: Default::default(),
+            ten_bit_high: Default::default(),
         }
     }
 }
@@ -81,7 +115,21 @@ impl Logic for I2CController {
     fn update(&mut self) {
         I2CBusDriver::link(&mut self.i2c, &mut self.driver.i2c);
         clock!(self, clock, driver);
-        dff_setup!(self, clock, counter, read_data, write_data, state, started, last_read);
+        dff_setup!(
+            self,
+            clock,
+            counter,
+            read_data,
+            write_data,
+            state,
+            started,
+            last_read,
+            address_byte,
+            address_low,
+            ten_bit,
+            read_10,
+            stage
+        );
         self.driver.run.next = false;
         self.driver.cmd.next = I2CDriverCmd::Noop;
         // Default values
@@ -91,6 +139,11 @@ impl Logic for I2CController {
         self.read_valid.next = false;
         self.ack.next = false;
         self.nack.next = false;
+        self.timeout.next = self.driver.timeout.val();
+        self.arbitration_lost.next = false;
+        // The first byte of a 10 bit address is 11110 followed by the top two address bits
+        self.ten_bit_high.next =
+            bits::<8>(0xF0) | (bit_cast::<8, 2>(self.address_10.val().get_bits::<2>(8)) << 1);
         match self.state.q.val() {
             State::Idle => {
                 if self.run.val() {
@@ -100,6 +153,9 @@ impl Logic for I2CController {
                             // Only the lower 7 bits are used.
                             // The last bit is set to 0 to indicate a write
                             self.write_data.d.next = self.write_data_in.val() << 1;
+                            self.address_byte.d.next = self.write_data_in.val() << 1;
+                            self.ten_bit.d.next = false;
+                            self.stage.d.next = 1.into();
                             if !self.started.q.val() {
                                 self.driver.cmd.next = I2CDriverCmd::SendStart;
                             } else {
@@ -113,6 +169,9 @@ impl Logic for I2CController {
                         I2CControllerCmd::BeginRead => {
                             // Set the lowest bit to indicate a read
                             self.write_data.d.next = (self.write_data_in.val() << 1) | 1;
+                            self.address_byte.d.next = (self.write_data_in.val() << 1) | 1;
+                            self.ten_bit.d.next = false;
+                            self.stage.d.next = 1.into();
                             if !self.started.q.val() {
                                 self.driver.cmd.next = I2CDriverCmd::SendStart;
                             } else {
@@ -123,6 +182,49 @@ impl Logic for I2CController {
                             self.state.d.next = State::SendBuffer;
                             self.started.d.next = true;
                         }
+                        I2CControllerCmd::BeginWrite10 => {
+                            // A 10 bit address always starts as a write
+                            self.write_data.d.next = self.ten_bit_high.val();
+                            self.address_byte.d.next = self.ten_bit_high.val();
+                            self.address_low.d.next = self.address_10.val().get_bits::<8>(0);
+                            self.ten_bit.d.next = true;
+                            self.read_10.d.next = false;
+                            self.stage.d.next = 1.into();
+                            if !self.started.q.val() {
+                                self.driver.cmd.next = I2CDriverCmd::SendStart;
+                            } else {
+                                self.driver.cmd.next = I2CDriverCmd::Restart;
+                            }
+                            self.driver.run.next = true;
+                            self.counter.d.next = 8.into();
+                            self.state.d.next = State::SendBuffer;
+                            self.started.d.next = true;
+                        }
+                        I2CControllerCmd::BeginRead10 => {
+                            // The read bit is sent after a repeated start, once the
+                            // target has been addressed
+                            self.write_data.d.next = self.ten_bit_high.val();
+                            self.address_byte.d.next = self.ten_bit_high.val();
+                            self.address_low.d.next = self.address_10.val().get_bits::<8>(0);
+                            self.ten_bit.d.next = true;
+                            self.read_10.d.next = true;
+                            self.stage.d.next = 1.into();
+                            if !self.started.q.val() {
+                                self.driver.cmd.next = I2CDriverCmd::SendStart;
+                            } else {
+                                self.driver.cmd.next = I2CDriverCmd::Restart;
+                            }
+                            self.driver.run.next = true;
+                            self.counter.d.next = 8.into();
+                            self.state.d.next = State::SendBuffer;
+                            self.started.d.next = true;
+                        }
+                        I2CControllerCmd::Recover => {
+                            self.driver.cmd.next = I2CDriverCmd::Recover;
+                            self.driver.run.next = true;
+                            self.state.d.next = State::WaitDriverIdle;
+                            self.started.d.next = false;
+                        }
                         I2CControllerCmd::EndTransmission => {
                             self.driver.cmd.next = I2CDriverCmd::SendStop;
                             self.driver.run.next = true;
@@ -198,12 +300,28 @@ impl Logic for I2CController {
             }
             State::WaitAck => {
                 if self.driver.read_valid.val() {
+                    self.state.d.next = State::Idle;
+                    self.stage.d.next = 0.into();
                     if self.driver.read_bit.val() {
                         self.nack.next = true;
+                    } else if self.ten_bit.q.val() & (self.stage.q.val() == 1) {
+                        // Send the rest of the 10 bit address
+                        self.write_data.d.next = self.address_low.q.val();
+                        self.counter.d.next = 8.into();
+                        self.stage.d.next = 2.into();
+                        self.state.d.next = State::SendBuffer;
+                    } else if self.ten_bit.q.val()
+                        & (self.stage.q.val() == 2)
+                        & self.read_10.q.val()
+                    {
+                        // Repeated start, and then the first byte again as a read
+                        self.write_data.d.next = self.address_byte.q.val() | 1;
+                        self.counter.d.next = 8.into();
+                        self.stage.d.next = 3.into();
+                        self.state.d.next = State::ReadRestart;
                     } else {
                         self.ack.next = true;
                     }
-                    self.state.d.next = State::Idle;
                 }
             }
             State::WaitDriverIdle => {
@@ -211,14 +329,48 @@ impl Logic for I2CController {
                     self.state.d.next = State::Idle;
                 }
             }
+            State::ReadRestart => {
+                if !self.driver.busy.val() {
+                    self.driver.cmd.next = I2CDriverCmd::Restart;
+                    self.driver.run.next = true;
+                    self.state.d.next = State::SendBuffer;
+                }
+            }
+            State::Retry => {
+                // Send the address again, once the driver has the bus
+                if !self.driver.busy.val() {
+                    self.driver.cmd.next = I2CDriverCmd::SendStart;
+                    self.driver.run.next = true;
+                    self.write_data.d.next = self.address_byte.q.val();
+                    self.counter.d.next = 8.into();
+                    self.stage.d.next = 1.into();
+                    self.state.d.next = State::SendBuffer;
+                    self.started.d.next = true;
+                }
+            }
             State::Error => {
                 self.error.next = true;
+                if self.run.val() & (self.cmd.val() == I2CControllerCmd::Recover) {
+                    self.driver.cmd.next = I2CDriverCmd::Recover;
+                    self.driver.run.next = true;
+                    self.state.d.next = State::WaitDriverIdle;
+                    self.started.d.next = false;
+                }
             }
             _ => {
                 self.state.d.next = State::Idle;
             }
         }
-        if self.driver.error.val() {
+        if self.driver.arbitration_lost.val() {
+            self.started.d.next = false;
+            if self.stage.q.val().any() {
+                self.state.d.next = State::Retry;
+            } else {
+                self.arbitration_lost.next = true;
+                self.state.d.next = State::Idle;
+            }
+        }
+        if self.driver.error.val() & (self.state.q.val() != State::Error) {
             self.state.d.next = State::Error;
         }
     }
@@ -229,6 +381,7 @@ fn test_i2c_controller_synthesizes() {
     let config = I2CConfig {
         delay_time: Duration::from_nanos(1500),
         clock_speed_hz: 48_000_000,
+        timeout: Duration::from_millis(25),
     };
     let mut uut = I2CController::new(config);
     uut.connect_all();
@@ -261,6 +414,7 @@ impl Default for I2CControllerTest {
         let config = I2CConfig {
             delay_time: Duration::from_micros(5),
             clock_speed_hz: 1_000_000,
+            timeout: Duration::from_millis(25),
         };
         Self {
             clock: Default::default(),
@@ -299,6 +453,7 @@ fn test_i2c_controller_operation() {
     uut.controller.cmd.connect();
     uut.controller.run.connect();
     uut.controller.write_data_in.connect();
+    uut.controller.address_10.connect();
     uut.connect_all();
     let vlog = generate_verilog(&uut);
     //println!("{}", vlog);
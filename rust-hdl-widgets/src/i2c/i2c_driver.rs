@@ -6,6 +6,9 @@ use std::time::Duration;
 pub struct I2CConfig {
     pub delay_time: Duration,
     pub clock_speed_hz: u64,
+    // How long to wait for a target that stretches the clock (or for the
+    // bus to become free) before giving up with a timeout
+    pub timeout: Duration,
 }
 
 #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
@@ -17,11 +20,13 @@ pub enum I2CDriverCmd {
     SendStop,
     GetBit,
     Restart,
+    Recover,
 }
 
 #[derive(Copy, Clone, PartialEq, Debug, LogicState)]
 enum State {
     Idle,
+    WaitBusFree,
     Start,
     Send,
     Error,
@@ -33,9 +38,27 @@ enum State {
     RestartDelay,
     Receive,
     ReceiveClock,
+    Stretch,
+    RecoverCheck,
+    RecoverLow,
+    RecoverHigh,
+    RecoverStop,
 }
 
-// Implement the bit-bang I2C interface as reported on Wikipedia
+// Implement the bit-bang I2C interface as reported on Wikipedia.
+//
+// Each time SCL is released, the driver waits for it to actually go high,
+// so that targets can stretch the clock.  If a target holds SCL low (or
+// the bus does not become free) for longer than the timeout, the driver
+// stops with an `error` and `timeout` set.  The `Recover` command (which
+// is also accepted in the error state) clocks SCL up to nine times until
+// a stuck target releases SDA, and then sends a stop.
+//
+// For multi-master buses, the driver tracks start and stop conditions on
+// the bus, and only sends a start once the bus is free.  If SDA is low
+// while the driver releases it (i.e., another master is sending a 0), then
+// arbitration is lost.  The driver releases the bus, pulses
+// `arbitration_lost` and returns to idle.
 #[derive(LogicBlock)]
 pub struct I2CDriver {
     pub i2c: I2CBusDriver,
@@ -44,30 +67,46 @@ pub struct I2CDriver {
     pub run: Signal<In, Bit>,
     pub busy: Signal<Out, Bit>,
     pub error: Signal<Out, Bit>,
+    pub timeout: Signal<Out, Bit>,
+    pub arbitration_lost: Signal<Out, Bit>,
+    pub bus_busy: Signal<Out, Bit>,
     pub read_bit: Signal<Out, Bit>,
     pub read_valid: Signal<Out, Bit>,
     state: DFF<State>,
+    resume: DFF<State>,
     delay: Shot<32>,
+    timer: Shot<32>,
     sda_is_high: Signal<Local, Bit>,
     scl_is_high: Signal<Local, Bit>,
     sda_flop: DFF<Bit>,
     scl_flop: DFF<Bit>,
+    sda_last: DFF<Bit>,
+    busy_flop: DFF<Bit>,
+    timed_out: DFF<Bit>,
+    pulses: DFF<Bits<4>>,
     clear_sda: Signal<Local, Bit>,
     clear_scl: Signal<Local, Bit>,
     set_sda: Signal<Local, Bit>,
     set_scl: Signal<Local, Bit>,
+    lost: Signal<Local, Bit>,
 }
 
 impl Logic for I2CDriver {
     #[hdl_gen]
     fn update(&mut self) {
-        dff_setup!(self, clock, state, sda_flop, scl_flop);
-        clock!(self, clock, delay);
+        dff_setup!(
+            self, clock, state, resume, sda_flop, scl_flop, sda_last, busy_flop, timed_out, pulses
+        );
+        clock!(self, clock, delay, timer);
         // Latch avoidance and default conditions
         self.delay.trigger.next = false;
+        self.timer.trigger.next = false;
         self.i2c.sda.drive_low.next = self.sda_flop.q.val();
         self.i2c.scl.drive_low.next = self.scl_flop.q.val();
         self.error.next = false;
+        self.timeout.next = self.timed_out.q.val();
+        self.arbitration_lost.next = false;
+        self.lost.next = false;
         // Helpers to make the code more readable
         self.sda_is_high.next = self.i2c.sda.line_state.val();
         self.scl_is_high.next = self.i2c.scl.line_state.val();
@@ -78,23 +117,23 @@ impl Logic for I2CDriver {
         self.read_bit.next = self.sda_is_high.val();
         self.read_valid.next = false;
         self.busy.next = (self.state.q.val() != State::Idle) & (self.state.q.val() != State::Error);
+        // Watch for start and stop conditions (from any master) on the bus
+        self.sda_last.d.next = self.sda_is_high.val();
+        if self.scl_is_high.val() & self.sda_last.q.val() & !self.sda_is_high.val() {
+            self.busy_flop.d.next = true;
+        }
+        if self.scl_is_high.val() & !self.sda_last.q.val() & self.sda_is_high.val() {
+            self.busy_flop.d.next = false;
+        }
+        self.bus_busy.next = self.busy_flop.q.val();
         match self.state.q.val() {
             State::Idle => {
                 if self.run.val() {
                     match self.cmd.val() {
                         I2CDriverCmd::SendStart => {
-                            // if (read_SDA() == 0) {
-                            //     arbitration_lost();
-                            // }
-                            if !self.sda_is_high.val() {
-                                self.state.d.next = State::Error
-                            } else {
-                                //  clear_SDA();
-                                //  I2C_delay();
-                                self.clear_sda.next = true;
-                                self.delay.trigger.next = true;
-                                self.state.d.next = State::Start
-                            }
+                            // Wait for the bus to be free
+                            self.timer.trigger.next = true;
+                            self.state.d.next = State::WaitBusFree;
                         }
                         I2CDriverCmd::SendTrue => {
                             // set_SDA()
@@ -131,11 +170,28 @@ impl Logic for I2CDriver {
                             self.delay.trigger.next = true;
                             self.state.d.next = State::Restart
                         }
+                        I2CDriverCmd::Recover => {
+                            self.set_sda.next = true;
+                            self.pulses.d.next = 0.into();
+                            self.state.d.next = State::RecoverCheck;
+                        }
                         I2CDriverCmd::Noop => {}
                         _ => {}
                     }
                 }
             }
+            State::WaitBusFree => {
+                if !self.busy_flop.q.val() & self.sda_is_high.val() & self.scl_is_high.val() {
+                    //  clear_SDA();
+                    //  I2C_delay();
+                    self.clear_sda.next = true;
+                    self.delay.trigger.next = true;
+                    self.state.d.next = State::Start
+                } else if self.timer.fired.val() {
+                    self.timed_out.d.next = true;
+                    self.state.d.next = State::Error;
+                }
+            }
             State::Start => {
                 if self.delay.fired.val() {
                     //  clear_SCL();
@@ -147,33 +203,57 @@ impl Logic for I2CDriver {
                 if self.delay.fired.val() {
                     // set_SCL()
                     self.set_scl.next = true;
-                    self.delay.trigger.next = true;
-                    self.state.d.next = State::StopSetup;
+                    self.timer.trigger.next = true;
+                    self.resume.d.next = State::StopSetup;
+                    self.state.d.next = State::Stretch;
                 }
             }
             State::Error => {
                 self.error.next = true;
+                if self.run.val() & (self.cmd.val() == I2CDriverCmd::Recover) {
+                    self.timed_out.d.next = false;
+                    self.set_sda.next = true;
+                    self.set_scl.next = true;
+                    self.pulses.d.next = 0.into();
+                    self.state.d.next = State::RecoverCheck;
+                }
             }
             State::Send => {
                 if self.delay.fired.val() {
                     // set_SCL()
                     self.set_scl.next = true;
-                    self.state.d.next = State::Clock;
-                    // I2C_delay()
-                    self.delay.trigger.next = true;
+                    self.timer.trigger.next = true;
+                    self.resume.d.next = State::Clock;
+                    self.state.d.next = State::Stretch;
                 }
             }
             State::Receive => {
                 if self.delay.fired.val() {
                     // set SCL()
                     self.set_scl.next = true;
-                    self.state.d.next = State::ReceiveClock;
-                    // I2C_delay()
+                    self.timer.trigger.next = true;
+                    self.resume.d.next = State::ReceiveClock;
+                    self.state.d.next = State::Stretch;
+                }
+            }
+            State::Stretch => {
+                // while (read_SCL() == 0) {
+                //     // clock stretching
+                // }
+                // I2C_delay()
+                if self.scl_is_high.val() {
                     self.delay.trigger.next = true;
+                    self.state.d.next = self.resume.q.val();
+                } else if self.timer.fired.val() {
+                    self.timed_out.d.next = true;
+                    self.state.d.next = State::Error;
                 }
             }
             State::Clock => {
-                if self.delay.fired.val() {
+                // Another master is driving SDA low while we sent a 1
+                if !self.sda_flop.q.val() & !self.sda_is_high.val() {
+                    self.lost.next = true;
+                } else if self.delay.fired.val() {
                     self.clear_scl.next = true;
                     self.state.d.next = State::Idle;
                 }
@@ -198,12 +278,15 @@ impl Logic for I2CDriver {
                 //   set_SCL();
                 if self.delay.fired.val() {
                     self.set_scl.next = true;
-                    self.state.d.next = State::RestartDelay;
-                    self.delay.trigger.next = true;
+                    self.timer.trigger.next = true;
+                    self.resume.d.next = State::RestartDelay;
+                    self.state.d.next = State::Stretch;
                 }
             }
             State::RestartDelay => {
-                if self.delay.fired.val() {
+                if !self.sda_is_high.val() {
+                    self.lost.next = true;
+                } else if self.delay.fired.val() {
                     self.clear_sda.next = true;
                     self.delay.trigger.next = true;
                     self.state.d.next = State::Start
@@ -215,12 +298,43 @@ impl Logic for I2CDriver {
                     //    arbitration_lost();
                     // }
                     if !self.i2c.sda.line_state.val() {
-                        self.state.d.next = State::Error;
+                        self.lost.next = true;
                     } else {
                         self.state.d.next = State::Idle;
                     }
                 }
             }
+            State::RecoverCheck => {
+                // Clock the bus until the target lets go of SDA (at most 9 times)
+                self.clear_scl.next = true;
+                self.delay.trigger.next = true;
+                if self.sda_is_high.val() | (self.pulses.q.val() == 9) {
+                    self.state.d.next = State::RecoverStop;
+                } else {
+                    self.state.d.next = State::RecoverLow;
+                }
+            }
+            State::RecoverLow => {
+                if self.delay.fired.val() {
+                    self.set_scl.next = true;
+                    self.timer.trigger.next = true;
+                    self.resume.d.next = State::RecoverHigh;
+                    self.state.d.next = State::Stretch;
+                }
+            }
+            State::RecoverHigh => {
+                if self.delay.fired.val() {
+                    self.pulses.d.next = self.pulses.q.val() + 1;
+                    self.state.d.next = State::RecoverCheck;
+                }
+            }
+            State::RecoverStop => {
+                if self.delay.fired.val() {
+                    self.clear_sda.next = true;
+                    self.delay.trigger.next = true;
+                    self.state.d.next = State::Stop;
+                }
+            }
             _ => {
                 self.state.d.next = State::Idle;
             }
@@ -237,6 +351,13 @@ impl Logic for I2CDriver {
         if self.clear_sda.val() {
             self.sda_flop.d.next = true;
         }
+        // On a lost arbitration, let go of the bus
+        if self.lost.val() {
+            self.arbitration_lost.next = true;
+            self.sda_flop.d.next = false;
+            self.scl_flop.d.next = false;
+            self.state.d.next = State::Idle;
+        }
         if self.run.val() & self.busy.val() {
             self.state.d.next = State::Error;
         }
@@ -252,16 +373,26 @@ impl I2CDriver {
             run: Default::default(),
             busy: Default::default(),
             delay: Shot::new(config.clock_speed_hz, config.delay_time),
+            timer: Shot::new(config.clock_speed_hz, config.timeout),
             sda_is_high: Default::default(),
             state: Default::default(),
+            resume: Default::default(),
             error: Default::default(),
+            timeout: Default::default(),
+            arbitration_lost: Default::default(),
+            bus_busy: Default::default(),
             scl_is_high: Default::default(),
             sda_flop: Default::default(),
             scl_flop: Default::default(),
+            sda_last: Default::default(),
+            busy_flop: Default::default(),
+            timed_out: Default::default(),
+            pulses: Default::default(),
             clear_sda: Default::default(),
             clear_scl: Default::default(),
             set_sda: Default::default(),
             set_scl: Default::default(),
+            lost: Default::default(),
             read_bit: Default::default(),
             read_valid: Default::default(),
         }
@@ -273,6 +404,7 @@ fn test_i2c_driver_synthesizes() {
     let config = I2CConfig {
         delay_time: Duration::from_nanos(1500),
         clock_speed_hz: 48_000_000,
+        timeout: Duration::from_millis(25),
     };
     let mut uut = I2CDriver::new(config);
     uut.connect_all();
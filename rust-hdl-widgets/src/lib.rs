@@ -1,13 +1,17 @@
 pub mod accum;
 pub mod auto_reset;
+pub mod crc;
+pub mod ddr_register;
 pub mod delay_line;
 pub mod dff;
 pub mod dff_with_init;
 pub mod edge_detector;
 pub mod edge_ff;
+pub mod ethernet;
 pub mod fifo;
 pub mod i2c;
 pub mod mac_fir;
+pub mod neg_edge_dff;
 pub mod open_drain;
 pub mod png;
 pub mod prelude;
@@ -15,7 +19,9 @@ pub mod pulser;
 pub mod pwm;
 pub mod ramrom;
 pub mod registered_edge_tristate;
+pub mod reset_dff;
 pub mod sdram;
+pub mod sequencer;
 pub mod shot;
 pub mod spi;
 pub mod strobe;
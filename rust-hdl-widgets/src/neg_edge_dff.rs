@@ -0,0 +1,69 @@
+use rust_hdl_core::prelude::*;
+
+// A register clocked on the falling edge of the clock.  Otherwise
+// identical to a [DFF].
+//
+// [DFF]: crate::dff::DFF
+#[derive(Clone, Debug, LogicBlock)]
+pub struct NegEdgeDFF<T: Synth> {
+    pub d: Signal<In, T>,
+    pub q: Signal<Out, T>,
+    pub clock: Signal<In, Clock>,
+}
+
+impl<T: Synth> NegEdgeDFF<T> {
+    pub fn new(init: T) -> Self {
+        Self {
+            d: Signal::default(),
+            q: Signal::new_with_default(init),
+            clock: Signal::default(),
+        }
+    }
+}
+
+impl<T: Synth> Default for NegEdgeDFF<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Synth> Logic for NegEdgeDFF<T> {
+    fn update(&mut self) {
+        if self.clock.neg_edge() {
+            self.q.next = self.d.val()
+        }
+    }
+    fn connect(&mut self) {
+        self.q.connect();
+    }
+    fn hdl(&self) -> Verilog {
+        Verilog::Custom(format!(
+            "\
+initial begin
+   q = {:x};
+end
+
+always @(negedge clock) begin
+   q <= d;
+end
+      ",
+            self.q.verilog()
+        ))
+    }
+    fn timing(&self) -> Vec<TimingInfo> {
+        vec![TimingInfo {
+            name: "neg_edge_dff".into(),
+            clock: "clock".into(),
+            inputs: vec!["d".into()],
+            outputs: vec!["q".into()],
+            ..Default::default()
+        }]
+    }
+}
+
+#[test]
+fn test_neg_edge_dff_synthesizes() {
+    let mut uut = NegEdgeDFF::<Bits<8>>::new(0x3C.into());
+    uut.connect_all();
+    yosys_validate("neg_edge_dff", &generate_verilog(&uut)).unwrap();
+}
@@ -1,8 +1,10 @@
-use crate::{dff_setup, prelude::DFFWithInit};
+use crate::{dff::DFF, dff_setup, dff_with_init::DFFWithInit};
 use rust_hdl_core::prelude::*;
 
 // Adopted from Alchitry.com Lucid module `pn_gen`
-// This version does not provide seed setting.  It generates a fixed sequence.
+// A 32 bit xorshift generator.  The default seed generates the same
+// sequence as `pn_gen`, or use `new` to provide a (non-zero) seed.
+// For the standard PRBS sequences, see `PRBSGenerator` below.
 #[derive(LogicBlock)]
 pub struct LFSRSimple {
     pub clock: Signal<In, Clock>,
@@ -17,21 +19,28 @@ pub struct LFSRSimple {
 
 const SEED: u128 = 0x843233523a613966423b622562592c62;
 
-impl Default for LFSRSimple {
-    fn default() -> Self {
+impl LFSRSimple {
+    pub fn new(seed: u128) -> Self {
+        assert_ne!(seed, 0, "The LFSR seed cannot be zero");
         Self {
             clock: Default::default(),
             strobe: Default::default(),
             num: Default::default(),
             t: Default::default(),
-            x: DFFWithInit::new((SEED & 0xFFFF_FFFF_u128).to_bits()),
-            y: DFFWithInit::new(((SEED >> 32) & 0xFFFF_FFFF_u128).to_bits()),
-            z: DFFWithInit::new(((SEED >> 64) & 0xFFFF_FFFF_u128).to_bits()),
-            w: DFFWithInit::new(((SEED >> 96) & 0xFFFF_FFFF_u128).to_bits()),
+            x: DFFWithInit::new((seed & 0xFFFF_FFFF_u128).to_bits()),
+            y: DFFWithInit::new(((seed >> 32) & 0xFFFF_FFFF_u128).to_bits()),
+            z: DFFWithInit::new(((seed >> 64) & 0xFFFF_FFFF_u128).to_bits()),
+            w: DFFWithInit::new(((seed >> 96) & 0xFFFF_FFFF_u128).to_bits()),
         }
     }
 }
 
+impl Default for LFSRSimple {
+    fn default() -> Self {
+        Self::new(SEED)
+    }
+}
+
 impl Logic for LFSRSimple {
     #[hdl_gen]
     fn update(&mut self) {
@@ -48,9 +57,294 @@ impl Logic for LFSRSimple {
     }
 }
 
+// The standard (ITU-T O.150) pseudo-random binary sequences.  Each
+// is generated by a Fibonacci LFSR with the polynomial x^order + x^tap + 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PRBSPolynomial {
+    PRBS7,
+    PRBS15,
+    PRBS23,
+    PRBS31,
+}
+
+impl PRBSPolynomial {
+    pub fn order(&self) -> usize {
+        match self {
+            PRBSPolynomial::PRBS7 => 7,
+            PRBSPolynomial::PRBS15 => 15,
+            PRBSPolynomial::PRBS23 => 23,
+            PRBSPolynomial::PRBS31 => 31,
+        }
+    }
+    pub fn tap(&self) -> usize {
+        match self {
+            PRBSPolynomial::PRBS7 => 6,
+            PRBSPolynomial::PRBS15 => 14,
+            PRBSPolynomial::PRBS23 => 18,
+            PRBSPolynomial::PRBS31 => 28,
+        }
+    }
+    pub fn period(&self) -> u64 {
+        (1 << self.order()) - 1
+    }
+    fn mask(&self) -> u32 {
+        ((1_u64 << self.order()) - 1) as u32
+    }
+}
+
+// Software model of the PRBS generator.  Words are filled with the
+// earliest bit of the sequence in the most significant position, which
+// matches the `PRBSGenerator` and `PRBSChecker` widgets.
+#[derive(Clone, Debug)]
+pub struct PRBSModel {
+    poly: PRBSPolynomial,
+    state: u32,
+}
+
+impl PRBSModel {
+    pub fn new(poly: PRBSPolynomial, seed: u32) -> Self {
+        let state = seed & poly.mask();
+        assert_ne!(state, 0, "The PRBS seed cannot be zero");
+        Self { poly, state }
+    }
+    pub fn next_bit(&mut self) -> bool {
+        let bit = ((self.state >> (self.poly.order() - 1)) ^ (self.state >> (self.poly.tap() - 1)))
+            & 1
+            != 0;
+        self.state = ((self.state << 1) | (bit as u32)) & self.poly.mask();
+        bit
+    }
+    pub fn next_word(&mut self, n: usize) -> u64 {
+        (0..n).fold(0, |acc, _| (acc << 1) | (self.next_bit() as u64))
+    }
+}
+
+// Generates `N` bits of the sequence per clock.  Each `strobe`
+// advances the sequence, and places the next `N` bits on `data`.
+#[derive(LogicBlock)]
+pub struct PRBSGenerator<const N: usize> {
+    pub clock: Signal<In, Clock>,
+    pub strobe: Signal<In, Bit>,
+    pub data: Signal<Out, Bits<N>>,
+    state: DFFWithInit<Bits<31>>,
+    word: DFF<Bits<N>>,
+    mask: Constant<Bits<31>>,
+    top: Constant<Bits<31>>,
+    tap: Constant<Bits<31>>,
+    lfsr: Signal<Local, Bits<31>>,
+    bits: Signal<Local, Bits<N>>,
+    feedback: Signal<Local, Bit>,
+}
+
+impl<const N: usize> PRBSGenerator<N> {
+    pub fn new(poly: PRBSPolynomial, seed: u32) -> Self {
+        let seed = PRBSModel::new(poly, seed).state;
+        Self {
+            clock: Default::default(),
+            strobe: Default::default(),
+            data: Default::default(),
+            state: DFFWithInit::new(seed.to_bits()),
+            word: Default::default(),
+            mask: Constant::new(poly.mask().to_bits()),
+            top: Constant::new((1_u32 << (poly.order() - 1)).to_bits()),
+            tap: Constant::new((1_u32 << (poly.tap() - 1)).to_bits()),
+            lfsr: Default::default(),
+            bits: Default::default(),
+            feedback: Default::default(),
+        }
+    }
+}
+
+impl<const N: usize> Logic for PRBSGenerator<N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, state, word);
+        self.lfsr.next = self.state.q.val();
+        self.bits.next = 0.into();
+        for _i in 0..N {
+            self.feedback.next =
+                (self.lfsr.val() & self.top.val()).any() ^ (self.lfsr.val() & self.tap.val()).any();
+            self.lfsr.next = (self.lfsr.val() << 1) & self.mask.val();
+            self.bits.next = self.bits.val() << 1;
+            if self.feedback.val() {
+                self.lfsr.next = self.lfsr.val() | 1;
+                self.bits.next = self.bits.val() | 1;
+            }
+        }
+        if self.strobe.val() {
+            self.state.d.next = self.lfsr.val();
+            self.word.d.next = self.bits.val();
+        }
+        self.data.next = self.word.q.val();
+    }
+}
+
+// Checks `N` bits of a received sequence per `strobe`.  While out of
+// lock, the checker loads its LFSR from the received bits, and declares
+// lock after enough error free words (at least 64 bits).  Once locked,
+// the LFSR runs free, so that each bit error is counted exactly once
+// in `errors`.  Lock is lost after 8 consecutive words with errors.
+// `error` flags a word with errors while locked, and `clear` resets
+// the error count.
+#[derive(LogicBlock)]
+pub struct PRBSChecker<const N: usize> {
+    pub clock: Signal<In, Clock>,
+    pub strobe: Signal<In, Bit>,
+    pub data: Signal<In, Bits<N>>,
+    pub clear: Signal<In, Bit>,
+    pub locked: Signal<Out, Bit>,
+    pub error: Signal<Out, Bit>,
+    pub errors: Signal<Out, Bits<32>>,
+    state: DFF<Bits<31>>,
+    lock: DFF<Bit>,
+    good_words: DFF<Bits<8>>,
+    bad_words: DFF<Bits<4>>,
+    error_count: DFF<Bits<32>>,
+    word_error: DFF<Bit>,
+    mask: Constant<Bits<31>>,
+    top: Constant<Bits<31>>,
+    tap: Constant<Bits<31>>,
+    data_msb: Constant<Bits<N>>,
+    lock_words: Constant<Bits<8>>,
+    loss_words: Constant<Bits<4>>,
+    lfsr: Signal<Local, Bits<31>>,
+    bits: Signal<Local, Bits<N>>,
+    expected: Signal<Local, Bit>,
+    received: Signal<Local, Bit>,
+    word_errors: Signal<Local, Bits<32>>,
+}
+
+impl<const N: usize> PRBSChecker<N> {
+    pub fn new(poly: PRBSPolynomial) -> Self {
+        Self {
+            clock: Default::default(),
+            strobe: Default::default(),
+            data: Default::default(),
+            clear: Default::default(),
+            locked: Default::default(),
+            error: Default::default(),
+            errors: Default::default(),
+            state: Default::default(),
+            lock: Default::default(),
+            good_words: Default::default(),
+            bad_words: Default::default(),
+            error_count: Default::default(),
+            word_error: Default::default(),
+            mask: Constant::new(poly.mask().to_bits()),
+            top: Constant::new((1_u32 << (poly.order() - 1)).to_bits()),
+            tap: Constant::new((1_u32 << (poly.tap() - 1)).to_bits()),
+            data_msb: Constant::new(Bits::<N>::default().replace_bit(N - 1, true)),
+            lock_words: Constant::new(64_usize.div_ceil(N).min(255).to_bits()),
+            loss_words: Constant::new(8.into()),
+            lfsr: Default::default(),
+            bits: Default::default(),
+            expected: Default::default(),
+            received: Default::default(),
+            word_errors: Default::default(),
+        }
+    }
+}
+
+impl<const N: usize> Logic for PRBSChecker<N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(
+            self,
+            clock,
+            state,
+            lock,
+            good_words,
+            bad_words,
+            error_count,
+            word_error
+        );
+        self.lfsr.next = self.state.q.val();
+        self.bits.next = self.data.val();
+        self.word_errors.next = 0.into();
+        for _i in 0..N {
+            self.expected.next =
+                (self.lfsr.val() & self.top.val()).any() ^ (self.lfsr.val() & self.tap.val()).any();
+            self.received.next = (self.bits.val() & self.data_msb.val()).any();
+            self.bits.next = self.bits.val() << 1;
+            if self.expected.val() ^ self.received.val() {
+                self.word_errors.next = self.word_errors.val() + 1;
+            }
+            self.lfsr.next = (self.lfsr.val() << 1) & self.mask.val();
+            if self.lock.q.val() {
+                if self.expected.val() {
+                    self.lfsr.next = self.lfsr.val() | 1;
+                }
+            } else if self.received.val() {
+                self.lfsr.next = self.lfsr.val() | 1;
+            }
+        }
+        if self.strobe.val() {
+            self.state.d.next = self.lfsr.val();
+            self.word_error.d.next = self.lock.q.val() & self.word_errors.val().any();
+            if self.lock.q.val() {
+                self.error_count.d.next = self.error_count.q.val() + self.word_errors.val();
+                if self.word_errors.val().any() {
+                    self.bad_words.d.next = self.bad_words.q.val() + 1;
+                    if self.bad_words.q.val() + 1 == self.loss_words.val() {
+                        self.lock.d.next = false;
+                        self.good_words.d.next = 0.into();
+                    }
+                } else {
+                    self.bad_words.d.next = 0.into();
+                }
+            } else if self.word_errors.val().any() {
+                self.good_words.d.next = 0.into();
+            } else {
+                self.good_words.d.next = self.good_words.q.val() + 1;
+                if self.good_words.q.val() + 1 == self.lock_words.val() {
+                    self.lock.d.next = true;
+                    self.bad_words.d.next = 0.into();
+                }
+            }
+        }
+        if self.clear.val() {
+            self.error_count.d.next = 0.into();
+        }
+        self.locked.next = self.lock.q.val();
+        self.error.next = self.word_error.q.val();
+        self.errors.next = self.error_count.q.val();
+    }
+}
+
 #[test]
 fn test_lfsr_simple_synthesizes() {
     let mut uut = LFSRSimple::default();
     uut.connect_all();
     yosys_validate("lfsr", &generate_verilog(&uut)).unwrap();
 }
+
+#[test]
+fn test_prbs_model_periods() {
+    for poly in [PRBSPolynomial::PRBS7, PRBSPolynomial::PRBS15] {
+        let mut model = PRBSModel::new(poly, !0);
+        let start = model.state;
+        let mut period = 0;
+        loop {
+            model.next_bit();
+            period += 1;
+            if model.state == start {
+                break;
+            }
+        }
+        assert_eq!(period, poly.period());
+    }
+}
+
+#[test]
+fn test_prbs_generator_synthesizes() {
+    let mut uut = PRBSGenerator::<8>::new(PRBSPolynomial::PRBS31, 1);
+    uut.connect_all();
+    yosys_validate("prbs_gen", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_prbs_checker_synthesizes() {
+    let mut uut = PRBSChecker::<8>::new(PRBSPolynomial::PRBS31);
+    uut.connect_all();
+    yosys_validate("prbs_check", &generate_verilog(&uut)).unwrap();
+}
@@ -1,4 +1,6 @@
 pub use crate::auto_reset::AutoReset;
+pub use crate::crc::{CRCSpec, CRC, CRC16_MODBUS, CRC16_XMODEM, CRC32, CRC32C, CRC8_CCITT};
+pub use crate::ddr_register::{DDRInputRegister, DDROutputRegister};
 pub use crate::declare_async_fifo;
 pub use crate::declare_expanding_fifo;
 pub use crate::declare_narrowing_fifo;
@@ -8,6 +10,13 @@ pub use crate::dff::DFF;
 pub use crate::dff_setup;
 pub use crate::dff_with_init::DFFWithInit;
 pub use crate::edge_detector::EdgeDetector;
+pub use crate::ethernet::crc32::{ethernet_crc32, EthernetCRC32};
+pub use crate::ethernet::gmii::{GMIIWiresMAC, GMIIWiresPHY};
+pub use crate::ethernet::mac::{GMIIEthernetMAC, RMIIEthernetMAC};
+pub use crate::ethernet::rmii::{RMIIWiresMAC, RMIIWiresPHY};
+pub use crate::ethernet::{
+    EthernetRxSink, EthernetRxSource, EthernetSpeed, EthernetTxSink, EthernetTxSource,
+};
 pub use crate::fifo::async_fifo::AsynchronousFIFO;
 pub use crate::fifo::cross_fifo::CrossNarrowFIFO;
 pub use crate::fifo::cross_fifo::CrossWidenFIFO;
@@ -22,26 +31,43 @@ pub use crate::i2c::i2c_driver::I2CConfig;
 pub use crate::i2c::i2c_target::I2CTarget;
 pub use crate::i2c::i2c_test_target::*;
 pub use crate::mac_fir::MultiplyAccumulateSymmetricFiniteImpulseResponseFilter;
+pub use crate::neg_edge_dff::NegEdgeDFF;
 pub use crate::open_drain::*;
-pub use crate::png::lfsr::LFSRSimple;
+pub use crate::png::lfsr::{LFSRSimple, PRBSChecker, PRBSGenerator, PRBSModel, PRBSPolynomial};
 pub use crate::pulser::Pulser;
 pub use crate::pwm::PulseWidthModulator;
+pub use crate::ramrom::dual_port_ram::{ReadPolicy, SimpleDualPortRAM, TrueDualPortRAM};
+pub use crate::ramrom::mem_init::{load_memory_file, parse_hex_words, MemInitError, MemoryImage};
 pub use crate::ramrom::ram::RAM;
 pub use crate::ramrom::rom::ROM;
 pub use crate::ramrom::sync_rom::SyncROM;
+pub use crate::reset_dff::{ResetDFF, ResetKind};
 pub use crate::sdram::basic_controller::SDRAMBaseController;
 pub use crate::sdram::buffer::SDRAMOnChipBuffer;
 pub use crate::sdram::burst_controller::SDRAMBurstController;
 pub use crate::sdram::cmd::SDRAMCommand;
+pub use crate::sdram::ddr3_controller::DDR3Controller;
+pub use crate::sdram::ddr3_phy::{DDR3PHYDriver, DDR3PHY, DDR3_PHY_READ_LATENCY};
+pub use crate::sdram::ddr3_timings::DDR3Timings;
 pub use crate::sdram::fifo_sdram::SDRAMFIFOController;
 pub use crate::sdram::timings::MemoryTimings;
 pub use crate::sdram::OutputBuffer;
 pub use crate::sdram::SDRAMDriver;
+pub use crate::sdram::{DDR3Device, DDR3Driver};
+pub use crate::sequencer::command_sequencer::CommandSequencer;
+pub use crate::sequencer::program::{
+    SequencerError, SequencerLabel, SequencerOp, SequencerProgram,
+};
 pub use crate::shot::Shot;
+pub use crate::spi::flash::{SPIFlashCmd, SPIFlashController};
 pub use crate::spi::master::SPIWiresSlave;
 pub use crate::spi::master::{SPIConfig, SPIMaster, SPIWiresMaster};
 pub use crate::spi::master_dynamic_mode::{SPIConfigDynamicMode, SPIMasterDynamicMode};
 pub use crate::spi::mux::{MuxMasters, MuxSlaves};
+pub use crate::spi::qspi_master::{
+    QSPIMaster, QSPISingleLane, QSPIWidth, QSPIWiresMaster, QSPIWiresSlave,
+};
+pub use crate::spi::qspi_slave::QSPISlave;
 pub use crate::spi::slave::SPISlave;
 pub use crate::strobe::Strobe;
 pub use crate::synchronizer::{BitSynchronizer, SyncReceiver, SyncSender, VectorSynchronizer};
@@ -0,0 +1,543 @@
+use rust_hdl_core::prelude::*;
+use rust_hdl_core::timing::TimingInfo;
+use std::collections::BTreeMap;
+
+// What the read data of a port shows on a cycle in which the same
+// port writes.  `ReadFirst` returns the old contents of the address,
+// `WriteFirst` returns the newly written data, and `NoChange` holds
+// the previous read data.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ReadPolicy {
+    #[default]
+    ReadFirst,
+    WriteFirst,
+    NoChange,
+}
+
+// Writes the byte lanes of `data` selected by `enable` into `word`.
+// Each of the `B` lanes is `W/B` bits wide.
+fn merge_lanes<const W: usize, const B: usize>(
+    word: Bits<W>,
+    data: Bits<W>,
+    enable: Bits<B>,
+) -> Bits<W> {
+    let lane = W / B;
+    let mut word = word;
+    for k in 0..B {
+        if enable.get_bit(k) {
+            for i in k * lane..(k + 1) * lane {
+                word = word.replace_bit(i, data.get_bit(i));
+            }
+        }
+    }
+    word
+}
+
+// The memory is split into one array per byte lane, so that each
+// lane write is a whole word write, which the block RAM inference
+// in the synthesis tools handles reliably.
+fn lane_memories<const W: usize, const N: usize, const B: usize>() -> String {
+    (0..B)
+        .map(|k| format!("reg[{}:0] mem_{}[{}:0];\n", W / B - 1, k, (1 << N) - 1))
+        .collect()
+}
+
+// Each lane is initialized separately, from the matching bits of `values`.
+fn lane_memory_init<const W: usize, const N: usize, const B: usize>(
+    values: &BTreeMap<Bits<N>, Bits<W>>,
+) -> Vec<MemoryInit> {
+    if values.is_empty() {
+        return vec![];
+    }
+    let lane = W / B;
+    (0..B)
+        .map(|k| {
+            let lane_values: BTreeMap<Bits<N>, Bits<W>> = values
+                .iter()
+                .map(|(address, value)| (*address, value.get_bits::<W>(k * lane)))
+                .collect();
+            MemoryInit::with_width(&format!("mem_{}", k), &lane_values, lane)
+        })
+        .collect()
+}
+
+// The lanes of `mem_k[address]` concatenated into a full word.
+fn lane_read<const B: usize>(address: &str) -> String {
+    format!(
+        "{{{}}}",
+        (0..B)
+            .rev()
+            .map(|k| format!("mem_{}[{}]", k, address))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn lane_range<const W: usize, const B: usize>(k: usize) -> String {
+    let lane = W / B;
+    format!("{}:{}", (k + 1) * lane - 1, k * lane)
+}
+
+// Verilog for one port of a true dual port RAM.  The read data is
+// written to `q`.
+fn port_verilog<const W: usize, const B: usize>(port: &str, q: &str, policy: ReadPolicy) -> String {
+    let write = |k: usize| {
+        format!(
+            "      if ({port}_write_enable[{k}]) mem_{k}[{port}_address] <= {port}_write_data[{range}];\n",
+            port = port,
+            k = k,
+            range = lane_range::<W, B>(k)
+        )
+    };
+    let mut body = String::new();
+    match policy {
+        ReadPolicy::ReadFirst => {
+            for k in 0..B {
+                body += &write(k);
+            }
+            body += &format!(
+                "      {} <= {};\n",
+                q,
+                lane_read::<B>(&format!("{}_address", port))
+            );
+        }
+        ReadPolicy::WriteFirst => {
+            for k in 0..B {
+                body += &format!(
+                    "      if ({port}_write_enable[{k}]) begin
+         mem_{k}[{port}_address] <= {port}_write_data[{range}];
+         {q}[{range}] <= {port}_write_data[{range}];
+      end else begin
+         {q}[{range}] <= mem_{k}[{port}_address];
+      end
+",
+                    port = port,
+                    k = k,
+                    q = q,
+                    range = lane_range::<W, B>(k)
+                );
+            }
+        }
+        ReadPolicy::NoChange => {
+            body += &format!("      if ({}_write_enable != 0) begin\n", port);
+            for k in 0..B {
+                body += &write(k);
+            }
+            body += &format!(
+                "      end else begin\n         {} <= {};\n      end\n",
+                q,
+                lane_read::<B>(&format!("{}_address", port))
+            );
+        }
+    }
+    format!(
+        "\
+always @(posedge {port}_clock) begin
+   if ({port}_enable) begin
+{body}   end
+end
+",
+        port = port,
+        body = body
+    )
+}
+
+fn output_register(clock: &str, q: &str, data: &str) -> String {
+    format!(
+        "\
+always @(posedge {clock}) begin
+   {data} <= {q};
+end
+",
+        clock = clock,
+        q = q,
+        data = data
+    )
+}
+
+fn check_lanes<const W: usize, const B: usize>() {
+    assert!(
+        B > 0 && W.is_multiple_of(B),
+        "The data width must be a multiple of the number of byte lanes"
+    );
+    assert!(W / B <= 64, "Byte lanes cannot be wider than 64 bits");
+}
+
+// One port of the simulation model of a dual port RAM.  Applies the
+// read policy to a (possible) write of the port, and models the
+// optional output register.
+#[derive(Clone, Debug, Default)]
+struct PortModel<const W: usize> {
+    q: Bits<W>,
+}
+
+impl<const W: usize> PortModel<W> {
+    fn cycle<const N: usize, const B: usize>(
+        &mut self,
+        mem: &mut BTreeMap<Bits<N>, Bits<W>>,
+        policy: ReadPolicy,
+        address: Bits<N>,
+        write_enable: Bits<B>,
+        write_data: Bits<W>,
+    ) {
+        let old = *mem.get(&address).unwrap_or(&Bits::<W>::default());
+        let new = merge_lanes(old, write_data, write_enable);
+        if write_enable.any() {
+            mem.insert(address, new);
+        }
+        match policy {
+            ReadPolicy::ReadFirst => self.q = old,
+            ReadPolicy::WriteFirst => self.q = new,
+            ReadPolicy::NoChange => {
+                if !write_enable.any() {
+                    self.q = old;
+                }
+            }
+        }
+    }
+}
+
+// A true dual port RAM with `2^N` words of `W` bits, and `B` byte
+// lanes per word.  Each port has its own clock, and can both read and
+// write.  Writes are masked per lane by `write_enable`, and nothing
+// happens on a port unless its `enable` is asserted.  The `policy`
+// sets what the read data shows when a port writes (the result of
+// both ports writing the same address is undefined, as it is in the
+// block RAMs).  The read latency is one clock, or two clocks with
+// `output_register` set.  Note that the iCE40 block RAMs have only
+// one write port, so this widget will not map to them.
+#[derive(LogicBlock, Default)]
+pub struct TrueDualPortRAM<const W: usize, const N: usize, const B: usize> {
+    pub a_clock: Signal<In, Clock>,
+    pub a_address: Signal<In, Bits<N>>,
+    pub a_enable: Signal<In, Bit>,
+    pub a_write_enable: Signal<In, Bits<B>>,
+    pub a_write_data: Signal<In, Bits<W>>,
+    pub a_read_data: Signal<Out, Bits<W>>,
+    pub b_clock: Signal<In, Clock>,
+    pub b_address: Signal<In, Bits<N>>,
+    pub b_enable: Signal<In, Bit>,
+    pub b_write_enable: Signal<In, Bits<B>>,
+    pub b_write_data: Signal<In, Bits<W>>,
+    pub b_read_data: Signal<Out, Bits<W>>,
+    _policy: ReadPolicy,
+    _output_register: bool,
+    _a: PortModel<W>,
+    _b: PortModel<W>,
+    _sim: BTreeMap<Bits<N>, Bits<W>>,
+}
+
+impl<const W: usize, const N: usize, const B: usize> TrueDualPortRAM<W, N, B> {
+    pub fn new(policy: ReadPolicy, output_register: bool) -> Self {
+        Self::with_contents(policy, output_register, BTreeMap::new())
+    }
+    pub fn with_contents(
+        policy: ReadPolicy,
+        output_register: bool,
+        values: BTreeMap<Bits<N>, Bits<W>>,
+    ) -> Self {
+        check_lanes::<W, B>();
+        Self {
+            _policy: policy,
+            _output_register: output_register,
+            _sim: values,
+            ..Default::default()
+        }
+    }
+}
+
+impl<const W: usize, const N: usize, const B: usize> Logic for TrueDualPortRAM<W, N, B> {
+    fn update(&mut self) {
+        if self.a_clock.pos_edge() {
+            let q = self._a.q;
+            if self.a_enable.val() {
+                self._a.cycle(
+                    &mut self._sim,
+                    self._policy,
+                    self.a_address.val(),
+                    self.a_write_enable.val(),
+                    self.a_write_data.val(),
+                );
+            }
+            self.a_read_data.next = if self._output_register { q } else { self._a.q };
+        }
+        if self.b_clock.pos_edge() {
+            let q = self._b.q;
+            if self.b_enable.val() {
+                self._b.cycle(
+                    &mut self._sim,
+                    self._policy,
+                    self.b_address.val(),
+                    self.b_write_enable.val(),
+                    self.b_write_data.val(),
+                );
+            }
+            self.b_read_data.next = if self._output_register { q } else { self._b.q };
+        }
+    }
+
+    fn connect(&mut self) {
+        self.a_read_data.connect();
+        self.b_read_data.connect();
+    }
+
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+        probe.visit_state("_a", &mut self._a.q);
+        probe.visit_state("_b", &mut self._b.q);
+    }
+
+    fn memory_init(&self) -> Vec<MemoryInit> {
+        lane_memory_init::<W, N, B>(&self._sim)
+    }
+
+    fn hdl(&self) -> Verilog {
+        let mut vlog = lane_memories::<W, N, B>();
+        if self._output_register {
+            vlog += &format!("reg[{}:0] a_q;\nreg[{}:0] b_q;\n", W - 1, W - 1);
+            vlog += &port_verilog::<W, B>("a", "a_q", self._policy);
+            vlog += &port_verilog::<W, B>("b", "b_q", self._policy);
+            vlog += &output_register("a_clock", "a_q", "a_read_data");
+            vlog += &output_register("b_clock", "b_q", "b_read_data");
+        } else {
+            vlog += &port_verilog::<W, B>("a", "a_read_data", self._policy);
+            vlog += &port_verilog::<W, B>("b", "b_read_data", self._policy);
+        }
+        Verilog::Custom(vlog)
+    }
+
+    fn timing(&self) -> Vec<TimingInfo> {
+        ["a", "b"]
+            .iter()
+            .map(|port| TimingInfo {
+                name: format!("tdp_ram_{}", port),
+                clock: format!("{}_clock", port),
+                inputs: vec![
+                    format!("{}_address", port),
+                    format!("{}_enable", port),
+                    format!("{}_write_enable", port),
+                    format!("{}_write_data", port),
+                ],
+                outputs: vec![format!("{}_read_data", port)],
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+// A simple dual port RAM with `2^N` words of `W` bits, and `B` byte
+// lanes per word.  One port only writes (masked per lane by
+// `write_enable`), and the other only reads, and each has its own
+// clock.  A read of the address being written on the same clock
+// returns the old contents.  The read latency is one clock, or two
+// clocks with `output_register` set.  This form maps onto the block
+// RAMs of all of the common FPGA families, including the iCE40.
+#[derive(LogicBlock, Default)]
+pub struct SimpleDualPortRAM<const W: usize, const N: usize, const B: usize> {
+    pub write_clock: Signal<In, Clock>,
+    pub write_address: Signal<In, Bits<N>>,
+    pub write_enable: Signal<In, Bits<B>>,
+    pub write_data: Signal<In, Bits<W>>,
+    pub read_clock: Signal<In, Clock>,
+    pub read_address: Signal<In, Bits<N>>,
+    pub read_enable: Signal<In, Bit>,
+    pub read_data: Signal<Out, Bits<W>>,
+    _output_register: bool,
+    _read: PortModel<W>,
+    _sim: BTreeMap<Bits<N>, Bits<W>>,
+}
+
+impl<const W: usize, const N: usize, const B: usize> SimpleDualPortRAM<W, N, B> {
+    pub fn new(output_register: bool) -> Self {
+        Self::with_contents(output_register, BTreeMap::new())
+    }
+    pub fn with_contents(output_register: bool, values: BTreeMap<Bits<N>, Bits<W>>) -> Self {
+        check_lanes::<W, B>();
+        Self {
+            _output_register: output_register,
+            _sim: values,
+            ..Default::default()
+        }
+    }
+}
+
+impl<const W: usize, const N: usize, const B: usize> Logic for SimpleDualPortRAM<W, N, B> {
+    fn update(&mut self) {
+        if self.read_clock.pos_edge() {
+            let q = self._read.q;
+            if self.read_enable.val() {
+                self._read.cycle::<N, B>(
+                    &mut self._sim,
+                    ReadPolicy::ReadFirst,
+                    self.read_address.val(),
+                    0.into(),
+                    Default::default(),
+                );
+            }
+            self.read_data.next = if self._output_register {
+                q
+            } else {
+                self._read.q
+            };
+        }
+        if self.write_clock.pos_edge() && self.write_enable.val().any() {
+            let address = self.write_address.val();
+            let old = *self._sim.get(&address).unwrap_or(&Bits::<W>::default());
+            self._sim.insert(
+                address,
+                merge_lanes(old, self.write_data.val(), self.write_enable.val()),
+            );
+        }
+    }
+
+    fn connect(&mut self) {
+        self.read_data.connect();
+    }
+
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", &mut self._sim);
+        probe.visit_state("_read", &mut self._read.q);
+    }
+
+    fn memory_init(&self) -> Vec<MemoryInit> {
+        lane_memory_init::<W, N, B>(&self._sim)
+    }
+
+    fn hdl(&self) -> Verilog {
+        let mut vlog = lane_memories::<W, N, B>();
+        vlog += &format!(
+            "\
+always @(posedge write_clock) begin
+{writes}end
+",
+            writes = (0..B)
+                .map(|k| format!(
+                    "   if (write_enable[{k}]) mem_{k}[write_address] <= write_data[{range}];\n",
+                    k = k,
+                    range = lane_range::<W, B>(k)
+                ))
+                .collect::<String>()
+        );
+        let q = if self._output_register {
+            vlog += &format!("reg[{}:0] read_q;\n", W - 1);
+            "read_q"
+        } else {
+            "read_data"
+        };
+        vlog += &format!(
+            "\
+always @(posedge read_clock) begin
+   if (read_enable) begin
+      {q} <= {read};
+   end
+end
+",
+            q = q,
+            read = lane_read::<B>("read_address")
+        );
+        if self._output_register {
+            vlog += &output_register("read_clock", "read_q", "read_data");
+        }
+        Verilog::Custom(vlog)
+    }
+
+    fn timing(&self) -> Vec<TimingInfo> {
+        vec![
+            TimingInfo {
+                name: "sdp_ram_read".into(),
+                clock: "read_clock".into(),
+                inputs: vec!["read_address".into(), "read_enable".into()],
+                outputs: vec!["read_data".into()],
+                ..Default::default()
+            },
+            TimingInfo {
+                name: "sdp_ram_write".into(),
+                clock: "write_clock".into(),
+                inputs: vec![
+                    "write_address".into(),
+                    "write_data".into(),
+                    "write_enable".into(),
+                ],
+                outputs: vec![],
+                ..Default::default()
+            },
+        ]
+    }
+}
+
+#[test]
+fn test_merge_lanes() {
+    let word: Bits<32> = 0x1122_3344_u32.to_bits();
+    let data: Bits<32> = 0xAABB_CCDD_u32.to_bits();
+    assert_eq!(
+        merge_lanes(word, data, Bits::<4>::from(0b0101)),
+        0x11BB_33DD_u32.to_bits::<32>()
+    );
+    assert_eq!(merge_lanes(word, data, Bits::<4>::from(0)), word);
+}
+
+#[test]
+fn test_wide_lane_memories() {
+    let mut word: Bits<160> = 0xAB_u32.to_bits();
+    word.set_bits::<8>(80, 0xCD_u32.to_bits());
+    word.set_bits::<8>(152, 0xEF_u32.to_bits());
+    let mut values: BTreeMap<Bits<2>, Bits<160>> = BTreeMap::new();
+    values.insert(1.into(), word);
+    let lanes = lane_memory_init::<160, 2, 2>(&values)
+        .into_iter()
+        .map(|x| (x.array, x.width, x.text))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lanes,
+        [
+            ("mem_0".to_string(), 80, format!("@1\n{:020x}\n", 0xAB)),
+            ("mem_1".to_string(), 80, format!("@1\nef{:018x}\n", 0xCD))
+        ]
+    );
+}
+
+#[test]
+fn test_tdp_ram_synthesizes() {
+    for policy in [
+        ReadPolicy::ReadFirst,
+        ReadPolicy::WriteFirst,
+        ReadPolicy::NoChange,
+    ] {
+        for output_register in [false, true] {
+            let mut uut = TrueDualPortRAM::<32, 8, 4>::new(policy, output_register);
+            uut.connect_all();
+            yosys_validate("tdp_ram", &generate_verilog(&uut)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_sdp_ram_synthesizes() {
+    let mut uut = SimpleDualPortRAM::<16, 8, 2>::new(true);
+    uut.connect_all();
+    yosys_validate("sdp_ram", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_sdp_ram_infers_block_ram() {
+    let mut uut = SimpleDualPortRAM::<16, 8, 2>::new(false);
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    for family in [
+        BlockRAMFamily::ICE40,
+        BlockRAMFamily::ECP5,
+        BlockRAMFamily::Xilinx7,
+    ] {
+        yosys_check_block_ram("sdp_ram_bram", &vlog, family).unwrap();
+    }
+}
+
+#[test]
+fn test_tdp_ram_infers_block_ram() {
+    let mut uut = TrueDualPortRAM::<16, 9, 2>::new(ReadPolicy::ReadFirst, false);
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    for family in [BlockRAMFamily::ECP5, BlockRAMFamily::Xilinx7] {
+        yosys_check_block_ram("tdp_ram_bram", &vlog, family).unwrap();
+    }
+}
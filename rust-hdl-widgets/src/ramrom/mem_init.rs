@@ -0,0 +1,360 @@
+use rust_hdl_core::prelude::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Loaders for the initial contents of the memory widgets.  Byte
+// oriented formats (Intel HEX, raw binaries and ELF files) are loaded
+// into a `MemoryImage`, which is then packed into words.  Plain hex
+// files (as read by `$readmemh`) hold words directly.
+
+#[derive(Debug)]
+pub enum MemInitError {
+    IOError(std::io::Error),
+    ParseError { line: usize, message: String },
+    ELFError(String),
+    AddressOutOfRange(u64),
+    UnknownFormat(String),
+}
+
+impl From<std::io::Error> for MemInitError {
+    fn from(x: std::io::Error) -> Self {
+        MemInitError::IOError(x)
+    }
+}
+
+fn parse_error(line: usize, message: &str) -> MemInitError {
+    MemInitError::ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+// A sparse byte addressed image of a memory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryImage {
+    pub bytes: BTreeMap<u64, u8>,
+}
+
+impl MemoryImage {
+    pub fn from_bin(data: &[u8], base: u64) -> Self {
+        Self {
+            bytes: data
+                .iter()
+                .enumerate()
+                .map(|(ndx, x)| (base + ndx as u64, *x))
+                .collect(),
+        }
+    }
+
+    // Supports the data, end of file, extended segment address and
+    // extended linear address records.  Start address records are
+    // ignored.
+    pub fn from_intel_hex(text: &str) -> Result<Self, MemInitError> {
+        let mut image = MemoryImage::default();
+        let mut offset = 0_u64;
+        for (ndx, line) in text.lines().enumerate() {
+            let line_number = ndx + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let record = line
+                .strip_prefix(':')
+                .ok_or_else(|| parse_error(line_number, "Record does not start with ':'"))?;
+            if record.len() % 2 != 0 || record.len() < 10 {
+                return Err(parse_error(line_number, "Malformed record"));
+            }
+            let bytes = (0..record.len() / 2)
+                .map(|i| u8::from_str_radix(&record[2 * i..2 * i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| parse_error(line_number, "Invalid hex digit"))?;
+            let count = bytes[0] as usize;
+            if bytes.len() != count + 5 {
+                return Err(parse_error(line_number, "Record length mismatch"));
+            }
+            if bytes.iter().fold(0_u8, |acc, x| acc.wrapping_add(*x)) != 0 {
+                return Err(parse_error(line_number, "Checksum mismatch"));
+            }
+            let address = ((bytes[1] as u64) << 8) | (bytes[2] as u64);
+            let data = &bytes[4..4 + count];
+            match bytes[3] {
+                0x00 => {
+                    for (i, x) in data.iter().enumerate() {
+                        image.bytes.insert(offset + address + i as u64, *x);
+                    }
+                }
+                0x01 => break,
+                0x02 if count == 2 => {
+                    offset = (((data[0] as u64) << 8) | (data[1] as u64)) << 4;
+                }
+                0x04 if count == 2 => {
+                    offset = (((data[0] as u64) << 8) | (data[1] as u64)) << 16;
+                }
+                0x03 | 0x05 => {}
+                _ => return Err(parse_error(line_number, "Unsupported record")),
+            }
+        }
+        Ok(image)
+    }
+
+    // Loads the `PT_LOAD` segments of a 32 or 64 bit ELF file at their
+    // physical addresses.  The part of a segment that is not in the file
+    // (e.g., the `.bss`) is zero filled.
+    pub fn from_elf(data: &[u8]) -> Result<Self, MemInitError> {
+        let elf_error = |x: &str| MemInitError::ELFError(x.into());
+        if data.len() < 52 || &data[0..4] != b"\x7fELF" {
+            return Err(elf_error("Not an ELF file"));
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(elf_error("Unknown ELF class")),
+        };
+        let little_endian = match data[5] {
+            1 => true,
+            2 => false,
+            _ => return Err(elf_error("Unknown ELF data encoding")),
+        };
+        let read = |offset: usize, size: usize| -> Result<u64, MemInitError> {
+            let bytes = data
+                .get(offset..offset + size)
+                .ok_or_else(|| elf_error("Truncated ELF file"))?;
+            Ok(if little_endian {
+                bytes.iter().rev().fold(0, |acc, x| (acc << 8) | *x as u64)
+            } else {
+                bytes.iter().fold(0, |acc, x| (acc << 8) | *x as u64)
+            })
+        };
+        // Offsets of the program header fields for each class
+        let (ph_offset, ph_entry_size, ph_count) = if is_64 {
+            (read(0x20, 8)?, read(0x36, 2)?, read(0x38, 2)?)
+        } else {
+            (read(0x1C, 4)?, read(0x2A, 2)?, read(0x2C, 2)?)
+        };
+        let mut image = MemoryImage::default();
+        for ndx in 0..ph_count {
+            let header = (ph_offset + ndx * ph_entry_size) as usize;
+            let (p_type, p_offset, p_paddr, p_filesz, p_memsz) = if is_64 {
+                (
+                    read(header, 4)?,
+                    read(header + 0x08, 8)?,
+                    read(header + 0x18, 8)?,
+                    read(header + 0x20, 8)?,
+                    read(header + 0x28, 8)?,
+                )
+            } else {
+                (
+                    read(header, 4)?,
+                    read(header + 0x04, 4)?,
+                    read(header + 0x0C, 4)?,
+                    read(header + 0x10, 4)?,
+                    read(header + 0x14, 4)?,
+                )
+            };
+            const PT_LOAD: u64 = 1;
+            if p_type != PT_LOAD {
+                continue;
+            }
+            let contents = data
+                .get(p_offset as usize..(p_offset + p_filesz) as usize)
+                .ok_or_else(|| elf_error("Segment extends past the end of the file"))?;
+            for i in 0..p_memsz {
+                let val = contents.get(i as usize).copied().unwrap_or(0);
+                image.bytes.insert(p_paddr + i, val);
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn base_address(&self) -> u64 {
+        self.bytes.keys().next().copied().unwrap_or(0)
+    }
+
+    // Pack the image into little endian words of `W` bits, with the
+    // word at address 0 starting at byte address `base`.  Words with
+    // no bytes in the image are left out, and missing bytes of the
+    // other words are zero.
+    pub fn to_words<const W: usize, const N: usize>(
+        &self,
+        base: u64,
+    ) -> Result<BTreeMap<Bits<N>, Bits<W>>, MemInitError> {
+        let word_bytes = (W as u64).div_ceil(8);
+        let mut words = BTreeMap::new();
+        for (address, val) in &self.bytes {
+            if *address < base {
+                return Err(MemInitError::AddressOutOfRange(*address));
+            }
+            let word_address = (address - base) / word_bytes;
+            let lane = ((address - base) % word_bytes) as usize;
+            if N < 64 && word_address >= (1 << N) {
+                return Err(MemInitError::AddressOutOfRange(*address));
+            }
+            let word: &mut Bits<W> = words
+                .entry(word_address.to_bits::<N>())
+                .or_insert_with(Default::default);
+            for bit in 0..8 {
+                if lane * 8 + bit < W {
+                    *word = word.replace_bit(lane * 8 + bit, (val >> bit) & 1 != 0);
+                } else if (val >> bit) & 1 != 0 {
+                    return Err(MemInitError::AddressOutOfRange(*address));
+                }
+            }
+        }
+        Ok(words)
+    }
+}
+
+fn bits_from_hex<const W: usize>(digits: &str) -> Option<Bits<W>> {
+    let mut val = Bits::<W>::default();
+    for (ndx, digit) in digits.chars().rev().filter(|x| *x != '_').enumerate() {
+        let digit = digit.to_digit(16)?;
+        for bit in 0..4 {
+            if (digit >> bit) & 1 != 0 {
+                if ndx * 4 + bit >= W {
+                    return None;
+                }
+                val = val.replace_bit(ndx * 4 + bit, true);
+            }
+        }
+    }
+    Some(val)
+}
+
+// Parses a plain hex file, in the format read by `$readmemh`.  The file
+// holds whitespace separated words, with `//` and `/* */` comments, and
+// `@address` directives to move to a new (word) address.
+pub fn parse_hex_words<const W: usize, const N: usize>(
+    text: &str,
+) -> Result<BTreeMap<Bits<N>, Bits<W>>, MemInitError> {
+    let mut words = BTreeMap::new();
+    let mut address = 0_u64;
+    let mut in_comment = false;
+    for (ndx, line) in text.lines().enumerate() {
+        let line_number = ndx + 1;
+        let mut line = line;
+        let mut tokens = vec![];
+        while !line.is_empty() {
+            if in_comment {
+                match line.find("*/") {
+                    Some(end) => {
+                        line = &line[end + 2..];
+                        in_comment = false;
+                    }
+                    None => line = "",
+                }
+                continue;
+            }
+            let (code, rest) = match (line.find("//"), line.find("/*")) {
+                (Some(a), Some(b)) if a < b => (&line[..a], ""),
+                (Some(a), None) => (&line[..a], ""),
+                (_, Some(b)) => {
+                    in_comment = true;
+                    (&line[..b], &line[b + 2..])
+                }
+                (None, None) => (line, ""),
+            };
+            tokens.extend(code.split_whitespace());
+            line = rest;
+        }
+        for token in tokens {
+            if let Some(target) = token.strip_prefix('@') {
+                address = u64::from_str_radix(target, 16)
+                    .map_err(|_| parse_error(line_number, "Invalid address"))?;
+                continue;
+            }
+            let word = bits_from_hex::<W>(token)
+                .ok_or_else(|| parse_error(line_number, "Invalid or oversized word"))?;
+            if N < 64 && address >= (1 << N) {
+                return Err(MemInitError::AddressOutOfRange(address));
+            }
+            words.insert(address.to_bits(), word);
+            address += 1;
+        }
+    }
+    Ok(words)
+}
+
+// Loads a memory initialization file, picking the format from the file
+// extension (`.ihex`, `.hex`, `.mem`, `.bin` or `.elf`).  A `.hex` file
+// is read as Intel HEX if it starts with a `:`, and as plain hex words
+// otherwise.  Byte oriented images are packed into little endian words,
+// starting from the lowest address in the image.
+pub fn load_memory_file<const W: usize, const N: usize>(
+    path: impl AsRef<Path>,
+) -> Result<BTreeMap<Bits<N>, Bits<W>>, MemInitError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let image = match extension.as_str() {
+        "ihex" => MemoryImage::from_intel_hex(&std::fs::read_to_string(path)?)?,
+        "hex" | "mem" => {
+            let text = std::fs::read_to_string(path)?;
+            if text.trim_start().starts_with(':') {
+                MemoryImage::from_intel_hex(&text)?
+            } else {
+                return parse_hex_words(&text);
+            }
+        }
+        "bin" => MemoryImage::from_bin(&std::fs::read(path)?, 0),
+        "elf" => MemoryImage::from_elf(&std::fs::read(path)?)?,
+        _ => return Err(MemInitError::UnknownFormat(path.display().to_string())),
+    };
+    image.to_words(image.base_address())
+}
+
+#[test]
+fn test_intel_hex_parses() {
+    let text = "\
+:020000040001F9
+:0400100001020304E2
+:00000001FF
+";
+    let image = MemoryImage::from_intel_hex(text).unwrap();
+    assert_eq!(image.bytes.len(), 4);
+    assert_eq!(image.bytes[&0x1_0010], 1);
+    assert_eq!(image.bytes[&0x1_0013], 4);
+    let words = image.to_words::<16, 4>(0x1_0010).unwrap();
+    assert_eq!(words[&0.into()], 0x0201);
+    assert_eq!(words[&1.into()], 0x0403);
+    assert!(MemoryImage::from_intel_hex(":0400100001020304E3").is_err());
+}
+
+#[test]
+fn test_hex_words_parse() {
+    let text = "// Header\n12 34 /* skip\n 99 */ 56\n@10\nabcd_ef01 // tail\n";
+    let words = parse_hex_words::<32, 8>(text).unwrap();
+    assert_eq!(words.len(), 4);
+    assert_eq!(words[&2.into()], 0x56);
+    assert_eq!(words[&0x10.into()], 0xABCD_EF01_u32.to_bits::<32>());
+    assert!(parse_hex_words::<8, 8>("123").is_err());
+}
+
+#[test]
+fn test_elf_segments_load() {
+    // A minimal 32 bit little endian ELF with a single PT_LOAD segment
+    // of 4 file bytes and 8 memory bytes at physical address 0x100
+    let mut elf = vec![0_u8; 0x60];
+    elf[0..4].copy_from_slice(b"\x7fELF");
+    elf[4] = 1;
+    elf[5] = 1;
+    elf[0x1C] = 0x34; // e_phoff
+    elf[0x2A] = 0x20; // e_phentsize
+    elf[0x2C] = 1; // e_phnum
+    let ph = 0x34;
+    elf[ph] = 1; // PT_LOAD
+    elf[ph + 0x04] = 0x58; // p_offset
+    elf[ph + 0x08] = 0xFF; // p_vaddr (ignored)
+    elf[ph + 0x0D] = 0x01; // p_paddr = 0x100
+    elf[ph + 0x10] = 4; // p_filesz
+    elf[ph + 0x14] = 8; // p_memsz
+    elf[0x58..0x5C].copy_from_slice(&[0xEF, 0xBE, 0xAD, 0xDE]);
+    let image = MemoryImage::from_elf(&elf).unwrap();
+    assert_eq!(image.bytes.len(), 8);
+    let words = image.to_words::<32, 4>(0x100).unwrap();
+    assert_eq!(words[&0.into()], 0xDEAD_BEEF_u32.to_bits::<32>());
+    assert_eq!(words[&1.into()], 0);
+    assert!(MemoryImage::from_elf(b"not an elf").is_err());
+}
@@ -1,3 +1,5 @@
+pub mod dual_port_ram;
+pub mod mem_init;
 pub mod ram;
 pub mod rom;
 pub mod sync_rom;
@@ -1,7 +1,9 @@
+use crate::ramrom::mem_init::{load_memory_file, MemInitError};
 use crate::ramrom::rom::make_btree_from_iterable;
 use rust_hdl_core::prelude::*;
 use rust_hdl_core::timing::TimingInfo;
 use std::collections::BTreeMap;
+use std::path::Path;
 
 #[derive(LogicInterface, Default)]
 pub struct RAMWrite<D: Synth, const N: usize> {
@@ -32,6 +34,13 @@ impl<D: Synth, const N: usize> RAM<D, N> {
     }
 }
 
+impl<const W: usize, const N: usize> RAM<Bits<W>, N> {
+    // Load the initial contents of the RAM from a file (see `load_memory_file`)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MemInitError> {
+        Ok(Self::new(load_memory_file(path)?))
+    }
+}
+
 impl<I: Iterator<Item = D>, D: Synth, const N: usize> From<I> for RAM<D, N> {
     fn from(v: I) -> Self {
         Self::new(make_btree_from_iterable(v))
@@ -56,31 +65,15 @@ impl<D: Synth, const N: usize> Logic for RAM<D, N> {
         self.read_data.connect();
     }
 
+    fn accept_state(&mut self, probe: &mut dyn ProbeMut) {
+        probe.visit_state("_sim", self._sim.as_mut());
+    }
+
     fn hdl(&self) -> Verilog {
-        let init = if self._sim.len() != 0 {
-            format!(
-                "initial begin\n{};\nend\n",
-                self._sim
-                    .iter()
-                    .map(|x| {
-                        format!(
-                            "mem[{}] = {}",
-                            x.0.verilog().to_string(),
-                            x.1.verilog().to_string()
-                        )
-                    })
-                    .collect::<Vec<_>>()
-                    .join(";\n")
-            )
-        } else {
-            "".into()
-        };
         Verilog::Custom(format!(
             "\
 reg[{D}:0] mem[{Acount}:0];
 
-{init}
-
 always @(posedge read_clock) begin
    read_data <= mem[read_address];
 end
@@ -92,11 +85,17 @@ always @(posedge write_clock) begin
 end
             ",
             D = D::BITS - 1,
-            Acount = (1 << N) - 1,
-            init = init
+            Acount = (1 << N) - 1
         ))
     }
 
+    fn memory_init(&self) -> Vec<MemoryInit> {
+        if self._sim.is_empty() {
+            return vec![];
+        }
+        vec![MemoryInit::new("mem", &self._sim)]
+    }
+
     fn timing(&self) -> Vec<TimingInfo> {
         vec![
             TimingInfo {
@@ -104,6 +103,7 @@ end
                 clock: "read_clock".into(),
                 inputs: vec!["read_address".into()],
                 outputs: vec!["read_data".into()],
+                ..Default::default()
             },
             TimingInfo {
                 name: "ram_write".into(),
@@ -114,6 +114,7 @@ end
                     "write_enable".into(),
                 ],
                 outputs: vec![],
+                ..Default::default()
             },
         ]
     }
@@ -1,5 +1,7 @@
+use crate::ramrom::mem_init::{load_memory_file, MemInitError};
 use rust_hdl_core::prelude::*;
 use std::collections::BTreeMap;
+use std::path::Path;
 
 #[derive(LogicBlock)]
 pub struct ROM<D: Synth, const N: usize> {
@@ -45,28 +47,43 @@ impl<D: Synth, const N: usize> Logic for ROM<D, N> {
     }
 
     fn hdl(&self) -> Verilog {
-        let cases = self
-            ._sim
-            .iter()
-            .map(|x| {
-                format!(
-                    "  {}: data = {};",
-                    x.0.verilog().to_string(),
-                    x.1.verilog().to_string()
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+        let default = D::default().verilog().to_string();
+        let last = match self._sim.keys().next_back() {
+            Some(last) => last.index(),
+            None => return Verilog::Custom(format!("always @* data = {};", default)),
+        };
         Verilog::Custom(format!(
             "\
+reg[{D}:0] mem[{last}:0];
+
 always @*
-case (address)
-  {cases}
-  default: data = {default};
-endcase
-        ",
-            cases = cases,
-            default = D::default().verilog().to_string()
+if (address <= {last}) data = mem[address];
+else data = {default};
+",
+            D = D::BITS - 1,
+            last = last,
+            default = default
         ))
     }
+
+    fn memory_init(&self) -> Vec<MemoryInit> {
+        // Only the populated part of the address space is stored, and
+        // any holes in it are filled with the default value
+        let last = match self._sim.keys().next_back() {
+            Some(last) => last.index(),
+            None => return vec![],
+        };
+        let values: BTreeMap<Bits<N>, D> = (0..=last)
+            .map(|x| x.to_bits())
+            .map(|x| (x, *self._sim.get(&x).unwrap_or(&D::default())))
+            .collect();
+        vec![MemoryInit::new("mem", &values)]
+    }
+}
+
+impl<const W: usize, const N: usize> ROM<Bits<W>, N> {
+    // Load the contents of the ROM from a file (see `load_memory_file`)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MemInitError> {
+        Ok(Self::new(load_memory_file(path)?))
+    }
 }
@@ -1,7 +1,9 @@
+use crate::ramrom::mem_init::{load_memory_file, MemInitError};
 use crate::ramrom::rom::make_btree_from_iterable;
 use rust_hdl_core::prelude::*;
 use rust_hdl_core::timing::TimingInfo;
 use std::collections::BTreeMap;
+use std::path::Path;
 
 #[derive(LogicBlock)]
 pub struct SyncROM<D: Synth, const N: usize> {
@@ -40,40 +42,38 @@ impl<D: Synth, const N: usize> Logic for SyncROM<D, N> {
     }
 
     fn hdl(&self) -> Verilog {
-        let init = self
-            ._sim
-            .iter()
-            .map(|x| {
-                format!(
-                    "mem[{}] = {}",
-                    x.0.verilog().to_string(),
-                    x.1.verilog().to_string()
-                )
-            })
-            .collect::<Vec<_>>()
-            .join(";\n");
         Verilog::Custom(format!(
             "\
 reg[{D}:0] mem [{Acount}:0];
 
-initial begin
-{init};
-end
-
 always @(posedge clock) begin
    data <= mem[address];
 end",
             D = D::BITS - 1,
-            Acount = (1 << N) - 1,
-            init = init
+            Acount = (1 << N) - 1
         ))
     }
+
+    fn memory_init(&self) -> Vec<MemoryInit> {
+        if self._sim.is_empty() {
+            return vec![];
+        }
+        vec![MemoryInit::new("mem", &self._sim)]
+    }
     fn timing(&self) -> Vec<TimingInfo> {
         vec![TimingInfo {
             name: "sync_rom".to_string(),
             clock: "clock".to_string(),
             inputs: vec!["address".to_string()],
             outputs: vec!["data".to_string()],
+            ..Default::default()
         }]
     }
 }
+
+impl<const W: usize, const N: usize> SyncROM<Bits<W>, N> {
+    // Load the contents of the ROM from a file (see `load_memory_file`)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MemInitError> {
+        Ok(Self::new(load_memory_file(path)?))
+    }
+}
@@ -0,0 +1,144 @@
+use rust_hdl_core::prelude::*;
+
+// How a `ResetDFF` responds to its reset.  A synchronous reset is
+// sampled on the rising edge of the clock, while an asynchronous reset
+// forces the register to its reset value as soon as it is asserted.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ResetKind {
+    #[default]
+    Synchronous,
+    AsyncActiveHigh,
+    AsyncActiveLow,
+}
+
+// A register with a reset and a clock enable.  While reset is asserted,
+// `q` is held at the reset value (which is also its power on value).
+// Otherwise, `q` takes the value of `d` on the rising edges of the clock
+// for which `enable` is high.  Use `dff_setup!(self, clock, reset = reset, ...)`
+// to wire these registers to a block level reset.
+#[derive(Clone, Debug, LogicBlock)]
+pub struct ResetDFF<T: Synth> {
+    pub d: Signal<In, T>,
+    pub q: Signal<Out, T>,
+    pub clock: Signal<In, Clock>,
+    pub reset: Signal<In, Reset>,
+    pub enable: Signal<In, Bit>,
+    _kind: ResetKind,
+    _value: T,
+}
+
+impl<T: Synth> ResetDFF<T> {
+    pub fn new(kind: ResetKind, value: T) -> Self {
+        Self {
+            d: Signal::default(),
+            q: Signal::new_with_default(value),
+            clock: Signal::default(),
+            reset: Signal::default(),
+            enable: Signal::default(),
+            _kind: kind,
+            _value: value,
+        }
+    }
+    pub fn synchronous(value: T) -> Self {
+        Self::new(ResetKind::Synchronous, value)
+    }
+    pub fn async_active_high(value: T) -> Self {
+        Self::new(ResetKind::AsyncActiveHigh, value)
+    }
+    pub fn async_active_low(value: T) -> Self {
+        Self::new(ResetKind::AsyncActiveLow, value)
+    }
+    fn in_reset(&self) -> bool {
+        match self._kind {
+            ResetKind::Synchronous | ResetKind::AsyncActiveHigh => self.reset.val().rst,
+            ResetKind::AsyncActiveLow => !self.reset.val().rst,
+        }
+    }
+}
+
+impl<T: Synth> Default for ResetDFF<T> {
+    fn default() -> Self {
+        Self::new(ResetKind::Synchronous, T::default())
+    }
+}
+
+impl<T: Synth> Logic for ResetDFF<T> {
+    fn update(&mut self) {
+        match self._kind {
+            ResetKind::Synchronous => {
+                if self.clock.pos_edge() {
+                    if self.in_reset() {
+                        self.q.next = self._value;
+                    } else if self.enable.val() {
+                        self.q.next = self.d.val();
+                    }
+                }
+            }
+            ResetKind::AsyncActiveHigh | ResetKind::AsyncActiveLow => {
+                if self.in_reset() {
+                    self.q.next = self._value;
+                } else if self.clock.pos_edge() && self.enable.val() {
+                    self.q.next = self.d.val();
+                }
+            }
+        }
+    }
+    fn connect(&mut self) {
+        self.q.connect();
+    }
+    fn hdl(&self) -> Verilog {
+        let (sensitivity, condition) = match self._kind {
+            ResetKind::Synchronous => ("posedge clock", "reset"),
+            ResetKind::AsyncActiveHigh => ("posedge clock or posedge reset", "reset"),
+            ResetKind::AsyncActiveLow => ("posedge clock or negedge reset", "!reset"),
+        };
+        Verilog::Custom(format!(
+            "\
+initial begin
+   q = {value:x};
+end
+
+always @({sensitivity}) begin
+   if ({condition})
+      q <= {value:x};
+   else if (enable)
+      q <= d;
+end
+      ",
+            value = self._value.verilog(),
+            sensitivity = sensitivity,
+            condition = condition
+        ))
+    }
+    fn timing(&self) -> Vec<TimingInfo> {
+        let synchronous = self._kind == ResetKind::Synchronous;
+        let mut inputs = vec!["d".to_string(), "enable".to_string()];
+        if synchronous {
+            inputs.push("reset".into());
+        }
+        vec![TimingInfo {
+            name: "reset_dff".into(),
+            clock: "clock".into(),
+            inputs,
+            outputs: vec!["q".into()],
+            reset: if synchronous {
+                None
+            } else {
+                Some("reset".into())
+            },
+        }]
+    }
+}
+
+#[test]
+fn test_reset_dff_synthesizes() {
+    for kind in [
+        ResetKind::Synchronous,
+        ResetKind::AsyncActiveHigh,
+        ResetKind::AsyncActiveLow,
+    ] {
+        let mut uut = ResetDFF::<Bits<8>>::new(kind, 0x5A.into());
+        uut.connect_all();
+        yosys_validate("reset_dff", &generate_verilog(&uut)).unwrap();
+    }
+}
@@ -0,0 +1,539 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::sdram::cmd::SDRAMCommand;
+use crate::sdram::ddr3_phy::{DDR3PHYDriver, DDR3_PHY_READ_LATENCY};
+use crate::sdram::ddr3_timings::DDR3Timings;
+use rust_hdl_core::prelude::*;
+
+// Controller states...
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum State {
+    Reset,
+    ClockEnable,
+    ExitReset,
+    ModeRegister,
+    ModeRegisterWait,
+    Calibrate,
+    CalibrateWait,
+    Idle,
+    Access,
+    PrechargeBank,
+    PrechargeWait,
+    PrechargeAll,
+    PrechargeAllWait,
+    Refresh,
+    RefreshWait,
+    Activate,
+    ActivateWait,
+    ReadCommand,
+    ReadData,
+    WriteCommand,
+    WriteData,
+}
+
+// A controller for a DDR3 device (through a PHY, such as the [DDR3PHY]).  It
+// runs the power up sequence (reset, clock enable, mode registers and ZQ
+// calibration), and then refreshes the device every tREFI.
+//
+// Each command reads or writes one burst of 8 words (a line of `L = 8 * D`
+// bits).  The command address is in lines, and is split (from low to high
+// bits) into the column, the bank and the row, so that sequential lines fill
+// a row before moving to the next bank.  Rows are left open after an access,
+// so that a later access to the same row only needs a column command.  A bank
+// is precharged when a different row is needed, and all of them are before a
+// refresh.  One command is handled at a time, and `busy` is held until its
+// burst completes.
+//
+// Constants:
+//  R - Row bits in the address
+//  C - Col bits in the address
+//  L - Line width (8 * D)
+//  D - Data bus width
+//
+// [DDR3PHY]: crate::sdram::ddr3_phy::DDR3PHY
+#[derive(LogicBlock)]
+pub struct DDR3Controller<const R: usize, const C: usize, const L: usize, const D: usize> {
+    pub clock: Signal<In, Clock>,
+    pub phy: DDR3PHYDriver<D>,
+    // Command interface
+    pub data_in: Signal<In, Bits<L>>,
+    pub write_not_read: Signal<In, Bit>,
+    pub cmd_strobe: Signal<In, Bit>,
+    pub cmd_address: Signal<In, Bits<32>>,
+    pub busy: Signal<Out, Bit>,
+    pub data_out: Signal<Out, Bits<L>>,
+    pub data_valid: Signal<Out, Bit>,
+    pub init_done: Signal<Out, Bit>,
+    state: DFF<State>,
+    delay_counter: DFF<Bits<32>>,
+    refresh_counter: DFF<Bits<16>>,
+    refresh_needed: DFF<Bit>,
+    since_activate: DFF<Bits<16>>,
+    since_read: DFF<Bits<16>>,
+    since_write: DFF<Bits<16>>,
+    mode_index: DFF<Bits<2>>,
+    transfer_counter: DFF<Bits<2>>,
+    bank_open: [DFF<Bit>; 8],
+    bank_row: [DFF<Bits<R>>; 8],
+    reg_address: DFF<Bits<32>>,
+    reg_data_write: DFF<Bits<L>>,
+    reg_data_read: DFF<Bits<L>>,
+    write_pending: DFF<Bit>,
+    read_pending: DFF<Bit>,
+    read_ready: DFF<Bit>,
+    initialized: DFF<Bit>,
+    addr_word: Signal<Local, Bits<32>>,
+    addr_bank: Signal<Local, Bits<3>>,
+    addr_row: Signal<Local, Bits<R>>,
+    addr_col: Signal<Local, Bits<15>>,
+    row_open: Signal<Local, Bit>,
+    row_hit: Signal<Local, Bit>,
+    any_open: Signal<Local, Bit>,
+    precharge_ok: Signal<Local, Bit>,
+    // Delays, in clocks (less one)
+    t_reset: Constant<Bits<32>>,
+    t_cke: Constant<Bits<32>>,
+    t_xpr: Constant<Bits<32>>,
+    t_mrd: Constant<Bits<32>>,
+    t_mod: Constant<Bits<32>>,
+    t_zqinit: Constant<Bits<32>>,
+    t_rp: Constant<Bits<32>>,
+    t_rcd: Constant<Bits<32>>,
+    t_rfc: Constant<Bits<32>>,
+    write_delay: Constant<Bits<32>>,
+    read_delay: Constant<Bits<32>>,
+    t_ras: Constant<Bits<16>>,
+    t_rrd: Constant<Bits<16>>,
+    t_rtp: Constant<Bits<16>>,
+    t_ccd: Constant<Bits<16>>,
+    write_to_precharge: Constant<Bits<16>>,
+    write_to_read: Constant<Bits<16>>,
+    read_to_write: Constant<Bits<16>>,
+    t_refresh: Constant<Bits<16>>,
+    mr0: Constant<Bits<15>>,
+    mr1: Constant<Bits<15>>,
+    mr2: Constant<Bits<15>>,
+    mr3: Constant<Bits<15>>,
+    burst_shift: Constant<Bits<32>>,
+    col_bits: Constant<Bits<32>>,
+    row_offset: Constant<Bits<32>>,
+    data_bits: Constant<Bits<32>>,
+    beat_bits: Constant<Bits<L>>,
+    rise_shift_in: Constant<Bits<L>>,
+    fall_shift_in: Constant<Bits<L>>,
+}
+
+fn less_one(x: u32) -> u32 {
+    x.max(1) - 1
+}
+
+impl<const R: usize, const C: usize, const L: usize, const D: usize> DDR3Controller<R, C, L, D> {
+    pub fn new(timings: DDR3Timings) -> DDR3Controller<R, C, L, D> {
+        assert_eq!(L, 8 * D);
+        assert!(R <= 15);
+        assert!((4..=10).contains(&C));
+        assert!(R + C + 3 <= 32);
+        let cycles = |x: u16| less_one(x as u32).to_bits::<32>();
+        let gaps = |x: u16| less_one(x as u32).to_bits::<16>();
+        Self {
+            clock: Default::default(),
+            phy: Default::default(),
+            data_in: Default::default(),
+            write_not_read: Default::default(),
+            cmd_strobe: Default::default(),
+            cmd_address: Default::default(),
+            busy: Default::default(),
+            data_out: Default::default(),
+            data_valid: Default::default(),
+            init_done: Default::default(),
+            state: Default::default(),
+            delay_counter: Default::default(),
+            refresh_counter: Default::default(),
+            refresh_needed: Default::default(),
+            since_activate: Default::default(),
+            since_read: Default::default(),
+            since_write: Default::default(),
+            mode_index: Default::default(),
+            transfer_counter: Default::default(),
+            bank_open: Default::default(),
+            bank_row: Default::default(),
+            reg_address: Default::default(),
+            reg_data_write: Default::default(),
+            reg_data_read: Default::default(),
+            write_pending: Default::default(),
+            read_pending: Default::default(),
+            read_ready: Default::default(),
+            initialized: Default::default(),
+            addr_word: Default::default(),
+            addr_bank: Default::default(),
+            addr_row: Default::default(),
+            addr_col: Default::default(),
+            row_open: Default::default(),
+            row_hit: Default::default(),
+            any_open: Default::default(),
+            precharge_ok: Default::default(),
+            t_reset: Constant::new(less_one(timings.t_reset()).to_bits()),
+            t_cke: Constant::new(less_one(timings.t_cke()).to_bits()),
+            t_xpr: Constant::new(cycles(timings.t_xpr())),
+            t_mrd: Constant::new(cycles(timings.t_mrd())),
+            t_mod: Constant::new(cycles(timings.t_mod())),
+            t_zqinit: Constant::new(cycles(timings.t_zqinit())),
+            t_rp: Constant::new(cycles(timings.t_rp())),
+            t_rcd: Constant::new(cycles(timings.t_rcd())),
+            t_rfc: Constant::new(cycles(timings.t_rfc())),
+            write_delay: Constant::new(less_one(timings.cas_write_latency).to_bits()),
+            read_delay: Constant::new(
+                less_one(timings.cas_latency + DDR3_PHY_READ_LATENCY).to_bits(),
+            ),
+            t_ras: Constant::new(gaps(timings.t_ras())),
+            t_rrd: Constant::new(gaps(timings.t_rrd())),
+            t_rtp: Constant::new(gaps(timings.t_rtp())),
+            t_ccd: Constant::new(gaps(timings.t_ccd())),
+            write_to_precharge: Constant::new(gaps(timings.write_to_precharge())),
+            write_to_read: Constant::new(gaps(timings.write_to_read())),
+            read_to_write: Constant::new(gaps(timings.read_to_write())),
+            t_refresh: Constant::new((timings.t_refi() * 9 / 10).to_bits()),
+            mr0: Constant::new(timings.mr0().to_bits()),
+            mr1: Constant::new(timings.mr1().to_bits()),
+            mr2: Constant::new(timings.mr2().to_bits()),
+            mr3: Constant::new(timings.mr3().to_bits()),
+            burst_shift: Constant::new(3_u32.to_bits()),
+            col_bits: Constant::new(C.to_bits()),
+            row_offset: Constant::new({ C + 3 }.to_bits()),
+            data_bits: Constant::new(D.to_bits()),
+            beat_bits: Constant::new({ 2 * D }.to_bits()),
+            rise_shift_in: Constant::new({ L - 2 * D }.to_bits()),
+            fall_shift_in: Constant::new({ L - D }.to_bits()),
+        }
+    }
+}
+
+impl<const R: usize, const C: usize, const L: usize, const D: usize> Logic
+    for DDR3Controller<R, C, L, D>
+{
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(
+            self,
+            clock,
+            state,
+            delay_counter,
+            refresh_counter,
+            refresh_needed,
+            since_activate,
+            since_read,
+            since_write,
+            mode_index,
+            transfer_counter,
+            reg_address,
+            reg_data_write,
+            reg_data_read,
+            write_pending,
+            read_pending,
+            read_ready,
+            initialized
+        );
+        for i in 0..8 {
+            self.bank_open[i].clock.next = self.clock.val();
+            self.bank_open[i].d.next = self.bank_open[i].q.val();
+            self.bank_row[i].clock.next = self.clock.val();
+            self.bank_row[i].d.next = self.bank_row[i].q.val();
+        }
+        self.delay_counter.d.next = self.delay_counter.q.val() + 1;
+        self.refresh_counter.d.next = self.refresh_counter.q.val() + 1;
+        // The time since the last command of each kind, saturating
+        if self.since_activate.q.val() != 0xFFFF {
+            self.since_activate.d.next = self.since_activate.q.val() + 1;
+        }
+        if self.since_read.q.val() != 0xFFFF {
+            self.since_read.d.next = self.since_read.q.val() + 1;
+        }
+        if self.since_write.q.val() != 0xFFFF {
+            self.since_write.d.next = self.since_write.q.val() + 1;
+        }
+        self.phy.reset_not.next = true;
+        self.phy.cke.next = true;
+        self.phy.cmd.next = SDRAMCommand::NOP;
+        self.phy.bank.next = 0.into();
+        self.phy.address.next = 0.into();
+        self.phy.write_enable.next = false;
+        self.phy.write_rise.next = self.reg_data_write.q.val().get_bits::<D>(0);
+        self.phy.write_fall.next = self
+            .reg_data_write
+            .q
+            .val()
+            .get_bits::<D>(self.data_bits.val().index());
+        self.data_out.next = self.reg_data_read.q.val();
+        self.data_valid.next = self.read_ready.q.val();
+        self.read_ready.d.next = false;
+        self.init_done.next = self.initialized.q.val();
+        // Split the line address into column, bank and row
+        self.addr_word.next = self.reg_address.q.val() << self.burst_shift.val();
+        self.addr_col.next = bit_cast::<15, C>(self.addr_word.val().get_bits::<C>(0));
+        self.addr_bank.next = self
+            .addr_word
+            .val()
+            .get_bits::<3>(self.col_bits.val().index());
+        self.addr_row.next = self
+            .addr_word
+            .val()
+            .get_bits::<R>(self.row_offset.val().index());
+        // Check for an open row in the addressed bank
+        self.row_open.next = false;
+        self.row_hit.next = false;
+        self.any_open.next = false;
+        for i in 0..8 {
+            if self.addr_bank.val().index() == i {
+                self.row_open.next = self.bank_open[i].q.val();
+                self.row_hit.next =
+                    self.bank_open[i].q.val() & (self.bank_row[i].q.val() == self.addr_row.val());
+            }
+            if self.bank_open[i].q.val() {
+                self.any_open.next = true;
+            }
+        }
+        self.precharge_ok.next = (self.since_activate.q.val() >= self.t_ras.val())
+            & (self.since_write.q.val() >= self.write_to_precharge.val())
+            & (self.since_read.q.val() >= self.t_rtp.val());
+        if self.refresh_counter.q.val() >= self.t_refresh.val() {
+            self.refresh_needed.d.next = true;
+        }
+        self.busy.next = (self.state.q.val() != State::Idle)
+            | self.write_pending.q.val()
+            | self.read_pending.q.val();
+        // State machine
+        match self.state.q.val() {
+            State::Reset => {
+                self.phy.reset_not.next = false;
+                self.phy.cke.next = false;
+                if self.delay_counter.q.val() == self.t_reset.val() {
+                    self.state.d.next = State::ClockEnable;
+                    self.delay_counter.d.next = 0.into();
+                }
+            }
+            State::ClockEnable => {
+                self.phy.cke.next = false;
+                if self.delay_counter.q.val() == self.t_cke.val() {
+                    self.state.d.next = State::ExitReset;
+                    self.delay_counter.d.next = 0.into();
+                }
+            }
+            State::ExitReset => {
+                if self.delay_counter.q.val() == self.t_xpr.val() {
+                    self.state.d.next = State::ModeRegister;
+                    self.mode_index.d.next = 0.into();
+                }
+            }
+            State::ModeRegister => {
+                // The mode registers are loaded in the order MR2, MR3, MR1, MR0
+                self.phy.cmd.next = SDRAMCommand::LoadModeRegister;
+                match self.mode_index.q.val().index() {
+                    0 => {
+                        self.phy.bank.next = 2.into();
+                        self.phy.address.next = self.mr2.val();
+                    }
+                    1 => {
+                        self.phy.bank.next = 3.into();
+                        self.phy.address.next = self.mr3.val();
+                    }
+                    2 => {
+                        self.phy.bank.next = 1.into();
+                        self.phy.address.next = self.mr1.val();
+                    }
+                    _ => {
+                        self.phy.bank.next = 0.into();
+                        self.phy.address.next = self.mr0.val();
+                    }
+                }
+                self.delay_counter.d.next = 0.into();
+                self.state.d.next = State::ModeRegisterWait;
+            }
+            State::ModeRegisterWait => {
+                if self.mode_index.q.val() == 3 {
+                    if self.delay_counter.q.val() >= self.t_mod.val() {
+                        self.state.d.next = State::Calibrate;
+                    }
+                } else if self.delay_counter.q.val() >= self.t_mrd.val() {
+                    self.mode_index.d.next = self.mode_index.q.val() + 1;
+                    self.state.d.next = State::ModeRegister;
+                }
+            }
+            State::Calibrate => {
+                // ZQ calibration (long) shares the burst terminate encoding, with A10 set
+                self.phy.cmd.next = SDRAMCommand::BurstTerminate;
+                self.phy.address.next = 0x400.into();
+                self.delay_counter.d.next = 0.into();
+                self.state.d.next = State::CalibrateWait;
+            }
+            State::CalibrateWait => {
+                if self.delay_counter.q.val() >= self.t_zqinit.val() {
+                    self.initialized.d.next = true;
+                    self.refresh_counter.d.next = 0.into();
+                    self.state.d.next = State::Idle;
+                }
+            }
+            State::Idle => {
+                if self.refresh_needed.q.val() {
+                    // Refresh takes the highest priority
+                    if self.any_open.val() {
+                        self.state.d.next = State::PrechargeAll;
+                    } else {
+                        self.state.d.next = State::Refresh;
+                    }
+                } else if self.read_pending.q.val() | self.write_pending.q.val() {
+                    self.state.d.next = State::Access;
+                }
+            }
+            State::Access => {
+                if self.row_hit.val() {
+                    if self.write_pending.q.val() {
+                        self.state.d.next = State::WriteCommand;
+                    } else {
+                        self.state.d.next = State::ReadCommand;
+                    }
+                } else if self.row_open.val() {
+                    self.state.d.next = State::PrechargeBank;
+                } else {
+                    self.state.d.next = State::Activate;
+                }
+            }
+            State::PrechargeBank => {
+                if self.precharge_ok.val() {
+                    self.phy.cmd.next = SDRAMCommand::Precharge;
+                    self.phy.bank.next = self.addr_bank.val();
+                    for i in 0..8 {
+                        if self.addr_bank.val().index() == i {
+                            self.bank_open[i].d.next = false;
+                        }
+                    }
+                    self.delay_counter.d.next = 0.into();
+                    self.state.d.next = State::PrechargeWait;
+                }
+            }
+            State::PrechargeWait => {
+                if self.delay_counter.q.val() >= self.t_rp.val() {
+                    self.state.d.next = State::Activate;
+                }
+            }
+            State::PrechargeAll => {
+                if self.precharge_ok.val() {
+                    self.phy.cmd.next = SDRAMCommand::Precharge;
+                    self.phy.address.next = 0x400.into();
+                    for i in 0..8 {
+                        self.bank_open[i].d.next = false;
+                    }
+                    self.delay_counter.d.next = 0.into();
+                    self.state.d.next = State::PrechargeAllWait;
+                }
+            }
+            State::PrechargeAllWait => {
+                if self.delay_counter.q.val() >= self.t_rp.val() {
+                    self.state.d.next = State::Refresh;
+                }
+            }
+            State::Refresh => {
+                self.phy.cmd.next = SDRAMCommand::AutoRefresh;
+                self.refresh_counter.d.next = 0.into();
+                self.refresh_needed.d.next = false;
+                self.delay_counter.d.next = 0.into();
+                self.state.d.next = State::RefreshWait;
+            }
+            State::RefreshWait => {
+                if self.delay_counter.q.val() >= self.t_rfc.val() {
+                    self.state.d.next = State::Idle;
+                }
+            }
+            State::Activate => {
+                if self.since_activate.q.val() >= self.t_rrd.val() {
+                    self.phy.cmd.next = SDRAMCommand::Active;
+                    self.phy.bank.next = self.addr_bank.val();
+                    self.phy.address.next = bit_cast::<15, R>(self.addr_row.val());
+                    for i in 0..8 {
+                        if self.addr_bank.val().index() == i {
+                            self.bank_open[i].d.next = true;
+                            self.bank_row[i].d.next = self.addr_row.val();
+                        }
+                    }
+                    self.since_activate.d.next = 0.into();
+                    self.delay_counter.d.next = 0.into();
+                    self.state.d.next = State::ActivateWait;
+                }
+            }
+            State::ActivateWait => {
+                if self.delay_counter.q.val() >= self.t_rcd.val() {
+                    if self.write_pending.q.val() {
+                        self.state.d.next = State::WriteCommand;
+                    } else {
+                        self.state.d.next = State::ReadCommand;
+                    }
+                }
+            }
+            State::ReadCommand => {
+                if (self.since_write.q.val() >= self.write_to_read.val())
+                    & (self.since_read.q.val() >= self.t_ccd.val())
+                {
+                    self.phy.cmd.next = SDRAMCommand::Read;
+                    self.phy.bank.next = self.addr_bank.val();
+                    self.phy.address.next = self.addr_col.val();
+                    self.since_read.d.next = 0.into();
+                    self.delay_counter.d.next = 0.into();
+                    self.transfer_counter.d.next = 0.into();
+                    self.state.d.next = State::ReadData;
+                }
+            }
+            State::ReadData => {
+                if self.delay_counter.q.val() >= self.read_delay.val() {
+                    // Shift in the two words of each clock from the top
+                    self.reg_data_read.d.next = (bit_cast::<L, D>(self.phy.read_fall.val())
+                        << self.fall_shift_in.val())
+                        | (bit_cast::<L, D>(self.phy.read_rise.val()) << self.rise_shift_in.val())
+                        | (self.reg_data_read.q.val() >> self.beat_bits.val());
+                    self.transfer_counter.d.next = self.transfer_counter.q.val() + 1;
+                    if self.transfer_counter.q.val() == 3 {
+                        self.read_pending.d.next = false;
+                        self.read_ready.d.next = true;
+                        self.state.d.next = State::Idle;
+                    }
+                }
+            }
+            State::WriteCommand => {
+                if (self.since_read.q.val() >= self.read_to_write.val())
+                    & (self.since_write.q.val() >= self.t_ccd.val())
+                {
+                    self.phy.cmd.next = SDRAMCommand::Write;
+                    self.phy.bank.next = self.addr_bank.val();
+                    self.phy.address.next = self.addr_col.val();
+                    self.since_write.d.next = 0.into();
+                    self.delay_counter.d.next = 0.into();
+                    self.transfer_counter.d.next = 0.into();
+                    self.state.d.next = State::WriteData;
+                }
+            }
+            State::WriteData => {
+                if self.delay_counter.q.val() >= self.write_delay.val() {
+                    self.phy.write_enable.next = true;
+                    self.reg_data_write.d.next =
+                        self.reg_data_write.q.val() >> self.beat_bits.val();
+                    self.transfer_counter.d.next = self.transfer_counter.q.val() + 1;
+                    if self.transfer_counter.q.val() == 3 {
+                        self.write_pending.d.next = false;
+                        self.state.d.next = State::Idle;
+                    }
+                }
+            }
+            _ => {
+                self.state.d.next = State::Reset;
+            }
+        }
+        // Handle the input command latching
+        if self.cmd_strobe.val() & !self.read_pending.q.val() & !self.write_pending.q.val() {
+            self.reg_address.d.next = self.cmd_address.val();
+            if self.write_not_read.val() {
+                self.write_pending.d.next = true;
+                self.reg_data_write.d.next = self.data_in.val();
+            } else {
+                self.read_pending.d.next = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::ddr_register::{DDRInputRegister, DDROutputRegister};
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::dff_with_init::DFFWithInit;
+use crate::sdram::cmd::{SDRAMCommand, SDRAMCommandEncoder};
+use crate::sdram::DDR3Driver;
+use rust_hdl_core::prelude::*;
+
+// Number of clocks (in addition to the CAS latency) from a read command on
+// the controller side of the PHY, to the first word of read data.  Write data
+// is sent `cas_write_latency` clocks after the write command.
+pub const DDR3_PHY_READ_LATENCY: u32 = 3;
+
+// The controller side of a DDR3 PHY.  There is one command per clock, and
+// two words of data per clock (`rise` goes out first).  A PHY registers the
+// commands and the write data before sending them to the device, and
+// presents the read data `DDR3_PHY_READ_LATENCY` clocks after the device
+// would have (so that the controller can count clocks from the command).
+#[derive(LogicInterface, Clone, Debug, Default)]
+#[join = "DDR3PHYDevice"]
+pub struct DDR3PHYDriver<const D: usize> {
+    pub reset_not: Signal<Out, Bit>,
+    pub cke: Signal<Out, Bit>,
+    pub cmd: Signal<Out, SDRAMCommand>,
+    pub bank: Signal<Out, Bits<3>>,
+    pub address: Signal<Out, Bits<15>>,
+    pub write_enable: Signal<Out, Bit>,
+    pub write_rise: Signal<Out, Bits<D>>,
+    pub write_fall: Signal<Out, Bits<D>>,
+    pub read_rise: Signal<In, Bits<D>>,
+    pub read_fall: Signal<In, Bits<D>>,
+}
+
+#[derive(LogicInterface, Clone, Debug, Default)]
+#[join = "DDR3PHYDriver"]
+pub struct DDR3PHYDevice<const D: usize> {
+    pub reset_not: Signal<In, Bit>,
+    pub cke: Signal<In, Bit>,
+    pub cmd: Signal<In, SDRAMCommand>,
+    pub bank: Signal<In, Bits<3>>,
+    pub address: Signal<In, Bits<15>>,
+    pub write_enable: Signal<In, Bit>,
+    pub write_rise: Signal<In, Bits<D>>,
+    pub write_fall: Signal<In, Bits<D>>,
+    pub read_rise: Signal<Out, Bits<D>>,
+    pub read_fall: Signal<Out, Bits<D>>,
+}
+
+// A DDR3 PHY built from the DDR registers, which map to the `ODDRX1F` and
+// `IDDRX1F` primitives when generated for the ECP5.  `clock90` must lag
+// `clock` by a quarter cycle (from a PLL).
+//
+// The clock to the device is inverted, so that the commands (launched on
+// the rising edge of `clock`) are centered on its rising edge.  The strobe
+// is edge aligned with the device clock, and the write data is launched on
+// `clock90`, so that it is centered on the strobe.  The read data is edge
+// aligned with the device clock, and is captured on the inverse of `clock90`.
+// There is no read leveling or delay calibration, so board skew must be
+// small compared with a quarter of the clock period.
+#[derive(LogicBlock)]
+pub struct DDR3PHY<const D: usize> {
+    pub clock: Signal<In, Clock>,
+    pub clock90: Signal<In, Clock>,
+    pub ctl: DDR3PHYDevice<D>,
+    pub ddr: DDR3Driver<D>,
+    encode: SDRAMCommandEncoder,
+    reset_not: DFF<Bit>,
+    cke: DFF<Bit>,
+    cs_not: DFFWithInit<Bit>,
+    ras_not: DFFWithInit<Bit>,
+    cas_not: DFFWithInit<Bit>,
+    we_not: DFFWithInit<Bit>,
+    bank: DFF<Bits<3>>,
+    address: DFF<Bits<15>>,
+    write_enable: DFF<Bit>,
+    drive: DFF<Bit>,
+    write_rise: DFF<Bits<D>>,
+    write_fall: DFF<Bits<D>>,
+    read_rise: DFF<Bits<D>>,
+    read_fall: DFF<Bits<D>>,
+    ck_out: DDROutputRegister<1>,
+    dqs_out: DDROutputRegister<1>,
+    dq_out: DDROutputRegister<D>,
+    dq_in: DDRInputRegister<D>,
+    capture_clock: Signal<Local, Clock>,
+}
+
+impl<const D: usize> Default for DDR3PHY<D> {
+    fn default() -> Self {
+        Self {
+            clock: Default::default(),
+            clock90: Default::default(),
+            ctl: Default::default(),
+            ddr: Default::default(),
+            encode: Default::default(),
+            reset_not: Default::default(),
+            cke: Default::default(),
+            // The command pins idle high (a NOP)
+            cs_not: DFFWithInit::new(true),
+            ras_not: DFFWithInit::new(true),
+            cas_not: DFFWithInit::new(true),
+            we_not: DFFWithInit::new(true),
+            bank: Default::default(),
+            address: Default::default(),
+            write_enable: Default::default(),
+            drive: Default::default(),
+            write_rise: Default::default(),
+            write_fall: Default::default(),
+            read_rise: Default::default(),
+            read_fall: Default::default(),
+            ck_out: Default::default(),
+            dqs_out: Default::default(),
+            dq_out: Default::default(),
+            dq_in: Default::default(),
+            capture_clock: Default::default(),
+        }
+    }
+}
+
+impl<const D: usize> Logic for DDR3PHY<D> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(
+            self,
+            clock,
+            reset_not,
+            cke,
+            cs_not,
+            ras_not,
+            cas_not,
+            we_not,
+            bank,
+            address,
+            write_enable,
+            drive,
+            write_rise,
+            write_fall,
+            read_rise,
+            read_fall
+        );
+        self.capture_clock.next = !self.clock90.val();
+        self.ck_out.clock.next = self.clock.val();
+        self.dqs_out.clock.next = self.clock.val();
+        self.dq_out.clock.next = self.clock90.val();
+        self.dq_in.clock.next = self.capture_clock.val();
+        // Register the command
+        self.encode.cmd.next = self.ctl.cmd.val();
+        self.reset_not.d.next = self.ctl.reset_not.val();
+        self.cke.d.next = self.ctl.cke.val();
+        self.cs_not.d.next = self.encode.cs_not.val();
+        self.ras_not.d.next = self.encode.ras_not.val();
+        self.cas_not.d.next = self.encode.cas_not.val();
+        self.we_not.d.next = self.encode.we_not.val();
+        self.bank.d.next = self.ctl.bank.val();
+        self.address.d.next = self.ctl.address.val();
+        self.ddr.reset_not.next = self.reset_not.q.val();
+        self.ddr.cke.next = self.cke.q.val();
+        self.ddr.odt.next = false;
+        self.ddr.cs_not.next = self.cs_not.q.val();
+        self.ddr.ras_not.next = self.ras_not.q.val();
+        self.ddr.cas_not.next = self.cas_not.q.val();
+        self.ddr.we_not.next = self.we_not.q.val();
+        self.ddr.bank.next = self.bank.q.val();
+        self.ddr.address.next = self.address.q.val();
+        // Forward the (inverted) clock
+        self.ck_out.d_rise.next = 0.into();
+        self.ck_out.d_fall.next = 1.into();
+        self.ddr.ck.next = self.ck_out.q.val().get_bit(0).into();
+        // The strobe toggles for each clock of write data
+        self.dqs_out.d_rise.next = 0.into();
+        self.dqs_out.d_fall.next = self.ctl.write_enable.val().into();
+        self.ddr.dqs.next = self.dqs_out.q.val().get_bit(0);
+        // Write data path.  The bus is driven for an extra clock, to cover
+        // the quarter cycle shift of the data.
+        self.write_enable.d.next = self.ctl.write_enable.val();
+        self.drive.d.next = self.write_enable.q.val();
+        self.write_rise.d.next = self.ctl.write_rise.val();
+        self.write_fall.d.next = self.ctl.write_fall.val();
+        self.dq_out.d_rise.next = self.write_rise.q.val();
+        self.dq_out.d_fall.next = self.write_fall.q.val();
+        self.ddr.write_data.next = self.dq_out.q.val();
+        self.ddr.write_enable.next = self.write_enable.q.val() | self.drive.q.val();
+        // Read data path
+        self.dq_in.d.next = self.ddr.read_data.val();
+        self.read_rise.d.next = self.dq_in.q_rise.val();
+        self.read_fall.d.next = self.dq_in.q_fall.val();
+        self.ctl.read_rise.next = self.read_rise.q.val();
+        self.ctl.read_fall.next = self.read_fall.q.val();
+    }
+}
+
+#[test]
+fn test_ddr3_phy_synthesizes() {
+    let mut uut = DDR3PHY::<16>::default();
+    uut.ctl.link_connect_dest();
+    uut.ddr.link_connect_dest();
+    uut.connect_all();
+    yosys_validate("ddr3_phy", &generate_verilog(&uut)).unwrap();
+}
@@ -0,0 +1,222 @@
+use crate::sdram::timings::nanos_to_clocks;
+
+// Timings for a DDR3 device.  As with [MemoryTimings], the times are given in
+// nanoseconds, and converted to clocks for the controller (and the simulator)
+// at the given clock speed.  Some minimums are given in clocks by the JEDEC
+// spec, and these are applied when converting.
+//
+// [MemoryTimings]: crate::sdram::timings::MemoryTimings
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DDR3Timings {
+    pub reset_low_nanoseconds: f64,
+    pub cke_delay_nanoseconds: f64,
+    pub t_rp_precharge_period_nanoseconds: f64,
+    pub t_rcd_row_to_column_min_time_nanoseconds: f64,
+    pub t_ras_row_active_min_time_nanoseconds: f64,
+    pub t_rc_row_to_row_min_time_nanoseconds: f64,
+    pub t_rrd_bank_to_bank_activate_min_time_nanoseconds: f64,
+    pub t_wr_write_recovery_time_nanoseconds: f64,
+    pub t_wtr_write_to_read_time_nanoseconds: f64,
+    pub t_rtp_read_to_precharge_time_nanoseconds: f64,
+    pub t_rfc_refresh_period_nanoseconds: f64,
+    pub t_refi_refresh_interval_nanoseconds: f64,
+    pub t_zqinit_clocks: u32,
+    pub cas_latency: u32,
+    pub cas_write_latency: u32,
+    pub clock_speed_hz: f64,
+    pub columns_per_bank: u32,
+    pub rows_per_bank: u32,
+}
+
+// Minimum mode register set to mode register set delay
+const T_MRD_CLOCKS: u16 = 4;
+// Minimum mode register set to other command delay
+const T_MOD_CLOCKS: u16 = 12;
+// Column command to column command delay (one BL8 burst)
+const T_CCD_CLOCKS: u16 = 4;
+
+impl DDR3Timings {
+    // Micron MT41K128M16 (2 Gb, x16), at up to 400 MHz (DDR3-800).
+    pub fn mt41k128m16(clock_speed_hz: f64) -> Self {
+        assert!(clock_speed_hz <= 400e6);
+        Self {
+            reset_low_nanoseconds: 200.0e3,
+            cke_delay_nanoseconds: 500.0e3,
+            t_rp_precharge_period_nanoseconds: 13.75,
+            t_rcd_row_to_column_min_time_nanoseconds: 13.75,
+            t_ras_row_active_min_time_nanoseconds: 35.0,
+            t_rc_row_to_row_min_time_nanoseconds: 48.75,
+            t_rrd_bank_to_bank_activate_min_time_nanoseconds: 10.0,
+            t_wr_write_recovery_time_nanoseconds: 15.0,
+            t_wtr_write_to_read_time_nanoseconds: 7.5,
+            t_rtp_read_to_precharge_time_nanoseconds: 7.5,
+            t_rfc_refresh_period_nanoseconds: 160.0,
+            t_refi_refresh_interval_nanoseconds: 7800.0,
+            t_zqinit_clocks: 512,
+            cas_latency: 6,
+            cas_write_latency: 5,
+            clock_speed_hz,
+            columns_per_bank: 1024, // 10 bits
+            rows_per_bank: 16384,   // 14 bits
+        }
+    }
+    // A small device with a short power up sequence, for simulation
+    pub fn fast_boot_sim(clock_speed_hz: f64) -> Self {
+        Self {
+            reset_low_nanoseconds: 500.0,
+            cke_delay_nanoseconds: 1000.0,
+            t_zqinit_clocks: 64,
+            columns_per_bank: 32,
+            rows_per_bank: 16,
+            ..Self::mt41k128m16(clock_speed_hz)
+        }
+    }
+    pub fn t_reset(&self) -> u32 {
+        long_nanos_to_clocks(self.reset_low_nanoseconds, self.clock_speed_hz)
+    }
+    pub fn t_cke(&self) -> u32 {
+        long_nanos_to_clocks(self.cke_delay_nanoseconds, self.clock_speed_hz)
+    }
+    // Exit reset to first command: max(5 clocks, tRFC + 10ns)
+    pub fn t_xpr(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_rfc_refresh_period_nanoseconds + 10.0,
+            self.clock_speed_hz,
+        )
+        .max(5)
+    }
+    pub fn t_mrd(&self) -> u16 {
+        T_MRD_CLOCKS
+    }
+    pub fn t_mod(&self) -> u16 {
+        T_MOD_CLOCKS
+    }
+    pub fn t_ccd(&self) -> u16 {
+        T_CCD_CLOCKS
+    }
+    pub fn t_zqinit(&self) -> u16 {
+        self.t_zqinit_clocks as u16
+    }
+    pub fn t_rp(&self) -> u16 {
+        nanos_to_clocks(self.t_rp_precharge_period_nanoseconds, self.clock_speed_hz)
+    }
+    pub fn t_rcd(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_rcd_row_to_column_min_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+    }
+    pub fn t_ras(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_ras_row_active_min_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+    }
+    pub fn t_rc(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_rc_row_to_row_min_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+    }
+    pub fn t_rrd(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_rrd_bank_to_bank_activate_min_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+        .max(4)
+    }
+    pub fn t_wr(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_wr_write_recovery_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+    }
+    pub fn t_wtr(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_wtr_write_to_read_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+        .max(4)
+    }
+    pub fn t_rtp(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_rtp_read_to_precharge_time_nanoseconds,
+            self.clock_speed_hz,
+        )
+        .max(4)
+    }
+    pub fn t_rfc(&self) -> u16 {
+        nanos_to_clocks(self.t_rfc_refresh_period_nanoseconds, self.clock_speed_hz)
+    }
+    pub fn t_refi(&self) -> u16 {
+        nanos_to_clocks(
+            self.t_refi_refresh_interval_nanoseconds,
+            self.clock_speed_hz,
+        )
+    }
+    // The write recovery and write to read times are measured from the end
+    // of the write burst, so these give the delay from the write command.
+    pub fn write_to_precharge(&self) -> u16 {
+        self.cas_write_latency as u16 + 4 + self.t_wr()
+    }
+    pub fn write_to_read(&self) -> u16 {
+        self.cas_write_latency as u16 + 4 + self.t_wtr()
+    }
+    // Read to write, leaving a clock for the bus to turn around
+    pub fn read_to_write(&self) -> u16 {
+        self.cas_latency as u16 + 6 - self.cas_write_latency as u16
+    }
+    // Write recovery in clocks, rounded up to a value MR0 can encode
+    pub fn write_recovery_clocks(&self) -> u32 {
+        match self.t_wr() {
+            0..=5 => 5,
+            6 => 6,
+            7 => 7,
+            8 => 8,
+            9..=10 => 10,
+            11..=12 => 12,
+            13..=14 => 14,
+            _ => 16,
+        }
+    }
+    // MR0: fixed BL8, sequential bursts, the CAS latency and write recovery,
+    // and a DLL reset.
+    pub fn mr0(&self) -> u32 {
+        assert!((5..=11).contains(&self.cas_latency));
+        let cl = (self.cas_latency - 4) << 4;
+        let wr = match self.write_recovery_clocks() {
+            16 => 0,
+            x if x <= 8 => x - 4,
+            x => x / 2,
+        } << 9;
+        let dll_reset = 1 << 8;
+        cl | wr | dll_reset
+    }
+    // MR1: DLL enabled, RZQ/6 output drive, on die termination disabled
+    pub fn mr1(&self) -> u32 {
+        0
+    }
+    // MR2: the CAS write latency
+    pub fn mr2(&self) -> u32 {
+        assert!((5..=8).contains(&self.cas_write_latency));
+        (self.cas_write_latency - 5) << 3
+    }
+    pub fn mr3(&self) -> u32 {
+        0
+    }
+}
+
+fn long_nanos_to_clocks(time_in_nanos: f64, clock_speed_hz: f64) -> u32 {
+    let clock_period_in_nanos = 1.0e9 / clock_speed_hz;
+    (time_in_nanos / clock_period_in_nanos).ceil() as u32
+}
+
+#[test]
+fn test_ddr3_mode_registers() {
+    let timings = DDR3Timings::mt41k128m16(400e6);
+    // CL = 6, WR = 6 (15ns at 2.5ns), DLL reset
+    assert_eq!(timings.mr0(), (2 << 4) | (2 << 9) | (1 << 8));
+    assert_eq!(timings.mr2(), 0);
+    assert_eq!(timings.t_rrd(), 4);
+    assert_eq!(timings.t_xpr(), 68);
+}
@@ -2,6 +2,9 @@ pub mod basic_controller;
 pub mod buffer;
 pub mod burst_controller;
 pub mod cmd;
+pub mod ddr3_controller;
+pub mod ddr3_phy;
+pub mod ddr3_timings;
 pub mod fifo_sdram;
 pub mod timings;
 
@@ -43,3 +46,47 @@ pub struct SDRAMDevice<const D: usize> {
     pub read_data: Signal<Out, Bits<D>>,
     pub write_enable: Signal<In, Bit>,
 }
+
+// The pins of a DDR3 device.  As with the SDR interface, the bidirectional
+// data bus is split into `write_data` and `read_data`, with `write_enable`
+// selecting the direction, so that the tristate buffers can be placed at the
+// top level.  The differential clock and strobe are given as single ended
+// signals (`ck_n` and `dqs_n` are their complements), and the data mask is
+// not used (tie `dm` low).  The strobe is only driven for writes.
+#[derive(LogicInterface, Clone, Debug, Default)]
+#[join = "DDR3Device"]
+pub struct DDR3Driver<const D: usize> {
+    pub ck: Signal<Out, Clock>,
+    pub reset_not: Signal<Out, Bit>,
+    pub cke: Signal<Out, Bit>,
+    pub odt: Signal<Out, Bit>,
+    pub cs_not: Signal<Out, Bit>,
+    pub ras_not: Signal<Out, Bit>,
+    pub cas_not: Signal<Out, Bit>,
+    pub we_not: Signal<Out, Bit>,
+    pub bank: Signal<Out, Bits<3>>,
+    pub address: Signal<Out, Bits<15>>,
+    pub dqs: Signal<Out, Bit>,
+    pub write_data: Signal<Out, Bits<D>>,
+    pub read_data: Signal<In, Bits<D>>,
+    pub write_enable: Signal<Out, Bit>,
+}
+
+#[derive(LogicInterface, Clone, Debug, Default)]
+#[join = "DDR3Driver"]
+pub struct DDR3Device<const D: usize> {
+    pub ck: Signal<In, Clock>,
+    pub reset_not: Signal<In, Bit>,
+    pub cke: Signal<In, Bit>,
+    pub odt: Signal<In, Bit>,
+    pub cs_not: Signal<In, Bit>,
+    pub ras_not: Signal<In, Bit>,
+    pub cas_not: Signal<In, Bit>,
+    pub we_not: Signal<In, Bit>,
+    pub bank: Signal<In, Bits<3>>,
+    pub address: Signal<In, Bits<15>>,
+    pub dqs: Signal<In, Bit>,
+    pub write_data: Signal<In, Bits<D>>,
+    pub read_data: Signal<Out, Bits<D>>,
+    pub write_enable: Signal<In, Bit>,
+}
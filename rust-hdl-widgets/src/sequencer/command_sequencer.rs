@@ -0,0 +1,387 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::i2c::i2c_bus::I2CBusDriver;
+use crate::i2c::i2c_controller::{I2CController, I2CControllerCmd};
+use crate::i2c::i2c_driver::I2CConfig;
+use crate::ramrom::ram::RAM;
+use crate::spi::master::{SPIConfig, SPIMaster, SPIWiresMaster};
+use rust_hdl_core::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum State {
+    Idle,
+    Fetch,
+    Decode,
+    Execute,
+    SPIWait,
+    I2CWait,
+    I2CIdle,
+    I2CAbort,
+    Branch,
+    Delay,
+    WaitInput,
+    Error,
+}
+
+/// Runs a program of SPI and I2C transactions, such as the register writes
+/// needed to bring up an ADC or a PLL chip.  The program is fetched from a
+/// [ROM](crate::ramrom::rom::ROM) or [SyncROM](crate::ramrom::sync_rom::SyncROM)
+/// of `2^A` 32 bit words connected to `program_address` and `program_data`,
+/// and is built with a [SequencerProgram](crate::sequencer::program::SequencerProgram),
+/// which also describes the instruction set.
+///
+/// Pulse `start` to run the program from the beginning.  When it reaches
+/// an `End` instruction, `done` pulses.  If an I2C transfer fails (or an
+/// unknown instruction turns up), the sequencer stops with `error` set
+/// until the next `start`.  Data read from the buses goes into 8 registers
+/// of 16 bits, which can be read on `reg_data` one clock after selecting
+/// them with `reg_address`.
+#[derive(LogicBlock)]
+pub struct CommandSequencer<const A: usize> {
+    pub clock: Signal<In, Clock>,
+    pub start: Signal<In, Bit>,
+    pub busy: Signal<Out, Bit>,
+    pub done: Signal<Out, Bit>,
+    pub error: Signal<Out, Bit>,
+    pub program_address: Signal<Out, Bits<A>>,
+    pub program_data: Signal<In, Bits<32>>,
+    pub inputs: Signal<In, Bits<8>>,
+    pub outputs: Signal<Out, Bits<8>>,
+    pub reg_address: Signal<In, Bits<3>>,
+    pub reg_data: Signal<Out, Bits<16>>,
+    pub spi: SPIWiresMaster,
+    pub i2c: I2CBusDriver,
+    spi_master: SPIMaster<16>,
+    i2c_controller: I2CController,
+    regs: RAM<Bits<16>, 3>,
+    host_regs: RAM<Bits<16>, 3>,
+    state: DFF<State>,
+    pc: DFF<Bits<A>>,
+    instruction: DFF<Bits<32>>,
+    delay: DFF<Bits<28>>,
+    loop_count: DFF<Bits<16>>,
+    outputs_flop: DFF<Bits<8>>,
+    opcode: Signal<Local, Bits<4>>,
+    target: Signal<Local, Bits<A>>,
+    flag: Signal<Local, Bit>,
+    reg_write: Signal<Local, Bit>,
+    reg_value: Signal<Local, Bits<16>>,
+}
+
+impl<const A: usize> CommandSequencer<A> {
+    pub fn new(spi_config: SPIConfig, i2c_config: I2CConfig) -> Self {
+        assert!(A <= 16, "Jump targets are limited to 16 bits");
+        Self {
+            clock: Default::default(),
+            start: Default::default(),
+            busy: Default::default(),
+            done: Default::default(),
+            error: Default::default(),
+            program_address: Default::default(),
+            program_data: Default::default(),
+            inputs: Default::default(),
+            outputs: Default::default(),
+            reg_address: Default::default(),
+            reg_data: Default::default(),
+            spi: Default::default(),
+            i2c: Default::default(),
+            spi_master: SPIMaster::new(spi_config),
+            i2c_controller: I2CController::new(i2c_config),
+            regs: Default::default(),
+            host_regs: Default::default(),
+            state: Default::default(),
+            pc: Default::default(),
+            instruction: Default::default(),
+            delay: Default::default(),
+            loop_count: Default::default(),
+            outputs_flop: Default::default(),
+            opcode: Default::default(),
+            target: Default::default(),
+            flag: Default::default(),
+            reg_write: Default::default(),
+            reg_value: Default::default(),
+        }
+    }
+}
+
+impl<const A: usize> Logic for CommandSequencer<A> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SPIWiresMaster::link(&mut self.spi, &mut self.spi_master.wires);
+        I2CBusDriver::link(&mut self.i2c, &mut self.i2c_controller.i2c);
+        clock!(self, clock, spi_master, i2c_controller);
+        self.regs.read_clock.next = self.clock.val();
+        self.regs.write_clock.next = self.clock.val();
+        self.host_regs.read_clock.next = self.clock.val();
+        self.host_regs.write_clock.next = self.clock.val();
+        dff_setup!(
+            self,
+            clock,
+            state,
+            pc,
+            instruction,
+            delay,
+            loop_count,
+            outputs_flop
+        );
+        // Decode the fields of the current instruction
+        self.opcode.next = self.instruction.q.val().get_bits::<4>(28);
+        self.target.next = self.instruction.q.val().get_bits::<A>(0);
+        self.flag.next = self.instruction.q.val().get_bit(27);
+        self.program_address.next = self.pc.q.val();
+        self.outputs.next = self.outputs_flop.q.val();
+        self.busy.next = (self.state.q.val() != State::Idle) & (self.state.q.val() != State::Error);
+        self.error.next = self.state.q.val() == State::Error;
+        self.done.next = false;
+        // The registers are kept in two RAMs, one read by the sequencer,
+        // and the other by the host
+        self.regs.read_address.next = self.instruction.q.val().get_bits::<3>(24);
+        self.host_regs.read_address.next = self.reg_address.val();
+        self.reg_data.next = self.host_regs.read_data.val();
+        self.reg_write.next = false;
+        self.reg_value.next = 0.into();
+        // The SPI transfer comes straight from the instruction
+        self.spi_master.data_outbound.next = self.instruction.q.val().get_bits::<16>(0);
+        self.spi_master.bits_outbound.next =
+            bit_cast::<16, 5>(self.instruction.q.val().get_bits::<5>(16));
+        self.spi_master.continued_transaction.next = self.flag.val();
+        self.spi_master.start_send.next = false;
+        self.i2c_controller.cmd.next = I2CControllerCmd::Noop;
+        self.i2c_controller.run.next = false;
+        self.i2c_controller.write_data_in.next = self.instruction.q.val().get_bits::<8>(0);
+        self.i2c_controller.address_10.next = 0.into();
+        match self.state.q.val() {
+            State::Idle => {
+                if self.start.val() {
+                    self.pc.d.next = 0.into();
+                    self.state.d.next = State::Fetch;
+                }
+            }
+            State::Fetch => {
+                // Gives a synchronous ROM time to read the instruction
+                self.state.d.next = State::Decode;
+            }
+            State::Decode => {
+                self.instruction.d.next = self.program_data.val();
+                self.pc.d.next = self.pc.q.val() + 1;
+                self.state.d.next = State::Execute;
+            }
+            State::Execute => {
+                self.state.d.next = State::Fetch;
+                match self.opcode.val().index() {
+                    0 => {
+                        // End
+                        self.done.next = true;
+                        self.state.d.next = State::Idle;
+                    }
+                    1 => {
+                        // SPITransfer
+                        self.state.d.next = State::Execute;
+                        if !self.spi_master.busy.val() {
+                            self.spi_master.start_send.next = true;
+                            self.state.d.next = State::SPIWait;
+                        }
+                    }
+                    2 => {
+                        // I2CBeginWrite
+                        self.state.d.next = State::Execute;
+                        if !self.i2c_controller.busy.val() {
+                            self.i2c_controller.cmd.next = I2CControllerCmd::BeginWrite;
+                            self.i2c_controller.run.next = true;
+                            self.state.d.next = State::I2CWait;
+                        }
+                    }
+                    3 => {
+                        // I2CBeginRead
+                        self.state.d.next = State::Execute;
+                        if !self.i2c_controller.busy.val() {
+                            self.i2c_controller.cmd.next = I2CControllerCmd::BeginRead;
+                            self.i2c_controller.run.next = true;
+                            self.state.d.next = State::I2CWait;
+                        }
+                    }
+                    4 => {
+                        // I2CWrite
+                        self.state.d.next = State::Execute;
+                        if !self.i2c_controller.busy.val() {
+                            self.i2c_controller.cmd.next = I2CControllerCmd::Write;
+                            self.i2c_controller.run.next = true;
+                            self.state.d.next = State::I2CWait;
+                        }
+                    }
+                    5 => {
+                        // I2CRead
+                        self.state.d.next = State::Execute;
+                        if !self.i2c_controller.busy.val() {
+                            if self.flag.val() {
+                                self.i2c_controller.cmd.next = I2CControllerCmd::ReadLast;
+                            } else {
+                                self.i2c_controller.cmd.next = I2CControllerCmd::Read;
+                            }
+                            self.i2c_controller.run.next = true;
+                            self.state.d.next = State::I2CWait;
+                        }
+                    }
+                    6 => {
+                        // I2CEnd
+                        self.state.d.next = State::Execute;
+                        if !self.i2c_controller.busy.val() {
+                            self.i2c_controller.cmd.next = I2CControllerCmd::EndTransmission;
+                            self.i2c_controller.run.next = true;
+                            self.state.d.next = State::I2CIdle;
+                        }
+                    }
+                    7 => {
+                        // Delay
+                        self.delay.d.next = self.instruction.q.val().get_bits::<28>(0);
+                        self.state.d.next = State::Delay;
+                    }
+                    8 => {
+                        // BranchBit - wait for the register to be read
+                        self.state.d.next = State::Branch;
+                    }
+                    9 => {
+                        // WaitInput
+                        self.state.d.next = State::WaitInput;
+                    }
+                    10 => {
+                        // SetLoop
+                        self.loop_count.d.next = self.instruction.q.val().get_bits::<16>(0);
+                    }
+                    11 => {
+                        // Loop
+                        self.loop_count.d.next = self.loop_count.q.val() - 1;
+                        if self.loop_count.q.val() != 1 {
+                            self.pc.d.next = self.target.val();
+                        }
+                    }
+                    12 => {
+                        // Jump
+                        self.pc.d.next = self.target.val();
+                    }
+                    13 => {
+                        // Output
+                        self.outputs_flop.d.next = self.instruction.q.val().get_bits::<8>(0);
+                    }
+                    _ => {
+                        self.state.d.next = State::Error;
+                    }
+                }
+            }
+            State::SPIWait => {
+                if self.spi_master.transfer_done.val() {
+                    self.reg_write.next = self.instruction.q.val().get_bit(23);
+                    self.reg_value.next = self.spi_master.data_inbound.val();
+                    self.state.d.next = State::Fetch;
+                }
+            }
+            State::I2CWait => {
+                if self.i2c_controller.ack.val() | self.i2c_controller.nack.val() {
+                    self.state.d.next = State::Fetch;
+                    if self.flag.val() {
+                        if self.i2c_controller.nack.val() {
+                            self.state.d.next = State::I2CAbort;
+                        }
+                    } else {
+                        // Record the NACK as a 1
+                        self.reg_write.next = true;
+                        self.reg_value.next =
+                            bit_cast::<16, 1>(self.i2c_controller.nack.val().into());
+                    }
+                }
+                if self.i2c_controller.read_valid.val() {
+                    self.reg_write.next = true;
+                    self.reg_value.next =
+                        bit_cast::<16, 8>(self.i2c_controller.read_data_out.val());
+                    self.state.d.next = State::Fetch;
+                }
+                if self.i2c_controller.error.val() | self.i2c_controller.arbitration_lost.val() {
+                    self.state.d.next = State::Error;
+                }
+            }
+            State::I2CIdle => {
+                if !self.i2c_controller.busy.val() {
+                    self.state.d.next = State::Fetch;
+                }
+            }
+            State::I2CAbort => {
+                // Release the bus before stopping
+                if !self.i2c_controller.busy.val() {
+                    self.i2c_controller.cmd.next = I2CControllerCmd::EndTransmission;
+                    self.i2c_controller.run.next = true;
+                    self.state.d.next = State::Error;
+                }
+            }
+            State::Branch => {
+                if self
+                    .regs
+                    .read_data
+                    .val()
+                    .get_bit(self.instruction.q.val().get_bits::<4>(16).index())
+                    == self.flag.val()
+                {
+                    self.pc.d.next = self.target.val();
+                }
+                self.state.d.next = State::Fetch;
+            }
+            State::Delay => {
+                if self.delay.q.val().any() {
+                    self.delay.d.next = self.delay.q.val() - 1;
+                } else {
+                    self.state.d.next = State::Fetch;
+                }
+            }
+            State::WaitInput => {
+                if self
+                    .inputs
+                    .val()
+                    .get_bit(self.instruction.q.val().get_bits::<3>(0).index())
+                    == self.flag.val()
+                {
+                    self.state.d.next = State::Fetch;
+                }
+            }
+            State::Error => {
+                // The I2C controller also needs a recovery before it can be used again
+                if self.i2c_controller.error.val() {
+                    self.i2c_controller.cmd.next = I2CControllerCmd::Recover;
+                    self.i2c_controller.run.next = true;
+                }
+                if self.start.val() {
+                    self.pc.d.next = 0.into();
+                    self.state.d.next = State::Fetch;
+                }
+            }
+            _ => {
+                self.state.d.next = State::Idle;
+            }
+        }
+        // Both copies of the registers are written together
+        self.regs.write_enable.next = self.reg_write.val();
+        self.regs.write_address.next = self.instruction.q.val().get_bits::<3>(24);
+        self.regs.write_data.next = self.reg_value.val();
+        self.host_regs.write_enable.next = self.reg_write.val();
+        self.host_regs.write_address.next = self.instruction.q.val().get_bits::<3>(24);
+        self.host_regs.write_data.next = self.reg_value.val();
+    }
+}
+
+#[test]
+fn test_command_sequencer_is_synthesizable() {
+    let spi_config = SPIConfig {
+        clock_speed: 48_000_000,
+        cs_off: true,
+        mosi_off: false,
+        speed_hz: 1_000_000,
+        cpha: true,
+        cpol: false,
+    };
+    let i2c_config = I2CConfig {
+        delay_time: std::time::Duration::from_micros(5),
+        clock_speed_hz: 48_000_000,
+        timeout: std::time::Duration::from_millis(25),
+    };
+    let mut uut = CommandSequencer::<8>::new(spi_config, i2c_config);
+    uut.connect_all();
+    yosys_validate("command_sequencer", &generate_verilog(&uut)).unwrap();
+}
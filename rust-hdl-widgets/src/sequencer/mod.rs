@@ -0,0 +1,2 @@
+pub mod command_sequencer;
+pub mod program;
@@ -0,0 +1,503 @@
+use crate::ramrom::rom::make_btree_from_iterable;
+use rust_hdl_core::prelude::*;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Errors found while assembling a [SequencerProgram]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SequencerError {
+    /// A label was used, but never placed
+    UnplacedLabel(usize),
+    /// A label was placed more than once
+    LabelPlacedTwice(usize),
+    /// The program does not fit in the ROM
+    ProgramTooLarge { words: usize, capacity: usize },
+    /// An instruction has a field that does not fit in its encoding
+    FieldOutOfRange {
+        index: usize,
+        field: &'static str,
+        value: u64,
+    },
+}
+
+/// A position in a [SequencerProgram], used as the target of jumps,
+/// branches and loops.  Labels can be used before they are placed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SequencerLabel(usize);
+
+/// The instructions understood by the sequencer.  Each one is encoded as a
+/// 32 bit word, with the opcode in the top 4 bits:
+///
+/// | Opcode | Instruction     | Fields                                                        |
+/// |--------|-----------------|---------------------------------------------------------------|
+/// | 0      | `End`           |                                                               |
+/// | 1      | `SPITransfer`   | `[27]` continued, `[26:24]` register, `[23]` store, `[20:16]` bits, `[15:0]` data |
+/// | 2      | `I2CBeginWrite` | `[27]` check, `[26:24]` register, `[6:0]` address             |
+/// | 3      | `I2CBeginRead`  | `[27]` check, `[26:24]` register, `[6:0]` address             |
+/// | 4      | `I2CWrite`      | `[27]` check, `[26:24]` register, `[7:0]` data                |
+/// | 5      | `I2CRead`       | `[27]` last, `[26:24]` register                               |
+/// | 6      | `I2CEnd`        |                                                               |
+/// | 7      | `Delay`         | `[27:0]` clock cycles                                         |
+/// | 8      | `BranchBit`     | `[27]` value, `[26:24]` register, `[19:16]` bit, `[15:0]` target |
+/// | 9      | `WaitInput`     | `[27]` value, `[2:0]` input                                   |
+/// | 10     | `SetLoop`       | `[15:0]` count                                                |
+/// | 11     | `Loop`          | `[15:0]` target                                               |
+/// | 12     | `Jump`          | `[15:0]` target                                               |
+/// | 13     | `Output`        | `[7:0]` value                                                 |
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SequencerOp {
+    /// Stop, and signal `done`
+    End,
+    /// Send the low `bits` (1 to 16) of `data` on the SPI bus.  If `store`
+    /// is set, the received bits go into `register`.  If `continued` is
+    /// set, the chip select stays on for the next transfer.
+    SPITransfer {
+        bits: u8,
+        data: u16,
+        continued: bool,
+        store: bool,
+        register: u8,
+    },
+    /// Start (or restart) an I2C write to `address`.  If `check` is set, a
+    /// NACK stops the program with an error.  Otherwise, the NACK (as a 1)
+    /// or ACK (as a 0) goes into `register`.
+    I2CBeginWrite {
+        address: u8,
+        check: bool,
+        register: u8,
+    },
+    /// Start (or restart) an I2C read from `address`.  The ACK is handled as
+    /// for [SequencerOp::I2CBeginWrite].
+    I2CBeginRead {
+        address: u8,
+        check: bool,
+        register: u8,
+    },
+    /// Write a byte on the I2C bus.  The ACK is handled as for
+    /// [SequencerOp::I2CBeginWrite].
+    I2CWrite { data: u8, check: bool, register: u8 },
+    /// Read a byte from the I2C bus into `register`.  The `last` byte is
+    /// NACKed, to end the read.
+    I2CRead { register: u8, last: bool },
+    /// Send an I2C stop
+    I2CEnd,
+    /// Wait for a number of clock cycles
+    Delay { cycles: u32 },
+    /// Go to `target` if `bit` of `register` equals `value`
+    BranchBit {
+        register: u8,
+        bit: u8,
+        value: bool,
+        target: SequencerLabel,
+    },
+    /// Wait until the `input` line equals `value`
+    WaitInput { input: u8, value: bool },
+    /// Set the loop counter
+    SetLoop { count: u16 },
+    /// Count down the loop counter, and go to `target` until it reaches zero
+    Loop { target: SequencerLabel },
+    /// Go to `target`
+    Jump { target: SequencerLabel },
+    /// Set the output lines
+    Output { value: u8 },
+}
+
+/// Builds the program for a [CommandSequencer](crate::sequencer::command_sequencer::CommandSequencer).
+/// For example, to enable writes on a SPI flash, and then wait for the
+/// write enable latch to show up in the status register:
+///
+/// ```
+/// # use rust_hdl_widgets::sequencer::program::SequencerProgram;
+/// let mut program = SequencerProgram::default();
+/// program.spi_write(8, 0x06);
+/// let poll = program.here();
+/// program
+///     .spi_write_continued(8, 0x05)
+///     .spi_read(8, 0, 0)
+///     .branch_if_clear(0, 1, poll)
+///     .end();
+/// let words = program.assemble().unwrap();
+/// assert_eq!(words.len(), 5);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SequencerProgram {
+    ops: Vec<SequencerOp>,
+    labels: Vec<Option<usize>>,
+    misplaced: Option<usize>,
+}
+
+impl SequencerProgram {
+    pub fn ops(&self) -> &[SequencerOp] {
+        &self.ops
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Add an instruction to the program
+    pub fn push(&mut self, op: SequencerOp) -> &mut Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Create a label, to be placed later with [SequencerProgram::place]
+    pub fn label(&mut self) -> SequencerLabel {
+        self.labels.push(None);
+        SequencerLabel(self.labels.len() - 1)
+    }
+
+    /// Place a label at the next instruction
+    pub fn place(&mut self, label: SequencerLabel) -> &mut Self {
+        if self.labels[label.0].is_some() {
+            // Reported when the program is assembled
+            self.misplaced.get_or_insert(label.0);
+        } else {
+            self.labels[label.0] = Some(self.ops.len());
+        }
+        self
+    }
+
+    /// Create a label at the next instruction
+    pub fn here(&mut self) -> SequencerLabel {
+        let label = self.label();
+        self.place(label);
+        label
+    }
+
+    pub fn spi_write(&mut self, bits: u8, data: u16) -> &mut Self {
+        self.spi(bits, data, false, None)
+    }
+
+    pub fn spi_write_continued(&mut self, bits: u8, data: u16) -> &mut Self {
+        self.spi(bits, data, true, None)
+    }
+
+    pub fn spi_read(&mut self, bits: u8, data: u16, register: u8) -> &mut Self {
+        self.spi(bits, data, false, Some(register))
+    }
+
+    pub fn spi_read_continued(&mut self, bits: u8, data: u16, register: u8) -> &mut Self {
+        self.spi(bits, data, true, Some(register))
+    }
+
+    fn spi(&mut self, bits: u8, data: u16, continued: bool, register: Option<u8>) -> &mut Self {
+        self.push(SequencerOp::SPITransfer {
+            bits,
+            data,
+            continued,
+            store: register.is_some(),
+            register: register.unwrap_or_default(),
+        })
+    }
+
+    pub fn i2c_begin_write(&mut self, address: u8) -> &mut Self {
+        self.push(SequencerOp::I2CBeginWrite {
+            address,
+            check: true,
+            register: 0,
+        })
+    }
+
+    pub fn i2c_begin_read(&mut self, address: u8) -> &mut Self {
+        self.push(SequencerOp::I2CBeginRead {
+            address,
+            check: true,
+            register: 0,
+        })
+    }
+
+    /// Address a target for writing, and put a 1 in `register` if it does
+    /// not respond (e.g., to poll an EEPROM for the end of a write cycle)
+    pub fn i2c_probe(&mut self, address: u8, register: u8) -> &mut Self {
+        self.push(SequencerOp::I2CBeginWrite {
+            address,
+            check: false,
+            register,
+        })
+    }
+
+    pub fn i2c_write(&mut self, data: u8) -> &mut Self {
+        self.push(SequencerOp::I2CWrite {
+            data,
+            check: true,
+            register: 0,
+        })
+    }
+
+    pub fn i2c_read(&mut self, register: u8) -> &mut Self {
+        self.push(SequencerOp::I2CRead {
+            register,
+            last: false,
+        })
+    }
+
+    pub fn i2c_read_last(&mut self, register: u8) -> &mut Self {
+        self.push(SequencerOp::I2CRead {
+            register,
+            last: true,
+        })
+    }
+
+    pub fn i2c_end(&mut self) -> &mut Self {
+        self.push(SequencerOp::I2CEnd)
+    }
+
+    pub fn delay(&mut self, cycles: u32) -> &mut Self {
+        self.push(SequencerOp::Delay { cycles })
+    }
+
+    /// Wait for (at least) `duration`, given the frequency of the sequencer clock
+    pub fn delay_for(&mut self, clock_speed_hz: u64, duration: Duration) -> &mut Self {
+        let cycles = (duration.as_nanos() * clock_speed_hz as u128).div_ceil(1_000_000_000);
+        self.delay(cycles.min(u32::MAX as u128) as u32)
+    }
+
+    pub fn branch_if_set(&mut self, register: u8, bit: u8, target: SequencerLabel) -> &mut Self {
+        self.push(SequencerOp::BranchBit {
+            register,
+            bit,
+            value: true,
+            target,
+        })
+    }
+
+    pub fn branch_if_clear(&mut self, register: u8, bit: u8, target: SequencerLabel) -> &mut Self {
+        self.push(SequencerOp::BranchBit {
+            register,
+            bit,
+            value: false,
+            target,
+        })
+    }
+
+    pub fn wait_input(&mut self, input: u8, value: bool) -> &mut Self {
+        self.push(SequencerOp::WaitInput { input, value })
+    }
+
+    /// Run the instructions from `target` up to here `count` times (use
+    /// [SequencerProgram::set_loop] ahead of `target` to set the count)
+    pub fn loop_to(&mut self, target: SequencerLabel) -> &mut Self {
+        self.push(SequencerOp::Loop { target })
+    }
+
+    pub fn set_loop(&mut self, count: u16) -> &mut Self {
+        self.push(SequencerOp::SetLoop { count })
+    }
+
+    pub fn jump(&mut self, target: SequencerLabel) -> &mut Self {
+        self.push(SequencerOp::Jump { target })
+    }
+
+    pub fn output(&mut self, value: u8) -> &mut Self {
+        self.push(SequencerOp::Output { value })
+    }
+
+    pub fn end(&mut self) -> &mut Self {
+        self.push(SequencerOp::End)
+    }
+
+    fn target(&self, label: SequencerLabel) -> Result<u64, SequencerError> {
+        self.labels[label.0]
+            .map(|x| x as u64)
+            .ok_or(SequencerError::UnplacedLabel(label.0))
+    }
+
+    /// Encode the program as 32 bit words
+    pub fn assemble(&self) -> Result<Vec<u32>, SequencerError> {
+        if let Some(label) = self.misplaced {
+            return Err(SequencerError::LabelPlacedTwice(label));
+        }
+        self.ops
+            .iter()
+            .enumerate()
+            .map(|(index, op)| self.encode(index, op))
+            .collect()
+    }
+
+    fn encode(&self, index: usize, op: &SequencerOp) -> Result<u32, SequencerError> {
+        let field = |field: &'static str, value: u64, bits: usize| {
+            if value >= (1 << bits) {
+                Err(SequencerError::FieldOutOfRange {
+                    index,
+                    field,
+                    value,
+                })
+            } else {
+                Ok(value as u32)
+            }
+        };
+        let register = |register: u8| Ok(field("register", register as u64, 3)? << 24);
+        let target = |label: SequencerLabel| field("target", self.target(label)?, 16);
+        let flag = |flag: bool| (flag as u32) << 27;
+        let opcode = |opcode: u32| opcode << 28;
+        Ok(match *op {
+            SequencerOp::End => opcode(0),
+            SequencerOp::SPITransfer {
+                bits,
+                data,
+                continued,
+                store,
+                register: reg,
+            } => {
+                if bits == 0 || bits > 16 {
+                    return Err(SequencerError::FieldOutOfRange {
+                        index,
+                        field: "bits",
+                        value: bits as u64,
+                    });
+                }
+                opcode(1)
+                    | flag(continued)
+                    | register(reg)?
+                    | (store as u32) << 23
+                    | (bits as u32) << 16
+                    | data as u32
+            }
+            SequencerOp::I2CBeginWrite {
+                address,
+                check,
+                register: reg,
+            } => opcode(2) | flag(check) | register(reg)? | field("address", address as u64, 7)?,
+            SequencerOp::I2CBeginRead {
+                address,
+                check,
+                register: reg,
+            } => opcode(3) | flag(check) | register(reg)? | field("address", address as u64, 7)?,
+            SequencerOp::I2CWrite {
+                data,
+                check,
+                register: reg,
+            } => opcode(4) | flag(check) | register(reg)? | data as u32,
+            SequencerOp::I2CRead {
+                register: reg,
+                last,
+            } => opcode(5) | flag(last) | register(reg)?,
+            SequencerOp::I2CEnd => opcode(6),
+            SequencerOp::Delay { cycles } => opcode(7) | field("cycles", cycles as u64, 28)?,
+            SequencerOp::BranchBit {
+                register: reg,
+                bit,
+                value,
+                target: label,
+            } => {
+                opcode(8)
+                    | flag(value)
+                    | register(reg)?
+                    | field("bit", bit as u64, 4)? << 16
+                    | target(label)?
+            }
+            SequencerOp::WaitInput { input, value } => {
+                opcode(9) | flag(value) | field("input", input as u64, 3)?
+            }
+            SequencerOp::SetLoop { count } => {
+                if count == 0 {
+                    return Err(SequencerError::FieldOutOfRange {
+                        index,
+                        field: "count",
+                        value: 0,
+                    });
+                }
+                opcode(10) | count as u32
+            }
+            SequencerOp::Loop { target: label } => opcode(11) | target(label)?,
+            SequencerOp::Jump { target: label } => opcode(12) | target(label)?,
+            SequencerOp::Output { value } => opcode(13) | value as u32,
+        })
+    }
+
+    /// The contents of a `2^A` word [ROM](crate::ramrom::rom::ROM) or
+    /// [SyncROM](crate::ramrom::sync_rom::SyncROM) that holds the program
+    pub fn rom<const A: usize>(&self) -> Result<BTreeMap<Bits<A>, Bits<32>>, SequencerError> {
+        let words = self.assemble()?;
+        if words.len() > (1 << A) {
+            return Err(SequencerError::ProgramTooLarge {
+                words: words.len(),
+                capacity: 1 << A,
+            });
+        }
+        Ok(make_btree_from_iterable(
+            words.into_iter().map(|x| (x as u64).to_bits()),
+        ))
+    }
+}
+
+#[test]
+fn test_sequencer_program_encoding() {
+    let mut program = SequencerProgram::default();
+    let done = program.label();
+    let top = program.here();
+    program
+        .spi_read_continued(16, 0x9F00, 3)
+        .i2c_probe(0x50, 2)
+        .i2c_read_last(5)
+        .delay(1000)
+        .branch_if_set(2, 0, top)
+        .set_loop(3)
+        .loop_to(done)
+        .wait_input(6, true)
+        .output(0xA5);
+    program.place(done).end();
+    assert_eq!(
+        program.assemble().unwrap(),
+        vec![
+            0x1B90_9F00,
+            0x2200_0050,
+            0x5D00_0000,
+            0x7000_03E8,
+            0x8A00_0000,
+            0xA000_0003,
+            0xB000_0009,
+            0x9800_0006,
+            0xD000_00A5,
+            0x0000_0000,
+        ]
+    );
+    let rom = program.rom::<4>().unwrap();
+    assert_eq!(rom.len(), 10);
+    let branch: Bits<4> = 4_u32.to_bits();
+    assert_eq!(rom[&branch], 0x8A00_0000_u64);
+    assert_eq!(
+        program.rom::<3>(),
+        Err(SequencerError::ProgramTooLarge {
+            words: 10,
+            capacity: 8
+        })
+    );
+}
+
+#[test]
+fn test_sequencer_program_errors() {
+    let mut program = SequencerProgram::default();
+    let nowhere = program.label();
+    program.jump(nowhere);
+    assert_eq!(program.assemble(), Err(SequencerError::UnplacedLabel(0)));
+    let mut program = SequencerProgram::default();
+    let top = program.here();
+    program.place(top).jump(top);
+    assert_eq!(program.assemble(), Err(SequencerError::LabelPlacedTwice(0)));
+    let mut program = SequencerProgram::default();
+    program.output(1).spi_write(17, 0);
+    assert_eq!(
+        program.assemble(),
+        Err(SequencerError::FieldOutOfRange {
+            index: 1,
+            field: "bits",
+            value: 17
+        })
+    );
+    let mut program = SequencerProgram::default();
+    program.i2c_read(8);
+    assert_eq!(
+        program.assemble(),
+        Err(SequencerError::FieldOutOfRange {
+            index: 0,
+            field: "register",
+            value: 8
+        })
+    );
+}
@@ -0,0 +1,382 @@
+use crate::dff::DFF;
+use crate::dff_setup;
+use crate::spi::master::SPIConfig;
+use crate::spi::qspi_master::{QSPIMaster, QSPIWidth, QSPIWiresMaster};
+use rust_hdl_core::prelude::*;
+
+/// Commands for the [SPIFlashController].  Assert `run` for one clock
+/// (while the controller is not `busy`) to start a command.
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+pub enum SPIFlashCmd {
+    Noop,
+    /// Read the JEDEC ID (manufacturer, memory type, capacity) into `jedec_id`
+    ReadId,
+    /// Read the status register into `status`
+    ReadStatus,
+    /// Poll the status register until the flash is no longer busy
+    WaitReady,
+    /// Erase the 4K sector that contains `address`, and wait for it to finish
+    SectorErase,
+    /// Enable writes, and start a page program at `address`
+    BeginProgram,
+    /// Program the byte on `write_data_in` at the next address of the page program
+    Write,
+    /// Finish the page program, and wait for it to complete
+    EndProgram,
+    /// Read the byte at `address` into `read_data_out`.  Sequential fetches
+    /// continue the read that is in progress, without sending a new command.
+    Fetch,
+    /// Finish the read that is in progress (any other command also does this)
+    EndRead,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum State {
+    Idle,
+    Dispatch,
+    Wait,
+    ChipSelectHigh,
+    IdDone,
+    StatusDone,
+    Poll,
+    PollCheck,
+    EraseCmd,
+    ProgramCmd,
+    FetchByte,
+    FetchDone,
+    FetchValid,
+}
+
+/// A controller for a (25 series) SPI NOR flash, built on a [QSPIMaster].
+/// It reads the JEDEC ID and the status register, erases sectors, programs
+/// pages a byte at a time, and reads with the fast read command.  A read is
+/// left open after each [SPIFlashCmd::Fetch], so that sequential fetches
+/// stream out of the flash in a continuous read, which makes the flash look
+/// like a (slow) byte wide ROM.  A controller built with [SPIFlashController::with_quad_read]
+/// reads with the fast read quad output command (0x6B) instead, which sends the
+/// command and address on one lane, and then (after 8 dummy clocks) reads the
+/// data on all four.  The dual reads (0x3B/0xBB) and the quad I/O read (0xEB)
+/// are not supported.
+#[derive(LogicBlock)]
+pub struct SPIFlashController {
+    pub wires: QSPIWiresMaster,
+    pub clock: Signal<In, Clock>,
+    pub cmd: Signal<In, SPIFlashCmd>,
+    pub run: Signal<In, Bit>,
+    pub busy: Signal<Out, Bit>,
+    pub address: Signal<In, Bits<24>>,
+    pub write_data_in: Signal<In, Bits<8>>,
+    pub read_data_out: Signal<Out, Bits<8>>,
+    pub read_valid: Signal<Out, Bit>,
+    pub jedec_id: Signal<Out, Bits<24>>,
+    pub status: Signal<Out, Bits<8>>,
+    spi: QSPIMaster<64>,
+    state: DFF<State>,
+    after: DFF<State>,
+    cmd_saved: DFF<SPIFlashCmd>,
+    address_saved: DFF<Bits<24>>,
+    write_data: DFF<Bits<8>>,
+    next_address: DFF<Bits<24>>,
+    read_open: DFF<Bit>,
+    lead_in: DFF<Bit>,
+    continued: DFF<Bit>,
+    id: DFF<Bits<24>>,
+    status_reg: DFF<Bits<8>>,
+    read_data: DFF<Bits<8>>,
+    cs_timer: DFF<Bits<16>>,
+    send: Signal<Local, Bit>,
+    send_bits: Signal<Local, Bits<16>>,
+    send_data: Signal<Local, Bits<64>>,
+    send_continued: Signal<Local, Bit>,
+    send_width: Signal<Local, QSPIWidth>,
+    send_receive: Signal<Local, Bit>,
+    send_dummy: Signal<Local, Bits<8>>,
+    cs_high_time: Constant<Bits<16>>,
+    quad: Constant<Bit>,
+}
+
+impl SPIFlashController {
+    pub fn new(config: SPIConfig) -> Self {
+        Self::build(config, false)
+    }
+    /// A controller that reads with the fast read quad output command
+    pub fn with_quad_read(config: SPIConfig) -> Self {
+        Self::build(config, true)
+    }
+    fn build(config: SPIConfig, quad: bool) -> Self {
+        // Keep the chip select off for at least one bit time between commands
+        let cs_high_time = (config.clock_speed / config.speed_hz).clamp(1, 0xFFFF);
+        Self {
+            wires: Default::default(),
+            clock: Default::default(),
+            cmd: Default::default(),
+            run: Default::default(),
+            busy: Default::default(),
+            address: Default::default(),
+            write_data_in: Default::default(),
+            read_data_out: Default::default(),
+            read_valid: Default::default(),
+            jedec_id: Default::default(),
+            status: Default::default(),
+            spi: QSPIMaster::new(config),
+            state: Default::default(),
+            after: Default::default(),
+            cmd_saved: Default::default(),
+            address_saved: Default::default(),
+            write_data: Default::default(),
+            next_address: Default::default(),
+            read_open: Default::default(),
+            lead_in: Default::default(),
+            continued: Default::default(),
+            id: Default::default(),
+            status_reg: Default::default(),
+            read_data: Default::default(),
+            cs_timer: Default::default(),
+            send: Default::default(),
+            send_bits: Default::default(),
+            send_data: Default::default(),
+            send_continued: Default::default(),
+            send_width: Default::default(),
+            send_receive: Default::default(),
+            send_dummy: Default::default(),
+            cs_high_time: Constant::new(cs_high_time.to_bits()),
+            quad: Constant::new(quad),
+        }
+    }
+}
+
+impl Logic for SPIFlashController {
+    #[hdl_gen]
+    fn update(&mut self) {
+        QSPIWiresMaster::link(&mut self.wires, &mut self.spi.wires);
+        clock!(self, clock, spi);
+        dff_setup!(
+            self,
+            clock,
+            state,
+            after,
+            cmd_saved,
+            address_saved,
+            write_data,
+            next_address,
+            read_open,
+            lead_in,
+            continued,
+            id,
+            status_reg,
+            read_data,
+            cs_timer
+        );
+        // Default values
+        self.busy.next = self.state.q.val() != State::Idle;
+        self.read_data_out.next = self.read_data.q.val();
+        self.read_valid.next = false;
+        self.jedec_id.next = self.id.q.val();
+        self.status.next = self.status_reg.q.val();
+        self.send.next = false;
+        self.send_bits.next = 0.into();
+        self.send_data.next = 0.into();
+        self.send_continued.next = false;
+        self.send_width.next = QSPIWidth::Single;
+        self.send_receive.next = false;
+        self.send_dummy.next = 0.into();
+        match self.state.q.val() {
+            State::Idle => {
+                if self.run.val() {
+                    self.cmd_saved.d.next = self.cmd.val();
+                    self.address_saved.d.next = self.address.val();
+                    self.write_data.d.next = self.write_data_in.val();
+                    self.state.d.next = State::Dispatch;
+                }
+            }
+            State::Dispatch => {
+                if self.read_open.q.val()
+                    & !((self.cmd_saved.q.val() == SPIFlashCmd::Fetch)
+                        & (self.address_saved.q.val() == self.next_address.q.val()))
+                {
+                    // Hang up on the read that is in progress, and then try again
+                    self.read_open.d.next = false;
+                    self.send.next = true;
+                    self.after.d.next = State::Dispatch;
+                } else {
+                    match self.cmd_saved.q.val() {
+                        SPIFlashCmd::ReadId => {
+                            self.send.next = true;
+                            self.send_bits.next = 32.into();
+                            self.send_data.next = bits::<64>(0x9F) << 24;
+                            self.after.d.next = State::IdDone;
+                        }
+                        SPIFlashCmd::ReadStatus => {
+                            self.send.next = true;
+                            self.send_bits.next = 16.into();
+                            self.send_data.next = 0x0500.into();
+                            self.after.d.next = State::StatusDone;
+                        }
+                        SPIFlashCmd::WaitReady => {
+                            self.state.d.next = State::Poll;
+                        }
+                        SPIFlashCmd::SectorErase => {
+                            // Write enable
+                            self.send.next = true;
+                            self.send_bits.next = 8.into();
+                            self.send_data.next = 0x06.into();
+                            self.after.d.next = State::EraseCmd;
+                        }
+                        SPIFlashCmd::BeginProgram => {
+                            // Write enable
+                            self.send.next = true;
+                            self.send_bits.next = 8.into();
+                            self.send_data.next = 0x06.into();
+                            self.after.d.next = State::ProgramCmd;
+                        }
+                        SPIFlashCmd::Write => {
+                            self.send.next = true;
+                            self.send_bits.next = 8.into();
+                            self.send_data.next = bit_cast::<64, 8>(self.write_data.q.val());
+                            self.send_continued.next = true;
+                            self.after.d.next = State::Idle;
+                        }
+                        SPIFlashCmd::EndProgram => {
+                            // Releasing the chip select starts the program cycle
+                            self.send.next = true;
+                            self.after.d.next = State::Poll;
+                        }
+                        SPIFlashCmd::Fetch => {
+                            if self.read_open.q.val() {
+                                self.state.d.next = State::FetchByte;
+                            } else if self.quad.val() {
+                                // Fast read quad output command and address.  The
+                                // dummy clocks come before the first byte of data.
+                                self.send.next = true;
+                                self.send_bits.next = 32.into();
+                                self.send_data.next = (bits::<64>(0x6B) << 24)
+                                    | bit_cast::<64, 24>(self.address_saved.q.val());
+                                self.send_continued.next = true;
+                                self.read_open.d.next = true;
+                                self.lead_in.d.next = true;
+                                self.after.d.next = State::FetchByte;
+                            } else {
+                                // Fast read command, address and 8 dummy bits
+                                self.send.next = true;
+                                self.send_bits.next = 40.into();
+                                self.send_data.next = (bits::<64>(0x0B) << 32)
+                                    | (bit_cast::<64, 24>(self.address_saved.q.val()) << 8);
+                                self.send_continued.next = true;
+                                self.read_open.d.next = true;
+                                self.after.d.next = State::FetchByte;
+                            }
+                        }
+                        _ => {
+                            self.state.d.next = State::Idle;
+                        }
+                    }
+                }
+            }
+            State::Wait => {
+                if self.spi.transfer_done.val() {
+                    if self.continued.q.val() {
+                        self.state.d.next = self.after.q.val();
+                    } else {
+                        self.cs_timer.d.next = 0.into();
+                        self.state.d.next = State::ChipSelectHigh;
+                    }
+                }
+            }
+            State::ChipSelectHigh => {
+                self.cs_timer.d.next = self.cs_timer.q.val() + 1;
+                if self.cs_timer.q.val() == self.cs_high_time.val() {
+                    self.state.d.next = self.after.q.val();
+                }
+            }
+            State::IdDone => {
+                self.id.d.next = self.spi.data_inbound.val().get_bits::<24>(0);
+                self.state.d.next = State::Idle;
+            }
+            State::StatusDone => {
+                self.status_reg.d.next = self.spi.data_inbound.val().get_bits::<8>(0);
+                self.state.d.next = State::Idle;
+            }
+            State::Poll => {
+                self.send.next = true;
+                self.send_bits.next = 16.into();
+                self.send_data.next = 0x0500.into();
+                self.after.d.next = State::PollCheck;
+            }
+            State::PollCheck => {
+                self.status_reg.d.next = self.spi.data_inbound.val().get_bits::<8>(0);
+                if self.spi.data_inbound.val().get_bit(0) {
+                    self.state.d.next = State::Poll;
+                } else {
+                    self.state.d.next = State::Idle;
+                }
+            }
+            State::EraseCmd => {
+                self.send.next = true;
+                self.send_bits.next = 32.into();
+                self.send_data.next =
+                    (bits::<64>(0x20) << 24) | bit_cast::<64, 24>(self.address_saved.q.val());
+                self.after.d.next = State::Poll;
+            }
+            State::ProgramCmd => {
+                self.send.next = true;
+                self.send_bits.next = 32.into();
+                self.send_data.next =
+                    (bits::<64>(0x02) << 24) | bit_cast::<64, 24>(self.address_saved.q.val());
+                self.send_continued.next = true;
+                self.after.d.next = State::Idle;
+            }
+            State::FetchByte => {
+                self.send.next = true;
+                self.send_bits.next = 8.into();
+                self.send_continued.next = true;
+                if self.quad.val() {
+                    self.send_width.next = QSPIWidth::Quad;
+                    self.send_receive.next = true;
+                    if self.lead_in.q.val() {
+                        self.send_dummy.next = 8.into();
+                    }
+                }
+                self.lead_in.d.next = false;
+                self.after.d.next = State::FetchDone;
+            }
+            State::FetchDone => {
+                self.read_data.d.next = self.spi.data_inbound.val().get_bits::<8>(0);
+                self.next_address.d.next = self.address_saved.q.val() + 1;
+                self.state.d.next = State::FetchValid;
+            }
+            State::FetchValid => {
+                self.read_valid.next = true;
+                self.state.d.next = State::Idle;
+            }
+            _ => {
+                self.state.d.next = State::Idle;
+            }
+        }
+        // Start a transfer on the SPI bus
+        self.spi.start_send.next = self.send.val();
+        self.spi.bits_outbound.next = self.send_bits.val();
+        self.spi.data_outbound.next = self.send_data.val();
+        self.spi.continued_transaction.next = self.send_continued.val();
+        self.spi.width.next = self.send_width.val();
+        self.spi.receive.next = self.send_receive.val();
+        self.spi.dummy_cycles.next = self.send_dummy.val();
+        if self.send.val() {
+            self.continued.d.next = self.send_continued.val();
+            self.state.d.next = State::Wait;
+        }
+    }
+}
+
+#[test]
+fn test_spi_flash_controller_synthesizes() {
+    let mut uut = SPIFlashController::new(SPIConfig {
+        clock_speed: 48_000_000,
+        cs_off: true,
+        mosi_off: true,
+        speed_hz: 1_000_000,
+        cpha: true,
+        cpol: true,
+    });
+    uut.connect_all();
+    yosys_validate("spi_flash_controller", &generate_verilog(&uut)).unwrap();
+}
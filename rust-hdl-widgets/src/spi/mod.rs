@@ -1,4 +1,7 @@
+pub mod flash;
 pub mod master;
 pub mod master_dynamic_mode;
 pub mod mux;
+pub mod qspi_master;
+pub mod qspi_slave;
 pub mod slave;
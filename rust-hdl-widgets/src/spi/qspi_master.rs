@@ -0,0 +1,402 @@
+use crate::spi::master::{SPIConfig, SPIWiresSlave};
+use crate::tristate::TristateBuffer;
+use crate::{dff::DFF, dff_setup, dff_with_init::DFFWithInit, strobe::Strobe};
+use rust_hdl_core::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum QSPIState {
+    Idle,
+    Dwell,
+    LoadBit,
+    MActive,
+    SampleMISO,
+    MIdle,
+    Finish,
+}
+
+/// The number of data lanes used by a transfer on a [QSPIWiresMaster] bus.
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+pub enum QSPIWidth {
+    /// Regular SPI - `io0` carries MOSI and `io1` carries MISO
+    Single,
+    /// Two bits per clock on `io0` and `io1`, in one direction
+    Dual,
+    /// Four bits per clock on `io0` through `io3`, in one direction
+    Quad,
+}
+
+/// The wires of a quad SPI bus.  The data lanes are bidirectional.  In
+/// single and dual transfers, the master drives `io2` (WP#) and `io3`
+/// (HOLD#) high.
+#[derive(LogicInterface, Default)]
+#[join = "QSPIWiresSlave"]
+pub struct QSPIWiresMaster {
+    pub io0: Signal<InOut, Bit>,
+    pub io1: Signal<InOut, Bit>,
+    pub io2: Signal<InOut, Bit>,
+    pub io3: Signal<InOut, Bit>,
+    pub msel: Signal<Out, Bit>,
+    pub mclk: Signal<Out, Bit>,
+}
+
+#[derive(LogicInterface, Default)]
+#[join = "QSPIWiresMaster"]
+pub struct QSPIWiresSlave {
+    pub io0: Signal<InOut, Bit>,
+    pub io1: Signal<InOut, Bit>,
+    pub io2: Signal<InOut, Bit>,
+    pub io3: Signal<InOut, Bit>,
+    pub msel: Signal<In, Bit>,
+    pub mclk: Signal<In, Bit>,
+}
+
+/// A SPI master that can also transfer data on two or four lanes.  Each
+/// transfer is like one of a [SPIMaster](crate::spi::master::SPIMaster),
+/// with a `width` that selects the number of lanes, and a `receive` flag
+/// that turns the lanes around (for dual and quad transfers) so that the
+/// slave can drive them.  A transfer starts with `dummy_cycles` clocks in
+/// which no data is exchanged, which gives the slave time to turn the lanes
+/// around.  Chaining transfers with `continued_transaction` changes modes
+/// within a single transaction - for example, a single lane command and
+/// address, followed by dummy cycles and quad lane read data.  The number
+/// of bits must be a multiple of the number of lanes.
+#[derive(LogicBlock)]
+pub struct QSPIMaster<const N: usize> {
+    pub clock: Signal<In, Clock>,
+    pub bits_outbound: Signal<In, Bits<16>>,
+    pub data_outbound: Signal<In, Bits<N>>,
+    pub data_inbound: Signal<Out, Bits<N>>,
+    pub start_send: Signal<In, Bit>,
+    pub transfer_done: Signal<Out, Bit>,
+    pub continued_transaction: Signal<In, Bit>,
+    pub width: Signal<In, QSPIWidth>,
+    pub receive: Signal<In, Bit>,
+    pub dummy_cycles: Signal<In, Bits<8>>,
+    pub busy: Signal<Out, Bit>,
+    pub wires: QSPIWiresMaster,
+    io0: TristateBuffer<Bit>,
+    io1: TristateBuffer<Bit>,
+    io2: TristateBuffer<Bit>,
+    io3: TristateBuffer<Bit>,
+    register_out: DFF<Bits<N>>,
+    register_in: DFF<Bits<N>>,
+    state: DFF<QSPIState>,
+    strobe: Strobe<32>,
+    pointer: DFF<Bits<16>>,
+    pointerm: Signal<Local, Bits<16>>,
+    step: Signal<Local, Bits<16>>,
+    lanes_in: Signal<Local, Bits<4>>,
+    width_save: DFF<QSPIWidth>,
+    receive_save: DFF<Bit>,
+    dummy: DFF<Bits<8>>,
+    dummy_cycle: DFF<Bit>,
+    lanes_out: DFF<Bits<4>>,
+    lanes_drive: DFF<Bits<4>>,
+    clock_state: DFF<Bit>,
+    done_flop: DFF<Bit>,
+    msel_flop: DFFWithInit<Bit>,
+    continued_save: DFF<Bit>,
+    cs_off: Constant<Bit>,
+    lanes_off: Constant<Bits<4>>,
+    cpha: Constant<Bit>,
+    cpol: Constant<Bit>,
+}
+
+impl<const N: usize> QSPIMaster<N> {
+    pub fn new(config: SPIConfig) -> Self {
+        assert!(8 * config.speed_hz <= config.clock_speed);
+        // Between transactions, io0 (MOSI) idles at mosi_off, and
+        // io2 (WP#) and io3 (HOLD#) are held high
+        let lanes_off = if config.mosi_off {
+            0b1101_u8
+        } else {
+            0b1100_u8
+        };
+        Self {
+            clock: Default::default(),
+            bits_outbound: Default::default(),
+            data_outbound: Default::default(),
+            data_inbound: Default::default(),
+            start_send: Default::default(),
+            transfer_done: Default::default(),
+            continued_transaction: Default::default(),
+            width: Default::default(),
+            receive: Default::default(),
+            dummy_cycles: Default::default(),
+            busy: Default::default(),
+            wires: Default::default(),
+            io0: Default::default(),
+            io1: Default::default(),
+            io2: Default::default(),
+            io3: Default::default(),
+            register_out: Default::default(),
+            register_in: Default::default(),
+            state: Default::default(),
+            strobe: Strobe::new(config.clock_speed, 4.0 * config.speed_hz as f64),
+            pointer: Default::default(),
+            pointerm: Default::default(),
+            step: Default::default(),
+            lanes_in: Default::default(),
+            width_save: Default::default(),
+            receive_save: Default::default(),
+            dummy: Default::default(),
+            dummy_cycle: Default::default(),
+            lanes_out: Default::default(),
+            lanes_drive: Default::default(),
+            clock_state: Default::default(),
+            done_flop: Default::default(),
+            msel_flop: DFFWithInit::new(config.cs_off),
+            continued_save: Default::default(),
+            cs_off: Constant::new(config.cs_off),
+            lanes_off: Constant::new(lanes_off.to_bits()),
+            cpha: Constant::new(config.cpha),
+            cpol: Constant::new(config.cpol),
+        }
+    }
+}
+
+impl<const N: usize> Logic for QSPIMaster<N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        // Setup the internals
+        dff_setup!(
+            self,
+            clock,
+            register_out,
+            register_in,
+            state,
+            pointer,
+            width_save,
+            receive_save,
+            dummy,
+            dummy_cycle,
+            lanes_out,
+            lanes_drive,
+            clock_state,
+            done_flop,
+            msel_flop,
+            continued_save
+        );
+        clock!(self, clock, strobe);
+        // Activate the baud strobe
+        self.strobe.enable.next = true;
+        // Connect the data lanes to the tristate buffers
+        Signal::<InOut, Bit>::link(&mut self.wires.io0, &mut self.io0.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io1, &mut self.io1.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io2, &mut self.io2.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io3, &mut self.io3.bus);
+        self.io0.write_data.next = self.lanes_out.q.val().get_bit(0);
+        self.io1.write_data.next = self.lanes_out.q.val().get_bit(1);
+        self.io2.write_data.next = self.lanes_out.q.val().get_bit(2);
+        self.io3.write_data.next = self.lanes_out.q.val().get_bit(3);
+        self.io0.write_enable.next = self.lanes_drive.q.val().get_bit(0);
+        self.io1.write_enable.next = self.lanes_drive.q.val().get_bit(1);
+        self.io2.write_enable.next = self.lanes_drive.q.val().get_bit(2);
+        self.io3.write_enable.next = self.lanes_drive.q.val().get_bit(3);
+        self.lanes_in.next = bit_cast::<4, 1>(self.io0.read_data.val().into())
+            | (bit_cast::<4, 1>(self.io1.read_data.val().into()) << 1)
+            | (bit_cast::<4, 1>(self.io2.read_data.val().into()) << 2)
+            | (bit_cast::<4, 1>(self.io3.read_data.val().into()) << 3);
+        // Connect the rest of the SPI lines to the flops
+        self.wires.mclk.next = self.clock_state.q.val();
+        self.wires.msel.next = self.msel_flop.q.val();
+        // Connect the output signals to the internal registers
+        self.data_inbound.next = self.register_in.q.val();
+        self.transfer_done.next = self.done_flop.q.val();
+        self.done_flop.d.next = false;
+        // The number of bits moved on each clock
+        self.step.next = 1.into();
+        if self.width_save.q.val() == QSPIWidth::Dual {
+            self.step.next = 2.into();
+        }
+        if self.width_save.q.val() == QSPIWidth::Quad {
+            self.step.next = 4.into();
+        }
+        self.pointerm.next = self.pointer.q.val() - self.step.val();
+        self.busy.next = true;
+        // The main state machine
+        match self.state.q.val() {
+            QSPIState::Idle => {
+                self.busy.next = false;
+                self.clock_state.d.next = self.cpol.val();
+                if self.start_send.val() {
+                    // Capture the outgoing data in our register
+                    self.register_out.d.next = self.data_outbound.val();
+                    self.state.d.next = QSPIState::Dwell; // Transition to the DWELL state
+                    self.pointer.d.next = self.bits_outbound.val(); // set bit pointer to number of bit to send (1 based)
+                    self.register_in.d.next = 0.into(); // Clear out the input store register
+                    self.msel_flop.d.next = !self.cs_off.val(); // Activate the chip select
+                    self.continued_save.d.next = self.continued_transaction.val();
+                    self.width_save.d.next = self.width.val();
+                    self.receive_save.d.next = self.receive.val();
+                    self.dummy.d.next = self.dummy_cycles.val();
+                    // Turn the lanes around (if needed) before the first clock edge
+                    match self.width.val() {
+                        QSPIWidth::Single => {
+                            self.lanes_drive.d.next = 0b1101.into();
+                        }
+                        QSPIWidth::Dual => {
+                            if self.receive.val() {
+                                self.lanes_drive.d.next = 0b1100.into();
+                            } else {
+                                self.lanes_drive.d.next = 0b1111.into();
+                            }
+                        }
+                        _ => {
+                            if self.receive.val() {
+                                self.lanes_drive.d.next = 0b0000.into();
+                            } else {
+                                self.lanes_drive.d.next = 0b1111.into();
+                            }
+                        }
+                    }
+                } else if !self.continued_save.q.val() {
+                    self.msel_flop.d.next = self.cs_off.val(); // Set the chip select signal to be "off"
+                    self.lanes_drive.d.next = 0b1101.into();
+                    self.lanes_out.d.next = self.lanes_off.val();
+                }
+            }
+            QSPIState::Dwell => {
+                if self.strobe.strobe.val() {
+                    // Dwell timeout has reached zero
+                    self.state.d.next = QSPIState::LoadBit; // Transition to the loadbit state
+                }
+            }
+            QSPIState::LoadBit => {
+                self.dummy_cycle.d.next = false;
+                if self.dummy.q.val().any() {
+                    // A clock cycle with no data
+                    self.dummy.d.next = self.dummy.q.val() - 1;
+                    self.dummy_cycle.d.next = true;
+                    self.state.d.next = QSPIState::MActive;
+                    self.clock_state.d.next = self.cpol.val() ^ self.cpha.val();
+                } else if self.pointer.q.val().any() {
+                    // We have data to send.  Fetch the next bits out of the register
+                    match self.width_save.q.val() {
+                        QSPIWidth::Single => {
+                            self.lanes_out.d.next = bits::<4>(0b1100)
+                                | bit_cast::<4, 1>(
+                                    self.register_out
+                                        .q
+                                        .val()
+                                        .get_bit(self.pointerm.val().index())
+                                        .into(),
+                                );
+                        }
+                        QSPIWidth::Dual => {
+                            self.lanes_out.d.next = bits::<4>(0b1100)
+                                | bit_cast::<4, 2>(
+                                    self.register_out
+                                        .q
+                                        .val()
+                                        .get_bits::<2>(self.pointerm.val().index()),
+                                );
+                        }
+                        _ => {
+                            self.lanes_out.d.next = self
+                                .register_out
+                                .q
+                                .val()
+                                .get_bits::<4>(self.pointerm.val().index());
+                        }
+                    }
+                    self.pointer.d.next = self.pointerm.val(); // Decrement the pointer
+                    self.state.d.next = QSPIState::MActive; // Move to the hold mclock low state
+                    self.clock_state.d.next = self.cpol.val() ^ self.cpha.val();
+                } else {
+                    self.clock_state.d.next = self.cpol.val();
+                    self.state.d.next = QSPIState::Finish; // No data, go back to idle
+                }
+            }
+            QSPIState::MActive => {
+                if self.strobe.strobe.val() {
+                    self.state.d.next = QSPIState::SampleMISO;
+                }
+            }
+            QSPIState::SampleMISO => {
+                if !self.dummy_cycle.q.val() {
+                    match self.width_save.q.val() {
+                        QSPIWidth::Single => {
+                            self.register_in.d.next = (self.register_in.q.val() << 1)
+                                | bit_cast::<N, 1>(self.lanes_in.val().get_bit(1).into());
+                        }
+                        QSPIWidth::Dual => {
+                            self.register_in.d.next = (self.register_in.q.val() << 2)
+                                | bit_cast::<N, 2>(self.lanes_in.val().get_bits::<2>(0));
+                        }
+                        _ => {
+                            self.register_in.d.next = (self.register_in.q.val() << 4)
+                                | bit_cast::<N, 4>(self.lanes_in.val());
+                        }
+                    }
+                }
+                self.clock_state.d.next = !self.clock_state.q.val();
+                self.state.d.next = QSPIState::MIdle;
+            }
+            QSPIState::MIdle => {
+                if self.strobe.strobe.val() {
+                    self.state.d.next = QSPIState::LoadBit;
+                }
+            }
+            QSPIState::Finish => {
+                if self.strobe.strobe.val() {
+                    self.state.d.next = QSPIState::Idle;
+                    self.done_flop.d.next = true;
+                }
+            }
+            _ => {
+                self.state.d.next = QSPIState::Idle;
+            }
+        }
+    }
+}
+
+/// Connects a (single lane) [SPIMaster](crate::spi::master::SPIMaster) to a
+/// device on a quad SPI bus.  MOSI drives `io0`, MISO is read from `io1`, and
+/// `io2` (WP#) and `io3` (HOLD#) are held high, as a quad SPI device expects
+/// when it is used in single lane mode.
+#[derive(LogicBlock, Default)]
+pub struct QSPISingleLane {
+    pub spi: SPIWiresSlave,
+    pub wires: QSPIWiresMaster,
+    io0: TristateBuffer<Bit>,
+    io1: TristateBuffer<Bit>,
+    io2: TristateBuffer<Bit>,
+    io3: TristateBuffer<Bit>,
+}
+
+impl Logic for QSPISingleLane {
+    #[hdl_gen]
+    fn update(&mut self) {
+        Signal::<InOut, Bit>::link(&mut self.wires.io0, &mut self.io0.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io1, &mut self.io1.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io2, &mut self.io2.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io3, &mut self.io3.bus);
+        self.io0.write_enable.next = true;
+        self.io0.write_data.next = self.spi.mosi.val();
+        self.io1.write_enable.next = false;
+        self.io1.write_data.next = false;
+        self.io2.write_enable.next = true;
+        self.io2.write_data.next = true;
+        self.io3.write_enable.next = true;
+        self.io3.write_data.next = true;
+        self.spi.miso.next = self.io1.read_data.val();
+        self.wires.msel.next = self.spi.msel.val();
+        self.wires.mclk.next = self.spi.mclk.val();
+    }
+}
+
+#[test]
+fn test_qspi_master_is_synthesizable() {
+    let config = SPIConfig {
+        clock_speed: 48_000_000,
+        cs_off: true,
+        mosi_off: true,
+        speed_hz: 1_000_000,
+        cpha: true,
+        cpol: true,
+    };
+    let mut dev = QSPIMaster::<64>::new(config);
+    dev.connect_all();
+    yosys_validate("qspi_master", &generate_verilog(&dev)).unwrap();
+}
@@ -0,0 +1,390 @@
+use crate::edge_detector::EdgeDetector;
+use crate::spi::master::SPIConfig;
+use crate::spi::qspi_master::{QSPIWidth, QSPIWiresSlave};
+use crate::synchronizer::BitSynchronizer;
+use crate::tristate::TristateBuffer;
+use crate::{dff::DFF, dff_setup};
+use rust_hdl_core::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Debug, LogicState)]
+enum QSPISlaveState {
+    Boot,
+    Idle,
+    Armed,
+    Capture,
+    Hold,
+    Update,
+    Settle,
+    Waiting,
+    Hangup,
+}
+
+/// The [QSPISlave] is the counterpart of the [QSPIMaster](crate::spi::qspi_master::QSPIMaster),
+/// and is mostly meant for testing it (or for modelling quad SPI devices
+/// in simulation).  It works like the [SPISlave](crate::spi::slave::SPISlave),
+/// except that each transfer has a `width`, and for dual and quad
+/// transfers, a `transmit` flag that says which end drives the data lanes.
+/// In single lane transfers, `io1` (MISO) is driven while the slave is
+/// selected.  The same caveats as for the [SPISlave](crate::spi::slave::SPISlave) apply.
+#[derive(LogicBlock)]
+pub struct QSPISlave<const N: usize> {
+    /// The clock driving the [QSPISlave]
+    pub clock: Signal<In, Clock>,
+    /// The bus connecting us to the [QSPIMaster](crate::spi::qspi_master::QSPIMaster).
+    pub wires: QSPIWiresSlave,
+    /// Indicates the [QSPISlave] is busy (typically, receiving data from the master).
+    pub busy: Signal<Out, Bit>,
+    /// Data received from the master is output on these wires.
+    pub data_inbound: Signal<Out, Bits<N>>,
+    /// Assert for a single cycle to latch the data to be sent back to the master.  Latches
+    /// `data_outbound`, `bits`, `width`, `transmit`, `dummy_cycles` and `continued_transaction` when asserted.
+    pub start_send: Signal<In, Bit>,
+    /// Data destined for the master on the next transaction.
+    pub data_outbound: Signal<In, Bits<N>>,
+    /// Number of bits to transfer.  Must be a multiple of the number of lanes.
+    pub bits: Signal<In, Bits<16>>,
+    /// The number of lanes used by the transfer.
+    pub width: Signal<In, QSPIWidth>,
+    /// For dual and quad transfers, set this to drive the lanes with `data_outbound`.
+    pub transmit: Signal<In, Bit>,
+    /// The number of clocks at the start of the transfer that carry no data.
+    pub dummy_cycles: Signal<In, Bits<8>>,
+    /// Set this to true to indicate that the next transaction will be continued from this one (i.e., do not hangup at the end).
+    pub continued_transaction: Signal<In, Bit>,
+    /// A flag that indicates the inbound data is valid.
+    pub transfer_done: Signal<Out, Bit>,
+    io0: TristateBuffer<Bit>,
+    io1: TristateBuffer<Bit>,
+    io2: TristateBuffer<Bit>,
+    io3: TristateBuffer<Bit>,
+    lanes_in: Signal<Local, Bits<4>>,
+    lanes_flop: DFF<Bits<4>>,
+    drive: Signal<Local, Bit>,
+    done_flop: DFF<Bit>,
+    register_out: DFF<Bits<N>>,
+    register_in: DFF<Bits<N>>,
+    state: DFF<QSPISlaveState>,
+    pointer: DFF<Bits<16>>,
+    step: Signal<Local, Bits<16>>,
+    width_saved: DFF<QSPIWidth>,
+    transmit_saved: DFF<Bit>,
+    dummy: DFF<Bits<8>>,
+    continued_saved: DFF<Bit>,
+    chained: DFF<Bit>,
+    capture_detector: EdgeDetector,
+    advance_detector: EdgeDetector,
+    edge_detector: EdgeDetector,
+    mclk_synchronizer: BitSynchronizer,
+    csel_synchronizer: BitSynchronizer,
+    escape: DFF<Bits<16>>,
+    clocks_per_baud: Constant<Bits<16>>,
+    cpha: Constant<Bit>,
+    cs_off: Constant<Bit>,
+    boot_delay: DFF<Bits<4>>,
+}
+
+impl<const N: usize> QSPISlave<N> {
+    /// Generate a new [QSPISlave] with the given [SPIConfig]
+    pub fn new(config: SPIConfig) -> Self {
+        // See the [SPISlave] for the reason for this constraint
+        assert!(config.cpha | (config.clock_speed >= 40 * config.speed_hz));
+        Self {
+            clock: Default::default(),
+            wires: Default::default(),
+            busy: Default::default(),
+            data_inbound: Default::default(),
+            start_send: Default::default(),
+            data_outbound: Default::default(),
+            bits: Default::default(),
+            width: Default::default(),
+            transmit: Default::default(),
+            dummy_cycles: Default::default(),
+            continued_transaction: Default::default(),
+            transfer_done: Default::default(),
+            io0: Default::default(),
+            io1: Default::default(),
+            io2: Default::default(),
+            io3: Default::default(),
+            lanes_in: Default::default(),
+            lanes_flop: Default::default(),
+            drive: Default::default(),
+            done_flop: Default::default(),
+            register_out: Default::default(),
+            register_in: Default::default(),
+            state: Default::default(),
+            pointer: Default::default(),
+            step: Default::default(),
+            width_saved: Default::default(),
+            transmit_saved: Default::default(),
+            dummy: Default::default(),
+            continued_saved: Default::default(),
+            chained: Default::default(),
+            capture_detector: EdgeDetector::new(!(config.cpol ^ config.cpha)),
+            advance_detector: EdgeDetector::new(config.cpol ^ config.cpha),
+            edge_detector: EdgeDetector::new(!config.cs_off),
+            mclk_synchronizer: BitSynchronizer::default(),
+            csel_synchronizer: BitSynchronizer::default(),
+            escape: Default::default(),
+            clocks_per_baud: Constant::new((2 * config.clock_speed / config.speed_hz).into()),
+            cpha: Constant::new(config.cpha),
+            cs_off: Constant::new(config.cs_off),
+            boot_delay: Default::default(),
+        }
+    }
+}
+
+impl<const N: usize> Logic for QSPISlave<N> {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(
+            self,
+            clock,
+            lanes_flop,
+            done_flop,
+            register_out,
+            register_in,
+            state,
+            pointer,
+            width_saved,
+            transmit_saved,
+            dummy,
+            continued_saved,
+            chained,
+            escape,
+            boot_delay
+        );
+        clock!(
+            self,
+            clock,
+            capture_detector,
+            advance_detector,
+            edge_detector,
+            mclk_synchronizer,
+            csel_synchronizer
+        );
+        // Connect the detectors
+        self.capture_detector.input_signal.next = self.mclk_synchronizer.sig_out.val();
+        self.advance_detector.input_signal.next = self.mclk_synchronizer.sig_out.val();
+        self.edge_detector.input_signal.next = self.csel_synchronizer.sig_out.val();
+        // Connect the synchronizers
+        self.mclk_synchronizer.sig_in.next = self.wires.mclk.val();
+        self.csel_synchronizer.sig_in.next = self.wires.msel.val();
+        // Connect the data lanes to the tristate buffers
+        Signal::<InOut, Bit>::link(&mut self.wires.io0, &mut self.io0.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io1, &mut self.io1.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io2, &mut self.io2.bus);
+        Signal::<InOut, Bit>::link(&mut self.wires.io3, &mut self.io3.bus);
+        self.lanes_in.next = bit_cast::<4, 1>(self.io0.read_data.val().into())
+            | (bit_cast::<4, 1>(self.io1.read_data.val().into()) << 1)
+            | (bit_cast::<4, 1>(self.io2.read_data.val().into()) << 2)
+            | (bit_cast::<4, 1>(self.io3.read_data.val().into()) << 3);
+        // Logic
+        self.busy.next = (self.state.q.val() != QSPISlaveState::Idle)
+            | (self.csel_synchronizer.sig_out.val() != self.cs_off.val());
+        self.data_inbound.next = self.register_in.q.val();
+        self.transfer_done.next = self.done_flop.q.val();
+        self.done_flop.d.next = false;
+        self.boot_delay.d.next = self.boot_delay.q.val() + 1;
+        // The number of bits moved on each clock, and the lanes that carry them
+        self.step.next = 1.into();
+        self.lanes_flop.d.next = bit_cast::<4, 1>(
+            self.register_out
+                .q
+                .val()
+                .get_bit(self.pointer.q.val().index())
+                .into(),
+        ) << 1;
+        if self.width_saved.q.val() == QSPIWidth::Dual {
+            self.step.next = 2.into();
+            self.lanes_flop.d.next = bit_cast::<4, 2>(
+                self.register_out
+                    .q
+                    .val()
+                    .get_bits::<2>(self.pointer.q.val().index()),
+            );
+        }
+        if self.width_saved.q.val() == QSPIWidth::Quad {
+            self.step.next = 4.into();
+            self.lanes_flop.d.next = self
+                .register_out
+                .q
+                .val()
+                .get_bits::<4>(self.pointer.q.val().index());
+        }
+        // Drive the data lanes once the dummy cycles are over
+        self.drive.next = self.transmit_saved.q.val()
+            & (self.width_saved.q.val() != QSPIWidth::Single)
+            & !self.dummy.q.val().any()
+            & ((self.state.q.val() == QSPISlaveState::Settle)
+                | (self.state.q.val() == QSPISlaveState::Capture)
+                | (self.state.q.val() == QSPISlaveState::Hold)
+                | (self.state.q.val() == QSPISlaveState::Update));
+        self.io0.write_data.next = self.lanes_flop.q.val().get_bit(0);
+        self.io1.write_data.next = self.lanes_flop.q.val().get_bit(1);
+        self.io2.write_data.next = self.lanes_flop.q.val().get_bit(2);
+        self.io3.write_data.next = self.lanes_flop.q.val().get_bit(3);
+        self.io0.write_enable.next = self.drive.val();
+        self.io1.write_enable.next = self.drive.val();
+        self.io2.write_enable.next =
+            self.drive.val() & (self.width_saved.q.val() == QSPIWidth::Quad);
+        self.io3.write_enable.next =
+            self.drive.val() & (self.width_saved.q.val() == QSPIWidth::Quad);
+        if (self.width_saved.q.val() == QSPIWidth::Single)
+            & (self.csel_synchronizer.sig_out.val() != self.cs_off.val())
+        {
+            self.io1.write_enable.next = true;
+        }
+        match self.state.q.val() {
+            QSPISlaveState::Boot => {
+                if self.boot_delay.q.val() == 8 {
+                    self.state.d.next = QSPISlaveState::Idle;
+                }
+            }
+            QSPISlaveState::Idle => {
+                if self.csel_synchronizer.sig_out.val() == self.cs_off.val() {
+                    self.chained.d.next = false;
+                }
+                if self.edge_detector.edge_signal.val() {
+                    self.register_in.d.next = 0.into();
+                    self.state.d.next = QSPISlaveState::Waiting;
+                    self.pointer.d.next = 0.into();
+                    self.width_saved.d.next = QSPIWidth::Single;
+                    self.transmit_saved.d.next = false;
+                    self.dummy.d.next = 0.into();
+                    self.escape.d.next = 0.into();
+                } else if self.start_send.val() {
+                    self.register_out.d.next = self.data_outbound.val();
+                    self.continued_saved.d.next = self.continued_transaction.val();
+                    self.width_saved.d.next = self.width.val();
+                    self.transmit_saved.d.next = self.transmit.val();
+                    self.dummy.d.next = self.dummy_cycles.val();
+                    self.pointer.d.next = self.bits.val() - 1;
+                    if self.width.val() == QSPIWidth::Dual {
+                        self.pointer.d.next = self.bits.val() - 2;
+                    }
+                    if self.width.val() == QSPIWidth::Quad {
+                        self.pointer.d.next = self.bits.val() - 4;
+                    }
+                    self.register_in.d.next = 0.into();
+                    self.state.d.next = QSPISlaveState::Armed;
+                }
+            }
+            QSPISlaveState::Armed => {
+                if self.csel_synchronizer.sig_out.val() != self.cs_off.val() {
+                    // A transfer that follows a continued one has already seen
+                    // its first advance edge
+                    if self.cpha.val() & !self.chained.q.val() {
+                        self.state.d.next = QSPISlaveState::Waiting;
+                    } else {
+                        self.state.d.next = QSPISlaveState::Settle;
+                    }
+                }
+            }
+            QSPISlaveState::Waiting => {
+                if self.advance_detector.edge_signal.val() {
+                    self.state.d.next = QSPISlaveState::Settle;
+                }
+                // Hangup condition.  CSEL should remain low for the entire transaction.
+                if self.cpha.val()
+                    & !self.continued_saved.q.val()
+                    & (self.csel_synchronizer.sig_out.val() == self.cs_off.val())
+                {
+                    self.state.d.next = QSPISlaveState::Idle;
+                }
+                if !self.cpha.val() & (self.csel_synchronizer.sig_out.val() == self.cs_off.val()) {
+                    self.escape.d.next = self.escape.q.val() + 1;
+                    if self.escape.q.val().all() {
+                        self.state.d.next = QSPISlaveState::Idle;
+                    }
+                }
+            }
+            QSPISlaveState::Settle => {
+                if self.capture_detector.edge_signal.val() {
+                    self.state.d.next = QSPISlaveState::Capture;
+                }
+                // Hangup condition.  CSEL should remain low for the entire transaction.
+                if self.csel_synchronizer.sig_out.val() == self.cs_off.val() {
+                    self.state.d.next = QSPISlaveState::Idle;
+                }
+            }
+            QSPISlaveState::Capture => {
+                // Nothing is captured during the dummy cycles
+                if !self.dummy.q.val().any() {
+                    match self.width_saved.q.val() {
+                        QSPIWidth::Single => {
+                            self.register_in.d.next = (self.register_in.q.val() << 1)
+                                | bit_cast::<N, 1>(self.lanes_in.val().get_bit(0).into());
+                        }
+                        QSPIWidth::Dual => {
+                            self.register_in.d.next = (self.register_in.q.val() << 2)
+                                | bit_cast::<N, 2>(self.lanes_in.val().get_bits::<2>(0));
+                        }
+                        _ => {
+                            self.register_in.d.next = (self.register_in.q.val() << 4)
+                                | bit_cast::<N, 4>(self.lanes_in.val());
+                        }
+                    }
+                }
+                self.state.d.next = QSPISlaveState::Hold;
+            }
+            QSPISlaveState::Hold => {
+                if self.advance_detector.edge_signal.val() {
+                    if self.dummy.q.val().any() {
+                        self.dummy.d.next = self.dummy.q.val() - 1;
+                        self.state.d.next = QSPISlaveState::Settle;
+                    } else if self.pointer.q.val().any() {
+                        self.state.d.next = QSPISlaveState::Update;
+                    } else {
+                        if self.continued_saved.q.val() {
+                            self.chained.d.next = true;
+                            self.done_flop.d.next = true;
+                            self.state.d.next = QSPISlaveState::Idle;
+                        } else {
+                            self.state.d.next = QSPISlaveState::Hangup;
+                        }
+                    }
+                    self.escape.d.next = 0.into();
+                } else if self.csel_synchronizer.sig_out.val() == self.cs_off.val() {
+                    self.done_flop.d.next = true;
+                    self.state.d.next = QSPISlaveState::Idle;
+                } else {
+                    self.escape.d.next = self.escape.q.val() + 1;
+                }
+                if self.escape.q.val() == self.clocks_per_baud.val() {
+                    self.done_flop.d.next = true;
+                    self.state.d.next = QSPISlaveState::Idle;
+                }
+            }
+            QSPISlaveState::Update => {
+                if self.pointer.q.val().any() {
+                    self.pointer.d.next = self.pointer.q.val() - self.step.val();
+                }
+                self.state.d.next = QSPISlaveState::Settle;
+            }
+            QSPISlaveState::Hangup => {
+                if self.csel_synchronizer.sig_out.val() == self.cs_off.val() {
+                    self.done_flop.d.next = true;
+                    self.state.d.next = QSPISlaveState::Idle;
+                }
+            }
+            _ => {
+                self.state.d.next = QSPISlaveState::Boot;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_qspi_slave_synthesizes() {
+    let config = SPIConfig {
+        clock_speed: 48_000_000,
+        cs_off: true,
+        mosi_off: false,
+        speed_hz: 1_000_000,
+        cpha: true,
+        cpol: false,
+    };
+    let mut uut: QSPISlave<64> = QSPISlave::new(config);
+    uut.connect_all();
+    yosys_validate("qspi_slave", &generate_verilog(&uut)).unwrap();
+}
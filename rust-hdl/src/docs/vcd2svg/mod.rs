@@ -1,6 +1,7 @@
 use crate::docs::vcd2svg::display_metrics::DisplayMetrics;
 use crate::docs::vcd2svg::trace_collection::TraceCollection;
 use crate::docs::vcd2svg::vcd_style::VCDStyle;
+use rust_hdl_core::failure::TraceTail;
 
 pub mod display_metrics;
 mod interval;
@@ -43,6 +44,13 @@ pub fn vcd_to_txt(
     traces.as_string(min_time_in_ps, max_time_in_ps, max_columns as usize)
 }
 
+/// Draw the [TraceTail] attached to a failed simulation as a text waveform
+pub fn trace_tail_to_txt(tail: &TraceTail, max_columns: u64) -> anyhow::Result<String> {
+    let signal_names = tail.signals.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+    let traces = TraceCollection::parse(&signal_names, tail.vcd.as_slice())?;
+    traces.as_string(tail.start_time, tail.end_time, max_columns as usize)
+}
+
 #[test]
 fn test_txt() {
     let msg = vcd_to_txt(
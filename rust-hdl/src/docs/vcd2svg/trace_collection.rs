@@ -7,7 +7,6 @@ use num_bigint::BigInt;
 use std::clone::Clone;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::fs::File;
 use std::iter::Iterator;
 use std::string::ToString;
 use svg::Document;
@@ -120,7 +119,7 @@ pub struct TraceCollection {
 }
 
 impl TraceCollection {
-    pub fn parse(signals: &[&str], mut file: File) -> anyhow::Result<Self> {
+    pub fn parse<R: std::io::Read>(signals: &[&str], mut file: R) -> anyhow::Result<Self> {
         let mut parser = vcd::Parser::new(&mut file);
         let header = parser.parse_header()?;
         let mut string_valued = HashMap::new();
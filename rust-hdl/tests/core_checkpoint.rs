@@ -0,0 +1,168 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock)]
+struct TestSDRAMDevice {
+    dram: SDRAMSimulator<5, 5, 10, 16>,
+    buffer: SDRAMOnChipBuffer<16>,
+    cntrl: SDRAMBaseController<5, 5, 64, 16>,
+    clock: Signal<In, Clock>,
+}
+
+impl Logic for TestSDRAMDevice {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SDRAMDriver::<16>::join(&mut self.cntrl.sdram, &mut self.buffer.buf_in);
+        SDRAMDriver::<16>::join(&mut self.buffer.buf_out, &mut self.dram.sdram);
+        clock!(self, clock, cntrl);
+    }
+}
+
+fn make_test_device() -> TestSDRAMDevice {
+    let timings = MemoryTimings::fast_boot_sim(100e6);
+    let mut uut = TestSDRAMDevice {
+        dram: SDRAMSimulator::new(timings),
+        buffer: Default::default(),
+        cntrl: SDRAMBaseController::new(3, timings, OutputBuffer::DelayTwo),
+        clock: Default::default(),
+    };
+    uut.cntrl.data_in.connect();
+    uut.cntrl.cmd_strobe.connect();
+    uut.cntrl.cmd_address.connect();
+    uut.cntrl.write_not_read.connect();
+    uut
+}
+
+macro_rules! sdram_basic_write {
+    ($sim: ident, $uut: ident, $cntrl: ident, $addr: expr, $data: expr) => {
+        $uut = $sim.watch(|x| !x.$cntrl.busy.val(), $uut)?;
+        $uut.$cntrl.cmd_address.next = ($addr).to_bits();
+        $uut.$cntrl.write_not_read.next = true;
+        $uut.$cntrl.data_in.next = ($data).to_bits();
+        $uut.$cntrl.cmd_strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.$cntrl.cmd_strobe.next = false;
+        $uut.$cntrl.cmd_address.next = 0.into();
+        $uut.$cntrl.write_not_read.next = false;
+        $uut.$cntrl.data_in.next = 0.into();
+    };
+}
+
+macro_rules! sdram_basic_read {
+    ($sim: ident, $uut: ident, $cntrl: ident, $addr: expr) => {{
+        $uut = $sim.watch(|x| !x.$cntrl.busy.val(), $uut)?;
+        $uut.$cntrl.cmd_address.next = ($addr).to_bits();
+        $uut.$cntrl.write_not_read.next = false;
+        $uut.$cntrl.cmd_strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.$cntrl.cmd_strobe.next = false;
+        $uut.$cntrl.cmd_address.next = 0.into();
+        $uut = $sim.watch(|x| x.$cntrl.data_valid.val(), $uut)?;
+        $uut.$cntrl.data_out.val()
+    }};
+}
+
+fn add_clock(sim: &mut Simulation<TestSDRAMDevice>) {
+    sim.add_clock(5000, |x: &mut Box<TestSDRAMDevice>| {
+        x.clock.next = !x.clock.val()
+    });
+}
+
+// Run the SDRAM through its boot sequence with nothing but the clock
+fn boot_checkpoint() -> Checkpoint {
+    let mut sim = Simulation::new();
+    add_clock(&mut sim);
+    sim.run_to_checkpoint(Box::new(make_test_device()), 5_000_000)
+        .unwrap()
+}
+
+#[test]
+fn test_checkpoint_after_sdram_boot() {
+    let boot = boot_checkpoint();
+    assert_eq!(boot.time(), 5_000_000);
+    // The contents of the RAMs that simulate the banks are included
+    assert!(boot.paths().any(|x| x == "dram.banks$0.mem._sim"));
+    assert!(boot.paths().any(|x| x == "cntrl.busy"));
+    // Write some data, starting from the booted state, and checkpoint again
+    let mut sim = Simulation::new();
+    add_clock(&mut sim);
+    sim.add_testbench(move |mut sim: Sim<TestSDRAMDevice>| {
+        let mut x = sim.init()?;
+        sim_assert_eq!(sim, sim.time(), 5_000_000, x);
+        sim_assert!(sim, !x.cntrl.busy.val(), x);
+        wait_clock_true!(sim, clock, x);
+        sdram_basic_write!(sim, x, cntrl, 0_u32, 0xDEAD_BEEF_CAFE_BABE_u64);
+        sdram_basic_write!(sim, x, cntrl, 4_u32, 0x1234_ABCD_5678_EFFE_u64);
+        sim.done(x)
+    });
+    sim.start_from(&boot);
+    let written = sim
+        .run_to_checkpoint(Box::new(make_test_device()), 7_000_000)
+        .unwrap();
+    // Two tests that start from the written state, and read the data back
+    for (address, expected) in [
+        (0_u32, 0xDEAD_BEEF_CAFE_BABE_u64),
+        (4_u32, 0x1234_ABCD_5678_EFFE_u64),
+    ] {
+        let mut sim = Simulation::new();
+        add_clock(&mut sim);
+        sim.add_testbench(move |mut sim: Sim<TestSDRAMDevice>| {
+            let mut x = sim.init()?;
+            wait_clock_true!(sim, clock, x);
+            let read = sdram_basic_read!(sim, x, cntrl, address);
+            sim_assert_eq!(sim, read, expected, x);
+            sim_assert!(sim, !x.dram.test_error.val(), x);
+            sim.done(x)
+        });
+        sim.start_from(&written);
+        sim.run(Box::new(make_test_device()), 10_000_000).unwrap();
+    }
+}
+
+#[test]
+fn test_checkpoint_round_trip_through_a_file() {
+    let boot = boot_checkpoint();
+    let path = std::env::temp_dir().join("rust_hdl_sdram_boot.chk");
+    boot.save(&path).unwrap();
+    let loaded = Checkpoint::load(&path).unwrap();
+    assert_eq!(loaded.time(), boot.time());
+    assert_eq!(loaded.to_text(), boot.to_text());
+    // The loaded checkpoint can be used to run a test
+    let mut sim = Simulation::new();
+    add_clock(&mut sim);
+    sim.add_testbench(move |mut sim: Sim<TestSDRAMDevice>| {
+        let mut x = sim.init()?;
+        sim_assert!(sim, !x.cntrl.busy.val(), x);
+        wait_clock_true!(sim, clock, x);
+        sdram_basic_write!(sim, x, cntrl, 8_u32, 0xFEED_FACE_0BAD_F00D_u64);
+        let read = sdram_basic_read!(sim, x, cntrl, 8_u32);
+        sim_assert_eq!(sim, read, 0xFEED_FACE_0BAD_F00D_u64, x);
+        sim_assert!(sim, !x.dram.test_error.val(), x);
+        sim.done(x)
+    });
+    sim.start_from(&loaded);
+    sim.run(Box::new(make_test_device()), 10_000_000).unwrap();
+    // Text that is not a checkpoint is rejected
+    assert!(Checkpoint::from_text("not a checkpoint").is_none());
+}
+
+#[test]
+fn test_checkpoint_restores_only_matching_circuits() {
+    let mut uut = DFF::<Bits<8>>::default();
+    uut.d.next = 0x42.into();
+    uut.update_all();
+    let checkpoint = Checkpoint::capture(&mut uut, 100);
+    assert_eq!(checkpoint.len(), 3);
+    let mut copy = DFF::<Bits<8>>::default();
+    checkpoint.restore(&mut copy).unwrap();
+    assert_eq!(copy.d.val(), 0x42);
+    let mut wider = DFF::<Bits<16>>::default();
+    assert_eq!(
+        checkpoint.restore(&mut wider),
+        Err(CheckpointError::Mismatch("d".into()))
+    );
+    let mut ram = RAM::<Bits<8>, 4>::default();
+    assert_eq!(
+        checkpoint.restore(&mut ram),
+        Err(CheckpointError::Missing("read_address".into()))
+    );
+}
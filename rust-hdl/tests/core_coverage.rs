@@ -0,0 +1,166 @@
+use rust_hdl::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Phase {
+    Idle,
+    Counting,
+    Overflow,
+}
+
+// Counts while `enable` is held, and gets stuck in `Overflow` if the
+// count ever wraps.
+#[derive(LogicBlock, Default)]
+struct Counter {
+    enable: Signal<In, Bit>,
+    count: Signal<Out, Bits<4>>,
+    clock: Signal<In, Clock>,
+    phase: DFF<Phase>,
+    counter: DFF<Bits<4>>,
+}
+
+impl Logic for Counter {
+    #[hdl_gen(coverage)]
+    fn update(&mut self) {
+        dff_setup!(self, clock, phase, counter);
+        self.count.next = self.counter.q.val();
+        match self.phase.q.val() {
+            Phase::Idle => {
+                if self.enable.val() {
+                    self.phase.d.next = Phase::Counting;
+                }
+            }
+            Phase::Counting => {
+                if !self.enable.val() {
+                    self.phase.d.next = Phase::Idle;
+                } else if self.counter.q.val() == 15 {
+                    self.phase.d.next = Phase::Overflow;
+                } else {
+                    self.counter.d.next = self.counter.q.val() + 1;
+                }
+            }
+            Phase::Overflow => {}
+            _ => {
+                self.phase.d.next = Phase::Idle;
+            }
+        }
+    }
+}
+
+fn run_counter(pulses: &[u64]) -> Coverage {
+    let mut uut = Counter::default();
+    uut.enable.connect();
+    uut.clock.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.enable_coverage();
+    sim.add_clock(5, |x: &mut Box<Counter>| x.clock.next = !x.clock.val());
+    let pulses = pulses.to_vec();
+    sim.add_testbench(move |mut sim: Sim<Counter>| {
+        let mut x = sim.init()?;
+        for pulse in &pulses {
+            x.enable.next = true;
+            wait_clock_cycles!(sim, clock, x, *pulse);
+            x.enable.next = false;
+            wait_clock_cycles!(sim, clock, x, 2);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000).unwrap();
+    sim.coverage().unwrap().clone()
+}
+
+#[test]
+fn test_coverage_of_states_and_transitions() {
+    let coverage = run_counter(&[3]);
+    let states = &coverage.instances["uut.phase"].states["q"];
+    assert_eq!(states.visits.len(), 3);
+    assert!(states.visits["Idle"] > 0);
+    assert!(states.visits["Counting"] > 0);
+    assert_eq!(states.visits["Overflow"], 0);
+    assert_eq!(states.transitions[&("Idle".into(), "Counting".into())], 1);
+    assert_eq!(states.transitions[&("Counting".into(), "Idle".into())], 1);
+    assert_eq!(states.transitions.len(), 2);
+    let report = coverage.to_text();
+    assert!(report.contains("not visited: Overflow"));
+}
+
+#[test]
+fn test_coverage_of_branches() {
+    let coverage = run_counter(&[3]);
+    let (module, points) = coverage
+        .branches
+        .iter()
+        .find(|(module, _)| module.ends_with("Counter"))
+        .unwrap();
+    assert!(module.starts_with("core_coverage::"));
+    // The match, the `if` in Idle and the `if`/`else if` chain in Counting
+    assert_eq!(points.len(), 4);
+    let arms = points.values().map(|x| x.len()).collect::<Vec<_>>();
+    assert!(arms.contains(&4));
+    // Overflow, the default arm and the wrap of the counter are never reached
+    let (hit, total) = coverage.branch_summary();
+    assert!(hit < total);
+    let report = coverage.to_text();
+    assert!(report.contains("not taken: rust-hdl/tests/core_coverage.rs"));
+    let lcov = coverage.to_lcov();
+    assert!(lcov.contains("SF:rust-hdl/tests/core_coverage.rs"));
+    assert!(lcov.contains("BRDA:"));
+    assert!(lcov.contains(",-\n"));
+    assert!(lcov.contains("FNDA:0,q::state::Overflow"));
+}
+
+#[test]
+fn test_coverage_of_toggles_merges_across_runs() {
+    let short = run_counter(&[3]);
+    let toggles = &short.instances["uut"].toggles["count"];
+    assert_eq!(toggles.rose.len(), 4);
+    // The count never reaches 4, so the upper bits never toggle
+    assert!(toggles.rose[0] && toggles.fell[0] && toggles.rose[1]);
+    assert!(!toggles.rose[2] && !toggles.rose[3]);
+    let long = run_counter(&[20]);
+    let mut merged = short.clone();
+    merged.merge(&long);
+    assert!(merged.instances["uut.phase"].states["q"].visits["Overflow"] > 0);
+    assert!(merged.branch_summary().0 > short.branch_summary().0);
+    assert!(merged.toggle_summary().0 >= long.toggle_summary().0);
+    coverage::accumulate(&short);
+    coverage::accumulate(&long);
+    assert!(coverage::accumulated().instances["uut.phase"].states["q"].visits["Overflow"] > 0);
+}
+
+// The same kind of logic, but without asking for coverage
+#[derive(LogicBlock, Default)]
+struct Uninstrumented {
+    enable: Signal<In, Bit>,
+    count: Signal<Out, Bits<4>>,
+}
+
+impl Logic for Uninstrumented {
+    #[hdl_gen]
+    fn update(&mut self) {
+        if self.enable.val() {
+            self.count.next = 1.into();
+        } else {
+            self.count.next = 0.into();
+        }
+    }
+}
+
+#[test]
+fn test_coverage_without_instrumentation_has_no_branches() {
+    let mut uut = Uninstrumented::default();
+    uut.enable.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.enable_coverage();
+    sim.add_testbench(move |mut sim: Sim<Uninstrumented>| {
+        let mut x = sim.init()?;
+        x.enable.next = true;
+        x = sim.wait(10, x)?;
+        sim_assert_eq!(sim, x.count.val(), 1, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100).unwrap();
+    let coverage = sim.coverage().unwrap();
+    assert!(coverage.branches.is_empty());
+}
@@ -0,0 +1,150 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock)]
+struct TestDDR3Device {
+    dram: DDR3Simulator<4, 5, 11, 16>,
+    phy: DDR3PHY<16>,
+    cntrl: DDR3Controller<4, 5, 128, 16>,
+    clock: Signal<In, Clock>,
+    clock90: Signal<In, Clock>,
+}
+
+impl Logic for TestDDR3Device {
+    #[hdl_gen]
+    fn update(&mut self) {
+        DDR3PHYDriver::<16>::join(&mut self.cntrl.phy, &mut self.phy.ctl);
+        DDR3Driver::<16>::join(&mut self.phy.ddr, &mut self.dram.ddr);
+        clock!(self, clock, cntrl, phy);
+        self.phy.clock90.next = self.clock90.val();
+    }
+}
+
+#[cfg(test)]
+fn make_test_device() -> TestDDR3Device {
+    let timings = DDR3Timings::fast_boot_sim(100e6);
+    let mut uut = TestDDR3Device {
+        dram: DDR3Simulator::new(timings),
+        phy: Default::default(),
+        cntrl: DDR3Controller::new(timings),
+        clock: Default::default(),
+        clock90: Default::default(),
+    };
+    uut.cntrl.data_in.connect();
+    uut.cntrl.cmd_strobe.connect();
+    uut.cntrl.cmd_address.connect();
+    uut.cntrl.write_not_read.connect();
+    uut.connect_all();
+    uut
+}
+
+#[cfg(test)]
+fn make_test_simulation() -> Simulation<TestDDR3Device> {
+    let mut sim = Simulation::new();
+    sim.add_clock(5000, |x: &mut Box<TestDDR3Device>| {
+        x.clock.next = !x.clock.val()
+    });
+    // clock90 lags clock by a quarter cycle
+    sim.add_phased_clock(5000, 2500, |x: &mut Box<TestDDR3Device>| {
+        x.clock90.next = !x.clock90.val()
+    });
+    sim
+}
+
+macro_rules! sdram_basic_write {
+    ($sim: ident, $uut: ident, $cntrl: ident, $addr: expr, $data: expr) => {
+        $uut = $sim.watch(|x| !x.$cntrl.busy.val(), $uut)?;
+        $uut.$cntrl.cmd_address.next = ($addr).to_bits();
+        $uut.$cntrl.write_not_read.next = true;
+        $uut.$cntrl.data_in.next = ($data).to_bits();
+        $uut.$cntrl.cmd_strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.$cntrl.cmd_strobe.next = false;
+        $uut.$cntrl.cmd_address.next = 0.into();
+        $uut.$cntrl.write_not_read.next = false;
+        $uut.$cntrl.data_in.next = 0.into();
+    };
+}
+
+macro_rules! sdram_basic_read {
+    ($sim: ident, $uut: ident, $cntrl: ident, $addr: expr) => {{
+        $uut = $sim.watch(|x| !x.$cntrl.busy.val(), $uut)?;
+        $uut.$cntrl.cmd_address.next = ($addr).to_bits();
+        $uut.$cntrl.write_not_read.next = false;
+        $uut.$cntrl.cmd_strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.$cntrl.cmd_strobe.next = false;
+        $uut.$cntrl.cmd_address.next = 0.into();
+        $uut = $sim.watch(|x| x.$cntrl.data_valid.val(), $uut)?;
+        $uut.$cntrl.data_out.val()
+    }};
+}
+
+#[test]
+fn test_ddr3_controller_is_synthesizable() {
+    let mut uut: DDR3Controller<14, 10, 128, 16> =
+        DDR3Controller::new(DDR3Timings::mt41k128m16(400e6));
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    yosys_validate("ddr3_controller", &vlog).unwrap();
+}
+
+#[test]
+fn test_ddr3_unit_is_synthesizable() {
+    let uut = make_test_device();
+    let vlog = generate_verilog(&uut);
+    yosys_validate("ddr3_test_unit", &vlog).unwrap();
+}
+
+#[test]
+fn test_ddr3_unit_boots() {
+    let uut = make_test_device();
+    let mut sim = make_test_simulation();
+    sim.add_testbench(move |mut sim: Sim<TestDDR3Device>| {
+        let mut x = sim.init()?;
+        x = sim.watch(|x| x.cntrl.init_done.val(), x)?;
+        // The device sees the end of calibration after the PHY delay
+        wait_clock_cycles!(sim, clock, x, 4);
+        sim_assert!(sim, x.dram.test_ready.val(), x);
+        // Long enough for several refreshes
+        wait_clock_cycles!(sim, clock, x, 5000);
+        sim_assert!(sim, !x.dram.test_error.val(), x);
+        sim_assert!(sim, x.dram.test_ready.val(), x);
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 1_000_000_000, &vcd_path!("ddr3_boot.vcd"))
+        .unwrap()
+}
+
+#[test]
+fn test_ddr3_unit_writes() {
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+    let uut = make_test_device();
+    let mut sim = make_test_simulation();
+    // Lines in a random order, so that the accesses hit and miss the open
+    // rows of all of the banks
+    let mut addresses = (0..512_u32).collect::<Vec<_>>();
+    addresses.shuffle(&mut rand::thread_rng());
+    let test_data = addresses
+        .iter()
+        .map(|addr| (*addr, rand::thread_rng().gen::<u128>()))
+        .collect::<Vec<_>>();
+    let send = test_data.clone();
+    let recv = test_data;
+    sim.add_testbench(move |mut sim: Sim<TestDDR3Device>| {
+        let mut x = sim.init()?;
+        x = sim.watch(|x| x.cntrl.init_done.val(), x)?;
+        wait_clock_true!(sim, clock, x);
+        for (addr, val) in &send {
+            sdram_basic_write!(sim, x, cntrl, *addr, *val);
+        }
+        for (addr, val) in recv.iter().rev() {
+            let read = sdram_basic_read!(sim, x, cntrl, *addr);
+            sim_assert_eq!(sim, read, val.to_bits::<128>(), x);
+        }
+        sim_assert!(sim, !x.dram.test_error.val(), x);
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 1_000_000_000, &vcd_path!("ddr3_writes.vcd"))
+        .unwrap()
+}
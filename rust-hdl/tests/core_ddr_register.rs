@@ -0,0 +1,135 @@
+use rust_hdl::prelude::*;
+
+#[test]
+fn test_neg_edge_dff_updates_on_falling_edge() {
+    let mut uut = NegEdgeDFF::<Bits<8>>::new(0x11.into());
+    uut.d.connect();
+    uut.clock.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<NegEdgeDFF<Bits<8>>>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<NegEdgeDFF<Bits<8>>>| {
+        let mut x = sim.init()?;
+        sim_assert_eq!(sim, x.q.val(), 0x11, x);
+        wait_clock_true!(sim, clock, x);
+        x.d.next = 0x42.into();
+        x = sim.wait(1, x)?;
+        // Nothing happens until the falling edge
+        sim_assert_eq!(sim, x.q.val(), 0x11, x);
+        wait_clock_false!(sim, clock, x);
+        x = sim.wait(1, x)?;
+        sim_assert_eq!(sim, x.q.val(), 0x42, x);
+        x.d.next = 0x24.into();
+        wait_clock_true!(sim, clock, x);
+        x = sim.wait(1, x)?;
+        sim_assert_eq!(sim, x.q.val(), 0x42, x);
+        wait_clock_false!(sim, clock, x);
+        x = sim.wait(1, x)?;
+        sim_assert_eq!(sim, x.q.val(), 0x24, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000).unwrap();
+}
+
+#[test]
+fn test_ddr_output_register_sim() {
+    let mut uut = DDROutputRegister::<8>::default();
+    uut.d_rise.connect();
+    uut.d_fall.connect();
+    uut.clock.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<DDROutputRegister<8>>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<DDROutputRegister<8>>| {
+        let mut x = sim.init()?;
+        for n in 0..8_u32 {
+            wait_clock_false!(sim, clock, x);
+            x.d_rise.next = (2 * n + 0x10).to_bits();
+            x.d_fall.next = (2 * n + 0x11).to_bits();
+            wait_clock_true!(sim, clock, x);
+            x = sim.wait(1, x)?;
+            sim_assert_eq!(sim, x.q.val(), (2 * n + 0x10).to_bits::<8>(), x);
+            // The data is captured on the rising edge, so later changes
+            // do not show up on the falling edge
+            x.d_fall.next = 0.into();
+            wait_clock_false!(sim, clock, x);
+            x = sim.wait(1, x)?;
+            sim_assert_eq!(sim, x.q.val(), (2 * n + 0x11).to_bits::<8>(), x);
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 10_000, &vcd_path!("ddr_output_register.vcd"))
+        .unwrap();
+}
+
+#[test]
+fn test_ddr_input_register_sim() {
+    let mut uut = DDRInputRegister::<8>::default();
+    uut.d.connect();
+    uut.clock.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<DDRInputRegister<8>>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<DDRInputRegister<8>>| {
+        let mut x = sim.init()?;
+        let pairs = [
+            (0x01_u32, 0x02_u32),
+            (0x13, 0x24),
+            (0x35, 0x46),
+            (0x57, 0x68),
+        ];
+        wait_clock_false!(sim, clock, x);
+        x.d.next = pairs[0].0.to_bits();
+        for n in 0..=pairs.len() {
+            wait_clock_true!(sim, clock, x);
+            x = sim.wait(1, x)?;
+            // Both halves of the previous clock cycle appear together
+            if n > 0 {
+                sim_assert_eq!(sim, x.q_rise.val(), pairs[n - 1].0.to_bits::<8>(), x);
+                sim_assert_eq!(sim, x.q_fall.val(), pairs[n - 1].1.to_bits::<8>(), x);
+            }
+            if n < pairs.len() {
+                x.d.next = pairs[n].1.to_bits();
+            }
+            wait_clock_false!(sim, clock, x);
+            x = sim.wait(1, x)?;
+            if n + 1 < pairs.len() {
+                x.d.next = pairs[n + 1].0.to_bits();
+            }
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 10_000, &vcd_path!("ddr_input_register.vcd"))
+        .unwrap();
+}
+
+#[test]
+fn test_ddr_registers_map_to_vendor_primitives() {
+    let mut uut = DDROutputRegister::<2>::default();
+    uut.connect_all();
+    let vlog = generate_verilog(&uut);
+    assert!(!vlog.contains("`define"));
+    assert!(vlog.contains("assign q = clock ? rise_data : fall_data;"));
+    let vlog = generate_verilog_for_target(&uut, VerilogTarget::ECP5);
+    assert!(vlog.starts_with("`define RUST_HDL_TARGET_ECP5\n"));
+    assert!(vlog.contains("ODDRX1F oddr_inst"));
+    let vlog = generate_verilog_for_target(&uut, VerilogTarget::ICE40);
+    assert!(vlog.starts_with("`define RUST_HDL_TARGET_ICE40\n"));
+    assert!(vlog.contains(".PACKAGE_PIN(q[i]), .OUTPUT_CLK(clock)"));
+    let vlog = generate_verilog_for_target(&uut, VerilogTarget::Xilinx7);
+    assert!(vlog.starts_with("`define RUST_HDL_TARGET_XILINX7\n"));
+    let mut uut = DDRInputRegister::<1>::default();
+    uut.connect_all();
+    let vlog = generate_verilog_for_target(&uut, VerilogTarget::Spartan6);
+    assert!(vlog.starts_with("`define RUST_HDL_TARGET_SPARTAN6\n"));
+    // Single bit ports are not indexed
+    assert!(vlog.contains(".D(d), .R(1'b0)"));
+    let vlog = generate_verilog_for_target(&uut, VerilogTarget::ICE40);
+    assert!(vlog.contains(".PACKAGE_PIN(d), .INPUT_CLK(clock)"));
+}
@@ -0,0 +1,174 @@
+use rust_hdl::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, LogicState)]
+enum Phase {
+    Idle,
+    Counting,
+    Done,
+}
+
+#[derive(LogicBlock, Default)]
+struct CounterTest {
+    pub clock: Signal<In, Clock>,
+    pub enable: Signal<In, Bit>,
+    pub count: Signal<Out, Bits<8>>,
+    counter: DFF<Bits<8>>,
+    phase: DFF<Phase>,
+}
+
+impl Logic for CounterTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter, phase);
+        self.count.next = self.counter.q.val();
+        match self.phase.q.val() {
+            Phase::Idle => {
+                if self.enable.val() {
+                    self.phase.d.next = Phase::Counting;
+                }
+            }
+            Phase::Counting => {
+                self.counter.d.next = self.counter.q.val() + 1;
+                if self.counter.q.val() == 9 {
+                    self.phase.d.next = Phase::Done;
+                }
+            }
+            Phase::Done => {}
+        }
+    }
+}
+
+fn counter_sim(expected: u64) -> Simulation<CounterTest> {
+    simple_sim!(CounterTest, clock, 100_000_000, sim, {
+        let mut x = sim.init()?;
+        x.enable.next = true;
+        x = sim.watch(|x| x.phase.q.val() == Phase::Done, x)?;
+        sim_assert_eq!(sim, x.count.val(), expected, x);
+        sim.done(x)
+    })
+}
+
+fn uut() -> Box<CounterTest> {
+    let mut uut = CounterTest::default();
+    uut.enable.connect();
+    Box::new(uut)
+}
+
+fn has_line(debugger: &Debugger<CounterTest>, text: &str) -> bool {
+    debugger
+        .transcript()
+        .iter()
+        .any(|x| x.lines().any(|line| line == text))
+}
+
+#[test]
+fn test_debugger_breaks_on_signal_values() {
+    let mut debugger = Debugger::script(&["print count", "print phase", "continue"])
+        .break_on_value("phase.q", "Phase::Done");
+    counter_sim(10)
+        .run_debug(uut(), 1_000_000, &mut debugger)
+        .unwrap();
+    println!("{:#?}", debugger.transcript());
+    assert!(debugger.transcript()[0].starts_with("Stopped at time"));
+    assert!(debugger.transcript()[0].ends_with("breakpoint 0 (phase.q == Phase::Done)"));
+    assert!(has_line(&debugger, "count = 8'ha"));
+    // Printing a prefix shows everything under it
+    assert!(has_line(&debugger, "phase.q = Done"));
+    assert!(has_line(&debugger, "phase.clock = 1'b1"));
+}
+
+#[test]
+fn test_debugger_steps_and_forces_values() {
+    let mut debugger = Debugger::script(&[
+        "break count 3",
+        "continue",
+        "force counter.q 0x20",
+        "cycle clock 2",
+        "print counter.q",
+        "release counter.q",
+        "cycle clock 2",
+        "print counter.q",
+        "release counter.q",
+        "step",
+        "time",
+        "force counter.q 1000",
+        "force nothing 1",
+        "frobnicate",
+        "breakpoints",
+        "delete 0",
+        "c",
+    ])
+    .stop_at_start();
+    // Forcing the counter past 9 means it counts all the way around
+    counter_sim(10)
+        .run_debug(uut(), 100_000_000, &mut debugger)
+        .unwrap();
+    println!("{:#?}", debugger.transcript());
+    assert_eq!(debugger.transcript()[0], "Stopped at time 0: start");
+    assert!(debugger.transcript()[1].ends_with("breakpoint 0 (count == 3)"));
+    // The counter logic drives the forced signal, but it holds its value across
+    // clocks until it is released
+    assert_eq!(debugger.transcript()[2], "counter.q = 8'h20");
+    assert!(debugger.transcript()[3].ends_with("rising edge of clock"));
+    assert_eq!(debugger.transcript()[4], "counter.q = 8'h20");
+    assert!(debugger.transcript()[5].ends_with("rising edge of clock"));
+    assert_eq!(debugger.transcript()[6], "counter.q = 8'h22");
+    assert_eq!(debugger.transcript()[7], "counter.q is not forced");
+    assert!(debugger.transcript()[8].ends_with("step"));
+    assert!(has_line(&debugger, "Cannot set counter.q to 1000"));
+    assert!(has_line(&debugger, "No signal nothing"));
+    assert!(has_line(
+        &debugger,
+        "Unknown command: frobnicate (try help)"
+    ));
+    assert!(has_line(&debugger, "0: count == 3"));
+}
+
+#[test]
+fn test_debugger_steps_through_update_passes() {
+    let mut commands = vec!["break count 3", "continue"];
+    for _ in 0..10 {
+        commands.extend(["delta", "print counter.q", "print count"]);
+    }
+    commands.push("continue");
+    let mut debugger = Debugger::script(&commands).stop_at_start();
+    counter_sim(10)
+        .run_debug(uut(), 1_000_000, &mut debugger)
+        .unwrap();
+    println!("{:#?}", debugger.transcript());
+    assert!(debugger.transcript()[1].ends_with("breakpoint 0 (count == 3)"));
+    assert!(debugger.transcript()[2].ends_with("delta step"));
+    // Part way through the clock edge, the counter has changed but the
+    // output it drives has not
+    assert!(debugger
+        .transcript()
+        .windows(2)
+        .any(|x| x[0] == "counter.q = 8'h4" && x[1] == "count = 8'h3"));
+}
+
+#[test]
+fn test_debugger_stops_when_a_testbench_halts() {
+    let mut debugger = Debugger::<CounterTest>::script(&["print phase.q", "quit"])
+        .break_on_halt()
+        .break_when("count is 5", |x| x.count.val() == 5);
+    let result = counter_sim(11).run_debug(uut(), 1_000_000, &mut debugger);
+    println!("{:#?}", debugger.transcript());
+    assert_eq!(result, Err(SimError::SimTerminated));
+    // The condition stops the simulation before the halt, and the script quits there
+    assert!(debugger.transcript()[0].ends_with("breakpoint 1 (count is 5)"));
+    assert!(has_line(&debugger, "phase.q = Counting"));
+    assert_eq!(debugger.transcript().len(), 2);
+    // Without a script, the debugger just lets the simulation fail
+    let mut debugger = Debugger::script(&[]).break_on_halt();
+    let result = counter_sim(11).run_debug(uut(), 1_000_000, &mut debugger);
+    assert!(matches!(result, Err(SimError::SimHalted(_))));
+    assert!(debugger.transcript()[0].ends_with("simulation halted"));
+}
+
+#[test]
+fn test_signed_values_parse_over_their_full_range() {
+    assert_eq!(Signed::<8>::from_text("-128"), Some(Signed::from(-128)));
+    assert_eq!(Signed::<8>::from_text("127"), Some(Signed::from(127)));
+    assert_eq!(Signed::<8>::from_text("128"), None);
+    assert_eq!(Signed::<8>::from_text("-129"), None);
+}
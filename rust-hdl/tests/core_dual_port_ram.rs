@@ -0,0 +1,229 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock)]
+struct TDPRAMTest {
+    pub clock: Signal<In, Clock>,
+    pub ram: TrueDualPortRAM<32, 6, 4>,
+}
+
+impl TDPRAMTest {
+    pub fn new(policy: ReadPolicy, output_register: bool) -> Self {
+        Self {
+            clock: Default::default(),
+            ram: TrueDualPortRAM::new(policy, output_register),
+        }
+    }
+}
+
+impl Logic for TDPRAMTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.ram.a_clock.next = self.clock.val();
+        self.ram.b_clock.next = self.clock.val();
+    }
+}
+
+fn make_tdp_test(policy: ReadPolicy, output_register: bool) -> TDPRAMTest {
+    let mut uut = TDPRAMTest::new(policy, output_register);
+    uut.ram.a_address.connect();
+    uut.ram.a_enable.connect();
+    uut.ram.a_write_enable.connect();
+    uut.ram.a_write_data.connect();
+    uut.ram.b_address.connect();
+    uut.ram.b_enable.connect();
+    uut.ram.b_write_enable.connect();
+    uut.ram.b_write_data.connect();
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_tdp_ram_test_synthesizes() {
+    let uut = make_tdp_test(ReadPolicy::WriteFirst, true);
+    yosys_validate("tdp_ram_test", &generate_verilog(&uut)).unwrap();
+}
+
+fn tdp_policy_test(policy: ReadPolicy, output_register: bool) {
+    let uut = make_tdp_test(policy, output_register);
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<TDPRAMTest>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<TDPRAMTest>| {
+        let mut x = sim.init()?;
+        let latency = if output_register { 2 } else { 1 };
+        wait_clock_true!(sim, clock, x);
+        // Fill the memory through port A, and read it back through port B
+        x.ram.a_enable.next = true;
+        x.ram.a_write_enable.next = 0xF.into();
+        for ndx in 0..64_u32 {
+            x.ram.a_address.next = ndx.to_bits();
+            x.ram.a_write_data.next = (ndx * 0x0101_0101).to_bits();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        x.ram.a_enable.next = false;
+        x.ram.a_write_enable.next = 0.into();
+        x.ram.b_enable.next = true;
+        for ndx in 0..64_u32 {
+            x.ram.b_address.next = ndx.to_bits();
+            wait_clock_cycles!(sim, clock, x, latency);
+            sim_assert_eq!(
+                sim,
+                x.ram.b_read_data.val(),
+                (ndx * 0x0101_0101).to_bits::<32>(),
+                x
+            );
+        }
+        // Read address 5 on port A, then write two of its lanes
+        x.ram.b_enable.next = false;
+        x.ram.a_enable.next = true;
+        x.ram.a_address.next = 5.into();
+        wait_clock_cycles!(sim, clock, x, latency);
+        sim_assert_eq!(sim, x.ram.a_read_data.val(), 0x0505_0505, x);
+        x.ram.a_write_enable.next = 0b0110.into();
+        x.ram.a_write_data.next = 0xAABB_CCDD_u32.to_bits();
+        wait_clock_cycle!(sim, clock, x);
+        x.ram.a_write_enable.next = 0.into();
+        x.ram.a_enable.next = false;
+        if output_register {
+            wait_clock_cycle!(sim, clock, x);
+        }
+        let expected = match policy {
+            ReadPolicy::ReadFirst => 0x0505_0505,
+            ReadPolicy::WriteFirst => 0x05BB_CC05,
+            ReadPolicy::NoChange => 0x0505_0505,
+        };
+        sim_assert_eq!(sim, x.ram.a_read_data.val(), expected, x);
+        // The byte enables only change the selected lanes
+        x.ram.b_enable.next = true;
+        x.ram.b_address.next = 5.into();
+        wait_clock_cycles!(sim, clock, x, latency);
+        sim_assert_eq!(sim, x.ram.b_read_data.val(), 0x05BB_CC05, x);
+        // Reads hold while a port is disabled
+        x.ram.b_enable.next = false;
+        x.ram.b_address.next = 6.into();
+        wait_clock_cycles!(sim, clock, x, latency);
+        sim_assert_eq!(sim, x.ram.b_read_data.val(), 0x05BB_CC05, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000).unwrap();
+}
+
+#[test]
+fn test_tdp_ram_read_policies() {
+    for policy in [
+        ReadPolicy::ReadFirst,
+        ReadPolicy::WriteFirst,
+        ReadPolicy::NoChange,
+    ] {
+        tdp_policy_test(policy, false);
+        tdp_policy_test(policy, true);
+    }
+}
+
+#[test]
+fn test_tdp_ram_no_change_holds_read_data() {
+    let uut = make_tdp_test(ReadPolicy::NoChange, false);
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<TDPRAMTest>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<TDPRAMTest>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        x.ram.a_enable.next = true;
+        x.ram.a_write_enable.next = 0xF.into();
+        x.ram.a_address.next = 1.into();
+        x.ram.a_write_data.next = 0x1234_5678_u32.to_bits();
+        wait_clock_cycle!(sim, clock, x);
+        x.ram.a_write_enable.next = 0.into();
+        wait_clock_cycle!(sim, clock, x);
+        sim_assert_eq!(sim, x.ram.a_read_data.val(), 0x1234_5678, x);
+        // A run of writes to other addresses leaves the read data alone
+        x.ram.a_write_enable.next = 0xF.into();
+        for ndx in 2..10_u32 {
+            x.ram.a_address.next = ndx.to_bits();
+            x.ram.a_write_data.next = ndx.to_bits();
+            wait_clock_cycle!(sim, clock, x);
+            sim_assert_eq!(sim, x.ram.a_read_data.val(), 0x1234_5678, x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000).unwrap();
+}
+
+#[derive(LogicBlock)]
+struct SDPRAMTest {
+    pub write_clock: Signal<In, Clock>,
+    pub read_clock: Signal<In, Clock>,
+    pub ram: SimpleDualPortRAM<16, 5, 2>,
+}
+
+impl Logic for SDPRAMTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        self.ram.write_clock.next = self.write_clock.val();
+        self.ram.read_clock.next = self.read_clock.val();
+    }
+}
+
+fn make_sdp_test() -> SDPRAMTest {
+    let mut uut = SDPRAMTest {
+        write_clock: Default::default(),
+        read_clock: Default::default(),
+        ram: SimpleDualPortRAM::new(true),
+    };
+    uut.ram.write_address.connect();
+    uut.ram.write_enable.connect();
+    uut.ram.write_data.connect();
+    uut.ram.read_address.connect();
+    uut.ram.read_enable.connect();
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_sdp_ram_test_synthesizes() {
+    let uut = make_sdp_test();
+    yosys_validate("sdp_ram_test", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_sdp_ram_crosses_clocks() {
+    let uut = make_sdp_test();
+    let data = (0..32)
+        .map(|_| rand::random::<u16>() as u32)
+        .collect::<Vec<_>>();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<SDPRAMTest>| {
+        x.write_clock.next = !x.write_clock.val()
+    });
+    sim.add_clock(7, |x: &mut Box<SDPRAMTest>| {
+        x.read_clock.next = !x.read_clock.val()
+    });
+    let write_data = data.clone();
+    sim.add_testbench(move |mut sim: Sim<SDPRAMTest>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, write_clock, x);
+        // Write the low lanes, then the high lanes
+        for lanes in [0b01_u32, 0b10] {
+            x.ram.write_enable.next = lanes.to_bits();
+            for (ndx, val) in write_data.iter().enumerate() {
+                x.ram.write_address.next = ndx.to_bits();
+                x.ram.write_data.next = val.to_bits();
+                wait_clock_cycle!(sim, write_clock, x);
+            }
+        }
+        x.ram.write_enable.next = 0.into();
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<SDPRAMTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, read_clock, x, 100);
+        x.ram.read_enable.next = true;
+        for (ndx, val) in data.iter().enumerate() {
+            x.ram.read_address.next = ndx.to_bits();
+            wait_clock_cycles!(sim, read_clock, x, 2);
+            sim_assert_eq!(sim, x.ram.read_data.val(), val.to_bits::<16>(), x);
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 100_000, &vcd_path!("sdp_ram.vcd"))
+        .unwrap();
+}
@@ -0,0 +1,102 @@
+use rust_hdl::docs::vcd2svg::trace_tail_to_txt;
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct CounterTest {
+    pub clock: Signal<In, Clock>,
+    pub enable: Signal<In, Bit>,
+    counter: DFF<Bits<8>>,
+}
+
+impl Logic for CounterTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, counter);
+        if self.enable.val() {
+            self.counter.d.next = self.counter.q.val() + 1;
+        }
+    }
+}
+
+fn uut() -> Box<CounterTest> {
+    let mut uut = CounterTest::default();
+    uut.enable.connect();
+    Box::new(uut)
+}
+
+fn halted(result: Result<(), SimError>) -> SimFailure {
+    match result {
+        Err(SimError::SimHalted(failure)) => *failure,
+        x => panic!("Expected the simulation to halt, got {:?}", x),
+    }
+}
+
+#[test]
+fn test_failed_assertions_record_context() {
+    let mut sim = simple_sim!(CounterTest, clock, 100_000_000, sim, {
+        let mut x = sim.init()?;
+        x.enable.next = true;
+        wait_clock_cycles!(sim, clock, x, 8);
+        sim_assert_eq!(sim, x.counter.q.val(), 12, x);
+        sim.done(x)
+    });
+    let failure = halted(sim.run(uut(), 1_000_000));
+    assert_eq!(failure.message, "x.counter.q.val() == 12");
+    assert_eq!(failure.expected.as_deref(), Some("12"));
+    assert_eq!(failure.actual.as_deref(), Some("Short(ShortBitVec(8))"));
+    assert!(failure.time > 0);
+    // The location is in the testbench, not in the simulation library
+    assert!(failure
+        .location
+        .starts_with("rust-hdl/tests/core_failure.rs:"));
+    assert!(failure.trace.is_none());
+    let text = SimError::SimHalted(Box::new(failure.clone())).to_string();
+    assert!(text.contains("expected 12, got Short(ShortBitVec(8))"));
+    assert!(text.contains(&format!("at time {}", failure.time)));
+    let mut sim = simple_sim!(CounterTest, clock, 100_000_000, sim, {
+        let x = sim.init()?;
+        sim_assert!(sim, x.enable.val(), x);
+        sim.done(x)
+    });
+    let failure = halted(sim.run(uut(), 1_000_000));
+    assert_eq!(failure.message, "x.enable.val()");
+    assert_eq!(failure.time, 0);
+    assert!(failure.expected.is_none());
+}
+
+#[test]
+fn test_testbench_panics_record_message() {
+    let mut sim = simple_sim!(CounterTest, clock, 100_000_000, sim, {
+        let x = sim.init()?;
+        assert!(x.enable.val(), "No value for the counter");
+        sim.done(x)
+    });
+    assert_eq!(
+        sim.run(uut(), 1_000_000),
+        Err(SimError::SimPanic("No value for the counter".into()))
+    );
+}
+
+#[test]
+fn test_failures_carry_a_trace_tail() {
+    let mut sim = simple_sim!(CounterTest, clock, 100_000_000, sim, {
+        let mut x = sim.init()?;
+        x.enable.next = true;
+        wait_clock_cycles!(sim, clock, x, 100);
+        sim_assert!(sim, x.counter.q.val() == 0, x);
+        sim.done(x)
+    });
+    // Keep the last 10 clock periods
+    sim.trace_tail(&["uut.clock", "uut.counter.q"], 100_000);
+    let failure = halted(sim.run(uut(), 10_000_000));
+    let tail = failure.trace.unwrap();
+    assert_eq!(tail.signals, vec!["uut.clock", "uut.counter.q"]);
+    assert_eq!(tail.end_time, failure.time);
+    assert_eq!(tail.start_time, failure.time - 100_000);
+    let text = trace_tail_to_txt(&tail, 100).unwrap();
+    println!("{}", text);
+    assert!(text.contains("uut.counter.q"));
+    // The counter values from the last few cycles are drawn
+    assert!(text.contains("0h5f"));
+    assert!(!text.contains("0h20"));
+}
@@ -0,0 +1,80 @@
+use rand::Rng;
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock)]
+struct CRCStreamTest {
+    feeder: LazyFIFOFeeder<Bits<8>, 12>,
+    fp: SyncFIFO<Bits<8>, 4, 5, 1>,
+    bp: SyncFIFO<Bits<8>, 4, 5, 1>,
+    reader: LazyFIFOReader<Bits<8>, 12>,
+    crc: CRCStream<16, 8>,
+    clock: Signal<In, Clock>,
+}
+
+impl Logic for CRCStreamTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, fp, bp, reader, feeder, crc);
+        FIFOWriteController::<Bits<8>>::join(&mut self.feeder.bus, &mut self.fp.bus_write);
+        FIFOReadResponder::<Bits<8>>::join(&mut self.fp.bus_read, &mut self.crc.read);
+        FIFOWriteController::<Bits<8>>::join(&mut self.crc.write, &mut self.bp.bus_write);
+        FIFOReadResponder::<Bits<8>>::join(&mut self.bp.bus_read, &mut self.reader.bus);
+    }
+}
+
+fn make_crc_stream_test(data: &[u8]) -> CRCStreamTest {
+    let data = data.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+    let mut uut = CRCStreamTest {
+        feeder: LazyFIFOFeeder::new(&data, &bursty_vec(data.len())),
+        fp: Default::default(),
+        bp: Default::default(),
+        reader: LazyFIFOReader::new(&data, &bursty_vec(data.len())),
+        crc: CRCStream::new(CRC16_MODBUS),
+        clock: Default::default(),
+    };
+    uut.clock.connect();
+    uut.feeder.start.connect();
+    uut.reader.start.connect();
+    uut.crc.clear.connect();
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_crc_stream_test_synthesizes() {
+    let uut = make_crc_stream_test(&[1, 2, 3, 4]);
+    let vlog = generate_verilog(&uut);
+    yosys_validate("crc_stream_test", &vlog).unwrap();
+}
+
+#[test]
+fn test_crc_stream_matches_reference() {
+    let data = (0..256)
+        .map(|_| rand::thread_rng().gen::<u8>())
+        .collect::<Vec<_>>();
+    let expected = CRC16_MODBUS.compute(&data);
+    let uut = make_crc_stream_test(&data);
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<CRCStreamTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<CRCStreamTest>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        x.crc.clear.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.crc.clear.next = false;
+        x.feeder.start.next = true;
+        x.reader.start.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.feeder.start.next = false;
+        x.reader.start.next = false;
+        x = sim.watch(|x| x.feeder.done.val() & x.reader.done.val(), x)?;
+        wait_clock_cycle!(sim, clock, x);
+        sim_assert!(sim, !x.reader.error.val(), x);
+        sim_assert_eq!(sim, x.crc.crc.val(), expected.to_bits::<16>(), x);
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 100_000, &vcd_path!("hls_crc_stream.vcd"))
+        .unwrap();
+}
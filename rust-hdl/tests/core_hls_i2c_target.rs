@@ -0,0 +1,248 @@
+use rust_hdl::prelude::*;
+use rust_hdl::widgets::i2c::i2c_controller::{I2CController, I2CControllerCmd};
+use std::time::Duration;
+
+#[derive(LogicBlock)]
+struct HLSI2CTargetTest {
+    clock: Signal<In, Clock>,
+    bus: SoCBusController<16, 8>,
+    target: HLSI2CTarget<4>,
+    controller: I2CController,
+    test_bus: I2CTestBus<2>,
+}
+
+impl HLSI2CTargetTest {
+    pub fn new(address: I2CTargetAddress) -> Self {
+        let config = I2CConfig {
+            delay_time: Duration::from_micros(5),
+            clock_speed_hz: 1_000_000,
+            timeout: Duration::from_millis(1),
+        };
+        Self {
+            clock: Default::default(),
+            bus: Default::default(),
+            target: HLSI2CTarget::new(address),
+            controller: I2CController::new(config),
+            test_bus: Default::default(),
+        }
+    }
+}
+
+impl Logic for HLSI2CTargetTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SoCBusController::<16, 8>::join(&mut self.bus, &mut self.target.upstream);
+        clock!(self, clock, controller);
+        self.bus.clock.next = self.clock.val();
+        I2CBusDriver::join(&mut self.controller.i2c, &mut self.test_bus.endpoints[0]);
+        I2CBusDriver::join(&mut self.target.i2c, &mut self.test_bus.endpoints[1]);
+    }
+}
+
+#[cfg(test)]
+fn make_test(address: I2CTargetAddress) -> HLSI2CTargetTest {
+    let mut uut = HLSI2CTargetTest::new(address);
+    uut.clock.connect();
+    uut.controller.cmd.connect();
+    uut.controller.run.connect();
+    uut.controller.write_data_in.connect();
+    uut.controller.address_10.connect();
+    uut.connect_all();
+    uut
+}
+
+// Select one of the ports on the target (address, write_data, read_data, status)
+macro_rules! bus_select {
+    ($sim: ident, $uut: ident, $port: expr) => {
+        wait_clock_true!($sim, clock, $uut);
+        $uut.bus.address.next = ($port as u32).to_bits();
+        $uut.bus.address_strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.bus.address_strobe.next = false;
+    };
+}
+
+macro_rules! bus_write {
+    ($sim: ident, $uut: ident, $val: expr) => {
+        $uut = $sim.watch(|x| x.bus.ready.val(), $uut)?;
+        $uut.bus.from_controller.next = ($val as u32).to_bits();
+        $uut.bus.strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.bus.strobe.next = false;
+    };
+}
+
+macro_rules! bus_read {
+    ($sim: ident, $uut: ident) => {{
+        $uut = $sim.watch(|x| x.bus.ready.val(), $uut)?;
+        let val = $uut.bus.to_controller.val();
+        $uut.bus.strobe.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.bus.strobe.next = false;
+        val
+    }};
+}
+
+#[test]
+fn test_hls_i2c_target_synthesizes() {
+    let uut = make_test(I2CTargetAddress::SevenBit(0x53));
+    yosys_validate("hls_i2c_target_test", &generate_verilog(&uut)).unwrap();
+}
+
+// Registers written over I2C show up on the SoC bus (and in the status
+// port), and registers written over the SoC bus can be read back over I2C
+// using a repeated start.
+#[test]
+fn test_hls_i2c_target_seven_bit() {
+    let uut = make_test(I2CTargetAddress::SevenBit(0x53));
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<HLSI2CTargetTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<HLSI2CTargetTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        // A different address is not acknowledged
+        i2c_begin_write!(sim, clock, x, 0x54);
+        sim_assert!(sim, x.controller.nack.val(), x);
+        i2c_end_transmission!(sim, clock, x);
+        // Write registers 3, 4 and 5 from the I2C side
+        i2c_begin_write!(sim, clock, x, 0x53);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 3);
+        for val in [0xDE, 0xAD, 0x42] {
+            i2c_write!(sim, clock, x, val);
+            sim_assert!(sim, x.controller.ack.val(), x);
+        }
+        i2c_end_transmission!(sim, clock, x);
+        // The status shows the last register written, and is cleared on read
+        bus_select!(sim, x, 3);
+        let status = bus_read!(sim, x);
+        sim_assert_eq!(sim, status, (I2C_TARGET_WRITTEN | 5) as u64, x);
+        bus_select!(sim, x, 3);
+        let status = bus_read!(sim, x);
+        sim_assert_eq!(sim, status, 5, x);
+        // Read the registers on the SoC bus
+        bus_select!(sim, x, 0);
+        bus_write!(sim, x, 3);
+        bus_select!(sim, x, 2);
+        for val in [0xDE, 0xAD, 0x42] {
+            let reg = bus_read!(sim, x);
+            sim_assert_eq!(sim, reg, val, x);
+        }
+        // Write registers 8..12 from the SoC bus
+        bus_select!(sim, x, 0);
+        bus_write!(sim, x, 8);
+        bus_select!(sim, x, 1);
+        for val in [0x12, 0x34, 0x56, 0x78] {
+            bus_write!(sim, x, val);
+        }
+        wait_clock_cycles!(sim, clock, x, 4);
+        // Read them back over I2C, using a repeated start
+        i2c_begin_write!(sim, clock, x, 0x53);
+        i2c_write!(sim, clock, x, 8);
+        i2c_begin_read!(sim, clock, x, 0x53);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        for val in [0x12, 0x34, 0x56] {
+            let reg = i2c_read!(sim, clock, x);
+            sim_assert_eq!(sim, reg, val, x);
+        }
+        let reg = i2c_read_last!(sim, clock, x);
+        sim_assert_eq!(sim, reg, 0x78, x);
+        i2c_end_transmission!(sim, clock, x);
+        // Reads from the SoC bus did not disturb the I2C writes
+        bus_select!(sim, x, 0);
+        bus_write!(sim, x, 4);
+        bus_select!(sim, x, 2);
+        let reg = bus_read!(sim, x);
+        sim_assert_eq!(sim, reg, 0xAD, x);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        1_000_000_000_000,
+        &vcd_path!("hls_i2c_target_7.vcd"),
+    )
+    .unwrap();
+}
+
+// Begin a transfer to a 10 bit address
+macro_rules! i2c_begin_10 {
+    ($sim: ident, $uut: ident, $cmd: expr, $addr: expr) => {
+        $uut = $sim.watch(|x| !x.controller.busy.val(), $uut)?;
+        wait_clock_true!($sim, clock, $uut);
+        $uut.controller.cmd.next = $cmd;
+        $uut.controller.address_10.next = ($addr as u32).to_bits();
+        $uut.controller.run.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.controller.run.next = false;
+        $uut = $sim.watch(|x| x.controller.nack.val() | x.controller.ack.val(), $uut)?;
+    };
+}
+
+#[test]
+fn test_hls_i2c_target_ten_bit() {
+    let uut = make_test(I2CTargetAddress::TenBit(0x2A5));
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<HLSI2CTargetTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<HLSI2CTargetTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        // Only the low byte differs, so the target drops out after it
+        i2c_begin_10!(sim, x, I2CControllerCmd::BeginWrite10, 0x2A6);
+        sim_assert!(sim, x.controller.nack.val(), x);
+        i2c_end_transmission!(sim, clock, x);
+        // Nor does it respond to a read without being addressed first
+        i2c_begin_write!(sim, clock, x, 0x7A);
+        i2c_end_transmission!(sim, clock, x);
+        i2c_begin_read!(sim, clock, x, 0x7A);
+        sim_assert!(sim, x.controller.nack.val(), x);
+        i2c_end_transmission!(sim, clock, x);
+        // Write registers 14, 15 and 0 (the pointer wraps)
+        i2c_begin_10!(sim, x, I2CControllerCmd::BeginWrite10, 0x2A5);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 14);
+        for val in [0xCA, 0xFE, 0x99] {
+            i2c_write!(sim, clock, x, val);
+            sim_assert!(sim, x.controller.ack.val(), x);
+        }
+        i2c_end_transmission!(sim, clock, x);
+        bus_select!(sim, x, 3);
+        let status = bus_read!(sim, x);
+        sim_assert_eq!(sim, status, (I2C_TARGET_WRITTEN as u64), x);
+        bus_select!(sim, x, 0);
+        bus_write!(sim, x, 14);
+        bus_select!(sim, x, 2);
+        for val in [0xCA, 0xFE, 0x99] {
+            let reg = bus_read!(sim, x);
+            sim_assert_eq!(sim, reg, val, x);
+        }
+        // Write from the SoC bus, and read back over I2C.  The controller
+        // sends the address, then a repeated start and the read header.
+        bus_select!(sim, x, 0);
+        bus_write!(sim, x, 6);
+        bus_select!(sim, x, 1);
+        for val in [0xA5, 0x5A] {
+            bus_write!(sim, x, val);
+        }
+        wait_clock_cycles!(sim, clock, x, 4);
+        i2c_begin_10!(sim, x, I2CControllerCmd::BeginWrite10, 0x2A5);
+        i2c_write!(sim, clock, x, 6);
+        i2c_begin_10!(sim, x, I2CControllerCmd::BeginRead10, 0x2A5);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        let reg = i2c_read!(sim, clock, x);
+        sim_assert_eq!(sim, reg, 0xA5, x);
+        let reg = i2c_read_last!(sim, clock, x);
+        sim_assert_eq!(sim, reg, 0x5A, x);
+        i2c_end_transmission!(sim, clock, x);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        1_000_000_000_000,
+        &vcd_path!("hls_i2c_target_10.vcd"),
+    )
+    .unwrap();
+}
@@ -0,0 +1,197 @@
+use rust_hdl::prelude::*;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[derive(LogicBlock)]
+struct LogicAnalyzerTest {
+    to_cpu: FIFOReadController<Bits<16>>,
+    from_cpu: FIFOWriteController<Bits<16>>,
+    to_cpu_fifo: SyncFIFO<Bits<16>, 6, 7, 1>,
+    from_cpu_fifo: SyncFIFO<Bits<16>, 6, 7, 1>,
+    controller: BaseController<8>,
+    analyzer: LogicAnalyzer<32, 6>,
+    counter: DFF<Bits<8>>,
+    clock: Signal<In, Clock>,
+}
+
+impl Default for LogicAnalyzerTest {
+    fn default() -> Self {
+        Self {
+            to_cpu: Default::default(),
+            from_cpu: Default::default(),
+            to_cpu_fifo: Default::default(),
+            from_cpu_fifo: Default::default(),
+            controller: Default::default(),
+            analyzer: LogicAnalyzer::new(&[("count", 8), ("phase", 1)]),
+            counter: Default::default(),
+            clock: Default::default(),
+        }
+    }
+}
+
+impl Logic for LogicAnalyzerTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, to_cpu_fifo, from_cpu_fifo, controller);
+        dff_setup!(self, clock, counter);
+        FIFOWriteController::<Bits<16>>::join(
+            &mut self.from_cpu,
+            &mut self.from_cpu_fifo.bus_write,
+        );
+        FIFOReadResponder::<Bits<16>>::join(
+            &mut self.from_cpu_fifo.bus_read,
+            &mut self.controller.from_cpu,
+        );
+        FIFOReadController::<Bits<16>>::join(&mut self.to_cpu, &mut self.to_cpu_fifo.bus_read);
+        FIFOWriteResponder::<Bits<16>>::join(
+            &mut self.to_cpu_fifo.bus_write,
+            &mut self.controller.to_cpu,
+        );
+        SoCBusController::<16, 8>::join(&mut self.controller.bus, &mut self.analyzer.upstream);
+        // The device under observation is a free running counter
+        self.counter.d.next = self.counter.q.val() + 1;
+        self.analyzer.probes.next =
+            bit_cast::<32, 8>(self.counter.q.val()).replace_bit(8, self.counter.q.val().get_bit(4));
+    }
+}
+
+fn make_test() -> LogicAnalyzerTest {
+    let mut uut = LogicAnalyzerTest::default();
+    uut.clock.connect();
+    uut.from_cpu.data.connect();
+    uut.from_cpu.write.connect();
+    uut.to_cpu.read.connect();
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_logic_analyzer_test_synthesizes() {
+    let uut = make_test();
+    yosys_validate("logic_analyzer_test", &generate_verilog(&uut)).unwrap();
+}
+
+enum LinkRequest {
+    Write(u8, Vec<u16>),
+    Read(u8, usize),
+}
+
+// A `ControllerLink` that forwards the requests to a testbench, which
+// feeds them to the controller in simulation.
+struct SimLink {
+    requests: Sender<LinkRequest>,
+    replies: Receiver<Vec<u16>>,
+}
+
+impl ControllerLink for SimLink {
+    type Error = ();
+    fn write(&mut self, address: u8, data: &[u16]) -> Result<(), ()> {
+        self.requests
+            .send(LinkRequest::Write(address, data.to_vec()))
+            .map_err(|_| ())?;
+        self.replies.recv().map_err(|_| ())?;
+        Ok(())
+    }
+    fn read(&mut self, address: u8, len: usize) -> Result<Vec<u16>, ()> {
+        self.requests
+            .send(LinkRequest::Read(address, len))
+            .map_err(|_| ())?;
+        self.replies.recv().map_err(|_| ())
+    }
+}
+
+fn run_capture(
+    make_trigger: impl FnOnce(&LogicAnalyzerHost) -> LogicAnalyzerTrigger + Send + 'static,
+) -> LogicAnalyzerCapture {
+    let uut = make_test();
+    let host = LogicAnalyzerHost::new(&uut.analyzer, 0);
+    let (request_tx, request_rx) = channel();
+    let (reply_tx, reply_rx) = channel();
+    let mut link = SimLink {
+        requests: request_tx,
+        replies: reply_rx,
+    };
+    let capture = std::thread::spawn(move || {
+        let trigger = make_trigger(&host);
+        host.arm(&mut link, &trigger).unwrap();
+        assert!(host.wait_for_capture(&mut link, 100).unwrap());
+        host.read_capture(&mut link, &trigger).unwrap()
+    });
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<LogicAnalyzerTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<LogicAnalyzerTest>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        while let Ok(request) = request_rx.recv() {
+            let (words, read_count) = match request {
+                LinkRequest::Write(address, data) => {
+                    let mut words = vec![0x0300 | (address as u16), data.len() as u16];
+                    words.extend(data);
+                    (words, 0)
+                }
+                LinkRequest::Read(address, len) => {
+                    (vec![0x0200 | (address as u16), len as u16], len)
+                }
+            };
+            for word in words {
+                x = sim.watch(|x| !x.from_cpu.full.val(), x)?;
+                x.from_cpu.data.next = word.to_bits();
+                x.from_cpu.write.next = true;
+                wait_clock_cycle!(sim, clock, x);
+                x.from_cpu.write.next = false;
+            }
+            let mut reply = vec![];
+            for _ in 0..read_count {
+                x = sim.watch(|x| !x.to_cpu.empty.val(), x)?;
+                reply.push(x.to_cpu.data.val().to_u16());
+                x.to_cpu.read.next = true;
+                wait_clock_cycle!(sim, clock, x);
+                x.to_cpu.read.next = false;
+            }
+            reply_tx.send(reply).unwrap();
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 1_000_000).unwrap();
+    capture.join().unwrap()
+}
+
+#[test]
+fn test_logic_analyzer_value_and_edge_trigger() {
+    let capture = run_capture(|host| {
+        host.trigger()
+            .equals("count", 0x30)
+            .rising("phase")
+            .pre_trigger(10)
+    });
+    assert_eq!(capture.samples.len(), 64);
+    assert_eq!(capture.trigger_index, 10);
+    // The samples are consecutive counts, with the trigger sample in place
+    for (ndx, _) in capture.samples.iter().enumerate() {
+        let count = (0x30 + ndx as u128 + 0x100 - 10) & 0xFF;
+        assert_eq!(capture.probe(ndx, "count"), count);
+        assert_eq!(capture.probe(ndx, "phase"), (count >> 4) & 1);
+    }
+    let mut vcd = vec![];
+    capture.write_vcd(&mut vcd, 10_000).unwrap();
+    let vcd = String::from_utf8(vcd).unwrap();
+    assert!(vcd.contains(" count $end"));
+    assert!(vcd.contains(" phase $end"));
+    assert!(vcd.contains(" trigger $end"));
+    capture
+        .write_vcd(
+            std::fs::File::create(vcd_path!("logic_analyzer_capture.vcd")).unwrap(),
+            10_000,
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_logic_analyzer_falling_edge_trigger() {
+    let capture = run_capture(|host| host.trigger().falling("phase").pre_trigger(63));
+    // All of the capture is from before the trigger
+    assert_eq!(capture.probe(63, "phase"), 0);
+    assert_eq!(capture.probe(62, "phase"), 1);
+    assert_eq!(capture.probe(63, "count") & 0x1F, 0);
+}
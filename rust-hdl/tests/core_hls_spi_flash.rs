@@ -0,0 +1,235 @@
+use rust_hdl::prelude::*;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+#[derive(LogicBlock)]
+struct SPIFlashTest {
+    to_cpu: FIFOReadController<Bits<16>>,
+    from_cpu: FIFOWriteController<Bits<16>>,
+    to_cpu_fifo: SyncFIFO<Bits<16>, 6, 7, 1>,
+    from_cpu_fifo: SyncFIFO<Bits<16>, 6, 7, 1>,
+    controller: BaseController<8>,
+    flash_controller: HLSSPIFlash,
+    flash: W25QSimulator<13>,
+    clock: Signal<In, Clock>,
+    flash_clock: Signal<In, Clock>,
+}
+
+impl SPIFlashTest {
+    fn new(quad: bool) -> Self {
+        let spi_config = SPIConfig {
+            clock_speed: 10_000_000,
+            cs_off: true,
+            mosi_off: true,
+            speed_hz: 1_000_000,
+            cpha: true,
+            cpol: true,
+        };
+        // The flash model runs from its own (faster) clock, and the
+        // program and erase times are shortened to keep the test quick
+        let flash_config = W25QConfig {
+            spi: SPIConfig {
+                clock_speed: 50_000_000,
+                ..spi_config
+            },
+            page_program_time: Duration::from_micros(20),
+            erase_time: Duration::from_micros(100),
+        };
+        Self {
+            to_cpu: Default::default(),
+            from_cpu: Default::default(),
+            to_cpu_fifo: Default::default(),
+            from_cpu_fifo: Default::default(),
+            controller: Default::default(),
+            flash_controller: if quad {
+                HLSSPIFlash::with_quad_read(spi_config)
+            } else {
+                HLSSPIFlash::new(spi_config)
+            },
+            flash: W25QSimulator::new(flash_config),
+            clock: Default::default(),
+            flash_clock: Default::default(),
+        }
+    }
+}
+
+impl Logic for SPIFlashTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, to_cpu_fifo, from_cpu_fifo, controller);
+        self.flash.clock.next = self.flash_clock.val();
+        FIFOWriteController::<Bits<16>>::join(
+            &mut self.from_cpu,
+            &mut self.from_cpu_fifo.bus_write,
+        );
+        FIFOReadResponder::<Bits<16>>::join(
+            &mut self.from_cpu_fifo.bus_read,
+            &mut self.controller.from_cpu,
+        );
+        FIFOReadController::<Bits<16>>::join(&mut self.to_cpu, &mut self.to_cpu_fifo.bus_read);
+        FIFOWriteResponder::<Bits<16>>::join(
+            &mut self.to_cpu_fifo.bus_write,
+            &mut self.controller.to_cpu,
+        );
+        SoCBusController::<16, 8>::join(
+            &mut self.controller.bus,
+            &mut self.flash_controller.upstream,
+        );
+        QSPIWiresMaster::join(&mut self.flash_controller.spi, &mut self.flash.wires);
+    }
+}
+
+fn make_test(quad: bool) -> SPIFlashTest {
+    let mut uut = SPIFlashTest::new(quad);
+    uut.clock.connect();
+    uut.flash_clock.connect();
+    uut.from_cpu.data.connect();
+    uut.from_cpu.write.connect();
+    uut.to_cpu.read.connect();
+    uut.connect_all();
+    uut
+}
+
+enum LinkRequest {
+    Write(u8, Vec<u16>),
+    Read(u8, usize),
+}
+
+// A `ControllerLink` that forwards the requests to a testbench, which
+// feeds them to the controller in simulation.
+struct SimLink {
+    requests: Sender<LinkRequest>,
+    replies: Receiver<Vec<u16>>,
+}
+
+impl ControllerLink for SimLink {
+    type Error = ();
+    fn write(&mut self, address: u8, data: &[u16]) -> Result<(), ()> {
+        self.requests
+            .send(LinkRequest::Write(address, data.to_vec()))
+            .map_err(|_| ())?;
+        self.replies.recv().map_err(|_| ())?;
+        Ok(())
+    }
+    fn read(&mut self, address: u8, len: usize) -> Result<Vec<u16>, ()> {
+        self.requests
+            .send(LinkRequest::Read(address, len))
+            .map_err(|_| ())?;
+        self.replies.recv().map_err(|_| ())
+    }
+}
+
+fn run_host<T: Send + 'static>(
+    quad: bool,
+    host_fn: impl FnOnce(&SPIFlashHost, &mut SimLink) -> T + Send + 'static,
+) -> T {
+    let uut = make_test(quad);
+    let host = SPIFlashHost::new(0);
+    let (request_tx, request_rx) = channel();
+    let (reply_tx, reply_rx) = channel();
+    let mut link = SimLink {
+        requests: request_tx,
+        replies: reply_rx,
+    };
+    let result = std::thread::spawn(move || host_fn(&host, &mut link));
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<SPIFlashTest>| x.clock.next = !x.clock.val());
+    sim.add_clock(1, |x: &mut Box<SPIFlashTest>| {
+        x.flash_clock.next = !x.flash_clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<SPIFlashTest>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        while let Ok(request) = request_rx.recv() {
+            let (words, read_count) = match request {
+                LinkRequest::Write(address, data) => {
+                    let mut words = vec![0x0300 | (address as u16), data.len() as u16];
+                    words.extend(data);
+                    (words, 0)
+                }
+                LinkRequest::Read(address, len) => {
+                    (vec![0x0200 | (address as u16), len as u16], len)
+                }
+            };
+            for word in words {
+                x = sim.watch(|x| !x.from_cpu.full.val(), x)?;
+                x.from_cpu.data.next = word.to_bits();
+                x.from_cpu.write.next = true;
+                wait_clock_cycle!(sim, clock, x);
+                x.from_cpu.write.next = false;
+            }
+            let mut reply = vec![];
+            for _ in 0..read_count {
+                x = sim.watch(|x| !x.to_cpu.empty.val(), x)?;
+                reply.push(x.to_cpu.data.val().to_u16());
+                x.to_cpu.read.next = true;
+                wait_clock_cycle!(sim, clock, x);
+                x.to_cpu.read.next = false;
+            }
+            reply_tx.send(reply).unwrap();
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000_000).unwrap();
+    result.join().unwrap()
+}
+
+#[test]
+fn test_spi_flash_test_synthesizes() {
+    let uut = make_test(false);
+    yosys_validate("spi_flash_test", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_spi_flash_quad_test_synthesizes() {
+    let uut = make_test(true);
+    yosys_validate("spi_flash_quad_test", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_spi_flash_id_and_status() {
+    let (id, status) = run_host(false, |host, link| {
+        (host.jedec_id(link).unwrap(), host.status(link).unwrap())
+    });
+    assert_eq!(id, 0xEF_40_0D);
+    assert_eq!(status, 0);
+}
+
+#[test]
+fn test_spi_flash_write_bin() {
+    // An (odd length) image that crosses a page boundary, which is written
+    // twice, so that the second write relies on the sector erase
+    let first = (0..161).map(|x| (x * 7 + 3) as u8).collect::<Vec<_>>();
+    let second = first.iter().map(|x| !x).collect::<Vec<_>>();
+    let path = std::env::temp_dir().join("spi_flash_test_image.bin");
+    std::fs::write(&path, &first).unwrap();
+    let expected = second.clone();
+    let (readback, erased) = run_host(false, move |host, link| {
+        host.write_bin(link, 0x0F0, &path).unwrap();
+        host.write_image(link, 0x0F0, &second).unwrap();
+        (
+            host.read(link, 0x0F0, second.len()).unwrap(),
+            host.read(link, 0x0E0, 16).unwrap(),
+        )
+    });
+    assert_eq!(readback, expected);
+    // The rest of the sector is erased
+    assert!(erased.iter().all(|x| *x == 0xFF));
+}
+
+#[test]
+fn test_spi_flash_quad_read() {
+    // Written on a single lane, and read back with the fast read quad
+    // output command, both from the start of a read and across a page
+    let image = (0..300).map(|x| (x * 13 + 5) as u8).collect::<Vec<_>>();
+    let expected = image.clone();
+    let (readback, tail) = run_host(true, move |host, link| {
+        host.write_image(link, 0x0F0, &image).unwrap();
+        (
+            host.read(link, 0x0F0, image.len()).unwrap(),
+            host.read(link, 0x1FB, 7).unwrap(),
+        )
+    });
+    assert_eq!(readback, expected);
+    assert_eq!(tail, expected[0x10B..0x112]);
+}
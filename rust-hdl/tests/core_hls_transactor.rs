@@ -0,0 +1,183 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct FIFOTest {
+    writer: FIFOWriteController<Bits<16>>,
+    reader: FIFOReadController<Bits<16>>,
+    fifo: SyncFIFO<Bits<16>, 4, 5, 1>,
+    clock: Signal<In, Clock>,
+}
+
+impl Logic for FIFOTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, fifo);
+        FIFOWriteController::<Bits<16>>::join(&mut self.writer, &mut self.fifo.bus_write);
+        FIFOReadController::<Bits<16>>::join(&mut self.reader, &mut self.fifo.bus_read);
+    }
+}
+
+fn run_fifo_test<M: ReferenceModel<Input = Bits<16>, Output = Bits<16>>>(
+    rng: SimRng,
+    board: Scoreboard<M>,
+) -> Result<(), SimError> {
+    let mut uut = FIFOTest::default();
+    uut.clock.connect();
+    uut.writer.data.connect();
+    uut.writer.write.connect();
+    uut.reader.read.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<FIFOTest>| x.clock.next = !x.clock.val());
+    let stimulus = Stimulus::Weighted(vec![
+        (4, Stimulus::Uniform),
+        (1, Stimulus::Range(0, 3)),
+        (1, Stimulus::Values(vec![0xFFFF.into()])),
+    ]);
+    let mut driver_rng = rng.clone();
+    let data = driver_rng.samples(&stimulus, 100);
+    let driver_board = board.clone();
+    sim.add_testbench(move |mut sim: Sim<FIFOTest>| {
+        let mut x = sim.init()?;
+        let mut driver = FIFOWriteDriver::new(|x: &mut FIFOTest| &mut x.writer, |x| x.clock.val())
+            .with_idle(driver_rng.clone(), 0.2, 10);
+        wait_clock_true!(sim, clock, x);
+        for word in data.clone() {
+            driver_board.input(word);
+            x = driver.write(&mut sim, x, word)?;
+        }
+        sim.done(x)
+    });
+    let monitor_board = board.clone();
+    sim.add_testbench(move |mut sim: Sim<FIFOTest>| {
+        let mut x = sim.init()?;
+        let mut reader = FIFOReadDriver::new(|x: &mut FIFOTest| &mut x.reader, |x| x.clock.val())
+            .with_idle(SimRng::new(rng.seed() + 1), 0.3, 20);
+        wait_clock_true!(sim, clock, x);
+        for _ in 0..100 {
+            let (y, word) = reader.read(&mut sim, x)?;
+            x = y;
+            sim_scoreboard!(sim, monitor_board, word, x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000)
+}
+
+#[test]
+fn test_fifo_with_random_stimulus_and_scoreboard() {
+    let rng = SimRng::from_env();
+    let board = Scoreboard::new("sync_fifo", rng.seed(), PassThrough::default());
+    run_fifo_test(rng, board.clone()).unwrap();
+    assert_eq!(board.finish(), Ok(100));
+}
+
+// A model that is wrong about one value, to check that the scoreboard
+// catches the mismatch, halts the simulation and reports the seed.
+struct BrokenModel;
+
+impl ReferenceModel for BrokenModel {
+    type Input = Bits<16>;
+    type Output = Bits<16>;
+    fn apply(&mut self, input: Bits<16>) -> Vec<Bits<16>> {
+        if input == 0xFFFF {
+            vec![0.into()]
+        } else {
+            vec![input]
+        }
+    }
+}
+
+#[test]
+fn test_scoreboard_mismatch_halts_simulation() {
+    // The stimulus for this seed includes 0xFFFF
+    let rng = SimRng::new(0x5EED);
+    let board = Scoreboard::new("broken", rng.seed(), BrokenModel);
+    let result = run_fifo_test(rng, board.clone());
+    assert!(matches!(result, Err(SimError::SimHalted(_))));
+    let err = board.finish().unwrap_err();
+    assert!(err.contains("Scoreboard broken failed: transaction"));
+    assert!(err.contains("RUST_HDL_SEED=0x5eed"));
+}
+
+#[derive(LogicBlock)]
+struct BusTest {
+    bus: SoCBusController<16, 8>,
+    bridge: Bridge<16, 8, 2>,
+    mosi: MOSIFIFOPort<16, 4, 5, 1>,
+    miso: MISOFIFOPort<16, 4, 5, 1>,
+    link: FIFOLink<Bits<16>>,
+}
+
+impl Default for BusTest {
+    fn default() -> Self {
+        Self {
+            bus: Default::default(),
+            bridge: Bridge::new(["mosi", "miso"]),
+            mosi: Default::default(),
+            miso: Default::default(),
+            link: Default::default(),
+        }
+    }
+}
+
+impl Logic for BusTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        SoCBusController::<16, 8>::join(&mut self.bus, &mut self.bridge.upstream);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[0], &mut self.mosi.bus);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[1], &mut self.miso.bus);
+        FIFOReadController::<Bits<16>>::join(&mut self.link.read, &mut self.mosi.fifo_bus);
+        FIFOWriteController::<Bits<16>>::join(&mut self.link.write, &mut self.miso.fifo_bus);
+    }
+}
+
+#[test]
+fn test_soc_bus_driver_loopback() {
+    let mut uut = BusTest::default();
+    uut.connect_all();
+    let mut rng = SimRng::from_env();
+    let board = Scoreboard::new("soc_bus_loopback", rng.seed(), PassThrough::default());
+    let blocks = (0..5)
+        .map(|_| {
+            let len = rng.range(1, 8) as usize;
+            rng.samples::<Bits<16>>(&Stimulus::Uniform, len)
+        })
+        .collect::<Vec<_>>();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<BusTest>| {
+        x.bus.clock.next = !x.bus.clock.val()
+    });
+    let monitor = board.clone();
+    sim.add_testbench(move |mut sim: Sim<BusTest>| {
+        let mut x = sim.init()?;
+        let driver = SoCBusDriver::new(|x: &mut BusTest| &mut x.bus, |x| x.bus.clock.val());
+        wait_clock_true!(sim, bus.clock, x);
+        for block in blocks.clone() {
+            for word in &block {
+                monitor.input(*word);
+            }
+            x = driver.write(&mut sim, x, 0.into(), &block)?;
+            wait_clock_cycles!(sim, bus.clock, x, 5);
+            let (y, words) = driver.read(&mut sim, x, 1.into(), block.len())?;
+            x = y;
+            for word in words {
+                sim_scoreboard!(sim, monitor, word, x);
+            }
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 100_000, &vcd_path!("soc_bus_driver.vcd"))
+        .unwrap();
+    assert!(board.finish().unwrap() > 5);
+}
+
+#[test]
+#[should_panic]
+fn test_idling_requires_at_least_one_cycle() {
+    let _ = FIFOWriteDriver::new(|x: &mut FIFOTest| &mut x.writer, |x| x.clock.val()).with_idle(
+        SimRng::new(1),
+        0.5,
+        0,
+    );
+}
@@ -0,0 +1,172 @@
+use rust_hdl::prelude::*;
+
+const HOST_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+const HOST_IP: [u8; 4] = [192, 168, 1, 1];
+const HOST_PORT: u16 = 6000;
+
+fn config() -> UDPConfig {
+    UDPConfig {
+        mac: [0x02, 0x00, 0x00, 0x12, 0x34, 0x56],
+        ip: [192, 168, 1, 10],
+        port: 5000,
+    }
+}
+
+fn ip_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], x[1]]) as u32)
+        .sum::<u32>();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn arp_request() -> Vec<u8> {
+    let mut frame = vec![0xFF; 6];
+    frame.extend_from_slice(&HOST_MAC);
+    frame.extend_from_slice(&[0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01]);
+    frame.extend_from_slice(&HOST_MAC);
+    frame.extend_from_slice(&HOST_IP);
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&config().ip);
+    frame
+}
+
+fn udp_datagram(payload: &[u8]) -> Vec<u8> {
+    let mut frame = config().mac.to_vec();
+    frame.extend_from_slice(&HOST_MAC);
+    frame.extend_from_slice(&[0x08, 0x00]);
+    let mut ip = vec![0x45, 0x00];
+    ip.extend_from_slice(&((payload.len() + 28) as u16).to_be_bytes());
+    ip.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00]);
+    ip.extend_from_slice(&HOST_IP);
+    ip.extend_from_slice(&config().ip);
+    let checksum = ip_checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&ip);
+    frame.extend_from_slice(&HOST_PORT.to_be_bytes());
+    frame.extend_from_slice(&config().port.to_be_bytes());
+    frame.extend_from_slice(&((payload.len() + 8) as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[derive(LogicBlock)]
+struct UDPHostTest {
+    pub eth_clock: Signal<In, Clock>,
+    pub sys_clock: Signal<In, Clock>,
+    phy: RMIIPHYSimulator,
+    host: UDPHost<8>,
+    bridge: Bridge<16, 8, 1>,
+    iport: MISOPort<16>,
+}
+
+impl Logic for UDPHostTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, eth_clock, phy);
+        self.host.eth_clock.next = self.eth_clock.val();
+        self.host.sys_clock.next = self.sys_clock.val();
+        RMIIWiresMAC::join(&mut self.host.rmii, &mut self.phy.wires);
+        SoCBusController::<16, 8>::join(&mut self.host.bus, &mut self.bridge.upstream);
+        SoCPortController::<16>::join(&mut self.bridge.nodes[0], &mut self.iport.bus);
+        self.iport.port_in.next = 0xCAFE.into();
+        self.iport.ready_in.next = true;
+    }
+}
+
+fn make_udp_host_test(frames: &[Vec<u8>]) -> UDPHostTest {
+    let path = vcd_path!("udp_host.pcap");
+    write_pcap(&path, frames).unwrap();
+    let mut uut = UDPHostTest {
+        eth_clock: Default::default(),
+        sys_clock: Default::default(),
+        phy: RMIIPHYSimulator::from_pcap(EthernetSpeed::Mbps100, &path).unwrap(),
+        host: UDPHost::new(
+            config(),
+            EthernetSpeed::Mbps100,
+            WordOrder::MostSignificantFirst,
+        ),
+        bridge: Bridge::new(["iport"]),
+        iport: Default::default(),
+    };
+    uut.phy.send.connect();
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_udp_host_synthesizes() {
+    let uut = make_udp_host_test(&[arp_request()]);
+    let vlog = generate_verilog(&uut);
+    yosys_validate("udp_host_test", &vlog).unwrap();
+}
+
+#[test]
+fn test_udp_host_answers_arp_and_commands() {
+    // Two pings followed by a read of the input port
+    let commands = [0x01, 0x42, 0x01, 0x43, 0x02, 0x00, 0x00, 0x01];
+    let uut = make_udp_host_test(&[arp_request(), udp_datagram(&commands)]);
+    let mut sim = Simulation::new();
+    sim.add_clock(10_000, |x: &mut Box<UDPHostTest>| {
+        x.eth_clock.next = !x.eth_clock.val()
+    });
+    sim.add_clock(4_000, |x: &mut Box<UDPHostTest>| {
+        x.sys_clock.next = !x.sys_clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<UDPHostTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, eth_clock, x, 20);
+        x.phy.send.next = true;
+        wait_clock_cycle!(sim, eth_clock, x);
+        x.phy.send.next = false;
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<UDPHostTest>| {
+        let mut x = sim.init()?;
+        let mut frames = vec![];
+        let mut payload = vec![];
+        while payload.len() < 6 {
+            let mut frame = vec![];
+            loop {
+                wait_clock_true!(sim, eth_clock, x);
+                if x.phy.capture.strobe.val() {
+                    frame.push(x.phy.capture.data.val().to_u8());
+                }
+                if x.phy.capture.end.val() {
+                    sim_assert!(sim, x.phy.capture.ok.val(), x);
+                    break;
+                }
+                wait_clock_false!(sim, eth_clock, x);
+            }
+            wait_clock_false!(sim, eth_clock, x);
+            sim_assert_eq!(sim, &frame[0..6], &HOST_MAC, x);
+            sim_assert_eq!(sim, &frame[6..12], &config().mac, x);
+            if frames.is_empty() {
+                // The first frame should be the ARP reply
+                sim_assert_eq!(sim, &frame[12..22], &[8, 6, 0, 1, 8, 0, 6, 4, 0, 2], x);
+                sim_assert_eq!(sim, &frame[22..28], &config().mac, x);
+                sim_assert_eq!(sim, &frame[28..32], &config().ip, x);
+                sim_assert_eq!(sim, &frame[32..38], &HOST_MAC, x);
+                sim_assert_eq!(sim, &frame[38..42], &HOST_IP, x);
+            } else {
+                sim_assert_eq!(sim, &frame[12..14], &[8, 0], x);
+                sim_assert_eq!(sim, ip_checksum(&frame[14..34]), 0, x);
+                sim_assert_eq!(sim, frame[23], 17, x);
+                sim_assert_eq!(sim, &frame[26..30], &config().ip, x);
+                sim_assert_eq!(sim, &frame[30..34], &HOST_IP, x);
+                sim_assert_eq!(sim, &frame[34..36], &config().port.to_be_bytes(), x);
+                sim_assert_eq!(sim, &frame[36..38], &HOST_PORT.to_be_bytes(), x);
+                let len = u16::from_be_bytes([frame[38], frame[39]]) as usize - 8;
+                payload.extend_from_slice(&frame[42..42 + len]);
+            }
+            frames.push(frame);
+        }
+        sim_assert_eq!(sim, payload, [0x01, 0x42, 0x01, 0x43, 0xCA, 0xFE], x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 1_000_000_000).unwrap();
+}
@@ -0,0 +1,349 @@
+use rust_hdl::prelude::*;
+use rust_hdl::widgets::i2c::i2c_controller::{I2CController, I2CControllerCmd};
+use std::time::Duration;
+
+// Two controllers and two targets on one bus.  The last endpoint on the
+// bus is driven directly by the testbench, to stretch the clock or to
+// get the bus stuck.
+#[derive(LogicBlock)]
+struct I2CMultiMasterTest {
+    clock: Signal<In, Clock>,
+    controller: I2CController,
+    controller_2: I2CController,
+    target_1: I2CTestTarget,
+    target_2: I2CTestTarget,
+    test_bus: I2CTestBus<5>,
+}
+
+impl Default for I2CMultiMasterTest {
+    fn default() -> Self {
+        let config = I2CConfig {
+            delay_time: Duration::from_micros(5),
+            clock_speed_hz: 1_000_000,
+            timeout: Duration::from_micros(500),
+        };
+        Self {
+            clock: Default::default(),
+            controller: I2CController::new(config),
+            controller_2: I2CController::new(config),
+            target_1: I2CTestTarget::new(0x53),
+            target_2: I2CTestTarget::new(0x57),
+            test_bus: Default::default(),
+        }
+    }
+}
+
+impl Logic for I2CMultiMasterTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, controller, controller_2, target_1, target_2);
+        I2CBusDriver::join(&mut self.controller.i2c, &mut self.test_bus.endpoints[0]);
+        I2CBusDriver::join(&mut self.controller_2.i2c, &mut self.test_bus.endpoints[1]);
+        I2CBusDriver::join(&mut self.target_1.i2c, &mut self.test_bus.endpoints[2]);
+        I2CBusDriver::join(&mut self.target_2.i2c, &mut self.test_bus.endpoints[3]);
+    }
+}
+
+#[cfg(test)]
+fn make_test() -> I2CMultiMasterTest {
+    let mut uut = I2CMultiMasterTest::default();
+    uut.clock.connect();
+    uut.controller.cmd.connect();
+    uut.controller.run.connect();
+    uut.controller.write_data_in.connect();
+    uut.controller.address_10.connect();
+    uut.controller_2.cmd.connect();
+    uut.controller_2.run.connect();
+    uut.controller_2.write_data_in.connect();
+    uut.controller_2.address_10.connect();
+    uut.test_bus.endpoints[4].sda.drive_low.connect();
+    uut.test_bus.endpoints[4].scl.drive_low.connect();
+    uut.connect_all();
+    uut
+}
+
+// Issue a command to the second controller, and wait for it to finish
+macro_rules! i2c_run_2 {
+    ($sim: ident, $uut: ident, $cmd: expr, $val: expr) => {
+        $uut = $sim.watch(|x| !x.controller_2.busy.val(), $uut)?;
+        wait_clock_true!($sim, clock, $uut);
+        $uut.controller_2.cmd.next = $cmd;
+        $uut.controller_2.write_data_in.next = ($val as u32).to_bits();
+        $uut.controller_2.run.next = true;
+        wait_clock_cycle!($sim, clock, $uut);
+        $uut.controller_2.run.next = false;
+        $uut = $sim.watch(
+            |x| {
+                x.controller_2.ack.val()
+                    | x.controller_2.nack.val()
+                    | x.controller_2.arbitration_lost.val()
+                    | !x.controller_2.busy.val()
+            },
+            $uut,
+        )?;
+    };
+}
+
+#[test]
+fn test_i2c_multi_master_synthesizes() {
+    let uut = make_test();
+    yosys_validate("i2c_multi_master", &generate_verilog(&uut)).unwrap();
+}
+
+// A target holds SCL low in the middle of a byte, and the controller waits
+// for it before carrying on.
+#[test]
+fn test_i2c_clock_stretching() {
+    let uut = make_test();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<I2CMultiMasterTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<I2CMultiMasterTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        i2c_begin_write!(sim, clock, x, 0x53);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 2);
+        // Stretch the clock for the next byte
+        x = sim.watch(|x| !x.controller.busy.val(), x)?;
+        wait_clock_true!(sim, clock, x);
+        x.controller.cmd.next = I2CControllerCmd::Write;
+        x.controller.write_data_in.next = 0xBE.into();
+        x.controller.run.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.controller.run.next = false;
+        x = sim.watch(|x| !x.test_bus.scl_state.val(), x)?;
+        x.test_bus.endpoints[4].scl.drive_low.next = true;
+        wait_clock_cycles!(sim, clock, x, 100);
+        sim_assert!(sim, x.controller.busy.val() & !x.controller.error.val(), x);
+        x.test_bus.endpoints[4].scl.drive_low.next = false;
+        x = sim.watch(|x| x.controller.nack.val() | x.controller.ack.val(), x)?;
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 0xEF);
+        i2c_end_transmission!(sim, clock, x);
+        // Read the value back
+        i2c_begin_write!(sim, clock, x, 0x53);
+        i2c_write!(sim, clock, x, 2);
+        i2c_begin_read!(sim, clock, x, 0x53);
+        let msb = i2c_read!(sim, clock, x);
+        let lsb = i2c_read_last!(sim, clock, x);
+        sim_assert_eq!(sim, msb, 0xBE, x);
+        sim_assert_eq!(sim, lsb, 0xEF, x);
+        i2c_end_transmission!(sim, clock, x);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        200_000_000_000,
+        &vcd_path!("i2c_stretch.vcd"),
+    )
+    .unwrap();
+}
+
+// A clock held low for too long is a timeout, as is a bus that never
+// becomes free.  A target stuck driving SDA low is freed up by clocking
+// the bus.
+#[test]
+fn test_i2c_timeout_and_recovery() {
+    let uut = make_test();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<I2CMultiMasterTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<I2CMultiMasterTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        // The clock is stretched for longer than the timeout
+        i2c_begin_write!(sim, clock, x, 0x53);
+        x = sim.watch(|x| !x.controller.busy.val(), x)?;
+        wait_clock_true!(sim, clock, x);
+        x.controller.cmd.next = I2CControllerCmd::Write;
+        x.controller.write_data_in.next = 0x01.into();
+        x.controller.run.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.controller.run.next = false;
+        x = sim.watch(|x| !x.test_bus.scl_state.val(), x)?;
+        x.test_bus.endpoints[4].scl.drive_low.next = true;
+        x = sim.watch(|x| x.controller.error.val(), x)?;
+        sim_assert!(sim, x.controller.timeout.val(), x);
+        x.test_bus.endpoints[4].scl.drive_low.next = false;
+        // Recovering sends a stop, after which the bus works again
+        wait_clock_true!(sim, clock, x);
+        x.controller.cmd.next = I2CControllerCmd::Recover;
+        x.controller.run.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.controller.run.next = false;
+        x = sim.watch(|x| !x.controller.busy.val(), x)?;
+        sim_assert!(
+            sim,
+            !x.controller.error.val() & !x.controller.timeout.val(),
+            x
+        );
+        // Now, SDA gets stuck low (as if a target was reset in the middle of a read)
+        x.test_bus.endpoints[4].sda.drive_low.next = true;
+        wait_clock_cycles!(sim, clock, x, 10);
+        x = sim.watch(|x| !x.controller.busy.val(), x)?;
+        wait_clock_true!(sim, clock, x);
+        x.controller.cmd.next = I2CControllerCmd::BeginWrite;
+        x.controller.write_data_in.next = 0x53.into();
+        x.controller.run.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.controller.run.next = false;
+        x = sim.watch(|x| x.controller.error.val(), x)?;
+        sim_assert!(sim, x.controller.timeout.val(), x);
+        wait_clock_true!(sim, clock, x);
+        x.controller.cmd.next = I2CControllerCmd::Recover;
+        x.controller.run.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.controller.run.next = false;
+        // The stuck target lets go after a few clocks
+        for _ in 0..3 {
+            x = sim.watch(|x| !x.test_bus.scl_state.val(), x)?;
+            x = sim.watch(|x| x.test_bus.scl_state.val(), x)?;
+        }
+        x.test_bus.endpoints[4].sda.drive_low.next = false;
+        x = sim.watch(|x| !x.controller.busy.val(), x)?;
+        sim_assert!(sim, !x.controller.error.val(), x);
+        // A normal transfer works again
+        i2c_begin_write!(sim, clock, x, 0x57);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 4);
+        i2c_write!(sim, clock, x, 0x12);
+        i2c_write!(sim, clock, x, 0x34);
+        i2c_end_transmission!(sim, clock, x);
+        i2c_begin_write!(sim, clock, x, 0x57);
+        i2c_write!(sim, clock, x, 4);
+        i2c_begin_read!(sim, clock, x, 0x57);
+        let msb = i2c_read!(sim, clock, x);
+        let lsb = i2c_read_last!(sim, clock, x);
+        sim_assert_eq!(sim, msb, 0x12, x);
+        sim_assert_eq!(sim, lsb, 0x34, x);
+        i2c_end_transmission!(sim, clock, x);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        200_000_000_000,
+        &vcd_path!("i2c_recover.vcd"),
+    )
+    .unwrap();
+}
+
+// Both controllers start at the same time.  The second one loses in the
+// address (0x57 > 0x53), and sends its address again once the bus is free.
+#[test]
+fn test_i2c_arbitration_in_address() {
+    let uut = make_test();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<I2CMultiMasterTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<I2CMultiMasterTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        i2c_begin_write!(sim, clock, x, 0x53);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 1);
+        i2c_write!(sim, clock, x, 0xAB);
+        i2c_write!(sim, clock, x, 0xCD);
+        i2c_end_transmission!(sim, clock, x);
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<I2CMultiMasterTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x57);
+        sim_assert!(sim, x.controller_2.ack.val(), x);
+        // By now, the first controller is done
+        sim_assert!(sim, !x.controller.busy.val(), x);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 1);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 0x12);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 0x34);
+        i2c_run_2!(sim, x, I2CControllerCmd::EndTransmission, 0);
+        // Check both writes
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x53);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 1);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginRead, 0x53);
+        i2c_run_2!(sim, x, I2CControllerCmd::Read, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0xAB, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::ReadLast, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0xCD, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x57);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 1);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginRead, 0x57);
+        i2c_run_2!(sim, x, I2CControllerCmd::Read, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0x12, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::ReadLast, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0x34, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::EndTransmission, 0);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        200_000_000_000,
+        &vcd_path!("i2c_arbitration_address.vcd"),
+    )
+    .unwrap();
+}
+
+// Both controllers address the same target, and the second one loses
+// while sending the register pointer.  It is told that arbitration was
+// lost, and starts the transfer over.
+#[test]
+fn test_i2c_arbitration_in_data() {
+    let uut = make_test();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<I2CMultiMasterTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<I2CMultiMasterTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        i2c_begin_write!(sim, clock, x, 0x53);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 5);
+        sim_assert!(sim, x.controller.ack.val(), x);
+        i2c_write!(sim, clock, x, 0x55);
+        i2c_write!(sim, clock, x, 0x66);
+        i2c_end_transmission!(sim, clock, x);
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<I2CMultiMasterTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x53);
+        sim_assert!(sim, x.controller_2.ack.val(), x);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 6);
+        sim_assert!(sim, x.controller_2.arbitration_lost.val(), x);
+        // Start over
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x53);
+        sim_assert!(sim, x.controller_2.ack.val(), x);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 6);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 0x77);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 0x88);
+        i2c_run_2!(sim, x, I2CControllerCmd::EndTransmission, 0);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x53);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 5);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginRead, 0x53);
+        i2c_run_2!(sim, x, I2CControllerCmd::Read, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0x55, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::ReadLast, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0x66, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginWrite, 0x53);
+        i2c_run_2!(sim, x, I2CControllerCmd::Write, 6);
+        i2c_run_2!(sim, x, I2CControllerCmd::BeginRead, 0x53);
+        i2c_run_2!(sim, x, I2CControllerCmd::Read, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0x77, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::ReadLast, 0);
+        sim_assert_eq!(sim, x.controller_2.read_data_out.val(), 0x88, x);
+        i2c_run_2!(sim, x, I2CControllerCmd::EndTransmission, 0);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        200_000_000_000,
+        &vcd_path!("i2c_arbitration_data.vcd"),
+    )
+    .unwrap();
+}
@@ -0,0 +1,182 @@
+use rust_hdl::prelude::*;
+use std::path::PathBuf;
+
+fn write_test_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join("rust_hdl_mem_init");
+    let _ = std::fs::create_dir_all(&dir);
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_rom_from_hex_file() {
+    let words = (0..200_u32).map(|x| (x * 37) & 0xFFF).collect::<Vec<_>>();
+    let text = words
+        .iter()
+        .map(|x| format!("{:03x}", x))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let path = write_test_file("rom.hex", text.as_bytes());
+    let mut uut = ROM::<Bits<12>, 8>::from_file(&path).unwrap();
+    uut.address.connect();
+    uut.connect_all();
+    let (vlog, files) = generate_verilog_with_memory_files(&uut, VerilogTarget::Generic);
+    assert!(vlog.contains("$readmemh"));
+    assert!(!vlog.contains("case (address)"));
+    assert_eq!(files.len(), 1);
+    let mut sim = Simulation::new();
+    sim.add_testbench(move |mut sim: Sim<ROM<Bits<12>, 8>>| {
+        let mut x = sim.init()?;
+        for (ndx, val) in words.iter().enumerate() {
+            x.address.next = ndx.to_bits();
+            x = sim.wait(1, x)?;
+            sim_assert_eq!(sim, x.data.val(), val.to_bits::<12>(), x);
+        }
+        // Past the end of the file the ROM reads as zero
+        x.address.next = 250.into();
+        x = sim.wait(1, x)?;
+        sim_assert_eq!(sim, x.data.val(), 0, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000).unwrap();
+}
+
+#[test]
+fn test_rom_from_hex_file_synthesizes() {
+    let path = write_test_file("rom_synth.hex", b"@4\n12\n34\n56\n");
+    let mut uut = ROM::<Bits<8>, 4>::from_file(&path).unwrap();
+    uut.address.connect();
+    uut.connect_all();
+    yosys_validate("rom_mem_file", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_sync_rom_from_intel_hex_file() {
+    let path = write_test_file(
+        "sync_rom.ihex",
+        b":040000001122334452\n:04000400556677883E\n:00000001FF\n",
+    );
+    let mut uut = SyncROM::<Bits<16>, 4>::from_file(&path).unwrap();
+    uut.address.connect();
+    uut.clock.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<SyncROM<Bits<16>, 4>>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<SyncROM<Bits<16>, 4>>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        for (ndx, val) in [0x2211_u32, 0x4433, 0x6655, 0x8877].iter().enumerate() {
+            x.address.next = ndx.to_bits();
+            wait_clock_cycle!(sim, clock, x);
+            sim_assert_eq!(sim, x.data.val(), val.to_bits::<16>(), x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000).unwrap();
+}
+
+#[test]
+fn test_ram_from_bin_file() {
+    let data = (0..64_u32).map(|x| (x * 3) as u8).collect::<Vec<_>>();
+    let path = write_test_file("ram.bin", &data);
+    let mut uut = RAM::<Bits<32>, 4>::from_file(&path).unwrap();
+    uut.read_address.connect();
+    uut.read_clock.connect();
+    uut.write_address.connect();
+    uut.write_clock.connect();
+    uut.write_data.connect();
+    uut.write_enable.connect();
+    uut.connect_all();
+    let (vlog, files) = generate_verilog_with_memory_files(&uut, VerilogTarget::Generic);
+    let name = vlog
+        .split("$readmemh(\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    let text = &files[name];
+    assert!(text.starts_with("@0\n09060300\n"));
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<RAM<Bits<32>, 4>>| {
+        x.read_clock.next = !x.read_clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<RAM<Bits<32>, 4>>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, read_clock, x);
+        for (ndx, val) in data.chunks(4).enumerate() {
+            x.read_address.next = ndx.to_bits();
+            wait_clock_cycle!(sim, read_clock, x);
+            let expected = u32::from_le_bytes([val[0], val[1], val[2], val[3]]);
+            sim_assert_eq!(sim, x.read_data.val(), expected.to_bits::<32>(), x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 10_000).unwrap();
+}
+
+#[test]
+fn test_unknown_extension_is_an_error() {
+    let path = write_test_file("rom.txt", b"12");
+    assert!(matches!(
+        load_memory_file::<8, 4>(&path),
+        Err(MemInitError::UnknownFormat(_))
+    ));
+}
+
+// The holes in the ROM are filled with the default value, and the contents
+// are either inlined into the Verilog, or loaded from a single `.mem` file.
+#[test]
+fn test_rom_verilog_text() {
+    let mut values = std::collections::BTreeMap::new();
+    values.insert(Bits::<4>::from(0), Bits::<8>::from(0x12));
+    values.insert(Bits::<4>::from(1), Bits::<8>::from(0x34));
+    values.insert(Bits::<4>::from(3), Bits::<8>::from(0x56));
+    let mut uut = ROM::new(values);
+    uut.address.connect();
+    uut.connect_all();
+    let module = |init: &str| {
+        format!(
+            "
+
+module top(address,data);
+    
+    // Module arguments
+    input wire  [3:0] address;
+    output reg  [7:0] data;
+    
+    // Update code (custom)
+    reg[7:0] mem[3:0];
+    
+    always @*
+    if (address <= 3) data = mem[address];
+    else data = 8'h0;
+    {}
+endmodule // top
+",
+            init
+        )
+    };
+    assert_eq!(
+        generate_verilog(&uut),
+        module(
+            "initial begin
+        mem[0] = 8'h12;
+        mem[1] = 8'h34;
+        mem[2] = 8'h00;
+        mem[3] = 8'h56;
+    end"
+        )
+    );
+    let (vlog, files) = generate_verilog_with_memory_files(&uut, VerilogTarget::Generic);
+    assert_eq!(files.len(), 1);
+    let (name, text) = files.iter().next().unwrap();
+    assert_eq!(text, "@0\n12\n34\n00\n56\n");
+    assert_eq!(
+        vlog,
+        module(&format!("initial $readmemh(\"{}\", mem);", name))
+    );
+}
@@ -0,0 +1,143 @@
+use rust_hdl::prelude::*;
+
+fn prbs_generator_test<const N: usize>(poly: PRBSPolynomial, seed: u32) {
+    let mut uut = PRBSGenerator::<N>::new(poly, seed);
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<PRBSGenerator<N>>| {
+        x.clock.next = !x.clock.val();
+    });
+    sim.add_testbench(move |mut sim: Sim<PRBSGenerator<N>>| {
+        let mut x = sim.init()?;
+        let mut model = PRBSModel::new(poly, seed);
+        wait_clock_cycles!(sim, clock, x, 10);
+        x.strobe.next = true;
+        for _ in 0..500 {
+            wait_clock_cycle!(sim, clock, x);
+            sim_assert_eq!(sim, x.data.val(), model.next_word(N).to_bits::<N>(), x);
+        }
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000).unwrap();
+}
+
+#[test]
+fn test_prbs_generator_matches_model() {
+    prbs_generator_test::<8>(PRBSPolynomial::PRBS7, 0x5A);
+    prbs_generator_test::<1>(PRBSPolynomial::PRBS15, 1);
+    prbs_generator_test::<16>(PRBSPolynomial::PRBS23, 0x12345);
+    prbs_generator_test::<32>(PRBSPolynomial::PRBS31, 0xDEAD_BEEF);
+}
+
+#[test]
+fn test_prbs_checker_counts_errors() {
+    let mut uut = PRBSChecker::<16>::new(PRBSPolynomial::PRBS31);
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<PRBSChecker<16>>| {
+        x.clock.next = !x.clock.val();
+    });
+    sim.add_testbench(move |mut sim: Sim<PRBSChecker<16>>| {
+        let mut x = sim.init()?;
+        let mut model = PRBSModel::new(PRBSPolynomial::PRBS31, 0x1357_9BDF);
+        wait_clock_cycles!(sim, clock, x, 10);
+        sim_assert!(sim, !x.locked.val(), x);
+        // Acquire lock on a clean stream
+        x.strobe.next = true;
+        for _ in 0..10 {
+            x.data.next = model.next_word(16).to_bits();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim_assert!(sim, x.locked.val(), x);
+        x.strobe.next = false;
+        x.clear.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.clear.next = false;
+        x.strobe.next = true;
+        // Inject single and double bit errors
+        let mut injected = 0_u32;
+        for ndx in 0..200 {
+            let mut word = model.next_word(16);
+            if ndx % 17 == 3 {
+                word ^= 0x0100;
+                injected += 1;
+            }
+            if ndx % 41 == 7 {
+                word ^= 0x8001;
+                injected += 2;
+            }
+            x.data.next = word.to_bits();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        x.strobe.next = false;
+        wait_clock_cycle!(sim, clock, x);
+        sim_assert!(sim, x.locked.val(), x);
+        sim_assert_eq!(sim, x.errors.val(), injected.to_bits::<32>(), x);
+        // Garbage should cause a loss of lock
+        x.strobe.next = true;
+        for ndx in 0..20_u64 {
+            x.data.next = ((!model.next_word(16) ^ ndx) & 0xFFFF).to_bits();
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim_assert!(sim, !x.locked.val(), x);
+        sim.done(x)
+    });
+    sim.run_to_file(Box::new(uut), 100_000, &vcd_path!("prbs_checker.vcd"))
+        .unwrap();
+}
+
+#[derive(LogicBlock)]
+struct PRBSLoopback {
+    pub clock: Signal<In, Clock>,
+    pub strobe: Signal<In, Bit>,
+    generator: PRBSGenerator<4>,
+    checker: PRBSChecker<4>,
+}
+
+impl Logic for PRBSLoopback {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, generator, checker);
+        self.generator.strobe.next = self.strobe.val();
+        self.checker.strobe.next = self.strobe.val();
+        self.checker.data.next = self.generator.data.val();
+        self.checker.clear.next = false;
+    }
+}
+
+#[test]
+fn test_prbs_loopback_synthesizes() {
+    let mut uut = PRBSLoopback {
+        clock: Default::default(),
+        strobe: Default::default(),
+        generator: PRBSGenerator::new(PRBSPolynomial::PRBS7, 1),
+        checker: PRBSChecker::new(PRBSPolynomial::PRBS7),
+    };
+    uut.connect_all();
+    yosys_validate("prbs_loopback", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_prbs_loopback_locks() {
+    let mut uut = PRBSLoopback {
+        clock: Default::default(),
+        strobe: Default::default(),
+        generator: PRBSGenerator::new(PRBSPolynomial::PRBS7, 1),
+        checker: PRBSChecker::new(PRBSPolynomial::PRBS7),
+    };
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<PRBSLoopback>| {
+        x.clock.next = !x.clock.val();
+    });
+    sim.add_testbench(move |mut sim: Sim<PRBSLoopback>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        x.strobe.next = true;
+        wait_clock_cycles!(sim, clock, x, 100);
+        sim_assert!(sim, x.checker.locked.val(), x);
+        sim_assert_eq!(sim, x.checker.errors.val(), 0, x);
+        sim.done(x)
+    });
+    sim.run(Box::new(uut), 100_000).unwrap();
+}
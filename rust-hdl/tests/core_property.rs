@@ -0,0 +1,174 @@
+use rust_hdl::prelude::*;
+use std::collections::VecDeque;
+
+#[derive(LogicBlock, Default)]
+struct FIFOTest {
+    writer: FIFOWriteController<Bits<8>>,
+    reader: FIFOReadController<Bits<8>>,
+    fifo: SyncFIFO<Bits<8>, 3, 4, 1>,
+    clock: Signal<In, Clock>,
+}
+
+impl Logic for FIFOTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, fifo);
+        FIFOWriteController::<Bits<8>>::join(&mut self.writer, &mut self.fifo.bus_write);
+        FIFOReadController::<Bits<8>>::join(&mut self.reader, &mut self.fifo.bus_read);
+    }
+}
+
+fn build() -> FIFOTest {
+    let mut uut = FIFOTest::default();
+    uut.clock.connect();
+    uut.writer.data.connect();
+    uut.writer.write.connect();
+    uut.reader.read.connect();
+    uut
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+    Push(Bits<8>),
+    Pop,
+}
+
+fn generate(rng: &mut SimRng) -> Op {
+    if rng.chance(0.6) {
+        Op::Push(rng.random())
+    } else {
+        Op::Pop
+    }
+}
+
+fn shrink(op: &Op) -> Vec<Op> {
+    match op {
+        Op::Push(x) => x.shrink().into_iter().map(Op::Push).collect(),
+        Op::Pop => vec![],
+    }
+}
+
+// Applies the operations to the FIFO, checking it against a `VecDeque`.
+// If `never_full` is set, the FIFO is also (wrongly) required to never
+// fill up.
+fn setup(sim: &mut Simulation<FIFOTest>, ops: &[Op], never_full: bool) {
+    let ops = ops.to_vec();
+    sim.add_clock(5, |x: &mut Box<FIFOTest>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<FIFOTest>| {
+        let mut x = sim.init()?;
+        let mut model = VecDeque::new();
+        wait_clock_true!(sim, clock, x);
+        for op in ops.clone() {
+            match op {
+                Op::Push(data) => {
+                    if !x.writer.full.val() {
+                        x.writer.data.next = data;
+                        x.writer.write.next = true;
+                        model.push_back(data);
+                    }
+                }
+                Op::Pop => {
+                    if !x.reader.empty.val() {
+                        sim_assert_eq!(sim, Some(x.reader.data.val()), model.pop_front(), x);
+                        x.reader.read.next = true;
+                    }
+                }
+            }
+            wait_clock_cycle!(sim, clock, x);
+            x.writer.write.next = false;
+            x.reader.read.next = false;
+            wait_clock_cycle!(sim, clock, x);
+            sim_assert_eq!(sim, x.reader.empty.val(), model.is_empty(), x);
+            sim_assert_eq!(sim, x.writer.full.val(), model.len() == 8, x);
+            if never_full {
+                sim_assert!(sim, !x.writer.full.val(), x);
+            }
+        }
+        sim.done(x)
+    });
+}
+
+#[test]
+fn test_fifo_matches_model_property() {
+    PropertyTest::new()
+        .cases(32)
+        .max_len(40)
+        .check(generate, build, |sim, ops| setup(sim, ops, false))
+        .unwrap_or_else(|failure| panic!("{}", failure));
+}
+
+#[test]
+fn test_failing_property_shrinks_to_minimal_sequence() {
+    let vcd = vcd_path!("property_minimal.vcd");
+    let _ = std::fs::remove_file(&vcd);
+    let failure = PropertyTest::new()
+        .seed(SimRng::new(42))
+        .cases(100)
+        .max_len(60)
+        .vcd_file(&vcd)
+        .shrink_with(shrink)
+        .check(generate, build, |sim, ops| setup(sim, ops, true))
+        .unwrap_err();
+    assert_eq!(failure.seed, 42);
+    assert!(matches!(failure.error, SimError::SimHalted(_)));
+    assert!(failure.original_len > 8);
+    // Filling the 8 entry FIFO takes exactly 8 pushes, of the simplest value
+    assert_eq!(failure.minimal, vec![Op::Push(0.into()); 8]);
+    assert!(failure.to_string().contains("RUST_HDL_SEED=0x2a"));
+    assert!(std::fs::metadata(&vcd).unwrap().len() > 0);
+}
+
+// Pushes values into the FIFO, and waits for each pop to find a value that
+// is (wrongly) required to be small.  A pop without a push waits forever.
+fn setup_blocking(sim: &mut Simulation<FIFOTest>, ops: &[Op]) {
+    let ops = ops.to_vec();
+    sim.add_clock(5, |x: &mut Box<FIFOTest>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<FIFOTest>| {
+        let mut x = sim.init()?;
+        wait_clock_true!(sim, clock, x);
+        for op in ops.clone() {
+            match op {
+                Op::Push(data) => {
+                    x.writer.data.next = data;
+                    x.writer.write.next = true;
+                }
+                Op::Pop => {
+                    x = sim.watch(|x| !x.reader.empty.val(), x)?;
+                    sim_assert!(sim, x.reader.data.val() < 0x80, x);
+                    x.reader.read.next = true;
+                }
+            }
+            wait_clock_cycle!(sim, clock, x);
+            x.writer.write.next = false;
+            x.reader.read.next = false;
+            wait_clock_cycle!(sim, clock, x);
+        }
+        sim.done(x)
+    });
+}
+
+#[test]
+fn test_shrinking_keeps_the_original_failure() {
+    // Alternate pushes of large values with pops, so every pop fails the
+    // assertion, but dropping a push leaves a pop waiting forever
+    let mut count = 0;
+    let generate = move |rng: &mut SimRng| {
+        count += 1;
+        if count % 2 == 1 {
+            Op::Push(rng.random::<Bits<8>>() | 0x80)
+        } else {
+            Op::Pop
+        }
+    };
+    let failure = PropertyTest::new()
+        .seed(SimRng::new(42))
+        .cases(1)
+        .max_len(10)
+        .max_time(10_000)
+        .check(generate, build, setup_blocking)
+        .unwrap_err();
+    assert!(matches!(failure.error, SimError::SimHalted(_)));
+    assert_eq!(failure.minimal.len(), 2);
+    assert!(matches!(failure.minimal[0], Op::Push(_)));
+    assert_eq!(failure.minimal[1], Op::Pop);
+}
@@ -0,0 +1,266 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock)]
+struct QSPITestPair {
+    clock: Signal<In, Clock>,
+    master: QSPIMaster<64>,
+    slave: QSPISlave<64>,
+}
+
+impl QSPITestPair {
+    pub fn new(config: SPIConfig) -> Self {
+        Self {
+            clock: Default::default(),
+            master: QSPIMaster::new(config),
+            slave: QSPISlave::new(config),
+        }
+    }
+}
+
+impl Logic for QSPITestPair {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, master, slave);
+        QSPIWiresMaster::join(&mut self.master.wires, &mut self.slave.wires);
+    }
+}
+
+#[cfg(test)]
+fn mk_qspi_config(cpha: bool, cpol: bool) -> SPIConfig {
+    SPIConfig {
+        clock_speed: 48_000_000,
+        cs_off: true,
+        mosi_off: true,
+        speed_hz: 1_200_000,
+        cpha,
+        cpol,
+    }
+}
+
+#[cfg(test)]
+fn mk_qspi_pair(config: SPIConfig) -> QSPITestPair {
+    let mut uut = QSPITestPair::new(config);
+    uut.master.continued_transaction.connect();
+    uut.master.start_send.connect();
+    uut.master.data_outbound.connect();
+    uut.master.bits_outbound.connect();
+    uut.master.width.connect();
+    uut.master.receive.connect();
+    uut.master.dummy_cycles.connect();
+    uut.slave.data_outbound.connect();
+    uut.slave.start_send.connect();
+    uut.slave.continued_transaction.connect();
+    uut.slave.bits.connect();
+    uut.slave.width.connect();
+    uut.slave.transmit.connect();
+    uut.slave.dummy_cycles.connect();
+    uut.connect_all();
+    uut
+}
+
+// One transfer of a transaction.  In `Single` transfers, the master and
+// slave exchange data.  Otherwise, the data flows from the slave to the
+// master if `receive` is set, and from the master to the slave if not.
+#[derive(Copy, Clone, Debug)]
+struct Phase {
+    width: QSPIWidth,
+    receive: bool,
+    dummy: u8,
+    bits: u16,
+    master: u64,
+    slave: u64,
+}
+
+#[cfg(test)]
+fn phase(width: QSPIWidth, receive: bool, dummy: u8, bits: u16, master: u64, slave: u64) -> Phase {
+    Phase {
+        width,
+        receive,
+        dummy,
+        bits,
+        master,
+        slave,
+    }
+}
+
+#[cfg(test)]
+fn test_qspi_transactions(config: SPIConfig, transactions: Vec<Vec<Phase>>, name: &str) {
+    let uut = mk_qspi_pair(config);
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<QSPITestPair>| x.clock.next = !x.clock.val());
+    let master_transactions = transactions.clone();
+    sim.add_testbench(move |mut sim: Sim<QSPITestPair>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 16);
+        for transaction in &master_transactions {
+            for (ndx, phase) in transaction.iter().enumerate() {
+                wait_clock_true!(sim, clock, x);
+                x.master.data_outbound.next = phase.master.to_bits();
+                x.master.bits_outbound.next = phase.bits.to_bits();
+                x.master.width.next = phase.width;
+                x.master.receive.next = phase.receive;
+                x.master.dummy_cycles.next = phase.dummy.to_bits();
+                x.master.continued_transaction.next = ndx + 1 < transaction.len();
+                x.master.start_send.next = true;
+                wait_clock_cycle!(sim, clock, x);
+                x.master.start_send.next = false;
+                x = sim.watch(|x| x.master.transfer_done.val(), x)?;
+                if phase.receive | (phase.width == QSPIWidth::Single) {
+                    sim_assert_eq!(sim, x.master.data_inbound.val(), phase.slave, x);
+                }
+                wait_clock_cycle!(sim, clock, x);
+            }
+        }
+        sim.done(x)
+    });
+    sim.add_testbench(move |mut sim: Sim<QSPITestPair>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 16);
+        for transaction in &transactions {
+            for (ndx, phase) in transaction.iter().enumerate() {
+                wait_clock_true!(sim, clock, x);
+                x.slave.data_outbound.next = phase.slave.to_bits();
+                x.slave.bits.next = phase.bits.to_bits();
+                x.slave.width.next = phase.width;
+                x.slave.transmit.next = phase.receive;
+                x.slave.dummy_cycles.next = phase.dummy.to_bits();
+                x.slave.continued_transaction.next = ndx + 1 < transaction.len();
+                x.slave.start_send.next = true;
+                wait_clock_cycle!(sim, clock, x);
+                x.slave.start_send.next = false;
+                x = sim.watch(|x| x.slave.transfer_done.val(), x)?;
+                if !phase.receive | (phase.width == QSPIWidth::Single) {
+                    sim_assert_eq!(sim, x.slave.data_inbound.val(), phase.master, x);
+                }
+            }
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        10_000_000,
+        &vcd_path!(format!("qspi_{}.vcd", name)),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_qspi_pair_synthesizes() {
+    let uut = mk_qspi_pair(mk_qspi_config(true, true));
+    yosys_validate("qspi_pair", &generate_verilog(&uut)).unwrap();
+}
+
+#[cfg(test)]
+fn single_transactions() -> Vec<Vec<Phase>> {
+    let xchange = phase(QSPIWidth::Single, false, 0, 32, 0xDEAD_BEEF, 0xCAFE_BABE);
+    vec![vec![xchange], vec![xchange]]
+}
+
+#[test]
+fn test_qspi_single_mode_00() {
+    test_qspi_transactions(
+        mk_qspi_config(false, false),
+        single_transactions(),
+        "single_00",
+    );
+}
+
+#[test]
+fn test_qspi_single_mode_01() {
+    test_qspi_transactions(
+        mk_qspi_config(false, true),
+        single_transactions(),
+        "single_01",
+    );
+}
+
+#[test]
+fn test_qspi_single_mode_10() {
+    test_qspi_transactions(
+        mk_qspi_config(true, false),
+        single_transactions(),
+        "single_10",
+    );
+}
+
+#[test]
+fn test_qspi_single_mode_11() {
+    test_qspi_transactions(
+        mk_qspi_config(true, true),
+        single_transactions(),
+        "single_11",
+    );
+}
+
+#[test]
+fn test_qspi_dual_write_and_read() {
+    test_qspi_transactions(
+        mk_qspi_config(true, true),
+        vec![
+            vec![
+                phase(QSPIWidth::Single, false, 0, 8, 0xA2, 0),
+                phase(QSPIWidth::Dual, false, 0, 16, 0x1234, 0),
+            ],
+            vec![
+                phase(QSPIWidth::Single, false, 0, 8, 0x3B, 0),
+                phase(QSPIWidth::Dual, true, 0, 32, 0, 0x8765_4321),
+            ],
+        ],
+        "dual",
+    );
+}
+
+#[test]
+fn test_qspi_quad_write_and_read() {
+    test_qspi_transactions(
+        mk_qspi_config(true, true),
+        vec![
+            vec![phase(QSPIWidth::Quad, false, 0, 32, 0x0123_4567, 0)],
+            vec![phase(
+                QSPIWidth::Quad,
+                true,
+                0,
+                64,
+                0,
+                0xFEDC_BA98_7654_3210,
+            )],
+        ],
+        "quad",
+    );
+}
+
+// A quad I/O fast read, as used by SPI NOR flash: the command goes out on
+// a single lane, the address on four lanes, and then the data comes back
+// on four lanes after some dummy cycles to turn the lanes around.
+#[cfg(test)]
+fn fast_read_transactions() -> Vec<Vec<Phase>> {
+    let fast_read = vec![
+        phase(QSPIWidth::Single, false, 0, 8, 0xEB, 0),
+        phase(QSPIWidth::Quad, false, 0, 32, 0x01_23_45_A0, 0),
+        phase(QSPIWidth::Quad, true, 4, 32, 0, 0x5EAD_F00D),
+        phase(QSPIWidth::Quad, true, 0, 16, 0, 0xC0DE),
+    ];
+    vec![
+        fast_read.clone(),
+        vec![phase(QSPIWidth::Single, false, 0, 16, 0x0500, 0x0003)],
+        fast_read,
+    ]
+}
+
+#[test]
+fn test_qspi_quad_fast_read_mode_00() {
+    test_qspi_transactions(
+        mk_qspi_config(false, false),
+        fast_read_transactions(),
+        "fast_read_00",
+    );
+}
+
+#[test]
+fn test_qspi_quad_fast_read_mode_11() {
+    test_qspi_transactions(
+        mk_qspi_config(true, true),
+        fast_read_transactions(),
+        "fast_read_11",
+    );
+}
@@ -0,0 +1,134 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock)]
+struct ResetCounter {
+    pub clock: Signal<In, Clock>,
+    pub reset: Signal<In, Reset>,
+    pub run: Signal<In, Bit>,
+    pub count: Signal<Out, Bits<8>>,
+    pub shadow: Signal<Out, Bits<8>>,
+    counter: ResetDFF<Bits<8>>,
+    latch: ResetDFF<Bits<8>>,
+}
+
+impl ResetCounter {
+    fn new(kind: ResetKind) -> Self {
+        Self {
+            clock: Default::default(),
+            reset: Default::default(),
+            run: Default::default(),
+            count: Default::default(),
+            shadow: Default::default(),
+            counter: ResetDFF::new(kind, 0x10.into()),
+            latch: ResetDFF::new(kind, 0xFF.into()),
+        }
+    }
+}
+
+impl Logic for ResetCounter {
+    #[hdl_gen]
+    fn update(&mut self) {
+        dff_setup!(self, clock, reset = reset, counter, latch);
+        self.counter.d.next = self.counter.q.val() + 1;
+        self.counter.enable.next = self.run.val();
+        self.latch.d.next = self.counter.q.val();
+        self.count.next = self.counter.q.val();
+        self.shadow.next = self.latch.q.val();
+    }
+}
+
+fn make_counter(kind: ResetKind) -> ResetCounter {
+    let mut uut = ResetCounter::new(kind);
+    uut.connect_all();
+    uut
+}
+
+#[test]
+fn test_reset_counter_synthesizes() {
+    for kind in [
+        ResetKind::Synchronous,
+        ResetKind::AsyncActiveHigh,
+        ResetKind::AsyncActiveLow,
+    ] {
+        let uut = make_counter(kind);
+        yosys_validate("reset_counter", &generate_verilog(&uut)).unwrap();
+    }
+}
+
+#[test]
+fn test_reset_counter_verilog() {
+    let vlog = generate_verilog(&make_counter(ResetKind::AsyncActiveHigh));
+    assert!(vlog.contains("always @(posedge clock or posedge reset)"));
+    let vlog = generate_verilog(&make_counter(ResetKind::AsyncActiveLow));
+    assert!(vlog.contains("always @(posedge clock or negedge reset)"));
+    let vlog = generate_verilog(&make_counter(ResetKind::Synchronous));
+    assert!(vlog.contains("always @(posedge clock)"));
+}
+
+fn reset_counter_test(kind: ResetKind) {
+    let active = kind != ResetKind::AsyncActiveLow;
+    let uut = make_counter(kind);
+    let mut sim = Simulation::new();
+    sim.add_clock(5, |x: &mut Box<ResetCounter>| x.clock.next = !x.clock.val());
+    sim.add_testbench(move |mut sim: Sim<ResetCounter>| {
+        let mut x = sim.init()?;
+        // Power on values
+        sim_assert_eq!(sim, x.count.val(), 0x10, x);
+        sim_assert_eq!(sim, x.shadow.val(), 0xFF, x);
+        x.reset.next = (!active).into();
+        wait_clock_true!(sim, clock, x);
+        // The clock enable gates the counter, but not the latch
+        wait_clock_cycles!(sim, clock, x, 4);
+        sim_assert_eq!(sim, x.count.val(), 0x10, x);
+        sim_assert_eq!(sim, x.shadow.val(), 0x10, x);
+        x.run.next = true;
+        wait_clock_cycles!(sim, clock, x, 5);
+        sim_assert_eq!(sim, x.count.val(), 0x15, x);
+        // Assert reset between clock edges
+        x = sim.wait(2, x)?;
+        x.reset.next = active.into();
+        x = sim.wait(1, x)?;
+        if kind == ResetKind::Synchronous {
+            // Nothing happens until the next clock edge
+            sim_assert_eq!(sim, x.count.val(), 0x15, x);
+        } else {
+            sim_assert_eq!(sim, x.count.val(), 0x10, x);
+            sim_assert_eq!(sim, x.shadow.val(), 0xFF, x);
+        }
+        wait_clock_cycles!(sim, clock, x, 3);
+        sim_assert_eq!(sim, x.count.val(), 0x10, x);
+        sim_assert_eq!(sim, x.shadow.val(), 0xFF, x);
+        x.reset.next = (!active).into();
+        wait_clock_cycles!(sim, clock, x, 3);
+        sim_assert_eq!(sim, x.count.val(), 0x13, x);
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        10_000,
+        &vcd_path!(format!("reset_counter_{:?}.vcd", kind)),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_reset_counter_sync() {
+    reset_counter_test(ResetKind::Synchronous);
+}
+
+#[test]
+fn test_reset_counter_async_high() {
+    reset_counter_test(ResetKind::AsyncActiveHigh);
+}
+
+#[test]
+fn test_reset_counter_async_low() {
+    reset_counter_test(ResetKind::AsyncActiveLow);
+}
+
+
+#[test]
+fn test_reset_counter_timing_check() {
+    // The async reset shows up as a reset edge of the registers
+    check_timing(&make_counter(ResetKind::AsyncActiveHigh));
+}
@@ -0,0 +1,115 @@
+use rust_hdl::prelude::*;
+
+#[derive(LogicBlock, Default)]
+struct PWMTest {
+    pub clock: Signal<In, Clock>,
+    pub threshold: Signal<In, Bits<8>>,
+    pub pwm: PulseWidthModulator<8>,
+}
+
+impl Logic for PWMTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, pwm);
+        self.pwm.enable.next = true;
+        self.pwm.threshold.next = self.threshold.val();
+    }
+}
+
+fn build() -> PWMTest {
+    let mut uut = PWMTest::default();
+    uut.threshold.connect();
+    uut
+}
+
+// Count the active cycles of the PWM over one period
+fn duty_cycle_scenario(threshold: u64, expected: u64) -> Simulation<PWMTest> {
+    simple_sim!(PWMTest, clock, 100_000_000, sim, {
+        let mut x = sim.init()?;
+        x.threshold.next = threshold.into();
+        wait_clock_cycles!(sim, clock, x, 256);
+        let mut accum = 0;
+        for _ in 0..256 {
+            wait_clock_cycle!(sim, clock, x);
+            if x.pwm.active.val() {
+                accum += 1;
+            }
+        }
+        sim_assert_eq!(sim, accum, expected, x);
+        sim.done(x)
+    })
+}
+
+#[test]
+fn test_runner_runs_scenarios_in_parallel() {
+    let mut runner = SimRunner::new(build).threads(4);
+    for threshold in [0, 1, 32, 128, 200, 255] {
+        runner = runner.scenario(&format!("pwm_{}", threshold), 100_000_000, move || {
+            duty_cycle_scenario(threshold, threshold)
+        });
+    }
+    let summary = runner.run();
+    println!("{}", summary);
+    summary.assert_passed();
+    assert_eq!(summary.results.len(), 6);
+    assert_eq!(summary.threads, 4);
+    // The results are reported in the order the scenarios were added
+    assert_eq!(summary.results[2].name, "pwm_32");
+    assert!(summary.results.iter().all(|x| x.sim_time > 0));
+}
+
+#[test]
+fn test_runner_reports_failures_and_writes_vcds() {
+    let dir = vcd_path!("runner");
+    let summary = SimRunner::new(build)
+        .vcd_dir(&dir)
+        .scenario("good", 100_000_000, || duty_cycle_scenario(64, 64))
+        .scenario("wrong_count", 100_000_000, || duty_cycle_scenario(64, 65))
+        .scenario("timeout", 1_000, || duty_cycle_scenario(64, 64))
+        .scenario("panics", 100_000_000, || panic!("Setup failed"))
+        .run();
+    assert!(!summary.all_passed());
+    assert_eq!(summary.passed().count(), 1);
+    assert_eq!(summary.get("good").unwrap().result, Ok(()));
+    assert!(matches!(
+        summary.get("wrong_count").unwrap().result,
+        Err(SimError::SimHalted(_))
+    ));
+    assert_eq!(
+        summary.get("timeout").unwrap().result,
+        Err(SimError::MaxTimeReached)
+    );
+    assert_eq!(
+        summary.get("panics").unwrap().result,
+        Err(SimError::SimPanic("Setup failed".into()))
+    );
+    let text = summary.to_string();
+    assert!(text.contains("1 passed, 3 failed"));
+    assert!(text.contains("FAIL wrong_count"));
+    for name in ["good", "wrong_count"] {
+        let vcd = summary.get(name).unwrap().vcd.clone().unwrap();
+        assert!(std::fs::metadata(vcd).unwrap().len() > 0);
+    }
+}
+
+#[test]
+fn test_runner_starts_scenarios_from_checkpoint() {
+    let mut sim = simple_sim!(PWMTest, clock, 100_000_000, sim, {
+        let mut x = sim.init()?;
+        x.threshold.next = 16.into();
+        sim.done(x)
+    });
+    let checkpoint = sim.run_to_checkpoint(Box::new(build()), 1_000_000).unwrap();
+    let summary = SimRunner::new(build)
+        .start_from(&checkpoint)
+        .scenario("resumed", 100_000_000, || {
+            simple_sim!(PWMTest, clock, 100_000_000, sim, {
+                let x = sim.init()?;
+                sim_assert_eq!(sim, sim.time(), 1_000_000, x);
+                sim_assert_eq!(sim, x.threshold.val(), 16, x);
+                sim.done(x)
+            })
+        })
+        .run();
+    summary.assert_passed();
+}
@@ -0,0 +1,267 @@
+use rust_hdl::prelude::*;
+use std::time::Duration;
+
+const CLOCK_SPEED_HZ: u64 = 1_000_000;
+
+fn i2c_config() -> I2CConfig {
+    I2CConfig {
+        delay_time: Duration::from_micros(5),
+        clock_speed_hz: CLOCK_SPEED_HZ,
+        timeout: Duration::from_millis(25),
+    }
+}
+
+// Reads the JEDEC ID of the flash, programs two bytes and polls the status
+// register until the program is done, then reads them back.  Writes two
+// bytes to the EEPROM, polls for the end of the write cycle, and reads them
+// back with a repeated start.  Finally, pulses an output line 3 times, and
+// waits for an input before ending.
+fn bring_up_program() -> SequencerProgram {
+    let mut program = SequencerProgram::default();
+    // JEDEC ID into registers 0 and 1
+    program
+        .spi_write_continued(8, 0x9F)
+        .spi_read_continued(16, 0, 0)
+        .spi_read(8, 0, 1);
+    // Write enable, and program 0xBEEF at address 0x100
+    program
+        .spi_write(8, 0x06)
+        .spi_write_continued(8, 0x02)
+        .spi_write_continued(8, 0x00)
+        .spi_write_continued(16, 0x0100)
+        .spi_write(16, 0xBEEF);
+    let flash_busy = program.here();
+    program
+        .spi_write_continued(8, 0x05)
+        .spi_read(8, 0, 2)
+        .branch_if_set(2, 0, flash_busy);
+    program
+        .spi_write_continued(8, 0x03)
+        .spi_write_continued(8, 0x00)
+        .spi_write_continued(16, 0x0100)
+        .spi_read(16, 0, 3);
+    // Write 0x12, 0x34 at EEPROM address 0x0010
+    program
+        .i2c_begin_write(0x50)
+        .i2c_write(0x00)
+        .i2c_write(0x10)
+        .i2c_write(0x12)
+        .i2c_write(0x34)
+        .i2c_end();
+    let eeprom_busy = program.here();
+    program
+        .i2c_probe(0x50, 4)
+        .i2c_end()
+        .branch_if_set(4, 0, eeprom_busy);
+    program
+        .i2c_begin_write(0x50)
+        .i2c_write(0x00)
+        .i2c_write(0x10)
+        .i2c_begin_read(0x50)
+        .i2c_read(5)
+        .i2c_read_last(6)
+        .i2c_end();
+    // Pulse output 0 three times
+    program.set_loop(3);
+    let pulse = program.here();
+    program
+        .output(1)
+        .delay(10)
+        .output(0)
+        .delay(10)
+        .loop_to(pulse);
+    program.wait_input(0, true).output(0x80).end();
+    program
+}
+
+#[derive(LogicBlock)]
+struct SequencerTest {
+    clock: Signal<In, Clock>,
+    sequencer: CommandSequencer<6>,
+    program: ROM<Bits<32>, 6>,
+    lanes: QSPISingleLane,
+    flash: W25QSimulator<13>,
+    eeprom: EEPROM24CxxSimulator<12>,
+    test_bus: I2CTestBus<2>,
+}
+
+impl Default for SequencerTest {
+    fn default() -> Self {
+        let flash_config = W25QConfig::sw();
+        let mut eeprom_config = EEPROM24CxxConfig::c32(CLOCK_SPEED_HZ);
+        eeprom_config.write_cycle_time = Duration::from_millis(1);
+        Self {
+            clock: Default::default(),
+            sequencer: CommandSequencer::new(flash_config.spi, i2c_config()),
+            program: ROM::new(bring_up_program().rom().unwrap()),
+            lanes: Default::default(),
+            flash: W25QSimulator::new(flash_config),
+            eeprom: EEPROM24CxxSimulator::new(eeprom_config),
+            test_bus: Default::default(),
+        }
+    }
+}
+
+impl Logic for SequencerTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, sequencer, flash, eeprom);
+        self.program.address.next = self.sequencer.program_address.val();
+        self.sequencer.program_data.next = self.program.data.val();
+        SPIWiresMaster::join(&mut self.sequencer.spi, &mut self.lanes.spi);
+        QSPIWiresMaster::join(&mut self.lanes.wires, &mut self.flash.wires);
+        I2CBusDriver::join(&mut self.sequencer.i2c, &mut self.test_bus.endpoints[0]);
+        I2CBusDriver::join(&mut self.eeprom.i2c, &mut self.test_bus.endpoints[1]);
+    }
+}
+
+#[cfg(test)]
+fn make_test() -> SequencerTest {
+    let mut uut = SequencerTest::default();
+    uut.sequencer.start.connect();
+    uut.sequencer.inputs.connect();
+    uut.sequencer.reg_address.connect();
+    uut.connect_all();
+    uut
+}
+
+// Read one of the sequencer registers
+macro_rules! read_reg {
+    ($sim: ident, $uut: ident, $reg: expr) => {{
+        wait_clock_true!($sim, clock, $uut);
+        $uut.sequencer.reg_address.next = ($reg as u32).to_bits();
+        wait_clock_cycles!($sim, clock, $uut, 2);
+        $uut.sequencer.reg_data.val()
+    }};
+}
+
+#[test]
+fn test_command_sequencer_synthesizes() {
+    let uut = make_test();
+    yosys_validate("command_sequencer_test", &generate_verilog(&uut)).unwrap();
+}
+
+#[test]
+fn test_command_sequencer_bring_up() {
+    let uut = make_test();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<SequencerTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<SequencerTest>| {
+        let mut x = sim.init()?;
+        wait_clock_cycles!(sim, clock, x, 10);
+        x.sequencer.start.next = true;
+        wait_clock_cycle!(sim, clock, x);
+        x.sequencer.start.next = false;
+        // The output pulses while the sequencer loops
+        for _ in 0..3 {
+            x = sim.watch(|x| x.sequencer.outputs.val() == 1, x)?;
+            x = sim.watch(|x| x.sequencer.outputs.val() == 0, x)?;
+        }
+        // Then it waits for the input
+        wait_clock_cycles!(sim, clock, x, 100);
+        sim_assert!(sim, x.sequencer.busy.val(), x);
+        sim_assert_eq!(sim, x.sequencer.outputs.val(), 0, x);
+        x.sequencer.inputs.next = 1.into();
+        x = sim.watch(|x| x.sequencer.done.val(), x)?;
+        wait_clock_cycle!(sim, clock, x);
+        sim_assert!(sim, !x.sequencer.busy.val(), x);
+        sim_assert!(sim, !x.sequencer.error.val(), x);
+        sim_assert_eq!(sim, x.sequencer.outputs.val(), 0x80, x);
+        let expected = [0xEF40, 0x0D, 0, 0xBEEF, 0, 0x12, 0x34];
+        for (reg, val) in expected.into_iter().enumerate() {
+            let data = read_reg!(sim, x, reg);
+            sim_assert_eq!(sim, data, val, x);
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        200_000_000_000,
+        &vcd_path!("command_sequencer_bring_up.vcd"),
+    )
+    .unwrap();
+}
+
+#[derive(LogicBlock)]
+struct SequencerSyncROMTest {
+    clock: Signal<In, Clock>,
+    sequencer: CommandSequencer<4>,
+    program: SyncROM<Bits<32>, 4>,
+    lanes: QSPISingleLane,
+    flash: W25QSimulator<13>,
+    test_bus: I2CTestBus<1>,
+}
+
+impl Default for SequencerSyncROMTest {
+    fn default() -> Self {
+        let flash_config = W25QConfig::sw();
+        // Read the JEDEC ID, and then address an I2C target that is not there
+        let mut program = SequencerProgram::default();
+        program
+            .spi_write_continued(8, 0x9F)
+            .spi_read_continued(16, 0, 0)
+            .spi_read(8, 0, 1)
+            .i2c_begin_write(0x50)
+            .i2c_end()
+            .end();
+        Self {
+            clock: Default::default(),
+            sequencer: CommandSequencer::new(flash_config.spi, i2c_config()),
+            program: SyncROM::new(program.rom().unwrap()),
+            lanes: Default::default(),
+            flash: W25QSimulator::new(flash_config),
+            test_bus: Default::default(),
+        }
+    }
+}
+
+impl Logic for SequencerSyncROMTest {
+    #[hdl_gen]
+    fn update(&mut self) {
+        clock!(self, clock, sequencer, program, flash);
+        self.program.address.next = self.sequencer.program_address.val();
+        self.sequencer.program_data.next = self.program.data.val();
+        SPIWiresMaster::join(&mut self.sequencer.spi, &mut self.lanes.spi);
+        QSPIWiresMaster::join(&mut self.lanes.wires, &mut self.flash.wires);
+        I2CBusDriver::join(&mut self.sequencer.i2c, &mut self.test_bus.endpoints[0]);
+    }
+}
+
+#[test]
+fn test_command_sequencer_sync_rom_error() {
+    let mut uut = SequencerSyncROMTest::default();
+    uut.sequencer.start.connect();
+    uut.sequencer.inputs.connect();
+    uut.sequencer.reg_address.connect();
+    uut.connect_all();
+    let mut sim = Simulation::new();
+    sim.add_clock(500_000, |x: &mut Box<SequencerSyncROMTest>| {
+        x.clock.next = !x.clock.val()
+    });
+    sim.add_testbench(move |mut sim: Sim<SequencerSyncROMTest>| {
+        let mut x = sim.init()?;
+        // Run the program twice, as the sequencer restarts after an error
+        for _ in 0..2 {
+            wait_clock_cycles!(sim, clock, x, 10);
+            x.sequencer.start.next = true;
+            wait_clock_cycle!(sim, clock, x);
+            x.sequencer.start.next = false;
+            x = sim.watch(|x| x.sequencer.error.val() | x.sequencer.done.val(), x)?;
+            sim_assert!(sim, x.sequencer.error.val(), x);
+            sim_assert!(sim, !x.sequencer.busy.val(), x);
+            let id = read_reg!(sim, x, 0);
+            sim_assert_eq!(sim, id, 0xEF40, x);
+            let id = read_reg!(sim, x, 1);
+            sim_assert_eq!(sim, id, 0x0D, x);
+        }
+        sim.done(x)
+    });
+    sim.run_to_file(
+        Box::new(uut),
+        100_000_000_000,
+        &vcd_path!("command_sequencer_sync_rom.vcd"),
+    )
+    .unwrap();
+}